@@ -1,4 +1,8 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Only the server speaks gRPC
+    if std::env::var_os("CARGO_FEATURE_SERVER").is_none() {
+        return Ok(());
+    }
     tonic_build::compile_protos("src/coretex_grpc/coretex.proto")?;
     Ok(())
 }
@@ -0,0 +1,17 @@
+/// Generates the C header into `OUT_DIR`; `tests/c_api.rs` checks that the
+/// copy shipped in `include/` matches it.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR")?;
+    let out_dir = std::env::var("OUT_DIR")?;
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))?;
+    cbindgen::Builder::new()
+        .with_src(format!("{}/src/lib.rs", crate_dir))
+        .with_config(config)
+        .generate()?
+        .write_to_file(format!("{}/coretexdb.h", out_dir));
+
+    Ok(())
+}
@@ -0,0 +1,155 @@
+/* CoreTexDB embedded EdgeDB C API. Generated by cbindgen; do not edit. */
+
+#ifndef CORETEXDB_H
+#define CORETEXDB_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum CoretexStatus {
+  CORETEX_STATUS_OK = 0,
+  CORETEX_STATUS_NULL_ARGUMENT = 1,
+  CORETEX_STATUS_INVALID_ARGUMENT = 2,
+  CORETEX_STATUS_COLLECTION_NOT_FOUND = 3,
+  CORETEX_STATUS_COLLECTION_EXISTS = 4,
+  CORETEX_STATUS_INVALID_DIMENSION = 5,
+  CORETEX_STATUS_IO_ERROR = 6,
+  CORETEX_STATUS_OUT_OF_MEMORY = 7,
+  CORETEX_STATUS_DISK_QUOTA_EXCEEDED = 8,
+  CORETEX_STATUS_CORRUPTED = 9,
+  CORETEX_STATUS_ENCRYPTION_ERROR = 10,
+  CORETEX_STATUS_SYNC_ERROR = 11,
+  // A bug inside the library; the handle should not be used further.
+  CORETEX_STATUS_PANIC = 12,
+} CoretexStatus;
+
+// Opaque database handle.
+typedef struct CoretexEdgeDb CoretexEdgeDb;
+
+// Opaque search results, nearest first.
+typedef struct CoretexSearchResults CoretexSearchResults;
+
+// Options for `coretex_edge_open`; start from `coretex_edge_config_default`.
+typedef struct CoretexEdgeConfig {
+  uintptr_t max_memory_mb;
+  uintptr_t max_disk_gb;
+  bool enable_compression;
+  // 32-byte AES-256 key, or NULL for no encryption.
+  const uint8_t *encryption_key;
+  uintptr_t encryption_key_len;
+} CoretexEdgeConfig;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Message for the last failed call on this thread, or NULL. Valid until the
+// next failing call on the same thread.
+const char *coretex_last_error(void);
+
+// Library version as a static string.
+const char *coretex_version(void);
+
+// Fills `config` with the defaults of `EdgeConfig`.
+//
+// # Safety
+// `config` must point to writable memory for a `CoretexEdgeConfig`.
+void coretex_edge_config_default(struct CoretexEdgeConfig *config);
+
+// Opens a database persisted in `data_dir`, recovering its previous state,
+// or an in-memory database when `data_dir` is NULL. `config` may be NULL.
+//
+// # Safety
+// Pointer arguments must be NULL or valid; `out` receives the handle.
+enum CoretexStatus coretex_edge_open(const char *data_dir,
+                                     const struct CoretexEdgeConfig *config,
+                                     struct CoretexEdgeDb **out);
+
+// Checkpoints and frees the database. `db` may be NULL.
+//
+// # Safety
+// `db` must come from `coretex_edge_open` and not be used afterwards.
+enum CoretexStatus coretex_edge_close(struct CoretexEdgeDb *db);
+
+// Forces logged changes to disk.
+//
+// # Safety
+// `db` must be a live handle.
+enum CoretexStatus coretex_edge_flush(const struct CoretexEdgeDb *db);
+
+// # Safety
+// `db` must be a live handle and `name` a valid string.
+enum CoretexStatus coretex_edge_create_collection(const struct CoretexEdgeDb *db,
+                                                  const char *name,
+                                                  uintptr_t dimension);
+
+// # Safety
+// `db` must be a live handle and `name` a valid string.
+enum CoretexStatus coretex_edge_delete_collection(const struct CoretexEdgeDb *db, const char *name);
+
+// Inserts or replaces `id`. `metadata_json` is a JSON document or NULL.
+//
+// # Safety
+// `vector` must point to `dimension` floats; strings must be valid.
+enum CoretexStatus coretex_edge_insert(const struct CoretexEdgeDb *db,
+                                       const char *collection,
+                                       const char *id,
+                                       const float *vector,
+                                       uintptr_t dimension,
+                                       const char *metadata_json);
+
+// Deletes `id`; `deleted` (may be NULL) reports whether it existed.
+//
+// # Safety
+// `db` must be a live handle; strings must be valid.
+enum CoretexStatus coretex_edge_delete(const struct CoretexEdgeDb *db,
+                                       const char *collection,
+                                       const char *id,
+                                       bool *deleted);
+
+// Finds the `k` nearest entries by cosine distance. Free `out` with
+// `coretex_results_free`.
+//
+// # Safety
+// `query` must point to `dimension` floats; `out` receives the results.
+enum CoretexStatus coretex_edge_search(const struct CoretexEdgeDb *db,
+                                       const char *collection,
+                                       const float *query,
+                                       uintptr_t dimension,
+                                       uintptr_t k,
+                                       struct CoretexSearchResults **out);
+
+// Number of hits; 0 for NULL.
+//
+// # Safety
+// `results` must be NULL or a live results handle.
+uintptr_t coretex_results_len(const struct CoretexSearchResults *results);
+
+// Id of hit `index`, or NULL when out of range.
+//
+// # Safety
+// `results` must be NULL or a live results handle.
+const char *coretex_results_id(const struct CoretexSearchResults *results, uintptr_t index);
+
+// Distance of hit `index`, or NaN when out of range.
+//
+// # Safety
+// `results` must be NULL or a live results handle.
+float coretex_results_distance(const struct CoretexSearchResults *results, uintptr_t index);
+
+// Metadata of hit `index` as JSON, or NULL when it has none.
+//
+// # Safety
+// `results` must be NULL or a live results handle.
+const char *coretex_results_metadata(const struct CoretexSearchResults *results, uintptr_t index);
+
+// # Safety
+// `results` must be NULL or come from `coretex_edge_search`.
+void coretex_results_free(struct CoretexSearchResults *results);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CORETEXDB_H */
@@ -0,0 +1,446 @@
+//! C API for the embedded EdgeDB
+//! Built as `libcoretexdb_c.a` / `libcoretexdb_c.so`; the matching header
+//! is `include/coretexdb.h`, regenerated by cbindgen into `OUT_DIR` on
+//! every build.
+//!
+//! Conventions:
+//! - Every function returns a `CoretexStatus`; on failure
+//!   `coretex_last_error()` describes the error on the calling thread.
+//! - Handles are opaque and must be released with their `_free`/`_close`
+//!   function. A database handle may be shared between threads.
+//! - Strings are NUL-terminated UTF-8. Strings returned by the library stay
+//!   owned by it and are valid until the owning handle is freed.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use coretexdb::coretex_edge::{EdgeConfig, EdgeDB, EdgeError};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoretexStatus {
+    Ok = 0,
+    NullArgument = 1,
+    InvalidArgument = 2,
+    CollectionNotFound = 3,
+    CollectionExists = 4,
+    InvalidDimension = 5,
+    IoError = 6,
+    OutOfMemory = 7,
+    DiskQuotaExceeded = 8,
+    Corrupted = 9,
+    EncryptionError = 10,
+    SyncError = 11,
+    /// A bug inside the library; the handle should not be used further.
+    Panic = 12,
+}
+
+/// Options for `coretex_edge_open`; start from `coretex_edge_config_default`.
+#[repr(C)]
+pub struct CoretexEdgeConfig {
+    pub max_memory_mb: usize,
+    pub max_disk_gb: usize,
+    pub enable_compression: bool,
+    /// 32-byte AES-256 key, or NULL for no encryption.
+    pub encryption_key: *const u8,
+    pub encryption_key_len: usize,
+}
+
+/// Opaque database handle.
+pub struct CoretexEdgeDb {
+    db: EdgeDB,
+}
+
+/// Opaque search results, nearest first.
+pub struct CoretexSearchResults {
+    hits: Vec<(CString, f32, Option<CString>)>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn status_of(e: &EdgeError) -> CoretexStatus {
+    match e {
+        EdgeError::CollectionNotFound(_) => CoretexStatus::CollectionNotFound,
+        EdgeError::CollectionExists(_) => CoretexStatus::CollectionExists,
+        EdgeError::InvalidDimension(_) => CoretexStatus::InvalidDimension,
+        EdgeError::IoError(_) => CoretexStatus::IoError,
+        EdgeError::OutOfMemory => CoretexStatus::OutOfMemory,
+        EdgeError::DiskQuotaExceeded => CoretexStatus::DiskQuotaExceeded,
+        EdgeError::Corrupted(_) => CoretexStatus::Corrupted,
+        EdgeError::EncryptionError(_) => CoretexStatus::EncryptionError,
+        EdgeError::SyncError(_) => CoretexStatus::SyncError,
+    }
+}
+
+/// Runs `body`, turning errors and panics into a status code.
+fn ffi_call(body: impl FnOnce() -> Result<(), (CoretexStatus, String)>) -> CoretexStatus {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => CoretexStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(_) => {
+            set_last_error("panic inside coretexdb".to_string());
+            CoretexStatus::Panic
+        }
+    }
+}
+
+fn edge_error(e: EdgeError) -> (CoretexStatus, String) {
+    (status_of(&e), e.to_string())
+}
+
+unsafe fn handle<'a>(db: *const CoretexEdgeDb) -> Result<&'a EdgeDB, (CoretexStatus, String)> {
+    db.as_ref()
+        .map(|handle| &handle.db)
+        .ok_or((CoretexStatus::NullArgument, "database handle is NULL".to_string()))
+}
+
+unsafe fn string_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, (CoretexStatus, String)> {
+    if value.is_null() {
+        return Err((CoretexStatus::NullArgument, format!("{} is NULL", name)));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| (CoretexStatus::InvalidArgument, format!("{} is not valid UTF-8", name)))
+}
+
+unsafe fn vector_arg(values: *const f32, len: usize) -> Result<Vec<f32>, (CoretexStatus, String)> {
+    if values.is_null() {
+        return Err((CoretexStatus::NullArgument, "vector is NULL".to_string()));
+    }
+    Ok(std::slice::from_raw_parts(values, len).to_vec())
+}
+
+/// Message for the last failed call on this thread, or NULL. Valid until the
+/// next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn coretex_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Library version as a static string.
+#[no_mangle]
+pub extern "C" fn coretex_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Fills `config` with the defaults of `EdgeConfig`.
+///
+/// # Safety
+/// `config` must point to writable memory for a `CoretexEdgeConfig`.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_config_default(config: *mut CoretexEdgeConfig) {
+    if let Some(config) = config.as_mut() {
+        let defaults = EdgeConfig::default();
+        *config = CoretexEdgeConfig {
+            max_memory_mb: defaults.max_memory_mb,
+            max_disk_gb: defaults.max_disk_gb,
+            enable_compression: defaults.enable_compression,
+            encryption_key: ptr::null(),
+            encryption_key_len: 0,
+        };
+    }
+}
+
+/// Opens a database persisted in `data_dir`, recovering its previous state,
+/// or an in-memory database when `data_dir` is NULL. `config` may be NULL.
+///
+/// # Safety
+/// Pointer arguments must be NULL or valid; `out` receives the handle.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_open(
+    data_dir: *const c_char,
+    config: *const CoretexEdgeConfig,
+    out: *mut *mut CoretexEdgeDb,
+) -> CoretexStatus {
+    ffi_call(|| {
+        if out.is_null() {
+            return Err((CoretexStatus::NullArgument, "out is NULL".to_string()));
+        }
+
+        let mut edge_config = EdgeConfig::default();
+        let mut key = None;
+        if let Some(config) = config.as_ref() {
+            edge_config = edge_config
+                .with_max_memory(config.max_memory_mb)
+                .with_max_disk(config.max_disk_gb)
+                .with_compression(config.enable_compression);
+            if !config.encryption_key.is_null() {
+                key = Some(std::slice::from_raw_parts(config.encryption_key, config.encryption_key_len));
+            }
+        }
+
+        let mut db = if data_dir.is_null() {
+            EdgeDB::in_memory_with_config(edge_config)
+        } else {
+            EdgeDB::with_config(edge_config).with_data_dir(string_arg(data_dir, "data_dir")?)
+        };
+        if let Some(key) = key {
+            db = db.with_encryption_key(key);
+        }
+
+        futures::executor::block_on(db.init()).map_err(edge_error)?;
+        *out = Box::into_raw(Box::new(CoretexEdgeDb { db }));
+        Ok(())
+    })
+}
+
+/// Checkpoints and frees the database. `db` may be NULL.
+///
+/// # Safety
+/// `db` must come from `coretex_edge_open` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_close(db: *mut CoretexEdgeDb) -> CoretexStatus {
+    if db.is_null() {
+        return CoretexStatus::Ok;
+    }
+    let handle = Box::from_raw(db);
+    ffi_call(|| futures::executor::block_on(handle.db.close()).map_err(edge_error))
+}
+
+/// Forces logged changes to disk.
+///
+/// # Safety
+/// `db` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_flush(db: *const CoretexEdgeDb) -> CoretexStatus {
+    ffi_call(|| futures::executor::block_on(handle(db)?.flush()).map_err(edge_error))
+}
+
+/// # Safety
+/// `db` must be a live handle and `name` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_create_collection(
+    db: *const CoretexEdgeDb,
+    name: *const c_char,
+    dimension: usize,
+) -> CoretexStatus {
+    ffi_call(|| {
+        let name = string_arg(name, "name")?;
+        futures::executor::block_on(handle(db)?.create_collection(name, dimension)).map_err(edge_error)
+    })
+}
+
+/// # Safety
+/// `db` must be a live handle and `name` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_delete_collection(db: *const CoretexEdgeDb, name: *const c_char) -> CoretexStatus {
+    ffi_call(|| {
+        let name = string_arg(name, "name")?;
+        futures::executor::block_on(handle(db)?.delete_collection(name)).map_err(edge_error)
+    })
+}
+
+/// Inserts or replaces `id`. `metadata_json` is a JSON document or NULL.
+///
+/// # Safety
+/// `vector` must point to `dimension` floats; strings must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_insert(
+    db: *const CoretexEdgeDb,
+    collection: *const c_char,
+    id: *const c_char,
+    vector: *const f32,
+    dimension: usize,
+    metadata_json: *const c_char,
+) -> CoretexStatus {
+    ffi_call(|| {
+        let collection = string_arg(collection, "collection")?;
+        let id = string_arg(id, "id")?;
+        let vector = vector_arg(vector, dimension)?;
+        let metadata = if metadata_json.is_null() {
+            None
+        } else {
+            let text = string_arg(metadata_json, "metadata_json")?;
+            Some(serde_json::from_str(text)
+                .map_err(|e| (CoretexStatus::InvalidArgument, format!("metadata_json: {}", e)))?)
+        };
+        futures::executor::block_on(handle(db)?.insert(collection, id, vector, metadata)).map_err(edge_error)
+    })
+}
+
+/// Deletes `id`; `deleted` (may be NULL) reports whether it existed.
+///
+/// # Safety
+/// `db` must be a live handle; strings must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_delete(
+    db: *const CoretexEdgeDb,
+    collection: *const c_char,
+    id: *const c_char,
+    deleted: *mut bool,
+) -> CoretexStatus {
+    ffi_call(|| {
+        let collection = string_arg(collection, "collection")?;
+        let id = string_arg(id, "id")?;
+        let removed = futures::executor::block_on(handle(db)?.delete(collection, id)).map_err(edge_error)?;
+        if let Some(deleted) = deleted.as_mut() {
+            *deleted = removed;
+        }
+        Ok(())
+    })
+}
+
+/// Finds the `k` nearest entries by cosine distance. Free `out` with
+/// `coretex_results_free`.
+///
+/// # Safety
+/// `query` must point to `dimension` floats; `out` receives the results.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_edge_search(
+    db: *const CoretexEdgeDb,
+    collection: *const c_char,
+    query: *const f32,
+    dimension: usize,
+    k: usize,
+    out: *mut *mut CoretexSearchResults,
+) -> CoretexStatus {
+    ffi_call(|| {
+        if out.is_null() {
+            return Err((CoretexStatus::NullArgument, "out is NULL".to_string()));
+        }
+        let db = handle(db)?;
+        let collection = string_arg(collection, "collection")?;
+        let query = vector_arg(query, dimension)?;
+
+        let hits = futures::executor::block_on(async {
+            let mut hits = Vec::new();
+            for result in db.search(collection, &query, k).await? {
+                let metadata = db.get(collection, &result.id).await?
+                    .map(|(_, metadata)| metadata)
+                    .filter(|metadata| metadata.as_object().is_none_or(|m| !m.is_empty()));
+                hits.push((
+                    CString::new(result.id).unwrap_or_default(),
+                    result.distance,
+                    metadata.and_then(|m| CString::new(m.to_string()).ok()),
+                ));
+            }
+            Ok(hits)
+        }).map_err(edge_error)?;
+
+        *out = Box::into_raw(Box::new(CoretexSearchResults { hits }));
+        Ok(())
+    })
+}
+
+/// Number of hits; 0 for NULL.
+///
+/// # Safety
+/// `results` must be NULL or a live results handle.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_results_len(results: *const CoretexSearchResults) -> usize {
+    results.as_ref().map_or(0, |results| results.hits.len())
+}
+
+/// Id of hit `index`, or NULL when out of range.
+///
+/// # Safety
+/// `results` must be NULL or a live results handle.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_results_id(results: *const CoretexSearchResults, index: usize) -> *const c_char {
+    results.as_ref()
+        .and_then(|results| results.hits.get(index))
+        .map_or(ptr::null(), |(id, _, _)| id.as_ptr())
+}
+
+/// Distance of hit `index`, or NaN when out of range.
+///
+/// # Safety
+/// `results` must be NULL or a live results handle.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_results_distance(results: *const CoretexSearchResults, index: usize) -> f32 {
+    results.as_ref()
+        .and_then(|results| results.hits.get(index))
+        .map_or(f32::NAN, |(_, distance, _)| *distance)
+}
+
+/// Metadata of hit `index` as JSON, or NULL when it has none.
+///
+/// # Safety
+/// `results` must be NULL or a live results handle.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_results_metadata(results: *const CoretexSearchResults, index: usize) -> *const c_char {
+    results.as_ref()
+        .and_then(|results| results.hits.get(index))
+        .and_then(|(_, _, metadata)| metadata.as_ref())
+        .map_or(ptr::null(), |metadata| metadata.as_ptr())
+}
+
+/// # Safety
+/// `results` must be NULL or come from `coretex_edge_search`.
+#[no_mangle]
+pub unsafe extern "C" fn coretex_results_free(results: *mut CoretexSearchResults) {
+    if !results.is_null() {
+        drop(Box::from_raw(results));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_api_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let collection = CString::new("objects").unwrap();
+        let cup = CString::new("cup").unwrap();
+        let metadata = CString::new(r#"{"label":"cup"}"#).unwrap();
+
+        unsafe {
+            let mut db = ptr::null_mut();
+            assert_eq!(coretex_edge_open(data_dir.as_ptr(), ptr::null(), &mut db), CoretexStatus::Ok);
+            assert_eq!(coretex_edge_create_collection(db, collection.as_ptr(), 2), CoretexStatus::Ok);
+            assert_eq!(coretex_edge_create_collection(db, collection.as_ptr(), 2), CoretexStatus::CollectionExists);
+            assert!(!coretex_last_error().is_null());
+
+            let vector = [1.0f32, 0.0];
+            assert_eq!(
+                coretex_edge_insert(db, collection.as_ptr(), cup.as_ptr(), vector.as_ptr(), 2, metadata.as_ptr()),
+                CoretexStatus::Ok
+            );
+            assert_eq!(
+                coretex_edge_insert(db, collection.as_ptr(), cup.as_ptr(), vector.as_ptr(), 1, ptr::null()),
+                CoretexStatus::InvalidDimension
+            );
+            assert_eq!(coretex_edge_close(db), CoretexStatus::Ok);
+
+            // Reopen and find it again
+            let mut db = ptr::null_mut();
+            assert_eq!(coretex_edge_open(data_dir.as_ptr(), ptr::null(), &mut db), CoretexStatus::Ok);
+            let mut results = ptr::null_mut();
+            let query = [0.9f32, 0.1];
+            assert_eq!(coretex_edge_search(db, collection.as_ptr(), query.as_ptr(), 2, 5, &mut results), CoretexStatus::Ok);
+            assert_eq!(coretex_results_len(results), 1);
+            assert_eq!(CStr::from_ptr(coretex_results_id(results, 0)).to_str().unwrap(), "cup");
+            assert_eq!(CStr::from_ptr(coretex_results_metadata(results, 0)).to_str().unwrap(), r#"{"label":"cup"}"#);
+            assert!(coretex_results_id(results, 1).is_null());
+            coretex_results_free(results);
+
+            let mut deleted = false;
+            assert_eq!(coretex_edge_delete(db, collection.as_ptr(), cup.as_ptr(), &mut deleted), CoretexStatus::Ok);
+            assert!(deleted);
+            assert_eq!(coretex_edge_close(db), CoretexStatus::Ok);
+        }
+    }
+
+    #[test]
+    fn test_c_api_null_arguments() {
+        unsafe {
+            assert_eq!(coretex_edge_flush(ptr::null()), CoretexStatus::NullArgument);
+            assert_eq!(coretex_edge_open(ptr::null(), ptr::null(), ptr::null_mut()), CoretexStatus::NullArgument);
+            assert_eq!(CStr::from_ptr(coretex_last_error()).to_str().unwrap(), "out is NULL");
+        }
+    }
+}
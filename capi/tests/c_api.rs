@@ -0,0 +1,70 @@
+//! Builds `tests/smoke.c` against the generated header and the shared
+//! library, the way a C application would, and runs it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const GENERATED_HEADER: &str = concat!(env!("OUT_DIR"), "/coretexdb.h");
+
+/// The directory cargo put `libcoretexdb_c.so` in: next to this test in
+/// `deps`, or the profile directory above it once uplifted by `cargo build`.
+/// The shared library is used as the static one needs the native libraries
+/// of every dependency (OpenSSL, ...) listed by hand.
+fn library_dir() -> PathBuf {
+    let deps = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    [deps.clone(), deps.parent().unwrap().to_path_buf()]
+        .into_iter()
+        .find(|dir| dir.join("libcoretexdb_c.so").exists())
+        .expect("libcoretexdb_c.so was not built")
+}
+
+fn compiler() -> String {
+    std::env::var("CC").unwrap_or_else(|_| "cc".to_string())
+}
+
+#[test]
+fn test_shipped_header_is_up_to_date() {
+    let shipped = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/coretexdb.h");
+    assert_eq!(
+        std::fs::read_to_string(&shipped).unwrap(),
+        std::fs::read_to_string(GENERATED_HEADER).unwrap(),
+        "include/coretexdb.h is stale; copy {} over it",
+        GENERATED_HEADER,
+    );
+}
+
+#[test]
+fn test_c_program_builds_and_runs() {
+    let build_dir = tempfile::tempdir().unwrap();
+    let data_dir = tempfile::tempdir().unwrap();
+    let program = build_dir.path().join("smoke");
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/smoke.c");
+    let include_dir = Path::new(GENERATED_HEADER).parent().unwrap();
+    let library_dir = library_dir();
+
+    let status = Command::new(compiler())
+        .args(["-std=c99", "-Wall", "-Werror"])
+        .arg("-I").arg(include_dir)
+        .arg(&source)
+        .arg("-L").arg(&library_dir)
+        .arg(format!("-Wl,-rpath,{}", library_dir.display()))
+        .arg("-lcoretexdb_c")
+        .arg("-o").arg(&program)
+        .status()
+        .expect("a C compiler is needed for this test");
+    assert!(status.success(), "compiling smoke.c failed");
+
+    for dir in [Some(data_dir.path()), None] {
+        let mut run = Command::new(&program);
+        if let Some(dir) = dir {
+            run.arg(dir);
+        }
+        let output = run.output().unwrap();
+        assert!(
+            output.status.success(),
+            "smoke.c failed: {}",
+            String::from_utf8_lossy(&output.stderr),
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).ends_with("ok\n"));
+    }
+}
@@ -0,0 +1,63 @@
+/* Exercises the C API the way an embedding application would, on the
+ * database in argv[1] or an in-memory one. Exits non-zero, naming the
+ * failed check, on the first failure. */
+#include <stdio.h>
+#include <string.h>
+
+#include "coretexdb.h"
+
+#define CHECK(cond)                                                            \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      const char *error = coretex_last_error();                                \
+      fprintf(stderr, "%s:%d: %s (%s)\n", __FILE__, __LINE__, #cond,           \
+              error ? error : "no error");                                     \
+      return 1;                                                                \
+    }                                                                          \
+  } while (0)
+
+int main(int argc, char **argv) {
+  const char *data_dir = argc > 1 ? argv[1] : NULL;
+  CoretexEdgeConfig config;
+  coretex_edge_config_default(&config);
+
+  CoretexEdgeDb *db = NULL;
+  CHECK(coretex_edge_open(data_dir, &config, &db) == CORETEX_STATUS_OK);
+  CHECK(coretex_edge_create_collection(db, "objects", 2) == CORETEX_STATUS_OK);
+  CHECK(coretex_edge_create_collection(db, "objects", 2) ==
+        CORETEX_STATUS_COLLECTION_EXISTS);
+
+  const float cup[] = {1.0f, 0.0f};
+  const float plate[] = {0.0f, 1.0f};
+  CHECK(coretex_edge_insert(db, "objects", "cup", cup, 2,
+                            "{\"label\":\"cup\"}") == CORETEX_STATUS_OK);
+  CHECK(coretex_edge_insert(db, "objects", "plate", plate, 2, NULL) ==
+        CORETEX_STATUS_OK);
+
+  /* A persisted database finds them again after a restart */
+  if (data_dir) {
+    CHECK(coretex_edge_close(db) == CORETEX_STATUS_OK);
+    CHECK(coretex_edge_open(data_dir, NULL, &db) == CORETEX_STATUS_OK);
+  }
+  const float query[] = {0.9f, 0.1f};
+  CoretexSearchResults *results = NULL;
+  CHECK(coretex_edge_search(db, "objects", query, 2, 5, &results) ==
+        CORETEX_STATUS_OK);
+  CHECK(coretex_results_len(results) == 2);
+  CHECK(strcmp(coretex_results_id(results, 0), "cup") == 0);
+  CHECK(strcmp(coretex_results_metadata(results, 0), "{\"label\":\"cup\"}") ==
+        0);
+  CHECK(coretex_results_distance(results, 0) <
+        coretex_results_distance(results, 1));
+  CHECK(coretex_results_id(results, 2) == NULL);
+  coretex_results_free(results);
+
+  bool deleted = false;
+  CHECK(coretex_edge_delete(db, "objects", "cup", &deleted) ==
+        CORETEX_STATUS_OK);
+  CHECK(deleted);
+  CHECK(coretex_edge_close(db) == CORETEX_STATUS_OK);
+
+  printf("coretexdb %s ok\n", coretex_version());
+  return 0;
+}
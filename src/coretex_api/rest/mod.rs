@@ -2,7 +2,7 @@
 
 use axum::{
     routing::{get, post, delete, put},
-    Json, Router, extract::State,
+    Json, Router, extract::State, http::{HeaderMap, StatusCode},
 };
 use tower_http::cors::{Any, CorsLayer};
 use serde::{Deserialize, Serialize};
@@ -12,13 +12,94 @@ use std::net::SocketAddr;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
 
-use crate::{CoreTexDB, DbConfig, SearchResult};
+use crate::{AsOf, CoreTexDB, DbConfig, DbTransaction, FieldQuery, IndexConfig, IndexType, MultiVectorConfig, MultiVectorSearchResult, RebuildProgress, SnapshotId, SparseVector, TieredSearchOptions, TransactionId, VectorField, DEFAULT_VECTOR_FIELD};
+use crate::coretex_core::DistanceMetric;
+use crate::coretex_hybrid::{FusedResult, HybridQuery, ScoreFusion};
+use crate::coretex_backup::{BackupConfig, BackupManager, BackupScheduler};
+use crate::coretex_monitoring_v2::{AlertSeverity, MetricsCollector, MonitoringConfig};
+use crate::coretex_lakehouse::{TierConfig, TieringDaemon, TieringDaemonConfig, VectorLakehouse};
+use crate::coretex_edge::{SyncRequest, SyncResponse, SyncServer};
+use crate::coretex_distributed::replication::{HttpReplicaClient, ReadPreference, ReplicaClient, ReplicaNode, ReplicaSnapshot, ReplicationLeader, ReplicationRole};
+use crate::coretex_distributed::two_phase::{HttpTwoPhaseTransport, TwoPhaseNode, TwoPhaseRequest, TwoPhaseResponse};
+use crate::coretex_distributed::consensus::{DbStateMachine, HttpRaftTransport};
+use crate::coretex_distributed::locks::{HttpLockTransport, Lease, LockError, LockRequest, LockService, LockTable};
+use crate::coretex_distributed::sharding::{HttpShardClient, ShardCoordinator, ShardingConfig, ShardingStrategy};
+use crate::coretex_utils::cluster::{ClusterManager, ClusterNode, NodeRole, NodeState};
+use crate::coretex_utils::raft::{RaftNode, RaftRequest, RaftResponse};
+use crate::coretex_utils::wal::{WalEntry, WalEntryType};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ApiConfig {
     pub address: String,
     pub port: u16,
     pub enable_cors: bool,
+    /// Scheduled backups run in the background while the server is up
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
+    /// This node's id in distributed transactions
+    #[serde(default = "default_node_id")]
+    pub node_id: String,
+    /// Base URLs of the other nodes by id
+    #[serde(default)]
+    pub peers: HashMap<String, String>,
+    /// Where the database keeps its data and journal
+    #[serde(default)]
+    pub db: DbConfig,
+    /// Opens `db` with `CoreTexDB::open`, keeping documents and the journal
+    /// on disk; otherwise documents live in memory
+    #[serde(default)]
+    pub durable: bool,
+    /// Whether this node leads read replicas or is one
+    #[serde(default)]
+    pub replication: Option<ReplicationRole>,
+    /// Runs a Raft group with `peers` whose committed entries apply to the
+    /// database; its state is kept under the data directory
+    #[serde(default)]
+    pub raft: bool,
+    /// Tiered storage searched by `/search`, with a daemon migrating
+    /// documents between tiers in the background
+    #[serde(default)]
+    pub lakehouse: Option<LakehouseConfig>,
+    /// Lets collections be split into shards over this node and `peers`;
+    /// which collections are sharded is kept under the data directory
+    #[serde(default)]
+    pub sharding: Option<ShardsConfig>,
+}
+
+/// A lakehouse under `{data_dir}/lakehouse`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LakehouseConfig {
+    #[serde(default)]
+    pub tiers: TierConfig,
+    #[serde(default)]
+    pub tiering: TieringDaemonConfig,
+}
+
+/// Shards placed over this node and its peers.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShardsConfig {
+    /// Copies kept of each shard, counting the primary
+    #[serde(default = "default_replication_factor")]
+    pub replication_factor: usize,
+    #[serde(default)]
+    pub coordinator: ShardingConfig,
+}
+
+fn default_replication_factor() -> usize {
+    1
+}
+
+impl Default for ShardsConfig {
+    fn default() -> Self {
+        Self {
+            replication_factor: default_replication_factor(),
+            coordinator: ShardingConfig::default(),
+        }
+    }
+}
+
+fn default_node_id() -> String {
+    "node1".to_string()
 }
 
 impl Default for ApiConfig {
@@ -27,6 +108,15 @@ impl Default for ApiConfig {
             address: "0.0.0.0".to_string(),
             port: 5000,
             enable_cors: true,
+            backup: None,
+            node_id: default_node_id(),
+            peers: HashMap::new(),
+            db: DbConfig::default(),
+            durable: false,
+            replication: None,
+            raft: false,
+            lakehouse: None,
+            sharding: None,
         }
     }
 }
@@ -36,6 +126,9 @@ pub struct CreateCollectionRequest {
     pub name: String,
     pub dimension: usize,
     pub distance_metric: Option<String>,
+    /// Splits the collection into shards, on a node with sharding enabled
+    #[serde(default)]
+    pub sharding: Option<ShardingStrategy>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -72,6 +165,63 @@ pub struct GetVectorResponse {
     pub metadata: serde_json::Value,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanVectorsQuery {
+    /// Return vectors with ids after this one
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Reads at an earlier point; `snapshot_id` wins if both are set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AsOfQuery {
+    /// Milliseconds since the Unix epoch
+    pub as_of: Option<u64>,
+    pub snapshot_id: Option<SnapshotId>,
+}
+
+impl AsOfQuery {
+    fn as_of(&self) -> Option<AsOf> {
+        self.snapshot_id.map(AsOf::Snapshot)
+            .or(self.as_of.map(AsOf::Timestamp))
+    }
+}
+
+/// New settings for a collection's index, e.g.
+/// `{"index_type": "hnsw", "parameters": {"m": 32, "ef_construction": 400}}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebuildIndexRequest {
+    /// `brute_force`, `hnsw` or `ivf`
+    pub index_type: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotResponse {
+    pub snapshot_id: SnapshotId,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanVectorsResponse {
+    pub vectors: Vec<VectorItem>,
+    /// Pass as `after` to fetch the next page; absent on the last page
+    pub next: Option<String>,
+}
+
+/// Requests carrying this header run inside the named transaction
+pub const TRANSACTION_HEADER: &str = "x-transaction-id";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BeginTransactionResponse {
+    pub transaction_id: TransactionId,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationAppendRequest {
+    pub entries: Vec<WalEntry>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteVectorsRequest {
     pub ids: Vec<String>,
@@ -88,12 +238,85 @@ pub struct SearchRequest {
     pub vector: Vec<f32>,
     pub k: usize,
     pub filter: Option<serde_json::Value>,
+    /// Also search the lakehouse cold tier (slower, reads object storage)
+    #[serde(default)]
+    pub include_cold: bool,
+    /// Search the collection as it was at this point
+    #[serde(default, flatten)]
+    pub as_of: AsOfQuery,
+    /// Named vector field to search instead of the main vectors
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Whether a read replica may answer, on a replication leader
+    #[serde(default)]
+    pub read: ReadPreference,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddVectorFieldRequest {
+    pub name: String,
+    pub dimension: usize,
+    pub distance_metric: Option<String>,
+    /// `brute_force`, `hnsw` (the default) or `ivf`
+    pub index_type: Option<String>,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldSearchRequest {
+    pub queries: Vec<FieldQuery>,
+    pub k: usize,
+    pub filter: Option<serde_json::Value>,
+    #[serde(default)]
+    pub fusion: ScoreFusion,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnableMultiVectorRequest {
+    pub dimension: usize,
+    pub distance_metric: Option<String>,
+    /// Index over the token vectors: `brute_force`, `hnsw` (the default) or `ivf`
+    pub index_type: Option<String>,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    pub candidates_per_token: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultiVectorSearchRequest {
+    pub query: Vec<Vec<f32>>,
+    pub k: usize,
+    pub filter: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultiVectorSearchResponse {
+    pub results: Vec<MultiVectorSearchResult>,
+    pub execution_time_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HybridSearchRequest {
+    pub query: HybridQuery,
+    #[serde(default)]
+    pub fusion: ScoreFusion,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FusedSearchResponse {
+    pub results: Vec<FusedResult>,
+    pub execution_time_ms: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResponse {
     pub results: Vec<SearchResultItem>,
     pub execution_time_ms: u64,
+    /// Shards of a sharded collection that did not answer; the results
+    /// are missing whatever they hold
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_shards: Vec<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -101,6 +324,9 @@ pub struct SearchResultItem {
     pub id: String,
     pub score: f32,
     pub metadata: Option<serde_json::Value>,
+    /// Storage tier the hit came from, when a lakehouse is attached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -171,23 +397,114 @@ impl<T> ApiResponse<T> {
 
 pub struct ApiState {
     pub db: Arc<RwLock<CoreTexDB>>,
+    /// Reconciles edge devices with `db`
+    pub sync: Arc<SyncServer>,
+    /// Ships `db`'s journal to read replicas, on a replication leader
+    pub leader: Option<Arc<ReplicationLeader>>,
+    /// Coordinates and takes part in two-phase commits against `db`
+    pub two_phase: Arc<TwoPhaseNode>,
+    /// Served at `/metrics`, e.g. the backup scheduler's runs and failures
+    pub metrics: MetricsCollector,
+    /// Cluster-wide leases, e.g. for index rebuilds, when Raft is on
+    pub locks: Option<Arc<LockService>>,
+    /// Routes requests for sharded collections, when sharding is on
+    pub shards: Option<Arc<ShardCoordinator>>,
+    /// Orders writes to `db` across the group, when Raft is on
+    pub raft: Option<Arc<RaftNode>>,
 }
 
 pub async fn start_server(config: ApiConfig) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let db = CoreTexDB::new();
-    db.init().await.map_err(|e| format!("Failed to init DB: {}", e))?;
+    let db = if config.durable {
+        CoreTexDB::open(config.db.clone()).await.map_err(|e| format!("Failed to open DB: {}", e))?
+    } else {
+        let db = CoreTexDB::with_config(config.db.clone());
+        db.init().await.map_err(|e| format!("Failed to init DB: {}", e))?;
+        db
+    };
+    let lakehouse = match &config.lakehouse {
+        Some(lakehouse_config) => Some(open_lakehouse(lakehouse_config, &db.config).await?),
+        None => None,
+    };
+    let db = match &lakehouse {
+        Some(lakehouse) => db.with_lakehouse(lakehouse.clone()),
+        None => db,
+    };
+
+    let metrics = MetricsCollector::new(MonitoringConfig::default());
+    let _backup_scheduler = match &config.backup {
+        Some(backup_config) => start_backup_scheduler(backup_config.clone(), &db.config, metrics.clone()).await?,
+        None => None,
+    };
+
+    let transport = HttpTwoPhaseTransport::new();
+    transport.add_peer(&config.node_id, &format!("http://127.0.0.1:{}", config.port));
+    for (node_id, base_url) in &config.peers {
+        transport.add_peer(node_id, base_url);
+    }
+    let two_phase_dir = std::path::Path::new(&db.config.data_dir).join("two_phase");
+    let sync_dir = std::path::Path::new(&db.config.data_dir).join("sync");
+    let shard_registry = std::path::Path::new(&db.config.data_dir).join("shards.json");
+
+    let db = Arc::new(RwLock::new(db));
+    let two_phase = Arc::new(TwoPhaseNode::open(&two_phase_dir, &config.node_id, db.clone(), Arc::new(transport)).await?);
+    let _two_phase_ticker = two_phase.start();
+
+    let leader = match &config.replication {
+        Some(ReplicationRole::Leader { replicas, token }) => {
+            let leader = Arc::new(ReplicationLeader::new(&config.node_id, db.clone()));
+            for (node_id, base_url) in replicas {
+                leader.add_replica(node_id, Arc::new(HttpReplicaClient::new(base_url).with_token(token))).await;
+            }
+            Some(leader)
+        }
+        _ => None,
+    };
+    let _shipper = leader.as_ref().map(|leader| leader.start());
+
+    let (raft, locks) = match config.raft {
+        true => {
+            let (raft, locks) = open_raft(&config, db.clone()).await?;
+            (Some(raft), Some(locks))
+        }
+        false => (None, None),
+    };
+    let _raft_ticker = raft.as_ref().map(|raft| raft.start());
+    let shards = match &config.sharding {
+        Some(shards_config) => Some(open_shards(&config, shards_config, &shard_registry).await?),
+        None => None,
+    };
+    let _tiering = match (&config.lakehouse, lakehouse) {
+        (Some(lakehouse_config), Some(lakehouse)) => {
+            Some(start_tiering_daemon(lakehouse_config, lakehouse, metrics.clone(), locks.clone()))
+        }
+        _ => None,
+    };
+    // Only a replica takes entries from a leader, and only with its token
+    let replication_routes = match &config.replication {
+        Some(ReplicationRole::Replica { token }) => Some(replication_routes(ReplicaNode::new(db.clone()), token)),
+        _ => None,
+    };
 
     let state = ApiState {
-        db: Arc::new(RwLock::new(db)),
+        sync: Arc::new(SyncServer::open(&sync_dir, db.clone())?),
+        leader,
+        two_phase,
+        db,
+        metrics,
+        locks: locks.clone(),
+        shards,
+        raft: raft.clone(),
     };
 
     let app = Router::new()
         .route("/health", get(health_check))
+        .route("/metrics", get(metrics_text))
         .route("/api/collections", get(list_collections))
         .route("/api/collections", post(create_collection))
         .route("/api/collections/:name", get(get_collection))
         .route("/api/collections/:name", delete(delete_collection))
         .route("/api/collections/:name/stats", get(get_collection_stats))
+        .route("/api/collections/:name/vectors", get(scan_vectors))
         .route("/api/collections/:name/vectors", post(insert_vectors))
         .route("/api/collections/:name/vectors", put(update_vectors))
         .route("/api/collections/:name/vectors/:id", get(get_vector))
@@ -195,7 +512,40 @@ pub async fn start_server(config: ApiConfig) -> Result<(), Box<dyn Error + Send
         .route("/api/collections/:name/search", post(search))
         .route("/api/collections/:name/batch-search", post(batch_search))
         .route("/api/collections/:name/count", get(get_vectors_count))
+        .route("/api/collections/:name/index/rebuild", post(rebuild_index))
+        .route("/api/collections/:name/index/rebuild", get(get_index_rebuild))
+        .route("/api/collections/:name/fields", post(add_vector_field))
+        .route("/api/collections/:name/fields/search", post(search_fields))
+        .route("/api/collections/:name/vectors/:id/fields", get(get_named_vectors))
+        .route("/api/collections/:name/vectors/:id/fields", put(set_named_vectors))
+        .route("/api/collections/:name/vectors/:id/sparse", get(get_sparse_vector))
+        .route("/api/collections/:name/vectors/:id/sparse", put(set_sparse_vector))
+        .route("/api/collections/:name/hybrid-search", post(hybrid_search))
+        .route("/api/collections/:name/multi-vector", post(enable_multi_vector))
+        .route("/api/collections/:name/multi-vector/search", post(search_multi_vector))
+        .route("/api/collections/:name/vectors/:id/multi", get(get_multi_vector))
+        .route("/api/collections/:name/vectors/:id/multi", put(set_multi_vector))
+        .route("/api/snapshots", post(create_snapshot))
+        .route("/api/snapshots/:id", delete(release_snapshot))
+        .route("/api/transactions", post(begin_transaction))
+        .route("/api/transactions/:id/commit", post(commit_transaction))
+        .route("/api/transactions/:id/rollback", post(rollback_transaction))
+        .route("/api/sync", post(sync))
+        .route("/api/2pc", post(two_phase_request))
         .with_state(Arc::new(state));
+    let replica = replication_routes.is_some();
+    let app = match replication_routes {
+        Some(routes) => app.merge(routes),
+        None => app,
+    };
+    let app = match &raft {
+        Some(raft) => app.merge(Router::new().route("/api/raft", post(raft_request)).with_state(raft.clone())),
+        None => app,
+    };
+    let app = match &locks {
+        Some(locks) => app.merge(Router::new().route("/api/locks", post(lock_request)).with_state(locks.clone())),
+        None => app,
+    };
 
     let app = if config.enable_cors {
         let cors = CorsLayer::new()
@@ -218,6 +568,7 @@ pub async fn start_server(config: ApiConfig) -> Result<(), Box<dyn Error + Send
     println!("Starting CortexDB API server on http://{}", addr);
     println!("API endpoints:");
     println!("  GET  /health                              - Health check");
+    println!("  GET  /metrics                             - Prometheus metrics");
     println!("  GET  /api/collections                     - List collections");
     println!("  POST /api/collections                    - Create collection");
     println!("  GET  /api/collections/:name               - Get collection info");
@@ -230,6 +581,18 @@ pub async fn start_server(config: ApiConfig) -> Result<(), Box<dyn Error + Send
     println!("  POST /api/collections/:name/search       - Search vectors");
     println!("  POST /api/collections/:name/batch-search - Batch search");
     println!("  GET  /api/collections/:name/count        - Get vectors count");
+    println!("  POST /api/transactions                   - Begin a transaction");
+    println!("  POST /api/transactions/:id/commit        - Commit a transaction");
+    println!("  POST /api/transactions/:id/rollback      - Roll back a transaction");
+    println!("  POST /api/sync                           - Edge device sync");
+    if replica {
+        println!("  POST /api/replication/append             - Apply replicated WAL entries");
+        println!("  POST /api/replication/snapshot           - Install a replication snapshot");
+    }
+    if raft.is_some() {
+        println!("  POST /api/raft                           - Raft messages between nodes");
+        println!("  POST /api/locks                          - Lock requests forwarded to the leader");
+    }
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
     axum::serve(listener, app).await?;
@@ -237,6 +600,170 @@ pub async fn start_server(config: ApiConfig) -> Result<(), Box<dyn Error + Send
     Ok(())
 }
 
+/// A shard coordinator over this node and every configured peer, all
+/// reached over HTTP, knowing the sharded collections of earlier runs.
+async fn open_shards(
+    config: &ApiConfig,
+    shards_config: &ShardsConfig,
+    registry: &std::path::Path,
+) -> Result<Arc<ShardCoordinator>, Box<dyn Error + Send + Sync>> {
+    let cluster = Arc::new(ClusterManager::new(&config.node_id, shards_config.replication_factor));
+    let coordinator = ShardCoordinator::new(cluster.clone())
+        .with_config(shards_config.coordinator.clone())
+        .with_registry(registry);
+
+    let local_url = format!("http://127.0.0.1:{}", config.port);
+    for (node_id, base_url) in std::iter::once((&config.node_id, &local_url)).chain(&config.peers) {
+        let url = reqwest::Url::parse(base_url).map_err(|e| format!("Invalid URL for node '{}': {}", node_id, e))?;
+        cluster.add_node(ClusterNode {
+            id: node_id.clone(),
+            address: url.host_str().unwrap_or_default().to_string(),
+            port: url.port_or_known_default().unwrap_or_default(),
+            role: NodeRole::Follower,
+            state: NodeState::Active,
+            last_heartbeat: 0,
+            shard_ids: Vec::new(),
+        }).await;
+        coordinator.register_node(node_id, Arc::new(HttpShardClient::new(base_url))).await;
+    }
+
+    coordinator.load_registry().await?;
+    Ok(Arc::new(coordinator))
+}
+
+/// How long a write waits for the Raft group to commit it.
+const RAFT_COMMIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Commits a write through the Raft group, which applies it to `db` here
+/// and on the other members. Only the leader takes writes; followers turn
+/// them away naming it.
+async fn commit_write(raft: &RaftNode, entry_type: WalEntryType, collection: &str, data: serde_json::Value) -> Result<(), String> {
+    let entry = WalEntry {
+        id: 0,
+        timestamp: chrono::Utc::now().timestamp() as u64,
+        entry_type,
+        collection: collection.to_string(),
+        data,
+    };
+    let proposal = raft.propose(entry).await.map_err(|e| e.to_string())?;
+    raft.wait_committed(proposal, RAFT_COMMIT_TIMEOUT).await.map_err(|e| e.to_string())
+}
+
+/// Documents in the form Insert and Update journal entries carry them.
+fn vector_records(vectors: &[(String, Vec<f32>, serde_json::Value)]) -> serde_json::Value {
+    let records: Vec<serde_json::Value> = vectors.iter()
+        .map(|(id, vector, metadata)| serde_json::json!({ "id": id, "vector": vector, "metadata": metadata }))
+        .collect();
+    serde_json::json!({ "vectors": records })
+}
+
+/// Turns away writes that would fail to apply before they reach the Raft
+/// log, where a failing entry is only skipped once it has committed.
+async fn check_vectors(db: &CoreTexDB, collection: &str, vectors: &[(String, Vec<f32>, serde_json::Value)]) -> Result<(), String> {
+    let schema = db.get_collection(collection).await.map_err(|e| e.to_string())?;
+    match vectors.iter().find(|(_, vector, _)| vector.len() != schema.dimension) {
+        Some((id, vector, _)) => Err(format!("Vector '{}' has dimension {}, expected {}", id, vector.len(), schema.dimension)),
+        None => Ok(()),
+    }
+}
+
+/// This node's Raft group member, with every configured peer as a member,
+/// and the lock service whose leases the group replicates next to `db`'s
+/// writes.
+async fn open_raft(config: &ApiConfig, db: Arc<RwLock<CoreTexDB>>) -> Result<(Arc<RaftNode>, Arc<LockService>), Box<dyn Error + Send + Sync>> {
+    let transport = HttpRaftTransport::new();
+    let lock_transport = HttpLockTransport::new();
+    for (node_id, base_url) in &config.peers {
+        transport.add_peer(node_id, base_url);
+        lock_transport.add_peer(node_id, base_url);
+    }
+    let mut members: Vec<String> = config.peers.keys().cloned().collect();
+    members.push(config.node_id.clone());
+    members.sort();
+
+    let raft_dir = std::path::Path::new(&config.db.data_dir).join("raft");
+    let table = LockTable::new(&config.node_id).with_inner(Box::new(DbStateMachine::new(db)));
+    let node = RaftNode::open(&raft_dir, &config.node_id, members, Arc::new(transport), Box::new(table.clone())).await
+        .map_err(|e| format!("Failed to open Raft state: {}", e))?;
+    let node = Arc::new(node);
+    let locks = LockService::new(node.clone(), table).with_transport(Arc::new(lock_transport));
+    Ok((node, Arc::new(locks)))
+}
+
+/// Opens the lakehouse and restores where its documents live.
+async fn open_lakehouse(config: &LakehouseConfig, db_config: &DbConfig) -> Result<Arc<VectorLakehouse>, Box<dyn Error + Send + Sync>> {
+    let dir = std::path::Path::new(&db_config.data_dir).join("lakehouse");
+    let lakehouse = VectorLakehouse::new(&dir.to_string_lossy())
+        .map_err(|e| format!("Failed to open lakehouse: {}", e))?
+        .with_config(config.tiers.clone());
+    lakehouse.load_metadata().await.map_err(|e| format!("Failed to load lakehouse metadata: {}", e))?;
+    Ok(Arc::new(lakehouse))
+}
+
+/// Migrates lakehouse documents between tiers on an interval. With Raft on,
+/// passes run under a cluster-wide lease so nodes sharing the lakehouse take
+/// turns.
+fn start_tiering_daemon(
+    config: &LakehouseConfig,
+    lakehouse: Arc<VectorLakehouse>,
+    metrics: MetricsCollector,
+    locks: Option<Arc<LockService>>,
+) -> Arc<TieringDaemon> {
+    let daemon = TieringDaemon::new(lakehouse, config.tiering.clone()).with_metrics(metrics);
+    let daemon = Arc::new(match locks {
+        Some(locks) => daemon.with_lock(locks),
+        None => daemon,
+    });
+
+    println!("Lakehouse tiering enabled (every {}s)", config.tiering.interval_secs);
+    daemon.start();
+    daemon
+}
+
+async fn start_backup_scheduler(
+    config: BackupConfig,
+    db_config: &DbConfig,
+    metrics: MetricsCollector,
+) -> Result<Option<Arc<BackupScheduler>>, Box<dyn Error + Send + Sync>> {
+    let manager = Arc::new(BackupManager::new(config, &db_config.data_dir).with_wal_dir(&db_config.wal_dir));
+    manager.initialize().await.map_err(|e| format!("Failed to init backups: {}", e))?;
+
+    let scheduler = match BackupScheduler::from_config(manager)? {
+        Some(scheduler) => Arc::new(scheduler.with_metrics(metrics.clone())),
+        None => return Ok(None),
+    };
+
+    // Failed runs raise alerts, which go to the log with the error behind them
+    let mut alerts = metrics.alert_receiver();
+    let alerting = scheduler.clone();
+    tokio::spawn(async move {
+        loop {
+            let alert = match alerts.recv().await {
+                Ok(alert) => alert,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            let error = alerting.stats().await.last_error.unwrap_or_default();
+            match alert.severity {
+                AlertSeverity::Info => tracing::info!(alert = %alert.name, %error, "{}", alert.message),
+                AlertSeverity::Warning => tracing::warn!(alert = %alert.name, %error, "{}", alert.message),
+                AlertSeverity::Error | AlertSeverity::Critical => {
+                    tracing::error!(alert = %alert.name, %error, "{}", alert.message)
+                }
+            }
+        }
+    });
+
+    println!("Scheduled backups enabled ({})", scheduler.schedule().expression());
+    scheduler.start();
+
+    Ok(Some(scheduler))
+}
+
+async fn metrics_text(State(state): State<Arc<ApiState>>) -> String {
+    state.metrics.get_metrics_text().await
+}
+
 async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse {
         status: "ok".to_string(),
@@ -249,18 +776,63 @@ async fn list_collections(
 ) -> Json<ApiResponse<Vec<String>>> {
     let db = state.db.read().await;
     match db.list_collections().await {
-        Ok(collections) => Json(ApiResponse::success(collections)),
+        Ok(mut collections) => {
+            if let Some(shards) = &state.shards {
+                collections.extend(shards.list_collections().await);
+            }
+            Json(ApiResponse::success(collections))
+        }
         Err(e) => Json(ApiResponse::error(&e.to_string())),
     }
 }
 
+/// The coordinator of `name` if it is a sharded collection.
+async fn sharded(state: &ApiState, name: &str) -> Option<Arc<ShardCoordinator>> {
+    let shards = state.shards.as_ref()?;
+    shards.get_collection(name).await.map(|_| shards.clone())
+}
+
 async fn create_collection(
     State(state): State<Arc<ApiState>>,
     Json(req): Json<CreateCollectionRequest>,
 ) -> Json<ApiResponse<CollectionInfo>> {
-    let db = state.db.read().await;
     let metric = req.distance_metric.unwrap_or_else(|| "cosine".to_string());
-    
+    if let Err(e) = crate::coretex_side_vectors::check_collection_name(&req.name) {
+        return Json(ApiResponse::error(&e.to_string()));
+    }
+
+    if let Some(strategy) = req.sharding {
+        let Some(shards) = &state.shards else {
+            return Json(ApiResponse::error("Sharding is not enabled on this node"));
+        };
+        return match shards.create_collection(&req.name, req.dimension, &metric, strategy).await {
+            Ok(_) => Json(ApiResponse::success(CollectionInfo {
+                name: req.name,
+                dimension: req.dimension,
+                distance_metric: metric,
+                vectors_count: 0,
+            })),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
+
+    if let Some(raft) = &state.raft {
+        if state.db.read().await.get_collection(&req.name).await.is_ok() {
+            return Json(ApiResponse::error(&format!("Collection '{}' already exists", req.name)));
+        }
+        let data = serde_json::json!({ "dimension": req.dimension, "metric": metric });
+        return match commit_write(raft, WalEntryType::CreateCollection, &req.name, data).await {
+            Ok(()) => Json(ApiResponse::success(CollectionInfo {
+                name: req.name,
+                dimension: req.dimension,
+                distance_metric: metric,
+                vectors_count: 0,
+            })),
+            Err(e) => Json(ApiResponse::error(&e)),
+        };
+    }
+
+    let db = state.db.read().await;
     match db.create_collection(&req.name, req.dimension, &metric).await {
         Ok(_) => {
             let info = CollectionInfo {
@@ -279,8 +851,21 @@ async fn get_collection(
     State(state): State<Arc<ApiState>>,
     axum::extract::Path(name): axum::extract::Path<String>,
 ) -> Json<ApiResponse<CollectionInfo>> {
+    if let Some(shards) = &state.shards {
+        if let Some(collection) = shards.get_collection(&name).await {
+            return match shards.count(&name).await {
+                Ok(count) => Json(ApiResponse::success(CollectionInfo {
+                    name: collection.name,
+                    dimension: collection.dimension,
+                    distance_metric: collection.metric,
+                    vectors_count: count,
+                })),
+                Err(e) => Json(ApiResponse::error(&e.to_string())),
+            };
+        }
+    }
+
     let db = state.db.read().await;
-    
     match db.get_collection(&name).await {
         Ok(schema) => {
             let count = db.get_vectors_count(&name).await.unwrap_or(0);
@@ -300,29 +885,389 @@ async fn delete_collection(
     State(state): State<Arc<ApiState>>,
     axum::extract::Path(name): axum::extract::Path<String>,
 ) -> Json<ApiResponse<String>> {
+    if let Some(shards) = sharded(&state, &name).await {
+        return match shards.delete_collection(&name).await {
+            Ok(()) => Json(ApiResponse::success(format!("Collection '{}' deleted", name))),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
+
+    if let Some(raft) = &state.raft {
+        if let Err(e) = state.db.read().await.get_collection(&name).await {
+            return Json(ApiResponse::error(&e.to_string()));
+        }
+        return match commit_write(raft, WalEntryType::DeleteCollection, &name, serde_json::json!({})).await {
+            Ok(()) => Json(ApiResponse::success(format!("Collection '{}' deleted", name))),
+            Err(e) => Json(ApiResponse::error(&e)),
+        };
+    }
+
     let db = state.db.read().await;
-    
     match db.delete_collection(&name).await {
         Ok(_) => Json(ApiResponse::success(format!("Collection '{}' deleted", name))),
         Err(e) => Json(ApiResponse::error(&e.to_string())),
     }
 }
 
+/// The transaction named by `TRANSACTION_HEADER`, if the request has one.
+async fn request_transaction<'a>(db: &'a CoreTexDB, headers: &HeaderMap) -> Result<Option<DbTransaction<'a>>, String> {
+    let Some(value) = headers.get(TRANSACTION_HEADER) else {
+        return Ok(None);
+    };
+    let id = value.to_str().ok()
+        .and_then(|value| value.trim().parse::<TransactionId>().ok())
+        .ok_or_else(|| format!("Invalid {} header", TRANSACTION_HEADER))?;
+    db.transaction(id).await.map(Some).map_err(|e| e.to_string())
+}
+
+async fn rebuild_index(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Json(req): Json<RebuildIndexRequest>,
+) -> Json<ApiResponse<RebuildProgress>> {
+    let index_type = match parse_index_type(&req.index_type) {
+        Ok(index_type) => index_type,
+        Err(e) => return Json(ApiResponse::error(&e)),
+    };
+    let config = IndexConfig {
+        name: format!("{}_{}", name, req.index_type),
+        index_type,
+        parameters: req.parameters,
+    };
+
+    let db = state.db.read().await;
+    let started = match &state.locks {
+        Some(locks) => db.rebuild_index_locked(&name, config, locks).await,
+        None => db.rebuild_index(&name, config).await,
+    };
+    match started {
+        Ok(progress) => Json(ApiResponse::success(progress)),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+fn parse_index_type(name: &str) -> Result<IndexType, String> {
+    match name {
+        "brute_force" => Ok(IndexType::BruteForce),
+        "hnsw" => Ok(IndexType::HNSW),
+        "ivf" => Ok(IndexType::IVF),
+        other => Err(format!("Unknown index type: {}", other)),
+    }
+}
+
+async fn add_vector_field(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Json(req): Json<AddVectorFieldRequest>,
+) -> Json<ApiResponse<VectorField>> {
+    let index_type = req.index_type.unwrap_or_else(|| "hnsw".to_string());
+    let field = VectorField {
+        index: IndexConfig {
+            name: format!("{}.{}_{}", name, req.name, index_type),
+            index_type: match parse_index_type(&index_type) {
+                Ok(index_type) => index_type,
+                Err(e) => return Json(ApiResponse::error(&e)),
+            },
+            parameters: req.parameters,
+        },
+        name: req.name,
+        dimension: req.dimension,
+        distance_metric: DistanceMetric::from_name(req.distance_metric.as_deref().unwrap_or("cosine")),
+    };
+
+    let db = state.db.read().await;
+    match db.add_vector_field(&name, field.clone()).await {
+        Ok(()) => Json(ApiResponse::success(field)),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn set_named_vectors(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path((name, id)): axum::extract::Path<(String, String)>,
+    Json(vectors): Json<HashMap<String, Vec<f32>>>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.read().await;
+
+    match db.set_named_vectors(&name, &id, vectors).await {
+        Ok(()) => Json(ApiResponse::success(format!("Vector fields of {} updated", id))),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn get_named_vectors(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path((name, id)): axum::extract::Path<(String, String)>,
+) -> Json<ApiResponse<HashMap<String, Vec<f32>>>> {
+    let db = state.db.read().await;
+
+    match db.get_named_vectors(&name, &id).await {
+        Ok(vectors) => Json(ApiResponse::success(vectors)),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn search_fields(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Json(req): Json<FieldSearchRequest>,
+) -> Json<ApiResponse<FusedSearchResponse>> {
+    let start = std::time::Instant::now();
+    let db = state.db.read().await;
+
+    match db.search_fields(&name, req.queries, req.k, req.filter, req.fusion).await {
+        Ok(results) => Json(ApiResponse::success(FusedSearchResponse {
+            results,
+            execution_time_ms: start.elapsed().as_millis() as u64,
+        })),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn set_sparse_vector(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path((name, id)): axum::extract::Path<(String, String)>,
+    Json(vector): Json<SparseVector>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.read().await;
+
+    match db.set_sparse_vector(&name, &id, vector).await {
+        Ok(()) => Json(ApiResponse::success(format!("Sparse vector of {} updated", id))),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn get_sparse_vector(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path((name, id)): axum::extract::Path<(String, String)>,
+) -> Json<ApiResponse<SparseVector>> {
+    let db = state.db.read().await;
+
+    match db.get_sparse_vector(&name, &id).await {
+        Ok(Some(vector)) => Json(ApiResponse::success(vector)),
+        Ok(None) => Json(ApiResponse::error(&format!("Document {} has no sparse vector", id))),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn enable_multi_vector(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Json(req): Json<EnableMultiVectorRequest>,
+) -> Json<ApiResponse<MultiVectorConfig>> {
+    let index_type = req.index_type.unwrap_or_else(|| "hnsw".to_string());
+    let config = MultiVectorConfig {
+        dimension: req.dimension,
+        distance_metric: DistanceMetric::from_name(req.distance_metric.as_deref().unwrap_or("cosine")),
+        index: IndexConfig {
+            name: format!("{}.multi_vector_{}", name, index_type),
+            index_type: match parse_index_type(&index_type) {
+                Ok(index_type) => index_type,
+                Err(e) => return Json(ApiResponse::error(&e)),
+            },
+            parameters: req.parameters,
+        },
+        candidates_per_token: req.candidates_per_token.unwrap_or(100),
+    };
+
+    let db = state.db.read().await;
+    match db.enable_multi_vector(&name, config.clone()).await {
+        Ok(()) => Json(ApiResponse::success(config)),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn set_multi_vector(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path((name, id)): axum::extract::Path<(String, String)>,
+    Json(vectors): Json<Vec<Vec<f32>>>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.read().await;
+
+    match db.set_multi_vector(&name, &id, vectors).await {
+        Ok(()) => Json(ApiResponse::success(format!("Multi-vector of {} updated", id))),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn get_multi_vector(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path((name, id)): axum::extract::Path<(String, String)>,
+) -> Json<ApiResponse<Vec<Vec<f32>>>> {
+    let db = state.db.read().await;
+
+    match db.get_multi_vector(&name, &id).await {
+        Ok(Some(vectors)) => Json(ApiResponse::success(vectors)),
+        Ok(None) => Json(ApiResponse::error(&format!("Document {} has no multi-vector", id))),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn search_multi_vector(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Json(req): Json<MultiVectorSearchRequest>,
+) -> Json<ApiResponse<MultiVectorSearchResponse>> {
+    let start = std::time::Instant::now();
+    let db = state.db.read().await;
+
+    match db.search_multi_vector(&name, &req.query, req.k, req.filter).await {
+        Ok(results) => Json(ApiResponse::success(MultiVectorSearchResponse {
+            results,
+            execution_time_ms: start.elapsed().as_millis() as u64,
+        })),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn hybrid_search(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Json(req): Json<HybridSearchRequest>,
+) -> Json<ApiResponse<FusedSearchResponse>> {
+    let start = std::time::Instant::now();
+    let db = state.db.read().await;
+
+    match db.search_hybrid(&name, &req.query, req.fusion).await {
+        Ok(results) => Json(ApiResponse::success(FusedSearchResponse {
+            results,
+            execution_time_ms: start.elapsed().as_millis() as u64,
+        })),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn get_index_rebuild(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> Json<ApiResponse<RebuildProgress>> {
+    let db = state.db.read().await;
+
+    match db.index_rebuild_progress(&name).await {
+        Some(progress) => Json(ApiResponse::success(progress)),
+        None => Json(ApiResponse::error(&format!("No index rebuild for collection {}", name))),
+    }
+}
+
+async fn create_snapshot(
+    State(state): State<Arc<ApiState>>,
+) -> Json<ApiResponse<SnapshotResponse>> {
+    let db = state.db.read().await;
+    Json(ApiResponse::success(SnapshotResponse { snapshot_id: db.create_snapshot().await }))
+}
+
+async fn release_snapshot(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(id): axum::extract::Path<SnapshotId>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.read().await;
+
+    if db.release_snapshot(id).await {
+        Json(ApiResponse::success(format!("Snapshot {} released", id)))
+    } else {
+        Json(ApiResponse::error(&format!("Snapshot {} not found", id)))
+    }
+}
+
+async fn begin_transaction(
+    State(state): State<Arc<ApiState>>,
+) -> Json<ApiResponse<BeginTransactionResponse>> {
+    let db = state.db.read().await;
+
+    match db.begin().await {
+        Ok(txn) => Json(ApiResponse::success(BeginTransactionResponse { transaction_id: txn.id() })),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn commit_transaction(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(id): axum::extract::Path<TransactionId>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.read().await;
+
+    let result = match db.transaction(id).await {
+        Ok(txn) => txn.commit().await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => Json(ApiResponse::success("committed".to_string())),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+async fn rollback_transaction(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(id): axum::extract::Path<TransactionId>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.read().await;
+
+    let result = match db.transaction(id).await {
+        Ok(txn) => txn.rollback().await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => Json(ApiResponse::success("rolled back".to_string())),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
 async fn insert_vectors(
     State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
     axum::extract::Path(name): axum::extract::Path<String>,
     Json(req): Json<InsertVectorsRequest>,
 ) -> Json<ApiResponse<InsertVectorsResponse>> {
-    let db = state.db.read().await;
-    
     let vectors: Vec<(String, Vec<f32>, serde_json::Value)> = req.vectors
         .into_iter()
         .map(|v| (v.id, v.vector, v.metadata.unwrap_or(serde_json::json!({}))))
         .collect();
+
+    if let Some(shards) = sharded(&state, &name).await {
+        if headers.contains_key(TRANSACTION_HEADER) {
+            return Json(ApiResponse::error("Sharded collections cannot be written in a transaction"));
+        }
+        return match shards.insert(&name, vectors).await {
+            Ok(ids) => Json(ApiResponse::success(InsertVectorsResponse {
+                status: "ok".to_string(),
+                count: ids.len(),
+                ids,
+            })),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
+
+    if let Some(raft) = &state.raft {
+        if headers.contains_key(TRANSACTION_HEADER) {
+            return Json(ApiResponse::error("Transactions are not replicated through Raft"));
+        }
+        if let Err(e) = check_vectors(&*state.db.read().await, &name, &vectors).await {
+            return Json(ApiResponse::error(&e));
+        }
+        let ids: Vec<String> = vectors.iter().map(|(id, _, _)| id.clone()).collect();
+        return match commit_write(raft, WalEntryType::Insert, &name, vector_records(&vectors)).await {
+            Ok(()) => Json(ApiResponse::success(InsertVectorsResponse {
+                status: "ok".to_string(),
+                count: ids.len(),
+                ids,
+            })),
+            Err(e) => Json(ApiResponse::error(&e)),
+        };
+    }
+
+    let db = state.db.read().await;
+    let txn = match request_transaction(&db, &headers).await {
+        Ok(txn) => txn,
+        Err(e) => return Json(ApiResponse::error(&e)),
+    };
     
     let ids: Vec<String> = vectors.iter().map(|(id, _, _)| id.clone()).collect();
     
-    match db.insert_vectors(&name, vectors).await {
+    let result = match txn {
+        Some(txn) => txn.insert(&name, vectors).await,
+        None => db.insert_vectors(&name, vectors).await,
+    };
+    match result {
         Ok(inserted_ids) => Json(ApiResponse::success(InsertVectorsResponse {
             status: "ok".to_string(),
             ids: inserted_ids,
@@ -334,11 +1279,36 @@ async fn insert_vectors(
 
 async fn get_vector(
     State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
     axum::extract::Path((name, id)): axum::extract::Path<(String, String)>,
+    axum::extract::Query(query): axum::extract::Query<AsOfQuery>,
 ) -> Json<ApiResponse<GetVectorResponse>> {
+    if let Some(shards) = sharded(&state, &name).await {
+        if headers.contains_key(TRANSACTION_HEADER) || query.as_of().is_some() {
+            return Json(ApiResponse::error("Sharded collections cannot be read in a transaction or as of an earlier point"));
+        }
+        return match shards.get(&name, &id).await {
+            Ok(Some((vector, metadata))) => Json(ApiResponse::success(GetVectorResponse {
+                id,
+                vector,
+                metadata,
+            })),
+            Ok(None) => Json(ApiResponse::error("Vector not found")),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
+
     let db = state.db.read().await;
+    let txn = match request_transaction(&db, &headers).await {
+        Ok(txn) => txn,
+        Err(e) => return Json(ApiResponse::error(&e)),
+    };
     
-    match db.get_vector(&name, &id).await {
+    let result = match (txn, query.as_of()) {
+        (Some(txn), _) => txn.get_vector(&name, &id).await,
+        (None, as_of) => db.get_vector(&name, &id, as_of).await,
+    };
+    match result {
         Ok(Some((vector, metadata))) => Json(ApiResponse::success(GetVectorResponse {
             id,
             vector,
@@ -349,14 +1319,88 @@ async fn get_vector(
     }
 }
 
+async fn scan_vectors(
+    State(state): State<Arc<ApiState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    axum::extract::Query(query): axum::extract::Query<ScanVectorsQuery>,
+) -> Json<ApiResponse<ScanVectorsResponse>> {
+    let db = state.db.read().await;
+    let limit = query.limit.unwrap_or(1000).clamp(1, 10_000);
+
+    match db.scan_vectors(&name, query.after.as_deref(), limit).await {
+        Ok(vectors) => {
+            let next = if vectors.len() == limit {
+                vectors.last().map(|(id, _, _)| id.clone())
+            } else {
+                None
+            };
+
+            Json(ApiResponse::success(ScanVectorsResponse {
+                vectors: vectors.into_iter()
+                    .map(|(id, vector, metadata)| VectorItem { id, vector, metadata: Some(metadata) })
+                    .collect(),
+                next,
+            }))
+        }
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
 async fn delete_vectors(
     State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
     axum::extract::Path(name): axum::extract::Path<String>,
     Json(req): Json<DeleteVectorsRequest>,
 ) -> Json<ApiResponse<DeleteVectorsResponse>> {
+    if let Some(shards) = sharded(&state, &name).await {
+        if headers.contains_key(TRANSACTION_HEADER) {
+            return Json(ApiResponse::error("Sharded collections cannot be written in a transaction"));
+        }
+        return match shards.delete(&name, &req.ids).await {
+            Ok(count) => Json(ApiResponse::success(DeleteVectorsResponse {
+                status: "ok".to_string(),
+                deleted_count: count,
+            })),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
+
+    if let Some(raft) = &state.raft {
+        if headers.contains_key(TRANSACTION_HEADER) {
+            return Json(ApiResponse::error("Transactions are not replicated through Raft"));
+        }
+        let mut deleted_count = 0;
+        {
+            let db = state.db.read().await;
+            if let Err(e) = db.get_collection(&name).await {
+                return Json(ApiResponse::error(&e.to_string()));
+            }
+            for id in &req.ids {
+                if matches!(db.get_vector(&name, id, None).await, Ok(Some(_))) {
+                    deleted_count += 1;
+                }
+            }
+        }
+        return match commit_write(raft, WalEntryType::Delete, &name, serde_json::json!({ "ids": req.ids })).await {
+            Ok(()) => Json(ApiResponse::success(DeleteVectorsResponse {
+                status: "ok".to_string(),
+                deleted_count,
+            })),
+            Err(e) => Json(ApiResponse::error(&e)),
+        };
+    }
+
     let db = state.db.read().await;
+    let txn = match request_transaction(&db, &headers).await {
+        Ok(txn) => txn,
+        Err(e) => return Json(ApiResponse::error(&e)),
+    };
     
-    match db.delete_vectors(&name, &req.ids).await {
+    let result = match txn {
+        Some(txn) => txn.delete(&name, &req.ids).await,
+        None => db.delete_vectors(&name, &req.ids).await,
+    };
+    match result {
         Ok(count) => Json(ApiResponse::success(DeleteVectorsResponse {
             status: "ok".to_string(),
             deleted_count: count,
@@ -367,13 +1411,142 @@ async fn delete_vectors(
 
 async fn search(
     State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
     axum::extract::Path(name): axum::extract::Path<String>,
     Json(req): Json<SearchRequest>,
 ) -> Json<ApiResponse<SearchResponse>> {
     let start = std::time::Instant::now();
+
+    if let Some(shards) = sharded(&state, &name).await {
+        if headers.contains_key(TRANSACTION_HEADER) || req.as_of.as_of().is_some() || req.field.is_some() {
+            return Json(ApiResponse::error("Sharded collections cannot be searched in a transaction, as of an earlier point or by vector field"));
+        }
+        return match shards.search(&name, req.vector, req.k, req.filter).await {
+            Ok(result) => Json(ApiResponse::success(SearchResponse {
+                results: result.hits.into_iter()
+                    .map(|hit| SearchResultItem {
+                        id: hit.id,
+                        score: 1.0 - hit.distance,
+                        metadata: hit.metadata,
+                        tier: None,
+                    })
+                    .collect(),
+                execution_time_ms: start.elapsed().as_millis() as u64,
+                failed_shards: result.failed_shards,
+            })),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
+
     let db = state.db.read().await;
+
+    if let Some(field) = req.field.as_deref().filter(|field| *field != DEFAULT_VECTOR_FIELD) {
+        if headers.contains_key(TRANSACTION_HEADER) || req.as_of.as_of().is_some() {
+            return Json(ApiResponse::error("Vector fields cannot be searched in a transaction or as of an earlier point"));
+        }
+
+        let results = match db.search_field(&name, field, req.vector, req.k, req.filter).await {
+            Ok(results) => results,
+            Err(e) => return Json(ApiResponse::error(&e.to_string())),
+        };
+        let data_map = db.data.read().await;
+        let collection_data = data_map.get(&name);
+        return Json(ApiResponse::success(SearchResponse {
+            results: results.into_iter()
+                .map(|r| SearchResultItem {
+                    metadata: collection_data.and_then(|cd| cd.get(&r.id)).map(|(_, m)| m.clone()),
+                    id: r.id,
+                    score: 1.0 - r.distance,
+                    tier: None,
+                })
+                .collect(),
+            execution_time_ms: start.elapsed().as_millis() as u64,
+            failed_shards: Vec::new(),
+        }));
+    }
+
+    match request_transaction(&db, &headers).await {
+        Ok(Some(txn)) => {
+            let results = match txn.search(&name, req.vector, req.k, req.filter).await {
+                Ok(results) => results,
+                Err(e) => return Json(ApiResponse::error(&e.to_string())),
+            };
+
+            let mut items = Vec::with_capacity(results.len());
+            for r in results {
+                let metadata = txn.get_vector(&name, &r.id).await.ok().flatten().map(|(_, m)| m);
+                items.push(SearchResultItem {
+                    id: r.id,
+                    score: 1.0 - r.distance,
+                    metadata,
+                    tier: None,
+                });
+            }
+            return Json(ApiResponse::success(SearchResponse {
+                results: items,
+                execution_time_ms: start.elapsed().as_millis() as u64,
+                failed_shards: Vec::new(),
+            }));
+        }
+        Ok(None) => {}
+        Err(e) => return Json(ApiResponse::error(&e)),
+    }
+
+    if let Some(as_of) = req.as_of.as_of() {
+        let results = match db.search(&name, req.vector, req.k, req.filter, Some(as_of)).await {
+            Ok(results) => results,
+            Err(e) => return Json(ApiResponse::error(&e.to_string())),
+        };
+
+        let mut items = Vec::with_capacity(results.len());
+        for r in results {
+            let metadata = db.get_vector(&name, &r.id, Some(as_of)).await.ok().flatten().map(|(_, m)| m);
+            items.push(SearchResultItem {
+                id: r.id,
+                score: 1.0 - r.distance,
+                metadata,
+                tier: None,
+            });
+        }
+        return Json(ApiResponse::success(SearchResponse {
+            results: items,
+            execution_time_ms: start.elapsed().as_millis() as u64,
+            failed_shards: Vec::new(),
+        }));
+    }
+
+    if db.lakehouse.is_some() {
+        let options = TieredSearchOptions::new(req.k)
+            .with_cold(req.include_cold);
+        let options = match req.filter {
+            Some(filter) => options.with_filter(filter),
+            None => options,
+        };
+
+        return match db.search_tiered(&name, req.vector, &options).await {
+            Ok(results) => Json(ApiResponse::success(SearchResponse {
+                results: results.into_iter()
+                    .map(|r| SearchResultItem {
+                        id: r.id,
+                        score: 1.0 - r.distance,
+                        metadata: r.metadata,
+                        tier: Some(r.tier.as_str().to_string()),
+                    })
+                    .collect(),
+                execution_time_ms: start.elapsed().as_millis() as u64,
+                failed_shards: Vec::new(),
+            })),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
     
-    match db.search(&name, req.vector, req.k, req.filter).await {
+    let results = match &state.leader {
+        Some(leader) => leader.search(&name, req.vector, req.k, req.filter, req.read).await
+            .map(|routed| routed.results)
+            .map_err(|e| e.to_string()),
+        None => db.search(&name, req.vector, req.k, req.filter, None).await.map_err(|e| e.to_string()),
+    };
+    match results {
         Ok(results) => {
             let db_guard = state.db.read().await;
             let data_map = db_guard.data.read().await;
@@ -390,6 +1563,7 @@ async fn search(
                         id: r.id,
                         score: 1.0 - r.distance,
                         metadata,
+                        tier: None,
                     }
                 })
                 .collect();
@@ -399,8 +1573,104 @@ async fn search(
             Json(ApiResponse::success(SearchResponse {
                 results: search_results,
                 execution_time_ms: execution_time,
+                failed_shards: Vec::new(),
             }))
         }
+        Err(e) => Json(ApiResponse::error(&e)),
+    }
+}
+
+async fn sync(
+    State(state): State<Arc<ApiState>>,
+    Json(req): Json<SyncRequest>,
+) -> Json<ApiResponse<SyncResponse>> {
+    match state.sync.handle(req).await {
+        Ok(response) => Json(ApiResponse::success(response)),
+        Err(e) => Json(ApiResponse::error(&e.to_string())),
+    }
+}
+
+/// Applies what the replication leader ships, on a replica.
+struct ReplicaEndpoint {
+    node: ReplicaNode,
+    /// The leader presents it as a bearer token
+    token: String,
+}
+
+impl ReplicaEndpoint {
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let presented = headers.get(axum::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        // Compared in full so the time taken does not tell how much matched
+        presented.len() == self.token.len()
+            && presented.bytes().zip(self.token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+fn replication_routes(node: ReplicaNode, token: &str) -> Router {
+    Router::new()
+        .route("/api/replication/append", post(replication_append))
+        .route("/api/replication/snapshot", post(replication_snapshot))
+        .with_state(Arc::new(ReplicaEndpoint {
+            node,
+            token: token.to_string(),
+        }))
+}
+
+fn replication_unauthorized() -> (StatusCode, Json<ApiResponse<u64>>) {
+    (StatusCode::UNAUTHORIZED, Json(ApiResponse::error("Missing or wrong replication token")))
+}
+
+async fn replication_append(
+    State(endpoint): State<Arc<ReplicaEndpoint>>,
+    headers: HeaderMap,
+    Json(req): Json<ReplicationAppendRequest>,
+) -> (StatusCode, Json<ApiResponse<u64>>) {
+    if !endpoint.authorized(&headers) {
+        return replication_unauthorized();
+    }
+    match endpoint.node.append(req.entries).await {
+        Ok(lsn) => (StatusCode::OK, Json(ApiResponse::success(lsn))),
+        Err(e) => (StatusCode::OK, Json(ApiResponse::error(&e.to_string()))),
+    }
+}
+
+async fn replication_snapshot(
+    State(endpoint): State<Arc<ReplicaEndpoint>>,
+    headers: HeaderMap,
+    Json(snapshot): Json<ReplicaSnapshot>,
+) -> (StatusCode, Json<ApiResponse<u64>>) {
+    if !endpoint.authorized(&headers) {
+        return replication_unauthorized();
+    }
+    match endpoint.node.install_snapshot(snapshot).await {
+        Ok(lsn) => (StatusCode::OK, Json(ApiResponse::success(lsn))),
+        Err(e) => (StatusCode::OK, Json(ApiResponse::error(&e.to_string()))),
+    }
+}
+
+async fn lock_request(
+    State(locks): State<Arc<LockService>>,
+    Json(request): Json<LockRequest>,
+) -> Json<ApiResponse<Result<Lease, LockError>>> {
+    Json(ApiResponse::success(locks.handle(request).await))
+}
+
+async fn raft_request(
+    State(raft): State<Arc<RaftNode>>,
+    Json(request): Json<RaftRequest>,
+) -> Json<ApiResponse<RaftResponse>> {
+    Json(ApiResponse::success(raft.handle(request).await))
+}
+
+async fn two_phase_request(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<TwoPhaseRequest>,
+) -> Json<ApiResponse<TwoPhaseResponse>> {
+    match state.two_phase.handle(request).await {
+        Ok(response) => Json(ApiResponse::success(response)),
         Err(e) => Json(ApiResponse::error(&e.to_string())),
     }
 }
@@ -409,8 +1679,14 @@ async fn get_vectors_count(
     State(state): State<Arc<ApiState>>,
     axum::extract::Path(name): axum::extract::Path<String>,
 ) -> Json<ApiResponse<usize>> {
+    if let Some(shards) = sharded(&state, &name).await {
+        return match shards.count(&name).await {
+            Ok(count) => Json(ApiResponse::success(count)),
+            Err(e) => Json(ApiResponse::error(&e.to_string())),
+        };
+    }
+
     let db = state.db.read().await;
-    
     match db.get_vectors_count(&name).await {
         Ok(count) => Json(ApiResponse::success(count)),
         Err(e) => Json(ApiResponse::error(&e.to_string())),
@@ -431,7 +1707,12 @@ async fn get_collection_stats(
                 dimension: schema.dimension,
                 metric: format!("{:?}", schema.distance_metric),
                 vector_count: count,
-                index_type: "hnsw".to_string(),
+                index_type: match schema.indexes.first().map(|config| &config.index_type) {
+                    Some(IndexType::BruteForce) => "brute_force",
+                    Some(IndexType::IVF) => "ivf",
+                    Some(IndexType::Scalar) => "scalar",
+                    Some(IndexType::HNSW) | None => "hnsw",
+                }.to_string(),
             };
             Json(ApiResponse::success(stats))
         }
@@ -446,31 +1727,52 @@ async fn update_vectors(
 ) -> Json<ApiResponse<UpdateVectorsResponse>> {
     let db = state.db.read().await;
     
-    let mut updated_count = 0;
+    let mut updates = Vec::new();
     
     if let Some(vectors) = req.vectors {
         for (i, id) in req.ids.iter().enumerate() {
             if i < vectors.len() {
-                if let Ok(Some((_, metadata))) = db.get_vector(&name, id).await {
+                if let Ok(Some((_, metadata))) = db.get_vector(&name, id, None).await {
                     let new_vector = vectors[i].clone();
                     let new_metadata = req.metadata.as_ref().map(|m| m.get(i).cloned()).flatten().unwrap_or(metadata);
-                    
-                    let _ = db.delete_vectors(&name, &[id.clone()]).await;
-                    let _ = db.insert_vectors(&name, vec![(id.clone(), new_vector, new_metadata)]).await;
-                    updated_count += 1;
+                    updates.push((id.clone(), new_vector, new_metadata));
                 }
             }
         }
     } else if let Some(metadata) = req.metadata {
         for (i, id) in req.ids.iter().enumerate() {
-            if let Ok(Some((vector, _))) = db.get_vector(&name, id).await {
+            if let Ok(Some((vector, _))) = db.get_vector(&name, id, None).await {
                 let new_metadata = metadata.get(i).cloned().unwrap_or(serde_json::json!({}));
-                let _ = db.delete_vectors(&name, &[id.clone()]).await;
-                let _ = db.insert_vectors(&name, vec![(id.clone(), vector, new_metadata)]).await;
-                updated_count += 1;
+                updates.push((id.clone(), vector, new_metadata));
             }
         }
     }
+
+    if let Some(raft) = &state.raft {
+        if updates.is_empty() {
+            return Json(ApiResponse::success(UpdateVectorsResponse {
+                status: "ok".to_string(),
+                updated_count: 0,
+            }));
+        }
+        if let Err(e) = check_vectors(&db, &name, &updates).await {
+            return Json(ApiResponse::error(&e));
+        }
+        drop(db);
+        return match commit_write(raft, WalEntryType::Update, &name, vector_records(&updates)).await {
+            Ok(()) => Json(ApiResponse::success(UpdateVectorsResponse {
+                status: "ok".to_string(),
+                updated_count: updates.len(),
+            })),
+            Err(e) => Json(ApiResponse::error(&e)),
+        };
+    }
+
+    let updated_count = updates.len();
+    for (id, vector, metadata) in updates {
+        let _ = db.delete_vectors(&name, &[id.clone()]).await;
+        let _ = db.insert_vectors(&name, vec![(id, vector, metadata)]).await;
+    }
     
     Json(ApiResponse::success(UpdateVectorsResponse {
         status: "ok".to_string(),
@@ -489,7 +1791,7 @@ async fn batch_search(
     let mut all_results: Vec<Vec<SearchResultItem>> = Vec::new();
     
     for query in req.queries {
-        match db.search(&name, query, req.k, req.filter.clone()).await {
+        match db.search(&name, query, req.k, req.filter.clone(), None).await {
             Ok(results) => {
                 let data_lock = db.data.read().await;
                 let collection_data = data_lock.get(&name);
@@ -505,6 +1807,7 @@ async fn batch_search(
                             id: r.id,
                             score: 1.0 - r.distance,
                             metadata,
+                            tier: None,
                         }
                     })
                     .collect();
@@ -524,3 +1827,181 @@ async fn batch_search(
         execution_time_ms: execution_time,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+
+    async fn spawn_node(config: ApiConfig) -> tokio::task::JoinHandle<()> {
+        let health = format!("http://127.0.0.1:{}/health", config.port);
+        let server = tokio::spawn(async move {
+            start_server(config).await.unwrap();
+        });
+        while reqwest::get(&health).await.is_err() {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        server
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(request: reqwest::RequestBuilder, body: Option<serde_json::Value>) -> T {
+        let request = match body {
+            Some(body) => request.header(reqwest::header::CONTENT_TYPE, "application/json").body(body.to_string()),
+            None => request,
+        };
+        let bytes = request.send().await.unwrap().bytes().await.unwrap();
+        let response: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(response["error"].is_null(), "{}", response);
+        serde_json::from_value(response["data"].clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sharded_collections_are_served_and_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let ports = [free_port(), free_port()];
+        let configs: Vec<ApiConfig> = (0..2)
+            .map(|i| ApiConfig {
+                address: "127.0.0.1".to_string(),
+                port: ports[i],
+                node_id: format!("node{}", i),
+                peers: HashMap::from([(format!("node{}", 1 - i), format!("http://127.0.0.1:{}", ports[1 - i]))]),
+                db: DbConfig::new(dir.path().join(format!("node{}", i)).to_str().unwrap()),
+                durable: true,
+                sharding: Some(ShardsConfig::default()),
+                ..ApiConfig::default()
+            })
+            .collect();
+        let first = spawn_node(configs[0].clone()).await;
+        let _second = spawn_node(configs[1].clone()).await;
+
+        let client = reqwest::Client::new();
+        let url = |port: u16, path: &str| format!("http://127.0.0.1:{}/api/collections{}", port, path);
+        let _: CollectionInfo = call(client.post(url(ports[0], "")), Some(serde_json::json!({
+            "name": "docs",
+            "dimension": 3,
+            "sharding": { "Hash": { "shards": 4 } },
+        }))).await;
+        let vectors: Vec<serde_json::Value> = (0..20)
+            .map(|i| serde_json::json!({ "id": format!("v{:02}", i), "vector": [1.0, i as f32, 0.0], "metadata": { "n": i } }))
+            .collect();
+        let _: InsertVectorsResponse = call(client.post(url(ports[0], "/docs/vectors")), Some(serde_json::json!({ "vectors": vectors }))).await;
+
+        // Both nodes hold some of the shards
+        let on_second: Vec<String> = call(client.get(url(ports[1], "")), None).await;
+        assert!(on_second.iter().any(|name| name.starts_with("docs_shard_")));
+
+        let found: SearchResponse = call(client.post(url(ports[0], "/docs/search")), Some(serde_json::json!({ "vector": [1.0, 0.0, 0.0], "k": 3 }))).await;
+        assert_eq!(found.results[0].id, "v00");
+        assert!(found.failed_shards.is_empty());
+
+        first.abort();
+        let _ = first.await;
+        let _first = spawn_node(configs[0].clone()).await;
+
+        let count: usize = call(client.get(url(ports[0], "/docs/count")), None).await;
+        assert_eq!(count, 20);
+        let vector: GetVectorResponse = call(client.get(url(ports[0], "/docs/vectors/v07")), None).await;
+        assert_eq!(vector.metadata["n"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_inserted_vectors_are_tiered_by_the_lakehouse_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let port = free_port();
+        let db = DbConfig::new(dir.path().to_str().unwrap());
+        let metadata_path = std::path::Path::new(&db.data_dir).join("lakehouse/metadata.json");
+        spawn_node(ApiConfig {
+            address: "127.0.0.1".to_string(),
+            port,
+            db,
+            // No room in the hot tier, so every pass moves documents to warm
+            lakehouse: Some(LakehouseConfig {
+                tiers: TierConfig { max_hot_size_gb: 0, ..TierConfig::default() },
+                tiering: TieringDaemonConfig { interval_secs: 1, ..TieringDaemonConfig::default() },
+            }),
+            ..ApiConfig::default()
+        }).await;
+
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://127.0.0.1:{}/api/collections{}", port, path);
+        let _: CollectionInfo = call(client.post(url("")), Some(serde_json::json!({ "name": "docs", "dimension": 2 }))).await;
+        let vectors = serde_json::json!({ "vectors": [{ "id": "a", "vector": [1.0, 0.0] }, { "id": "b", "vector": [0.0, 1.0] }] });
+        let _: InsertVectorsResponse = call(client.post(url("/docs/vectors")), Some(vectors)).await;
+
+        loop {
+            if let Ok(data) = tokio::fs::read(&metadata_path).await {
+                let table: HashMap<String, crate::DocumentMeta> = serde_json::from_slice(&data).unwrap();
+                if table.len() == 2 && table.values().all(|meta| meta.tier == crate::StorageTier::Warm) {
+                    break;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raft_writes_commit_through_the_leader() {
+        let dir = tempfile::tempdir().unwrap();
+        let ports = [free_port(), free_port()];
+        for i in 0..2 {
+            spawn_node(ApiConfig {
+                address: "127.0.0.1".to_string(),
+                port: ports[i],
+                node_id: format!("node{}", i),
+                peers: HashMap::from([(format!("node{}", 1 - i), format!("http://127.0.0.1:{}", ports[1 - i]))]),
+                db: DbConfig::new(dir.path().join(format!("node{}", i)).to_str().unwrap()),
+                raft: true,
+                ..ApiConfig::default()
+            }).await;
+        }
+
+        let client = reqwest::Client::new();
+        let url = |port: u16, path: &str| format!("http://127.0.0.1:{}/api/collections{}", port, path);
+        let post = |port: u16, path: &str, body: serde_json::Value| client.post(url(port, path))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send();
+
+        // Only the leader takes the write, once one is elected
+        let create = serde_json::json!({ "name": "docs", "dimension": 2 });
+        let leader = 'elected: loop {
+            for port in ports {
+                let bytes = post(port, "", create.clone()).await.unwrap().bytes().await.unwrap();
+                let response: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+                if response["error"].is_null() {
+                    break 'elected port;
+                }
+                assert!(response["error"].as_str().unwrap().contains("leader"), "{}", response);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        };
+        let follower = ports.into_iter().find(|port| *port != leader).unwrap();
+
+        let vectors = serde_json::json!({ "vectors": [{ "id": "a", "vector": [1.0, 0.0], "metadata": { "n": 1 } }] });
+        let _: InsertVectorsResponse = call(client.post(url(leader, "/docs/vectors")), Some(vectors.clone())).await;
+
+        let bytes = post(follower, "/docs/vectors", vectors).await.unwrap().bytes().await.unwrap();
+        let response: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(response["error"].as_str().unwrap().contains("Not the leader"), "{}", response);
+
+        let wrong_dimension = serde_json::json!({ "vectors": [{ "id": "b", "vector": [1.0, 0.0, 0.0] }] });
+        let bytes = post(leader, "/docs/vectors", wrong_dimension).await.unwrap().bytes().await.unwrap();
+        let response: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(response["error"].as_str().unwrap().contains("dimension"), "{}", response);
+
+        // The follower applies the insert once the leader's next heartbeat
+        // tells it the entry committed
+        loop {
+            let bytes = client.get(url(follower, "/docs/vectors/a")).send().await.unwrap().bytes().await.unwrap();
+            let response: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            if response["error"].is_null() {
+                assert_eq!(response["data"]["metadata"]["n"], 1);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+}
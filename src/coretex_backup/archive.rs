@@ -0,0 +1,514 @@
+//! Backup archive format
+//! Packs a backup directory into a single tar archive, optionally compressed
+//! with zstd and encrypted with AES-256-GCM. Archives are streamed to and
+//! from disk, so a backup is never held in memory whole.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use super::BackupError;
+use crate::coretex_security::{KeyManager, VaultKMS};
+
+const ARCHIVE_MAGIC: &[u8; 8] = b"CTXBAK2\n";
+/// Archives encrypted in one piece, before payloads were sealed in chunks
+const LEGACY_MAGIC: &[u8; 8] = b"CTXBAK1\n";
+/// Plaintext bytes per sealed chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+/// Random part of each chunk's nonce; the rest is the chunk counter and a
+/// flag marking the last chunk
+const NONCE_PREFIX_SIZE: usize = 7;
+/// Headers are a few hundred bytes; anything far larger is corruption
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+#[cfg(feature = "compression")]
+const ZSTD_LEVEL: i32 = 3;
+
+pub const ARCHIVE_EXTENSION: &str = "ctxbak";
+
+/// Key material used to encrypt a backup archive.
+#[derive(Clone)]
+pub struct BackupKey {
+    pub id: String,
+    pub material: Vec<u8>,
+}
+
+impl BackupKey {
+    /// Identifies the key material without revealing it, so restores can tell
+    /// a wrong key apart from a tampered archive.
+    pub fn fingerprint(&self) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.material)
+            .expect("HMAC can take key of any size");
+        mac.update(b"coretex_backup_key");
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+/// Supplies encryption keys for backup archives.
+#[async_trait]
+pub trait BackupKeySource: Send + Sync {
+    /// Key used to encrypt new backups
+    async fn current_key(&self) -> Result<BackupKey, BackupError>;
+    /// Key recorded in an existing backup
+    async fn key(&self, key_id: &str) -> Result<BackupKey, BackupError>;
+}
+
+#[async_trait]
+impl BackupKeySource for KeyManager {
+    async fn current_key(&self) -> Result<BackupKey, BackupError> {
+        let key = self.get_primary_key().await
+            .ok_or_else(|| BackupError::EncryptionError("no primary key available".to_string()))?;
+        Ok(BackupKey { id: key.id, material: key.key })
+    }
+
+    async fn key(&self, key_id: &str) -> Result<BackupKey, BackupError> {
+        let key = self.get_key(key_id).await
+            .ok_or_else(|| BackupError::KeyMismatch(format!("key '{}' not found", key_id)))?;
+        Ok(BackupKey { id: key.id, material: key.key })
+    }
+}
+
+#[async_trait]
+impl BackupKeySource for VaultKMS {
+    async fn current_key(&self) -> Result<BackupKey, BackupError> {
+        let key_id = self.config().key_id.clone().unwrap_or_else(|| "default".to_string());
+        self.key(&key_id).await
+    }
+
+    async fn key(&self, key_id: &str) -> Result<BackupKey, BackupError> {
+        let key = self.get_key(key_id).await
+            .map_err(|e| BackupError::KeyMismatch(format!("key '{}': {}", key_id, e)))?;
+        if !key.enabled {
+            return Err(BackupError::KeyMismatch(format!("key '{}' is disabled", key_id)));
+        }
+        Ok(BackupKey { id: key.id, material: key.key })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveHeader {
+    pub compression: Option<String>,
+    pub encryption: Option<ArchiveEncryption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEncryption {
+    pub algorithm: String,
+    pub key_id: String,
+    pub key_fingerprint: String,
+    pub nonce: Vec<u8>,
+}
+
+/// Packs a directory into an archive at `dest`.
+///
+/// Layout: magic, u32 LE header length, JSON header, payload. When encrypted
+/// the payload is sealed in chunks of `CHUNK_SIZE` bytes, each under a nonce
+/// made of the header's nonce prefix, the chunk's u32 BE counter and a byte
+/// marking the last chunk, with the header as associated data; a reordered
+/// or truncated archive fails authentication.
+pub fn pack_dir(src: &Path, dest: &Path, compress: bool, key: Option<&BackupKey>) -> Result<ArchiveHeader, BackupError> {
+    pack_with(dest, compress, key, |out| {
+        let mut builder = tar::Builder::new(out);
+        builder.append_dir_all(".", src)?;
+        builder.finish()
+    })
+}
+
+/// Packs a single file, e.g. an archived WAL segment, in the same layout as
+/// `pack_dir`.
+pub fn pack_file(src: &Path, dest: &Path, compress: bool, key: Option<&BackupKey>) -> Result<ArchiveHeader, BackupError> {
+    pack_with(dest, compress, key, |out| {
+        io::copy(&mut File::open(src)?, out)?;
+        Ok(())
+    })
+}
+
+/// Packs bytes already in memory in the same layout as `pack_dir`.
+pub fn pack_bytes(payload: &[u8], dest: &Path, compress: bool, key: Option<&BackupKey>) -> Result<ArchiveHeader, BackupError> {
+    pack_with(dest, compress, key, |out| out.write_all(payload))
+}
+
+/// Streams what `write_payload` writes through the compressor and cipher
+/// into a temporary file, which replaces `dest` once it is on disk.
+fn pack_with(
+    dest: &Path,
+    compress: bool,
+    key: Option<&BackupKey>,
+    write_payload: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<ArchiveHeader, BackupError> {
+    if compress && !cfg!(feature = "compression") {
+        return Err(BackupError::CompressionError("built without the `compression` feature".to_string()));
+    }
+
+    let mut header = ArchiveHeader {
+        compression: compress.then(|| "zstd".to_string()),
+        encryption: None,
+    };
+    let mut prefix = [0u8; NONCE_PREFIX_SIZE];
+    if let Some(key) = key {
+        OsRng.fill_bytes(&mut prefix);
+        header.encryption = Some(ArchiveEncryption {
+            algorithm: "AES-256-GCM".to_string(),
+            key_id: key.id.clone(),
+            key_fingerprint: key.fingerprint(),
+            nonce: prefix.to_vec(),
+        });
+    }
+    let header_bytes = serde_json::to_vec(&header)
+        .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+    let seal = match key {
+        Some(key) => Some(Seal::new(cipher(key)?, prefix, header_bytes.clone())),
+        None => None,
+    };
+
+    let mut tmp_name = dest.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = dest.with_file_name(tmp_name);
+
+    let written = (|| -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(ARCHIVE_MAGIC)?;
+        out.write_all(&(header_bytes.len() as u32).to_le_bytes())?;
+        out.write_all(&header_bytes)?;
+
+        let mut payload = Compressor::new(Sealer { out, seal, buffer: Vec::new() }, compress)?;
+        write_payload(&mut payload)?;
+        let out = payload.finish()?.finish()?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&tmp, dest)?;
+        crate::coretex_journal::sync_parent(dest)
+    })();
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(BackupError::IoError(e.to_string()));
+    }
+
+    Ok(header)
+}
+
+/// Reads an archive's header without touching its payload.
+pub fn read_header(path: &Path) -> Result<ArchiveHeader, BackupError> {
+    let mut input = BufReader::new(File::open(path).map_err(|e| BackupError::IoError(e.to_string()))?);
+    let (_, header, _) = read_header_from(&mut input)?;
+    Ok(header)
+}
+
+/// Returns whether the archive is in the legacy layout, its header, and the
+/// header's bytes as they were bound to the ciphertext.
+fn read_header_from(input: &mut impl Read) -> Result<(bool, ArchiveHeader, Vec<u8>), BackupError> {
+    let corrupt = || BackupError::IntegrityError("archive header is corrupt".to_string());
+
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic).map_err(|_| corrupt())?;
+    let legacy = match &magic {
+        ARCHIVE_MAGIC => false,
+        LEGACY_MAGIC => true,
+        _ => return Err(corrupt()),
+    };
+
+    let mut len = [0u8; 4];
+    input.read_exact(&mut len).map_err(|_| corrupt())?;
+    let header_len = u32::from_le_bytes(len) as usize;
+    if header_len > MAX_HEADER_SIZE {
+        return Err(corrupt());
+    }
+
+    let mut header_bytes = vec![0u8; header_len];
+    input.read_exact(&mut header_bytes).map_err(|_| corrupt())?;
+    let header: ArchiveHeader = serde_json::from_slice(&header_bytes).map_err(|_| corrupt())?;
+
+    Ok((legacy, header, header_bytes))
+}
+
+/// Verifies, decrypts and unpacks an archive into `dest`. `key` must be the key
+/// named in the archive header when the archive is encrypted. Files are
+/// written as their chunks authenticate, so unpack into a staging directory.
+pub fn unpack_to_dir(archive: &Path, key: Option<&BackupKey>, dest: &Path) -> Result<(), BackupError> {
+    let mut payload = open_payload(archive, key)?;
+
+    tar::Archive::new(&mut payload)
+        .unpack(dest)
+        .map_err(|e| BackupError::IntegrityError(format!("cannot unpack archive: {}", e)))?;
+    // The tar ends before the padding after it; the last chunk still has to
+    // authenticate
+    io::copy(&mut payload, &mut io::sink()).map_err(read_error)?;
+    Ok(())
+}
+
+/// Verifies and decrypts an archive back into the bytes it was packed from.
+pub fn unpack_bytes(archive: &Path, key: Option<&BackupKey>) -> Result<Vec<u8>, BackupError> {
+    let mut content = Vec::new();
+    open_payload(archive, key)?.read_to_end(&mut content).map_err(read_error)?;
+    Ok(content)
+}
+
+/// Opens an archive's payload for reading, decrypted and decompressed on the
+/// way. Reads fail with `InvalidData` at the first chunk that does not
+/// authenticate.
+fn open_payload(path: &Path, key: Option<&BackupKey>) -> Result<Box<dyn Read + Send>, BackupError> {
+    let mut input = BufReader::new(File::open(path).map_err(|e| BackupError::IoError(e.to_string()))?);
+    let (legacy, header, header_bytes) = read_header_from(&mut input)?;
+
+    let payload: Box<dyn Read + Send> = match &header.encryption {
+        None => Box::new(input),
+        Some(encryption) => {
+            let key = key.ok_or_else(|| BackupError::KeyMismatch(format!("key '{}' required", encryption.key_id)))?;
+            if key.fingerprint() != encryption.key_fingerprint {
+                return Err(BackupError::KeyMismatch(format!(
+                    "key '{}' does not match the key this backup was encrypted with", key.id
+                )));
+            }
+
+            if legacy {
+                if encryption.nonce.len() != 12 {
+                    return Err(BackupError::IntegrityError("invalid nonce length".to_string()));
+                }
+                let mut sealed = Vec::new();
+                input.read_to_end(&mut sealed).map_err(|e| BackupError::IoError(e.to_string()))?;
+                let content = cipher(key)?
+                    .decrypt(Nonce::from_slice(&encryption.nonce), Payload { msg: &sealed, aad: &header_bytes })
+                    .map_err(|_| BackupError::IntegrityError("archive failed authentication".to_string()))?;
+                Box::new(io::Cursor::new(content))
+            } else {
+                let prefix: [u8; NONCE_PREFIX_SIZE] = encryption.nonce.as_slice().try_into()
+                    .map_err(|_| BackupError::IntegrityError("invalid nonce length".to_string()))?;
+                Box::new(Opener {
+                    input,
+                    seal: Seal::new(cipher(key)?, prefix, header_bytes),
+                    chunk: Vec::new(),
+                    pos: 0,
+                    done: false,
+                })
+            }
+        }
+    };
+
+    match header.compression.as_deref() {
+        Some("zstd") => decompressed(payload),
+        Some(other) => Err(BackupError::CompressionError(format!("unsupported compression '{}'", other))),
+        None => Ok(payload),
+    }
+}
+
+fn read_error(e: io::Error) -> BackupError {
+    match e.kind() {
+        io::ErrorKind::InvalidData => BackupError::IntegrityError(e.to_string()),
+        _ => BackupError::IoError(e.to_string()),
+    }
+}
+
+fn cipher(key: &BackupKey) -> Result<Aes256Gcm, BackupError> {
+    if key.material.len() != 32 {
+        return Err(BackupError::EncryptionError(format!(
+            "key '{}' must be 256 bits, got {}", key.id, key.material.len() * 8
+        )));
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.material)))
+}
+
+/// Seals and opens the chunks of one archive, in order.
+struct Seal {
+    cipher: Aes256Gcm,
+    prefix: [u8; NONCE_PREFIX_SIZE],
+    header: Vec<u8>,
+    counter: u32,
+}
+
+impl Seal {
+    fn new(cipher: Aes256Gcm, prefix: [u8; NONCE_PREFIX_SIZE], header: Vec<u8>) -> Self {
+        Self { cipher, prefix, header, counter: 0 }
+    }
+
+    fn next_nonce(&mut self, last: bool) -> io::Result<[u8; 12]> {
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_SIZE].copy_from_slice(&self.prefix);
+        nonce[NONCE_PREFIX_SIZE..11].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[11] = last as u8;
+        self.counter = self.counter.checked_add(1)
+            .ok_or_else(|| io::Error::other("archive has too many chunks"))?;
+        Ok(nonce)
+    }
+
+    fn seal(&mut self, chunk: &[u8], last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce(last)?;
+        self.cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: chunk, aad: &self.header })
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    fn open(&mut self, sealed: &[u8], last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce(last)?;
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: sealed, aad: &self.header })
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "archive failed authentication"))
+    }
+}
+
+/// Encrypts what is written to it chunk by chunk, or passes it through
+/// without a seal. `finish` seals the last chunk.
+struct Sealer<W: Write> {
+    out: W,
+    seal: Option<Seal>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Sealer<W> {
+    fn finish(mut self) -> io::Result<W> {
+        if let Some(seal) = &mut self.seal {
+            let sealed = seal.seal(&self.buffer, true)?;
+            self.out.write_all(&sealed)?;
+        }
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> Write for Sealer<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let Some(seal) = &mut self.seal else {
+            return self.out.write(data);
+        };
+        self.buffer.extend_from_slice(data);
+        // Only a chunk with more data after it is known not to be the last
+        while self.buffer.len() > CHUNK_SIZE {
+            let sealed = seal.seal(&self.buffer[..CHUNK_SIZE], false)?;
+            self.out.write_all(&sealed)?;
+            self.buffer.drain(..CHUNK_SIZE);
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Decrypts a sealed payload chunk by chunk as it is read.
+struct Opener<R: BufRead> {
+    input: R,
+    seal: Seal,
+    chunk: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Opener<R> {
+    fn next_chunk(&mut self) -> io::Result<()> {
+        let mut sealed = Vec::with_capacity(CHUNK_SIZE + TAG_SIZE);
+        (&mut self.input).take((CHUNK_SIZE + TAG_SIZE) as u64).read_to_end(&mut sealed)?;
+        let last = sealed.len() < CHUNK_SIZE + TAG_SIZE || self.input.fill_buf()?.is_empty();
+        self.chunk = self.seal.open(&sealed, last)?;
+        self.pos = 0;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Opener<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Compresses what is written to it, or passes it through.
+enum Compressor<W: Write> {
+    Plain(W),
+    #[cfg(feature = "compression")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> Compressor<W> {
+    fn new(out: W, compress: bool) -> io::Result<Self> {
+        #[cfg(feature = "compression")]
+        if compress {
+            return Ok(Self::Zstd(zstd::stream::write::Encoder::new(out, ZSTD_LEVEL)?));
+        }
+        // `pack_with` refuses to compress without the feature
+        let _ = compress;
+        Ok(Self::Plain(out))
+    }
+
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Plain(out) => Ok(out),
+            #[cfg(feature = "compression")]
+            Self::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(out) => out.write(data),
+            #[cfg(feature = "compression")]
+            Self::Zstd(encoder) => encoder.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(out) => out.flush(),
+            #[cfg(feature = "compression")]
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "compression")]
+fn decompressed(payload: Box<dyn Read + Send>) -> Result<Box<dyn Read + Send>, BackupError> {
+    let decoder = zstd::stream::read::Decoder::new(payload)
+        .map_err(|e| BackupError::CompressionError(e.to_string()))?;
+    Ok(Box::new(decoder))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompressed(_payload: Box<dyn Read + Send>) -> Result<Box<dyn Read + Send>, BackupError> {
+    Err(BackupError::CompressionError("built without the `compression` feature".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> BackupKey {
+        BackupKey { id: "backup-key".to_string(), material: vec![7; 32] }
+    }
+
+    #[test]
+    fn test_sealed_archive_roundtrip_and_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("segment.sealed");
+        // Whole chunks only, so the last chunk is an empty one
+        let payload: Vec<u8> = (0..3 * CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+
+        pack_bytes(&payload, &path, false, Some(&key())).unwrap();
+        assert!(!dir.path().join("segment.sealed.tmp").exists());
+        assert_eq!(unpack_bytes(&path, Some(&key())).unwrap(), payload);
+
+        let other = BackupKey { id: "backup-key".to_string(), material: vec![8; 32] };
+        assert!(matches!(unpack_bytes(&path, Some(&other)), Err(BackupError::KeyMismatch(_))));
+
+        // Dropping the last chunk leaves whole chunks, none of them sealed as the last
+        let archive = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &archive[..archive.len() - TAG_SIZE]).unwrap();
+        assert!(matches!(unpack_bytes(&path, Some(&key())), Err(BackupError::IntegrityError(_))));
+    }
+}
@@ -0,0 +1,1767 @@
+//! Backup and recovery module for CoreTexDB
+//! Provides comprehensive backup, restore, and disaster recovery capabilities
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::coretex_persistence::{PersistenceConfig, PersistenceManager, StorageBackend};
+use crate::coretex_utils::wal::{WalEntry, WalEntryType, WriteAheadLog};
+
+pub mod archive;
+pub mod remote;
+pub mod retention;
+pub mod scheduler;
+
+pub use archive::{BackupKey, BackupKeySource};
+pub use remote::{InMemoryObjectStore, ObjectInfo, ObjectStore, RemoteBackupConfig, UploadReport, UploadedPart};
+pub use retention::RetentionPolicy;
+pub use scheduler::{BackupScheduler, CronSchedule, SchedulerStats};
+
+/// Backup files are hashed this much at a time.
+const CHECKSUM_CHUNK_SIZE: usize = 1024 * 1024;
+/// File of an incremental backup holding its journal entries.
+const INCREMENT_JOURNAL: &str = "journal.log";
+
+/// Numbers backups taken by this process, for unique backup ids.
+static BACKUP_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConfig {
+    pub backup_dir: String,
+    pub retention_days: i32,
+    pub incremental_enabled: bool,
+    pub compression_enabled: bool,
+    pub encryption_enabled: bool,
+    pub schedule: BackupSchedule,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    /// Object-store target; used once a store is attached with `with_object_store`
+    #[serde(default)]
+    pub remote: Option<RemoteBackupConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BackupSchedule {
+    Manual,
+    Hourly,
+    Daily,
+    Weekly,
+    /// Five-field cron expression, e.g. `"30 2 * * 1-5"`
+    Cron(String),
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            backup_dir: "./backups".to_string(),
+            retention_days: 30,
+            incremental_enabled: true,
+            compression_enabled: true,
+            encryption_enabled: false,
+            schedule: BackupSchedule::Daily,
+            retention: RetentionPolicy::default(),
+            remote: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupMetadata {
+    pub id: String,
+    pub name: String,
+    pub backup_type: BackupType,
+    pub created_at: i64,
+    pub size_bytes: u64,
+    pub collection_count: usize,
+    pub vector_count: u64,
+    pub checksum: String,
+    pub parent_backup_id: Option<String>,
+    pub status: BackupStatus,
+    #[serde(default)]
+    pub compressed: bool,
+    /// Id of the key the archive is encrypted with, if any
+    #[serde(default)]
+    pub encryption_key_id: Option<String>,
+    /// Last journal entry written before the copy finished, so nothing
+    /// after it is in the backup; `None` without a WAL directory
+    #[serde(default)]
+    pub last_lsn: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupType {
+    Full,
+    /// The journal since its parent backup, replayed on top of it
+    Incremental,
+    Snapshot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupStatus {
+    InProgress,
+    Completed,
+    Failed,
+}
+
+pub struct BackupManager {
+    config: BackupConfig,
+    backups: Arc<RwLock<HashMap<String, BackupMetadata>>>,
+    data_dir: String,
+    key_source: Option<Arc<dyn BackupKeySource>>,
+    object_store: Option<Arc<dyn ObjectStore>>,
+    wal_dir: Option<String>,
+}
+
+impl BackupManager {
+    pub fn new(config: BackupConfig, data_dir: &str) -> Self {
+        Self {
+            config,
+            backups: Arc::new(RwLock::new(HashMap::new())),
+            data_dir: data_dir.to_string(),
+            key_source: None,
+            object_store: None,
+            wal_dir: None,
+        }
+    }
+
+    /// Keys for `encryption_enabled`, e.g. a `KeyManager` or `VaultKMS`.
+    pub fn with_key_source(mut self, key_source: Arc<dyn BackupKeySource>) -> Self {
+        self.key_source = Some(key_source);
+        self
+    }
+
+    /// Remote target for `upload_backup` and `download_backup`, e.g. S3, MinIO or
+    /// an `InMemoryObjectStore`.
+    pub fn with_object_store(mut self, object_store: Arc<dyn ObjectStore>) -> Self {
+        self.object_store = Some(object_store);
+        self
+    }
+
+    /// The database's journal directory; `create_backup` archives its
+    /// segments so restores can replay past the backup.
+    pub fn with_wal_dir(mut self, wal_dir: &str) -> Self {
+        self.wal_dir = Some(wal_dir.to_string());
+        self
+    }
+
+    pub fn config(&self) -> &BackupConfig {
+        &self.config
+    }
+
+    pub async fn initialize(&self) -> Result<(), BackupError> {
+        let backup_dir = PathBuf::from(&self.config.backup_dir);
+        
+        if !backup_dir.exists() {
+            fs::create_dir_all(&backup_dir)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        self.load_backup_metadata().await?;
+
+        Ok(())
+    }
+
+    async fn load_backup_metadata(&self) -> Result<(), BackupError> {
+        let metadata_file = PathBuf::from(&self.config.backup_dir).join("backups.json");
+        
+        if metadata_file.exists() {
+            let content = fs::read_to_string(&metadata_file)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+            
+            let metadata: Vec<BackupMetadata> = serde_json::from_str(&content)
+                .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+            
+            let mut backups = self.backups.write().await;
+            for backup in metadata {
+                backups.insert(backup.id.clone(), backup);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn save_backup_metadata(&self) -> Result<(), BackupError> {
+        let backups = self.backups.read().await;
+        let metadata: Vec<&BackupMetadata> = backups.values().collect();
+        
+        let content = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+        
+        // Replaced whole, so a crash leaves the old catalog or the new one
+        let metadata_file = PathBuf::from(&self.config.backup_dir).join("backups.json");
+        tokio::task::spawn_blocking(move || crate::coretex_journal::write_durably(&metadata_file, content.as_bytes()))
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?
+            .map_err(|e| BackupError::IoError(e.to_string()))
+    }
+
+    pub async fn create_backup(&self, name: &str, backup_type: BackupType) -> Result<String, BackupError> {
+        // The sequence keeps backups of one name taken in the same
+        // millisecond apart
+        let backup_id = format!("backup_{}_{}_{}",
+            name.replace(" ", "_"),
+            chrono::Utc::now().timestamp_millis(),
+            BACKUP_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        );
+
+        // Resolve the key up front so a missing key never leaves a plaintext copy behind
+        let key = self.encryption_key().await?;
+        let compress = self.config.compression_enabled && cfg!(feature = "compression");
+
+        let backup_dir = PathBuf::from(&self.config.backup_dir).join(&backup_id);
+        
+        fs::create_dir_all(&backup_dir)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        // Without a journal reaching back to a parent, an incremental backup
+        // has nothing to build on and a full one is taken instead
+        let increment = match backup_type {
+            BackupType::Incremental => self.journal_since_latest().await?,
+            _ => None,
+        };
+        let backup_type = match &increment {
+            None if backup_type == BackupType::Incremental => BackupType::Full,
+            _ => backup_type,
+        };
+        let parent_backup_id = increment.as_ref().map(|increment| increment.parent.clone());
+
+        let metadata = BackupMetadata {
+            id: backup_id.clone(),
+            name: name.to_string(),
+            backup_type,
+            created_at: chrono::Utc::now().timestamp(),
+            size_bytes: 0,
+            collection_count: 0,
+            vector_count: 0,
+            checksum: String::new(),
+            parent_backup_id,
+            status: BackupStatus::InProgress,
+            compressed: compress,
+            encryption_key_id: key.as_ref().map(|k| k.id.clone()),
+            last_lsn: None,
+        };
+
+        {
+            let mut backups = self.backups.write().await;
+            backups.insert(backup_id.clone(), metadata);
+        }
+
+        let copied = match &increment {
+            Some(increment) => {
+                let journal = backup_dir.join(INCREMENT_JOURNAL);
+                WriteAheadLog::write_segment(&journal, &increment.entries)
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+                Ok((Self::dir_size(&backup_dir).await, 0, Some(increment.last_lsn)))
+            }
+            None => self.copy_data(&backup_dir).await,
+        };
+        let (total_size, collection_count, last_lsn) = match copied {
+            Ok(copied) => copied,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&backup_dir).await;
+                self.backups.write().await.remove(&backup_id);
+                return Err(e);
+            }
+        };
+        if let Some(wal_dir) = &self.wal_dir {
+            self.archive_wal(&WriteAheadLog::new(wal_dir)).await?;
+        }
+
+        let checksum = if compress || key.is_some() {
+            let archive_path = self.archive_path(&backup_id);
+            if let Err(e) = Self::write_archive(&backup_dir, &archive_path, compress, key).await {
+                let _ = fs::remove_dir_all(&backup_dir).await;
+                self.backups.write().await.remove(&backup_id);
+                return Err(e);
+            }
+            fs::remove_dir_all(&backup_dir)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+            self.calculate_checksum(&archive_path).await?
+        } else {
+            self.calculate_checksum(&backup_dir).await?
+        };
+
+        {
+            let mut backups = self.backups.write().await;
+            if let Some(backup) = backups.get_mut(&backup_id) {
+                backup.size_bytes = total_size;
+                backup.collection_count = collection_count;
+                backup.checksum = checksum;
+                backup.last_lsn = last_lsn;
+                backup.status = BackupStatus::Completed;
+            }
+        }
+
+        self.save_backup_metadata().await?;
+        self.apply_retention().await?;
+
+        Ok(backup_id)
+    }
+
+    /// Copies the collections, config and index into `backup_dir`. Returns
+    /// the bytes and collections copied and the last journal entry written
+    /// by the time the copy finished.
+    async fn copy_data(&self, backup_dir: &PathBuf) -> Result<(u64, usize, Option<u64>), BackupError> {
+        let collections_dir = PathBuf::from(&self.data_dir).join("collections");
+        
+        let mut total_size: u64 = 0;
+        let mut collection_count = 0;
+        
+        if collections_dir.exists() {
+            let mut entries = fs::read_dir(&collections_dir)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+            
+            while let Some(entry) = entries.next_entry().await
+                .map_err(|e| BackupError::IoError(e.to_string()))?
+            {
+                let path = entry.path();
+                if path.is_dir() {
+                    let collection_name = path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string();
+                    
+                    let dest = backup_dir.join("collections").join(&collection_name);
+                    Self::copy_dir(&path, &dest).await?;
+                    
+                    let size = Self::dir_size(&dest).await;
+                    total_size += size;
+                    collection_count += 1;
+                }
+            }
+        }
+
+        let config_file = PathBuf::from(&self.data_dir).join("config");
+        if config_file.exists() {
+            let dest = backup_dir.join("config");
+            fs::copy(&config_file, &dest)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        let index_dir = PathBuf::from(&self.data_dir).join("index");
+        if index_dir.exists() {
+            let dest = backup_dir.join("index");
+            Self::copy_dir(&index_dir, &dest).await?;
+        }
+
+        Ok((total_size, collection_count, self.journal_lsn().await?))
+    }
+
+    /// The journal since the latest completed backup, if the live journal
+    /// still holds all of it.
+    async fn journal_since_latest(&self) -> Result<Option<Increment>, BackupError> {
+        let (Some(wal_dir), Some(journal_lsn)) = (&self.wal_dir, self.journal_lsn().await?) else {
+            return Ok(None);
+        };
+        let Some(parent) = self.latest_completed_backup().await else {
+            return Ok(None);
+        };
+        // Past the journal when a restore went back before the parent
+        let Some(parent_lsn) = parent.last_lsn.filter(|lsn| *lsn <= journal_lsn) else {
+            return Ok(None);
+        };
+
+        let mut entries = Vec::new();
+        let segments = WriteAheadLog::new(wal_dir).segment_files()
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+        for segment in &segments {
+            entries.extend(WriteAheadLog::read_segment(segment)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?);
+        }
+        entries.sort_by_key(|e| e.id);
+        entries.dedup_by_key(|e| e.id);
+
+        // A full copy may have started before entries it does not hold; as
+        // in `restore_to_target`, replaying those again is harmless
+        let full_parent = parent.backup_type != BackupType::Incremental;
+        entries.retain(|e| e.id > parent_lsn || (full_parent && e.timestamp as i64 >= parent.created_at));
+
+        // Entries a checkpoint already dropped from the journal are gone
+        let mut last_lsn = parent_lsn;
+        for entry in entries.iter().filter(|e| e.id > parent_lsn) {
+            if entry.id != last_lsn + 1 {
+                return Ok(None);
+            }
+            last_lsn = entry.id;
+        }
+        if last_lsn < journal_lsn {
+            return Ok(None);
+        }
+
+        Ok(Some(Increment { parent: parent.id, entries, last_lsn }))
+    }
+
+    /// The database's last journal entry: the newest one in its WAL
+    /// directory, or the one its storage was checkpointed at if that is later.
+    async fn journal_lsn(&self) -> Result<Option<u64>, BackupError> {
+        let Some(wal_dir) = &self.wal_dir else {
+            return Ok(None);
+        };
+
+        let metadata_path = PathBuf::from(&self.data_dir).join("metadata.json");
+        let mut lsn = match fs::read(&metadata_path).await {
+            Ok(bytes) => serde_json::from_slice::<crate::DatabaseMetadata>(&bytes)
+                .map_err(|e| BackupError::SerializationError(e.to_string()))?
+                .applied_lsn,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(BackupError::IoError(e.to_string())),
+        };
+
+        let segments = WriteAheadLog::new(wal_dir).segment_files()
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+        // Newest first; the active segment can be empty right after a rotation
+        for segment in segments.iter().rev() {
+            let entries = WriteAheadLog::read_segment(segment)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+            if let Some(last) = entries.iter().map(|e| e.id).max() {
+                lsn = lsn.max(last);
+                break;
+            }
+        }
+        Ok(Some(lsn))
+    }
+
+    fn archive_path(&self, backup_id: &str) -> PathBuf {
+        PathBuf::from(&self.config.backup_dir).join(format!("{}.{}", backup_id, archive::ARCHIVE_EXTENSION))
+    }
+
+    /// The archive file for packed backups, otherwise the backup directory.
+    fn stored_path(&self, backup_id: &str) -> PathBuf {
+        let archive_path = self.archive_path(backup_id);
+        if archive_path.exists() {
+            archive_path
+        } else {
+            PathBuf::from(&self.config.backup_dir).join(backup_id)
+        }
+    }
+
+    async fn remove_stored_backup(&self, backup_id: &str) -> Result<(), BackupError> {
+        let backup_dir = PathBuf::from(&self.config.backup_dir).join(backup_id);
+        if backup_dir.exists() {
+            fs::remove_dir_all(&backup_dir)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        let archive_path = self.archive_path(backup_id);
+        if archive_path.exists() {
+            fs::remove_file(&archive_path)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Packs `src` into an archive at `dest`, streamed through a temporary
+    /// file so a crash never leaves a truncated archive.
+    async fn write_archive(src: &PathBuf, dest: &PathBuf, compress: bool, key: Option<BackupKey>) -> Result<(), BackupError> {
+        let (src, dest) = (src.clone(), dest.clone());
+        tokio::task::spawn_blocking(move || archive::pack_dir(&src, &dest, compress, key.as_ref()))
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))??;
+        Ok(())
+    }
+
+    async fn unpack_archive(&self, archive_path: &PathBuf, dest: &PathBuf) -> Result<(), BackupError> {
+        let key = self.archive_key(archive_path).await?;
+
+        let (archive_path, dest) = (archive_path.clone(), dest.clone());
+        tokio::task::spawn_blocking(move || archive::unpack_to_dir(&archive_path, key.as_ref(), &dest))
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?
+    }
+
+    /// Reads a whole archive back into the bytes it was packed from.
+    async fn unpack_bytes(&self, archive_path: &Path) -> Result<Vec<u8>, BackupError> {
+        let key = self.archive_key(archive_path).await?;
+
+        let archive_path = archive_path.to_path_buf();
+        tokio::task::spawn_blocking(move || archive::unpack_bytes(&archive_path, key.as_ref()))
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?
+    }
+
+    /// Restores a backup; an incremental one is replayed on top of the
+    /// backups it builds on.
+    pub async fn restore_backup(&self, backup_id: &str) -> Result<RestoreReport, BackupError> {
+        let chain = self.backup_chain(backup_id).await?;
+
+        // Unpack and authenticate every archive before touching the data directory
+        let mut dirs = Vec::with_capacity(chain.len());
+        let mut result = Ok(());
+        for backup in &chain {
+            match self.unpacked_dir(&backup.id).await {
+                Ok(dir) => dirs.push(dir),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        if result.is_ok() {
+            result = async {
+                for (dir, _) in &dirs {
+                    // Incremental backups taken before they held a journal are full copies
+                    if dir.join(INCREMENT_JOURNAL).exists() {
+                        self.replay_increment(dir).await?;
+                    } else {
+                        self.restore_from_dir(dir).await?;
+                    }
+                }
+                Ok::<(), BackupError>(())
+            }.await;
+        }
+
+        for (dir, staged) in &dirs {
+            if *staged {
+                let _ = fs::remove_dir_all(dir).await;
+            }
+        }
+        result?;
+
+        let collections_dir = PathBuf::from(&self.data_dir).join("collections");
+        let collection_count = Self::count_collections(&collections_dir).await?;
+        let vector_count = Self::count_vectors(&collections_dir).await?;
+
+        Ok(RestoreReport {
+            backup_id: backup_id.to_string(),
+            restored_at: chrono::Utc::now().timestamp(),
+            collection_count,
+            vector_count,
+            success: true,
+        })
+    }
+
+    /// The backups restoring `backup_id` applies, the full backup first.
+    async fn backup_chain(&self, backup_id: &str) -> Result<Vec<BackupMetadata>, BackupError> {
+        let backups = self.backups.read().await;
+        let mut chain: Vec<BackupMetadata> = Vec::new();
+        let mut next = Some(backup_id.to_string());
+
+        while let Some(id) = next {
+            let backup = backups.get(&id)
+                .ok_or_else(|| BackupError::BackupNotFound(id.clone()))?;
+            if backup.status != BackupStatus::Completed {
+                return Err(BackupError::BackupIncomplete(id));
+            }
+            if chain.len() > backups.len() {
+                return Err(BackupError::IntegrityError(format!("backup '{}' is its own ancestor", backup_id)));
+            }
+
+            next = match backup.backup_type {
+                BackupType::Incremental => Some(backup.parent_backup_id.clone()
+                    .ok_or_else(|| BackupError::IntegrityError(format!("incremental backup '{}' has no parent", id)))?),
+                _ => None,
+            };
+            chain.push(backup.clone());
+        }
+
+        chain.reverse();
+        Ok(chain)
+    }
+
+    /// The directory holding a backup's files, and whether it is a staging
+    /// copy unpacked from its archive that the caller removes.
+    async fn unpacked_dir(&self, backup_id: &str) -> Result<(PathBuf, bool), BackupError> {
+        let stored = self.stored_path(backup_id);
+        if !stored.exists() {
+            return Err(BackupError::BackupNotFound(backup_id.to_string()));
+        }
+        if stored.is_dir() {
+            return Ok((stored, false));
+        }
+
+        let staging = PathBuf::from(&self.config.backup_dir).join(format!(".restore_{}", backup_id));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        if let Err(e) = self.unpack_archive(&stored, &staging).await {
+            let _ = fs::remove_dir_all(&staging).await;
+            return Err(e);
+        }
+        Ok((staging, true))
+    }
+
+    async fn restore_from_dir(&self, backup_dir: &PathBuf) -> Result<(), BackupError> {
+        let data_dir = PathBuf::from(&self.data_dir);
+        let collections_dir = data_dir.join("collections");
+        
+        if collections_dir.exists() {
+            fs::remove_dir_all(&collections_dir)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        let backup_collections = backup_dir.join("collections");
+        if backup_collections.exists() {
+            Self::copy_dir(&backup_collections, &collections_dir).await?;
+        }
+
+        let backup_config = backup_dir.join("config");
+        if backup_config.exists() {
+            fs::copy(&backup_config, &data_dir.join("config"))
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        let backup_index = backup_dir.join("index");
+        if backup_index.exists() {
+            let dest_index = data_dir.join("index");
+            if dest_index.exists() {
+                fs::remove_dir_all(&dest_index)
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+            }
+            Self::copy_dir(&backup_index, &dest_index).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn replay_increment(&self, backup_dir: &Path) -> Result<(), BackupError> {
+        let entries = WriteAheadLog::read_segment(&backup_dir.join(INCREMENT_JOURNAL))
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        let persistence = self.replay_target();
+        for entry in &entries {
+            self.apply_wal_entry(&persistence, entry).await?;
+        }
+        Ok(())
+    }
+
+    /// Storage that replayed journal entries are applied to.
+    fn replay_target(&self) -> PersistenceManager {
+        PersistenceManager::new(PersistenceConfig {
+            backend: StorageBackend::FileSystem,
+            data_dir: self.data_dir.clone(),
+            rocksdb_config: None,
+            ..Default::default()
+        })
+    }
+
+    /// The key new archives are encrypted with, if encryption is enabled.
+    async fn encryption_key(&self) -> Result<Option<BackupKey>, BackupError> {
+        if !self.config.encryption_enabled {
+            return Ok(None);
+        }
+        let source = self.key_source.as_ref()
+            .ok_or_else(|| BackupError::EncryptionError("encryption enabled but no key source configured".to_string()))?;
+        Ok(Some(source.current_key().await?))
+    }
+
+    /// The key named in an archive's header, if it is encrypted.
+    async fn archive_key(&self, archive_path: &Path) -> Result<Option<BackupKey>, BackupError> {
+        let path = archive_path.to_path_buf();
+        let header = tokio::task::spawn_blocking(move || archive::read_header(&path))
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))??;
+        match &header.encryption {
+            Some(encryption) => {
+                let source = self.key_source.as_ref()
+                    .ok_or_else(|| BackupError::KeyMismatch("backup is encrypted but no key source configured".to_string()))?;
+                Ok(Some(source.key(&encryption.key_id).await?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn wal_archive_dir(&self) -> PathBuf {
+        PathBuf::from(&self.config.backup_dir).join("wal_archive")
+    }
+
+    /// Copies every WAL segment into the archive next to the backups, sealed
+    /// as `<segment>.sealed` with the backup key when encryption is enabled.
+    /// The active segment is re-copied on each call, so archiving is idempotent.
+    pub async fn archive_wal(&self, wal: &WriteAheadLog) -> Result<usize, BackupError> {
+        let key = self.encryption_key().await?;
+        let compress = self.config.compression_enabled && cfg!(feature = "compression");
+
+        let archive_dir = self.wal_archive_dir();
+        fs::create_dir_all(&archive_dir)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        let segments = wal.segment_files()
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        for segment in &segments {
+            let Some(file_name) = segment.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let plain_path = archive_dir.join(file_name);
+
+            let Some(key) = &key else {
+                fs::copy(segment, &plain_path)
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+                continue;
+            };
+
+            let sealed_path = archive_dir.join(format!("{}.sealed", file_name));
+            let (segment, key) = (segment.clone(), key.clone());
+            tokio::task::spawn_blocking(move || archive::pack_file(&segment, &sealed_path, compress, Some(&key)))
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))??;
+
+            // A copy archived before encryption was enabled is superseded
+            if plain_path.exists() {
+                fs::remove_file(&plain_path)
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+            }
+        }
+
+        Ok(segments.len())
+    }
+
+    /// Returns all archived WAL entries ordered by LSN, without duplicates.
+    /// Sealed segments are opened with the key named in their header.
+    pub async fn archived_wal_entries(&self) -> Result<Vec<WalEntry>, BackupError> {
+        let archive_dir = self.wal_archive_dir();
+        let mut entries: Vec<WalEntry> = Vec::new();
+
+        if !archive_dir.exists() {
+            return Ok(entries);
+        }
+
+        let mut dir = fs::read_dir(&archive_dir)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        while let Some(entry) = dir.next_entry().await
+            .map_err(|e| BackupError::IoError(e.to_string()))?
+        {
+            let path = entry.path();
+            if WriteAheadLog::is_segment(&path) {
+                let segment = WriteAheadLog::read_segment(&path)
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+                entries.extend(segment);
+            } else if path.extension().is_some_and(|ext| ext == "sealed") {
+                let content = self.unpack_bytes(&path).await?;
+                entries.extend(WriteAheadLog::parse_segment(&content));
+            }
+        }
+
+        entries.sort_by_key(|e| e.id);
+        entries.dedup_by_key(|e| e.id);
+        Ok(entries)
+    }
+
+    pub async fn restore_to_timestamp(&self, timestamp: i64) -> Result<PointInTimeRestoreReport, BackupError> {
+        self.restore_to_target(RecoveryTarget::Timestamp(timestamp)).await
+    }
+
+    pub async fn restore_to_lsn(&self, lsn: u64) -> Result<PointInTimeRestoreReport, BackupError> {
+        self.restore_to_target(RecoveryTarget::Lsn(lsn)).await
+    }
+
+    /// Restores the latest completed backup taken before the target and replays
+    /// archived WAL entries on top of it, stopping at the target. The live
+    /// journal is archived first, and afterwards both it and the archive are
+    /// cut at the target, so the restored timeline is the only one left.
+    /// Run it with the database closed.
+    pub async fn restore_to_target(&self, target: RecoveryTarget) -> Result<PointInTimeRestoreReport, BackupError> {
+        if let Some(wal_dir) = &self.wal_dir {
+            self.archive_wal(&WriteAheadLog::new(wal_dir)).await?;
+        }
+        let entries = self.archived_wal_entries().await?;
+
+        let target_timestamp = match target {
+            RecoveryTarget::Timestamp(ts) => ts,
+            RecoveryTarget::Lsn(lsn) => entries.iter()
+                .filter(|e| e.id <= lsn)
+                .last()
+                .map(|e| e.timestamp as i64)
+                .ok_or_else(|| BackupError::NoBaseBackup(format!("LSN {} is not in the WAL archive", lsn)))?,
+        };
+
+        let restored_lsn = entries.iter()
+            .filter(|e| match target {
+                RecoveryTarget::Timestamp(ts) => e.timestamp as i64 <= ts,
+                RecoveryTarget::Lsn(lsn) => e.id <= lsn,
+            })
+            .map(|e| e.id)
+            .max()
+            .unwrap_or(0);
+
+        // WAL timestamps have second granularity, so backups that know their
+        // last LSN are picked by it; a backup holding entries past the target
+        // cannot be rolled back to it. Backups from before LSNs were recorded
+        // rank by when they were taken.
+        let base = {
+            let backups = self.backups.read().await;
+            backups.values()
+                .filter(|b| b.status == BackupStatus::Completed)
+                .filter(|b| match b.last_lsn {
+                    Some(last_lsn) => last_lsn <= restored_lsn,
+                    None => b.created_at <= target_timestamp,
+                })
+                .max_by(|a, b| match (a.last_lsn, b.last_lsn) {
+                    (Some(a_lsn), Some(b_lsn)) => a_lsn.cmp(&b_lsn).then(a.created_at.cmp(&b.created_at)),
+                    _ => a.created_at.cmp(&b.created_at),
+                })
+                .cloned()
+                .ok_or_else(|| BackupError::NoBaseBackup(format!("no completed backup before {:?}", target)))?
+        };
+
+        // Stopping at the last archived entry instead would report a target
+        // that was never reached
+        let reached = entries.last().is_some_and(|last| match target {
+            RecoveryTarget::Timestamp(ts) => last.timestamp as i64 >= ts,
+            RecoveryTarget::Lsn(lsn) => last.id >= lsn,
+        });
+        if !reached {
+            return Err(BackupError::TargetNotArchived(format!("{:?} is past the last archived WAL entry", target)));
+        }
+
+        self.restore_backup(&base.id).await?;
+
+        // A backup without its last LSN also gets the entries from the same
+        // second as it, since the copy may have started before them; inserts
+        // and deletes are idempotent.
+        let to_replay: Vec<&WalEntry> = entries.iter()
+            .filter(|e| match base.last_lsn {
+                Some(last_lsn) => e.id > last_lsn,
+                None => e.timestamp as i64 >= base.created_at,
+            })
+            .filter(|e| e.id <= restored_lsn)
+            .collect();
+
+        let persistence = self.replay_target();
+        for entry in &to_replay {
+            self.apply_wal_entry(&persistence, entry).await?;
+        }
+
+        self.fence_wal(restored_lsn).await?;
+
+        let collections_dir = PathBuf::from(&self.data_dir).join("collections");
+        let collection_count = Self::count_collections(&collections_dir).await?;
+        let vector_count = Self::count_vectors(&collections_dir).await?;
+
+        Ok(PointInTimeRestoreReport {
+            restore: RestoreReport {
+                backup_id: base.id.clone(),
+                restored_at: chrono::Utc::now().timestamp(),
+                collection_count,
+                vector_count,
+                success: true,
+            },
+            target,
+            replayed_entries: to_replay.len(),
+            last_applied_lsn: to_replay.last().map(|e| e.id),
+        })
+    }
+
+    /// Drops the journal and archived entries after `lsn` and records it as
+    /// applied, so the next `CoreTexDB::open` does not replay the changes the
+    /// restore went back past, and new entries do not reuse their LSNs.
+    async fn fence_wal(&self, lsn: u64) -> Result<(), BackupError> {
+        if let Some(wal_dir) = &self.wal_dir {
+            let mut wal = WriteAheadLog::new(wal_dir);
+            wal.init().await.map_err(|e| BackupError::IoError(e.to_string()))?;
+            wal.truncate_after(lsn).await.map_err(|e| BackupError::IoError(e.to_string()))?;
+        }
+
+        let archive_dir = self.wal_archive_dir();
+        if archive_dir.exists() {
+            let compress = self.config.compression_enabled && cfg!(feature = "compression");
+            let mut dir = fs::read_dir(&archive_dir)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+            while let Some(entry) = dir.next_entry().await
+                .map_err(|e| BackupError::IoError(e.to_string()))?
+            {
+                let path = entry.path();
+                if WriteAheadLog::is_segment(&path) {
+                    let segment = WriteAheadLog::read_segment(&path)
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+                    if segment.iter().all(|e| e.id <= lsn) {
+                        continue;
+                    }
+                    let kept: Vec<WalEntry> = segment.into_iter().filter(|e| e.id <= lsn).collect();
+                    WriteAheadLog::write_segment(&path, &kept)
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+                } else if path.extension().is_some_and(|ext| ext == "sealed") {
+                    let segment = WriteAheadLog::parse_segment(&self.unpack_bytes(&path).await?);
+                    if segment.iter().all(|e| e.id <= lsn) {
+                        continue;
+                    }
+                    let kept: Vec<WalEntry> = segment.into_iter().filter(|e| e.id <= lsn).collect();
+                    let content = WriteAheadLog::serialize_segment(&kept)
+                        .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+                    let key = self.archive_key(&path).await?;
+                    tokio::task::spawn_blocking(move || archive::pack_bytes(&content, &path, compress, key.as_ref()))
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))??;
+                }
+            }
+        }
+
+        let metadata_path = PathBuf::from(&self.data_dir).join("metadata.json");
+        let mut metadata = match fs::read(&metadata_path).await {
+            Ok(bytes) => serde_json::from_slice::<crate::DatabaseMetadata>(&bytes)
+                .map_err(|e| BackupError::SerializationError(e.to_string()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => crate::DatabaseMetadata::default(),
+            Err(e) => return Err(BackupError::IoError(e.to_string())),
+        };
+        metadata.applied_lsn = lsn;
+        metadata.last_modified = chrono::Utc::now().timestamp() as u64;
+
+        let content = serde_json::to_vec_pretty(&metadata)
+            .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+        write_durably(metadata_path, content).await
+    }
+
+    async fn apply_wal_entry(&self, persistence: &PersistenceManager, entry: &WalEntry) -> Result<(), BackupError> {
+        // A transaction is replayed whole, since its LSN is the only recovery target in it
+        for part in entry.parts() {
+            self.apply_wal_part(persistence, &part).await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_wal_part(&self, persistence: &PersistenceManager, entry: &WalEntry) -> Result<(), BackupError> {
+        let collection_dir = PathBuf::from(&self.data_dir)
+            .join("collections")
+            .join(&entry.collection);
+
+        match entry.entry_type {
+            WalEntryType::CreateCollection => {
+                fs::create_dir_all(collection_dir.join("vectors"))
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+                // Journals of `CoreTexDB::open` carry the whole schema
+                if let Some(schema) = entry.data.get("schema") {
+                    let schema = serde_json::to_vec_pretty(schema)
+                        .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+                    fs::write(collection_dir.join("schema.json"), schema)
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+                }
+            }
+            WalEntryType::DeleteCollection => {
+                if collection_dir.exists() {
+                    fs::remove_dir_all(&collection_dir)
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+                }
+            }
+            WalEntryType::Insert | WalEntryType::Update => {
+                for record in Self::wal_records(&entry.data) {
+                    let id = record["id"].as_str()
+                        .ok_or_else(|| BackupError::ReplayError(format!("entry {} has no vector id", entry.id)))?;
+                    let vector: Vec<f32> = record["vector"].as_array()
+                        .ok_or_else(|| BackupError::ReplayError(format!("entry {} has no vector", entry.id)))?
+                        .iter()
+                        .filter_map(|v| v.as_f64())
+                        .map(|v| v as f32)
+                        .collect();
+                    let metadata = record.get("metadata").filter(|m| !m.is_null());
+
+                    persistence.save_vector(&entry.collection, id, &vector, metadata)
+                        .await
+                        .map_err(|e| BackupError::ReplayError(e.to_string()))?;
+                }
+            }
+            WalEntryType::Delete => {
+                let ids: Vec<String> = match entry.data.get("ids").and_then(|v| v.as_array()) {
+                    Some(ids) => ids.iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+                    None => entry.data["id"].as_str().map(|id| vec![id.to_string()]).unwrap_or_default(),
+                };
+
+                for id in ids {
+                    persistence.delete_vector(&entry.collection, &id)
+                        .await
+                        .map_err(|e| BackupError::ReplayError(e.to_string()))?;
+                }
+            }
+            WalEntryType::Transaction => {
+                return Err(BackupError::ReplayError(format!("entry {} nests a transaction", entry.id)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert/update payloads are either a single `{id, vector, metadata}` record
+    /// or a batch under `vectors`.
+    fn wal_records(data: &serde_json::Value) -> Vec<&serde_json::Value> {
+        match data.get("vectors").and_then(|v| v.as_array()) {
+            Some(batch) => batch.iter().collect(),
+            None => vec![data],
+        }
+    }
+
+    pub async fn list_backups(&self) -> Vec<BackupMetadata> {
+        let backups = self.backups.read().await;
+        let mut list: Vec<BackupMetadata> = backups.values().cloned().collect();
+        list.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        list
+    }
+
+    pub async fn get_backup(&self, backup_id: &str) -> Option<BackupMetadata> {
+        self.backups.read().await.get(backup_id).cloned()
+    }
+
+    pub async fn delete_backup(&self, backup_id: &str) -> Result<bool, BackupError> {
+        self.remove_stored_backup(backup_id).await?;
+
+        {
+            let mut backups = self.backups.write().await;
+            backups.remove(backup_id);
+        }
+
+        self.save_backup_metadata().await?;
+
+        Ok(true)
+    }
+
+    pub async fn verify_backup(&self, backup_id: &str) -> Result<bool, BackupError> {
+        let backups = self.backups.read().await;
+        let backup = backups.get(backup_id)
+            .ok_or_else(|| BackupError::BackupNotFound(backup_id.to_string()))?;
+        
+        let current_checksum = self.calculate_checksum(&self.stored_path(backup_id)).await?;
+        
+        Ok(current_checksum == backup.checksum)
+    }
+
+    async fn latest_completed_backup(&self) -> Option<BackupMetadata> {
+        let backups = self.backups.read().await;
+        backups.values()
+            .filter(|b| b.status == BackupStatus::Completed)
+            .max_by_key(|b| b.created_at)
+            .cloned()
+    }
+
+    /// Deletes backups that fall outside `retention_days` and the GFS rotation,
+    /// returning the ids that were removed.
+    pub async fn apply_retention(&self) -> Result<Vec<String>, BackupError> {
+        let mut backups = self.backups.write().await;
+        let all: Vec<BackupMetadata> = backups.values().cloned().collect();
+        let to_delete = self.config.retention.expired(
+            &all,
+            self.config.retention_days,
+            chrono::Utc::now().timestamp(),
+        );
+        
+        for backup_id in &to_delete {
+            self.remove_stored_backup(backup_id).await?;
+            backups.remove(backup_id);
+        }
+
+        drop(backups);
+        self.save_backup_metadata().await?;
+
+        Ok(to_delete)
+    }
+
+    async fn copy_dir(src: &PathBuf, dst: &PathBuf) -> Result<(), BackupError> {
+        fs::create_dir_all(dst)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        let mut entries = fs::read_dir(src)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        while let Some(entry) = entries.next_entry().await
+            .map_err(|e| BackupError::IoError(e.to_string()))?
+        {
+            let src_path = entry.path();
+            let dst_path = dst.join(entry.file_name());
+            
+            if src_path.is_dir() {
+                Box::pin(Self::copy_dir(&src_path, &dst_path)).await?;
+            } else {
+                fs::copy(&src_path, &dst_path)
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn dir_size(path: &PathBuf) -> u64 {
+        let mut total: u64 = 0;
+        
+        if let Ok(entries) = fs::read_dir(path).await {
+            let mut entries = entries;
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                if let Ok(meta) = entry.metadata().await {
+                    if meta.is_file() {
+                        total += meta.len();
+                    } else if meta.is_dir() {
+                        total += Box::pin(Self::dir_size(&entry.path())).await;
+                    }
+                }
+            }
+        }
+        
+        total
+    }
+
+    async fn count_collections(path: &PathBuf) -> Result<usize, BackupError> {
+        let mut count = 0;
+        
+        if path.exists() {
+            let mut entries = fs::read_dir(path)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+            
+            while let Some(entry) = entries.next_entry().await
+                .map_err(|e| BackupError::IoError(e.to_string()))?
+            {
+                if entry.path().is_dir() {
+                    count += 1;
+                }
+            }
+        }
+        
+        Ok(count)
+    }
+
+    async fn count_vectors(path: &PathBuf) -> Result<u64, BackupError> {
+        let mut count: u64 = 0;
+        
+        if path.exists() {
+            let mut entries = fs::read_dir(path)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+            
+            while let Some(entry) = entries.next_entry().await
+                .map_err(|e| BackupError::IoError(e.to_string()))?
+            {
+                let entry_path = entry.path();
+                
+                if entry_path.is_dir() {
+                    let vectors_dir = entry_path.join("vectors");
+                    if vectors_dir.exists() {
+                        let mut vector_entries = fs::read_dir(&vectors_dir)
+                            .await
+                            .map_err(|e| BackupError::IoError(e.to_string()))?;
+                        
+                        while let Some(_) = vector_entries.next_entry().await
+                            .map_err(|e| BackupError::IoError(e.to_string()))?
+                        {
+                            count += 1;
+                        }
+                    }
+                }
+            }
+        }
+        
+        Ok(count)
+    }
+
+    /// SHA-256 of a file, or of a directory's files in path order with
+    /// their relative paths, so a renamed or moved file changes it too.
+    /// Files are read in chunks rather than whole.
+    async fn calculate_checksum(&self, path: &PathBuf) -> Result<String, BackupError> {
+        use sha2::{Digest, Sha256};
+        use tokio::io::AsyncReadExt;
+
+        let mut hasher = Sha256::new();
+
+        if path.exists() {
+            let mut stack = vec![path.clone()];
+
+            while let Some(current) = stack.pop() {
+                if current.is_dir() {
+                    let mut entries = fs::read_dir(&current)
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+                    let mut children = Vec::new();
+                    while let Some(entry) = entries.next_entry().await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?
+                    {
+                        children.push(entry.path());
+                    }
+                    // Popped in ascending order
+                    children.sort_by(|a, b| b.cmp(a));
+                    stack.extend(children);
+                } else {
+                    if current != *path {
+                        let relative = current.strip_prefix(path).unwrap_or(&current);
+                        hasher.update(relative.to_string_lossy().as_bytes());
+                        hasher.update([0]);
+                    }
+
+                    let mut file = fs::File::open(&current)
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+                    let mut buffer = vec![0u8; CHECKSUM_CHUNK_SIZE];
+                    loop {
+                        let read = file.read(&mut buffer)
+                            .await
+                            .map_err(|e| BackupError::IoError(e.to_string()))?;
+                        if read == 0 {
+                            break;
+                        }
+                        hasher.update(&buffer[..read]);
+                    }
+                }
+            }
+        }
+
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+/// `coretex_journal::write_durably` off the async runtime.
+async fn write_durably(path: PathBuf, content: Vec<u8>) -> Result<(), BackupError> {
+    tokio::task::spawn_blocking(move || crate::coretex_journal::write_durably(&path, &content))
+        .await
+        .map_err(|e| BackupError::IoError(e.to_string()))?
+        .map_err(|e| BackupError::IoError(e.to_string()))
+}
+
+/// The journal an incremental backup holds.
+struct Increment {
+    parent: String,
+    entries: Vec<WalEntry>,
+    last_lsn: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub backup_id: String,
+    pub restored_at: i64,
+    pub collection_count: usize,
+    pub vector_count: u64,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecoveryTarget {
+    Timestamp(i64),
+    Lsn(u64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointInTimeRestoreReport {
+    pub restore: RestoreReport,
+    pub target: RecoveryTarget,
+    pub replayed_entries: usize,
+    pub last_applied_lsn: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    IoError(String),
+    SerializationError(String),
+    BackupNotFound(String),
+    BackupIncomplete(String),
+    NoBaseBackup(String),
+    ReplayError(String),
+    InvalidSchedule(String),
+    EncryptionError(String),
+    CompressionError(String),
+    KeyMismatch(String),
+    IntegrityError(String),
+    RemoteError(String),
+    TargetNotArchived(String),
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::IoError(msg) => write!(f, "IO Error: {}", msg),
+            BackupError::SerializationError(msg) => write!(f, "Serialization Error: {}", msg),
+            BackupError::BackupNotFound(id) => write!(f, "Backup not found: {}", id),
+            BackupError::BackupIncomplete(id) => write!(f, "Backup incomplete: {}", id),
+            BackupError::NoBaseBackup(msg) => write!(f, "No base backup: {}", msg),
+            BackupError::ReplayError(msg) => write!(f, "WAL replay error: {}", msg),
+            BackupError::InvalidSchedule(msg) => write!(f, "Invalid backup schedule: {}", msg),
+            BackupError::EncryptionError(msg) => write!(f, "Encryption Error: {}", msg),
+            BackupError::CompressionError(msg) => write!(f, "Compression Error: {}", msg),
+            BackupError::KeyMismatch(msg) => write!(f, "Key mismatch: {}", msg),
+            BackupError::IntegrityError(msg) => write!(f, "Backup integrity check failed: {}", msg),
+            BackupError::RemoteError(msg) => write!(f, "Remote storage error: {}", msg),
+            BackupError::TargetNotArchived(msg) => write!(f, "Recovery target not archived: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backup_manager_new() {
+        let config = BackupConfig::default();
+        let manager = BackupManager::new(config, "./data");
+        
+        assert_eq!(manager.config().backup_dir, "./backups");
+    }
+
+    #[tokio::test]
+    async fn test_create_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let backup_dir = temp_dir.path().join("backups");
+        
+        fs::create_dir_all(&data_dir).await.unwrap();
+        fs::create_dir_all(&data_dir.join("collections/test")).await.unwrap();
+        
+        let config = BackupConfig {
+            backup_dir: backup_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        
+        let manager = BackupManager::new(config, &data_dir.to_string_lossy());
+        manager.initialize().await.unwrap();
+        
+        let backup_id = manager.create_backup("test_backup", BackupType::Full).await.unwrap();
+        
+        let backups = manager.list_backups().await;
+        assert!(!backups.is_empty());
+
+        // A second backup of the same name right away keeps both
+        let second_id = manager.create_backup("test_backup", BackupType::Full).await.unwrap();
+        assert_ne!(second_id, backup_id);
+        assert_eq!(manager.list_backups().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_list_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let backup_dir = temp_dir.path().join("backups");
+        
+        fs::create_dir_all(&data_dir).await.unwrap();
+        
+        let config = BackupConfig {
+            backup_dir: backup_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        
+        let manager = BackupManager::new(config, &data_dir.to_string_lossy());
+        manager.initialize().await.unwrap();
+        
+        let backups = manager.list_backups().await;
+        assert!(backups.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let backup_dir = temp_dir.path().join("backups");
+        
+        fs::create_dir_all(&data_dir).await.unwrap();
+        
+        let config = BackupConfig {
+            backup_dir: backup_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        
+        let manager = BackupManager::new(config, &data_dir.to_string_lossy());
+        manager.initialize().await.unwrap();
+        
+        let backup_id = manager.create_backup("test", BackupType::Full).await.unwrap();
+        
+        let deleted = manager.delete_backup(&backup_id).await.unwrap();
+        assert!(deleted);
+    }
+
+    #[tokio::test]
+    async fn test_restore_to_lsn_replays_archived_wal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let backup_dir = temp_dir.path().join("backups");
+        let wal_dir = temp_dir.path().join("wal");
+
+        let persistence = PersistenceManager::new(PersistenceConfig {
+            data_dir: data_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        });
+        persistence.initialize().await.unwrap();
+        persistence.save_vector("docs", "v1", &[1.0, 0.0], None).await.unwrap();
+
+        let config = BackupConfig {
+            backup_dir: backup_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &data_dir.to_string_lossy());
+        manager.initialize().await.unwrap();
+        manager.create_backup("base", BackupType::Full).await.unwrap();
+
+        let mut wal = WriteAheadLog::new(&wal_dir.to_string_lossy());
+        wal.init().await.unwrap();
+        let insert = wal.create_entry(
+            WalEntryType::Insert,
+            "docs",
+            serde_json::json!({"id": "v2", "vector": [0.0, 1.0], "metadata": {"tag": "b"}}),
+        ).await.unwrap();
+        wal.create_entry(
+            WalEntryType::Delete,
+            "docs",
+            serde_json::json!({"ids": ["v1", "v2"]}),
+        ).await.unwrap();
+
+        assert_eq!(manager.archive_wal(&wal).await.unwrap(), 1);
+
+        let report = manager.restore_to_lsn(insert.id).await.unwrap();
+        assert_eq!(report.replayed_entries, 1);
+        assert_eq!(report.last_applied_lsn, Some(insert.id));
+        assert_eq!(report.restore.vector_count, 2);
+
+        let (vector, metadata) = persistence.load_vector("docs", "v2").await.unwrap().unwrap();
+        assert_eq!(vector, vec![0.0, 1.0]);
+        assert_eq!(metadata.unwrap()["tag"], "b");
+        assert!(persistence.load_vector("docs", "v1").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_backup_of_open_database_archives_its_journal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_config = crate::DbConfig::new(&temp_dir.path().to_string_lossy());
+        let db = crate::CoreTexDB::open(db_config.clone()).await.unwrap();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![("a".to_string(), vec![1.0, 0.0], serde_json::json!({"tag": "a"}))]).await.unwrap();
+
+        let config = BackupConfig {
+            backup_dir: temp_dir.path().join("backups").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &db_config.data_dir).with_wal_dir(&db_config.wal_dir);
+        manager.initialize().await.unwrap();
+        manager.create_backup("base", BackupType::Full).await.unwrap();
+
+        let archived = manager.archived_wal_entries().await.unwrap();
+        assert_eq!(archived.len(), 2);
+        assert!(matches!(archived[0].entry_type, WalEntryType::CreateCollection));
+
+        db.delete_vectors("docs", &["a".to_string()]).await.unwrap();
+        let report = manager.restore_to_lsn(archived[1].id).await.unwrap();
+        assert_eq!(report.restore.vector_count, 1);
+        let data_dir = PathBuf::from(&db_config.data_dir);
+        assert!(data_dir.join("collections/docs/schema.json").exists());
+        assert!(data_dir.join("collections/docs/vectors/a.vec").exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_replays_a_transaction_whole() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_config = crate::DbConfig::new(&temp_dir.path().to_string_lossy());
+        let db = crate::CoreTexDB::open(db_config.clone()).await.unwrap();
+        db.create_collection("a", 2, "cosine").await.unwrap();
+        db.create_collection("b", 2, "cosine").await.unwrap();
+
+        let config = BackupConfig {
+            backup_dir: temp_dir.path().join("backups").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &db_config.data_dir).with_wal_dir(&db_config.wal_dir);
+        manager.initialize().await.unwrap();
+        manager.create_backup("base", BackupType::Full).await.unwrap();
+
+        let txn = db.begin().await.unwrap();
+        txn.insert("a", vec![("x".to_string(), vec![1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+        txn.insert("b", vec![("y".to_string(), vec![0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+        txn.commit().await.unwrap();
+        let commit_lsn = db.journal.last_lsn().await;
+        db.delete_vectors("a", &["x".to_string()]).await.unwrap();
+
+        manager.archive_wal(&WriteAheadLog::new(&db_config.wal_dir)).await.unwrap();
+        let report = manager.restore_to_lsn(commit_lsn).await.unwrap();
+        assert_eq!(report.last_applied_lsn, Some(commit_lsn));
+        let data_dir = PathBuf::from(&db_config.data_dir);
+        assert!(data_dir.join("collections/a/vectors/x.vec").exists());
+        assert!(data_dir.join("collections/b/vectors/y.vec").exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_fences_the_journal_at_the_target() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_config = crate::DbConfig::new(&temp_dir.path().to_string_lossy());
+        let config = BackupConfig {
+            backup_dir: temp_dir.path().join("backups").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &db_config.data_dir).with_wal_dir(&db_config.wal_dir);
+        manager.initialize().await.unwrap();
+
+        let before_delete = {
+            let db = crate::CoreTexDB::open(db_config.clone()).await.unwrap();
+            db.create_collection("docs", 2, "cosine").await.unwrap();
+            db.insert_vectors("docs", vec![
+                ("a".to_string(), vec![1.0, 0.0], serde_json::json!({})),
+                ("b".to_string(), vec![0.0, 1.0], serde_json::json!({})),
+            ]).await.unwrap();
+            manager.create_backup("base", BackupType::Full).await.unwrap();
+            let before_delete = db.journal.last_lsn().await;
+            db.delete_vectors("docs", &["b".to_string()]).await.unwrap();
+            before_delete
+        };
+
+        // The delete is only in the live journal, which the restore archives
+        let result = manager.restore_to_lsn(before_delete + 2).await;
+        assert!(matches!(result, Err(BackupError::TargetNotArchived(_))));
+
+        let report = manager.restore_to_lsn(before_delete).await.unwrap();
+        assert_eq!(report.last_applied_lsn, Some(before_delete));
+        assert_eq!(manager.archived_wal_entries().await.unwrap().last().unwrap().id, before_delete);
+
+        let db = crate::CoreTexDB::open(db_config.clone()).await.unwrap();
+        assert!(db.get_vector("docs", "b", None).await.unwrap().is_some());
+        assert_eq!(db.journal.last_lsn().await, before_delete);
+        assert_eq!(db.load_metadata().await.unwrap().applied_lsn, before_delete);
+    }
+
+    #[tokio::test]
+    async fn test_restore_picks_the_base_backup_by_lsn() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_config = crate::DbConfig::new(&temp_dir.path().to_string_lossy());
+        let config = BackupConfig {
+            backup_dir: temp_dir.path().join("backups").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &db_config.data_dir).with_wal_dir(&db_config.wal_dir);
+        manager.initialize().await.unwrap();
+
+        let (first, target) = {
+            let db = crate::CoreTexDB::open(db_config.clone()).await.unwrap();
+            db.create_collection("docs", 2, "cosine").await.unwrap();
+            db.insert_vectors("docs", vec![("a".to_string(), vec![1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+            let first = manager.create_backup("first", BackupType::Full).await.unwrap();
+            db.insert_vectors("docs", vec![("b".to_string(), vec![0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+            let target = db.journal.last_lsn().await;
+            db.delete_vectors("docs", &["a".to_string()]).await.unwrap();
+            // Likely the same second as the target, which a timestamp would not tell apart
+            manager.create_backup("second", BackupType::Full).await.unwrap();
+            (first, target)
+        };
+
+        let backups = manager.list_backups().await;
+        assert!(backups.iter().all(|b| b.last_lsn.is_some()));
+
+        let report = manager.restore_to_lsn(target).await.unwrap();
+        assert_eq!(report.restore.backup_id, first);
+        // Only the entries after the base backup's LSN are replayed
+        assert_eq!(report.replayed_entries, 1);
+        assert_eq!(report.last_applied_lsn, Some(target));
+        let db = crate::CoreTexDB::open(db_config.clone()).await.unwrap();
+        assert!(db.get_vector("docs", "a", None).await.unwrap().is_some());
+        assert!(db.get_vector("docs", "b", None).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_backup_holds_the_journal_since_its_parent() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_config = crate::DbConfig::new(&temp_dir.path().to_string_lossy());
+        let config = BackupConfig {
+            backup_dir: temp_dir.path().join("backups").to_string_lossy().to_string(),
+            compression_enabled: false,
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &db_config.data_dir).with_wal_dir(&db_config.wal_dir);
+        manager.initialize().await.unwrap();
+
+        let (full, increment) = {
+            let db = crate::CoreTexDB::open(db_config.clone()).await.unwrap();
+            db.create_collection("docs", 2, "cosine").await.unwrap();
+            let full = manager.create_backup("full", BackupType::Full).await.unwrap();
+            db.insert_vectors("docs", vec![("b".to_string(), vec![0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+            let increment = manager.create_backup("increment", BackupType::Incremental).await.unwrap();
+            db.insert_vectors("docs", vec![("c".to_string(), vec![1.0, 1.0], serde_json::json!({}))]).await.unwrap();
+            (full, increment)
+        };
+
+        let metadata = manager.get_backup(&increment).await.unwrap();
+        assert_eq!(metadata.backup_type, BackupType::Incremental);
+        assert_eq!(metadata.parent_backup_id.as_deref(), Some(full.as_str()));
+        let stored = manager.stored_path(&increment);
+        assert!(stored.join(INCREMENT_JOURNAL).exists());
+        assert!(!stored.join("collections").exists());
+
+        let collections_dir = PathBuf::from(&db_config.data_dir).join("collections");
+        fs::remove_dir_all(&collections_dir).await.unwrap();
+        manager.restore_backup(&increment).await.unwrap();
+        assert!(collections_dir.join("docs/schema.json").exists());
+        assert!(collections_dir.join("docs/vectors/b.vec").exists());
+        assert!(!collections_dir.join("docs/vectors/c.vec").exists());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_backup_without_journal_is_full() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = BackupConfig {
+            backup_dir: temp_dir.path().join("backups").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &temp_dir.path().join("data").to_string_lossy());
+        manager.initialize().await.unwrap();
+
+        manager.create_backup("first", BackupType::Full).await.unwrap();
+        let backup_id = manager.create_backup("second", BackupType::Incremental).await.unwrap();
+        let metadata = manager.get_backup(&backup_id).await.unwrap();
+        assert_eq!(metadata.backup_type, BackupType::Full);
+        assert!(metadata.parent_backup_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_restore_to_timestamp_without_base_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let backup_dir = temp_dir.path().join("backups");
+
+        let config = BackupConfig {
+            backup_dir: backup_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &data_dir.to_string_lossy());
+        manager.initialize().await.unwrap();
+
+        let result = manager.restore_to_timestamp(chrono::Utc::now().timestamp()).await;
+        assert!(matches!(result, Err(BackupError::NoBaseBackup(_))));
+    }
+
+    async fn encrypted_manager(root: &std::path::Path, keys: Arc<crate::coretex_security::KeyManager>) -> BackupManager {
+        let config = BackupConfig {
+            backup_dir: root.join("backups").to_string_lossy().to_string(),
+            encryption_enabled: true,
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &root.join("data").to_string_lossy())
+            .with_key_source(keys);
+        manager.initialize().await.unwrap();
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let vector_file = temp_dir.path().join("data/collections/docs/vectors/v1.vec");
+        fs::create_dir_all(vector_file.parent().unwrap()).await.unwrap();
+        fs::write(&vector_file, b"secret vector").await.unwrap();
+
+        let keys = Arc::new(crate::coretex_security::KeyManager::new());
+        keys.generate_key("backup-key", 256).await.unwrap();
+        let manager = encrypted_manager(temp_dir.path(), keys).await;
+
+        let backup_id = manager.create_backup("nightly", BackupType::Full).await.unwrap();
+        let metadata = manager.get_backup(&backup_id).await.unwrap();
+        assert_eq!(metadata.encryption_key_id.as_deref(), Some("backup-key"));
+
+        let archive = fs::read(manager.archive_path(&backup_id)).await.unwrap();
+        assert!(!archive.windows(13).any(|w| w == b"secret vector"));
+        assert!(manager.verify_backup(&backup_id).await.unwrap());
+
+        fs::remove_file(&vector_file).await.unwrap();
+        let report = manager.restore_backup(&backup_id).await.unwrap();
+        assert_eq!(report.vector_count, 1);
+        assert_eq!(fs::read(&vector_file).await.unwrap(), b"secret vector");
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_restore_rejects_wrong_key_and_tampering() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let vector_file = temp_dir.path().join("data/collections/docs/vectors/v1.vec");
+        fs::create_dir_all(vector_file.parent().unwrap()).await.unwrap();
+        fs::write(&vector_file, b"original").await.unwrap();
+
+        let keys = Arc::new(crate::coretex_security::KeyManager::new());
+        keys.generate_key("backup-key", 256).await.unwrap();
+        let manager = encrypted_manager(temp_dir.path(), keys).await;
+        let backup_id = manager.create_backup("nightly", BackupType::Full).await.unwrap();
+
+        // Same key id, different key material
+        let other_keys = Arc::new(crate::coretex_security::KeyManager::new());
+        other_keys.generate_key("backup-key", 256).await.unwrap();
+        let wrong_key_manager = encrypted_manager(temp_dir.path(), other_keys).await;
+        let result = wrong_key_manager.restore_backup(&backup_id).await;
+        assert!(matches!(result, Err(BackupError::KeyMismatch(_))));
+
+        let archive_path = manager.archive_path(&backup_id);
+        let mut archive = fs::read(&archive_path).await.unwrap();
+        let last = archive.len() - 1;
+        archive[last] ^= 0xff;
+        fs::write(&archive_path, archive).await.unwrap();
+
+        let result = manager.restore_backup(&backup_id).await;
+        assert!(matches!(result, Err(BackupError::IntegrityError(_))));
+        assert_eq!(fs::read(&vector_file).await.unwrap(), b"original");
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_seals_archived_wal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let keys = Arc::new(crate::coretex_security::KeyManager::new());
+        keys.generate_key("backup-key", 256).await.unwrap();
+        let manager = encrypted_manager(temp_dir.path(), keys.clone()).await;
+
+        let mut wal = WriteAheadLog::new(&temp_dir.path().join("wal").to_string_lossy());
+        wal.init().await.unwrap();
+        wal.create_entry(
+            WalEntryType::Insert,
+            "docs",
+            serde_json::json!({"id": "v1", "vector": [1.0, 0.0], "metadata": {"tag": "secret tag"}}),
+        ).await.unwrap();
+        assert_eq!(manager.archive_wal(&wal).await.unwrap(), 1);
+
+        let archive_dir = temp_dir.path().join("backups/wal_archive");
+        assert!(!archive_dir.join("wal.log").exists());
+        let sealed = fs::read(archive_dir.join("wal.log.sealed")).await.unwrap();
+        assert!(!sealed.windows(10).any(|w| w == b"secret tag"));
+
+        let entries = manager.archived_wal_entries().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data["metadata"]["tag"], "secret tag");
+
+        // Another key under the same id cannot open the archive
+        let other_keys = Arc::new(crate::coretex_security::KeyManager::new());
+        other_keys.generate_key("backup-key", 256).await.unwrap();
+        let wrong_key_manager = encrypted_manager(temp_dir.path(), other_keys).await;
+        assert!(matches!(wrong_key_manager.archived_wal_entries().await, Err(BackupError::KeyMismatch(_))));
+    }
+
+    #[tokio::test]
+    async fn test_encryption_requires_key_source() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = BackupConfig {
+            backup_dir: temp_dir.path().join("backups").to_string_lossy().to_string(),
+            encryption_enabled: true,
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &temp_dir.path().join("data").to_string_lossy());
+        manager.initialize().await.unwrap();
+
+        let result = manager.create_backup("nightly", BackupType::Full).await;
+        assert!(matches!(result, Err(BackupError::EncryptionError(_))));
+        assert!(manager.list_backups().await.is_empty());
+    }
+}
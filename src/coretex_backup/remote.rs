@@ -0,0 +1,754 @@
+//! Remote backup targets
+//! Object-store abstraction for shipping backup archives off the host
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::RwLock;
+
+use super::{archive, BackupError, BackupManager, BackupMetadata, BackupStatus};
+use crate::coretex_lakehouse::s3::{S3Object, S3Part, S3Storage};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteBackupConfig {
+    /// Key prefix backups are stored under, e.g. `"coretex/backups"`
+    pub prefix: String,
+    /// Archives larger than this are sent with multipart upload
+    pub multipart_threshold: usize,
+    pub part_size: usize,
+    /// Upload every backup as soon as it completes
+    pub upload_on_create: bool,
+}
+
+impl Default for RemoteBackupConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "backups".to_string(),
+            multipart_threshold: 64 * 1024 * 1024,
+            part_size: 16 * 1024 * 1024,
+            upload_on_create: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadedPart {
+    pub part_number: u32,
+    pub etag: String,
+    pub size: u64,
+}
+
+/// Minimal object-store API shaped after S3, so S3, MinIO and in-process fakes
+/// can all serve as backup targets.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put_object(&self, key: &str, data: Vec<u8>) -> Result<(), BackupError>;
+    /// Uploads a file in one request; `sha256` is the hex digest of its
+    /// content. Stores that send over the network stream it; the default
+    /// reads it into memory.
+    async fn put_file(&self, key: &str, path: &Path, _sha256: &str) -> Result<(), BackupError> {
+        let data = fs::read(path).await.map_err(|e| BackupError::IoError(e.to_string()))?;
+        self.put_object(key, data).await
+    }
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, BackupError>;
+    /// Reads bytes `start..end` (end exclusive) of an object
+    async fn get_object_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>, BackupError>;
+    async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>, BackupError>;
+    async fn delete_object(&self, key: &str) -> Result<(), BackupError>;
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>, BackupError>;
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, BackupError>;
+    async fn upload_part(&self, key: &str, upload_id: &str, part_number: u32, data: Vec<u8>) -> Result<UploadedPart, BackupError>;
+    async fn list_parts(&self, key: &str, upload_id: &str) -> Result<Vec<UploadedPart>, BackupError>;
+    async fn complete_multipart_upload(&self, key: &str, upload_id: &str, parts: Vec<UploadedPart>) -> Result<(), BackupError>;
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), BackupError>;
+}
+
+struct PendingUpload {
+    key: String,
+    parts: BTreeMap<u32, Vec<u8>>,
+}
+
+/// In-process object store for tests and local development.
+pub struct InMemoryObjectStore {
+    objects: RwLock<HashMap<String, (Vec<u8>, i64)>>,
+    uploads: RwLock<HashMap<String, PendingUpload>>,
+    next_upload_id: AtomicU64,
+    /// Number of part uploads left before parts start failing; `usize::MAX` disables faults
+    parts_before_failure: AtomicUsize,
+}
+
+impl InMemoryObjectStore {
+    pub fn new() -> Self {
+        Self {
+            objects: RwLock::new(HashMap::new()),
+            uploads: RwLock::new(HashMap::new()),
+            next_upload_id: AtomicU64::new(1),
+            parts_before_failure: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    /// Lets `parts` more part uploads succeed, then fails the rest until reset.
+    pub fn fail_parts_after(&self, parts: usize) {
+        self.parts_before_failure.store(parts, Ordering::SeqCst);
+    }
+
+    pub fn clear_faults(&self) {
+        self.parts_before_failure.store(usize::MAX, Ordering::SeqCst);
+    }
+
+    pub async fn pending_uploads(&self) -> usize {
+        self.uploads.read().await.len()
+    }
+
+    fn not_found(key: &str) -> BackupError {
+        BackupError::RemoteError(format!("object not found: {}", key))
+    }
+}
+
+impl Default for InMemoryObjectStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ObjectStore for InMemoryObjectStore {
+    async fn put_object(&self, key: &str, data: Vec<u8>) -> Result<(), BackupError> {
+        self.objects.write().await.insert(key.to_string(), (data, chrono::Utc::now().timestamp()));
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, BackupError> {
+        self.objects.read().await
+            .get(key)
+            .map(|(data, _)| data.clone())
+            .ok_or_else(|| Self::not_found(key))
+    }
+
+    async fn get_object_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>, BackupError> {
+        let objects = self.objects.read().await;
+        let (data, _) = objects.get(key).ok_or_else(|| Self::not_found(key))?;
+        let end = (end as usize).min(data.len());
+        let start = (start as usize).min(end);
+        Ok(data[start..end].to_vec())
+    }
+
+    async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>, BackupError> {
+        Ok(self.objects.read().await.get(key).map(|(data, modified)| ObjectInfo {
+            key: key.to_string(),
+            size: data.len() as u64,
+            last_modified: *modified,
+        }))
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), BackupError> {
+        self.objects.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>, BackupError> {
+        let mut objects: Vec<ObjectInfo> = self.objects.read().await
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, (data, modified))| ObjectInfo {
+                key: key.clone(),
+                size: data.len() as u64,
+                last_modified: *modified,
+            })
+            .collect();
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, BackupError> {
+        let upload_id = format!("upload_{}", self.next_upload_id.fetch_add(1, Ordering::SeqCst));
+        self.uploads.write().await.insert(upload_id.clone(), PendingUpload {
+            key: key.to_string(),
+            parts: BTreeMap::new(),
+        });
+        Ok(upload_id)
+    }
+
+    async fn upload_part(&self, key: &str, upload_id: &str, part_number: u32, data: Vec<u8>) -> Result<UploadedPart, BackupError> {
+        let remaining = self.parts_before_failure.load(Ordering::SeqCst);
+        if remaining == 0 {
+            return Err(BackupError::RemoteError("injected part upload failure".to_string()));
+        }
+        if remaining != usize::MAX {
+            self.parts_before_failure.store(remaining - 1, Ordering::SeqCst);
+        }
+
+        let mut uploads = self.uploads.write().await;
+        let upload = uploads.get_mut(upload_id)
+            .filter(|u| u.key == key)
+            .ok_or_else(|| BackupError::RemoteError(format!("no such upload: {}", upload_id)))?;
+
+        let part = UploadedPart {
+            part_number,
+            etag: etag(&data),
+            size: data.len() as u64,
+        };
+        upload.parts.insert(part_number, data);
+        Ok(part)
+    }
+
+    async fn list_parts(&self, key: &str, upload_id: &str) -> Result<Vec<UploadedPart>, BackupError> {
+        let uploads = self.uploads.read().await;
+        let upload = uploads.get(upload_id)
+            .filter(|u| u.key == key)
+            .ok_or_else(|| BackupError::RemoteError(format!("no such upload: {}", upload_id)))?;
+
+        Ok(upload.parts.iter()
+            .map(|(number, data)| UploadedPart {
+                part_number: *number,
+                etag: etag(data),
+                size: data.len() as u64,
+            })
+            .collect())
+    }
+
+    async fn complete_multipart_upload(&self, key: &str, upload_id: &str, parts: Vec<UploadedPart>) -> Result<(), BackupError> {
+        let mut uploads = self.uploads.write().await;
+        let upload = uploads.remove(upload_id)
+            .filter(|u| u.key == key)
+            .ok_or_else(|| BackupError::RemoteError(format!("no such upload: {}", upload_id)))?;
+
+        let mut data = Vec::new();
+        for part in &parts {
+            let bytes = upload.parts.get(&part.part_number)
+                .ok_or_else(|| BackupError::RemoteError(format!("part {} was never uploaded", part.part_number)))?;
+            if etag(bytes) != part.etag {
+                return Err(BackupError::RemoteError(format!("etag mismatch for part {}", part.part_number)));
+            }
+            data.extend_from_slice(bytes);
+        }
+
+        drop(uploads);
+        self.put_object(key, data).await
+    }
+
+    async fn abort_multipart_upload(&self, _key: &str, upload_id: &str) -> Result<(), BackupError> {
+        self.uploads.write().await.remove(upload_id);
+        Ok(())
+    }
+}
+
+fn etag(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(data))
+}
+
+fn s3_object_info(object: S3Object) -> ObjectInfo {
+    let last_modified = object.last_modified.as_deref()
+        .and_then(|v| chrono::DateTime::parse_from_rfc3339(v)
+            .or_else(|_| chrono::DateTime::parse_from_rfc2822(v))
+            .ok())
+        .map(|t| t.timestamp())
+        .unwrap_or(0);
+    ObjectInfo { key: object.key, size: object.size, last_modified }
+}
+
+/// Backups on S3 or MinIO.
+#[async_trait]
+impl ObjectStore for S3Storage {
+    async fn put_object(&self, key: &str, data: Vec<u8>) -> Result<(), BackupError> {
+        S3Storage::put_object(self, key, &data).await.map_err(BackupError::RemoteError)
+    }
+
+    async fn put_file(&self, key: &str, path: &Path, sha256: &str) -> Result<(), BackupError> {
+        S3Storage::put_object_file(self, key, path, sha256).await.map_err(BackupError::RemoteError)
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, BackupError> {
+        S3Storage::get_object(self, key).await.map_err(BackupError::RemoteError)
+    }
+
+    async fn get_object_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>, BackupError> {
+        S3Storage::get_object_range(self, key, start, end).await.map_err(BackupError::RemoteError)
+    }
+
+    async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>, BackupError> {
+        Ok(S3Storage::head_object(self, key).await
+            .map_err(BackupError::RemoteError)?
+            .map(s3_object_info))
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), BackupError> {
+        S3Storage::delete_object(self, key).await.map_err(BackupError::RemoteError)
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>, BackupError> {
+        Ok(S3Storage::list_objects(self, prefix).await
+            .map_err(BackupError::RemoteError)?
+            .into_iter()
+            .map(s3_object_info)
+            .collect())
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, BackupError> {
+        S3Storage::create_multipart_upload(self, key).await.map_err(BackupError::RemoteError)
+    }
+
+    async fn upload_part(&self, key: &str, upload_id: &str, part_number: u32, data: Vec<u8>) -> Result<UploadedPart, BackupError> {
+        let part = S3Storage::upload_part(self, key, upload_id, part_number, data).await
+            .map_err(BackupError::RemoteError)?;
+        Ok(UploadedPart { part_number: part.part_number, etag: part.etag, size: part.size })
+    }
+
+    async fn list_parts(&self, key: &str, upload_id: &str) -> Result<Vec<UploadedPart>, BackupError> {
+        Ok(S3Storage::list_parts(self, key, upload_id).await
+            .map_err(BackupError::RemoteError)?
+            .into_iter()
+            .map(|p| UploadedPart { part_number: p.part_number, etag: p.etag, size: p.size })
+            .collect())
+    }
+
+    async fn complete_multipart_upload(&self, key: &str, upload_id: &str, parts: Vec<UploadedPart>) -> Result<(), BackupError> {
+        let parts: Vec<S3Part> = parts.into_iter()
+            .map(|p| S3Part { part_number: p.part_number, etag: p.etag, size: p.size })
+            .collect();
+        S3Storage::complete_multipart_upload(self, key, upload_id, &parts).await.map_err(BackupError::RemoteError)
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), BackupError> {
+        S3Storage::abort_multipart_upload(self, key, upload_id).await.map_err(BackupError::RemoteError)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadReport {
+    pub backup_id: String,
+    pub key: String,
+    pub size_bytes: u64,
+    /// Zero for single-request uploads
+    pub parts: usize,
+    /// Parts already present from an earlier, interrupted attempt
+    pub resumed_parts: usize,
+}
+
+/// Multipart state persisted next to the backups so an interrupted upload can resume.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UploadState {
+    key: String,
+    upload_id: String,
+    part_size: usize,
+}
+
+const METADATA_SUFFIX: &str = ".json";
+
+impl BackupManager {
+    fn object_store(&self) -> Result<&Arc<dyn ObjectStore>, BackupError> {
+        self.object_store.as_ref()
+            .ok_or_else(|| BackupError::RemoteError("no object store configured".to_string()))
+    }
+
+    fn remote_config(&self) -> RemoteBackupConfig {
+        self.config.remote.clone().unwrap_or_default()
+    }
+
+    fn remote_key(&self, name: &str) -> String {
+        let prefix = self.remote_config().prefix;
+        let prefix = prefix.trim_end_matches('/');
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", prefix, name)
+        }
+    }
+
+    fn uploads_dir(&self) -> PathBuf {
+        PathBuf::from(&self.config.backup_dir).join(".uploads")
+    }
+
+    /// Whether completed backups should be pushed to the object store right away.
+    pub fn uploads_on_create(&self) -> bool {
+        self.object_store.is_some() && self.remote_config().upload_on_create
+    }
+
+    /// Uploads a completed backup as a single archive object followed by its metadata.
+    /// Archives above `multipart_threshold` go up in parts; if an upload is
+    /// interrupted, calling this again skips the parts the store already has.
+    pub async fn upload_backup(&self, backup_id: &str) -> Result<UploadReport, BackupError> {
+        let store = self.object_store()?.clone();
+        let remote = self.remote_config();
+
+        let mut metadata = self.get_backup(backup_id).await
+            .ok_or_else(|| BackupError::BackupNotFound(backup_id.to_string()))?;
+        if metadata.status != BackupStatus::Completed {
+            return Err(BackupError::BackupIncomplete(backup_id.to_string()));
+        }
+
+        fs::create_dir_all(self.uploads_dir())
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        // Directory backups are packed without compression or encryption so the
+        // remote copy is always a single archive. The packed file is kept until the
+        // upload completes so a resumed upload sends identical bytes.
+        let stored = self.stored_path(backup_id);
+        let source = if stored.is_dir() {
+            let packed = self.uploads_dir().join(format!("{}.{}", backup_id, archive::ARCHIVE_EXTENSION));
+            if !packed.exists() {
+                Self::write_archive(&stored, &packed, false, None).await?;
+            }
+            packed
+        } else if stored.exists() {
+            stored
+        } else {
+            return Err(BackupError::BackupNotFound(backup_id.to_string()));
+        };
+
+        let size_bytes = fs::metadata(&source)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?
+            .len();
+        metadata.checksum = self.calculate_checksum(&source).await?;
+
+        let key = self.remote_key(&format!("{}.{}", backup_id, archive::ARCHIVE_EXTENSION));
+
+        // Large archives are read one part at a time
+        let (parts, resumed_parts) = if size_bytes > remote.multipart_threshold as u64 {
+            self.multipart_upload(&store, backup_id, &key, &source, size_bytes, remote.part_size.max(1)).await?
+        } else {
+            store.put_file(&key, &source, &metadata.checksum).await?;
+            (0, 0)
+        };
+
+        let metadata_bytes = serde_json::to_vec_pretty(&metadata)
+            .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+        store.put_object(&self.remote_key(&format!("{}{}", backup_id, METADATA_SUFFIX)), metadata_bytes).await?;
+
+        if source != self.stored_path(backup_id) {
+            let _ = fs::remove_file(&source).await;
+        }
+
+        Ok(UploadReport {
+            backup_id: backup_id.to_string(),
+            key,
+            size_bytes,
+            parts,
+            resumed_parts,
+        })
+    }
+
+    async fn multipart_upload(
+        &self,
+        store: &Arc<dyn ObjectStore>,
+        backup_id: &str,
+        key: &str,
+        source: &Path,
+        size: u64,
+        part_size: usize,
+    ) -> Result<(usize, usize), BackupError> {
+        let state_path = self.uploads_dir().join(format!("{}.json", backup_id));
+
+        let previous: Option<UploadState> = match fs::read(&state_path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).ok(),
+            Err(_) => None,
+        };
+
+        // Resume only if the store still knows the upload and the part layout matches
+        let mut existing: HashMap<u32, UploadedPart> = HashMap::new();
+        let state = match previous {
+            Some(state) if state.key == key && state.part_size == part_size => {
+                match store.list_parts(key, &state.upload_id).await {
+                    Ok(parts) => {
+                        existing = parts.into_iter().map(|p| (p.part_number, p)).collect();
+                        Some(state)
+                    }
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+
+        let state = match state {
+            Some(state) => state,
+            None => {
+                let state = UploadState {
+                    key: key.to_string(),
+                    upload_id: store.create_multipart_upload(key).await?,
+                    part_size,
+                };
+                let bytes = serde_json::to_vec(&state)
+                    .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+                fs::write(&state_path, bytes)
+                    .await
+                    .map_err(|e| BackupError::IoError(e.to_string()))?;
+                state
+            }
+        };
+
+        let mut file = fs::File::open(source)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+        let mut completed = Vec::new();
+        let mut resumed = 0;
+        for (index, offset) in (0..size).step_by(part_size).enumerate() {
+            let part_number = index as u32 + 1;
+            let len = (size - offset).min(part_size as u64);
+            match existing.remove(&part_number) {
+                Some(part) if part.size == len => {
+                    resumed += 1;
+                    completed.push(part);
+                }
+                _ => {
+                    let mut chunk = vec![0u8; len as usize];
+                    file.seek(SeekFrom::Start(offset))
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+                    file.read_exact(&mut chunk)
+                        .await
+                        .map_err(|e| BackupError::IoError(e.to_string()))?;
+                    // On failure the state file stays behind for the next attempt
+                    completed.push(store.upload_part(key, &state.upload_id, part_number, chunk).await?);
+                }
+            }
+        }
+
+        let parts = completed.len();
+        store.complete_multipart_upload(key, &state.upload_id, completed).await?;
+        let _ = fs::remove_file(&state_path).await;
+
+        Ok((parts, resumed))
+    }
+
+    /// Abandons an interrupted multipart upload and frees the parts held by the store.
+    pub async fn abort_upload(&self, backup_id: &str) -> Result<bool, BackupError> {
+        let store = self.object_store()?;
+        let state_path = self.uploads_dir().join(format!("{}.json", backup_id));
+
+        let state: UploadState = match fs::read(&state_path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| BackupError::SerializationError(e.to_string()))?,
+            Err(_) => return Ok(false),
+        };
+
+        store.abort_multipart_upload(&state.key, &state.upload_id).await?;
+        let _ = fs::remove_file(&state_path).await;
+        let _ = fs::remove_file(self.uploads_dir().join(format!("{}.{}", backup_id, archive::ARCHIVE_EXTENSION))).await;
+
+        Ok(true)
+    }
+
+    /// Metadata of every backup whose upload completed, newest first.
+    pub async fn list_remote_backups(&self) -> Result<Vec<BackupMetadata>, BackupError> {
+        let store = self.object_store()?;
+        let prefix = self.remote_key("");
+
+        let mut backups = Vec::new();
+        for object in store.list_objects(&prefix).await? {
+            if !object.key.ends_with(METADATA_SUFFIX) {
+                continue;
+            }
+            let bytes = store.get_object(&object.key).await?;
+            let metadata: BackupMetadata = serde_json::from_slice(&bytes)
+                .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+            backups.push(metadata);
+        }
+
+        backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(backups)
+    }
+
+    /// Fetches a remote backup into `backup_dir` and registers it locally. The
+    /// archive is downloaded in ranges into a `.part` file, so an interrupted
+    /// download continues where it stopped.
+    pub async fn download_backup(&self, backup_id: &str) -> Result<BackupMetadata, BackupError> {
+        let store = self.object_store()?.clone();
+        let chunk_size = self.remote_config().part_size.max(1) as u64;
+
+        let metadata_bytes = store.get_object(&self.remote_key(&format!("{}{}", backup_id, METADATA_SUFFIX))).await
+            .map_err(|_| BackupError::BackupNotFound(backup_id.to_string()))?;
+        let mut metadata: BackupMetadata = serde_json::from_slice(&metadata_bytes)
+            .map_err(|e| BackupError::SerializationError(e.to_string()))?;
+
+        let key = self.remote_key(&format!("{}.{}", backup_id, archive::ARCHIVE_EXTENSION));
+        let size = store.head_object(&key).await?
+            .ok_or_else(|| BackupError::BackupNotFound(backup_id.to_string()))?
+            .size;
+
+        let archive_path = self.archive_path(backup_id);
+        let partial = archive_path.with_extension("part");
+
+        let mut offset = match fs::metadata(&partial).await {
+            Ok(existing) if existing.len() <= size => existing.len(),
+            _ => 0,
+        };
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(offset > 0)
+            .truncate(offset == 0)
+            .open(&partial)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        while offset < size {
+            let end = (offset + chunk_size).min(size);
+            let chunk = store.get_object_range(&key, offset, end).await?;
+            if chunk.is_empty() {
+                return Err(BackupError::RemoteError(format!("short read at offset {} of {}", offset, key)));
+            }
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| BackupError::IoError(e.to_string()))?;
+            offset += chunk.len() as u64;
+        }
+        file.flush()
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+        drop(file);
+
+        let checksum = self.calculate_checksum(&partial).await?;
+        if checksum != metadata.checksum {
+            let _ = fs::remove_file(&partial).await;
+            return Err(BackupError::IntegrityError(format!("downloaded archive for {} does not match its checksum", backup_id)));
+        }
+
+        // A directory copy of the same backup would shadow the archive
+        self.remove_stored_backup(backup_id).await?;
+        fs::rename(&partial, &archive_path)
+            .await
+            .map_err(|e| BackupError::IoError(e.to_string()))?;
+
+        metadata.status = BackupStatus::Completed;
+        self.backups.write().await.insert(backup_id.to_string(), metadata.clone());
+        self.save_backup_metadata().await?;
+
+        Ok(metadata)
+    }
+
+    /// Removes a backup's archive and metadata from the object store.
+    pub async fn delete_remote_backup(&self, backup_id: &str) -> Result<(), BackupError> {
+        let store = self.object_store()?;
+        store.delete_object(&self.remote_key(&format!("{}{}", backup_id, METADATA_SUFFIX))).await?;
+        store.delete_object(&self.remote_key(&format!("{}.{}", backup_id, archive::ARCHIVE_EXTENSION))).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{BackupConfig, BackupType};
+
+    async fn manager(root: &std::path::Path, store: Arc<InMemoryObjectStore>, remote: RemoteBackupConfig) -> BackupManager {
+        let config = BackupConfig {
+            backup_dir: root.join("backups").to_string_lossy().to_string(),
+            compression_enabled: false,
+            remote: Some(remote),
+            ..Default::default()
+        };
+        let manager = BackupManager::new(config, &root.join("data").to_string_lossy())
+            .with_object_store(store);
+        manager.initialize().await.unwrap();
+        manager
+    }
+
+    async fn write_collection(root: &std::path::Path, bytes: usize) {
+        let collection = root.join("data/collections/docs");
+        fs::create_dir_all(&collection).await.unwrap();
+        let payload: Vec<u8> = (0..bytes).map(|i| (i * 31 % 251) as u8).collect();
+        fs::write(collection.join("vectors.bin"), payload).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_list_and_download() {
+        let store = Arc::new(InMemoryObjectStore::new());
+        let source = tempfile::tempdir().unwrap();
+        write_collection(source.path(), 4096).await;
+
+        let primary = manager(source.path(), store.clone(), RemoteBackupConfig::default()).await;
+        let backup_id = primary.create_backup("nightly", BackupType::Full).await.unwrap();
+        let report = primary.upload_backup(&backup_id).await.unwrap();
+        assert_eq!(report.parts, 0);
+
+        let target = tempfile::tempdir().unwrap();
+        let replica = manager(target.path(), store.clone(), RemoteBackupConfig::default()).await;
+        let remote = replica.list_remote_backups().await.unwrap();
+        assert_eq!(remote.len(), 1);
+        assert_eq!(remote[0].id, backup_id);
+
+        replica.download_backup(&backup_id).await.unwrap();
+        assert!(replica.verify_backup(&backup_id).await.unwrap());
+        replica.restore_backup(&backup_id).await.unwrap();
+
+        let restored = fs::read(target.path().join("data/collections/docs/vectors.bin")).await.unwrap();
+        let original = fs::read(source.path().join("data/collections/docs/vectors.bin")).await.unwrap();
+        assert_eq!(restored, original);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_resumes_after_failure() {
+        let store = Arc::new(InMemoryObjectStore::new());
+        let root = tempfile::tempdir().unwrap();
+        write_collection(root.path(), 16 * 1024).await;
+
+        let remote = RemoteBackupConfig {
+            multipart_threshold: 1024,
+            part_size: 2048,
+            ..Default::default()
+        };
+        let manager = manager(root.path(), store.clone(), remote).await;
+        let backup_id = manager.create_backup("large", BackupType::Full).await.unwrap();
+
+        store.fail_parts_after(3);
+        assert!(manager.upload_backup(&backup_id).await.is_err());
+        assert_eq!(store.pending_uploads().await, 1);
+        assert!(manager.list_remote_backups().await.unwrap().is_empty());
+
+        store.clear_faults();
+        let report = manager.upload_backup(&backup_id).await.unwrap();
+        assert!(report.parts > 3);
+        assert_eq!(report.resumed_parts, 3);
+        assert_eq!(store.pending_uploads().await, 0);
+
+        // The parts read from the archive add up to it, and its checksum is
+        // its SHA-256
+        use sha2::{Digest, Sha256};
+        let object = store.get_object(&report.key).await.unwrap();
+        assert_eq!(object.len() as u64, report.size_bytes);
+        let remote = manager.list_remote_backups().await.unwrap();
+        assert_eq!(remote.len(), 1);
+        assert_eq!(remote[0].checksum, hex::encode(Sha256::digest(&object)));
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes_partial_file() {
+        let store = Arc::new(InMemoryObjectStore::new());
+        let source = tempfile::tempdir().unwrap();
+        write_collection(source.path(), 8192).await;
+
+        let remote = RemoteBackupConfig { part_size: 1000, ..Default::default() };
+        let primary = manager(source.path(), store.clone(), remote.clone()).await;
+        let backup_id = primary.create_backup("nightly", BackupType::Full).await.unwrap();
+        let report = primary.upload_backup(&backup_id).await.unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let replica = manager(target.path(), store.clone(), remote).await;
+
+        // Simulate a download that stopped half way through
+        let object = store.get_object(&report.key).await.unwrap();
+        let partial = replica.archive_path(&backup_id).with_extension("part");
+        fs::write(&partial, &object[..object.len() / 2]).await.unwrap();
+
+        let metadata = replica.download_backup(&backup_id).await.unwrap();
+        assert_eq!(metadata.status, BackupStatus::Completed);
+        assert!(!partial.exists());
+        assert_eq!(fs::read(replica.archive_path(&backup_id)).await.unwrap(), object);
+    }
+}
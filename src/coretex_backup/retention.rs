@@ -0,0 +1,161 @@
+//! Backup retention with grandfather-father-son rotation
+
+use std::collections::{HashMap, HashSet};
+use chrono::{Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{BackupMetadata, BackupStatus};
+
+/// Grandfather-father-son retention. Everything newer than `retention_days` is
+/// kept; older backups survive only as the newest backup of one of the last
+/// `keep_daily` days, `keep_weekly` ISO weeks or `keep_monthly` months.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_daily: 7,
+            keep_weekly: 4,
+            keep_monthly: 12,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Returns the ids of backups that may be deleted at `now`.
+    /// Parents of retained incremental backups are always retained.
+    pub fn expired(&self, backups: &[BackupMetadata], retention_days: i32, now: i64) -> Vec<String> {
+        let cutoff = now - retention_days as i64 * 86400;
+        let mut keep: HashSet<&str> = backups.iter()
+            .filter(|b| b.created_at >= cutoff || b.status == BackupStatus::InProgress)
+            .map(|b| b.id.as_str())
+            .collect();
+
+        let mut completed: Vec<&BackupMetadata> = backups.iter()
+            .filter(|b| b.status == BackupStatus::Completed)
+            .collect();
+        completed.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+        let date_of = |b: &BackupMetadata| Utc.timestamp_opt(b.created_at, 0).single().unwrap_or_default();
+
+        keep.extend(Self::newest_per_bucket(&completed, self.keep_daily, |b| {
+            let d = date_of(b);
+            (d.year(), d.ordinal())
+        }));
+        keep.extend(Self::newest_per_bucket(&completed, self.keep_weekly, |b| {
+            let w = date_of(b).iso_week();
+            (w.year(), w.week())
+        }));
+        keep.extend(Self::newest_per_bucket(&completed, self.keep_monthly, |b| {
+            let d = date_of(b);
+            (d.year(), d.month())
+        }));
+
+        let by_id: HashMap<&str, &BackupMetadata> = backups.iter().map(|b| (b.id.as_str(), b)).collect();
+        let mut pending: Vec<&str> = keep.iter().copied().collect();
+        while let Some(id) = pending.pop() {
+            if let Some(parent) = by_id.get(id).and_then(|b| b.parent_backup_id.as_deref()) {
+                if keep.insert(parent) {
+                    pending.push(parent);
+                }
+            }
+        }
+
+        backups.iter()
+            .filter(|b| !keep.contains(b.id.as_str()))
+            .map(|b| b.id.clone())
+            .collect()
+    }
+
+    /// `backups` must be sorted newest first.
+    fn newest_per_bucket<'a, K, F>(backups: &[&'a BackupMetadata], limit: usize, bucket: F) -> Vec<&'a str>
+    where
+        K: Eq + std::hash::Hash,
+        F: Fn(&BackupMetadata) -> K,
+    {
+        let mut seen = HashSet::new();
+        let mut kept = Vec::new();
+
+        for backup in backups {
+            if seen.len() >= limit {
+                break;
+            }
+            if seen.insert(bucket(backup)) {
+                kept.push(backup.id.as_str());
+            }
+        }
+
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::BackupType;
+
+    const DAY: i64 = 86400;
+
+    fn backup(id: &str, created_at: i64, parent: Option<&str>) -> BackupMetadata {
+        BackupMetadata {
+            id: id.to_string(),
+            name: id.to_string(),
+            backup_type: if parent.is_some() { BackupType::Incremental } else { BackupType::Full },
+            created_at,
+            size_bytes: 0,
+            collection_count: 0,
+            vector_count: 0,
+            checksum: String::new(),
+            parent_backup_id: parent.map(String::from),
+            status: BackupStatus::Completed,
+            compressed: false,
+            encryption_key_id: None,
+            last_lsn: None,
+        }
+    }
+
+    #[test]
+    fn test_gfs_rotation() {
+        // 2024-03-31 00:00:00 UTC, one backup per day for 90 days
+        let now = 1_711_843_200;
+        let backups: Vec<BackupMetadata> = (0..90)
+            .map(|d| backup(&format!("b{}", d), now - d * DAY, None))
+            .collect();
+
+        let policy = RetentionPolicy { keep_daily: 3, keep_weekly: 2, keep_monthly: 3 };
+        let expired = policy.expired(&backups, 0, now);
+        let kept: Vec<&str> = backups.iter()
+            .map(|b| b.id.as_str())
+            .filter(|id| !expired.iter().any(|e| e == id))
+            .collect();
+
+        // last three days, plus the newest of the previous week, plus the newest
+        // of February and January
+        assert!(kept.contains(&"b0") && kept.contains(&"b1") && kept.contains(&"b2"));
+        assert!(kept.contains(&"b7"));
+        assert!(kept.contains(&"b31"));
+        assert!(kept.contains(&"b60"));
+        assert_eq!(kept.len(), 6);
+    }
+
+    #[test]
+    fn test_keeps_parents_of_incrementals() {
+        let now = 1_711_843_200;
+        let backups = vec![
+            backup("full", now - 40 * DAY, None),
+            backup("inc1", now - 39 * DAY, Some("full")),
+            backup("inc2", now - DAY, Some("inc1")),
+            backup("old", now - 50 * DAY, None),
+        ];
+
+        let policy = RetentionPolicy { keep_daily: 0, keep_weekly: 0, keep_monthly: 0 };
+        let expired = policy.expired(&backups, 30, now);
+
+        assert_eq!(expired, vec!["old".to_string()]);
+    }
+}
@@ -0,0 +1,384 @@
+//! Background backup scheduling
+//! Runs backups on cron-like schedules and applies retention after each run
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use super::{BackupError, BackupManager, BackupSchedule, BackupType};
+use crate::coretex_monitoring_v2::{AlertCondition, AlertRule, AlertSeverity, MetricsCollector};
+
+/// A five-field cron expression: `minute hour day-of-month month day-of-week`.
+/// Supports `*`, lists, ranges and steps, plus the `@hourly`/`@daily`/`@weekly`/`@monthly` macros.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days_of_month: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    days_of_week: BTreeSet<u32>,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, BackupError> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(BackupError::InvalidSchedule(format!(
+                "expected 5 fields in '{}', found {}", expression, fields.len()
+            )));
+        }
+
+        let mut days_of_week = Self::parse_field(fields[4], 0, 7)?;
+        if days_of_week.remove(&7) {
+            days_of_week.insert(0);
+        }
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: Self::parse_field(fields[0], 0, 59)?,
+            hours: Self::parse_field(fields[1], 0, 23)?,
+            days_of_month: Self::parse_field(fields[2], 1, 31)?,
+            months: Self::parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            dom_restricted: fields[2] != "*",
+            dow_restricted: fields[4] != "*",
+        })
+    }
+
+    fn parse_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>, BackupError> {
+        let invalid = || BackupError::InvalidSchedule(format!("invalid cron field '{}'", field));
+        let mut values = BTreeSet::new();
+
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+                None => (part, 1),
+            };
+            if step == 0 {
+                return Err(invalid());
+            }
+
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some((a, b)) = range.split_once('-') {
+                (a.parse().map_err(|_| invalid())?, b.parse().map_err(|_| invalid())?)
+            } else {
+                let value: u32 = range.parse().map_err(|_| invalid())?;
+                // `5/15` means "from 5 to the end of the range every 15"
+                if part.contains('/') { (value, max) } else { (value, value) }
+            };
+
+            if start < min || end > max || start > end {
+                return Err(invalid());
+            }
+
+            values.extend((start..=end).step_by(step as usize));
+        }
+
+        Ok(values)
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool {
+        let dom = self.days_of_month.contains(&time.day());
+        let dow = self.days_of_week.contains(&time.weekday().num_days_from_sunday());
+
+        // Standard cron semantics: when both day fields are restricted either may match.
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+
+    /// Returns the first matching minute strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = Utc
+            .with_ymd_and_hms(after.year(), after.month(), after.day(), after.hour(), after.minute(), 0)
+            .single()?
+            + ChronoDuration::minutes(1);
+        let limit = after.year() + 5;
+
+        while time.year() <= limit {
+            if !self.months.contains(&time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+                continue;
+            }
+            if !self.day_matches(&time) {
+                time = Utc.with_ymd_and_hms(time.year(), time.month(), time.day(), 0, 0, 0).single()?
+                    + ChronoDuration::days(1);
+                continue;
+            }
+            if !self.hours.contains(&time.hour()) {
+                time = Utc.with_ymd_and_hms(time.year(), time.month(), time.day(), time.hour(), 0, 0).single()?
+                    + ChronoDuration::hours(1);
+                continue;
+            }
+            if !self.minutes.contains(&time.minute()) {
+                time += ChronoDuration::minutes(1);
+                continue;
+            }
+            return Some(time);
+        }
+
+        None
+    }
+}
+
+impl BackupSchedule {
+    /// Resolves the schedule to a cron expression; `Manual` has none.
+    pub fn cron(&self) -> Result<Option<CronSchedule>, BackupError> {
+        match self {
+            BackupSchedule::Manual => Ok(None),
+            BackupSchedule::Hourly => CronSchedule::parse("@hourly").map(Some),
+            BackupSchedule::Daily => CronSchedule::parse("@daily").map(Some),
+            BackupSchedule::Weekly => CronSchedule::parse("@weekly").map(Some),
+            BackupSchedule::Cron(expr) => CronSchedule::parse(expr).map(Some),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerStats {
+    pub runs: u64,
+    pub failures: u64,
+    pub consecutive_failures: u64,
+    pub last_backup_id: Option<String>,
+    pub last_error: Option<String>,
+    pub last_run_at: Option<i64>,
+}
+
+pub struct BackupScheduler {
+    manager: Arc<BackupManager>,
+    schedule: CronSchedule,
+    metrics: Option<MetricsCollector>,
+    full_backup_every: u64,
+    runs: AtomicU64,
+    stats: tokio::sync::RwLock<SchedulerStats>,
+    running: AtomicBool,
+    shutdown: Notify,
+}
+
+impl BackupScheduler {
+    pub fn new(manager: Arc<BackupManager>, schedule: CronSchedule) -> Self {
+        Self {
+            manager,
+            schedule,
+            metrics: None,
+            full_backup_every: 7,
+            runs: AtomicU64::new(0),
+            stats: tokio::sync::RwLock::new(SchedulerStats::default()),
+            running: AtomicBool::new(false),
+            shutdown: Notify::new(),
+        }
+    }
+
+    /// Builds a scheduler from the manager's configured `BackupSchedule`.
+    /// Returns `None` for manual schedules.
+    pub fn from_config(manager: Arc<BackupManager>) -> Result<Option<Self>, BackupError> {
+        Ok(manager.config().schedule.cron()?.map(|schedule| Self::new(manager, schedule)))
+    }
+
+    pub fn with_metrics(mut self, metrics: MetricsCollector) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// When incremental backups are enabled, every n-th run is a full backup.
+    pub fn with_full_backup_every(mut self, runs: u64) -> Self {
+        self.full_backup_every = runs.max(1);
+        self
+    }
+
+    pub fn schedule(&self) -> &CronSchedule {
+        &self.schedule
+    }
+
+    pub async fn stats(&self) -> SchedulerStats {
+        self.stats.read().await.clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        self.running.store(true, Ordering::SeqCst);
+        let scheduler = self.clone();
+
+        tokio::spawn(async move {
+            while scheduler.is_running() {
+                let now = Utc::now();
+                let next = match scheduler.schedule.next_after(now) {
+                    Some(next) => next,
+                    None => break,
+                };
+                let wait = (next - now).to_std().unwrap_or_default();
+
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = scheduler.shutdown.notified() => break,
+                }
+
+                if !scheduler.is_running() {
+                    break;
+                }
+
+                let _ = scheduler.run_once().await;
+            }
+        })
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.shutdown.notify_waiters();
+    }
+
+    /// Takes one scheduled backup, applies retention, uploads it when a remote
+    /// target is configured and records the outcome.
+    pub async fn run_once(&self) -> Result<String, BackupError> {
+        let run = self.runs.fetch_add(1, Ordering::SeqCst);
+        let backup_type = if self.manager.config().incremental_enabled && run % self.full_backup_every != 0 {
+            BackupType::Incremental
+        } else {
+            BackupType::Full
+        };
+
+        let started = Instant::now();
+        let result = match self.manager.create_backup("scheduled", backup_type).await {
+            Ok(id) => self.manager.apply_retention().await.map(|_| id),
+            Err(e) => Err(e),
+        };
+        let result = match result {
+            Ok(id) if self.manager.uploads_on_create() => self.manager.upload_backup(&id).await.map(|_| id),
+            other => other,
+        };
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let mut stats = self.stats.write().await;
+        stats.runs += 1;
+        stats.last_run_at = Some(Utc::now().timestamp());
+
+        match &result {
+            Ok(id) => {
+                stats.consecutive_failures = 0;
+                stats.last_backup_id = Some(id.clone());
+                stats.last_error = None;
+            }
+            Err(e) => {
+                stats.failures += 1;
+                stats.consecutive_failures += 1;
+                stats.last_error = Some(e.to_string());
+            }
+        }
+
+        if let Some(metrics) = &self.metrics {
+            let status = if result.is_ok() { "success" } else { "failure" };
+            let labels: HashMap<String, String> = [("status".to_string(), status.to_string())].into();
+
+            metrics.increment_counter("coretex_backup_runs_total", 1.0, labels).await;
+            metrics.observe_histogram("coretex_backup_duration_seconds", elapsed, HashMap::new()).await;
+            metrics.set_gauge("coretex_backup_consecutive_failures", stats.consecutive_failures as f64, HashMap::new()).await;
+            if result.is_ok() {
+                metrics.set_gauge("coretex_backup_last_success_timestamp", Utc::now().timestamp() as f64, HashMap::new()).await;
+            }
+
+            metrics.check_alerts(&[Self::failure_alert_rule()]).await;
+        }
+
+        result
+    }
+
+    /// Fires whenever the most recent scheduled backup failed.
+    pub fn failure_alert_rule() -> AlertRule {
+        AlertRule {
+            name: "backup_failed".to_string(),
+            metric: "coretex_backup_consecutive_failures".to_string(),
+            condition: AlertCondition::Above,
+            threshold: 0.0,
+            severity: AlertSeverity::Error,
+            message: "Scheduled backup failed".to_string(),
+            enabled: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::BackupConfig;
+    use crate::coretex_monitoring_v2::MonitoringConfig;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let hourly = CronSchedule::parse("@hourly").unwrap();
+        assert_eq!(hourly.next_after(at(2024, 1, 1, 10, 30)), Some(at(2024, 1, 1, 11, 0)));
+
+        let every_15 = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_15.next_after(at(2024, 1, 1, 10, 15)), Some(at(2024, 1, 1, 10, 30)));
+
+        // 02:30 on weekdays; 2024-01-06 is a Saturday
+        let weekdays = CronSchedule::parse("30 2 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(at(2024, 1, 6, 0, 0)), Some(at(2024, 1, 8, 2, 30)));
+
+        let monthly = CronSchedule::parse("@monthly").unwrap();
+        assert_eq!(monthly.next_after(at(2024, 12, 15, 0, 0)), Some(at(2025, 1, 1, 0, 0)));
+    }
+
+    #[test]
+    fn test_cron_rejects_invalid() {
+        assert!(CronSchedule::parse("* * *").is_err());
+        assert!(CronSchedule::parse("61 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(BackupSchedule::Manual.cron().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_run_raises_alert() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let blocker = temp_dir.path().join("not_a_dir");
+        tokio::fs::write(&blocker, b"").await.unwrap();
+
+        // backup_dir points at a regular file, so creating the backup fails
+        let config = BackupConfig {
+            backup_dir: blocker.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let manager = Arc::new(BackupManager::new(config, &temp_dir.path().to_string_lossy()));
+        let metrics = MetricsCollector::new(MonitoringConfig::default());
+        let mut alerts = metrics.alert_receiver();
+
+        let scheduler = BackupScheduler::new(manager, CronSchedule::parse("@daily").unwrap())
+            .with_metrics(metrics);
+
+        assert!(scheduler.run_once().await.is_err());
+        assert_eq!(scheduler.stats().await.consecutive_failures, 1);
+
+        let alert = alerts.try_recv().unwrap();
+        assert_eq!(alert.name, "backup_failed");
+    }
+}
@@ -6,7 +6,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::net::SocketAddr;
 
-use crate::{CoreTexDB, DbConfig, ApiConfig, start_server};
+use crate::{CoreTexDB, DbConfig, ApiConfig, LakehouseConfig, TieringDaemonConfig, start_server};
+use crate::coretex_backup::{BackupConfig, BackupSchedule};
 
 /// Run the CLI
 pub fn run_cli() -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -49,6 +50,22 @@ async fn run_cli_async() -> Result<(), Box<dyn Error + Send + Sync>> {
                     .long("data-dir")
                     .help("Directory to store data")
                     .default_value("./data"),
+            )
+            .arg(
+                Arg::new("backup-schedule")
+                    .long("backup-schedule")
+                    .help("Cron expression for scheduled backups (e.g. \"0 2 * * *\")"),
+            )
+            .arg(
+                Arg::new("tiering-interval")
+                    .long("tiering-interval")
+                    .help("Seconds between lakehouse tiering passes; enables the lakehouse"),
+            )
+            .arg(
+                Arg::new("durable")
+                    .long("durable")
+                    .help("Keep documents and the journal under the data directory")
+                    .action(ArgAction::SetTrue),
             ),
     );
 
@@ -189,10 +206,32 @@ async fn run_cli_async() -> Result<(), Box<dyn Error + Send + Sync>> {
             println!("Starting CoreTexDB server on {}:{}", address, port);
             println!("Data directory: {}", data_dir);
 
+            let backup = sub_matches.get_one::<String>("backup-schedule").map(|expr| BackupConfig {
+                backup_dir: format!("{}/backups", data_dir),
+                schedule: BackupSchedule::Cron(expr.clone()),
+                ..Default::default()
+            });
+
+            let lakehouse = match sub_matches.get_one::<String>("tiering-interval") {
+                Some(secs) => Some(LakehouseConfig {
+                    tiering: TieringDaemonConfig {
+                        interval_secs: secs.parse().map_err(|_| format!("Invalid tiering interval: {}", secs))?,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+                None => None,
+            };
+
             let config = ApiConfig {
                 address: address.clone(),
                 port: port.parse().unwrap(),
                 enable_cors: true,
+                backup,
+                db: DbConfig::new(data_dir),
+                durable: sub_matches.get_flag("durable"),
+                lakehouse,
+                ..Default::default()
             };
 
             start_server(config).await?;
@@ -290,7 +329,7 @@ async fn run_cli_async() -> Result<(), Box<dyn Error + Send + Sync>> {
 
                     let db_ref = db.clone();
                     let db_guard = db_ref.read().await;
-                    match db_guard.get_vector(collection, id).await {
+                    match db_guard.get_vector(collection, id, None).await {
                         Ok(Some((vector, metadata))) => {
                             println!("Vector ID: {}", id);
                             println!("Vector: {:?}", &vector[..10.min(vector.len())]);
@@ -341,7 +380,7 @@ async fn run_cli_async() -> Result<(), Box<dyn Error + Send + Sync>> {
                 .collect();
 
             let db_ref = db.clone();
-            let results = db_ref.read().await.search(collection, vector, k, None).await
+            let results = db_ref.read().await.search(collection, vector, k, None, None).await
                 .map_err(|e| format!("Search failed: {}", e))?;
 
             println!("Search results from '{}' (k={}):", collection, k);
@@ -386,7 +425,7 @@ async fn run_cli_async() -> Result<(), Box<dyn Error + Send + Sync>> {
             
             for i in 0..10.min(count) {
                 let query: Vec<f32> = (0..dimension).map(|_| rand::random::<f32>()).collect();
-                let _ = db_ref.read().await.search(collection, query, 10, None).await;
+                let _ = db_ref.read().await.search(collection, query, 10, None, None).await;
             }
 
             let search_time = search_start.elapsed();
@@ -95,8 +95,39 @@ pub struct CollectionSchema {
     pub distance_metric: DistanceMetric, 
     pub indexes: Vec<IndexConfig>, 
     pub metadata_schema: Option<serde_json::Value>, 
+    /// Named vectors stored next to each document's main vector
+    #[serde(default)]
+    pub vector_fields: Vec<VectorField>,
+    /// Token-level vectors for late-interaction search, when enabled
+    #[serde(default)]
+    pub multi_vector: Option<MultiVectorConfig>,
 } 
 
+/// A named vector field with its own dimension, metric and index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorField {
+    pub name: String,
+    pub dimension: usize,
+    pub distance_metric: DistanceMetric,
+    pub index: IndexConfig,
+}
+
+/// Per-document token vectors scored with MaxSim late interaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiVectorConfig {
+    pub dimension: usize,
+    pub distance_metric: DistanceMetric,
+    /// Index over every token vector, used to pick candidate documents
+    pub index: IndexConfig,
+    /// Token hits fetched per query vector when picking candidates
+    #[serde(default = "default_candidates_per_token")]
+    pub candidates_per_token: usize,
+}
+
+fn default_candidates_per_token() -> usize {
+    100
+}
+
 /// Distance metric for vector similarity 
 #[derive(Debug, Clone, Serialize, Deserialize)] 
 pub enum DistanceMetric { 
@@ -106,6 +137,27 @@ pub enum DistanceMetric {
     Manhattan, 
 } 
 
+impl DistanceMetric {
+    /// Parse a metric name as indexes take it; unknown names mean cosine
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "euclidean" => DistanceMetric::Euclidean,
+            "dotproduct" => DistanceMetric::DotProduct,
+            "manhattan" => DistanceMetric::Manhattan,
+            _ => DistanceMetric::Cosine,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DistanceMetric::Cosine => "cosine",
+            DistanceMetric::Euclidean => "euclidean",
+            DistanceMetric::DotProduct => "dotproduct",
+            DistanceMetric::Manhattan => "manhattan",
+        }
+    }
+}
+
 /// Index configuration 
 #[derive(Debug, Clone, Serialize, Deserialize)] 
 pub struct IndexConfig { 
@@ -155,6 +207,9 @@ pub enum CoreTexError {
 
     #[error("Invalid dimension: {0}")]
     InvalidDimension(String),
+
+    #[error("Transaction error: {0}")]
+    TransactionError(String),
 } 
 
 pub type Result<T> = std::result::Result<T, CoreTexError>; 
\ No newline at end of file
@@ -0,0 +1,175 @@
+//! Raft for CoreTexDB
+//! `HttpRaftTransport` carries Raft messages between servers over the REST
+//! API (`POST /api/raft`), and `DbStateMachine` applies the WAL entries a
+//! Raft group commits to a database, the same way replicas apply what a
+//! replication leader ships.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use super::replication::{apply_entry, ReplicaSnapshot};
+use super::sharding::Envelope;
+use crate::coretex_utils::raft::{RaftError, RaftRequest, RaftResponse, RaftStateMachine, RaftTransport};
+use crate::coretex_utils::wal::{WalEntry, WalEntryType};
+use crate::CoreTexDB;
+
+/// Reaches other Raft nodes through the REST API of the servers hosting
+/// them.
+pub struct HttpRaftTransport {
+    client: reqwest::Client,
+    /// Base URLs by node id.
+    peers: std::sync::RwLock<HashMap<String, String>>,
+}
+
+impl HttpRaftTransport {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            peers: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn add_peer(&self, node_id: &str, base_url: &str) {
+        self.peers.write().unwrap().insert(node_id.to_string(), base_url.trim_end_matches('/').to_string());
+    }
+}
+
+impl Default for HttpRaftTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RaftTransport for HttpRaftTransport {
+    async fn send(&self, _from: &str, to: &str, request: RaftRequest) -> Result<RaftResponse, RaftError> {
+        let unreachable = |e: &dyn std::fmt::Display| RaftError::Unreachable(format!("{}: {}", to, e));
+        let base_url = self.peers.read().unwrap().get(to).cloned()
+            .ok_or_else(|| unreachable(&"no address known"))?;
+        let body = serde_json::to_string(&request).map_err(|e| unreachable(&e))?;
+
+        let response = self.client.post(format!("{}/api/raft", base_url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| unreachable(&e))?;
+        let bytes = response.bytes().await.map_err(|e| unreachable(&e))?;
+        let envelope: Envelope<RaftResponse> = serde_json::from_slice(&bytes).map_err(|e| unreachable(&e))?;
+        match (envelope.data, envelope.error) {
+            (Some(data), _) => Ok(data),
+            (None, error) => Err(unreachable(&error.unwrap_or_else(|| "empty response".to_string()))),
+        }
+    }
+}
+
+/// Applies committed WAL entries to a database. Snapshots are the whole
+/// database, as replication snapshots are.
+///
+/// A node reopened from disk re-applies the log after its snapshot to a
+/// database that may already hold it, so applying an entry again leaves the
+/// database as it was.
+#[derive(Clone)]
+pub struct DbStateMachine {
+    db: Arc<RwLock<CoreTexDB>>,
+}
+
+impl DbStateMachine {
+    pub fn new(db: Arc<RwLock<CoreTexDB>>) -> Self {
+        Self { db }
+    }
+}
+
+fn state_machine_error(e: impl std::fmt::Display) -> RaftError {
+    RaftError::StateMachineError(e.to_string())
+}
+
+#[async_trait]
+impl RaftStateMachine for DbStateMachine {
+    async fn apply(&mut self, _index: u64, entry: &WalEntry) -> Result<(), RaftError> {
+        let db = self.db.read().await;
+        let exists = db.list_collections().await.map_err(state_machine_error)?.contains(&entry.collection);
+        let applied = match entry.entry_type {
+            WalEntryType::DeleteCollection if !exists => false,
+            // Only schema entries replace a collection that exists
+            WalEntryType::CreateCollection if exists && entry.data.get("schema").is_none() => false,
+            // The collection was deleted by an earlier entry, which every
+            // node applies the same way
+            WalEntryType::Insert | WalEntryType::Update | WalEntryType::Delete if !exists => false,
+            _ => true,
+        };
+        if applied {
+            apply_entry(&db, entry).await.map_err(state_machine_error)?;
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> Result<Vec<u8>, RaftError> {
+        let snapshot = self.db.read().await.replica_snapshot().await.map_err(state_machine_error)?;
+        serde_json::to_vec(&snapshot).map_err(state_machine_error)
+    }
+
+    async fn restore(&mut self, data: &[u8]) -> Result<(), RaftError> {
+        let snapshot: ReplicaSnapshot = serde_json::from_slice(data).map_err(state_machine_error)?;
+        self.db.read().await.install_replica_snapshot(snapshot).await.map_err(state_machine_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::coretex_utils::raft::{sim::SimNetwork, RaftConfig, RaftNode};
+
+    fn insert(id: u64, doc: &str) -> WalEntry {
+        WalEntry {
+            id,
+            timestamp: 0,
+            entry_type: WalEntryType::Insert,
+            collection: "docs".to_string(),
+            data: serde_json::json!({"vectors": [{"id": doc, "vector": [1.0, 0.0], "metadata": {}}]}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_db_state_machine_applies_commits_and_reopens() {
+        let root = tempfile::tempdir().unwrap();
+        let raft_dir = root.path().join("raft");
+        let network = Arc::new(SimNetwork::new());
+        let members = vec!["solo".to_string()];
+        let db = Arc::new(RwLock::new(CoreTexDB::new()));
+
+        {
+            let node = RaftNode::open(&raft_dir, "solo", members.clone(), network.clone(), Box::new(DbStateMachine::new(db.clone())))
+                .await.unwrap()
+                .with_config(RaftConfig { snapshot_threshold: 3, ..RaftConfig::default() });
+            assert!(node.campaign().await);
+
+            let create = WalEntry {
+                id: 1,
+                timestamp: 0,
+                entry_type: WalEntryType::CreateCollection,
+                collection: "docs".to_string(),
+                data: serde_json::json!({"dimension": 2, "metric": "cosine"}),
+            };
+            for entry in [create, insert(2, "a"), insert(3, "b"), insert(4, "c")] {
+                let proposal = node.propose(entry).await.unwrap();
+                node.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+            }
+            assert!(node.status().await.snapshot_index > 0);
+        }
+        assert_eq!(db.read().await.get_vectors_count("docs").await.unwrap(), 3);
+
+        // Reopened over the same database, the snapshot and the log after it
+        // apply again without tripping over what is already there
+        let node = RaftNode::open(&raft_dir, "solo", members, network, Box::new(DbStateMachine::new(db.clone())))
+            .await.unwrap();
+        assert!(node.campaign().await);
+        let proposal = node.propose(insert(5, "d")).await.unwrap();
+        node.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(db.read().await.get_vectors_count("docs").await.unwrap(), 4);
+        assert!(node.status().await.last_storage_error.is_none());
+    }
+}
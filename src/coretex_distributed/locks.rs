@@ -0,0 +1,787 @@
+//! Cluster-wide leased locks
+//! Lock state is replicated through Raft: acquire, renew and release are
+//! commands in the Raft log, applied by a `LockTable` on every node, so all
+//! nodes agree on who holds a key. Non-leaders forward requests to the
+//! leader through a `LockTransport`; between servers that is
+//! `HttpLockTransport` over the REST API (`POST /api/locks`).
+//!
+//! A lease lasts `lease_ms` from the moment the leader stamped the request
+//! and must be renewed before then; `LockGuard` renews in the background.
+//! Every grant gets a fencing token larger than any before it, so storage
+//! can refuse writes from a holder whose lease lapsed without it noticing:
+//! stores run such writes through a `Fence`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use super::sharding::Envelope;
+use crate::coretex_utils::raft::{RaftError, RaftNode, RaftStateMachine};
+use crate::coretex_utils::wal::{WalEntry, WalEntryType};
+
+/// WAL entries for this collection carry lock commands.
+pub const LOCK_COLLECTION: &str = "__locks";
+
+pub type FencingToken = u64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LockError {
+    /// Carries the leader this node knows of, if any.
+    NotLeader(Option<String>),
+    Held { key: String, owner: String, expires_at_ms: u64 },
+    /// The lease expired or was taken over.
+    LeaseLost(String),
+    StaleToken { resource: String, token: FencingToken, highest: FencingToken },
+    ConsensusError(String),
+    /// The fence could not read or store its tokens.
+    StorageError(String),
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockError::NotLeader(Some(leader)) => write!(f, "Not the leader; leader is {}", leader),
+            LockError::NotLeader(None) => write!(f, "Not the leader; no leader known"),
+            LockError::Held { key, owner, expires_at_ms } => {
+                write!(f, "Lock {} is held by {} until {}", key, owner, expires_at_ms)
+            }
+            LockError::LeaseLost(key) => write!(f, "Lease on {} was lost", key),
+            LockError::StaleToken { resource, token, highest } => {
+                write!(f, "Fencing token {} for {} is older than {}", token, resource, highest)
+            }
+            LockError::ConsensusError(msg) => write!(f, "Consensus error: {}", msg),
+            LockError::StorageError(msg) => write!(f, "Fence storage error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+impl From<RaftError> for LockError {
+    fn from(e: RaftError) -> Self {
+        match e {
+            RaftError::NotLeader(leader) => LockError::NotLeader(leader),
+            e => LockError::ConsensusError(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LockConfig {
+    pub lease_ms: u64,
+    /// How often a `LockGuard` renews; well under `lease_ms`.
+    pub renew_interval_ms: u64,
+    /// How long to wait for a lock command to commit.
+    pub commit_timeout_ms: u64,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            lease_ms: 10_000,
+            renew_interval_ms: 3_000,
+            commit_timeout_ms: 5_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    pub key: String,
+    pub owner: String,
+    pub token: FencingToken,
+    /// By the clock of the leader that granted or last renewed it.
+    pub expires_at_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LockRequest {
+    Acquire { key: String, owner: String },
+    Renew { key: String, owner: String, token: FencingToken },
+    Release { key: String, owner: String, token: FencingToken },
+}
+
+/// A request as the leader logged it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LockCommand {
+    request: LockRequest,
+    lease_ms: u64,
+    now_ms: u64,
+    /// `node:sequence` of the leader that proposed it, which waits for the
+    /// outcome.
+    request_id: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct LockSnapshot {
+    leases: BTreeMap<String, Lease>,
+    last_token: FencingToken,
+    inner: Option<Vec<u8>>,
+}
+
+struct LockTableState {
+    node_id: String,
+    leases: BTreeMap<String, Lease>,
+    last_token: FencingToken,
+    /// Commands this node proposed whose proposer still waits for them
+    pending: HashSet<String>,
+    /// Outcomes of pending commands, until `take_outcome`.
+    outcomes: HashMap<String, Result<Lease, LockError>>,
+}
+
+/// Raft state machine holding the leases. Entries for other collections go
+/// to the inner state machine, so a node can replicate its data and its
+/// locks through one Raft group. Clones share the same table.
+#[derive(Clone)]
+pub struct LockTable {
+    state: Arc<Mutex<LockTableState>>,
+    inner: Arc<tokio::sync::Mutex<Option<Box<dyn RaftStateMachine>>>>,
+}
+
+impl LockTable {
+    pub fn new(node_id: &str) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(LockTableState {
+                node_id: node_id.to_string(),
+                leases: BTreeMap::new(),
+                last_token: 0,
+                pending: HashSet::new(),
+                outcomes: HashMap::new(),
+            })),
+            inner: Arc::new(tokio::sync::Mutex::new(None)),
+        }
+    }
+
+    /// Set before the table is handed to a node.
+    pub fn with_inner(self, inner: Box<dyn RaftStateMachine>) -> Self {
+        *self.inner.try_lock().expect("the table is not in use yet") = Some(inner);
+        self
+    }
+
+    /// The lease on `key` as of the last applied command; it may have
+    /// expired since.
+    pub fn lease(&self, key: &str) -> Option<Lease> {
+        self.state.lock().unwrap().leases.get(key).cloned()
+    }
+
+    pub fn leases(&self) -> Vec<Lease> {
+        self.state.lock().unwrap().leases.values().cloned().collect()
+    }
+
+    /// Keeps the outcome of `request_id` once it is applied.
+    fn expect_outcome(&self, request_id: &str) {
+        self.state.lock().unwrap().pending.insert(request_id.to_string());
+    }
+
+    fn take_outcome(&self, request_id: &str) -> Option<Result<Lease, LockError>> {
+        self.state.lock().unwrap().outcomes.remove(request_id)
+    }
+
+    /// Drops the outcome of `request_id`, now or when it is applied, for a
+    /// proposer that stopped waiting.
+    fn forget_outcome(&self, request_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.remove(request_id);
+        state.outcomes.remove(request_id);
+    }
+}
+
+impl LockTableState {
+    fn execute(&mut self, command: &LockCommand) -> Result<Lease, LockError> {
+        let now_ms = command.now_ms;
+        self.leases.retain(|_, lease| lease.expires_at_ms > now_ms);
+
+        match &command.request {
+            LockRequest::Acquire { key, owner } => {
+                if let Some(lease) = self.leases.get(key) {
+                    return Err(LockError::Held {
+                        key: key.clone(),
+                        owner: lease.owner.clone(),
+                        expires_at_ms: lease.expires_at_ms,
+                    });
+                }
+                self.last_token += 1;
+                let lease = Lease {
+                    key: key.clone(),
+                    owner: owner.clone(),
+                    token: self.last_token,
+                    expires_at_ms: now_ms + command.lease_ms,
+                };
+                self.leases.insert(key.clone(), lease.clone());
+                Ok(lease)
+            }
+            LockRequest::Renew { key, owner, token } => match self.leases.get_mut(key) {
+                Some(lease) if lease.owner == *owner && lease.token == *token => {
+                    lease.expires_at_ms = now_ms + command.lease_ms;
+                    Ok(lease.clone())
+                }
+                _ => Err(LockError::LeaseLost(key.clone())),
+            },
+            LockRequest::Release { key, owner, token } => match self.leases.get(key) {
+                Some(lease) if lease.owner == *owner && lease.token == *token => {
+                    Ok(self.leases.remove(key).unwrap())
+                }
+                _ => Err(LockError::LeaseLost(key.clone())),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl RaftStateMachine for LockTable {
+    async fn apply(&mut self, index: u64, entry: &WalEntry) -> Result<(), RaftError> {
+        if entry.collection != LOCK_COLLECTION {
+            return match self.inner.lock().await.as_mut() {
+                Some(inner) => inner.apply(index, entry).await,
+                None => Ok(()),
+            };
+        }
+
+        let command: LockCommand = serde_json::from_value(entry.data.clone())
+            .map_err(|e| RaftError::StateMachineError(format!("lock command: {}", e)))?;
+        let mut state = self.state.lock().unwrap();
+        let outcome = state.execute(&command);
+        if command.request_id.split(':').next() == Some(state.node_id.as_str()) && state.pending.remove(&command.request_id) {
+            state.outcomes.insert(command.request_id, outcome);
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> Result<Vec<u8>, RaftError> {
+        let inner = match self.inner.lock().await.as_ref() {
+            Some(inner) => Some(inner.snapshot().await?),
+            None => None,
+        };
+        let state = self.state.lock().unwrap();
+        let snapshot = LockSnapshot {
+            leases: state.leases.clone(),
+            last_token: state.last_token,
+            inner,
+        };
+        serde_json::to_vec(&snapshot).map_err(|e| RaftError::StateMachineError(e.to_string()))
+    }
+
+    async fn restore(&mut self, data: &[u8]) -> Result<(), RaftError> {
+        let snapshot: LockSnapshot = serde_json::from_slice(data)
+            .map_err(|e| RaftError::StateMachineError(e.to_string()))?;
+
+        if let (Some(inner), Some(data)) = (self.inner.lock().await.as_mut(), snapshot.inner) {
+            inner.restore(&data).await?;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.leases = snapshot.leases;
+        state.last_token = snapshot.last_token;
+        Ok(())
+    }
+}
+
+/// How a node reaches the leader's `LockService`.
+#[async_trait]
+pub trait LockTransport: Send + Sync {
+    async fn send(&self, to: &str, request: LockRequest) -> Result<Lease, LockError>;
+}
+
+/// Calls services in the same process directly.
+#[derive(Default)]
+pub struct LocalLockTransport {
+    services: std::sync::RwLock<HashMap<String, Weak<LockService>>>,
+}
+
+impl LocalLockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, service: &Arc<LockService>) {
+        self.services.write().unwrap().insert(service.node_id().to_string(), Arc::downgrade(service));
+    }
+}
+
+#[async_trait]
+impl LockTransport for LocalLockTransport {
+    async fn send(&self, to: &str, request: LockRequest) -> Result<Lease, LockError> {
+        let service = self.services.read().unwrap()
+            .get(to)
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| LockError::ConsensusError(format!("node unreachable: {}", to)))?;
+        service.handle(request).await
+    }
+}
+
+/// Reaches other servers' lock services through their REST API. The reply
+/// carries the leader's outcome, so a refusal comes back as the same
+/// `LockError` a local call would give.
+pub struct HttpLockTransport {
+    client: reqwest::Client,
+    /// Base URLs by node id.
+    peers: std::sync::RwLock<HashMap<String, String>>,
+}
+
+impl HttpLockTransport {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            peers: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn add_peer(&self, node_id: &str, base_url: &str) {
+        self.peers.write().unwrap().insert(node_id.to_string(), base_url.trim_end_matches('/').to_string());
+    }
+}
+
+impl Default for HttpLockTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl LockTransport for HttpLockTransport {
+    async fn send(&self, to: &str, request: LockRequest) -> Result<Lease, LockError> {
+        let unreachable = |e: &dyn std::fmt::Display| LockError::ConsensusError(format!("node unreachable: {}: {}", to, e));
+        let base_url = self.peers.read().unwrap().get(to).cloned()
+            .ok_or_else(|| unreachable(&"no address known"))?;
+        let body = serde_json::to_string(&request).map_err(|e| unreachable(&e))?;
+
+        let response = self.client.post(format!("{}/api/locks", base_url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| unreachable(&e))?;
+        let bytes = response.bytes().await.map_err(|e| unreachable(&e))?;
+        let envelope: Envelope<Result<Lease, LockError>> = serde_json::from_slice(&bytes).map_err(|e| unreachable(&e))?;
+        match (envelope.data, envelope.error) {
+            (Some(outcome), _) => outcome,
+            (None, error) => Err(unreachable(&error.unwrap_or_else(|| "empty response".to_string()))),
+        }
+    }
+}
+
+pub struct LockService {
+    raft: Arc<RaftNode>,
+    table: LockTable,
+    config: LockConfig,
+    transport: Option<Arc<dyn LockTransport>>,
+    next_request: AtomicU64,
+}
+
+impl LockService {
+    /// `table` must be the state machine `raft` applies to.
+    pub fn new(raft: Arc<RaftNode>, table: LockTable) -> Self {
+        Self {
+            raft,
+            table,
+            config: LockConfig::default(),
+            transport: None,
+            next_request: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_config(mut self, config: LockConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Forwards requests made on a follower to the leader.
+    pub fn with_transport(mut self, transport: Arc<dyn LockTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    pub fn node_id(&self) -> &str {
+        self.raft.id()
+    }
+
+    pub fn config(&self) -> &LockConfig {
+        &self.config
+    }
+
+    pub fn table(&self) -> &LockTable {
+        &self.table
+    }
+
+    /// Takes `key` if nobody holds an unexpired lease on it. The lease is
+    /// not renewed; use `acquire` for that.
+    pub async fn try_acquire(&self, key: &str) -> Result<Lease, LockError> {
+        let owner = format!("{}/{}", self.node_id(), self.next_request.fetch_add(1, Ordering::Relaxed));
+        self.handle(LockRequest::Acquire { key: key.to_string(), owner }).await
+    }
+
+    /// Takes `key` and keeps renewing it until the guard is released or
+    /// dropped.
+    pub async fn acquire(self: &Arc<Self>, key: &str) -> Result<LockGuard, LockError> {
+        let started = Instant::now();
+        let lease = self.try_acquire(key).await?;
+        Ok(LockGuard::start(self.clone(), lease, started))
+    }
+
+    pub async fn renew(&self, lease: &Lease) -> Result<Lease, LockError> {
+        self.handle(LockRequest::Renew {
+            key: lease.key.clone(),
+            owner: lease.owner.clone(),
+            token: lease.token,
+        }).await
+    }
+
+    pub async fn release(&self, lease: &Lease) -> Result<(), LockError> {
+        self.handle(LockRequest::Release {
+            key: lease.key.clone(),
+            owner: lease.owner.clone(),
+            token: lease.token,
+        }).await.map(|_| ())
+    }
+
+    /// Runs a request on the leader, forwarding it there from a follower.
+    pub async fn handle(&self, request: LockRequest) -> Result<Lease, LockError> {
+        if !self.raft.is_leader().await {
+            let leader = self.raft.leader().await;
+            return match (&self.transport, leader) {
+                (Some(transport), Some(leader)) if leader != self.node_id() => transport.send(&leader, request).await,
+                (_, leader) => Err(LockError::NotLeader(leader)),
+            };
+        }
+
+        let request_id = format!("{}:{}", self.node_id(), self.next_request.fetch_add(1, Ordering::Relaxed));
+        let command = LockCommand {
+            request,
+            lease_ms: self.config.lease_ms,
+            now_ms: now_ms(),
+            request_id: request_id.clone(),
+        };
+        let entry = WalEntry {
+            id: 0,
+            timestamp: command.now_ms,
+            entry_type: WalEntryType::Update,
+            collection: LOCK_COLLECTION.to_string(),
+            data: serde_json::to_value(&command).map_err(|e| LockError::ConsensusError(e.to_string()))?,
+        };
+
+        self.table.expect_outcome(&request_id);
+        let committed = async {
+            let proposal = self.raft.propose(entry).await?;
+            self.raft.wait_committed(proposal, Duration::from_millis(self.config.commit_timeout_ms)).await
+        }.await;
+        if let Err(e) = committed {
+            // The command may still commit later; nobody takes its outcome
+            self.table.forget_outcome(&request_id);
+            return Err(e.into());
+        }
+        self.table.take_outcome(&request_id)
+            .unwrap_or_else(|| Err(LockError::ConsensusError("lock command was not applied".to_string())))
+    }
+}
+
+/// A lease renewed in the background. The holder should check `is_held`
+/// before acting and pass `token` along with every write it makes.
+pub struct LockGuard {
+    service: Arc<LockService>,
+    lease: Arc<Mutex<Lease>>,
+    /// Local deadline: the lease is measured from before the request went
+    /// out, so this never outlasts the leader's view.
+    valid_until: Arc<Mutex<Instant>>,
+    lost: Arc<AtomicBool>,
+    renewal: JoinHandle<()>,
+}
+
+impl LockGuard {
+    fn start(service: Arc<LockService>, lease: Lease, started: Instant) -> Self {
+        let lease_duration = Duration::from_millis(service.config.lease_ms);
+        let lease = Arc::new(Mutex::new(lease));
+        let valid_until = Arc::new(Mutex::new(started + lease_duration));
+        let lost = Arc::new(AtomicBool::new(false));
+
+        let renewal = {
+            let service = service.clone();
+            let lease = lease.clone();
+            let valid_until = valid_until.clone();
+            let lost = lost.clone();
+            tokio::spawn(async move {
+                let interval = Duration::from_millis(service.config.renew_interval_ms.max(1));
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let current = lease.lock().unwrap().clone();
+                    let started = Instant::now();
+                    match service.renew(&current).await {
+                        Ok(renewed) => {
+                            *lease.lock().unwrap() = renewed;
+                            *valid_until.lock().unwrap() = started + lease_duration;
+                        }
+                        Err(LockError::LeaseLost(_)) => {
+                            lost.store(true, Ordering::SeqCst);
+                            return;
+                        }
+                        // Keep trying while the lease may still be valid
+                        Err(_) if Instant::now() < *valid_until.lock().unwrap() => {}
+                        Err(_) => {
+                            lost.store(true, Ordering::SeqCst);
+                            return;
+                        }
+                    }
+                }
+            })
+        };
+
+        Self { service, lease, valid_until, lost, renewal }
+    }
+
+    pub fn key(&self) -> String {
+        self.lease.lock().unwrap().key.clone()
+    }
+
+    pub fn token(&self) -> FencingToken {
+        self.lease.lock().unwrap().token
+    }
+
+    pub fn is_held(&self) -> bool {
+        !self.lost.load(Ordering::SeqCst) && Instant::now() < *self.valid_until.lock().unwrap()
+    }
+
+    pub async fn release(self) -> Result<(), LockError> {
+        self.renewal.abort();
+        let lease = self.lease.lock().unwrap().clone();
+        self.service.release(&lease).await
+    }
+}
+
+impl Drop for LockGuard {
+    /// Stops renewing; without a `release` the lease lapses on its own.
+    fn drop(&mut self) {
+        self.renewal.abort();
+    }
+}
+
+/// The highest fencing token seen per resource. A store runs writes
+/// through `fenced`, so one from a holder that was superseded is refused.
+/// A fence from `open` keeps the tokens in a file, which every process
+/// writing the same storage must share.
+#[derive(Default)]
+pub struct Fence {
+    /// Highest token per resource, locked while a write to it runs
+    resources: Mutex<HashMap<String, Arc<tokio::sync::Mutex<FencingToken>>>>,
+    path: Option<PathBuf>,
+}
+
+impl Fence {
+    /// A fence for writers in this process only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the tokens in `path`. A check holds an exclusive lock on a
+    /// lock file of its resource next to `path` and re-reads the tokens, so
+    /// processes sharing the file, e.g. nodes mounting one data directory,
+    /// fence each other too.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self {
+            resources: Mutex::new(HashMap::new()),
+            path: Some(path.into()),
+        }
+    }
+
+    /// Accepts `token` if no larger one was seen for `resource`.
+    pub async fn check(&self, resource: &str, token: FencingToken) -> Result<(), LockError> {
+        self.fenced(resource, token, async {}).await
+    }
+
+    /// Runs `write` if `token` is accepted for `resource`. No other check
+    /// of `resource` runs until `write` finishes, so a newer holder cannot
+    /// slip in between the check and the write; other resources are not
+    /// held up.
+    pub async fn fenced<T>(&self, resource: &str, token: FencingToken, write: impl Future<Output = T>) -> Result<T, LockError> {
+        let slot = self.resources.lock().unwrap().entry(resource.to_string()).or_default().clone();
+        let mut highest = slot.lock().await;
+        let _locked = match &self.path {
+            Some(path) => {
+                let locked = lock_file(resource_lock_path(path, resource)).await?;
+                *highest = raise_token(path.clone(), resource.to_string(), token).await?;
+                Some(locked)
+            }
+            None => None,
+        };
+
+        if token < *highest {
+            return Err(LockError::StaleToken { resource: resource.to_string(), token, highest: *highest });
+        }
+        *highest = token;
+        Ok(write.await)
+    }
+}
+
+/// The lock file that serializes checks of `resource` across processes.
+fn resource_lock_path(path: &std::path::Path, resource: &str) -> PathBuf {
+    use sha2::{Digest, Sha256};
+    let digest = hex::encode(Sha256::digest(resource.as_bytes()));
+    path.with_extension(format!("{}.lock", &digest[..16]))
+}
+
+/// Waits for an exclusive lock on `path`, held until the file is dropped.
+async fn lock_file(path: PathBuf) -> Result<File, LockError> {
+    tokio::task::spawn_blocking(move || open_locked(&path))
+        .await
+        .map_err(|e| LockError::StorageError(e.to_string()))?
+        .map_err(|e| LockError::StorageError(e.to_string()))
+}
+
+fn open_locked(path: &std::path::Path) -> std::io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Records `token` for `resource` in the file at `path` if it is the
+/// highest yet, under the lock of the whole file. Returns the highest
+/// token seen before.
+async fn raise_token(path: PathBuf, resource: String, token: FencingToken) -> Result<FencingToken, LockError> {
+    tokio::task::spawn_blocking(move || {
+        let _locked = open_locked(&path.with_extension("lock")).map_err(|e| LockError::StorageError(e.to_string()))?;
+        let mut tokens = read_tokens(&path)?;
+        let seen = tokens.get(&resource).copied().unwrap_or(0);
+        if token > seen {
+            tokens.insert(resource, token);
+            write_tokens(&path, &tokens)?;
+        }
+        Ok(seen)
+    })
+    .await
+    .map_err(|e| LockError::StorageError(e.to_string()))?
+}
+
+fn read_tokens(path: &std::path::Path) -> Result<HashMap<String, FencingToken>, LockError> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| LockError::StorageError(format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(LockError::StorageError(e.to_string())),
+    }
+}
+
+fn write_tokens(path: &std::path::Path, tokens: &HashMap<String, FencingToken>) -> Result<(), LockError> {
+    let data = serde_json::to_vec(tokens).map_err(|e| LockError::StorageError(e.to_string()))?;
+    crate::coretex_journal::write_durably(path, &data).map_err(|e| LockError::StorageError(e.to_string()))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::coretex_utils::raft::sim::SimNetwork;
+    use crate::coretex_utils::raft::RaftConfig;
+
+    /// Lock services on `node1..nodeN` of one Raft group, with node1 leading.
+    pub(crate) async fn lock_cluster(size: usize, config: LockConfig) -> Vec<Arc<LockService>> {
+        let network = Arc::new(SimNetwork::new());
+        let transport = Arc::new(LocalLockTransport::new());
+        let members: Vec<String> = (1..=size).map(|i| format!("node{}", i)).collect();
+
+        let mut services = Vec::new();
+        for id in &members {
+            let table = LockTable::new(id);
+            let raft = Arc::new(
+                RaftNode::new(id, members.clone(), network.clone(), Box::new(table.clone()))
+                    .with_config(RaftConfig::default()),
+            );
+            network.register(&raft);
+            let service = Arc::new(
+                LockService::new(raft, table)
+                    .with_config(config.clone())
+                    .with_transport(transport.clone()),
+            );
+            transport.register(&service);
+            services.push(service);
+        }
+
+        assert!(services[0].raft.campaign().await);
+        services
+    }
+
+    #[tokio::test]
+    async fn test_lock_is_exclusive_across_nodes() {
+        let services = lock_cluster(3, LockConfig::default()).await;
+        let (leader, follower) = (&services[0], &services[2]);
+
+        // Forwarded to the leader and replicated everywhere
+        let lease = follower.try_acquire("index-rebuild").await.unwrap();
+        assert!(lease.owner.starts_with("node3/"));
+        leader.raft.tick().await;
+        for service in &services {
+            assert_eq!(service.table().lease("index-rebuild"), Some(lease.clone()));
+        }
+
+        let err = leader.try_acquire("index-rebuild").await.unwrap_err();
+        assert!(matches!(err, LockError::Held { ref owner, .. } if *owner == lease.owner));
+        assert!(services[1].try_acquire("lakehouse-migration").await.is_ok());
+
+        // Only the holder can release, and the next holder gets a larger token
+        let stranger = Lease { owner: "node2/99".to_string(), ..lease.clone() };
+        assert_eq!(leader.release(&stranger).await, Err(LockError::LeaseLost("index-rebuild".to_string())));
+        follower.release(&lease).await.unwrap();
+        let next = services[1].try_acquire("index-rebuild").await.unwrap();
+        assert!(next.token > lease.token);
+    }
+
+    #[tokio::test]
+    async fn test_lease_expires_unless_renewed() {
+        let config = LockConfig { lease_ms: 100, renew_interval_ms: 20, commit_timeout_ms: 1_000 };
+        let services = lock_cluster(1, config).await;
+        let service = &services[0];
+
+        let guard = service.acquire("job").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(guard.is_held());
+        assert!(service.try_acquire("job").await.is_err());
+
+        // A crashed holder stops renewing and the lease lapses
+        let stale = guard.token();
+        drop(guard);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let lease = service.try_acquire("job").await.unwrap();
+        assert!(lease.token > stale);
+
+        let fence = Fence::new();
+        fence.check("job", lease.token).await.unwrap();
+        assert!(matches!(fence.check("job", stale).await, Err(LockError::StaleToken { .. })));
+
+        // A renewal with the superseded token is refused
+        let old = Lease { token: stale, ..lease.clone() };
+        assert_eq!(service.renew(&old).await, Err(LockError::LeaseLost("job".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_fence_file_is_shared_between_fences() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fence.json");
+        let (first, second) = (Fence::open(&path), Fence::open(&path));
+
+        assert_eq!(first.fenced("tiering", 2, async { "written" }).await, Ok("written"));
+        assert!(matches!(second.fenced("tiering", 1, async {}).await, Err(LockError::StaleToken { highest: 2, .. })));
+        second.check("tiering", 3).await.unwrap();
+        assert!(first.check("tiering", 2).await.is_err());
+        assert!(first.check("other", 1).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_fence_holds_only_the_resource_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let fence = Fence::open(dir.path().join("fence.json"));
+
+        // A write to one resource does not hold up checks of another
+        let written = tokio::time::timeout(Duration::from_secs(5), fence.fenced("a", 1, fence.check("b", 1))).await;
+        assert_eq!(written.unwrap(), Ok(Ok(())));
+        assert!(fence.check("a", 1).await.is_ok());
+        assert!(fence.check("b", 0).await.is_err());
+    }
+}
@@ -5,6 +5,32 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+pub mod sharding;
+pub mod replication;
+pub mod two_phase;
+pub mod locks;
+pub mod consensus;
+
+pub use sharding::{
+    ShardCoordinator, ShardingConfig, ShardingStrategy, ShardingError, ShardedCollection,
+    ShardedSearchResult, ShardHit, ShardClient, LocalShardClient, HttpShardClient,
+    RebalanceConfig, ShardMove, MigrationState, MigrationProgress, RebalanceProgress, RebalanceReport,
+};
+pub use replication::{
+    ReplicationLeader, ReplicaNode, ReplicaClient, HttpReplicaClient, ReplicationConfig, ReplicationError, ReplicationRole,
+    ReplicationStatus, ReplicaLag, ReplicaSnapshot, SnapshotCollection, ReadPreference, RoutedSearch,
+};
+pub use two_phase::{
+    TwoPhaseNode, TwoPhaseConfig, TwoPhaseError, TwoPhaseTransport, HttpTwoPhaseTransport, TwoPhaseRequest,
+    TwoPhaseResponse, TwoPhaseStatus, Decision,
+};
+pub use consensus::{HttpRaftTransport, DbStateMachine};
+pub use locks::{
+    LockService, LockTable, LockConfig, LockError, LockGuard, LockRequest, LockTransport, LocalLockTransport, HttpLockTransport,
+    Lease, FencingToken, Fence,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DistributedTransactionState {
@@ -27,7 +53,9 @@ pub struct DistributedTransaction {
     pub timeout: Duration,
 }
 
-#[derive(Debug, Clone)]
+/// `Insert` and `Update` payloads are a JSON `{"vector", "metadata"}`
+/// object; the constructors below encode it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DistributedOperation {
     Insert { collection: String, id: String, payload: Vec<u8> },
     Update { collection: String, id: String, payload: Vec<u8> },
@@ -35,6 +63,82 @@ pub enum DistributedOperation {
     Read { collection: String, id: String },
 }
 
+impl DistributedOperation {
+    pub fn insert(collection: &str, id: &str, vector: Vec<f32>, metadata: serde_json::Value) -> Self {
+        DistributedOperation::Insert {
+            collection: collection.to_string(),
+            id: id.to_string(),
+            payload: VectorPayload { vector, metadata }.encode(),
+        }
+    }
+
+    /// Fails to prepare unless the vector exists.
+    pub fn update(collection: &str, id: &str, vector: Vec<f32>, metadata: serde_json::Value) -> Self {
+        DistributedOperation::Update {
+            collection: collection.to_string(),
+            id: id.to_string(),
+            payload: VectorPayload { vector, metadata }.encode(),
+        }
+    }
+
+    pub fn delete(collection: &str, id: &str) -> Self {
+        DistributedOperation::Delete {
+            collection: collection.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    pub fn collection(&self) -> &str {
+        match self {
+            DistributedOperation::Insert { collection, .. }
+            | DistributedOperation::Update { collection, .. }
+            | DistributedOperation::Delete { collection, .. }
+            | DistributedOperation::Read { collection, .. } => collection,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            DistributedOperation::Insert { id, .. }
+            | DistributedOperation::Update { id, .. }
+            | DistributedOperation::Delete { id, .. }
+            | DistributedOperation::Read { id, .. } => id,
+        }
+    }
+
+    /// The same operation on another collection.
+    pub fn with_collection(self, collection: String) -> Self {
+        match self {
+            DistributedOperation::Insert { id, payload, .. } => DistributedOperation::Insert { collection, id, payload },
+            DistributedOperation::Update { id, payload, .. } => DistributedOperation::Update { collection, id, payload },
+            DistributedOperation::Delete { id, .. } => DistributedOperation::Delete { collection, id },
+            DistributedOperation::Read { id, .. } => DistributedOperation::Read { collection, id },
+        }
+    }
+
+    /// `collection:id`, the key the operation locks.
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.collection(), self.id())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct VectorPayload {
+    vector: Vec<f32>,
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+impl VectorPayload {
+    fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(payload).map_err(|e| format!("invalid vector payload: {}", e))
+    }
+}
+
 pub struct TwoPhaseCommit {
     transactions: Arc<RwLock<HashMap<String, DistributedTransaction>>>,
     node_id: String,
@@ -198,6 +302,8 @@ impl TwoPhaseCommit {
 pub struct DistributedLockManager {
     locks: Arc<RwLock<HashMap<String, DistributedLock>>>,
     node_id: String,
+    service: Option<Arc<LockService>>,
+    guards: Arc<RwLock<HashMap<String, LockGuard>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -213,10 +319,41 @@ impl DistributedLockManager {
         Self {
             locks: Arc::new(RwLock::new(HashMap::new())),
             node_id: node_id.to_string(),
+            service: None,
+            guards: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Takes locks cluster-wide through `service` instead of in this
+    /// process. Held locks are renewed until released, and the `timeout`
+    /// passed to `acquire_lock` gives way to the service's lease.
+    pub fn with_service(mut self, service: Arc<LockService>) -> Self {
+        self.service = Some(service);
+        self
+    }
+
+    /// The fencing token of a lock this manager holds through its service.
+    pub async fn fencing_token(&self, key: &str) -> Option<FencingToken> {
+        let guards = self.guards.read().await;
+        guards.get(key).filter(|guard| guard.is_held()).map(LockGuard::token)
+    }
+
     pub async fn acquire_lock(&self, key: &str, timeout: Duration) -> Result<bool, String> {
+        if let Some(service) = &self.service {
+            let mut guards = self.guards.write().await;
+            if guards.get(key).is_some_and(LockGuard::is_held) {
+                return Ok(true);
+            }
+            return match service.acquire(key).await {
+                Ok(guard) => {
+                    guards.insert(key.to_string(), guard);
+                    Ok(true)
+                }
+                Err(LockError::Held { .. }) => Err("Lock held by another node".to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+        }
+
         let mut locks = self.locks.write().await;
         
         if let Some(lock) = locks.get(key) {
@@ -249,6 +386,14 @@ impl DistributedLockManager {
     }
 
     pub async fn release_lock(&self, key: &str) -> Result<bool, String> {
+        if self.service.is_some() {
+            let guard = self.guards.write().await.remove(key);
+            return match guard {
+                Some(guard) => guard.release().await.map(|_| true).map_err(|e| e.to_string()),
+                None => Ok(false),
+            };
+        }
+
         let mut locks = self.locks.write().await;
         
         if let Some(lock) = locks.get(key) {
@@ -262,6 +407,14 @@ impl DistributedLockManager {
     }
 
     pub async fn is_locked(&self, key: &str) -> bool {
+        if let Some(service) = &self.service {
+            let now_ms = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            return service.table().lease(key).is_some_and(|lease| lease.expires_at_ms > now_ms);
+        }
+
         let locks = self.locks.read().await;
         
         if let Some(lock) = locks.get(key) {
@@ -278,6 +431,8 @@ impl DistributedLockManager {
 pub struct TransactionCoordinator {
     two_pc: Arc<TwoPhaseCommit>,
     lock_manager: Arc<DistributedLockManager>,
+    network: Option<Arc<TwoPhaseNode>>,
+    shards: Option<Arc<ShardCoordinator>>,
 }
 
 impl TransactionCoordinator {
@@ -285,29 +440,46 @@ impl TransactionCoordinator {
         Self {
             two_pc: Arc::new(TwoPhaseCommit::new(node_id)),
             lock_manager: Arc::new(DistributedLockManager::new(node_id)),
+            network: None,
+            shards: None,
         }
     }
 
+    /// Runs transactions over the network through `node`: every participant
+    /// prepares and applies the operations against its own database.
+    pub fn with_network(mut self, node: Arc<TwoPhaseNode>) -> Self {
+        self.network = Some(node);
+        self
+    }
+
+    /// Sends each operation only to the nodes holding its shard, instead of
+    /// to every participant. Operations must then name sharded collections.
+    pub fn with_shards(mut self, shards: Arc<ShardCoordinator>) -> Self {
+        self.shards = Some(shards);
+        self
+    }
+
+    /// Over the network, `participants` are ignored when shards route the
+    /// operations; otherwise every participant gets all of them.
     pub async fn execute_transaction(
         &self,
         participants: Vec<String>,
         operations: Vec<DistributedOperation>,
     ) -> Result<(), String> {
+        if let Some(node) = &self.network {
+            let operations = match &self.shards {
+                Some(shards) => shards.route_operations(operations).await.map_err(|e| e.to_string())?,
+                None => participants.into_iter()
+                    .map(|participant| (participant, operations.clone()))
+                    .collect(),
+            };
+            return node.execute(operations).await.map(|_| ()).map_err(|e| e.to_string());
+        }
+
         let tx_id = self.two_pc.begin_transaction(participants.clone()).await;
         
         for op in &operations {
-            let lock_key = match op {
-                DistributedOperation::Insert { collection, id, .. } => 
-                    format!("{}:{}", collection, id),
-                DistributedOperation::Update { collection, id, .. } => 
-                    format!("{}:{}", collection, id),
-                DistributedOperation::Delete { collection, id, .. } => 
-                    format!("{}:{}", collection, id),
-                DistributedOperation::Read { collection, id, .. } => 
-                    format!("{}:{}", collection, id),
-            };
-            
-            self.lock_manager.acquire_lock(&lock_key, Duration::from_secs(30)).await?;
+            self.lock_manager.acquire_lock(&op.key(), Duration::from_secs(30)).await?;
             self.two_pc.add_operation(&tx_id, op.clone()).await?;
         }
         
@@ -371,6 +543,75 @@ mod tests {
         assert!(released.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_distributed_lock_through_service() {
+        let services = locks::tests::lock_cluster(2, LockConfig::default()).await;
+        let first = DistributedLockManager::new("node1").with_service(services[0].clone());
+        let second = DistributedLockManager::new("node2").with_service(services[1].clone());
+
+        assert!(first.acquire_lock("rebuild", Duration::from_secs(10)).await.unwrap());
+        assert!(second.acquire_lock("rebuild", Duration::from_secs(10)).await.is_err());
+        assert!(first.is_locked("rebuild").await);
+        let token = first.fencing_token("rebuild").await.unwrap();
+
+        assert!(first.release_lock("rebuild").await.unwrap());
+        assert!(second.acquire_lock("rebuild", Duration::from_secs(10)).await.unwrap());
+        assert!(second.fencing_token("rebuild").await.unwrap() > token);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_coordinator_routes_operations_to_shards() {
+        use crate::coretex_utils::cluster::{ClusterManager, ClusterNode, NodeRole, NodeState};
+        use two_phase::sim::TwoPhaseCluster;
+
+        let dir = tempfile::tempdir().unwrap();
+        let network = TwoPhaseCluster::new(dir.path(), 3, TwoPhaseConfig::default()).await.unwrap();
+        let cluster = Arc::new(ClusterManager::new("node1", 1));
+        let shards = Arc::new(ShardCoordinator::new(cluster.clone()));
+        for (i, node) in ["node1", "node2", "node3"].into_iter().enumerate() {
+            cluster.add_node(ClusterNode {
+                id: node.to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 7000 + i as u16,
+                role: NodeRole::Follower,
+                state: NodeState::Active,
+                last_heartbeat: 0,
+                shard_ids: Vec::new(),
+            }).await;
+            // Only placement matters here; the writes go through two-phase commit
+            shards.register_node(node, Arc::new(LocalShardClient::new(Arc::new(crate::CoreTexDB::new())))).await;
+        }
+        let sharded = shards.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 3 }).await.unwrap();
+
+        // Each participant only has the shard collections it holds
+        let mut owners = HashMap::new();
+        for shard in 0..3 {
+            let (primary, _) = cluster.get_shard_nodes(sharded.cluster_shards[shard as usize]).await.unwrap();
+            let db = network.db(&primary).unwrap();
+            db.read().await.create_collection(&sharded.shard_collection(shard), 3, "cosine").await.unwrap();
+            owners.insert(shard, primary);
+        }
+
+        let coordinator = TransactionCoordinator::new("node1")
+            .with_network(network.node("node1").unwrap())
+            .with_shards(shards);
+        let ids: Vec<String> = (0..12).map(|i| format!("v{}", i)).collect();
+        let operations = ids.iter()
+            .map(|id| DistributedOperation::insert("docs", id, vec![1.0, 0.0, 0.0], serde_json::json!({})))
+            .collect();
+        coordinator.execute_transaction(Vec::new(), operations).await.unwrap();
+
+        for id in &ids {
+            let shard = sharded.strategy.shard_for(id);
+            for node in ["node1", "node2", "node3"] {
+                let db = network.db(node).unwrap();
+                let db = db.read().await;
+                let stored = db.get_vector(&sharded.shard_collection(shard), id, None).await.is_ok_and(|v| v.is_some());
+                assert_eq!(stored, owners[&shard] == node, "{} on {}", id, node);
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_transaction_coordinator() {
         let coordinator = TransactionCoordinator::new("node1");
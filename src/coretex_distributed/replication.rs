@@ -0,0 +1,915 @@
+//! Read replicas
+//! `ReplicationLeader` ships the journal of a durable database (see
+//! `CoreTexDB::open`) to its replicas, so every write the database takes, by
+//! whatever path, reaches them. Replicas receive the journal in order,
+//! asynchronously, from a background task. A replica that is new, restarted,
+//! or needs entries the journal no longer holds first installs a snapshot
+//! and then follows the tail.
+//!
+//! Searches either stay on the leader or go to any replica that is less
+//! stale than a bound. A replica's staleness is measured from the last
+//! moment it provably had every write the leader had made: a shipment that
+//! leaves it caught up proves this for the time the shipment was sent.
+//! Replicas only apply what the leader sends them; writing to one directly
+//! makes it diverge.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use super::sharding::{Envelope, RemoteSearch, VectorRecord};
+use crate::coretex_index::SearchResult;
+use crate::coretex_utils::wal::{WalEntry, WalEntryType};
+use crate::coretex_side_vectors::{main_collection, side_collection};
+use crate::{CollectionSchema, CoreTexDB, CoreTexError, SideVectors};
+
+#[derive(Debug, Clone)]
+pub enum ReplicationError {
+    /// The replica needs entries from `expected` on but was sent `got`.
+    LogGap { expected: u64, got: u64 },
+    InvalidEntry(String),
+    DatabaseError(String),
+    NodeError(String),
+}
+
+impl std::fmt::Display for ReplicationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplicationError::LogGap { expected, got } => write!(f, "Log gap: expected entry {}, got {}", expected, got),
+            ReplicationError::InvalidEntry(msg) => write!(f, "Invalid log entry: {}", msg),
+            ReplicationError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
+            ReplicationError::NodeError(msg) => write!(f, "Node error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ReplicationError {}
+
+fn database_error(e: impl std::fmt::Display) -> ReplicationError {
+    ReplicationError::DatabaseError(e.to_string())
+}
+
+fn node_error(e: impl std::fmt::Display) -> ReplicationError {
+    ReplicationError::NodeError(e.to_string())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplicationConfig {
+    /// Pause between two rounds of shipping to every replica.
+    pub ship_interval_ms: u64,
+    /// Entries sent to a replica per request.
+    pub batch_size: usize,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            ship_interval_ms: 50,
+            batch_size: 512,
+        }
+    }
+}
+
+/// A server's part in replication.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "snake_case")]
+pub enum ReplicationRole {
+    /// Ships its journal to `replicas`, base URLs by node id, presenting
+    /// `token` to them.
+    Leader {
+        replicas: HashMap<String, String>,
+        token: String,
+    },
+    /// Serves `/api/replication/*` to a leader that presents `token`.
+    Replica { token: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadPreference {
+    #[default]
+    LeaderOnly,
+    /// Any replica less than `max_lag_ms` behind; the leader when there is
+    /// none.
+    AnyReplica { max_lag_ms: u64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotCollection {
+    pub name: String,
+    pub dimension: usize,
+    pub metric: String,
+    pub vectors: Vec<VectorRecord>,
+    /// The whole schema, with vector fields and multi-vector settings.
+    #[serde(default)]
+    pub schema: Option<CollectionSchema>,
+    /// Named, sparse and token vectors by document id.
+    #[serde(default)]
+    pub side_vectors: BTreeMap<String, SideVectors>,
+}
+
+/// The leader's whole database as of log entry `lsn`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicaSnapshot {
+    pub lsn: u64,
+    pub collections: Vec<SnapshotCollection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicaLag {
+    pub node_id: String,
+    /// Last entry the replica confirmed; `None` before its first snapshot.
+    pub acked_lsn: Option<u64>,
+    pub lag_entries: u64,
+    /// `None` until the replica has caught up once.
+    pub lag_ms: Option<u64>,
+    pub snapshots_installed: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationStatus {
+    pub leader_lsn: u64,
+    pub replicas: Vec<ReplicaLag>,
+}
+
+#[derive(Debug)]
+pub struct RoutedSearch {
+    /// The leader or replica that answered.
+    pub node_id: String,
+    pub results: Vec<SearchResult>,
+}
+
+/// How the leader reaches one replica.
+#[async_trait]
+pub trait ReplicaClient: Send + Sync {
+    /// Applies entries that directly follow the replica's last applied one
+    /// and returns the new last applied LSN. An empty batch is a heartbeat.
+    async fn append(&self, entries: Vec<WalEntry>) -> Result<u64, ReplicationError>;
+    /// Replaces the replica's data with `snapshot` and returns its LSN.
+    async fn install_snapshot(&self, snapshot: ReplicaSnapshot) -> Result<u64, ReplicationError>;
+    async fn search(&self, collection: &str, query: &[f32], k: usize, filter: Option<serde_json::Value>) -> Result<Vec<SearchResult>, ReplicationError>;
+}
+
+/// Applies one log entry. Leader and replicas both go through here, so they
+/// interpret entries the same way.
+pub(super) async fn apply_entry(db: &CoreTexDB, entry: &WalEntry) -> Result<(), ReplicationError> {
+    let invalid = |what: &str| ReplicationError::InvalidEntry(format!("entry {} has no {}", entry.id, what));
+
+    match entry.entry_type {
+        WalEntryType::CreateCollection if entry.data.get("schema").is_some() => {
+            let schema = serde_json::from_value(entry.data["schema"].clone()).map_err(|_| invalid("schema"))?;
+            db.put_collection_schema(schema).await.map_err(database_error)?;
+        }
+        WalEntryType::CreateCollection => {
+            let dimension = entry.data["dimension"].as_u64().ok_or_else(|| invalid("dimension"))?;
+            let metric = entry.data["metric"].as_str().unwrap_or("cosine");
+            db.create_collection(&entry.collection, dimension as usize, metric).await.map_err(database_error)?;
+        }
+        WalEntryType::DeleteCollection => {
+            db.delete_collection(&entry.collection).await.map_err(database_error)?;
+        }
+        // Side vectors come as documents of their own collection
+        WalEntryType::Insert | WalEntryType::Update | WalEntryType::Delete | WalEntryType::Transaction => {
+            db.apply_entry_writes(entry).await.map_err(database_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// The follower side: applies what the leader ships.
+pub struct ReplicaNode {
+    db: Arc<RwLock<CoreTexDB>>,
+    /// Also serializes appends and snapshot installs.
+    applied_lsn: Mutex<u64>,
+}
+
+impl ReplicaNode {
+    pub fn new(db: Arc<RwLock<CoreTexDB>>) -> Self {
+        Self {
+            db,
+            applied_lsn: Mutex::new(0),
+        }
+    }
+
+    pub async fn applied_lsn(&self) -> u64 {
+        *self.applied_lsn.lock().await
+    }
+}
+
+#[async_trait]
+impl ReplicaClient for ReplicaNode {
+    async fn append(&self, entries: Vec<WalEntry>) -> Result<u64, ReplicationError> {
+        let mut applied_lsn = self.applied_lsn.lock().await;
+        let db = self.db.read().await;
+
+        for entry in &entries {
+            if entry.id <= *applied_lsn {
+                continue;
+            }
+            if entry.id != *applied_lsn + 1 {
+                return Err(ReplicationError::LogGap { expected: *applied_lsn + 1, got: entry.id });
+            }
+            apply_entry(&db, entry).await?;
+            *applied_lsn = entry.id;
+        }
+        Ok(*applied_lsn)
+    }
+
+    async fn install_snapshot(&self, snapshot: ReplicaSnapshot) -> Result<u64, ReplicationError> {
+        let mut applied_lsn = self.applied_lsn.lock().await;
+        let lsn = snapshot.lsn;
+        self.db.read().await.install_replica_snapshot(snapshot).await.map_err(database_error)?;
+
+        *applied_lsn = lsn;
+        Ok(lsn)
+    }
+
+    async fn search(&self, collection: &str, query: &[f32], k: usize, filter: Option<serde_json::Value>) -> Result<Vec<SearchResult>, ReplicationError> {
+        self.db.read().await.search(collection, query.to_vec(), k, filter, None).await.map_err(database_error)
+    }
+}
+
+/// Reaches a replica through the REST API of the node hosting it.
+pub struct HttpReplicaClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl HttpReplicaClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    /// Presents `token` as a bearer token, which replicas require.
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    async fn post<T: serde::de::DeserializeOwned>(&self, path: &str, body: String) -> Result<T, ReplicationError> {
+        let mut request = self.client.post(format!("{}{}", self.base_url, path))
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .map_err(node_error)?;
+        let bytes = response.bytes().await.map_err(node_error)?;
+        let envelope: Envelope<T> = serde_json::from_slice(&bytes).map_err(node_error)?;
+        match (envelope.data, envelope.error) {
+            (Some(data), _) => Ok(data),
+            (None, error) => Err(ReplicationError::NodeError(error.unwrap_or_else(|| "empty response".to_string()))),
+        }
+    }
+}
+
+#[async_trait]
+impl ReplicaClient for HttpReplicaClient {
+    async fn append(&self, entries: Vec<WalEntry>) -> Result<u64, ReplicationError> {
+        let body = serde_json::json!({ "entries": entries });
+        self.post("/api/replication/append", body.to_string()).await
+    }
+
+    async fn install_snapshot(&self, snapshot: ReplicaSnapshot) -> Result<u64, ReplicationError> {
+        let body = serde_json::to_string(&snapshot).map_err(node_error)?;
+        self.post("/api/replication/snapshot", body).await
+    }
+
+    async fn search(&self, collection: &str, query: &[f32], k: usize, filter: Option<serde_json::Value>) -> Result<Vec<SearchResult>, ReplicationError> {
+        let body = serde_json::json!({ "vector": query, "k": k, "filter": filter });
+        let search: RemoteSearch = self.post(&format!("/api/collections/{}/search", collection), body.to_string()).await?;
+
+        // The REST API reports `score = 1 - distance`
+        Ok(search.results.into_iter()
+            .map(|hit| SearchResult {
+                id: hit.id,
+                distance: 1.0 - hit.score,
+            })
+            .collect())
+    }
+}
+
+#[derive(Default)]
+struct ReplicaState {
+    acked_lsn: Option<u64>,
+    /// The replica had every write the leader made before this time.
+    synced_at_ms: Option<u64>,
+    snapshots_installed: u64,
+    last_error: Option<String>,
+}
+
+struct Replica {
+    client: Arc<dyn ReplicaClient>,
+    /// Held for a whole shipment so a replica never gets two at once.
+    shipping: Mutex<()>,
+    state: std::sync::Mutex<ReplicaState>,
+}
+
+impl Replica {
+    fn lag_ms(&self, now: u64) -> Option<u64> {
+        self.state.lock().unwrap().synced_at_ms.map(|synced_at| now.saturating_sub(synced_at))
+    }
+}
+
+pub struct ReplicationLeader {
+    node_id: String,
+    db: Arc<RwLock<CoreTexDB>>,
+    config: ReplicationConfig,
+    replicas: Arc<RwLock<HashMap<String, Arc<Replica>>>>,
+    next_reader: AtomicUsize,
+}
+
+impl ReplicationLeader {
+    pub fn new(node_id: &str, db: Arc<RwLock<CoreTexDB>>) -> Self {
+        Self {
+            node_id: node_id.to_string(),
+            db,
+            config: ReplicationConfig::default(),
+            replicas: Arc::new(RwLock::new(HashMap::new())),
+            next_reader: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_config(mut self, config: ReplicationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Adds a replica, or replaces the client of a known one. Either way it
+    /// starts from a snapshot.
+    pub async fn add_replica(&self, node_id: &str, client: Arc<dyn ReplicaClient>) {
+        self.replicas.write().await.insert(node_id.to_string(), Arc::new(Replica {
+            client,
+            shipping: Mutex::new(()),
+            state: std::sync::Mutex::new(ReplicaState::default()),
+        }));
+    }
+
+    pub async fn remove_replica(&self, node_id: &str) -> bool {
+        self.replicas.write().await.remove(node_id).is_some()
+    }
+
+    /// The LSN of the leader database's last journal entry.
+    pub async fn last_lsn(&self) -> u64 {
+        self.db.read().await.journal.last_lsn().await
+    }
+
+    /// The whole database, taken while writes wait.
+    pub async fn snapshot(&self) -> Result<ReplicaSnapshot, ReplicationError> {
+        self.db.read().await.replica_snapshot().await.map_err(database_error)
+    }
+
+    /// When the journal entry `lsn` was written, in milliseconds.
+    async fn written_at(&self, lsn: u64) -> Option<u64> {
+        let db = self.db.read().await;
+        let entry = db.journal.tail(lsn.checked_sub(1)?, 1).await.ok()??.pop()?;
+        Some(entry.data["timestamp_ms"].as_u64().unwrap_or(entry.timestamp * 1000))
+    }
+
+    /// Ships to every replica until each is caught up or fails.
+    pub async fn ship(&self) {
+        let replicas: Vec<Arc<Replica>> = self.replicas.read().await.values().cloned().collect();
+        join_all(replicas.iter().map(|replica| self.ship_to(replica))).await;
+    }
+
+    async fn ship_to(&self, replica: &Replica) {
+        let _shipping = replica.shipping.lock().await;
+
+        loop {
+            let acked = replica.state.lock().unwrap().acked_lsn;
+            // Taken before reading the log: every write made before this
+            // time is in what gets read
+            let sent_at = now_ms();
+            let (batch, leader_lsn) = {
+                let db = self.db.read().await;
+                let leader_lsn = db.journal.last_lsn().await;
+                let batch = match acked {
+                    Some(acked) => db.journal.tail(acked, self.config.batch_size.max(1)).await,
+                    None => Ok(None),
+                };
+                (batch, leader_lsn)
+            };
+            let batch = match batch {
+                Ok(batch) => batch,
+                Err(e) => {
+                    replica.state.lock().unwrap().last_error = Some(e.to_string());
+                    return;
+                }
+            };
+
+            let result = match batch {
+                Some(entries) => replica.client.append(entries).await,
+                None => match self.snapshot().await {
+                    Ok(snapshot) => replica.client.install_snapshot(snapshot).await.inspect(|_| {
+                        replica.state.lock().unwrap().snapshots_installed += 1;
+                    }),
+                    Err(e) => Err(e),
+                },
+            };
+
+            let applied = match result {
+                // The replica lost its data, e.g. in a restart
+                Ok(applied) if acked.is_some_and(|acked| applied < acked) => None,
+                Ok(applied) => Some(applied),
+                Err(ReplicationError::LogGap { .. }) => None,
+                Err(e) => {
+                    replica.state.lock().unwrap().last_error = Some(e.to_string());
+                    return;
+                }
+            };
+
+            let behind = {
+                let mut state = replica.state.lock().unwrap();
+                state.acked_lsn = applied;
+                state.last_error = None;
+                match applied {
+                    Some(applied) if applied >= leader_lsn => {
+                        state.synced_at_ms = Some(sent_at);
+                        return;
+                    }
+                    behind => behind,
+                }
+            };
+            let Some(applied) = behind else {
+                continue;
+            };
+
+            // Everything written before the first missing entry is there
+            if let Some(written_at) = self.written_at(applied + 1).await {
+                replica.state.lock().unwrap().synced_at_ms = Some(written_at);
+            }
+        }
+    }
+
+    /// Ships every `ship_interval_ms` until the task is aborted.
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        let leader = self.clone();
+
+        tokio::spawn(async move {
+            let interval = Duration::from_millis(leader.config.ship_interval_ms.max(1));
+            loop {
+                leader.ship().await;
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    pub async fn status(&self) -> ReplicationStatus {
+        let leader_lsn = self.last_lsn().await;
+        let now = now_ms();
+
+        let mut replicas: Vec<ReplicaLag> = self.replicas.read().await.iter()
+            .map(|(node_id, replica)| {
+                let lag_ms = replica.lag_ms(now);
+                let state = replica.state.lock().unwrap();
+                ReplicaLag {
+                    node_id: node_id.clone(),
+                    acked_lsn: state.acked_lsn,
+                    lag_entries: leader_lsn - state.acked_lsn.unwrap_or(0).min(leader_lsn),
+                    lag_ms,
+                    snapshots_installed: state.snapshots_installed,
+                    last_error: state.last_error.clone(),
+                }
+            })
+            .collect();
+        replicas.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+        ReplicationStatus { leader_lsn, replicas }
+    }
+
+    /// Searches where `read` allows, spreading reads over the eligible
+    /// replicas. Falls back to the leader when none is fresh enough or all
+    /// of them fail.
+    pub async fn search(
+        &self,
+        collection: &str,
+        query: Vec<f32>,
+        k: usize,
+        filter: Option<serde_json::Value>,
+        read: ReadPreference,
+    ) -> Result<RoutedSearch, ReplicationError> {
+        if let ReadPreference::AnyReplica { max_lag_ms } = read {
+            let now = now_ms();
+            let mut candidates: Vec<(String, Arc<Replica>)> = self.replicas.read().await.iter()
+                .filter(|(_, replica)| replica.lag_ms(now).is_some_and(|lag| lag < max_lag_ms))
+                .map(|(node_id, replica)| (node_id.clone(), replica.clone()))
+                .collect();
+            candidates.sort_by(|a, b| a.0.cmp(&b.0));
+
+            if !candidates.is_empty() {
+                let start = self.next_reader.fetch_add(1, Ordering::Relaxed);
+                for i in 0..candidates.len() {
+                    let (node_id, replica) = &candidates[(start + i) % candidates.len()];
+                    if let Ok(results) = replica.client.search(collection, &query, k, filter.clone()).await {
+                        return Ok(RoutedSearch { node_id: node_id.clone(), results });
+                    }
+                }
+            }
+        }
+
+        let results = self.db.read().await.search(collection, query, k, filter, None).await.map_err(database_error)?;
+        Ok(RoutedSearch {
+            node_id: self.node_id.clone(),
+            results,
+        })
+    }
+}
+
+impl CoreTexDB {
+    /// Everything a replica needs, as of the journal's last entry. Journaled
+    /// writes hold the collection or document lock, so holding both keeps
+    /// any of them from coming between the data and its LSN.
+    pub async fn replica_snapshot(&self) -> crate::Result<ReplicaSnapshot> {
+        let collections = self.collections.read().await;
+        let data = self.data.read().await;
+
+        let mut names: Vec<&String> = collections.keys().collect();
+        names.sort();
+        let mut snapshot = Vec::with_capacity(names.len());
+        for name in names {
+            let schema = collections[name].clone();
+            let mut documents: Vec<VectorRecord> = data.get(name).into_iter().flatten()
+                .map(|(id, (vector, metadata))| (id.clone(), vector.clone(), metadata.clone()))
+                .collect();
+            if let Some(collection_data) = data.get(name) {
+                for id in self.tiered_out_ids(name) {
+                    if let Some((vector, metadata)) = self.document(collection_data, name, &id).await? {
+                        documents.push((id, vector, metadata));
+                    }
+                }
+            }
+            documents.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let mut side_vectors = BTreeMap::new();
+            for (id, _, _) in &documents {
+                let side = self.current_side_vectors(name, id).await;
+                if !side.is_empty() {
+                    side_vectors.insert(id.to_string(), side);
+                }
+            }
+            snapshot.push(SnapshotCollection {
+                name: name.clone(),
+                dimension: schema.dimension,
+                metric: schema.distance_metric.as_str().to_string(),
+                vectors: documents,
+                schema: Some(schema),
+                side_vectors,
+            });
+        }
+
+        Ok(ReplicaSnapshot {
+            lsn: self.journal.last_lsn().await,
+            collections: snapshot,
+        })
+    }
+
+    /// Replaces every collection with those of `snapshot`. The snapshot is
+    /// loaded into a database to one side, then swapped in while the
+    /// collection and data locks are held, so readers see either the old
+    /// collections or the new ones and never a collection missing or half
+    /// loaded.
+    pub async fn install_replica_snapshot(&self, snapshot: ReplicaSnapshot) -> crate::Result<()> {
+        let staged = CoreTexDB::with_config(crate::DbConfig { memory_only: true, ..self.config.clone() });
+        for collection in snapshot.collections {
+            match collection.schema {
+                Some(schema) => staged.put_collection_schema(schema).await?,
+                None => staged.create_collection(&collection.name, collection.dimension, &collection.metric).await?,
+            }
+            if !collection.vectors.is_empty() {
+                staged.insert_vectors(&collection.name, collection.vectors).await?;
+            }
+            if !collection.side_vectors.is_empty() {
+                staged.put_side_vectors(&collection.name, collection.side_vectors).await?;
+            }
+        }
+
+        let mut collections = self.collections.write().await;
+        let mut data = self.data.write().await;
+        for name in collections.keys() {
+            self.check_unreserved_collection(name)?;
+        }
+        let staged_collections = std::mem::take(&mut *staged.collections.write().await);
+        let staged_data = std::mem::take(&mut *staged.data.write().await);
+
+        // Every document and side vector set that goes, then every one that
+        // comes, with what it replaces
+        let mut writes = BTreeMap::new();
+        for (name, documents) in data.iter() {
+            for id in documents.keys().cloned().chain(self.tiered_out_ids(name)) {
+                writes.insert((side_collection(name), id.clone()), None);
+                writes.insert((name.clone(), id), None);
+            }
+        }
+        for (name, documents) in &staged_data {
+            for (id, document) in documents {
+                let side = staged.current_side_vectors(name, id).await.to_value();
+                writes.insert((side_collection(name), id.clone()), side);
+                writes.insert((name.clone(), id.clone()), Some(document.clone()));
+            }
+        }
+        let mut changes = Vec::with_capacity(writes.len());
+        for ((collection, id), value) in &writes {
+            let before = match main_collection(collection) {
+                Some(main) if data.contains_key(main) => self.current_side_vectors(main, id).await.to_value(),
+                Some(_) => None,
+                None => match data.get(collection) {
+                    Some(collection_data) => self.document(collection_data, collection, id).await?,
+                    None => None,
+                },
+            };
+            if before.is_some() || value.is_some() {
+                changes.push(((collection.clone(), id.clone()), before));
+            }
+        }
+
+        {
+            let storage = self.storage.read().await;
+            for ((collection, id), value) in &writes {
+                let key = format!("{}:{}", collection, id);
+                match value {
+                    Some((vector, metadata)) => storage.store(&key, vector, metadata).await,
+                    None => storage.delete(&key).await.map(|_| ()),
+                }.map_err(|e| CoreTexError::StorageError(e.to_string()))?;
+            }
+        }
+        if self.journal.is_open().await {
+            let collections_dir = std::path::PathBuf::from(&self.config.data_dir).join("collections");
+            for name in collections.keys().filter(|name| !staged_collections.contains_key(*name)) {
+                for dir in [name.clone(), side_collection(name)] {
+                    if collections_dir.join(&dir).exists() {
+                        std::fs::remove_dir_all(collections_dir.join(&dir))?;
+                    }
+                }
+            }
+            for schema in staged_collections.values() {
+                crate::coretex_journal::write_schema(&self.config.data_dir, schema)?;
+            }
+
+            // Storage holds the snapshot now; nothing journaled before it
+            // is to be replayed over it
+            let mut metadata = self.load_metadata().await?;
+            metadata.applied_lsn = self.journal.last_lsn().await;
+            metadata.collections = staged_collections.keys().cloned().collect();
+            metadata.last_modified = chrono::Utc::now().timestamp() as u64;
+            self.save_metadata(&metadata).await?;
+        }
+
+        self.versions.record(changes);
+        self.index_manager.replace_with(&staged.index_manager).await;
+        self.named_vectors.replace_with(&staged.named_vectors).await;
+        self.sparse_vectors.replace_with(&staged.sparse_vectors).await;
+        self.multi_vectors.replace_with(&staged.multi_vectors).await;
+        self.tiered_out.write().unwrap().clear();
+        *collections = staged_collections;
+        *data = staged_data;
+
+        self.feed_lakehouse(writes.into_iter()
+            .filter(|((collection, _), _)| main_collection(collection).is_none())
+            .collect());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vectors(ids: &[&str]) -> Vec<VectorRecord> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| (id.to_string(), vec![1.0, i as f32, 0.0], serde_json::json!({ "n": i })))
+            .collect()
+    }
+
+    fn replica() -> (Arc<ReplicaNode>, Arc<RwLock<CoreTexDB>>) {
+        let db = Arc::new(RwLock::new(CoreTexDB::new()));
+        (Arc::new(ReplicaNode::new(db.clone())), db)
+    }
+
+    /// A leader over a durable database, with a collection `docs`.
+    async fn leader(config: ReplicationConfig) -> (ReplicationLeader, Arc<RwLock<CoreTexDB>>, tempfile::TempDir) {
+        let root = tempfile::tempdir().unwrap();
+        let db = CoreTexDB::open(crate::DbConfig::new(&root.path().to_string_lossy())).await.unwrap();
+        db.create_collection("docs", 3, "cosine").await.unwrap();
+        let db = Arc::new(RwLock::new(db));
+        (ReplicationLeader::new("leader", db.clone()).with_config(config), db, root)
+    }
+
+    #[tokio::test]
+    async fn test_replicas_follow_leader_writes() {
+        let (leader, leader_db, _root) = leader(ReplicationConfig::default()).await;
+        let (replica1, db1) = replica();
+        let (replica2, db2) = replica();
+        leader.add_replica("r1", replica1.clone()).await;
+        leader.add_replica("r2", replica2.clone()).await;
+
+        leader_db.read().await.insert_vectors("docs", vectors(&["a", "b", "c"])).await.unwrap();
+        leader.ship().await;
+        {
+            // A transaction ships as the one entry it is journaled as
+            let db = leader_db.read().await;
+            let txn = db.begin().await.unwrap();
+            txn.delete("docs", &["b".to_string()]).await.unwrap();
+            txn.insert("docs", vec![("d".to_string(), vec![0.0, 0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+            txn.commit().await.unwrap();
+        }
+        let lsn = leader.last_lsn().await;
+        leader.ship().await;
+
+        for db in [&db1, &db2] {
+            let db = db.read().await;
+            assert_eq!(db.get_vectors_count("docs").await.unwrap(), 3);
+            assert!(db.get_vector("docs", "b", None).await.unwrap().is_none());
+            assert!(db.get_vector("docs", "d", None).await.unwrap().is_some());
+        }
+        assert_eq!(replica1.applied_lsn().await, lsn);
+
+        let status = leader.status().await;
+        assert_eq!(status.leader_lsn, lsn);
+        for lag in &status.replicas {
+            assert_eq!(lag.acked_lsn, Some(lsn));
+            assert_eq!(lag.lag_entries, 0);
+            assert!(lag.lag_ms.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_replaces_a_durable_replica_in_one_step() {
+        let (_leader, leader_db, _root) = leader(ReplicationConfig::default()).await;
+        leader_db.read().await.insert_vectors("docs", vectors(&["a", "b"])).await.unwrap();
+        let snapshot = leader_db.read().await.replica_snapshot().await.unwrap();
+
+        let root = tempfile::tempdir().unwrap();
+        let config = crate::DbConfig::new(&root.path().to_string_lossy());
+        {
+            let db = CoreTexDB::open(config.clone()).await.unwrap();
+            db.create_collection("stale", 3, "cosine").await.unwrap();
+            db.insert_vectors("stale", vectors(&["x"])).await.unwrap();
+            db.create_collection("docs", 3, "cosine").await.unwrap();
+            db.insert_vectors("docs", vectors(&["old"])).await.unwrap();
+
+            db.install_replica_snapshot(snapshot).await.unwrap();
+            assert_eq!(db.list_collections().await.unwrap(), vec!["docs".to_string()]);
+            assert_eq!(db.get_vectors_count("docs").await.unwrap(), 2);
+            assert!(db.get_vector("docs", "old", None).await.unwrap().is_none());
+            assert_eq!(db.search("docs", vec![1.0, 1.0, 0.0], 5, None, None).await.unwrap().len(), 2);
+        }
+
+        // Nothing journaled before the snapshot is replayed over it
+        let db = CoreTexDB::open(config).await.unwrap();
+        assert_eq!(db.list_collections().await.unwrap(), vec!["docs".to_string()]);
+        assert_eq!(db.get_vectors_count("docs").await.unwrap(), 2);
+        assert!(db.get_vector("docs", "old", None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_replica_catches_up_from_snapshot_and_tail() {
+        let (leader, leader_db, _root) = leader(ReplicationConfig {
+            batch_size: 2,
+            ..Default::default()
+        }).await;
+        for id in ["a", "b", "c", "d"] {
+            leader_db.read().await.insert_vectors("docs", vectors(&[id])).await.unwrap();
+        }
+
+        let (replica1, db1) = replica();
+        leader.add_replica("r1", replica1.clone()).await;
+        leader.ship().await;
+        assert_eq!(db1.read().await.get_vectors_count("docs").await.unwrap(), 4);
+
+        for id in ["e", "f", "g"] {
+            leader_db.read().await.insert_vectors("docs", vectors(&[id])).await.unwrap();
+        }
+        leader.ship().await;
+        let status = leader.status().await;
+        assert_eq!(status.replicas[0].snapshots_installed, 1);
+        assert_eq!(status.replicas[0].acked_lsn, Some(status.leader_lsn));
+        assert_eq!(db1.read().await.get_vectors_count("docs").await.unwrap(), 7);
+
+        // A restarted replica has nothing and gets a fresh snapshot
+        let (restarted, db2) = replica();
+        leader.add_replica("r1", restarted).await;
+        leader.ship().await;
+        assert_eq!(db2.read().await.get_vectors_count("docs").await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_carries_schema_and_side_vectors() {
+        let (leader, leader_db, _root) = leader(ReplicationConfig::default()).await;
+        {
+            let db = leader_db.read().await;
+            db.insert_vectors("docs", vectors(&["a", "b"])).await.unwrap();
+            db.enable_multi_vector("docs", crate::MultiVectorConfig {
+                dimension: 2,
+                distance_metric: crate::coretex_core::DistanceMetric::DotProduct,
+                index: crate::IndexConfig {
+                    name: "docs.multi_vector".to_string(),
+                    index_type: crate::IndexType::BruteForce,
+                    parameters: HashMap::new(),
+                },
+                candidates_per_token: 10,
+            }).await.unwrap();
+            db.set_multi_vector("docs", "a", vec![vec![1.0, 0.0]]).await.unwrap();
+        }
+
+        let (replica1, db1) = replica();
+        leader.add_replica("r1", replica1).await;
+        leader.ship().await;
+
+        // Side vectors written after the snapshot ship in the tail
+        leader_db.read().await
+            .set_sparse_vector("docs", "b", crate::SparseVector::new(vec![7], vec![1.0]).unwrap()).await.unwrap();
+        leader.ship().await;
+
+        let db = db1.read().await;
+        assert!(db.get_collection("docs").await.unwrap().multi_vector.is_some());
+        assert_eq!(db.get_multi_vector("docs", "a").await.unwrap().unwrap(), vec![vec![1.0, 0.0]]);
+        assert_eq!(db.get_sparse_vector("docs", "b").await.unwrap().unwrap().indices(), &[7]);
+    }
+
+    #[tokio::test]
+    async fn test_leader_needs_a_journal() {
+        let leader = ReplicationLeader::new("leader", Arc::new(RwLock::new(CoreTexDB::new())));
+        let (replica1, _) = replica();
+        leader.add_replica("r1", replica1).await;
+        leader.ship().await;
+        leader.ship().await;
+
+        let status = leader.status().await;
+        assert!(status.replicas[0].last_error.as_deref().is_some_and(|e| e.contains("journal")));
+    }
+
+    #[tokio::test]
+    async fn test_read_routing_respects_staleness() {
+        let (leader, leader_db, _root) = leader(ReplicationConfig::default()).await;
+        let (replica1, _) = replica();
+        let (replica2, _) = replica();
+        leader.add_replica("r1", replica1).await;
+        leader.add_replica("r2", replica2).await;
+        leader_db.read().await.insert_vectors("docs", vectors(&["a", "b"])).await.unwrap();
+
+        // Never shipped to: no replica qualifies yet
+        let read = ReadPreference::AnyReplica { max_lag_ms: 60_000 };
+        let routed = leader.search("docs", vec![1.0, 0.0, 0.0], 2, None, read).await.unwrap();
+        assert_eq!(routed.node_id, "leader");
+
+        leader.ship().await;
+        let mut served_by = Vec::new();
+        for _ in 0..2 {
+            let routed = leader.search("docs", vec![1.0, 0.0, 0.0], 2, None, read).await.unwrap();
+            assert_eq!(routed.results[0].id, "a");
+            served_by.push(routed.node_id);
+        }
+        served_by.sort();
+        assert_eq!(served_by, vec!["r1", "r2"]);
+
+        let strict = ReadPreference::AnyReplica { max_lag_ms: 0 };
+        assert_eq!(leader.search("docs", vec![1.0, 0.0, 0.0], 2, None, strict).await.unwrap().node_id, "leader");
+        let routed = leader.search("docs", vec![1.0, 0.0, 0.0], 2, None, ReadPreference::LeaderOnly).await.unwrap();
+        assert_eq!(routed.node_id, "leader");
+    }
+
+    #[tokio::test]
+    async fn test_background_shipping() {
+        let (leader, leader_db, _root) = leader(ReplicationConfig {
+            ship_interval_ms: 10,
+            ..Default::default()
+        }).await;
+        let leader = Arc::new(leader);
+        let (replica1, db1) = replica();
+        leader.add_replica("r1", replica1.clone()).await;
+        let shipper = leader.start();
+
+        leader_db.read().await.insert_vectors("docs", vectors(&["a"])).await.unwrap();
+        let lsn = leader.last_lsn().await;
+        for _ in 0..100 {
+            if replica1.applied_lsn().await == lsn {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shipper.abort();
+
+        assert_eq!(replica1.applied_lsn().await, lsn);
+        assert!(db1.read().await.get_vector("docs", "a", None).await.unwrap().is_some());
+    }
+}
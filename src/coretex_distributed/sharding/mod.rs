@@ -0,0 +1,1149 @@
+//! Sharded collections
+//! A sharded collection is split into shards by hashing or by ranges of the
+//! vector id. Each shard is a regular collection (`<name>_shard_<n>`) on the
+//! nodes `ClusterManager` places it on. `ShardCoordinator` routes writes by
+//! id and answers searches by asking every shard for its own top-k and
+//! merging the hits by distance. `rebalance` moves shards between nodes
+//! while they keep serving.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::coretex_utils::cluster::{ClusterManager, Shard};
+use crate::CoreTexDB;
+use super::DistributedOperation;
+
+mod rebalance;
+
+pub use rebalance::{RebalanceConfig, ShardMove, MigrationState, MigrationProgress, RebalanceProgress, RebalanceReport};
+use rebalance::Migration;
+
+/// A vector as it travels between nodes: id, vector, metadata.
+pub type VectorRecord = (String, Vec<f32>, serde_json::Value);
+
+#[derive(Debug, Clone)]
+pub enum ShardingError {
+    CollectionNotFound(String),
+    CollectionExists(String),
+    InvalidStrategy(String),
+    NoNodes(String),
+    NodeError(String),
+    /// Carries the shards that did not answer in time.
+    PartialResults(Vec<u32>),
+    MigrationFailed(String),
+    StorageError(String),
+    /// Some copies of a shard took a write and others did not.
+    PartialWrite(String),
+}
+
+impl std::fmt::Display for ShardingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShardingError::CollectionNotFound(name) => write!(f, "Sharded collection not found: {}", name),
+            ShardingError::CollectionExists(name) => write!(f, "Sharded collection already exists: {}", name),
+            ShardingError::InvalidStrategy(msg) => write!(f, "Invalid sharding strategy: {}", msg),
+            ShardingError::NoNodes(msg) => write!(f, "No nodes available: {}", msg),
+            ShardingError::NodeError(msg) => write!(f, "Node error: {}", msg),
+            ShardingError::PartialResults(shards) => write!(f, "Shards {:?} did not respond", shards),
+            ShardingError::MigrationFailed(msg) => write!(f, "Shard migration failed: {}", msg),
+            ShardingError::StorageError(msg) => write!(f, "Shard registry error: {}", msg),
+            ShardingError::PartialWrite(msg) => write!(f, "Write reached only some copies of a shard: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ShardingError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShardingStrategy {
+    /// `fnv1a(id) % shards`.
+    Hash { shards: u32 },
+    /// Shard `i` holds ids in `[split_points[i - 1], split_points[i])`, so
+    /// there is one more shard than split points.
+    Range { split_points: Vec<String> },
+}
+
+impl ShardingStrategy {
+    pub fn shard_count(&self) -> u32 {
+        match self {
+            ShardingStrategy::Hash { shards } => *shards,
+            ShardingStrategy::Range { split_points } => split_points.len() as u32 + 1,
+        }
+    }
+
+    pub fn shard_for(&self, id: &str) -> u32 {
+        match self {
+            ShardingStrategy::Hash { shards } => (fnv1a(id.as_bytes()) % *shards as u64) as u32,
+            ShardingStrategy::Range { split_points } => {
+                split_points.partition_point(|point| point.as_str() <= id) as u32
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), ShardingError> {
+        match self {
+            ShardingStrategy::Hash { shards: 0 } => {
+                Err(ShardingError::InvalidStrategy("at least one shard is required".to_string()))
+            }
+            ShardingStrategy::Range { split_points } if split_points.windows(2).any(|w| w[0] >= w[1]) => {
+                Err(ShardingError::InvalidStrategy("split points must be strictly increasing".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Stable across processes and Rust versions, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardedCollection {
+    pub name: String,
+    pub dimension: usize,
+    pub metric: String,
+    pub strategy: ShardingStrategy,
+    /// `ClusterManager` shard id of each shard, indexed by shard number.
+    pub cluster_shards: Vec<u32>,
+}
+
+impl ShardedCollection {
+    pub fn shard_collection(&self, shard: u32) -> String {
+        format!("{}_shard_{}", self.name, shard)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardHit {
+    pub id: String,
+    pub distance: f32,
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShardedSearchResult {
+    /// Merged hits, nearest first.
+    pub hits: Vec<ShardHit>,
+    pub shards_queried: usize,
+    /// Shards that timed out or failed on every node holding them; the hits
+    /// are missing whatever those shards held.
+    pub failed_shards: Vec<u32>,
+}
+
+impl ShardedSearchResult {
+    pub fn is_partial(&self) -> bool {
+        !self.failed_shards.is_empty()
+    }
+}
+
+/// Operations the coordinator runs against one node's collections.
+#[async_trait]
+pub trait ShardClient: Send + Sync {
+    async fn create_collection(&self, name: &str, dimension: usize, metric: &str) -> Result<(), ShardingError>;
+    async fn delete_collection(&self, name: &str) -> Result<(), ShardingError>;
+    async fn insert(&self, collection: &str, vectors: Vec<VectorRecord>) -> Result<(), ShardingError>;
+    async fn get(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, ShardingError>;
+    async fn delete(&self, collection: &str, ids: &[String]) -> Result<usize, ShardingError>;
+    async fn search(&self, collection: &str, query: &[f32], k: usize, filter: Option<serde_json::Value>) -> Result<Vec<ShardHit>, ShardingError>;
+    async fn count(&self, collection: &str) -> Result<usize, ShardingError>;
+    /// Up to `limit` vectors in id order, starting after `after`.
+    async fn scan(&self, collection: &str, after: Option<&str>, limit: usize) -> Result<Vec<VectorRecord>, ShardingError>;
+}
+
+/// A node running in this process.
+pub struct LocalShardClient {
+    db: Arc<CoreTexDB>,
+}
+
+impl LocalShardClient {
+    pub fn new(db: Arc<CoreTexDB>) -> Self {
+        Self { db }
+    }
+}
+
+fn node_error(e: impl std::fmt::Display) -> ShardingError {
+    ShardingError::NodeError(e.to_string())
+}
+
+#[async_trait]
+impl ShardClient for LocalShardClient {
+    async fn create_collection(&self, name: &str, dimension: usize, metric: &str) -> Result<(), ShardingError> {
+        self.db.create_collection(name, dimension, metric).await.map_err(node_error)
+    }
+
+    async fn delete_collection(&self, name: &str) -> Result<(), ShardingError> {
+        self.db.delete_collection(name).await.map_err(node_error)
+    }
+
+    async fn insert(&self, collection: &str, vectors: Vec<VectorRecord>) -> Result<(), ShardingError> {
+        self.db.insert_vectors(collection, vectors).await.map(|_| ()).map_err(node_error)
+    }
+
+    async fn get(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, ShardingError> {
+        self.db.get_vector(collection, id, None).await.map_err(node_error)
+    }
+
+    async fn delete(&self, collection: &str, ids: &[String]) -> Result<usize, ShardingError> {
+        self.db.delete_vectors(collection, ids).await.map_err(node_error)
+    }
+
+    async fn search(&self, collection: &str, query: &[f32], k: usize, filter: Option<serde_json::Value>) -> Result<Vec<ShardHit>, ShardingError> {
+        let results = self.db.search(collection, query.to_vec(), k, filter, None).await.map_err(node_error)?;
+
+        let mut hits = Vec::with_capacity(results.len());
+        for result in results {
+            let metadata = self.db.get_vector(collection, &result.id, None).await
+                .map_err(node_error)?
+                .map(|(_, metadata)| metadata);
+            hits.push(ShardHit {
+                id: result.id,
+                distance: result.distance,
+                metadata,
+            });
+        }
+        Ok(hits)
+    }
+
+    async fn count(&self, collection: &str) -> Result<usize, ShardingError> {
+        self.db.get_vectors_count(collection).await.map_err(node_error)
+    }
+
+    async fn scan(&self, collection: &str, after: Option<&str>, limit: usize) -> Result<Vec<VectorRecord>, ShardingError> {
+        self.db.scan_vectors(collection, after, limit).await.map_err(node_error)
+    }
+}
+
+/// A node reached through its REST API.
+pub struct HttpShardClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+/// The subset of the REST `ApiResponse` envelope the client reads.
+#[derive(Deserialize)]
+pub(super) struct Envelope<T> {
+    pub(super) data: Option<T>,
+    pub(super) error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoteVector {
+    vector: Vec<f32>,
+    metadata: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct RemoteDeleted {
+    deleted_count: usize,
+}
+
+#[derive(Deserialize)]
+struct RemoteScan {
+    vectors: Vec<RemoteScanItem>,
+}
+
+#[derive(Deserialize)]
+struct RemoteScanItem {
+    id: String,
+    vector: Vec<f32>,
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RemoteSearch {
+    pub(super) results: Vec<RemoteHit>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RemoteHit {
+    pub(super) id: String,
+    pub(super) score: f32,
+    pub(super) metadata: Option<serde_json::Value>,
+}
+
+impl HttpShardClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<serde_json::Value>,
+    ) -> Result<Option<T>, ShardingError> {
+        let url = format!("{}/api/collections{}", self.base_url, path);
+        let mut request = self.client.request(method, url).query(query);
+        if let Some(body) = body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+
+        let response = request.send().await.map_err(node_error)?;
+        let bytes = response.bytes().await.map_err(node_error)?;
+        let envelope: Envelope<T> = serde_json::from_slice(&bytes).map_err(node_error)?;
+        match (envelope.data, envelope.error) {
+            (Some(data), _) => Ok(Some(data)),
+            (None, Some(error)) if error == "Vector not found" => Ok(None),
+            (None, Some(error)) => Err(ShardingError::NodeError(error)),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl ShardClient for HttpShardClient {
+    async fn create_collection(&self, name: &str, dimension: usize, metric: &str) -> Result<(), ShardingError> {
+        let body = serde_json::json!({ "name": name, "dimension": dimension, "distance_metric": metric });
+        self.call::<serde_json::Value>(reqwest::Method::POST, "", &[], Some(body)).await.map(|_| ())
+    }
+
+    async fn delete_collection(&self, name: &str) -> Result<(), ShardingError> {
+        self.call::<serde_json::Value>(reqwest::Method::DELETE, &format!("/{}", name), &[], None).await.map(|_| ())
+    }
+
+    async fn insert(&self, collection: &str, vectors: Vec<VectorRecord>) -> Result<(), ShardingError> {
+        let vectors: Vec<serde_json::Value> = vectors.into_iter()
+            .map(|(id, vector, metadata)| serde_json::json!({ "id": id, "vector": vector, "metadata": metadata }))
+            .collect();
+        let body = serde_json::json!({ "vectors": vectors });
+        self.call::<serde_json::Value>(reqwest::Method::POST, &format!("/{}/vectors", collection), &[], Some(body)).await.map(|_| ())
+    }
+
+    async fn get(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, ShardingError> {
+        let vector: Option<RemoteVector> = self.call(reqwest::Method::GET, &format!("/{}/vectors/{}", collection, id), &[], None).await?;
+        Ok(vector.map(|v| (v.vector, v.metadata)))
+    }
+
+    async fn delete(&self, collection: &str, ids: &[String]) -> Result<usize, ShardingError> {
+        let body = serde_json::json!({ "ids": ids });
+        let deleted: Option<RemoteDeleted> = self.call(reqwest::Method::DELETE, &format!("/{}/vectors", collection), &[], Some(body)).await?;
+        Ok(deleted.map_or(0, |d| d.deleted_count))
+    }
+
+    async fn search(&self, collection: &str, query: &[f32], k: usize, filter: Option<serde_json::Value>) -> Result<Vec<ShardHit>, ShardingError> {
+        let body = serde_json::json!({ "vector": query, "k": k, "filter": filter });
+        let search: Option<RemoteSearch> = self.call(reqwest::Method::POST, &format!("/{}/search", collection), &[], Some(body)).await?;
+
+        // The REST API reports `score = 1 - distance`
+        Ok(search.map(|s| s.results).unwrap_or_default()
+            .into_iter()
+            .map(|hit| ShardHit {
+                id: hit.id,
+                distance: 1.0 - hit.score,
+                metadata: hit.metadata,
+            })
+            .collect())
+    }
+
+    async fn count(&self, collection: &str) -> Result<usize, ShardingError> {
+        let count: Option<usize> = self.call(reqwest::Method::GET, &format!("/{}/count", collection), &[], None).await?;
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn scan(&self, collection: &str, after: Option<&str>, limit: usize) -> Result<Vec<VectorRecord>, ShardingError> {
+        let mut query = vec![("limit", limit.to_string())];
+        if let Some(after) = after {
+            query.push(("after", after.to_string()));
+        }
+
+        let scan: Option<RemoteScan> = self.call(reqwest::Method::GET, &format!("/{}/vectors", collection), &query, None).await?;
+        Ok(scan.map(|s| s.vectors).unwrap_or_default()
+            .into_iter()
+            .map(|item| (item.id, item.vector, item.metadata.unwrap_or(serde_json::json!({}))))
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShardingConfig {
+    /// How long one node gets to answer a shard search before the next
+    /// replica is tried.
+    pub shard_timeout_ms: u64,
+    /// Return what the other shards found when a shard fails, instead of an
+    /// error.
+    pub allow_partial_results: bool,
+}
+
+impl Default for ShardingConfig {
+    fn default() -> Self {
+        Self {
+            shard_timeout_ms: 1000,
+            allow_partial_results: true,
+        }
+    }
+}
+
+pub struct ShardCoordinator {
+    cluster: Arc<ClusterManager>,
+    config: ShardingConfig,
+    clients: Arc<RwLock<HashMap<String, Arc<dyn ShardClient>>>>,
+    collections: Arc<RwLock<HashMap<String, ShardedCollection>>>,
+    rebalance_config: RebalanceConfig,
+    /// Shard moves in flight, by cluster shard id. Writes hold the read lock
+    /// for their whole duration so a routing switch never races one.
+    migrations: Arc<RwLock<HashMap<u32, Arc<Migration>>>>,
+    /// Moves of the current or most recent rebalance, for progress reports.
+    migration_history: Arc<RwLock<Vec<Arc<Migration>>>>,
+    /// File the collections and their shard placement are saved to.
+    registry: Option<PathBuf>,
+}
+
+/// What `ShardCoordinator` saves to its registry file.
+#[derive(Serialize, Deserialize)]
+struct Registry {
+    collections: Vec<ShardedCollection>,
+    shards: Vec<Shard>,
+}
+
+impl ShardCoordinator {
+    pub fn new(cluster: Arc<ClusterManager>) -> Self {
+        Self {
+            cluster,
+            config: ShardingConfig::default(),
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            collections: Arc::new(RwLock::new(HashMap::new())),
+            rebalance_config: RebalanceConfig::default(),
+            migrations: Arc::new(RwLock::new(HashMap::new())),
+            migration_history: Arc::new(RwLock::new(Vec::new())),
+            registry: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: ShardingConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_rebalance_config(mut self, config: RebalanceConfig) -> Self {
+        self.rebalance_config = config;
+        self
+    }
+
+    /// Saves the sharded collections, and the nodes their shards are on, to
+    /// `path` whenever they change. `load_registry` reads them back.
+    pub fn with_registry(mut self, path: impl Into<PathBuf>) -> Self {
+        self.registry = Some(path.into());
+        self
+    }
+
+    /// Restores the collections and shard placement saved by an earlier
+    /// run, returning how many collections were restored.
+    pub async fn load_registry(&self) -> Result<usize, ShardingError> {
+        let Some(path) = &self.registry else {
+            return Ok(0);
+        };
+        let Some(registry) = read_registry(path)? else {
+            return Ok(0);
+        };
+
+        for shard in registry.shards {
+            self.cluster.restore_shard(shard).await;
+        }
+        let mut collections = self.collections.write().await;
+        for collection in registry.collections {
+            collections.insert(collection.name.clone(), collection);
+        }
+        Ok(collections.len())
+    }
+
+    /// Makes a cluster node usable for shards. Nodes must also be active
+    /// members of the `ClusterManager`.
+    pub async fn register_node(&self, node_id: &str, client: Arc<dyn ShardClient>) {
+        self.clients.write().await.insert(node_id.to_string(), client);
+    }
+
+    pub async fn get_collection(&self, name: &str) -> Option<ShardedCollection> {
+        self.collections.read().await.get(name).cloned()
+    }
+
+    pub async fn list_collections(&self) -> Vec<String> {
+        self.collections.read().await.keys().cloned().collect()
+    }
+
+    /// Spreads the shards round-robin over the active, registered nodes and
+    /// creates each shard's collection on its primary and replicas. If that
+    /// fails, whatever was created is removed again.
+    pub async fn create_collection(
+        &self,
+        name: &str,
+        dimension: usize,
+        metric: &str,
+        strategy: ShardingStrategy,
+    ) -> Result<ShardedCollection, ShardingError> {
+        strategy.validate()?;
+        // Held throughout, so no other create takes the name or shard ids
+        let mut collections = self.collections.write().await;
+        if collections.contains_key(name) {
+            return Err(ShardingError::CollectionExists(name.to_string()));
+        }
+
+        let mut nodes: Vec<String> = {
+            let clients = self.clients.read().await;
+            self.cluster.get_active_nodes().await
+                .into_iter()
+                .map(|node| node.id)
+                .filter(|id| clients.contains_key(id))
+                .collect()
+        };
+        if nodes.is_empty() {
+            return Err(ShardingError::NoNodes(format!("cannot place shards of '{}'", name)));
+        }
+        nodes.sort();
+
+        let mut collection = ShardedCollection {
+            name: name.to_string(),
+            dimension,
+            metric: metric.to_string(),
+            strategy,
+            cluster_shards: Vec::new(),
+        };
+        let mut created = Vec::new();
+        let mut placed = self.place_shards(&mut collection, &nodes, &mut created).await;
+        if placed.is_ok() {
+            collections.insert(name.to_string(), collection.clone());
+            placed = self.save_registry(&collections).await;
+        }
+
+        if let Err(e) = placed {
+            collections.remove(name);
+            // Best effort: a shard collection left behind is only unused
+            for (node, shard_collection) in created {
+                if let Ok(client) = self.client(&node).await {
+                    let _ = client.delete_collection(&shard_collection).await;
+                }
+            }
+            for cluster_shard in &collection.cluster_shards {
+                self.cluster.remove_shard(*cluster_shard).await;
+            }
+            return Err(e);
+        }
+        Ok(collection)
+    }
+
+    /// Places each shard of `collection` and creates its collection on every
+    /// node holding it, recording in `created` where that succeeded.
+    async fn place_shards(
+        &self,
+        collection: &mut ShardedCollection,
+        nodes: &[String],
+        created: &mut Vec<(String, String)>,
+    ) -> Result<(), ShardingError> {
+        let first_id = self.cluster.next_shard_id().await;
+        for shard in 0..collection.strategy.shard_count() {
+            let cluster_shard = first_id + shard;
+            let primary = &nodes[shard as usize % nodes.len()];
+            self.cluster.create_shard(cluster_shard, primary).await;
+            collection.cluster_shards.push(cluster_shard);
+
+            let shard_collection = collection.shard_collection(shard);
+            for node in self.shard_nodes(cluster_shard).await {
+                self.client(&node).await?
+                    .create_collection(&shard_collection, collection.dimension, &collection.metric)
+                    .await?;
+                created.push((node, shard_collection.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete_collection(&self, name: &str) -> Result<(), ShardingError> {
+        let collection = {
+            let mut collections = self.collections.write().await;
+            let collection = collections.remove(name)
+                .ok_or(ShardingError::CollectionNotFound(name.to_string()))?;
+            self.save_registry(&collections).await?;
+            collection
+        };
+
+        for (shard, cluster_shard) in collection.cluster_shards.iter().enumerate() {
+            for node in self.shard_nodes(*cluster_shard).await {
+                self.client(&node).await?
+                    .delete_collection(&collection.shard_collection(shard as u32))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Groups vectors by shard and writes each group to the shard's primary
+    /// and replicas, and to the new owner of a shard that is being moved.
+    pub async fn insert(&self, collection: &str, vectors: Vec<VectorRecord>) -> Result<Vec<String>, ShardingError> {
+        let migrations = self.migrations.read().await;
+        let sharded = self.collection(collection).await?;
+        let ids: Vec<String> = vectors.iter().map(|(id, _, _)| id.clone()).collect();
+
+        let mut groups: HashMap<u32, Vec<VectorRecord>> = HashMap::new();
+        for vector in vectors {
+            groups.entry(sharded.strategy.shard_for(&vector.0)).or_default().push(vector);
+        }
+
+        let writes = groups.into_iter().map(|(shard, group)| {
+            let (sharded, migrations) = (&sharded, &migrations);
+            async move {
+                let name = sharded.shard_collection(shard);
+                let cluster_shard = sharded.cluster_shards[shard as usize];
+                self.write_shard(sharded, shard, &ShardWrite::Insert(group.clone())).await?;
+                if let Some(migration) = migrations.get(&cluster_shard) {
+                    migration.forward_insert(self.client(&migration.shard_move.to).await?.as_ref(), &name, group).await?;
+                }
+                Ok::<_, ShardingError>(())
+            }
+        });
+        for result in futures::future::join_all(writes).await {
+            result?;
+        }
+
+        Ok(ids)
+    }
+
+    /// Reads from the shard's primary, falling back to its replicas.
+    pub async fn get(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, ShardingError> {
+        let sharded = self.collection(collection).await?;
+        let shard = sharded.strategy.shard_for(id);
+        let name = sharded.shard_collection(shard);
+
+        let mut last_error = ShardingError::NoNodes(format!("shard {} of '{}'", shard, collection));
+        for node in self.shard_nodes(sharded.cluster_shards[shard as usize]).await {
+            match self.client(&node).await?.get(&name, id).await {
+                Ok(vector) => return Ok(vector),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    pub async fn delete(&self, collection: &str, ids: &[String]) -> Result<usize, ShardingError> {
+        let migrations = self.migrations.read().await;
+        let sharded = self.collection(collection).await?;
+
+        let mut groups: HashMap<u32, Vec<String>> = HashMap::new();
+        for id in ids {
+            groups.entry(sharded.strategy.shard_for(id)).or_default().push(id.clone());
+        }
+
+        let mut deleted = 0;
+        for (shard, group) in groups {
+            let name = sharded.shard_collection(shard);
+            let cluster_shard = sharded.cluster_shards[shard as usize];
+            let counts = self.write_shard(&sharded, shard, &ShardWrite::Delete(group.clone())).await?;
+            // Replicas hold the same ids; count the primary only
+            deleted += counts[0];
+            if let Some(migration) = migrations.get(&cluster_shard) {
+                migration.forward_delete(self.client(&migration.shard_move.to).await?.as_ref(), &name, &group).await?;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Vectors in all shards, as counted by each shard's primary.
+    pub async fn count(&self, collection: &str) -> Result<usize, ShardingError> {
+        let sharded = self.collection(collection).await?;
+
+        let mut total = 0;
+        for (shard, cluster_shard) in sharded.cluster_shards.iter().enumerate() {
+            let primary = self.shard_nodes(*cluster_shard).await.into_iter().next()
+                .ok_or_else(|| ShardingError::NoNodes(format!("shard {} of '{}'", shard, collection)))?;
+            total += self.client(&primary).await?.count(&sharded.shard_collection(shard as u32)).await?;
+        }
+        Ok(total)
+    }
+
+    /// Asks every shard for its `k` nearest hits in parallel and keeps the
+    /// overall `k` nearest. A shard that fails or times out on all of its
+    /// nodes is reported in `failed_shards`, or fails the whole search when
+    /// partial results are disabled.
+    pub async fn search(
+        &self,
+        collection: &str,
+        query: Vec<f32>,
+        k: usize,
+        filter: Option<serde_json::Value>,
+    ) -> Result<ShardedSearchResult, ShardingError> {
+        let sharded = self.collection(collection).await?;
+        let timeout = Duration::from_millis(self.config.shard_timeout_ms);
+
+        let searches = (0..sharded.strategy.shard_count()).map(|shard| {
+            let name = sharded.shard_collection(shard);
+            let cluster_shard = sharded.cluster_shards[shard as usize];
+            let (query, filter) = (&query, &filter);
+            async move {
+                for node in self.shard_nodes(cluster_shard).await {
+                    let Ok(client) = self.client(&node).await else {
+                        continue;
+                    };
+                    let search = client.search(&name, query, k, filter.clone());
+                    if let Ok(Ok(hits)) = tokio::time::timeout(timeout, search).await {
+                        return (shard, Some(hits));
+                    }
+                }
+                (shard, None)
+            }
+        });
+
+        let mut hits = Vec::new();
+        let mut failed_shards = Vec::new();
+        for (shard, shard_hits) in futures::future::join_all(searches).await {
+            match shard_hits {
+                Some(shard_hits) => hits.extend(shard_hits),
+                None => failed_shards.push(shard),
+            }
+        }
+
+        if !failed_shards.is_empty() && !self.config.allow_partial_results {
+            return Err(ShardingError::PartialResults(failed_shards));
+        }
+
+        hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(std::cmp::Ordering::Equal));
+        hits.truncate(k);
+
+        Ok(ShardedSearchResult {
+            hits,
+            shards_queried: sharded.cluster_shards.len(),
+            failed_shards,
+        })
+    }
+
+    /// Groups `operations` by the nodes that hold their shard, primary and
+    /// replicas alike, each renamed to its shard's collection. Shards that
+    /// are being moved are refused: their writes have to be forwarded to
+    /// the new owner, which a distributed transaction cannot do.
+    pub async fn route_operations(
+        &self,
+        operations: Vec<DistributedOperation>,
+    ) -> Result<HashMap<String, Vec<DistributedOperation>>, ShardingError> {
+        let migrations = self.migrations.read().await;
+        let mut by_node: HashMap<String, Vec<DistributedOperation>> = HashMap::new();
+
+        for op in operations {
+            let sharded = self.collection(op.collection()).await?;
+            let shard = sharded.strategy.shard_for(op.id());
+            let cluster_shard = sharded.cluster_shards[shard as usize];
+            if migrations.contains_key(&cluster_shard) {
+                return Err(ShardingError::MigrationFailed(format!(
+                    "shard {} of '{}' is being moved", shard, sharded.name
+                )));
+            }
+
+            let nodes = self.shard_nodes(cluster_shard).await;
+            if nodes.is_empty() {
+                return Err(ShardingError::NoNodes(format!("shard {} of '{}'", shard, sharded.name)));
+            }
+            let op = op.with_collection(sharded.shard_collection(shard));
+            for node in nodes {
+                by_node.entry(node).or_default().push(op.clone());
+            }
+        }
+        Ok(by_node)
+    }
+
+    /// Writes `collections` and the placement of their shards to the
+    /// registry file, if there is one.
+    async fn save_registry(&self, collections: &HashMap<String, ShardedCollection>) -> Result<(), ShardingError> {
+        let Some(path) = &self.registry else {
+            return Ok(());
+        };
+
+        let mut shards = Vec::new();
+        for collection in collections.values() {
+            for cluster_shard in &collection.cluster_shards {
+                if let Some(shard) = self.cluster.get_shard(*cluster_shard).await {
+                    shards.push(shard);
+                }
+            }
+        }
+        write_registry(path, &Registry {
+            collections: collections.values().cloned().collect(),
+            shards,
+        })
+    }
+
+    async fn collection(&self, name: &str) -> Result<ShardedCollection, ShardingError> {
+        self.get_collection(name).await
+            .ok_or(ShardingError::CollectionNotFound(name.to_string()))
+    }
+
+    /// Runs a write on every copy of a shard at once and returns how many
+    /// vectors each wrote, primary first. A shard placed on no node fails
+    /// with `NoNodes`. If only some copies take the write, it is undone on
+    /// those from what the primary held before, and the write fails with
+    /// `NodeError`; `PartialWrite` means the undo failed too, and names the
+    /// copies that missed the write.
+    async fn write_shard(&self, sharded: &ShardedCollection, shard: u32, write: &ShardWrite) -> Result<Vec<usize>, ShardingError> {
+        let collection = &sharded.name;
+        let nodes = self.shard_nodes(sharded.cluster_shards[shard as usize]).await;
+        if nodes.is_empty() {
+            return Err(ShardingError::NoNodes(format!("shard {} of '{}'", shard, collection)));
+        }
+        let name = sharded.shard_collection(shard);
+        let before = match nodes.len() {
+            1 => Vec::new(),
+            _ => write.before(self.client(&nodes[0]).await?.as_ref(), &name).await?,
+        };
+
+        let results = futures::future::join_all(nodes.iter().map(|node| {
+            let name = &name;
+            async move {
+                let client = self.client(node).await?;
+                write.run(client.as_ref(), name).await.map(|count| (client, count))
+            }
+        })).await;
+
+        let mut written = Vec::new();
+        let mut failed = Vec::new();
+        for (node, result) in nodes.iter().zip(results) {
+            match result {
+                Ok(copy) => written.push(copy),
+                Err(e) => failed.push(format!("{}: {}", node, e)),
+            }
+        }
+        if failed.is_empty() {
+            return Ok(written.into_iter().map(|(_, count)| count).collect());
+        }
+
+        let mut undone = true;
+        for (client, _) in &written {
+            undone &= ShardWrite::restore(client.as_ref(), &name, &before).await.is_ok();
+        }
+        if undone {
+            Err(ShardingError::NodeError(failed.join("; ")))
+        } else {
+            Err(ShardingError::PartialWrite(format!("shard {} of '{}' missed on {}", shard, collection, failed.join("; "))))
+        }
+    }
+
+    /// Primary first, then replicas, as currently placed by the cluster.
+    async fn shard_nodes(&self, cluster_shard: u32) -> Vec<String> {
+        match self.cluster.get_shard_nodes(cluster_shard).await {
+            Some((primary, replicas)) => std::iter::once(primary).chain(replicas).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    async fn client(&self, node_id: &str) -> Result<Arc<dyn ShardClient>, ShardingError> {
+        self.clients.read().await.get(node_id).cloned()
+            .ok_or_else(|| ShardingError::NoNodes(format!("node '{}' is not registered", node_id)))
+    }
+}
+
+/// A write to every copy of a shard.
+enum ShardWrite {
+    Insert(Vec<VectorRecord>),
+    Delete(Vec<String>),
+}
+
+impl ShardWrite {
+    /// Returns how many vectors were written.
+    async fn run(&self, client: &dyn ShardClient, collection: &str) -> Result<usize, ShardingError> {
+        match self {
+            ShardWrite::Insert(vectors) => client.insert(collection, vectors.clone()).await.map(|_| vectors.len()),
+            ShardWrite::Delete(ids) => client.delete(collection, ids).await,
+        }
+    }
+
+    /// What each id the write touches holds on one copy.
+    async fn before(&self, client: &dyn ShardClient, collection: &str) -> Result<Vec<(String, Option<(Vec<f32>, serde_json::Value)>)>, ShardingError> {
+        let ids: Vec<&String> = match self {
+            ShardWrite::Insert(vectors) => vectors.iter().map(|(id, _, _)| id).collect(),
+            ShardWrite::Delete(ids) => ids.iter().collect(),
+        };
+        let values = futures::future::join_all(ids.iter().map(|id| client.get(collection, id))).await;
+        ids.into_iter().zip(values).map(|(id, value)| Ok((id.clone(), value?))).collect()
+    }
+
+    /// Puts back what `before` read.
+    async fn restore(client: &dyn ShardClient, collection: &str, before: &[(String, Option<(Vec<f32>, serde_json::Value)>)]) -> Result<(), ShardingError> {
+        let mut vectors = Vec::new();
+        let mut absent = Vec::new();
+        for (id, value) in before {
+            match value {
+                Some((vector, metadata)) => vectors.push((id.clone(), vector.clone(), metadata.clone())),
+                None => absent.push(id.clone()),
+            }
+        }
+        if !absent.is_empty() {
+            client.delete(collection, &absent).await?;
+        }
+        if !vectors.is_empty() {
+            client.insert(collection, vectors).await?;
+        }
+        Ok(())
+    }
+}
+
+fn read_registry(path: &Path) -> Result<Option<Registry>, ShardingError> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| ShardingError::StorageError(format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ShardingError::StorageError(e.to_string())),
+    }
+}
+
+fn write_registry(path: &Path, registry: &Registry) -> Result<(), ShardingError> {
+    let data = serde_json::to_vec(registry).map_err(|e| ShardingError::StorageError(e.to_string()))?;
+    crate::coretex_journal::write_durably(path, &data).map_err(|e| ShardingError::StorageError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coretex_utils::cluster::{ClusterNode, NodeRole, NodeState};
+
+    /// Delays every search to simulate an overloaded node.
+    struct SlowShardClient {
+        inner: LocalShardClient,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl ShardClient for SlowShardClient {
+        async fn create_collection(&self, name: &str, dimension: usize, metric: &str) -> Result<(), ShardingError> {
+            self.inner.create_collection(name, dimension, metric).await
+        }
+
+        async fn delete_collection(&self, name: &str) -> Result<(), ShardingError> {
+            self.inner.delete_collection(name).await
+        }
+
+        async fn insert(&self, collection: &str, vectors: Vec<VectorRecord>) -> Result<(), ShardingError> {
+            self.inner.insert(collection, vectors).await
+        }
+
+        async fn get(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, ShardingError> {
+            self.inner.get(collection, id).await
+        }
+
+        async fn delete(&self, collection: &str, ids: &[String]) -> Result<usize, ShardingError> {
+            self.inner.delete(collection, ids).await
+        }
+
+        async fn search(&self, collection: &str, query: &[f32], k: usize, filter: Option<serde_json::Value>) -> Result<Vec<ShardHit>, ShardingError> {
+            tokio::time::sleep(self.delay).await;
+            self.inner.search(collection, query, k, filter).await
+        }
+
+        async fn count(&self, collection: &str) -> Result<usize, ShardingError> {
+            self.inner.count(collection).await
+        }
+
+        async fn scan(&self, collection: &str, after: Option<&str>, limit: usize) -> Result<Vec<VectorRecord>, ShardingError> {
+            self.inner.scan(collection, after, limit).await
+        }
+    }
+
+    pub(super) async fn cluster(nodes: usize, slow_node: Option<usize>) -> (ShardCoordinator, Vec<Arc<CoreTexDB>>) {
+        let cluster = Arc::new(ClusterManager::new("node0", 1));
+        let coordinator = ShardCoordinator::new(cluster.clone()).with_config(ShardingConfig {
+            shard_timeout_ms: 100,
+            allow_partial_results: true,
+        });
+
+        let mut dbs = Vec::new();
+        for i in 0..nodes {
+            let id = format!("node{}", i);
+            cluster.add_node(ClusterNode {
+                id: id.clone(),
+                address: "127.0.0.1".to_string(),
+                port: 7000 + i as u16,
+                role: NodeRole::Follower,
+                state: NodeState::Active,
+                last_heartbeat: 0,
+                shard_ids: Vec::new(),
+            }).await;
+
+            let db = Arc::new(CoreTexDB::new());
+            let client: Arc<dyn ShardClient> = if slow_node == Some(i) {
+                Arc::new(SlowShardClient { inner: LocalShardClient::new(db.clone()), delay: Duration::from_secs(5) })
+            } else {
+                Arc::new(LocalShardClient::new(db.clone()))
+            };
+            coordinator.register_node(&id, client).await;
+            dbs.push(db);
+        }
+        (coordinator, dbs)
+    }
+
+    /// Joins `node_id` to the coordinator's cluster, or changes its state if
+    /// it is already a member.
+    pub(super) async fn add_node(coordinator: &ShardCoordinator, node_id: &str, state: NodeState) -> Arc<CoreTexDB> {
+        coordinator.cluster.add_node(ClusterNode {
+            id: node_id.to_string(),
+            address: "127.0.0.1".to_string(),
+            port: 7100,
+            role: NodeRole::Follower,
+            state,
+            last_heartbeat: 0,
+            shard_ids: Vec::new(),
+        }).await;
+
+        let db = Arc::new(CoreTexDB::new());
+        if coordinator.clients.read().await.contains_key(node_id) {
+            return db;
+        }
+        coordinator.register_node(node_id, Arc::new(LocalShardClient::new(db.clone()))).await;
+        db
+    }
+
+    pub(super) fn vectors(count: usize) -> Vec<VectorRecord> {
+        (0..count)
+            .map(|i| {
+                let angle = i as f32 * 0.05;
+                (format!("v{:03}", i), vec![angle.cos(), angle.sin(), 0.0], serde_json::json!({ "n": i }))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sharding_strategy_routing() {
+        let hash = ShardingStrategy::Hash { shards: 4 };
+        assert_eq!(hash.shard_for("doc-1"), hash.shard_for("doc-1"));
+        assert!((0..100).all(|i| hash.shard_for(&format!("doc-{}", i)) < 4));
+
+        let range = ShardingStrategy::Range { split_points: vec!["g".to_string(), "p".to_string()] };
+        assert_eq!(range.shard_count(), 3);
+        assert_eq!(range.shard_for("apple"), 0);
+        assert_eq!(range.shard_for("g"), 1);
+        assert_eq!(range.shard_for("kiwi"), 1);
+        assert_eq!(range.shard_for("zebra"), 2);
+
+        let unsorted = ShardingStrategy::Range { split_points: vec!["p".to_string(), "g".to_string()] };
+        assert!(matches!(unsorted.validate(), Err(ShardingError::InvalidStrategy(_))));
+    }
+
+    #[tokio::test]
+    async fn test_sharded_insert_and_scatter_gather_search() {
+        let (coordinator, dbs) = cluster(3, None).await;
+        let collection = coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 6 }).await.unwrap();
+        coordinator.insert("docs", vectors(40)).await.unwrap();
+
+        // Every vector lives on exactly one shard
+        let mut stored = 0;
+        for shard in 0..6 {
+            let db = &dbs[shard % 3];
+            stored += db.get_vectors_count(&collection.shard_collection(shard as u32)).await.unwrap();
+        }
+        assert_eq!(stored, 40);
+
+        let (vector, metadata) = coordinator.get("docs", "v007").await.unwrap().unwrap();
+        assert_eq!(metadata["n"], 7);
+        assert_eq!(vector.len(), 3);
+
+        let result = coordinator.search("docs", vec![1.0, 0.0, 0.0], 5, None).await.unwrap();
+        assert!(!result.is_partial());
+        assert_eq!(result.shards_queried, 6);
+        let ids: Vec<&str> = result.hits.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["v000", "v001", "v002", "v003", "v004"]);
+        assert!(result.hits.windows(2).all(|w| w[0].distance <= w[1].distance));
+        assert_eq!(result.hits[1].metadata.as_ref().unwrap()["n"], 1);
+
+        assert_eq!(coordinator.delete("docs", &["v000".to_string(), "missing".to_string()]).await.unwrap(), 1);
+        let result = coordinator.search("docs", vec![1.0, 0.0, 0.0], 1, None).await.unwrap();
+        assert_eq!(result.hits[0].id, "v001");
+    }
+
+    #[tokio::test]
+    async fn test_sharded_search_returns_partial_results_on_timeout() {
+        let (coordinator, _dbs) = cluster(2, Some(1)).await;
+        coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Range { split_points: vec!["v020".to_string()] }).await.unwrap();
+        coordinator.insert("docs", vectors(40)).await.unwrap();
+
+        let result = coordinator.search("docs", vec![0.0, 1.0, 0.0], 3, None).await.unwrap();
+        assert_eq!(result.failed_shards, vec![1]);
+        assert!(result.hits.iter().all(|h| h.id.as_str() < "v020"));
+
+        let strict = coordinator.with_config(ShardingConfig { shard_timeout_ms: 100, allow_partial_results: false });
+        assert!(matches!(
+            strict.search("docs", vec![0.0, 1.0, 0.0], 3, None).await,
+            Err(ShardingError::PartialResults(shards)) if shards == vec![1]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_writes_to_unplaced_or_partly_failed_shards_are_errors() {
+        let cluster = Arc::new(ClusterManager::new("node0", 2));
+        let coordinator = ShardCoordinator::new(cluster);
+        let dbs = [add_node(&coordinator, "node0", NodeState::Active).await, add_node(&coordinator, "node1", NodeState::Active).await];
+        let collection = coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 1 }).await.unwrap();
+
+        // One copy of the shard is lost
+        let (_, replicas) = coordinator.cluster.get_shard_nodes(collection.cluster_shards[0]).await.unwrap();
+        let replica = if replicas[0] == "node0" { &dbs[0] } else { &dbs[1] };
+        replica.delete_collection(&collection.shard_collection(0)).await.unwrap();
+        let primary = if replicas[0] == "node0" { &dbs[1] } else { &dbs[0] };
+        primary.insert_vectors(&collection.shard_collection(0), vectors(1)).await.unwrap();
+
+        // The copy that took the write has it undone
+        let mut changed = vectors(3);
+        changed[0].2 = serde_json::json!({ "n": 100 });
+        assert!(matches!(coordinator.insert("docs", changed).await, Err(ShardingError::NodeError(_))));
+        assert!(matches!(coordinator.delete("docs", &["v000".to_string()]).await, Err(ShardingError::NodeError(_))));
+        assert_eq!(primary.get_vectors_count(&collection.shard_collection(0)).await.unwrap(), 1);
+        assert_eq!(primary.get_vector(&collection.shard_collection(0), "v000", None).await.unwrap().unwrap().1["n"], 0);
+
+        // A coordinator that knows the collection but not where its shard is
+        let unplaced = ShardCoordinator::new(Arc::new(ClusterManager::new("node0", 1)));
+        unplaced.collections.write().await.insert("docs".to_string(), collection);
+        assert!(matches!(unplaced.insert("docs", vectors(3)).await, Err(ShardingError::NoNodes(_))));
+        assert!(matches!(unplaced.delete("docs", &["v000".to_string()]).await, Err(ShardingError::NoNodes(_))));
+    }
+
+    #[tokio::test]
+    async fn test_failed_create_removes_the_shards_it_made() {
+        let (coordinator, dbs) = cluster(3, None).await;
+        dbs[2].create_collection("docs_shard_2", 3, "cosine").await.unwrap();
+
+        assert!(coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 3 }).await.is_err());
+        assert!(coordinator.get_collection("docs").await.is_none());
+        assert!(dbs[0].list_collections().await.unwrap().is_empty());
+        assert!(dbs[1].list_collections().await.unwrap().is_empty());
+        assert_eq!(coordinator.cluster.next_shard_id().await, 0);
+
+        dbs[2].delete_collection("docs_shard_2").await.unwrap();
+        coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 3 }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_registry_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = dir.path().join("shards.json");
+        let (coordinator, dbs) = cluster(3, None).await;
+        let coordinator = coordinator.with_registry(&registry);
+        coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 4 }).await.unwrap();
+        coordinator.create_collection("gone", 3, "cosine", ShardingStrategy::Hash { shards: 2 }).await.unwrap();
+        coordinator.delete_collection("gone").await.unwrap();
+        coordinator.insert("docs", vectors(20)).await.unwrap();
+
+        // A new process knows the nodes but not the shards
+        let restarted = ShardCoordinator::new(Arc::new(ClusterManager::new("node0", 1))).with_registry(&registry);
+        for (i, db) in dbs.iter().enumerate() {
+            let id = format!("node{}", i);
+            add_node(&restarted, &id, NodeState::Active).await;
+            restarted.register_node(&id, Arc::new(LocalShardClient::new(db.clone()))).await;
+        }
+        assert_eq!(restarted.load_registry().await.unwrap(), 1);
+
+        assert_eq!(restarted.list_collections().await, vec!["docs".to_string()]);
+        let (_, metadata) = restarted.get("docs", "v007").await.unwrap().unwrap();
+        assert_eq!(metadata["n"], 7);
+        let result = restarted.search("docs", vec![1.0, 0.0, 0.0], 20, None).await.unwrap();
+        assert_eq!(result.hits.len(), 20);
+        assert_eq!(restarted.count("docs").await.unwrap(), 20);
+    }
+}
@@ -0,0 +1,502 @@
+//! Online shard rebalancing
+//! Moving a copy of a shard from one node to another takes four steps:
+//!
+//! 1. the new owner gets an empty collection, and every write to the shard is
+//!    forwarded to it as well (dual-write);
+//! 2. the existing vectors are copied over in throttled batches, in id order;
+//!    the new owner indexes them as they arrive;
+//! 3. routing switches to the new owner in one `ClusterManager` update, taken
+//!    while no write is in flight;
+//! 4. after a grace period for in-flight reads, the old copy is dropped.
+//!
+//! Ids that reach the new owner through the dual-write path are skipped by
+//! the copy, so a batch read before a write can never overwrite it or bring
+//! back a deleted vector.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::{ShardClient, ShardCoordinator, ShardingError, VectorRecord};
+
+#[derive(Debug, Clone)]
+pub struct RebalanceConfig {
+    /// Vectors read from the old owner per batch.
+    pub batch_size: usize,
+    /// Caps copy throughput per shard move; `None` copies as fast as the
+    /// nodes allow.
+    pub max_vectors_per_sec: Option<u64>,
+    /// How long the old copy is kept after routing switched away from it.
+    pub gc_delay_ms: u64,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            max_vectors_per_sec: Some(10_000),
+            gc_delay_ms: 5_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardMove {
+    pub collection: String,
+    pub shard: u32,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MigrationState {
+    Pending,
+    Copying,
+    Switching,
+    CleaningUp,
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationProgress {
+    pub shard_move: ShardMove,
+    pub state: MigrationState,
+    pub copied: usize,
+    /// Vectors on the old owner when copying started.
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebalanceProgress {
+    pub migrations: Vec<MigrationProgress>,
+}
+
+impl RebalanceProgress {
+    pub fn is_running(&self) -> bool {
+        self.migrations.iter().any(|m| !matches!(m.state, MigrationState::Done | MigrationState::Failed(_)))
+    }
+
+    pub fn completed(&self) -> usize {
+        self.migrations.iter().filter(|m| m.state == MigrationState::Done).count()
+    }
+
+    pub fn vectors_copied(&self) -> usize {
+        self.migrations.iter().map(|m| m.copied).sum()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RebalanceReport {
+    pub moves: usize,
+    pub vectors_copied: usize,
+}
+
+pub(super) struct Migration {
+    pub(super) shard_move: ShardMove,
+    /// Ids the dual-write path has already written to or deleted from the
+    /// new owner. Held while a batch is copied, so copies and forwarded
+    /// writes never interleave.
+    touched: Mutex<HashSet<String>>,
+    progress: std::sync::Mutex<MigrationProgress>,
+}
+
+impl Migration {
+    fn new(shard_move: ShardMove) -> Self {
+        Self {
+            progress: std::sync::Mutex::new(MigrationProgress {
+                shard_move: shard_move.clone(),
+                state: MigrationState::Pending,
+                copied: 0,
+                total: 0,
+            }),
+            shard_move,
+            touched: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub(super) async fn forward_insert(&self, target: &dyn ShardClient, collection: &str, vectors: Vec<VectorRecord>) -> Result<(), ShardingError> {
+        let mut touched = self.touched.lock().await;
+        let ids: Vec<String> = vectors.iter().map(|(id, _, _)| id.clone()).collect();
+        target.insert(collection, vectors).await?;
+        touched.extend(ids);
+        Ok(())
+    }
+
+    pub(super) async fn forward_delete(&self, target: &dyn ShardClient, collection: &str, ids: &[String]) -> Result<(), ShardingError> {
+        let mut touched = self.touched.lock().await;
+        target.delete(collection, ids).await?;
+        touched.extend(ids.iter().cloned());
+        Ok(())
+    }
+
+    async fn copy_batch(&self, target: &dyn ShardClient, collection: &str, batch: Vec<VectorRecord>) -> Result<(), ShardingError> {
+        let touched = self.touched.lock().await;
+        let batch: Vec<VectorRecord> = batch.into_iter().filter(|(id, _, _)| !touched.contains(id)).collect();
+        if !batch.is_empty() {
+            target.insert(collection, batch).await?;
+        }
+        Ok(())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut MigrationProgress)) {
+        f(&mut self.progress.lock().unwrap());
+    }
+
+    fn progress(&self) -> MigrationProgress {
+        self.progress.lock().unwrap().clone()
+    }
+}
+
+impl ShardCoordinator {
+    /// Moves that drain shards off nodes that are no longer active and then
+    /// even out shard copies per node to within one.
+    pub async fn plan_rebalance(&self) -> Vec<ShardMove> {
+        let mut active: Vec<String> = {
+            let clients = self.clients.read().await;
+            self.cluster.get_active_nodes().await
+                .into_iter()
+                .map(|node| node.id)
+                .filter(|id| clients.contains_key(id))
+                .collect()
+        };
+        if active.is_empty() {
+            return Vec::new();
+        }
+        active.sort();
+
+        let mut load: HashMap<String, usize> = active.iter().map(|id| (id.clone(), 0)).collect();
+        let mut shards: Vec<(String, u32, Vec<String>)> = Vec::new();
+        let mut collections: Vec<_> = self.collections.read().await.values().cloned().collect();
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for collection in collections {
+            for (shard, cluster_shard) in collection.cluster_shards.iter().enumerate() {
+                let holders = self.shard_nodes(*cluster_shard).await;
+                for holder in &holders {
+                    if let Some(count) = load.get_mut(holder) {
+                        *count += 1;
+                    }
+                }
+                shards.push((collection.name.clone(), shard as u32, holders));
+            }
+        }
+
+        let mut moves = Vec::new();
+        for (collection, shard, holders) in shards.iter_mut() {
+            for i in 0..holders.len() {
+                if load.contains_key(&holders[i]) {
+                    continue;
+                }
+                let Some(to) = least_loaded(&load, holders) else {
+                    continue;
+                };
+                *load.get_mut(&to).unwrap() += 1;
+                moves.push(ShardMove {
+                    collection: collection.clone(),
+                    shard: *shard,
+                    from: std::mem::replace(&mut holders[i], to.clone()),
+                    to,
+                });
+            }
+        }
+
+        loop {
+            let (busiest, most) = load.iter()
+                .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+                .map(|(id, count)| (id.clone(), *count))
+                .unwrap();
+            let Some(idlest) = least_loaded(&load, &[]) else {
+                break;
+            };
+            if most <= load[&idlest] + 1 {
+                break;
+            }
+
+            let Some((collection, shard, holders)) = shards.iter_mut()
+                .find(|(_, _, holders)| holders.contains(&busiest) && !holders.contains(&idlest))
+            else {
+                break;
+            };
+            let position = holders.iter().position(|h| *h == busiest).unwrap();
+            holders[position] = idlest.clone();
+            *load.get_mut(&busiest).unwrap() -= 1;
+            *load.get_mut(&idlest).unwrap() += 1;
+            moves.push(ShardMove {
+                collection: collection.clone(),
+                shard: *shard,
+                from: busiest,
+                to: idlest,
+            });
+        }
+
+        moves
+    }
+
+    /// Plans and carries out a rebalance, one shard move at a time. Reads
+    /// and writes keep working throughout; `rebalance_progress` reports how
+    /// far it got.
+    pub async fn rebalance(&self) -> Result<RebalanceReport, ShardingError> {
+        let moves = self.plan_rebalance().await;
+        self.run_moves(moves).await
+    }
+
+    /// Moves one copy of a shard, e.g. off a node about to be replaced.
+    pub async fn migrate_shard(&self, shard_move: ShardMove) -> Result<RebalanceReport, ShardingError> {
+        self.run_moves(vec![shard_move]).await
+    }
+
+    pub async fn rebalance_progress(&self) -> RebalanceProgress {
+        RebalanceProgress {
+            migrations: self.migration_history.read().await.iter().map(|m| m.progress()).collect(),
+        }
+    }
+
+    async fn run_moves(&self, moves: Vec<ShardMove>) -> Result<RebalanceReport, ShardingError> {
+        let migrations: Vec<Arc<Migration>> = moves.into_iter().map(|m| Arc::new(Migration::new(m))).collect();
+        *self.migration_history.write().await = migrations.clone();
+
+        let mut report = RebalanceReport::default();
+        for migration in migrations {
+            match self.run_migration(&migration).await {
+                Ok(copied) => {
+                    report.moves += 1;
+                    report.vectors_copied += copied;
+                }
+                Err(e) => {
+                    migration.update(|p| p.state = MigrationState::Failed(e.to_string()));
+                    return Err(e);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    async fn run_migration(&self, migration: &Arc<Migration>) -> Result<usize, ShardingError> {
+        let shard_move = &migration.shard_move;
+        let sharded = self.collection(&shard_move.collection).await?;
+        let cluster_shard = *sharded.cluster_shards.get(shard_move.shard as usize)
+            .ok_or_else(|| ShardingError::MigrationFailed(format!("'{}' has no shard {}", shard_move.collection, shard_move.shard)))?;
+        let name = sharded.shard_collection(shard_move.shard);
+
+        let holders = self.shard_nodes(cluster_shard).await;
+        if !holders.contains(&shard_move.from) {
+            return Err(ShardingError::MigrationFailed(format!("{} does not hold {}", shard_move.from, name)));
+        }
+        if holders.contains(&shard_move.to) {
+            return Err(ShardingError::MigrationFailed(format!("{} already holds {}", shard_move.to, name)));
+        }
+
+        let target = self.client(&shard_move.to).await?;
+        // Leftovers of an earlier move that failed
+        let _ = target.delete_collection(&name).await;
+        target.create_collection(&name, sharded.dimension, &sharded.metric).await?;
+
+        self.migrations.write().await.insert(cluster_shard, migration.clone());
+        migration.update(|p| p.state = MigrationState::Copying);
+
+        let copied = match self.copy_shard(migration, &holders, &name, target.as_ref()).await {
+            Ok(copied) => copied,
+            Err(e) => {
+                self.migrations.write().await.remove(&cluster_shard);
+                let _ = target.delete_collection(&name).await;
+                return Err(e);
+            }
+        };
+
+        migration.update(|p| p.state = MigrationState::Switching);
+        {
+            let mut migrations = self.migrations.write().await;
+            self.cluster.replace_shard_node(cluster_shard, &shard_move.from, &shard_move.to).await;
+            migrations.remove(&cluster_shard);
+        }
+        // Until the new placement is saved the old copy is kept
+        self.save_registry(&*self.collections.read().await).await?;
+
+        migration.update(|p| p.state = MigrationState::CleaningUp);
+        tokio::time::sleep(Duration::from_millis(self.rebalance_config.gc_delay_ms)).await;
+        // A node that left the cluster may already be gone
+        if let Ok(source) = self.client(&shard_move.from).await {
+            let _ = source.delete_collection(&name).await;
+        }
+
+        migration.update(|p| p.state = MigrationState::Done);
+        Ok(copied)
+    }
+
+    /// Copies from the old owner, or from another holder of the shard if it
+    /// no longer answers.
+    async fn copy_shard(
+        &self,
+        migration: &Migration,
+        holders: &[String],
+        name: &str,
+        target: &dyn ShardClient,
+    ) -> Result<usize, ShardingError> {
+        let mut sources = vec![migration.shard_move.from.clone()];
+        sources.extend(holders.iter().filter(|h| **h != migration.shard_move.from).cloned());
+
+        let mut source = None;
+        for node in sources {
+            let Ok(client) = self.client(&node).await else {
+                continue;
+            };
+            if let Ok(total) = client.count(name).await {
+                migration.update(|p| p.total = total);
+                source = Some(client);
+                break;
+            }
+        }
+        let source = source.ok_or_else(|| ShardingError::MigrationFailed(format!("no node can serve {}", name)))?;
+
+        let started = Instant::now();
+        let batch_size = self.rebalance_config.batch_size.max(1);
+        let mut after: Option<String> = None;
+        let mut copied = 0;
+
+        loop {
+            let batch = source.scan(name, after.as_deref(), batch_size).await?;
+            let Some((last, _, _)) = batch.last() else {
+                break;
+            };
+            after = Some(last.clone());
+            let len = batch.len();
+
+            migration.copy_batch(target, name, batch).await?;
+            copied += len;
+            migration.update(|p| p.copied = copied);
+
+            if let Some(rate) = self.rebalance_config.max_vectors_per_sec {
+                let due = Duration::from_secs_f64(copied as f64 / rate.max(1) as f64);
+                if let Some(wait) = due.checked_sub(started.elapsed()) {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            if len < batch_size {
+                break;
+            }
+        }
+
+        Ok(copied)
+    }
+}
+
+/// Active node with the fewest shard copies, skipping `exclude`; ties go to
+/// the lowest id.
+fn least_loaded(load: &HashMap<String, usize>, exclude: &[String]) -> Option<String> {
+    load.iter()
+        .filter(|(id, _)| !exclude.contains(id))
+        .min_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)))
+        .map(|(id, _)| id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{add_node, cluster, vectors};
+    use super::super::ShardingStrategy;
+    use crate::coretex_utils::cluster::NodeState;
+
+    fn fast() -> RebalanceConfig {
+        RebalanceConfig {
+            batch_size: 8,
+            max_vectors_per_sec: None,
+            gc_delay_ms: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_moves_shard_to_new_node() {
+        let (coordinator, mut dbs) = cluster(2, None).await;
+        let coordinator = coordinator.with_rebalance_config(fast());
+        coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 4 }).await.unwrap();
+        coordinator.insert("docs", vectors(40)).await.unwrap();
+
+        dbs.push(add_node(&coordinator, "node2", NodeState::Active).await);
+        let moves = coordinator.plan_rebalance().await;
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].to, "node2");
+
+        let report = coordinator.rebalance().await.unwrap();
+        assert_eq!(report.moves, 1);
+
+        let progress = coordinator.rebalance_progress().await;
+        assert!(!progress.is_running());
+        assert_eq!(progress.completed(), 1);
+        assert_eq!(progress.migrations[0].copied, progress.migrations[0].total);
+
+        // The new owner serves the shard and the old copy is gone
+        let name = format!("docs_shard_{}", moves[0].shard);
+        assert_eq!(dbs[2].get_vectors_count(&name).await.unwrap(), report.vectors_copied);
+        let old = if moves[0].from == "node0" { &dbs[0] } else { &dbs[1] };
+        assert!(old.get_vectors_count(&name).await.is_err());
+
+        let result = coordinator.search("docs", vec![1.0, 0.0, 0.0], 40, None).await.unwrap();
+        assert_eq!(result.hits.len(), 40);
+        assert!(coordinator.plan_rebalance().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_dual_writes_during_copy() {
+        let (coordinator, _dbs) = cluster(1, None).await;
+        let coordinator = Arc::new(coordinator.with_rebalance_config(RebalanceConfig {
+            batch_size: 4,
+            max_vectors_per_sec: Some(100),
+            gc_delay_ms: 0,
+        }));
+        coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 1 }).await.unwrap();
+        coordinator.insert("docs", vectors(40)).await.unwrap();
+        let new_db = add_node(&coordinator, "node1", NodeState::Active).await;
+
+        let rebalance = {
+            let coordinator = coordinator.clone();
+            tokio::spawn(async move {
+                coordinator.migrate_shard(ShardMove {
+                    collection: "docs".to_string(),
+                    shard: 0,
+                    from: "node0".to_string(),
+                    to: "node1".to_string(),
+                }).await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(coordinator.rebalance_progress().await.is_running());
+        // "v039" has not been copied yet; the new version must survive the copy
+        coordinator.insert("docs", vec![
+            ("v039".to_string(), vec![0.0, 0.0, 1.0], serde_json::json!({ "n": "updated" })),
+            ("new".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({})),
+        ]).await.unwrap();
+        coordinator.delete("docs", &["v038".to_string()]).await.unwrap();
+
+        rebalance.await.unwrap().unwrap();
+
+        assert_eq!(new_db.get_vectors_count("docs_shard_0").await.unwrap(), 40);
+        assert_eq!(coordinator.get("docs", "v039").await.unwrap().unwrap().1["n"], "updated");
+        assert!(coordinator.get("docs", "new").await.unwrap().is_some());
+        assert!(coordinator.get("docs", "v038").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_drains_leaving_node() {
+        let (coordinator, _dbs) = cluster(3, None).await;
+        let coordinator = coordinator.with_rebalance_config(fast());
+        coordinator.create_collection("docs", 3, "cosine", ShardingStrategy::Hash { shards: 6 }).await.unwrap();
+        coordinator.insert("docs", vectors(30)).await.unwrap();
+
+        add_node(&coordinator, "node1", NodeState::Leaving).await;
+        let moves = coordinator.plan_rebalance().await;
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|m| m.from == "node1" && m.to != "node1"));
+
+        coordinator.rebalance().await.unwrap();
+        let result = coordinator.search("docs", vec![1.0, 0.0, 0.0], 30, None).await.unwrap();
+        assert_eq!(result.hits.len(), 30);
+        for shard in 0..6 {
+            let (primary, _) = coordinator.cluster.get_shard_nodes(shard).await.unwrap();
+            assert_ne!(primary, "node1");
+        }
+    }
+}
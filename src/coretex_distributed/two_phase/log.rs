@@ -0,0 +1,155 @@
+//! Durable two-phase commit log
+//! `two_phase.log` holds one JSON record per line. A node writes coordinator
+//! records for transactions it runs and participant records for ones it
+//! takes part in; finished transactions are dropped when the log is opened.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use super::TwoPhaseError;
+use crate::coretex_distributed::DistributedOperation;
+use crate::coretex_journal::{append_json_lines, read_json_lines, write_json_lines};
+
+const LOG_FILE: &str = "two_phase.log";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum LogRecord {
+    /// Coordinator: the commit decision. Presumed abort means aborts are
+    /// never logged.
+    Commit { tx_id: String, participants: Vec<String> },
+    /// Coordinator: every participant acknowledged the commit.
+    End { tx_id: String },
+    /// Participant: voted yes and must hold on to `operations`.
+    Prepared { tx_id: String, coordinator: String, operations: Vec<DistributedOperation> },
+    Committed { tx_id: String },
+    Aborted { tx_id: String },
+}
+
+pub(crate) struct TwoPhaseLog {
+    path: PathBuf,
+}
+
+impl TwoPhaseLog {
+    /// Opens the log in `dir` and returns the records of transactions that
+    /// are not finished yet.
+    pub(crate) fn open(dir: &Path) -> Result<(Self, Vec<LogRecord>), TwoPhaseError> {
+        fs::create_dir_all(dir).map_err(storage_error)?;
+        let log = Self { path: dir.join(LOG_FILE) };
+
+        let (records, _) = read_json_lines::<LogRecord>(&log.path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::InvalidData => TwoPhaseError::Corrupted(format!("log {}", e)),
+            _ => storage_error(e),
+        })?;
+        let ended: HashSet<&str> = records.iter()
+            .filter_map(|record| match record {
+                LogRecord::End { tx_id } => Some(tx_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        let resolved: HashSet<&str> = records.iter()
+            .filter_map(|record| match record {
+                LogRecord::Committed { tx_id } | LogRecord::Aborted { tx_id } => Some(tx_id.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        let live: Vec<LogRecord> = records.iter()
+            .filter(|record| match record {
+                LogRecord::Commit { tx_id, .. } => !ended.contains(tx_id.as_str()),
+                LogRecord::Prepared { tx_id, .. } => !resolved.contains(tx_id.as_str()),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+
+        // Also drops a torn final line, so appends start on a line of their own
+        log.rewrite(&live)?;
+        Ok((log, live))
+    }
+
+    /// Returns once the record is on disk.
+    pub(crate) fn append(&self, record: &LogRecord) -> Result<(), TwoPhaseError> {
+        append_json_lines(&self.path, std::slice::from_ref(record)).map_err(storage_error)
+    }
+
+    fn rewrite(&self, records: &[LogRecord]) -> Result<(), TwoPhaseError> {
+        write_json_lines(&self.path, records).map_err(storage_error)
+    }
+}
+
+fn storage_error(e: std::io::Error) -> TwoPhaseError {
+    TwoPhaseError::StorageError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn tx(id: &str) -> String {
+        id.to_string()
+    }
+
+    #[test]
+    fn test_two_phase_log_keeps_unfinished_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (log, live) = TwoPhaseLog::open(dir.path()).unwrap();
+            assert!(live.is_empty());
+
+            let participants = vec!["node1".to_string(), "node2".to_string()];
+            log.append(&LogRecord::Commit { tx_id: tx("t1"), participants: participants.clone() }).unwrap();
+            log.append(&LogRecord::Commit { tx_id: tx("t2"), participants }).unwrap();
+            log.append(&LogRecord::End { tx_id: tx("t1") }).unwrap();
+            for id in ["t3", "t4", "t5"] {
+                log.append(&LogRecord::Prepared { tx_id: tx(id), coordinator: "node1".to_string(), operations: vec![] }).unwrap();
+            }
+            log.append(&LogRecord::Committed { tx_id: tx("t3") }).unwrap();
+            log.append(&LogRecord::Aborted { tx_id: tx("t4") }).unwrap();
+        }
+
+        // Simulate a crash halfway through appending
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        file.write_all(b"{\"Committed\":{\"tx_").unwrap();
+
+        let (_, live) = TwoPhaseLog::open(dir.path()).unwrap();
+        assert_eq!(live.len(), 2);
+        assert!(matches!(&live[0], LogRecord::Commit { tx_id, participants } if tx_id == "t2" && participants.len() == 2));
+        assert!(matches!(&live[1], LogRecord::Prepared { tx_id, .. } if tx_id == "t5"));
+
+        // Finished transactions were compacted away
+        let lines = fs::read_to_string(dir.path().join(LOG_FILE)).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_two_phase_log_appends_after_a_torn_line() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let (log, _) = TwoPhaseLog::open(dir.path()).unwrap();
+            log.append(&LogRecord::Commit { tx_id: tx("t1"), participants: vec!["node1".to_string()] }).unwrap();
+        }
+        // Nothing to compact, only the torn line to drop
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        file.write_all(b"{\"Commit\":{\"tx_").unwrap();
+
+        {
+            let (log, live) = TwoPhaseLog::open(dir.path()).unwrap();
+            assert_eq!(live.len(), 1);
+            log.append(&LogRecord::Commit { tx_id: tx("t2"), participants: vec!["node1".to_string()] }).unwrap();
+        }
+
+        let (_, live) = TwoPhaseLog::open(dir.path()).unwrap();
+        let ids: Vec<&str> = live.iter()
+            .filter_map(|record| match record {
+                LogRecord::Commit { tx_id, .. } => Some(tx_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec!["t1", "t2"]);
+    }
+}
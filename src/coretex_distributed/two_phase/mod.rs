@@ -0,0 +1,789 @@
+//! Two-phase commit across CoreTexDB nodes
+//! Every `TwoPhaseNode` can coordinate transactions and take part in them.
+//! The coordinator sends each participant its `DistributedOperation`s in a
+//! prepare; the participant locks the keys they touch, reserving them in its
+//! `CoreTexDB` so nothing else writes them or deletes their collection, then
+//! checks them and votes. Only a unanimous yes commits.
+//!
+//! Both sides log durably before they answer: a participant its operations
+//! before voting yes, the coordinator its decision before sending commits.
+//! Aborts are presumed and never logged, so a coordinator asked about a
+//! transaction it holds no commit record for answers abort. After a crash the
+//! coordinator re-sends unacknowledged commits and participants ask about
+//! transactions they prepared but never heard the outcome of; both happen in
+//! `tick()` (or `start()`, which ticks on a timer). `sim` provides an
+//! in-process network with fault injection for tests.
+
+mod log;
+pub mod sim;
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use super::sharding::Envelope;
+use super::{DistributedOperation, VectorPayload};
+use crate::CoreTexDB;
+use log::{LogRecord, TwoPhaseLog};
+
+#[derive(Debug, Clone)]
+pub enum TwoPhaseError {
+    Unreachable(String),
+    Timeout(String),
+    /// Carries why the transaction was aborted.
+    Aborted(String),
+    InvalidOperation(String),
+    DatabaseError(String),
+    StorageError(String),
+    Corrupted(String),
+}
+
+impl std::fmt::Display for TwoPhaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TwoPhaseError::Unreachable(node) => write!(f, "Node unreachable: {}", node),
+            TwoPhaseError::Timeout(node) => write!(f, "Timed out waiting for {}", node),
+            TwoPhaseError::Aborted(reason) => write!(f, "Transaction aborted: {}", reason),
+            TwoPhaseError::InvalidOperation(msg) => write!(f, "Invalid operation: {}", msg),
+            TwoPhaseError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
+            TwoPhaseError::StorageError(msg) => write!(f, "Storage error: {}", msg),
+            TwoPhaseError::Corrupted(msg) => write!(f, "Corrupted two-phase commit log: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TwoPhaseError {}
+
+fn database_error(e: impl std::fmt::Display) -> TwoPhaseError {
+    TwoPhaseError::DatabaseError(e.to_string())
+}
+
+fn reserved_keys(operations: &[DistributedOperation]) -> Vec<(String, String)> {
+    operations.iter()
+        .map(|operation| (operation.collection().to_string(), operation.id().to_string()))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct TwoPhaseConfig {
+    /// How long the coordinator waits for votes before aborting.
+    pub prepare_timeout_ms: u64,
+    /// How long to wait for a commit, abort or decision reply. Commits that
+    /// go unacknowledged are re-sent on the next tick.
+    pub commit_timeout_ms: u64,
+    /// How long a prepared participant waits for the outcome before asking
+    /// the coordinator for it.
+    pub in_doubt_timeout_ms: u64,
+    pub tick_interval_ms: u64,
+}
+
+impl Default for TwoPhaseConfig {
+    fn default() -> Self {
+        Self {
+            prepare_timeout_ms: 5_000,
+            commit_timeout_ms: 5_000,
+            in_doubt_timeout_ms: 10_000,
+            tick_interval_ms: 1_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Decision {
+    Commit,
+    Abort,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TwoPhaseRequest {
+    Prepare {
+        tx_id: String,
+        coordinator: String,
+        operations: Vec<DistributedOperation>,
+    },
+    Commit { tx_id: String },
+    Abort { tx_id: String },
+    /// Asked of the coordinator by a participant in doubt.
+    Decision { tx_id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TwoPhaseResponse {
+    /// `reason` says why a participant voted no.
+    Vote { commit: bool, reason: Option<String> },
+    Ack,
+    Decision(Decision),
+}
+
+#[async_trait]
+pub trait TwoPhaseTransport: Send + Sync {
+    async fn send(&self, from: &str, to: &str, request: TwoPhaseRequest) -> Result<TwoPhaseResponse, TwoPhaseError>;
+}
+
+/// Reaches nodes through the REST API's `/api/2pc` route.
+pub struct HttpTwoPhaseTransport {
+    client: reqwest::Client,
+    /// Base URLs by node id.
+    peers: std::sync::RwLock<HashMap<String, String>>,
+}
+
+impl HttpTwoPhaseTransport {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            peers: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn add_peer(&self, node_id: &str, base_url: &str) {
+        self.peers.write().unwrap().insert(node_id.to_string(), base_url.trim_end_matches('/').to_string());
+    }
+}
+
+impl Default for HttpTwoPhaseTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl TwoPhaseTransport for HttpTwoPhaseTransport {
+    async fn send(&self, _from: &str, to: &str, request: TwoPhaseRequest) -> Result<TwoPhaseResponse, TwoPhaseError> {
+        let unreachable = |e: &dyn std::fmt::Display| TwoPhaseError::Unreachable(format!("{}: {}", to, e));
+        let base_url = self.peers.read().unwrap().get(to).cloned()
+            .ok_or_else(|| unreachable(&"no address known"))?;
+        let body = serde_json::to_string(&request).map_err(|e| TwoPhaseError::InvalidOperation(e.to_string()))?;
+
+        let response = self.client.post(format!("{}/api/2pc", base_url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| unreachable(&e))?;
+        let bytes = response.bytes().await.map_err(|e| unreachable(&e))?;
+        let envelope: Envelope<TwoPhaseResponse> = serde_json::from_slice(&bytes).map_err(|e| unreachable(&e))?;
+        match (envelope.data, envelope.error) {
+            (Some(data), _) => Ok(data),
+            (None, error) => Err(unreachable(&error.unwrap_or_else(|| "empty response".to_string()))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoPhaseStatus {
+    pub node_id: String,
+    /// Transactions this node committed that a participant has not
+    /// acknowledged yet.
+    pub pending_commits: Vec<String>,
+    /// Transactions this node prepared without knowing the outcome.
+    pub in_doubt: Vec<String>,
+}
+
+struct PreparedTransaction {
+    coordinator: String,
+    operations: Vec<DistributedOperation>,
+    /// `None` after recovery, so the outcome is asked for on the next tick.
+    prepared_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct TwoPhaseState {
+    /// Coordinator: transactions collecting votes, `true` once a participant
+    /// was told the transaction aborted.
+    voting: HashMap<String, bool>,
+    /// Coordinator: committed transactions by the participants yet to
+    /// acknowledge.
+    committing: HashMap<String, HashSet<String>>,
+    /// Participant: transactions voted yes on and not finished yet.
+    prepared: HashMap<String, PreparedTransaction>,
+    /// `collection:id` keys held by prepared transactions.
+    locks: HashMap<String, String>,
+    log: Option<TwoPhaseLog>,
+}
+
+impl TwoPhaseState {
+    fn append(&self, record: LogRecord) -> Result<(), TwoPhaseError> {
+        match &self.log {
+            Some(log) => log.append(&record),
+            None => Ok(()),
+        }
+    }
+
+    fn release_locks(&mut self, tx_id: &str) {
+        self.locks.retain(|_, holder| holder != tx_id);
+    }
+}
+
+pub struct TwoPhaseNode {
+    id: String,
+    config: TwoPhaseConfig,
+    db: Arc<RwLock<CoreTexDB>>,
+    transport: Arc<dyn TwoPhaseTransport>,
+    state: Mutex<TwoPhaseState>,
+    next_tx: AtomicU64,
+}
+
+impl TwoPhaseNode {
+    /// A node that keeps its protocol state in memory only.
+    pub fn new(id: &str, db: Arc<RwLock<CoreTexDB>>, transport: Arc<dyn TwoPhaseTransport>) -> Self {
+        Self::build(id, db, transport, TwoPhaseState::default())
+    }
+
+    /// A node logging to `dir`. Pending commits and prepared transactions
+    /// are recovered and resolved on the next tick.
+    pub async fn open(
+        dir: &Path,
+        id: &str,
+        db: Arc<RwLock<CoreTexDB>>,
+        transport: Arc<dyn TwoPhaseTransport>,
+    ) -> Result<Self, TwoPhaseError> {
+        let (log, records) = TwoPhaseLog::open(dir)?;
+        let mut state = TwoPhaseState { log: Some(log), ..Default::default() };
+        let database = db.read().await;
+
+        for record in records {
+            match record {
+                LogRecord::Commit { tx_id, participants } => {
+                    state.committing.insert(tx_id, participants.into_iter().collect());
+                }
+                LogRecord::Prepared { tx_id, coordinator, operations } => {
+                    for operation in &operations {
+                        state.locks.insert(operation.key(), tx_id.clone());
+                    }
+                    database.reserve(&tx_id, &reserved_keys(&operations)).map_err(database_error)?;
+                    state.prepared.insert(tx_id, PreparedTransaction { coordinator, operations, prepared_at: None });
+                }
+                _ => {}
+            }
+        }
+        drop(database);
+
+        Ok(Self::build(id, db, transport, state))
+    }
+
+    fn build(id: &str, db: Arc<RwLock<CoreTexDB>>, transport: Arc<dyn TwoPhaseTransport>, state: TwoPhaseState) -> Self {
+        Self {
+            id: id.to_string(),
+            config: TwoPhaseConfig::default(),
+            db,
+            transport,
+            state: Mutex::new(state),
+            next_tx: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_config(mut self, config: TwoPhaseConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub async fn status(&self) -> TwoPhaseStatus {
+        let state = self.state.lock().await;
+        let mut pending_commits: Vec<String> = state.committing.keys().cloned().collect();
+        let mut in_doubt: Vec<String> = state.prepared.keys().cloned().collect();
+        pending_commits.sort();
+        in_doubt.sort();
+
+        TwoPhaseStatus {
+            node_id: self.id.clone(),
+            pending_commits,
+            in_doubt,
+        }
+    }
+
+    /// Ticks every `tick_interval_ms` in the background.
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        let node = self.clone();
+
+        tokio::spawn(async move {
+            let interval = Duration::from_millis(node.config.tick_interval_ms.max(1));
+            loop {
+                tokio::time::sleep(interval).await;
+                node.tick().await;
+            }
+        })
+    }
+
+    /// Runs a transaction over `operations`, keyed by the participant that
+    /// applies them, and returns its id. `Ok` means the commit is durable at
+    /// the coordinator; participants that missed it apply it once a later
+    /// tick reaches them.
+    pub async fn execute(&self, operations: HashMap<String, Vec<DistributedOperation>>) -> Result<String, TwoPhaseError> {
+        if operations.is_empty() {
+            return Err(TwoPhaseError::InvalidOperation("transaction has no participants".to_string()));
+        }
+
+        let tx_id = self.next_tx_id();
+        let participants: Vec<String> = operations.keys().cloned().collect();
+        self.state.lock().await.voting.insert(tx_id.clone(), false);
+
+        let timeout = Duration::from_millis(self.config.prepare_timeout_ms);
+        let votes = join_all(operations.into_iter().map(|(participant, operations)| {
+            let request = TwoPhaseRequest::Prepare {
+                tx_id: tx_id.clone(),
+                coordinator: self.id.clone(),
+                operations,
+            };
+            async move {
+                let vote = self.send(&participant, request, timeout).await;
+                (participant, vote)
+            }
+        })).await;
+
+        let mut refusal = None;
+        for (participant, vote) in votes {
+            let reason = match vote {
+                Ok(TwoPhaseResponse::Vote { commit: true, .. }) => continue,
+                Ok(TwoPhaseResponse::Vote { reason, .. }) => reason.unwrap_or_else(|| "voted no".to_string()),
+                Ok(response) => format!("unexpected response {:?}", response),
+                Err(e) => e.to_string(),
+            };
+            refusal.get_or_insert(format!("{}: {}", participant, reason));
+        }
+
+        {
+            let mut state = self.state.lock().await;
+            if state.voting.remove(&tx_id).unwrap_or(true) {
+                refusal.get_or_insert("a participant was already told it aborted".to_string());
+            }
+            if refusal.is_none() {
+                match state.append(LogRecord::Commit { tx_id: tx_id.clone(), participants: participants.clone() }) {
+                    Ok(()) => {
+                        state.committing.insert(tx_id.clone(), participants.iter().cloned().collect());
+                    }
+                    Err(e) => refusal = Some(e.to_string()),
+                }
+            }
+        }
+
+        if let Some(reason) = refusal {
+            // Best effort: participants that miss the abort learn it when
+            // they ask
+            let timeout = Duration::from_millis(self.config.commit_timeout_ms);
+            join_all(participants.iter().map(|participant| {
+                self.send(participant, TwoPhaseRequest::Abort { tx_id: tx_id.clone() }, timeout)
+            })).await;
+            return Err(TwoPhaseError::Aborted(reason));
+        }
+
+        self.send_commits(&tx_id, participants).await;
+        Ok(tx_id)
+    }
+
+    /// Answers a request from a coordinator or participant, this node
+    /// included.
+    pub async fn handle(&self, request: TwoPhaseRequest) -> Result<TwoPhaseResponse, TwoPhaseError> {
+        match request {
+            TwoPhaseRequest::Prepare { tx_id, coordinator, operations } => self.prepare(tx_id, coordinator, operations).await,
+            TwoPhaseRequest::Commit { tx_id } => self.finish(&tx_id, Decision::Commit).await.map(|_| TwoPhaseResponse::Ack),
+            TwoPhaseRequest::Abort { tx_id } => self.finish(&tx_id, Decision::Abort).await.map(|_| TwoPhaseResponse::Ack),
+            TwoPhaseRequest::Decision { tx_id } => Ok(TwoPhaseResponse::Decision(self.decision(&tx_id).await)),
+        }
+    }
+
+    /// Re-sends unacknowledged commits and asks coordinators about
+    /// transactions in doubt for longer than `in_doubt_timeout_ms`.
+    pub async fn tick(&self) {
+        let (commits, in_doubt) = {
+            let state = self.state.lock().await;
+            let in_doubt_timeout = Duration::from_millis(self.config.in_doubt_timeout_ms);
+
+            let commits: Vec<(String, Vec<String>)> = state.committing.iter()
+                .map(|(tx_id, participants)| (tx_id.clone(), participants.iter().cloned().collect()))
+                .collect();
+            let in_doubt: Vec<(String, String)> = state.prepared.iter()
+                .filter(|(_, prepared)| prepared.prepared_at.is_none_or(|at| at.elapsed() >= in_doubt_timeout))
+                .map(|(tx_id, prepared)| (tx_id.clone(), prepared.coordinator.clone()))
+                .collect();
+            (commits, in_doubt)
+        };
+
+        for (tx_id, participants) in commits {
+            self.send_commits(&tx_id, participants).await;
+        }
+
+        let timeout = Duration::from_millis(self.config.commit_timeout_ms);
+        for (tx_id, coordinator) in in_doubt {
+            // Without an answer the transaction stays in doubt and keeps its
+            // locks: having voted yes, this node may not decide alone
+            let request = TwoPhaseRequest::Decision { tx_id: tx_id.clone() };
+            if let Ok(TwoPhaseResponse::Decision(decision)) = self.send(&coordinator, request, timeout).await {
+                let _ = self.finish(&tx_id, decision).await;
+            }
+        }
+    }
+
+    fn next_tx_id(&self) -> String {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        format!("tx_{}_{}_{}", self.id, nanos, self.next_tx.fetch_add(1, Ordering::Relaxed))
+    }
+
+    async fn send(&self, to: &str, request: TwoPhaseRequest, timeout: Duration) -> Result<TwoPhaseResponse, TwoPhaseError> {
+        match tokio::time::timeout(timeout, self.transport.send(&self.id, to, request)).await {
+            Ok(response) => response,
+            Err(_) => Err(TwoPhaseError::Timeout(to.to_string())),
+        }
+    }
+
+    async fn send_commits(&self, tx_id: &str, participants: Vec<String>) {
+        let timeout = Duration::from_millis(self.config.commit_timeout_ms);
+        let acks = join_all(participants.into_iter().map(|participant| async move {
+            let ack = self.send(&participant, TwoPhaseRequest::Commit { tx_id: tx_id.to_string() }, timeout).await;
+            (participant, ack)
+        })).await;
+
+        let mut state = self.state.lock().await;
+        let Some(remaining) = state.committing.get_mut(tx_id) else {
+            return;
+        };
+        for (participant, ack) in acks {
+            if matches!(ack, Ok(TwoPhaseResponse::Ack)) {
+                remaining.remove(&participant);
+            }
+        }
+
+        // Without the End record the commit is only re-sent after a restart,
+        // which participants acknowledge again
+        if remaining.is_empty() {
+            let _ = state.append(LogRecord::End { tx_id: tx_id.to_string() });
+            state.committing.remove(tx_id);
+        }
+    }
+
+    async fn prepare(
+        &self,
+        tx_id: String,
+        coordinator: String,
+        operations: Vec<DistributedOperation>,
+    ) -> Result<TwoPhaseResponse, TwoPhaseError> {
+        let vote_no = |reason: String| Ok(TwoPhaseResponse::Vote { commit: false, reason: Some(reason) });
+
+        let mut state = self.state.lock().await;
+        if state.prepared.contains_key(&tx_id) {
+            return Ok(TwoPhaseResponse::Vote { commit: true, reason: None });
+        }
+
+        let keys: Vec<String> = operations.iter().map(DistributedOperation::key).collect();
+        if let Some(key) = keys.iter().find(|key| state.locks.contains_key(*key)) {
+            return vote_no(format!("{} is locked by another transaction", key));
+        }
+
+        // Reserved before the check, so what it checks still holds at commit
+        if let Err(e) = self.db.read().await.reserve(&tx_id, &reserved_keys(&operations)) {
+            return vote_no(e.to_string());
+        }
+        let prepared = match self.validate(&operations).await {
+            Ok(()) => state.append(LogRecord::Prepared {
+                tx_id: tx_id.clone(),
+                coordinator: coordinator.clone(),
+                operations: operations.clone(),
+            }).map_err(|e| e.to_string()),
+            Err(reason) => Err(reason),
+        };
+        if let Err(reason) = prepared {
+            self.db.read().await.release(&tx_id);
+            return vote_no(reason);
+        }
+        for key in keys {
+            state.locks.insert(key, tx_id.clone());
+        }
+        state.prepared.insert(tx_id, PreparedTransaction {
+            coordinator,
+            operations,
+            prepared_at: Some(Instant::now()),
+        });
+        Ok(TwoPhaseResponse::Vote { commit: true, reason: None })
+    }
+
+    async fn validate(&self, operations: &[DistributedOperation]) -> Result<(), String> {
+        let db = self.db.read().await;
+
+        for operation in operations {
+            let schema = db.get_collection(operation.collection()).await.map_err(|e| e.to_string())?;
+            match operation {
+                DistributedOperation::Insert { payload, .. } | DistributedOperation::Update { payload, .. } => {
+                    let vector = VectorPayload::decode(payload)?.vector;
+                    if vector.len() != schema.dimension {
+                        return Err(format!(
+                            "{} has dimension {}, collection expects {}", operation.key(), vector.len(), schema.dimension
+                        ));
+                    }
+                }
+                _ => {}
+            }
+            if matches!(operation, DistributedOperation::Update { .. }) {
+                let existing = db.get_vector(operation.collection(), operation.id(), None).await.map_err(|e| e.to_string())?;
+                if existing.is_none() {
+                    return Err(format!("{} does not exist", operation.key()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies or drops a prepared transaction. One that is not prepared
+    /// here was finished already, or never prepared, in which case the
+    /// coordinator cannot have committed it.
+    async fn finish(&self, tx_id: &str, decision: Decision) -> Result<(), TwoPhaseError> {
+        let mut state = self.state.lock().await;
+        let Some(prepared) = state.prepared.get(tx_id) else {
+            return Ok(());
+        };
+
+        match decision {
+            Decision::Commit => {
+                self.apply(tx_id, &prepared.operations).await?;
+                state.append(LogRecord::Committed { tx_id: tx_id.to_string() })?;
+            }
+            Decision::Abort => state.append(LogRecord::Aborted { tx_id: tx_id.to_string() })?,
+        }
+        state.prepared.remove(tx_id);
+        state.release_locks(tx_id);
+        self.db.read().await.release(tx_id);
+        Ok(())
+    }
+
+    /// Applies in one local transaction. Upserts and deletes are idempotent,
+    /// so applying again after a crash before `Committed` was logged is
+    /// harmless.
+    async fn apply(&self, tx_id: &str, operations: &[DistributedOperation]) -> Result<(), TwoPhaseError> {
+        let db = self.db.read().await;
+        let txn = db.begin_as(tx_id).await.map_err(database_error)?;
+
+        let staged = async {
+            for operation in operations {
+                match operation {
+                    DistributedOperation::Insert { collection, id, payload }
+                    | DistributedOperation::Update { collection, id, payload } => {
+                        let payload = VectorPayload::decode(payload).map_err(TwoPhaseError::InvalidOperation)?;
+                        txn.upsert(collection, vec![(id.clone(), payload.vector, payload.metadata)]).await.map_err(database_error)?;
+                    }
+                    DistributedOperation::Delete { collection, id } => {
+                        txn.delete(collection, std::slice::from_ref(id)).await.map_err(database_error)?;
+                    }
+                    DistributedOperation::Read { .. } => {}
+                }
+            }
+            Ok(())
+        }.await;
+
+        match staged {
+            Ok(()) => txn.commit().await.map_err(database_error),
+            Err(e) => {
+                let _ = txn.rollback().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn decision(&self, tx_id: &str) -> Decision {
+        let mut state = self.state.lock().await;
+        if state.committing.contains_key(tx_id) {
+            return Decision::Commit;
+        }
+        if let Some(told_abort) = state.voting.get_mut(tx_id) {
+            *told_abort = true;
+        }
+        Decision::Abort
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::sim::{Fault, RequestKind, TwoPhaseCluster};
+
+    fn config() -> TwoPhaseConfig {
+        TwoPhaseConfig {
+            prepare_timeout_ms: 100,
+            commit_timeout_ms: 100,
+            in_doubt_timeout_ms: 0,
+            tick_interval_ms: 10,
+        }
+    }
+
+    async fn cluster(dir: &Path) -> TwoPhaseCluster {
+        let cluster = TwoPhaseCluster::new(dir, 3, config()).await.unwrap();
+        for node in ["node1", "node2", "node3"] {
+            let db = cluster.db(node).unwrap();
+            db.read().await.create_collection("docs", 3, "cosine").await.unwrap();
+        }
+        cluster
+    }
+
+    fn insert(id: &str) -> DistributedOperation {
+        DistributedOperation::insert("docs", id, vec![1.0, 0.0, 0.0], serde_json::json!({ "id": id }))
+    }
+
+    fn operations(ops: &[(&str, DistributedOperation)]) -> HashMap<String, Vec<DistributedOperation>> {
+        let mut by_node: HashMap<String, Vec<DistributedOperation>> = HashMap::new();
+        for (node, op) in ops {
+            by_node.entry(node.to_string()).or_default().push(op.clone());
+        }
+        by_node
+    }
+
+    async fn stored(cluster: &TwoPhaseCluster, node: &str, id: &str) -> bool {
+        let db = cluster.db(node).unwrap();
+        let db = db.read().await;
+        db.get_vector("docs", id, None).await.unwrap().is_some()
+    }
+
+    #[tokio::test]
+    async fn test_two_phase_commit_applies_on_every_participant() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = cluster(dir.path()).await;
+        {
+            let db = cluster.db("node3").unwrap();
+            db.read().await.insert_vectors("docs", vec![("old".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+        }
+
+        let coordinator = cluster.node("node1").unwrap();
+        coordinator.execute(operations(&[
+            ("node1", insert("a")),
+            ("node2", insert("b")),
+            ("node3", DistributedOperation::update("docs", "old", vec![0.0, 0.0, 1.0], serde_json::json!({ "v": 2 }))),
+            ("node3", insert("c")),
+        ])).await.unwrap();
+
+        assert!(stored(&cluster, "node1", "a").await);
+        assert!(stored(&cluster, "node2", "b").await);
+        assert!(stored(&cluster, "node3", "c").await);
+        let db = cluster.db("node3").unwrap();
+        assert_eq!(db.read().await.get_vector("docs", "old", None).await.unwrap().unwrap().1["v"], 2);
+
+        for node in ["node1", "node2", "node3"] {
+            let status = cluster.node(node).unwrap().status().await;
+            assert!(status.pending_commits.is_empty() && status.in_doubt.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_phase_no_vote_aborts_everywhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = cluster(dir.path()).await;
+        let coordinator = cluster.node("node1").unwrap();
+
+        // node3 cannot apply an update to a vector it does not have
+        let err = coordinator.execute(operations(&[
+            ("node2", insert("a")),
+            ("node3", DistributedOperation::update("docs", "missing", vec![1.0, 0.0, 0.0], serde_json::json!({}))),
+        ])).await.unwrap_err();
+        assert!(matches!(&err, TwoPhaseError::Aborted(reason) if reason.contains("node3")));
+        assert!(!stored(&cluster, "node2", "a").await);
+        assert!(cluster.node("node2").unwrap().status().await.in_doubt.is_empty());
+
+        // Dimension mismatches are refused too, and node2 holds no lock on "a"
+        let err = coordinator.execute(operations(&[
+            ("node2", insert("a")),
+            ("node3", DistributedOperation::insert("docs", "x", vec![1.0, 0.0], serde_json::json!({}))),
+        ])).await.unwrap_err();
+        assert!(err.to_string().contains("dimension"));
+        coordinator.execute(operations(&[("node2", insert("a"))])).await.unwrap();
+        assert!(stored(&cluster, "node2", "a").await);
+    }
+
+    #[tokio::test]
+    async fn test_two_phase_presumed_abort_resolves_in_doubt_participant() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = cluster(dir.path()).await;
+        let coordinator = cluster.node("node1").unwrap();
+
+        // node2 answers too late; node3 votes yes, but the vote and the abort
+        // that follows are both lost
+        cluster.network().inject("node2", RequestKind::Prepare, Fault::Delay(Duration::from_millis(500)));
+        cluster.network().inject("node3", RequestKind::Prepare, Fault::DropResponse);
+        cluster.network().inject("node3", RequestKind::Abort, Fault::DropRequest);
+
+        let err = coordinator.execute(operations(&[
+            ("node2", insert("a")),
+            ("node3", insert("b")),
+        ])).await.unwrap_err();
+        assert!(err.to_string().contains("Timed out") || err.to_string().contains("unreachable"));
+        assert_eq!(cluster.node("node3").unwrap().status().await.in_doubt.len(), 1);
+
+        // The coordinator holds no commit record, so node3 is told to abort
+        cluster.network().heal();
+        cluster.tick().await;
+        assert!(cluster.node("node3").unwrap().status().await.in_doubt.is_empty());
+        assert!(!stored(&cluster, "node2", "a").await);
+        assert!(!stored(&cluster, "node3", "b").await);
+
+        coordinator.execute(operations(&[("node3", insert("b"))])).await.unwrap();
+        assert!(stored(&cluster, "node3", "b").await);
+    }
+
+    #[tokio::test]
+    async fn test_two_phase_recovers_commit_after_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cluster = cluster(dir.path()).await;
+
+        // The commit never reaches node3
+        cluster.network().inject("node3", RequestKind::Commit, Fault::DropRequest);
+        let tx_id = cluster.node("node1").unwrap().execute(operations(&[
+            ("node2", insert("a")),
+            ("node3", insert("b")),
+        ])).await.unwrap();
+        assert!(stored(&cluster, "node2", "a").await);
+        assert!(!stored(&cluster, "node3", "b").await);
+
+        // Both crash before the commit is re-sent
+        cluster.crash("node1");
+        cluster.crash("node3");
+        cluster.network().heal();
+        let coordinator = cluster.restart("node1").await.unwrap();
+        let participant = cluster.restart("node3").await.unwrap();
+        assert_eq!(coordinator.status().await.pending_commits, vec![tx_id.clone()]);
+        assert_eq!(participant.status().await.in_doubt, vec![tx_id]);
+
+        // node3's key stays locked while it is in doubt
+        let err = coordinator.execute(operations(&[("node3", insert("b"))])).await.unwrap_err();
+        assert!(err.to_string().contains("locked"));
+
+        cluster.tick().await;
+        assert!(stored(&cluster, "node3", "b").await);
+        assert!(coordinator.status().await.pending_commits.is_empty());
+        assert!(participant.status().await.in_doubt.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prepared_keys_are_reserved_in_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = cluster(dir.path()).await;
+
+        // node3 prepares but the commit does not reach it yet
+        cluster.network().inject("node3", RequestKind::Commit, Fault::DropRequest);
+        cluster.node("node1").unwrap().execute(operations(&[
+            ("node2", insert("a")),
+            ("node3", insert("b")),
+        ])).await.unwrap();
+        assert_eq!(cluster.node("node3").unwrap().status().await.in_doubt.len(), 1);
+
+        // Neither the collection nor the key can change under it meanwhile
+        {
+            let db = cluster.db("node3").unwrap();
+            let db = db.read().await;
+            assert!(db.delete_collection("docs").await.is_err());
+            assert!(db.insert_vectors("docs", vec![("b".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({}))]).await.is_err());
+            db.insert_vectors("docs", vec![("c".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+        }
+
+        cluster.network().heal();
+        cluster.tick().await;
+        assert!(stored(&cluster, "node3", "b").await);
+        assert!(cluster.node("node3").unwrap().status().await.in_doubt.is_empty());
+        let db = cluster.db("node3").unwrap();
+        db.read().await.delete_collection("docs").await.unwrap();
+    }
+}
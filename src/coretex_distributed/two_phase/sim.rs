@@ -0,0 +1,184 @@
+//! In-process two-phase commit network
+//! `SimNetwork` delivers requests by calling the target node directly and can
+//! cut nodes off or drop, lose the reply to, or delay one kind of request to
+//! a node. `TwoPhaseCluster` runs a set of nodes on one, each with its own
+//! `CoreTexDB`, and crashes and restarts them from their logs.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+use async_trait::async_trait;
+
+use super::{TwoPhaseConfig, TwoPhaseError, TwoPhaseNode, TwoPhaseRequest, TwoPhaseResponse, TwoPhaseTransport};
+use crate::CoreTexDB;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    Prepare,
+    Commit,
+    Abort,
+    Decision,
+}
+
+impl RequestKind {
+    pub fn of(request: &TwoPhaseRequest) -> Self {
+        match request {
+            TwoPhaseRequest::Prepare { .. } => RequestKind::Prepare,
+            TwoPhaseRequest::Commit { .. } => RequestKind::Commit,
+            TwoPhaseRequest::Abort { .. } => RequestKind::Abort,
+            TwoPhaseRequest::Decision { .. } => RequestKind::Decision,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    /// The request never arrives.
+    DropRequest,
+    /// The request is handled but the reply is lost.
+    DropResponse,
+    /// The request arrives this much later.
+    Delay(Duration),
+}
+
+#[derive(Default)]
+pub struct SimNetwork {
+    nodes: RwLock<HashMap<String, Weak<TwoPhaseNode>>>,
+    isolated: RwLock<HashSet<String>>,
+    /// By target node and request kind.
+    faults: RwLock<HashMap<(String, RequestKind), Fault>>,
+}
+
+impl SimNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, node: &Arc<TwoPhaseNode>) {
+        self.nodes.write().unwrap().insert(node.id().to_string(), Arc::downgrade(node));
+    }
+
+    pub fn unregister(&self, node_id: &str) {
+        self.nodes.write().unwrap().remove(node_id);
+    }
+
+    /// Fails every request to and from `node_id`.
+    pub fn isolate(&self, node_id: &str) {
+        self.isolated.write().unwrap().insert(node_id.to_string());
+    }
+
+    /// Applies `fault` to every `kind` request sent to `node_id` until
+    /// `heal`.
+    pub fn inject(&self, node_id: &str, kind: RequestKind, fault: Fault) {
+        self.faults.write().unwrap().insert((node_id.to_string(), kind), fault);
+    }
+
+    /// Reconnects isolated nodes and clears every fault.
+    pub fn heal(&self) {
+        self.isolated.write().unwrap().clear();
+        self.faults.write().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl TwoPhaseTransport for SimNetwork {
+    async fn send(&self, from: &str, to: &str, request: TwoPhaseRequest) -> Result<TwoPhaseResponse, TwoPhaseError> {
+        let unreachable = || TwoPhaseError::Unreachable(to.to_string());
+        {
+            let isolated = self.isolated.read().unwrap();
+            if isolated.contains(from) || isolated.contains(to) {
+                return Err(unreachable());
+            }
+        }
+
+        let fault = self.faults.read().unwrap().get(&(to.to_string(), RequestKind::of(&request))).copied();
+        match fault {
+            Some(Fault::DropRequest) => return Err(unreachable()),
+            Some(Fault::Delay(delay)) => tokio::time::sleep(delay).await,
+            _ => {}
+        }
+
+        let node = self.nodes.read().unwrap()
+            .get(to)
+            .and_then(Weak::upgrade)
+            .ok_or_else(unreachable)?;
+        let response = node.handle(request).await?;
+
+        match fault {
+            Some(Fault::DropResponse) => Err(unreachable()),
+            _ => Ok(response),
+        }
+    }
+}
+
+/// Nodes `node1..nodeN` on one `SimNetwork`, each logging to its own
+/// directory under `dir`. A node's `CoreTexDB` outlives crashes, as a
+/// database with its own storage would.
+pub struct TwoPhaseCluster {
+    dir: PathBuf,
+    config: TwoPhaseConfig,
+    network: Arc<SimNetwork>,
+    nodes: BTreeMap<String, Arc<TwoPhaseNode>>,
+    dbs: HashMap<String, Arc<tokio::sync::RwLock<CoreTexDB>>>,
+}
+
+impl TwoPhaseCluster {
+    pub async fn new(dir: &Path, size: usize, config: TwoPhaseConfig) -> Result<Self, TwoPhaseError> {
+        let mut cluster = Self {
+            dir: dir.to_path_buf(),
+            config,
+            network: Arc::new(SimNetwork::new()),
+            nodes: BTreeMap::new(),
+            dbs: HashMap::new(),
+        };
+
+        for i in 1..=size {
+            let node_id = format!("node{}", i);
+            cluster.dbs.insert(node_id.clone(), Arc::new(tokio::sync::RwLock::new(CoreTexDB::new())));
+            cluster.restart(&node_id).await?;
+        }
+        Ok(cluster)
+    }
+
+    pub fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    /// `None` while the node is crashed.
+    pub fn node(&self, node_id: &str) -> Option<Arc<TwoPhaseNode>> {
+        self.nodes.get(node_id).cloned()
+    }
+
+    pub fn db(&self, node_id: &str) -> Option<Arc<tokio::sync::RwLock<CoreTexDB>>> {
+        self.dbs.get(node_id).cloned()
+    }
+
+    /// Stops a node, losing everything it did not log.
+    pub fn crash(&mut self, node_id: &str) {
+        self.nodes.remove(node_id);
+        self.network.unregister(node_id);
+    }
+
+    /// Starts a node again from its log.
+    pub async fn restart(&mut self, node_id: &str) -> Result<Arc<TwoPhaseNode>, TwoPhaseError> {
+        let db = self.dbs.get(node_id)
+            .cloned()
+            .ok_or_else(|| TwoPhaseError::Unreachable(node_id.to_string()))?;
+        let node = Arc::new(
+            TwoPhaseNode::open(&self.dir.join(node_id), node_id, db, self.network.clone()).await?
+                .with_config(self.config.clone()),
+        );
+
+        self.network.register(&node);
+        self.nodes.insert(node_id.to_string(), node.clone());
+        Ok(node)
+    }
+
+    /// Ticks every running node once.
+    pub async fn tick(&self) {
+        for node in self.nodes.values() {
+            node.tick().await;
+        }
+    }
+}
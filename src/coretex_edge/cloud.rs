@@ -0,0 +1,463 @@
+//! Cloud side of edge sync: the server that reconciles edge devices with a
+//! `CoreTexDB`, and the HTTP transport that reaches it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use async_trait::async_trait;
+use tokio::sync::{Mutex, RwLock};
+
+use super::server_log::{LogRecord, ServerLog, ServerLogFile};
+use super::sync::{SyncChange, SyncRequest, SyncResponse, SyncTransport, SyncVersion};
+use super::EdgeError;
+use crate::coretex_api::rest::ApiResponse;
+use crate::coretex_journal;
+use crate::coretex_side_vectors as side_vectors;
+use crate::coretex_utils::wal::{WalEntry, WalEntryType};
+use crate::CoreTexDB;
+
+/// Talks to a server's `POST /api/sync` endpoint.
+pub struct HttpSyncTransport {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpSyncTransport {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: format!("{}/api/sync", base_url.trim_end_matches('/')),
+        }
+    }
+}
+
+#[async_trait]
+impl SyncTransport for HttpSyncTransport {
+    async fn exchange(&self, request: SyncRequest) -> Result<SyncResponse, EdgeError> {
+        let body = serde_json::to_vec(&request).map_err(|e| EdgeError::SyncError(e.to_string()))?;
+        let response = self.client.post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| EdgeError::SyncError(e.to_string()))?;
+        let bytes = response.bytes().await.map_err(|e| EdgeError::SyncError(e.to_string()))?;
+
+        let response: ApiResponse<SyncResponse> = serde_json::from_slice(&bytes)
+            .map_err(|e| EdgeError::SyncError(format!("invalid sync response: {}", e)))?;
+        response.data.ok_or_else(|| EdgeError::SyncError(response.error.unwrap_or_default()))
+    }
+}
+
+/// Journal entries read per step of catching up.
+const RECONCILE_BATCH: usize = 256;
+
+struct ServerState {
+    logs: HashMap<String, ServerLog>,
+    /// Journal entries up to here are versioned.
+    journal_lsn: u64,
+    file: Option<ServerLogFile>,
+}
+
+impl ServerState {
+    fn append(&self, records: &[LogRecord]) -> Result<(), EdgeError> {
+        match &self.file {
+            Some(file) => file.append(records),
+            None => Ok(()),
+        }
+    }
+
+    fn record(&mut self, collection: &str, id: &str, version: SyncVersion, deleted: bool, lsn: u64) -> LogRecord {
+        let entry = self.logs.entry(collection.to_string()).or_default().record(id, version, deleted, lsn);
+        LogRecord::Entry { collection: collection.to_string(), id: id.to_string(), entry }
+    }
+
+    /// Versions the writes of a journal entry made outside sync as `origin`'s,
+    /// as of the entry's timestamp.
+    /// Writes an entry already holds a version for, from a push or from later
+    /// in the journal, are skipped.
+    fn version_entry(&mut self, origin: &str, entry: &WalEntry) -> Result<Vec<LogRecord>, EdgeError> {
+        let writes: Vec<(String, String, bool)> = match entry.entry_type {
+            WalEntryType::DeleteCollection => self.logs.get(&entry.collection)
+                .map(|log| log.entries.iter()
+                    .filter(|(_, logged)| !logged.deleted)
+                    .map(|(id, _)| (entry.collection.clone(), id.clone(), true))
+                    .collect())
+                .unwrap_or_default(),
+            _ => coretex_journal::entry_writes(entry).map_err(server_error)?
+                .into_iter()
+                .filter(|((collection, _), _)| side_vectors::main_collection(collection).is_none())
+                .map(|((collection, id), value)| (collection, id, value.is_none()))
+                .collect(),
+        };
+
+        let written_ms = entry.data["timestamp_ms"].as_i64().unwrap_or(entry.timestamp as i64 * 1000);
+        let mut records = Vec::new();
+        for (collection, id, deleted) in writes {
+            let previous = self.logs.get(&collection).and_then(|log| log.entries.get(&id));
+            if previous.is_some_and(|logged| logged.lsn >= entry.id) {
+                continue;
+            }
+            // Stamped with when the write was made, not when it is found, so
+            // an edge write made after it still wins. The server applied it
+            // after `previous`, so it still orders after that.
+            let version = match previous {
+                Some(logged) if logged.version.timestamp_ms >= written_ms => SyncVersion {
+                    timestamp_ms: logged.version.timestamp_ms + 1,
+                    origin: origin.to_string(),
+                },
+                _ => SyncVersion { timestamp_ms: written_ms, origin: origin.to_string() },
+            };
+            records.push(self.record(&collection, &id, version, deleted, entry.id));
+        }
+        Ok(records)
+    }
+}
+
+/// Server side of the sync protocol. Applies pushed changes to a `CoreTexDB`
+/// and keeps a change log for edges to pull from. Writes made outside sync
+/// (e.g. the REST API) are found in the database journal, so the database
+/// must come from `CoreTexDB::open`.
+pub struct SyncServer {
+    db: Arc<RwLock<CoreTexDB>>,
+    node_id: String,
+    epoch: String,
+    state: Mutex<ServerState>,
+}
+
+impl SyncServer {
+    /// A server that keeps its change log in memory only; edges start over
+    /// whenever it restarts.
+    pub fn new(db: Arc<RwLock<CoreTexDB>>) -> Self {
+        Self::build(db, uuid::Uuid::new_v4().to_string(), ServerState {
+            logs: HashMap::new(),
+            journal_lsn: 0,
+            file: None,
+        })
+    }
+
+    /// A server logging to `dir`. Its epoch and versions survive restarts,
+    /// so edges resume where they stopped.
+    pub fn open(dir: &Path, db: Arc<RwLock<CoreTexDB>>) -> Result<Self, EdgeError> {
+        let (file, recovered) = ServerLogFile::open(dir)?;
+        Ok(Self::build(db, recovered.epoch, ServerState {
+            logs: recovered.logs,
+            journal_lsn: recovered.journal_lsn,
+            file: Some(file),
+        }))
+    }
+
+    fn build(db: Arc<RwLock<CoreTexDB>>, epoch: String, state: ServerState) -> Self {
+        Self {
+            db,
+            node_id: "server".to_string(),
+            epoch,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Origin stamped on writes made directly on the server.
+    pub fn with_node_id(mut self, node_id: &str) -> Self {
+        self.node_id = node_id.to_string();
+        self
+    }
+
+    pub async fn handle(&self, request: SyncRequest) -> Result<SyncResponse, EdgeError> {
+        match request {
+            SyncRequest::Handshake { .. } => Ok(SyncResponse::Handshake { epoch: self.epoch.clone() }),
+            SyncRequest::Push { collection, dimension, changes, .. } => {
+                self.push(&collection, dimension, changes).await
+            }
+            SyncRequest::Pull { node_id, collection, since, limit } => {
+                self.pull(&node_id, &collection, since, limit.max(1)).await
+            }
+        }
+    }
+
+    async fn push(&self, collection: &str, dimension: usize, changes: Vec<SyncChange>) -> Result<SyncResponse, EdgeError> {
+        let db = self.db.read().await;
+
+        match db.get_collection(collection).await {
+            Ok(schema) if schema.dimension != dimension => {
+                return Err(EdgeError::InvalidDimension(format!(
+                    "Expected {}, got {}",
+                    schema.dimension,
+                    dimension
+                )));
+            }
+            Ok(_) => {}
+            Err(_) => db.create_collection(collection, dimension, "cosine").await.map_err(server_error)?,
+        }
+        if let Some(vector) = changes.iter().filter_map(|change| change.vector.as_ref()).find(|v| v.len() != dimension) {
+            return Err(EdgeError::InvalidDimension(format!("Expected {}, got {}", dimension, vector.len())));
+        }
+
+        self.reconcile(&db).await?;
+        let mut state = self.state.lock().await;
+
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
+        let mut writes = BTreeMap::new();
+        for change in changes {
+            let logged = state.logs.get(collection).and_then(|log| log.entries.get(&change.id));
+            if logged.is_some_and(|entry| entry.version >= change.version) {
+                rejected.push(change.id);
+                continue;
+            }
+
+            let value = change.vector.map(|vector| {
+                (vector, change.metadata.unwrap_or_else(|| serde_json::json!({})))
+            });
+            accepted.push((change.id.clone(), change.version, value.is_none()));
+            writes.insert((collection.to_string(), change.id), value);
+        }
+
+        // Versioned with the journal entry that holds them, so catching up
+        // on the journal later does not take them for outside writes
+        let lsn = {
+            let mut data = db.data.write().await;
+            if !data.contains_key(collection) {
+                return Err(server_error(crate::CoreTexError::CollectionNotFound(collection.to_string())));
+            }
+            db.apply_journaled_writes(&mut data, writes).await.map_err(server_error)?
+        };
+        let records: Vec<LogRecord> = accepted.into_iter()
+            .map(|(id, version, deleted)| state.record(collection, &id, version, deleted, lsn.unwrap_or(0)))
+            .collect();
+        state.append(&records)?;
+
+        Ok(SyncResponse::Push { accepted: records.len(), rejected })
+    }
+
+    async fn pull(&self, node_id: &str, collection: &str, since: u64, limit: usize) -> Result<SyncResponse, EdgeError> {
+        let db = self.db.read().await;
+        if db.get_collection(collection).await.is_err() {
+            return Ok(SyncResponse::Pull { changes: Vec::new(), cursor: 0, has_more: false });
+        }
+
+        self.reconcile(&db).await?;
+        let state = self.state.lock().await;
+        let Some(log) = state.logs.get(collection) else {
+            return Ok(SyncResponse::Pull { changes: Vec::new(), cursor: 0, has_more: false });
+        };
+
+        let mut entries: Vec<_> = log.entries.iter()
+            .filter(|(_, entry)| entry.sequence > since && entry.version.origin != node_id)
+            .collect();
+        entries.sort_by_key(|(_, entry)| entry.sequence);
+        let has_more = entries.len() > limit;
+        entries.truncate(limit);
+
+        let cursor = match entries.last() {
+            Some((_, entry)) if has_more => entry.sequence,
+            _ => log.sequence,
+        };
+
+        let data = db.data.read().await;
+        let collection_data = data.get(collection);
+        let mut changes = Vec::with_capacity(entries.len());
+        for (id, entry) in entries {
+            let stored = match collection_data.filter(|_| !entry.deleted) {
+                Some(cd) => db.document(cd, collection, id).await.map_err(server_error)?,
+                None => None,
+            };
+            let (vector, metadata) = stored.unzip();
+            changes.push(SyncChange {
+                id: id.clone(),
+                vector,
+                metadata,
+                version: entry.version.clone(),
+            });
+        }
+
+        Ok(SyncResponse::Pull { changes, cursor, has_more })
+    }
+
+    /// Versions writes that bypassed sync as of now, so edges pull them like
+    /// any other change. Only journal entries after the last one seen are
+    /// read, and none while the change log is locked.
+    async fn reconcile(&self, db: &CoreTexDB) -> Result<(), EdgeError> {
+        loop {
+            let lsn = self.state.lock().await.journal_lsn;
+            let entries = db.journal.tail(lsn, RECONCILE_BATCH).await.map_err(server_error)?
+                .ok_or_else(|| EdgeError::SyncError(format!("The journal no longer reaches back to entry {}", lsn + 1)))?;
+            let Some(last) = entries.last().map(|entry| entry.id) else {
+                return Ok(());
+            };
+
+            let mut state = self.state.lock().await;
+            let seen = state.journal_lsn;
+            let mut records = Vec::new();
+            for entry in entries.iter().filter(|entry| entry.id > seen) {
+                records.extend(state.version_entry(&self.node_id, entry)?);
+            }
+            state.journal_lsn = state.journal_lsn.max(last);
+            records.push(LogRecord::Journal { lsn: state.journal_lsn });
+            state.append(&records)?;
+        }
+    }
+}
+
+#[async_trait]
+impl SyncTransport for SyncServer {
+    async fn exchange(&self, request: SyncRequest) -> Result<SyncResponse, EdgeError> {
+        self.handle(request).await
+    }
+}
+
+fn server_error(e: crate::CoreTexError) -> EdgeError {
+    EdgeError::SyncError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coretex_edge::sync::EdgeSyncClient;
+    use crate::coretex_edge::EdgeDB;
+    use crate::coretex_edge::EdgeConfig;
+
+    async fn edge(dir: &std::path::Path, node_id: &str) -> Arc<EdgeDB> {
+        let db = EdgeDB::with_config(EdgeConfig::default())
+            .with_data_dir(dir.to_str().unwrap())
+            .with_node_id(node_id);
+        db.init().await.unwrap();
+        Arc::new(db)
+    }
+
+    async fn open_server(dir: &std::path::Path) -> (Arc<RwLock<CoreTexDB>>, Arc<SyncServer>) {
+        let db = CoreTexDB::open(crate::DbConfig::new(&dir.join("db").to_string_lossy())).await.unwrap();
+        let db = Arc::new(RwLock::new(db));
+        (db.clone(), Arc::new(SyncServer::open(&dir.join("sync"), db).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_edge_changes_reach_other_edges() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server_dir = tempfile::tempdir().unwrap();
+        let (cloud, server) = open_server(server_dir.path()).await;
+
+        let robot = edge(&temp_dir.path().join("robot"), "robot").await;
+        robot.create_collection("memories", 2).await.unwrap();
+        robot.insert("memories", "a", vec![1.0, 0.0], Some(serde_json::json!({"room": "lab"}))).await.unwrap();
+        robot.insert("memories", "b", vec![0.0, 1.0], None).await.unwrap();
+        robot.insert("memories", "c", vec![0.5, 0.5], None).await.unwrap();
+        robot.delete("memories", "c").await.unwrap();
+
+        let client = EdgeSyncClient::new(robot.clone(), server.clone()).with_batch_size(1);
+        let report = client.sync().await.unwrap();
+        assert_eq!(report.pushed, 3);
+        assert_eq!(report.pulled, 0);
+        assert_eq!(cloud.read().await.get_vectors_count("memories").await.unwrap(), 2);
+
+        // Nothing new on either side
+        let report = client.sync().await.unwrap();
+        assert_eq!((report.pushed, report.pulled), (0, 0));
+
+        let other = edge(&temp_dir.path().join("other"), "other").await;
+        other.create_collection("memories", 2).await.unwrap();
+        let report = EdgeSyncClient::new(other.clone(), server.clone()).with_batch_size(1).sync().await.unwrap();
+        assert_eq!(report.pulled, 3);
+        let (_, metadata) = other.get("memories", "a").await.unwrap().unwrap();
+        assert_eq!(metadata["room"], "lab");
+        assert!(other.get("memories", "c").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_conflicts_resolve_last_writer_wins() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server_dir = tempfile::tempdir().unwrap();
+        let (cloud, server) = open_server(server_dir.path()).await;
+
+        let robot = edge(temp_dir.path(), "robot").await;
+        robot.create_collection("memories", 2).await.unwrap();
+        robot.insert("memories", "a", vec![1.0, 0.0], None).await.unwrap();
+        let client = EdgeSyncClient::new(robot.clone(), server.clone());
+        client.sync().await.unwrap();
+
+        // Offline edit on the robot, then a later edit in the cloud
+        robot.insert("memories", "a", vec![0.0, 1.0], None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let cloud_db = cloud.read().await;
+        cloud_db.delete_vectors("memories", &["a".to_string()]).await.unwrap();
+        cloud_db.insert_vectors("memories", vec![("a".to_string(), vec![0.6, 0.8], serde_json::json!({}))]).await.unwrap();
+        cloud_db.insert_vectors("memories", vec![("z".to_string(), vec![0.1, 0.2], serde_json::json!({}))]).await.unwrap();
+        drop(cloud_db);
+
+        let report = client.sync().await.unwrap();
+        assert_eq!(report.rejected, 1);
+        assert_eq!(report.pulled, 2);
+        assert_eq!(robot.get("memories", "a").await.unwrap().unwrap().0, vec![0.6, 0.8]);
+        assert!(robot.get("memories", "z").await.unwrap().is_some());
+
+        // A newer edge delete wins over the cloud copy
+        robot.delete("memories", "z").await.unwrap();
+        let report = client.sync().await.unwrap();
+        assert_eq!(report.pushed, 1);
+        assert!(cloud.read().await.get_vector("memories", "z", None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_outside_writes_are_versioned_when_made() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server_dir = tempfile::tempdir().unwrap();
+        let (cloud, server) = open_server(server_dir.path()).await;
+
+        let robot = edge(temp_dir.path(), "robot").await;
+        robot.create_collection("memories", 2).await.unwrap();
+        robot.insert("memories", "a", vec![1.0, 0.0], None).await.unwrap();
+        let client = EdgeSyncClient::new(robot.clone(), server.clone());
+        client.sync().await.unwrap();
+
+        // A cloud edit, then a later offline edit on the robot, both found
+        // by the server only at the next sync
+        cloud.read().await
+            .insert_vectors("memories", vec![("a".to_string(), vec![0.6, 0.8], serde_json::json!({}))]).await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        robot.insert("memories", "a", vec![0.0, 1.0], None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+        let report = client.sync().await.unwrap();
+        assert_eq!((report.pushed, report.rejected), (1, 0));
+        assert_eq!(cloud.read().await.get_vector("memories", "a", None).await.unwrap().unwrap().0, vec![0.0, 1.0]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_progress_survives_restart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let server_dir = tempfile::tempdir().unwrap();
+        let (cloud, server) = open_server(server_dir.path()).await;
+
+        {
+            let robot = edge(temp_dir.path(), "robot").await;
+            robot.create_collection("memories", 2).await.unwrap();
+            robot.insert("memories", "a", vec![1.0, 0.0], None).await.unwrap();
+            EdgeSyncClient::new(robot.clone(), server.clone()).sync().await.unwrap();
+            robot.insert("memories", "b", vec![0.0, 1.0], None).await.unwrap();
+            robot.flush().await.unwrap();
+        }
+
+        let robot = edge(temp_dir.path(), "robot").await;
+        let report = EdgeSyncClient::new(robot.clone(), server.clone()).sync().await.unwrap();
+        assert_eq!(report.pushed, 1);
+        assert_eq!(cloud.read().await.get_vectors_count("memories").await.unwrap(), 2);
+
+        // A write outside sync, then the server restarts with its database
+        cloud.read().await
+            .insert_vectors("memories", vec![("z".to_string(), vec![0.1, 0.2], serde_json::json!({}))]).await
+            .unwrap();
+        drop((cloud, server));
+        let (cloud, restarted) = open_server(server_dir.path()).await;
+
+        let report = EdgeSyncClient::new(robot.clone(), restarted).sync().await.unwrap();
+        assert_eq!((report.pushed, report.rejected, report.pulled), (0, 0, 1));
+        assert!(robot.get("memories", "z").await.unwrap().is_some());
+
+        // A server that lost its log versions what it holds anew, newer than
+        // what the robot has
+        let fresh_dir = tempfile::tempdir().unwrap();
+        let fresh = Arc::new(SyncServer::open(fresh_dir.path(), cloud.clone()).unwrap());
+        let report = EdgeSyncClient::new(robot.clone(), fresh).sync().await.unwrap();
+        assert_eq!((report.pushed, report.rejected), (0, 2));
+    }
+}
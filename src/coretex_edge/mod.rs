@@ -0,0 +1,680 @@
+//! Edge Deployment for CortexDB
+//! Embedded mode for resource-constrained devices
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+mod cloud;
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+mod server_log;
+mod storage;
+pub mod sync;
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+use storage::{EdgeOp, EdgeStorage};
+use sync::{ChangeLog, SyncChange, SyncCursor, SyncVersion};
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+pub use cloud::{HttpSyncTransport, SyncServer};
+pub use sync::{EdgeSyncClient, SyncReport, SyncRequest, SyncResponse, SyncTransport};
+
+pub struct EdgeDB {
+    data_dir: String,
+    in_memory: bool,
+    collections: Arc<RwLock<std::collections::HashMap<String, EdgeCollection>>>,
+    config: EdgeConfig,
+    /// Origin stamped on local writes for sync conflict resolution.
+    node_id: String,
+    encryption_key: Option<Vec<u8>>,
+    /// Opened by `init`; `None` for in-memory databases.
+    storage: Mutex<Option<EdgeStorage>>,
+    memory_bytes: AtomicUsize,
+}
+
+#[derive(Debug, Clone)]
+pub struct EdgeConfig {
+    pub max_memory_mb: usize,
+    pub max_disk_gb: usize,
+    pub cache_size_mb: usize,
+    pub enable_compression: bool,
+    pub enable_encryption: bool,
+}
+
+impl Default for EdgeConfig {
+    fn default() -> Self {
+        Self {
+            max_memory_mb: 256,
+            max_disk_gb: 1,
+            cache_size_mb: 64,
+            enable_compression: false,
+            enable_encryption: false,
+        }
+    }
+}
+
+impl EdgeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_memory(mut self, mb: usize) -> Self {
+        self.max_memory_mb = mb;
+        self
+    }
+
+    pub fn with_max_disk(mut self, gb: usize) -> Self {
+        self.max_disk_gb = gb;
+        self
+    }
+
+    pub fn with_cache_size(mut self, mb: usize) -> Self {
+        self.cache_size_mb = mb;
+        self
+    }
+
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.enable_compression = enabled;
+        self
+    }
+
+    pub fn with_encryption(mut self, enabled: bool) -> Self {
+        self.enable_encryption = enabled;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct EdgeCollection {
+    pub name: String,
+    pub dimension: usize,
+    pub vectors: std::collections::HashMap<String, Vec<f32>>,
+    pub metadata: std::collections::HashMap<String, serde_json::Value>,
+    pub(crate) changes: ChangeLog,
+}
+
+impl EdgeCollection {
+    pub fn new(name: &str, dimension: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            dimension,
+            vectors: std::collections::HashMap::new(),
+            metadata: std::collections::HashMap::new(),
+            changes: ChangeLog::default(),
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.vectors.iter()
+            .map(|(id, vector)| entry_memory_bytes(id, vector, self.metadata.get(id)))
+            .sum()
+    }
+}
+
+/// Rough in-memory cost of one entry: key, vector, metadata text and map overhead.
+fn entry_memory_bytes(id: &str, vector: &[f32], metadata: Option<&serde_json::Value>) -> usize {
+    const ENTRY_OVERHEAD: usize = 64;
+    id.len() + vector.len() * 4 + metadata.map(|m| m.to_string().len()).unwrap_or(0) + ENTRY_OVERHEAD
+}
+
+impl EdgeDB {
+    pub fn new() -> Self {
+        Self::build(EdgeConfig::default(), true)
+    }
+
+    pub fn with_config(config: EdgeConfig) -> Self {
+        Self::build(config, false)
+    }
+
+    fn build(config: EdgeConfig, in_memory: bool) -> Self {
+        Self {
+            data_dir: "./data".to_string(),
+            in_memory,
+            collections: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            config,
+            node_id: format!("edge-{}", uuid::Uuid::new_v4()),
+            encryption_key: None,
+            storage: Mutex::new(None),
+            memory_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self::new()
+    }
+
+    pub fn in_memory_with_config(config: EdgeConfig) -> Self {
+        Self::build(config, true)
+    }
+
+    pub fn with_data_dir(mut self, dir: &str) -> Self {
+        self.data_dir = dir.to_string();
+        self.in_memory = false;
+        self
+    }
+
+    /// Names this device in sync versions. Should be stable across restarts;
+    /// a random id is used otherwise.
+    pub fn with_node_id(mut self, node_id: &str) -> Self {
+        self.node_id = node_id.to_string();
+        self
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Sets the 256-bit key used to encrypt data at rest and turns encryption on.
+    pub fn with_encryption_key(mut self, key: &[u8]) -> Self {
+        self.encryption_key = Some(key.to_vec());
+        self.config.enable_encryption = true;
+        self
+    }
+
+    /// Opens the data directory and recovers whatever was persisted there:
+    /// the latest snapshot plus every change logged after it.
+    pub async fn init(&self) -> Result<(), EdgeError> {
+        if self.in_memory {
+            return Ok(());
+        }
+
+        let mut collections = self.collections.write().await;
+        let mut storage = self.storage.lock().await;
+        if storage.is_some() {
+            return Ok(());
+        }
+
+        let key = if self.config.enable_encryption {
+            Some(self.encryption_key.clone()
+                .ok_or_else(|| EdgeError::EncryptionError("encryption is enabled but no key was set".to_string()))?)
+        } else {
+            None
+        };
+        let max_disk_bytes = (self.config.max_disk_gb as u64).saturating_mul(1024 * 1024 * 1024);
+
+        let dir = Path::new(&self.data_dir).to_path_buf();
+        let compress = self.config.enable_compression;
+        let (opened, recovered) = storage::blocking(move || {
+            EdgeStorage::open(&dir, compress, key.as_deref(), max_disk_bytes)
+        }).await?;
+
+        self.memory_bytes.store(recovered.values().map(EdgeCollection::memory_bytes).sum(), Ordering::SeqCst);
+        *collections = recovered;
+        *storage = Some(opened);
+
+        Ok(())
+    }
+
+    /// Logs `op` (when backed by disk) and then applies it.
+    async fn commit(&self, collections: &mut std::collections::HashMap<String, EdgeCollection>, op: EdgeOp) -> Result<(), EdgeError> {
+        let mut storage = self.storage.lock().await;
+        if let Some(storage) = storage.as_mut() {
+            storage.append(&op, collections).await?;
+            op.apply(collections);
+            storage.maybe_checkpoint(collections).await?;
+        } else {
+            op.apply(collections);
+        }
+        Ok(())
+    }
+
+    pub async fn create_collection(&self, name: &str, dimension: usize) -> Result<(), EdgeError> {
+        let mut collections = self.collections.write().await;
+        
+        if collections.contains_key(name) {
+            return Err(EdgeError::CollectionExists(name.to_string()));
+        }
+
+        self.commit(&mut collections, EdgeOp::CreateCollection {
+            name: name.to_string(),
+            dimension,
+        }).await
+    }
+
+    pub async fn delete_collection(&self, name: &str) -> Result<(), EdgeError> {
+        let mut collections = self.collections.write().await;
+        
+        let freed = collections.get(name)
+            .ok_or(EdgeError::CollectionNotFound(name.to_string()))?
+            .memory_bytes();
+
+        self.commit(&mut collections, EdgeOp::DeleteCollection { name: name.to_string() }).await?;
+        self.memory_bytes.fetch_sub(freed, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    pub async fn list_collections(&self) -> Vec<String> {
+        let collections = self.collections.read().await;
+        collections.keys().cloned().collect()
+    }
+
+    pub async fn insert(&self, collection: &str, id: &str, vector: Vec<f32>, metadata: Option<serde_json::Value>) -> Result<(), EdgeError> {
+        let mut collections = self.collections.write().await;
+        
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+
+        // Without new metadata an overwrite keeps the existing metadata
+        let change = SyncChange {
+            id: id.to_string(),
+            vector: Some(vector),
+            metadata: metadata.or_else(|| coll.metadata.get(id).cloned()),
+            version: SyncVersion::next(&self.node_id, coll.changes.version(id)),
+        };
+
+        self.commit_change(&mut collections, collection, change, true).await
+    }
+
+    /// Applies an insert (`vector` set) or delete, enforcing the dimension and
+    /// memory budget.
+    async fn commit_change(
+        &self,
+        collections: &mut std::collections::HashMap<String, EdgeCollection>,
+        collection: &str,
+        change: SyncChange,
+        local: bool,
+    ) -> Result<(), EdgeError> {
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+
+        let SyncChange { id, vector, metadata, version } = change;
+        let previous = coll.vectors.get(&id)
+            .map(|old| entry_memory_bytes(&id, old, coll.metadata.get(&id)))
+            .unwrap_or(0);
+
+        let Some(vector) = vector else {
+            self.commit(collections, EdgeOp::Delete {
+                collection: collection.to_string(),
+                id,
+                version,
+                local,
+            }).await?;
+            self.memory_bytes.fetch_sub(previous, Ordering::SeqCst);
+            return Ok(());
+        };
+
+        if vector.len() != coll.dimension {
+            return Err(EdgeError::InvalidDimension(format!(
+                "Expected {}, got {}",
+                coll.dimension,
+                vector.len()
+            )));
+        }
+
+        let needed = entry_memory_bytes(&id, &vector, metadata.as_ref());
+        let used = self.memory_bytes.load(Ordering::SeqCst) - previous;
+        if used + needed > self.config.max_memory_mb * 1024 * 1024 {
+            return Err(EdgeError::OutOfMemory);
+        }
+
+        self.commit(collections, EdgeOp::Insert {
+            collection: collection.to_string(),
+            id,
+            vector,
+            metadata: metadata.map(|m| m.to_string()),
+            version,
+            local,
+        }).await?;
+        self.memory_bytes.store(used + needed, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    pub async fn search(&self, collection: &str, query: &[f32], k: usize) -> Result<Vec<EdgeSearchResult>, EdgeError> {
+        let collections = self.collections.read().await;
+        
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+
+        let mut results: Vec<EdgeSearchResult> = coll.vectors
+            .iter()
+            .map(|(id, vector)| {
+                let distance = cosine_distance(query, vector);
+                EdgeSearchResult {
+                    id: id.clone(),
+                    distance,
+                }
+            })
+            .collect();
+
+        results.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        results.truncate(k);
+
+        Ok(results)
+    }
+
+    pub async fn get(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, EdgeError> {
+        let collections = self.collections.read().await;
+        
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+
+        if let Some(vector) = coll.vectors.get(id) {
+            let metadata = coll.metadata.get(id).cloned().unwrap_or(serde_json::json!({}));
+            Ok(Some((vector.clone(), metadata)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn delete(&self, collection: &str, id: &str) -> Result<bool, EdgeError> {
+        let mut collections = self.collections.write().await;
+        
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+
+        if !coll.vectors.contains_key(id) {
+            return Ok(false);
+        }
+
+        // The tombstone keeps the delete from losing to older copies during sync
+        let change = SyncChange {
+            id: id.to_string(),
+            vector: None,
+            metadata: None,
+            version: SyncVersion::next(&self.node_id, coll.changes.version(id)),
+        };
+        self.commit_change(&mut collections, collection, change, true).await?;
+
+        Ok(true)
+    }
+
+    /// Applies a change pulled from the server unless the local copy is at
+    /// least as new. Returns whether it was applied.
+    pub(crate) async fn apply_remote(&self, collection: &str, change: SyncChange) -> Result<bool, EdgeError> {
+        let mut collections = self.collections.write().await;
+
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+        if coll.changes.version(&change.id).is_some_and(|current| *current >= change.version) {
+            return Ok(false);
+        }
+
+        self.commit_change(&mut collections, collection, change, false).await?;
+        Ok(true)
+    }
+
+    /// Local changes after sequence `after`, oldest first, tagged with their sequence.
+    pub(crate) async fn local_changes(&self, collection: &str, after: u64, limit: usize) -> Result<Vec<(u64, SyncChange)>, EdgeError> {
+        let collections = self.collections.read().await;
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+
+        Ok(coll.changes.local_since(after, limit)
+            .into_iter()
+            .map(|(id, entry)| {
+                let vector = coll.vectors.get(id).filter(|_| !entry.deleted);
+                (entry.sequence, SyncChange {
+                    id: id.clone(),
+                    vector: vector.cloned(),
+                    metadata: vector.and_then(|_| coll.metadata.get(id).cloned()),
+                    version: entry.version.clone(),
+                })
+            })
+            .collect())
+    }
+
+    /// Returns the collection's dimension and sync progress.
+    pub(crate) async fn sync_cursor(&self, collection: &str) -> Result<(usize, SyncCursor), EdgeError> {
+        let collections = self.collections.read().await;
+        let coll = collections.get(collection)
+            .ok_or(EdgeError::CollectionNotFound(collection.to_string()))?;
+        Ok((coll.dimension, coll.changes.cursor.clone()))
+    }
+
+    pub(crate) async fn set_sync_cursor(&self, collection: &str, cursor: SyncCursor) -> Result<(), EdgeError> {
+        let mut collections = self.collections.write().await;
+        if !collections.contains_key(collection) {
+            return Err(EdgeError::CollectionNotFound(collection.to_string()));
+        }
+        self.commit(&mut collections, EdgeOp::SyncCursor {
+            collection: collection.to_string(),
+            cursor,
+        }).await
+    }
+
+    pub async fn get_stats(&self) -> EdgeStats {
+        let collections = self.collections.read().await;
+        
+        let total_vectors = collections.values().map(|coll| coll.vectors.len()).sum();
+        let disk_usage_bytes = self.storage.lock().await
+            .as_ref()
+            .map(|storage| storage.disk_usage() as usize)
+            .unwrap_or(0);
+
+        EdgeStats {
+            collection_count: collections.len(),
+            total_vectors,
+            memory_usage_bytes: self.memory_bytes.load(Ordering::SeqCst),
+            disk_usage_bytes,
+        }
+    }
+
+    /// Forces every logged change to stable storage.
+    pub async fn flush(&self) -> Result<(), EdgeError> {
+        if let Some(storage) = self.storage.lock().await.as_mut() {
+            storage.sync().await?;
+        }
+        Ok(())
+    }
+
+    /// Folds the log into a fresh snapshot so the next `init` starts fast.
+    pub async fn close(&self) -> Result<(), EdgeError> {
+        let collections = self.collections.read().await;
+        if let Some(storage) = self.storage.lock().await.as_mut() {
+            storage.checkpoint(&collections).await?;
+        }
+        Ok(())
+    }
+
+    /// Encodes every collection in the on-disk snapshot format, for hosts
+    /// without a filesystem that persist the bytes themselves.
+    pub async fn snapshot(&self) -> Result<Vec<u8>, EdgeError> {
+        let collections = self.collections.read().await;
+        storage::encode_snapshot(0, &collections, false, None)
+    }
+
+    /// Opens an in-memory database holding the collections of `snapshot`.
+    pub fn from_snapshot(config: EdgeConfig, snapshot: &[u8]) -> Result<Self, EdgeError> {
+        let (_, recovered) = storage::decode_snapshot(snapshot, None)?;
+        let db = Self::in_memory_with_config(config);
+        db.memory_bytes.store(recovered.values().map(EdgeCollection::memory_bytes).sum(), Ordering::SeqCst);
+        *db.collections.try_write().expect("new database is unshared") = recovered;
+        Ok(db)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EdgeSearchResult {
+    pub id: String,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct EdgeStats {
+    pub collection_count: usize,
+    pub total_vectors: usize,
+    pub memory_usage_bytes: usize,
+    pub disk_usage_bytes: usize,
+}
+
+#[derive(Debug)]
+pub enum EdgeError {
+    CollectionNotFound(String),
+    CollectionExists(String),
+    InvalidDimension(String),
+    IoError(String),
+    OutOfMemory,
+    DiskQuotaExceeded,
+    Corrupted(String),
+    EncryptionError(String),
+    SyncError(String),
+}
+
+impl std::fmt::Display for EdgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgeError::CollectionNotFound(name) => {
+                write!(f, "Collection not found: {}", name)
+            },
+            EdgeError::CollectionExists(name) => {
+                write!(f, "Collection already exists: {}", name)
+            },
+            EdgeError::InvalidDimension(msg) => {
+                write!(f, "Invalid dimension: {}", msg)
+            },
+            EdgeError::IoError(msg) => {
+                write!(f, "IO error: {}", msg)
+            },
+            EdgeError::OutOfMemory => {
+                write!(f, "Out of memory")
+            },
+            EdgeError::DiskQuotaExceeded => {
+                write!(f, "Disk quota exceeded")
+            },
+            EdgeError::Corrupted(msg) => {
+                write!(f, "Corrupted data: {}", msg)
+            },
+            EdgeError::EncryptionError(msg) => {
+                write!(f, "Encryption error: {}", msg)
+            },
+            EdgeError::SyncError(msg) => {
+                write!(f, "Sync error: {}", msg)
+            },
+        }
+    }
+}
+
+impl std::error::Error for EdgeError {}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    1.0 - (dot / (norm_a * norm_b))
+}
+
+#[cfg(feature = "embedded")]
+pub mod embedded {
+    use super::*;
+    
+    pub type CortexDb = EdgeDB;
+    
+    pub fn new_embedded() -> CortexDb {
+        EdgeDB::in_memory()
+    }
+    
+    pub fn new_embedded_with_config(config: EdgeConfig) -> CortexDb {
+        EdgeDB::with_config(config)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn persistent_db(dir: &Path, config: EdgeConfig) -> EdgeDB {
+        EdgeDB::with_config(config).with_data_dir(dir.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_edge_db_survives_reopen() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        {
+            let db = persistent_db(temp_dir.path(), EdgeConfig::default());
+            db.init().await.unwrap();
+            db.create_collection("memories", 3).await.unwrap();
+            db.insert("memories", "a", vec![1.0, 0.0, 0.0], Some(serde_json::json!({"room": "kitchen"}))).await.unwrap();
+            db.insert("memories", "b", vec![0.0, 1.0, 0.0], None).await.unwrap();
+            db.delete("memories", "b").await.unwrap();
+            db.flush().await.unwrap();
+        }
+
+        let db = persistent_db(temp_dir.path(), EdgeConfig::default());
+        db.init().await.unwrap();
+
+        let (vector, metadata) = db.get("memories", "a").await.unwrap().unwrap();
+        assert_eq!(vector, vec![1.0, 0.0, 0.0]);
+        assert_eq!(metadata["room"], "kitchen");
+        assert!(db.get("memories", "b").await.unwrap().is_none());
+
+        // A checkpointed store reopens to the same state
+        db.close().await.unwrap();
+        let db = persistent_db(temp_dir.path(), EdgeConfig::default());
+        db.init().await.unwrap();
+        let stats = db.get_stats().await;
+        assert_eq!(stats.total_vectors, 1);
+        assert!(stats.disk_usage_bytes > 0);
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_edge_db_encrypted_compressed_reopen() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let key = [7u8; 32];
+        let config = EdgeConfig::default().with_compression(true);
+
+        {
+            let db = persistent_db(temp_dir.path(), config.clone()).with_encryption_key(&key);
+            db.init().await.unwrap();
+            db.create_collection("c", 2).await.unwrap();
+            db.insert("c", "x", vec![0.5, 0.5], None).await.unwrap();
+            db.close().await.unwrap();
+            db.insert("c", "y", vec![0.1, 0.9], None).await.unwrap();
+            db.flush().await.unwrap();
+        }
+
+        let db = persistent_db(temp_dir.path(), config.clone()).with_encryption_key(&key);
+        db.init().await.unwrap();
+        assert_eq!(db.get_stats().await.total_vectors, 2);
+
+        let wrong = persistent_db(temp_dir.path(), config.clone()).with_encryption_key(&[8u8; 32]);
+        assert!(matches!(wrong.init().await, Err(EdgeError::EncryptionError(_))));
+
+        let missing = persistent_db(temp_dir.path(), config.with_encryption(true));
+        assert!(matches!(missing.init().await, Err(EdgeError::EncryptionError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_edge_db_snapshot_round_trip() {
+        let db = EdgeDB::in_memory();
+        db.create_collection("c", 2).await.unwrap();
+        db.insert("c", "x", vec![0.5, 0.5], Some(serde_json::json!({"tag": "x"}))).await.unwrap();
+
+        let restored = EdgeDB::from_snapshot(EdgeConfig::default(), &db.snapshot().await.unwrap()).unwrap();
+        let (vector, metadata) = restored.get("c", "x").await.unwrap().unwrap();
+        assert_eq!(vector, vec![0.5, 0.5]);
+        assert_eq!(metadata["tag"], "x");
+        assert!(restored.get_stats().await.memory_usage_bytes > 0);
+
+        assert!(matches!(EdgeDB::from_snapshot(EdgeConfig::default(), b"garbage"), Err(EdgeError::Corrupted(_))));
+    }
+
+    #[tokio::test]
+    async fn test_edge_db_memory_budget() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = persistent_db(temp_dir.path(), EdgeConfig::default().with_max_memory(1));
+        db.init().await.unwrap();
+        db.create_collection("c", 1024).await.unwrap();
+
+        let mut inserted = 0;
+        let result = loop {
+            match db.insert("c", &inserted.to_string(), vec![0.0; 1024], None).await {
+                Ok(()) => inserted += 1,
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(result, EdgeError::OutOfMemory));
+        assert!(inserted > 0 && inserted < 256);
+
+        // Freeing an entry makes room again
+        db.delete("c", "0").await.unwrap();
+        db.insert("c", "again", vec![0.0; 1024], None).await.unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! Durable change log of the sync server
+//! `sync.log` holds one JSON record per line: the server's epoch, the
+//! version of every entry edges can pull, and how far into the database
+//! journal the server has versioned writes made outside sync. Superseded
+//! records are dropped when the log is opened.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use super::sync::SyncVersion;
+use super::EdgeError;
+use crate::coretex_journal::{append_json_lines, read_json_lines, write_json_lines};
+
+const LOG_FILE: &str = "sync.log";
+
+/// Versions of one collection's entries, in the order edges pull them.
+#[derive(Default)]
+pub(super) struct ServerLog {
+    pub(super) sequence: u64,
+    pub(super) entries: HashMap<String, ServerEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct ServerEntry {
+    pub(super) version: SyncVersion,
+    pub(super) sequence: u64,
+    pub(super) deleted: bool,
+    /// The journal entry that holds the write, 0 if it needed none.
+    pub(super) lsn: u64,
+}
+
+impl ServerLog {
+    pub(super) fn record(&mut self, id: &str, version: SyncVersion, deleted: bool, lsn: u64) -> ServerEntry {
+        self.sequence += 1;
+        let entry = ServerEntry {
+            version,
+            sequence: self.sequence,
+            deleted,
+            lsn,
+        };
+        self.entries.insert(id.to_string(), entry.clone());
+        entry
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) enum LogRecord {
+    Epoch(String),
+    Entry { collection: String, id: String, entry: ServerEntry },
+    /// Journal entries up to `lsn` are versioned.
+    Journal { lsn: u64 },
+}
+
+/// What the server knew when it stopped.
+pub(super) struct Recovered {
+    pub(super) epoch: String,
+    pub(super) journal_lsn: u64,
+    pub(super) logs: HashMap<String, ServerLog>,
+}
+
+pub(super) struct ServerLogFile {
+    path: PathBuf,
+}
+
+impl ServerLogFile {
+    /// Opens the log in `dir`, starting a new epoch if there is none yet.
+    pub(super) fn open(dir: &Path) -> Result<(Self, Recovered), EdgeError> {
+        fs::create_dir_all(dir).map_err(io_error)?;
+        let file = Self { path: dir.join(LOG_FILE) };
+
+        let mut recovered = Recovered {
+            epoch: String::new(),
+            journal_lsn: 0,
+            logs: HashMap::new(),
+        };
+        let (records, _) = read_json_lines::<LogRecord>(&file.path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::InvalidData => EdgeError::Corrupted(format!("sync log {}", e)),
+            _ => io_error(e),
+        })?;
+        for record in records {
+            match record {
+                LogRecord::Epoch(epoch) => recovered.epoch = epoch,
+                LogRecord::Journal { lsn } => recovered.journal_lsn = recovered.journal_lsn.max(lsn),
+                LogRecord::Entry { collection, id, entry } => {
+                    let log = recovered.logs.entry(collection).or_default();
+                    log.sequence = log.sequence.max(entry.sequence);
+                    log.entries.insert(id, entry);
+                }
+            }
+        }
+        if recovered.epoch.is_empty() {
+            recovered.epoch = uuid::Uuid::new_v4().to_string();
+        }
+
+        // Also drops a torn final line, so appends start on a line of their own
+        let mut records = vec![
+            LogRecord::Epoch(recovered.epoch.clone()),
+            LogRecord::Journal { lsn: recovered.journal_lsn },
+        ];
+        for (collection, log) in &recovered.logs {
+            records.extend(log.entries.iter().map(|(id, entry)| LogRecord::Entry {
+                collection: collection.clone(),
+                id: id.clone(),
+                entry: entry.clone(),
+            }));
+        }
+        file.rewrite(&records)?;
+        Ok((file, recovered))
+    }
+
+    /// Returns once the records are on disk.
+    pub(super) fn append(&self, records: &[LogRecord]) -> Result<(), EdgeError> {
+        append_json_lines(&self.path, records).map_err(io_error)
+    }
+
+    fn rewrite(&self, records: &[LogRecord]) -> Result<(), EdgeError> {
+        write_json_lines(&self.path, records).map_err(io_error)
+    }
+}
+
+fn io_error(e: std::io::Error) -> EdgeError {
+    EdgeError::IoError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn version(origin: &str, timestamp_ms: i64) -> SyncVersion {
+        SyncVersion { timestamp_ms, origin: origin.to_string() }
+    }
+
+    #[test]
+    fn test_server_log_keeps_latest_versions_and_epoch() {
+        let dir = tempfile::tempdir().unwrap();
+        let epoch = {
+            let (file, recovered) = ServerLogFile::open(dir.path()).unwrap();
+            let mut log = ServerLog::default();
+            let first = log.record("a", version("robot", 1), false, 3);
+            let second = log.record("a", version("server", 2), true, 4);
+            file.append(&[
+                LogRecord::Entry { collection: "docs".to_string(), id: "a".to_string(), entry: first },
+                LogRecord::Entry { collection: "docs".to_string(), id: "a".to_string(), entry: second },
+                LogRecord::Journal { lsn: 4 },
+            ]).unwrap();
+            recovered.epoch
+        };
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        file.write_all(b"{\"Journal\":{\"ls").unwrap();
+
+        let (file, recovered) = ServerLogFile::open(dir.path()).unwrap();
+        assert_eq!(recovered.epoch, epoch);
+        assert_eq!(recovered.journal_lsn, 4);
+        let log = &recovered.logs["docs"];
+        assert_eq!(log.sequence, 2);
+        assert!(log.entries["a"].deleted);
+        assert_eq!(log.entries["a"].version, version("server", 2));
+
+        // Appends after the torn line survive the next open
+        file.append(&[LogRecord::Journal { lsn: 9 }]).unwrap();
+        let (_, recovered) = ServerLogFile::open(dir.path()).unwrap();
+        assert_eq!(recovered.journal_lsn, 9);
+    }
+}
@@ -0,0 +1,492 @@
+//! On-disk format for EdgeDB
+//! A snapshot of every collection plus an append-only log of the changes made
+//! since that snapshot. Recovery loads the snapshot and replays the log.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::sync::{ChangeLog, SyncCursor, SyncVersion};
+use super::{EdgeCollection, EdgeError};
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"CTXEDGE1";
+const SNAPSHOT_FILE: &str = "edge.snapshot";
+const WAL_FILE: &str = "edge.wal";
+/// Log records are `u32 length`, `u32 crc32`, payload.
+const RECORD_HEADER_LEN: u64 = 8;
+#[cfg(feature = "compression")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// One durable change. Metadata is kept as JSON text because bincode cannot
+/// round-trip `serde_json::Value`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum EdgeOp {
+    CreateCollection { name: String, dimension: usize },
+    DeleteCollection { name: String },
+    /// `local` is false for changes pulled from the server, which are never
+    /// pushed back.
+    Insert { collection: String, id: String, vector: Vec<f32>, metadata: Option<String>, version: SyncVersion, local: bool },
+    Delete { collection: String, id: String, version: SyncVersion, local: bool },
+    /// Records how far a collection has been pushed to and pulled from the server.
+    SyncCursor { collection: String, cursor: SyncCursor },
+}
+
+impl EdgeOp {
+    /// Applies the op to in-memory collections. Ops that no longer fit the
+    /// state (e.g. an insert into a dropped collection) are ignored.
+    pub(crate) fn apply(self, collections: &mut HashMap<String, EdgeCollection>) {
+        match self {
+            EdgeOp::CreateCollection { name, dimension } => {
+                collections.entry(name.clone()).or_insert_with(|| EdgeCollection::new(&name, dimension));
+            }
+            EdgeOp::DeleteCollection { name } => {
+                collections.remove(&name);
+            }
+            EdgeOp::Insert { collection, id, vector, metadata, version, local } => {
+                if let Some(coll) = collections.get_mut(&collection) {
+                    if vector.len() != coll.dimension {
+                        return;
+                    }
+                    coll.changes.record(&id, version, false, local);
+                    match metadata.and_then(|m| serde_json::from_str(&m).ok()) {
+                        Some(metadata) => coll.metadata.insert(id.clone(), metadata),
+                        None => coll.metadata.remove(&id),
+                    };
+                    coll.vectors.insert(id, vector);
+                }
+            }
+            EdgeOp::Delete { collection, id, version, local } => {
+                if let Some(coll) = collections.get_mut(&collection) {
+                    coll.changes.record(&id, version, true, local);
+                    coll.vectors.remove(&id);
+                    coll.metadata.remove(&id);
+                }
+            }
+            EdgeOp::SyncCursor { collection, cursor } => {
+                if let Some(coll) = collections.get_mut(&collection) {
+                    coll.changes.cursor = cursor;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+    /// Last log sequence number contained in the snapshot.
+    sequence: u64,
+    compression: Option<String>,
+    /// AES-256-GCM; the nonce prefixes the payload.
+    encrypted: bool,
+    /// SHA-256 of the payload as stored.
+    checksum: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredCollection {
+    name: String,
+    dimension: usize,
+    entries: Vec<(String, Vec<f32>, Option<String>)>,
+    changes: ChangeLog,
+}
+
+pub(crate) struct EdgeStorage {
+    dir: PathBuf,
+    /// Only written to by `blocking` closures.
+    wal: Arc<Mutex<File>>,
+    wal_len: u64,
+    snapshot_len: u64,
+    sequence: u64,
+    compress: bool,
+    cipher: Option<Aes256Gcm>,
+    max_disk_bytes: u64,
+    checkpoint_bytes: u64,
+}
+
+impl EdgeStorage {
+    /// Opens `dir`, loading the snapshot and replaying the log. A torn record
+    /// at the end of the log, left by a crash mid-write, is cut off.
+    pub(crate) fn open(
+        dir: &Path,
+        compress: bool,
+        key: Option<&[u8]>,
+        max_disk_bytes: u64,
+    ) -> Result<(Self, HashMap<String, EdgeCollection>), EdgeError> {
+        std::fs::create_dir_all(dir).map_err(io_error)?;
+
+        if compress && !cfg!(feature = "compression") {
+            return Err(EdgeError::IoError("compression requires the `compression` feature".to_string()));
+        }
+        let cipher = match key {
+            Some(key) if key.len() == 32 => Some(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))),
+            Some(key) => return Err(EdgeError::EncryptionError(format!("key must be 256 bits, got {}", key.len() * 8))),
+            None => None,
+        };
+
+        let snapshot_path = dir.join(SNAPSHOT_FILE);
+        let (mut sequence, mut collections, snapshot_len) = if snapshot_path.exists() {
+            let data = std::fs::read(&snapshot_path).map_err(io_error)?;
+            let (sequence, collections) = decode_snapshot(&data, cipher.as_ref())?;
+            (sequence, collections, data.len() as u64)
+        } else {
+            (0, HashMap::new(), 0)
+        };
+
+        let mut wal = OpenOptions::new().read(true).write(true).create(true).truncate(false)
+            .open(dir.join(WAL_FILE))
+            .map_err(io_error)?;
+        let mut log = Vec::new();
+        wal.read_to_end(&mut log).map_err(io_error)?;
+
+        let mut offset = 0usize;
+        while let Some((payload, next)) = next_record(&log, offset) {
+            let (op_sequence, op) = decode_op(payload, cipher.as_ref())?;
+            // Ops already folded into the snapshot are skipped; a crash between
+            // writing the snapshot and truncating the log leaves them behind
+            if op_sequence > sequence {
+                op.apply(&mut collections);
+                sequence = op_sequence;
+            }
+            offset = next;
+        }
+
+        if offset < log.len() {
+            wal.set_len(offset as u64).map_err(io_error)?;
+            wal.sync_all().map_err(io_error)?;
+        }
+        wal.seek(SeekFrom::Start(offset as u64)).map_err(io_error)?;
+
+        let storage = Self {
+            dir: dir.to_path_buf(),
+            wal: Arc::new(Mutex::new(wal)),
+            wal_len: offset as u64,
+            snapshot_len,
+            sequence,
+            compress,
+            cipher,
+            max_disk_bytes,
+            checkpoint_bytes: (max_disk_bytes / 4).clamp(64 * 1024, 16 * 1024 * 1024),
+        };
+        Ok((storage, collections))
+    }
+
+    pub(crate) fn disk_usage(&self) -> u64 {
+        self.snapshot_len + self.wal_len
+    }
+
+    /// Logs `op` before the caller applies it. When the log would push the
+    /// store over its disk budget the state is checkpointed first, and the op
+    /// is refused if that does not free enough space.
+    pub(crate) async fn append(&mut self, op: &EdgeOp, collections: &HashMap<String, EdgeCollection>) -> Result<(), EdgeError> {
+        let payload = encode_op(self.sequence + 1, op, self.cipher.as_ref())?;
+        let record_len = RECORD_HEADER_LEN + payload.len() as u64;
+
+        if self.disk_usage() + record_len > self.max_disk_bytes {
+            self.checkpoint(collections).await?;
+            if self.disk_usage() + record_len > self.max_disk_bytes {
+                return Err(EdgeError::DiskQuotaExceeded);
+            }
+        }
+
+        let mut record = Vec::with_capacity(record_len as usize);
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+
+        let wal = self.wal.clone();
+        let wal_len = self.wal_len;
+        blocking(move || {
+            let mut wal = wal.lock().unwrap();
+            if let Err(e) = wal.write_all(&record) {
+                // Drop a partial record so the next append starts on a boundary
+                wal.set_len(wal_len).ok();
+                wal.seek(SeekFrom::Start(wal_len)).ok();
+                return Err(io_error(e));
+            }
+            Ok(())
+        }).await?;
+
+        self.wal_len += record_len;
+        self.sequence += 1;
+        Ok(())
+    }
+
+    /// Checkpoints once the log has grown past a quarter of the disk budget
+    /// (between 64 KiB and 16 MiB).
+    pub(crate) async fn maybe_checkpoint(&mut self, collections: &HashMap<String, EdgeCollection>) -> Result<(), EdgeError> {
+        if self.wal_len > self.checkpoint_bytes {
+            self.checkpoint(collections).await?;
+        }
+        Ok(())
+    }
+
+    /// Forces logged changes to disk.
+    pub(crate) async fn sync(&mut self) -> Result<(), EdgeError> {
+        let wal = self.wal.clone();
+        blocking(move || wal.lock().unwrap().sync_data().map_err(io_error)).await
+    }
+
+    /// Writes a fresh snapshot next to the old one, renames it into place and
+    /// then empties the log.
+    pub(crate) async fn checkpoint(&mut self, collections: &HashMap<String, EdgeCollection>) -> Result<(), EdgeError> {
+        let data = encode_snapshot(self.sequence, collections, self.compress, self.cipher.as_ref())?;
+        let snapshot_len = data.len() as u64;
+
+        let dir = self.dir.clone();
+        let wal = self.wal.clone();
+        blocking(move || {
+            let path = dir.join(SNAPSHOT_FILE);
+            let tmp_path = dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+            {
+                let mut file = File::create(&tmp_path).map_err(io_error)?;
+                file.write_all(&data).map_err(io_error)?;
+                file.sync_all().map_err(io_error)?;
+            }
+            std::fs::rename(&tmp_path, &path).map_err(io_error)?;
+            // Persist the rename itself; not every platform can open a directory
+            if let Ok(dir) = File::open(&dir) {
+                dir.sync_all().ok();
+            }
+
+            let mut wal = wal.lock().unwrap();
+            wal.set_len(0).map_err(io_error)?;
+            wal.seek(SeekFrom::Start(0)).map_err(io_error)?;
+            wal.sync_all().map_err(io_error)
+        }).await?;
+
+        self.snapshot_len = snapshot_len;
+        self.wal_len = 0;
+        Ok(())
+    }
+}
+
+/// Runs file I/O on the blocking pool when called inside a tokio runtime,
+/// so it never stalls the runtime's workers; inline otherwise, e.g. under
+/// the C API's executor or on wasm32, which has neither.
+pub(crate) async fn blocking<T: Send + 'static>(io: impl FnOnce() -> Result<T, EdgeError> + Send + 'static) -> Result<T, EdgeError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+        return runtime.spawn_blocking(io).await
+            .map_err(|e| EdgeError::IoError(e.to_string()))?;
+    }
+    io()
+}
+
+fn io_error(e: std::io::Error) -> EdgeError {
+    EdgeError::IoError(e.to_string())
+}
+
+fn next_record(log: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let header_end = offset + RECORD_HEADER_LEN as usize;
+    if header_end > log.len() {
+        return None;
+    }
+    let len = u32::from_le_bytes(log[offset..offset + 4].try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(log[offset + 4..header_end].try_into().ok()?);
+    let payload = log.get(header_end..header_end + len)?;
+    if crc32(payload) != crc {
+        return None;
+    }
+    Some((payload, header_end + len))
+}
+
+fn encode_op(sequence: u64, op: &EdgeOp, cipher: Option<&Aes256Gcm>) -> Result<Vec<u8>, EdgeError> {
+    let plain = bincode::serialize(&(sequence, op)).map_err(|e| EdgeError::IoError(e.to_string()))?;
+    match cipher {
+        Some(cipher) => seal(cipher, &plain),
+        None => Ok(plain),
+    }
+}
+
+fn decode_op(payload: &[u8], cipher: Option<&Aes256Gcm>) -> Result<(u64, EdgeOp), EdgeError> {
+    let plain = match cipher {
+        Some(cipher) => open_sealed(cipher, payload)?,
+        None => payload.to_vec(),
+    };
+    bincode::deserialize(&plain).map_err(|e| EdgeError::Corrupted(format!("log record: {}", e)))
+}
+
+/// Nonce followed by ciphertext.
+fn seal(cipher: &Aes256Gcm, plain: &[u8]) -> Result<Vec<u8>, EdgeError> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(cipher.encrypt(Nonce::from_slice(&nonce), plain)
+        .map_err(|e| EdgeError::EncryptionError(e.to_string()))?);
+    Ok(sealed)
+}
+
+fn open_sealed(cipher: &Aes256Gcm, sealed: &[u8]) -> Result<Vec<u8>, EdgeError> {
+    if sealed.len() < 12 {
+        return Err(EdgeError::Corrupted("sealed record too short".to_string()));
+    }
+    cipher.decrypt(Nonce::from_slice(&sealed[..12]), &sealed[12..])
+        .map_err(|_| EdgeError::EncryptionError("wrong key or tampered data".to_string()))
+}
+
+/// Layout: magic, u32 LE header length, JSON header, payload.
+pub(crate) fn encode_snapshot(
+    sequence: u64,
+    collections: &HashMap<String, EdgeCollection>,
+    compress: bool,
+    cipher: Option<&Aes256Gcm>,
+) -> Result<Vec<u8>, EdgeError> {
+    let stored: Vec<StoredCollection> = collections.values()
+        .map(|coll| StoredCollection {
+            name: coll.name.clone(),
+            dimension: coll.dimension,
+            entries: coll.vectors.iter()
+                .map(|(id, vector)| (id.clone(), vector.clone(), coll.metadata.get(id).map(|m| m.to_string())))
+                .collect(),
+            changes: coll.changes.clone(),
+        })
+        .collect();
+    let mut payload = bincode::serialize(&stored).map_err(|e| EdgeError::IoError(e.to_string()))?;
+
+    let compression = if compress {
+        payload = compress_payload(&payload)?;
+        Some("zstd".to_string())
+    } else {
+        None
+    };
+
+    if let Some(cipher) = cipher {
+        payload = seal(cipher, &payload)?;
+    }
+
+    let header = SnapshotHeader {
+        version: 1,
+        sequence,
+        compression,
+        encrypted: cipher.is_some(),
+        checksum: hex::encode(Sha256::digest(&payload)),
+    };
+    let header = serde_json::to_vec(&header).map_err(|e| EdgeError::IoError(e.to_string()))?;
+
+    let mut data = Vec::with_capacity(12 + header.len() + payload.len());
+    data.extend_from_slice(SNAPSHOT_MAGIC);
+    data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    data.extend_from_slice(&header);
+    data.extend_from_slice(&payload);
+    Ok(data)
+}
+
+pub(crate) fn decode_snapshot(data: &[u8], cipher: Option<&Aes256Gcm>) -> Result<(u64, HashMap<String, EdgeCollection>), EdgeError> {
+    if data.len() < 12 || &data[..8] != SNAPSHOT_MAGIC {
+        return Err(EdgeError::Corrupted("not an EdgeDB snapshot".to_string()));
+    }
+    let header_len = u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize;
+    let header_bytes = data.get(12..12 + header_len)
+        .ok_or_else(|| EdgeError::Corrupted("truncated snapshot header".to_string()))?;
+    let header: SnapshotHeader = serde_json::from_slice(header_bytes)
+        .map_err(|e| EdgeError::Corrupted(format!("snapshot header: {}", e)))?;
+    let mut payload = data[12 + header_len..].to_vec();
+
+    if hex::encode(Sha256::digest(&payload)) != header.checksum {
+        return Err(EdgeError::Corrupted("snapshot checksum mismatch".to_string()));
+    }
+
+    if header.encrypted {
+        let cipher = cipher.ok_or_else(|| EdgeError::EncryptionError("snapshot is encrypted but no key was given".to_string()))?;
+        payload = open_sealed(cipher, &payload)?;
+    }
+
+    match header.compression.as_deref() {
+        Some("zstd") => payload = decompress_payload(&payload)?,
+        Some(other) => return Err(EdgeError::Corrupted(format!("unsupported compression '{}'", other))),
+        None => {}
+    }
+
+    let stored: Vec<StoredCollection> = bincode::deserialize(&payload)
+        .map_err(|e| EdgeError::Corrupted(format!("snapshot payload: {}", e)))?;
+
+    let mut collections = HashMap::new();
+    for stored in stored {
+        let mut coll = EdgeCollection::new(&stored.name, stored.dimension);
+        for (id, vector, metadata) in stored.entries {
+            if let Some(metadata) = metadata.and_then(|m| serde_json::from_str(&m).ok()) {
+                coll.metadata.insert(id.clone(), metadata);
+            }
+            coll.vectors.insert(id, vector);
+        }
+        coll.changes = stored.changes;
+        collections.insert(stored.name, coll);
+    }
+
+    Ok((header.sequence, collections))
+}
+
+#[cfg(feature = "compression")]
+fn compress_payload(data: &[u8]) -> Result<Vec<u8>, EdgeError> {
+    zstd::encode_all(data, ZSTD_LEVEL).map_err(|e| EdgeError::IoError(e.to_string()))
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress_payload(_data: &[u8]) -> Result<Vec<u8>, EdgeError> {
+    Err(EdgeError::IoError("built without the `compression` feature".to_string()))
+}
+
+#[cfg(feature = "compression")]
+fn decompress_payload(data: &[u8]) -> Result<Vec<u8>, EdgeError> {
+    zstd::decode_all(data).map_err(|e| EdgeError::Corrupted(e.to_string()))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress_payload(_data: &[u8]) -> Result<Vec<u8>, EdgeError> {
+    Err(EdgeError::IoError("built without the `compression` feature".to_string()))
+}
+
+/// CRC-32 (IEEE), bitwise; log records are small enough not to need a table.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[tokio::test]
+    async fn test_torn_log_tail_is_truncated() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let op = EdgeOp::CreateCollection { name: "c".to_string(), dimension: 2 };
+
+        {
+            let (mut storage, collections) = EdgeStorage::open(temp_dir.path(), false, None, u64::MAX).unwrap();
+            storage.append(&op, &collections).await.unwrap();
+            storage.sync().await.unwrap();
+        }
+
+        // Simulate a crash halfway through writing a second record
+        let wal_path = temp_dir.path().join(WAL_FILE);
+        let good_len = std::fs::metadata(&wal_path).unwrap().len();
+        let mut wal = OpenOptions::new().append(true).open(&wal_path).unwrap();
+        wal.write_all(&[200, 0, 0, 0, 1, 2, 3]).unwrap();
+
+        let (storage, collections) = EdgeStorage::open(temp_dir.path(), false, None, u64::MAX).unwrap();
+        assert!(collections.contains_key("c"));
+        assert_eq!(storage.wal_len, good_len);
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), good_len);
+    }
+}
@@ -0,0 +1,270 @@
+//! Edge-to-cloud synchronization
+//! Every entry carries a version (`timestamp`, `origin`); conflicting writes
+//! resolve last-writer-wins, with the origin breaking timestamp ties. Deletes
+//! leave tombstones so they win over older writes too.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{EdgeDB, EdgeError};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SyncVersion {
+    pub timestamp_ms: i64,
+    pub origin: String,
+}
+
+impl SyncVersion {
+    /// A version for a new write by `origin` that orders after `previous`,
+    /// even when the local clock is behind it.
+    pub fn next(origin: &str, previous: Option<&SyncVersion>) -> Self {
+        let now = chrono::Utc::now().timestamp_millis();
+        Self {
+            timestamp_ms: previous.map_or(now, |p| now.max(p.timestamp_ms + 1)),
+            origin: origin.to_string(),
+        }
+    }
+}
+
+/// One entry's latest state. `vector` is `None` for a delete.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncChange {
+    pub id: String,
+    pub vector: Option<Vec<f32>>,
+    pub metadata: Option<serde_json::Value>,
+    pub version: SyncVersion,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncRequest {
+    Handshake {
+        node_id: String,
+    },
+    Push {
+        node_id: String,
+        collection: String,
+        dimension: usize,
+        changes: Vec<SyncChange>,
+    },
+    /// Changes made on the server after `since`, excluding the caller's own.
+    Pull {
+        node_id: String,
+        collection: String,
+        since: u64,
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncResponse {
+    /// `epoch` changes whenever the server loses its change log, e.g. when a
+    /// server without a log directory restarts.
+    Handshake {
+        epoch: String,
+    },
+    /// `rejected` lists ids the server already holds a newer version of.
+    Push {
+        accepted: usize,
+        rejected: Vec<String>,
+    },
+    Pull {
+        changes: Vec<SyncChange>,
+        cursor: u64,
+        has_more: bool,
+    },
+}
+
+/// Per-entry versions of an edge collection plus its sync progress.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ChangeLog {
+    /// Sequence of the last change applied to the collection.
+    pub(crate) sequence: u64,
+    pub(crate) entries: HashMap<String, LoggedChange>,
+    pub(crate) cursor: SyncCursor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LoggedChange {
+    pub(crate) version: SyncVersion,
+    pub(crate) sequence: u64,
+    pub(crate) deleted: bool,
+    /// Written on this device rather than pulled from the server.
+    pub(crate) local: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SyncCursor {
+    /// Last local sequence acknowledged by the server.
+    pub(crate) pushed: u64,
+    /// Last server sequence pulled.
+    pub(crate) pulled: u64,
+    /// Server epoch the cursors refer to.
+    pub(crate) epoch: Option<String>,
+}
+
+impl ChangeLog {
+    pub(crate) fn record(&mut self, id: &str, version: SyncVersion, deleted: bool, local: bool) {
+        self.sequence += 1;
+        self.entries.insert(id.to_string(), LoggedChange {
+            version,
+            sequence: self.sequence,
+            deleted,
+            local,
+        });
+    }
+
+    pub(crate) fn version(&self, id: &str) -> Option<&SyncVersion> {
+        self.entries.get(id).map(|entry| &entry.version)
+    }
+
+    /// Local changes made after sequence `after`, oldest first.
+    pub(crate) fn local_since(&self, after: u64, limit: usize) -> Vec<(&String, &LoggedChange)> {
+        let mut changes: Vec<_> = self.entries.iter()
+            .filter(|(_, entry)| entry.local && entry.sequence > after)
+            .collect();
+        changes.sort_by_key(|(_, entry)| entry.sequence);
+        changes.truncate(limit);
+        changes
+    }
+}
+
+#[async_trait]
+pub trait SyncTransport: Send + Sync {
+    async fn exchange(&self, request: SyncRequest) -> Result<SyncResponse, EdgeError>;
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    pub collections: usize,
+    /// Local changes the server accepted.
+    pub pushed: usize,
+    /// Local changes the server already had newer versions of.
+    pub rejected: usize,
+    /// Server changes applied locally.
+    pub pulled: usize,
+}
+
+/// Pushes an `EdgeDB`'s local changes to a server and pulls the server's
+/// changes back, in batches. Progress is stored with the collection, so an
+/// interrupted sync resumes where it stopped.
+pub struct EdgeSyncClient {
+    db: Arc<EdgeDB>,
+    transport: Arc<dyn SyncTransport>,
+    batch_size: usize,
+    collections: Option<Vec<String>>,
+}
+
+impl EdgeSyncClient {
+    pub fn new(db: Arc<EdgeDB>, transport: Arc<dyn SyncTransport>) -> Self {
+        Self {
+            db,
+            transport,
+            batch_size: 256,
+            collections: None,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Limits sync to these collections; by default every local collection syncs.
+    pub fn with_collections(mut self, collections: Vec<String>) -> Self {
+        self.collections = Some(collections);
+        self
+    }
+
+    pub async fn sync(&self) -> Result<SyncReport, EdgeError> {
+        let epoch = match self.transport.exchange(SyncRequest::Handshake {
+            node_id: self.db.node_id().to_string(),
+        }).await? {
+            SyncResponse::Handshake { epoch } => epoch,
+            other => return Err(unexpected(&other)),
+        };
+
+        let collections = match &self.collections {
+            Some(collections) => collections.clone(),
+            None => self.db.list_collections().await,
+        };
+
+        let mut report = SyncReport::default();
+        for collection in collections {
+            self.sync_collection(&collection, &epoch, &mut report).await?;
+            report.collections += 1;
+        }
+        Ok(report)
+    }
+
+    async fn sync_collection(&self, collection: &str, epoch: &str, report: &mut SyncReport) -> Result<(), EdgeError> {
+        let node_id = self.db.node_id().to_string();
+        let (dimension, mut cursor) = self.db.sync_cursor(collection).await?;
+
+        // A server that lost its log may have lost our data too: start over
+        if cursor.epoch.as_deref() != Some(epoch) {
+            cursor = SyncCursor {
+                pushed: 0,
+                pulled: 0,
+                epoch: Some(epoch.to_string()),
+            };
+        }
+
+        loop {
+            let batch = self.db.local_changes(collection, cursor.pushed, self.batch_size).await?;
+            let Some(last) = batch.last().map(|(sequence, _)| *sequence) else {
+                break;
+            };
+
+            match self.transport.exchange(SyncRequest::Push {
+                node_id: node_id.clone(),
+                collection: collection.to_string(),
+                dimension,
+                changes: batch.into_iter().map(|(_, change)| change).collect(),
+            }).await? {
+                SyncResponse::Push { accepted, rejected } => {
+                    report.pushed += accepted;
+                    report.rejected += rejected.len();
+                }
+                other => return Err(unexpected(&other)),
+            }
+
+            cursor.pushed = last;
+            self.db.set_sync_cursor(collection, cursor.clone()).await?;
+        }
+
+        loop {
+            let (changes, next, has_more) = match self.transport.exchange(SyncRequest::Pull {
+                node_id: node_id.clone(),
+                collection: collection.to_string(),
+                since: cursor.pulled,
+                limit: self.batch_size,
+            }).await? {
+                SyncResponse::Pull { changes, cursor, has_more } => (changes, cursor, has_more),
+                other => return Err(unexpected(&other)),
+            };
+
+            for change in changes {
+                if self.db.apply_remote(collection, change).await? {
+                    report.pulled += 1;
+                }
+            }
+
+            cursor.pulled = next;
+            self.db.set_sync_cursor(collection, cursor.clone()).await?;
+            if !has_more {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn unexpected(response: &SyncResponse) -> EdgeError {
+    EdgeError::SyncError(format!("unexpected response: {:?}", response))
+}
@@ -2,6 +2,8 @@
 
 use std::error::Error;
 
+use crate::coretex_embedding::ImageEmbeddingService;
+
 #[derive(Debug, Clone)]
 pub struct VideoEmbeddingService {
     model_name: String,
@@ -55,6 +57,20 @@ impl VideoEmbeddingService {
         Ok(embedding)
     }
 
+    /// One embedding per sampled frame, every `frame_sample_rate`-th frame,
+    /// for multi-vector documents that keep the frames apart. Each frame is
+    /// embedded as the image it is.
+    pub fn embed_frames(&self, frames: &[Vec<u8>]) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+        let step = self.frame_sample_rate.max(1) as usize;
+        let images = ImageEmbeddingService::new(&self.model_name, self.dimension, &self.device);
+
+        frames
+            .iter()
+            .step_by(step)
+            .map(|frame| images.embed_image(frame))
+            .collect()
+    }
+
     pub fn embed_video_path(&self, path: &str) -> Result<Vec<f32>, Box<dyn Error + Send + Sync>> {
         let data = std::fs::read(path)?;
         
@@ -78,6 +94,16 @@ impl VideoEmbeddingService {
         self.embed_video(&frames)
     }
 
+    pub fn embed_frame_paths(&self, frame_paths: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+        let mut frames = Vec::new();
+
+        for path in frame_paths {
+            frames.push(std::fs::read(path)?);
+        }
+
+        self.embed_frames(&frames)
+    }
+
     pub fn get_dimension(&self) -> usize {
         self.dimension
     }
@@ -121,6 +147,25 @@ mod tests {
         assert!(norm > 0.0);
     }
 
+    #[test]
+    fn test_embed_frames() {
+        let service = VideoEmbeddingService::new("videoclip", 64, "cpu", 2);
+        let frames: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 100]).collect();
+
+        let embeddings = service.embed_frames(&frames).unwrap();
+
+        assert_eq!(embeddings.len(), 3);
+        for embedding in &embeddings {
+            assert_eq!(embedding.len(), 64);
+            let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+            assert!((norm - 1.0).abs() < 1e-5);
+        }
+        assert_ne!(embeddings[0], embeddings[1]);
+        // A sampled frame embeds as the same image would
+        let image = ImageEmbeddingService::new("videoclip", 64, "cpu").embed_image(&frames[2]).unwrap();
+        assert_eq!(embeddings[1], image);
+    }
+
     #[test]
     fn test_embed_video_empty() {
         let service = VideoEmbeddingService::with_defaults();
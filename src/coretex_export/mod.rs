@@ -135,6 +135,35 @@ impl VectorExporter {
 
 pub struct ParquetExporter;
 
+#[cfg(feature = "parquet")]
+impl ParquetExporter {
+    /// Writes one row per item. Object fields become columns: uniformly typed
+    /// scalars keep their type, equal-length numeric arrays become
+    /// fixed-size float lists and anything else is stored as JSON text.
+    pub fn export_simple<T: serde::Serialize + Send + Sync + 'static>(
+        data: &[T],
+        filename: &str,
+    ) -> Result<String, String> {
+        let values: Vec<serde_json::Value> = data.iter()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        let batch = json_record_batch(&values)?;
+
+        let props = parquet::file::properties::WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::ZSTD(Default::default()))
+            .build();
+        let file = File::create(filename).map_err(|e| e.to_string())?;
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), Some(props))
+            .map_err(|e| e.to_string())?;
+        writer.write(&batch).map_err(|e| e.to_string())?;
+        writer.close().map_err(|e| e.to_string())?;
+
+        Ok(filename.to_string())
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
 impl ParquetExporter {
     pub fn export_simple<T: serde::Serialize + Send + Sync + 'static>(
         _data: &[T],
@@ -144,6 +173,106 @@ impl ParquetExporter {
     }
 }
 
+#[cfg(feature = "parquet")]
+fn json_record_batch(values: &[serde_json::Value]) -> Result<arrow_array::RecordBatch, String> {
+    use arrow_array::{ArrayRef, StringArray};
+    use std::sync::Arc;
+
+    if values.iter().any(|v| !v.is_object()) {
+        let column: ArrayRef = Arc::new(values.iter().map(|v| Some(v.to_string())).collect::<StringArray>());
+        return arrow_array::RecordBatch::try_from_iter(vec![("value", column)]).map_err(|e| e.to_string());
+    }
+
+    let mut fields: Vec<&str> = Vec::new();
+    for value in values {
+        for key in value.as_object().into_iter().flat_map(|o| o.keys()) {
+            if !fields.contains(&key.as_str()) {
+                fields.push(key);
+            }
+        }
+    }
+
+    let columns: Vec<(&str, ArrayRef)> = fields.into_iter()
+        .map(|field| {
+            let column: Vec<Option<&serde_json::Value>> = values.iter()
+                .map(|v| v.get(field).filter(|v| !v.is_null()))
+                .collect();
+            let array = json_scalar_column(&column)
+                .or_else(|| json_vector_column(&column))
+                .unwrap_or_else(|| Arc::new(column.iter().map(|v| v.map(|v| v.to_string())).collect::<StringArray>()));
+            (field, array)
+        })
+        .collect();
+
+    arrow_array::RecordBatch::try_from_iter(columns).map_err(|e| e.to_string())
+}
+
+/// Builds a typed column when every present value is a string, an integer,
+/// a number or a bool. Returns `None` for mixed or nested values.
+#[cfg(feature = "parquet")]
+pub(crate) fn json_scalar_column(values: &[Option<&serde_json::Value>]) -> Option<arrow_array::ArrayRef> {
+    use arrow_array::{BooleanArray, Float64Array, Int64Array, StringArray};
+    use std::sync::Arc;
+
+    let present: Vec<&serde_json::Value> = values.iter().flatten().copied().collect();
+    if present.is_empty() {
+        return None;
+    }
+
+    if present.iter().all(|v| v.is_string()) {
+        Some(Arc::new(values.iter().map(|v| v.and_then(|v| v.as_str())).collect::<StringArray>()))
+    } else if present.iter().all(|v| v.is_i64()) {
+        Some(Arc::new(values.iter().map(|v| v.and_then(|v| v.as_i64())).collect::<Int64Array>()))
+    } else if present.iter().all(|v| v.is_number()) {
+        Some(Arc::new(values.iter().map(|v| v.and_then(|v| v.as_f64())).collect::<Float64Array>()))
+    } else if present.iter().all(|v| v.is_boolean()) {
+        Some(Arc::new(values.iter().map(|v| v.and_then(|v| v.as_bool())).collect::<BooleanArray>()))
+    } else {
+        None
+    }
+}
+
+/// Builds a `FixedSizeList<Float32>` column when every present value is a
+/// numeric array of the same non-zero length.
+#[cfg(feature = "parquet")]
+pub(crate) fn json_vector_column(values: &[Option<&serde_json::Value>]) -> Option<arrow_array::ArrayRef> {
+    let vectors: Vec<Option<Vec<f32>>> = values.iter()
+        .map(|v| match v {
+            None => Some(None),
+            Some(v) => v.as_array()
+                .and_then(|a| a.iter().map(|x| x.as_f64().map(|x| x as f32)).collect::<Option<Vec<f32>>>())
+                .map(Some),
+        })
+        .collect::<Option<_>>()?;
+
+    let dimension = vectors.iter().flatten().next()?.len();
+    if dimension == 0 || vectors.iter().flatten().any(|v| v.len() != dimension) {
+        return None;
+    }
+    Some(vector_array(&vectors, dimension))
+}
+
+#[cfg(feature = "parquet")]
+pub(crate) fn vector_array(vectors: &[Option<Vec<f32>>], dimension: usize) -> arrow_array::ArrayRef {
+    use arrow_array::builder::{FixedSizeListBuilder, Float32Builder};
+    use std::sync::Arc;
+
+    let mut builder = FixedSizeListBuilder::with_capacity(Float32Builder::new(), dimension as i32, vectors.len());
+    for vector in vectors {
+        match vector {
+            Some(vector) => {
+                builder.values().append_slice(vector);
+                builder.append(true);
+            }
+            None => {
+                builder.values().append_nulls(dimension);
+                builder.append(false);
+            }
+        }
+    }
+    Arc::new(builder.finish())
+}
+
 pub struct OrcExporter;
 
 impl OrcExporter {
@@ -272,7 +401,12 @@ impl CollectionExporter {
                 self.exporter.export_csv(data, &filename)?;
             }
             ExportFormat::Parquet => {
-                return Err("Parquet format not available. Use JSON or CSV.".to_string());
+                let rows: Vec<serde_json::Value> = data.iter()
+                    .map(serde_json::to_value)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| e.to_string())?;
+                let path = Path::new(&self.exporter.output_path).join(&filename);
+                ParquetExporter::export_simple(&rows, &path.to_string_lossy())?;
             }
             ExportFormat::Orc => {
                 return Err("ORC format not available. Use JSON or CSV.".to_string());
@@ -339,6 +473,35 @@ mod tests {
         assert_eq!(ExportFormat::JsonLines.extension(), "jsonl");
     }
 
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_export() {
+        use arrow_array::Array;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vectors.parquet");
+
+        let data = vec![
+            serde_json::json!({"id": "1", "score": 0.5, "vector": [1.0, 2.0], "tags": ["a"]}),
+            serde_json::json!({"id": "2", "score": 2, "vector": [3.0, 4.0]}),
+        ];
+        ParquetExporter::export_simple(&data, path.to_str().unwrap()).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.map(|b| b.unwrap()).next().unwrap();
+        let schema = batch.schema();
+
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(schema.field_with_name("score").unwrap().data_type(), &arrow_schema::DataType::Float64);
+        assert!(matches!(schema.field_with_name("vector").unwrap().data_type(),
+            arrow_schema::DataType::FixedSizeList(_, 2)));
+        assert_eq!(schema.field_with_name("tags").unwrap().data_type(), &arrow_schema::DataType::Utf8);
+        assert!(batch.column_by_name("tags").unwrap().is_null(1));
+    }
+
     #[tokio::test]
     async fn test_batch_exporter() {
         let exporter = BatchExporter::new(2);
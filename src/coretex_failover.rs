@@ -8,6 +8,8 @@ use tokio::sync::{broadcast, RwLock};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::coretex_utils::raft::RaftNode;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeHealth {
     pub node_id: String,
@@ -72,6 +74,7 @@ pub struct FailoverManager {
     event_sender: broadcast::Sender<FailoverEvent>,
     current_term: Arc<RwLock<u64>>,
     voted_for: Arc<RwLock<Option<String>>>,
+    raft: Option<Arc<RaftNode>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -95,9 +98,17 @@ impl FailoverManager {
             event_sender,
             current_term: Arc::new(RwLock::new(0)),
             voted_for: Arc::new(RwLock::new(None)),
+            raft: None,
         }
     }
 
+    /// Runs elections through Raft: `start_election` campaigns for real and
+    /// leadership is whatever Raft last agreed on.
+    pub fn with_raft(mut self, raft: Arc<RaftNode>) -> Self {
+        self.raft = Some(raft);
+        self
+    }
+
     pub async fn register_node(&self, node_id: &str, health: NodeHealth) {
         let mut nodes = self.nodes.write().await;
         nodes.insert(node_id.to_string(), health);
@@ -167,6 +178,24 @@ impl FailoverManager {
     }
 
     pub async fn start_election(&self) -> Option<String> {
+        if let Some(raft) = &self.raft {
+            let won = raft.campaign().await;
+            let status = raft.status().await;
+            *self.current_term.write().await = status.term;
+            if let Some(leader) = &status.leader {
+                self.set_leader(leader).await;
+            }
+            if !won {
+                return None;
+            }
+
+            let _ = self.event_sender.send(FailoverEvent::LeaderElected {
+                node_id: self.local_node_id.clone(),
+                term: status.term,
+            });
+            return Some(self.local_node_id.clone());
+        }
+
         let mut term = self.current_term.write().await;
         *term += 1;
         let current_term = *term;
@@ -197,6 +226,9 @@ impl FailoverManager {
     }
 
     pub async fn get_leader(&self) -> Option<String> {
+        if let Some(raft) = &self.raft {
+            return raft.leader().await;
+        }
         let leader = self.leader_id.read().await;
         leader.clone()
     }
@@ -274,6 +306,9 @@ impl FailoverManager {
     }
 
     pub async fn is_leader(&self) -> bool {
+        if let Some(raft) = &self.raft {
+            return raft.is_leader().await;
+        }
         let leader = self.leader_id.read().await;
         leader.as_deref() == Some(&self.local_node_id)
     }
@@ -449,6 +484,29 @@ mod tests {
         assert!(is_leader);
     }
 
+    #[tokio::test]
+    async fn test_leader_election_through_raft() {
+        use crate::coretex_utils::raft::sim::{MemoryStateMachine, SimNetwork};
+
+        let network = Arc::new(SimNetwork::new());
+        let raft = Arc::new(RaftNode::new(
+            "node1",
+            vec!["node1".to_string()],
+            network.clone(),
+            Box::new(MemoryStateMachine::new()),
+        ));
+        network.register(&raft);
+
+        let manager = FailoverManager::new("node1", FailoverConfig::default()).with_raft(raft.clone());
+        let mut events = manager.event_receiver();
+
+        assert_eq!(manager.start_election().await, Some("node1".to_string()));
+        assert!(manager.is_leader().await);
+        assert_eq!(manager.get_cluster_stats().await.term, 1);
+        assert!(matches!(events.recv().await, Ok(FailoverEvent::LeaderChanged { .. })));
+        assert!(matches!(events.recv().await, Ok(FailoverEvent::LeaderElected { term: 1, .. })));
+    }
+
     #[tokio::test]
     async fn test_set_leader() {
         let manager = FailoverManager::new("node1", FailoverConfig::default());
@@ -0,0 +1,403 @@
+//! Named vector fields for CoreTexDB
+//! A collection can hold several named vectors per document next to its main
+//! one, e.g. title, body and image embeddings, each with its own dimension,
+//! metric and index. Searches target one field or fuse several.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::coretex_core::{DistanceMetric, IndexConfig, VectorField};
+use crate::coretex_hybrid::{FusedResult, MultiModalResult, ScoreFusion, ScoreFusionEngine};
+use crate::{simd_utils, CoreTexDB, CoreTexError, IndexManager, SearchResult};
+
+/// The name searches use for a collection's main vector.
+pub const DEFAULT_VECTOR_FIELD: &str = "default";
+
+/// One field's part of a multi-field search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldQuery {
+    pub field: String,
+    pub vector: Vec<f32>,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl FieldQuery {
+    pub fn new(field: &str, vector: Vec<f32>) -> Self {
+        Self { field: field.to_string(), vector, weight: default_weight() }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+impl VectorField {
+    pub fn new(name: &str, dimension: usize, distance_metric: DistanceMetric, index: IndexConfig) -> Self {
+        Self { name: name.to_string(), dimension, distance_metric, index }
+    }
+}
+
+/// A collection's named vectors by field and document id.
+type FieldVectors = HashMap<String, HashMap<String, Vec<f32>>>;
+
+/// Named vectors by collection.
+#[derive(Default)]
+pub struct NamedVectorStore {
+    vectors: RwLock<HashMap<String, FieldVectors>>,
+}
+
+impl NamedVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The named vectors of a document, by field.
+    pub(crate) async fn get(&self, collection: &str, id: &str) -> HashMap<String, Vec<f32>> {
+        let vectors = self.vectors.read().await;
+        vectors.get(collection)
+            .map(|fields| fields.iter()
+                .filter_map(|(name, vectors)| vectors.get(id).map(|v| (name.clone(), v.clone())))
+                .collect())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the named vectors of a document in the store and the field
+    /// indexes.
+    pub(crate) async fn replace(&self, index_manager: &IndexManager, collection: &str, id: &str, named: HashMap<String, Vec<f32>>) -> crate::Result<()> {
+        let mut vectors = self.vectors.write().await;
+        let fields = vectors.entry(collection.to_string()).or_default();
+
+        for (field, field_vectors) in fields.iter_mut() {
+            if !named.contains_key(field) && field_vectors.remove(id).is_some() {
+                if let Ok(Some(index)) = index_manager.get_index(&field_index_name(collection, field)).await {
+                    let _ = index.remove(id).await;
+                }
+            }
+        }
+        for (field, vector) in named {
+            if let Ok(Some(index)) = index_manager.get_index(&field_index_name(collection, &field)).await {
+                index.add(id, &vector).await
+                    .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+            }
+            fields.entry(field).or_default().insert(id.to_string(), vector);
+        }
+        Ok(())
+    }
+
+    /// Takes every named vector of `other` in place of these. Field indexes
+    /// move with `IndexManager::replace_with`.
+    pub(crate) async fn replace_with(&self, other: &NamedVectorStore) {
+        let vectors = std::mem::take(&mut *other.vectors.write().await);
+        *self.vectors.write().await = vectors;
+    }
+
+    /// Drops every named vector of a collection along with the field indexes.
+    pub(crate) async fn remove_collection(&self, index_manager: &IndexManager, collection: &str, fields: &[VectorField]) {
+        self.vectors.write().await.remove(collection);
+        for field in fields {
+            let _ = index_manager.delete_index(&field_index_name(collection, &field.name)).await;
+        }
+    }
+}
+
+/// The `IndexManager` name of a field's index.
+pub fn field_index_name(collection: &str, field: &str) -> String {
+    format!("{}.{}_hnsw", collection, field)
+}
+
+impl CoreTexDB {
+    /// Adds a named vector field to a collection. Documents get vectors for
+    /// it through `set_named_vectors`.
+    pub async fn add_vector_field(&self, collection: &str, field: VectorField) -> crate::Result<()> {
+        if field.name.is_empty() || field.name == DEFAULT_VECTOR_FIELD {
+            return Err(CoreTexError::ValidationError(format!("Invalid vector field name '{}'", field.name)));
+        }
+        if field.dimension == 0 {
+            return Err(CoreTexError::InvalidDimension(format!("Vector field '{}' needs a dimension", field.name)));
+        }
+
+        let mut collections = self.collections.write().await;
+        let mut schema = collections.get(collection)
+            .cloned()
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        if schema.vector_fields.iter().any(|f| f.name == field.name) {
+            return Err(CoreTexError::ValidationError(format!("Vector field '{}' already exists", field.name)));
+        }
+
+        schema.vector_fields.push(field);
+        self.change_schema(&mut collections, schema).await
+    }
+
+    /// Creates a collection whose documents carry the named `fields` as well
+    /// as a main vector of `dimension`.
+    pub async fn create_collection_with_fields(&self, name: &str, dimension: usize, metric: &str, fields: Vec<VectorField>) -> crate::Result<()> {
+        self.create_collection(name, dimension, metric).await?;
+        for field in fields {
+            if let Err(e) = self.add_vector_field(name, field).await {
+                let _ = self.delete_collection(name).await;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets named vectors of a document that is already in the collection,
+    /// replacing earlier vectors of the same fields.
+    pub async fn set_named_vectors(&self, collection: &str, id: &str, vectors: HashMap<String, Vec<f32>>) -> crate::Result<()> {
+        {
+            let collections = self.collections.read().await;
+            let schema = collections.get(collection)
+                .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+            for (name, vector) in &vectors {
+                let field = schema.vector_fields.iter()
+                    .find(|f| &f.name == name)
+                    .ok_or_else(|| CoreTexError::ValidationError(format!("Unknown vector field '{}'", name)))?;
+                if vector.len() != field.dimension {
+                    return Err(CoreTexError::DimensionMismatch { expected: field.dimension, actual: vector.len() });
+                }
+            }
+        }
+
+        self.update_side_vectors(collection, id, |side| side.named.extend(vectors)).await
+    }
+
+    /// The named vectors of a document, by field.
+    pub async fn get_named_vectors(&self, collection: &str, id: &str) -> crate::Result<HashMap<String, Vec<f32>>> {
+        if !self.collections.read().await.contains_key(collection) {
+            return Err(CoreTexError::CollectionNotFound(collection.to_string()));
+        }
+
+        Ok(self.named_vectors.get(collection, id).await)
+    }
+
+    /// Searches one vector field; `DEFAULT_VECTOR_FIELD` searches the main
+    /// vectors.
+    pub async fn search_field(
+        &self,
+        collection: &str,
+        field: &str,
+        query: Vec<f32>,
+        k: usize,
+        filter: Option<serde_json::Value>,
+    ) -> crate::Result<Vec<SearchResult>> {
+        if field == DEFAULT_VECTOR_FIELD {
+            return self.search(collection, query, k, filter, None).await;
+        }
+
+        {
+            let collections = self.collections.read().await;
+            let schema = collections.get(collection)
+                .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+            let field = schema.vector_fields.iter()
+                .find(|f| f.name == field)
+                .ok_or_else(|| CoreTexError::ValidationError(format!("Unknown vector field '{}'", field)))?;
+            if query.len() != field.dimension {
+                return Err(CoreTexError::DimensionMismatch { expected: field.dimension, actual: query.len() });
+            }
+        }
+
+        let index = self.index_manager.get_index(&field_index_name(collection, field)).await
+            .ok()
+            .flatten()
+            .ok_or_else(|| CoreTexError::IndexError(format!("No index for vector field '{}'", field)))?;
+        let results = index.search(&query, k * 2).await
+            .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+
+        // Only documents still in the collection, and matching the filter
+        let data = self.data.read().await;
+        let collection_data = data.get(collection);
+        Ok(results.into_iter()
+            .filter(|r| match collection_data.and_then(|cd| cd.get(&r.id)) {
+                Some((_, metadata)) => filter.as_ref().is_none_or(|f| Self::matches_filter(metadata, f)),
+                None => false,
+            })
+            .take(k)
+            .collect())
+    }
+
+    /// Searches several vector fields and fuses the results with `fusion`.
+    /// Each field contributes `1 / (1 + distance)` as its score, weighted by
+    /// its query's weight.
+    pub async fn search_fields(
+        &self,
+        collection: &str,
+        queries: Vec<FieldQuery>,
+        k: usize,
+        filter: Option<serde_json::Value>,
+        fusion: ScoreFusion,
+    ) -> crate::Result<Vec<FusedResult>> {
+        if queries.is_empty() {
+            return Err(CoreTexError::ValidationError("No vector fields to search".to_string()));
+        }
+        if matches!(fusion, ScoreFusion::LearningToRank) {
+            return Err(CoreTexError::ValidationError("Learning to rank fusion is not supported for vector fields".to_string()));
+        }
+
+        // Each field brings more than `k` candidates so documents that rank
+        // well overall but not first in any field are not missed
+        let mut candidates = Vec::new();
+        for query in &queries {
+            for hit in self.search_field(collection, &query.field, query.vector.clone(), k * 2, filter.clone()).await? {
+                if !candidates.contains(&hit.id) {
+                    candidates.push(hit.id);
+                }
+            }
+        }
+
+        // Every candidate is then scored on every field it has a vector for,
+        // not only on the fields that happened to return it
+        let metrics: HashMap<String, DistanceMetric> = {
+            let collections = self.collections.read().await;
+            collections.get(collection)
+                .map(|schema| schema.vector_fields.iter()
+                    .map(|f| (f.name.clone(), f.distance_metric.clone()))
+                    .chain([(DEFAULT_VECTOR_FIELD.to_string(), schema.distance_metric.clone())])
+                    .collect())
+                .unwrap_or_default()
+        };
+        let data = self.data.read().await;
+        let named = self.named_vectors.vectors.read().await;
+        let mut results = Vec::new();
+        for query in &queries {
+            let metric = metrics.get(&query.field).cloned().unwrap_or(DistanceMetric::Cosine);
+            let mut scored: Vec<(&String, f32)> = if query.field == DEFAULT_VECTOR_FIELD {
+                let collection_data = data.get(collection);
+                candidates.iter()
+                    .filter_map(|id| collection_data?.get(id).map(|(vector, _)| (id, field_distance(&query.vector, vector, &metric))))
+                    .collect()
+            } else {
+                let field_vectors = named.get(collection).and_then(|fields| fields.get(&query.field));
+                candidates.iter()
+                    .filter_map(|id| field_vectors?.get(id).map(|vector| (id, field_distance(&query.vector, vector, &metric))))
+                    .collect()
+            };
+            scored.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+
+            results.extend(scored.into_iter().enumerate().map(|(rank, (id, distance))| MultiModalResult {
+                id: id.clone(),
+                score: 1.0 / (1.0 + distance),
+                rank,
+                source: query.field.clone(),
+                weight: query.weight,
+                metadata: None,
+            }));
+        }
+
+        let mut fused = ScoreFusionEngine::new(fusion).fuse(&results);
+        fused.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+        fused.truncate(k);
+        Ok(fused)
+    }
+}
+
+/// The distance a field index reports under `metric`: Euclidean distance,
+/// or cosine distance, which the indexes fall back to for other metrics.
+pub(crate) fn field_distance(a: &[f32], b: &[f32], metric: &DistanceMetric) -> f32 {
+    match metric {
+        DistanceMetric::Euclidean => simd_utils::euclidean_distance(a, b),
+        _ => 1.0 - simd_utils::cosine_similarity(a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coretex_core::IndexType;
+
+    fn field(name: &str, dimension: usize) -> VectorField {
+        VectorField::new(name, dimension, DistanceMetric::Cosine, IndexConfig {
+            name: format!("{}_index", name),
+            index_type: IndexType::HNSW,
+            parameters: HashMap::new(),
+        })
+    }
+
+    async fn articles() -> CoreTexDB {
+        let db = CoreTexDB::new();
+        db.create_collection_with_fields("articles", 2, "cosine", vec![field("title", 3), field("image", 2)]).await.unwrap();
+
+        db.insert_vectors("articles", vec![
+            ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"lang": "en"})),
+            ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"lang": "de"})),
+            ("c".to_string(), vec![0.7, 0.7], serde_json::json!({"lang": "en"})),
+        ]).await.unwrap();
+        for (id, title, image) in [
+            ("a", vec![1.0, 0.0, 0.0], vec![0.0, 1.0]),
+            ("b", vec![0.0, 1.0, 0.0], vec![1.0, 0.0]),
+            ("c", vec![0.0, 0.0, 1.0], vec![0.9, 0.1]),
+        ] {
+            db.set_named_vectors("articles", id, [("title".to_string(), title), ("image".to_string(), image)].into()).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_named_fields_have_their_own_dimension_and_index() {
+        let db = articles().await;
+
+        let schema = db.get_collection("articles").await.unwrap();
+        assert_eq!(schema.vector_fields.len(), 2);
+        assert!(db.add_vector_field("articles", field("title", 4)).await.is_err());
+
+        let err = db.set_named_vectors("articles", "a", [("title".to_string(), vec![1.0, 0.0])].into()).await;
+        assert!(matches!(err, Err(CoreTexError::DimensionMismatch { expected: 3, actual: 2 })));
+        let err = db.set_named_vectors("articles", "missing", [("title".to_string(), vec![1.0, 0.0, 0.0])].into()).await;
+        assert!(matches!(err, Err(CoreTexError::DocumentNotFound(_))));
+
+        let results = db.search_field("articles", "title", vec![0.0, 0.1, 1.0], 1, None).await.unwrap();
+        assert_eq!(results[0].id, "c");
+        let results = db.search_field("articles", "image", vec![1.0, 0.0], 1, None).await.unwrap();
+        assert_eq!(results[0].id, "b");
+        let results = db.search_field("articles", "image", vec![1.0, 0.0], 1, Some(serde_json::json!({"lang": "en"}))).await.unwrap();
+        assert_eq!(results[0].id, "c");
+        let results = db.search_field("articles", DEFAULT_VECTOR_FIELD, vec![1.0, 0.0], 1, None).await.unwrap();
+        assert_eq!(results[0].id, "a");
+
+        // Deleting a document drops its named vectors too
+        db.delete_vectors("articles", &["b".to_string()]).await.unwrap();
+        assert!(db.get_named_vectors("articles", "b").await.unwrap().is_empty());
+        assert_eq!(db.get_named_vectors("articles", "a").await.unwrap().len(), 2);
+        let results = db.search_field("articles", "image", vec![1.0, 0.0], 3, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["c", "a"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_fields_fuses_scores() {
+        let db = articles().await;
+
+        // "a" leads on title, "b" on image; the weight decides
+        let queries = vec![
+            FieldQuery::new("title", vec![1.0, 0.0, 0.0]).with_weight(2.0),
+            FieldQuery::new("image", vec![1.0, 0.0]),
+        ];
+        let fused = db.search_fields("articles", queries.clone(), 3, None, ScoreFusion::WeightedSum).await.unwrap();
+        assert_eq!(fused.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        // a: 2 * 1/(1+0) + 1/(1+1)
+        assert!((fused[0].score - 2.5).abs() < 1e-5);
+
+        // Every candidate is scored on both fields, so a small `k` does not
+        // change the scores
+        let queries = vec![queries[0].clone().with_weight(0.1), queries[1].clone()];
+        let all = db.search_fields("articles", queries.clone(), 3, None, ScoreFusion::WeightedSum).await.unwrap();
+        assert_eq!(all.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["b", "c", "a"]);
+        for _ in 0..10 {
+            let fused = db.search_fields("articles", queries.clone(), 1, None, ScoreFusion::WeightedSum).await.unwrap();
+            assert_eq!(fused.len(), 1);
+            assert_eq!(fused[0].id, "b");
+            assert_eq!(fused[0].score, all[0].score);
+        }
+
+        let fused = db.search_fields("articles", queries.clone(), 3, None, ScoreFusion::RRF { k: 60 }).await.unwrap();
+        assert_eq!(fused.len(), 3);
+
+        assert!(db.search_fields("articles", queries, 3, None, ScoreFusion::LearningToRank).await.is_err());
+        assert!(db.search_fields("articles", vec![FieldQuery::new("body", vec![1.0])], 3, None, ScoreFusion::WeightedSum).await.is_err());
+    }
+}
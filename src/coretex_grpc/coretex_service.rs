@@ -110,6 +110,7 @@ impl self::coretex_service_server::CoretexService for CoretexService {
             req.query_vector,
             req.k as usize,
             None,
+            None,
         )
         .await
         .map_err(|e| Status::internal(e.to_string()))?;
@@ -135,7 +136,7 @@ impl self::coretex_service_server::CoretexService for CoretexService {
         let req = request.into_inner();
         
         let db = self.db.read().await;
-        let result = db.get_vector(&req.collection, &req.id)
+        let result = db.get_vector(&req.collection, &req.id, None)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
         
@@ -9,6 +9,9 @@ use chrono::{DateTime, Utc};
 pub struct MultiModalDocument {
     pub id: String,
     pub vector: Option<VectorData>,
+    /// Token- or frame-level vectors for late-interaction search
+    #[serde(default)]
+    pub multi_vector: Option<MultiVectorData>,
     pub text: Option<TextData>,
     pub scalar_fields: HashMap<String, ScalarValue>,
     pub time_series: Option<TimeSeriesData>,
@@ -25,6 +28,13 @@ pub struct VectorData {
     pub model: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiVectorData {
+    pub vectors: Vec<Vec<f32>>,
+    pub dimension: usize,
+    pub model: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextData {
     pub content: String,
@@ -68,6 +78,7 @@ impl MultiModalDocument {
         Self {
             id,
             vector: None,
+            multi_vector: None,
             text: None,
             scalar_fields: HashMap::new(),
             time_series: None,
@@ -88,6 +99,16 @@ impl MultiModalDocument {
         self
     }
 
+    pub fn with_multi_vector(mut self, vectors: Vec<Vec<f32>>) -> Self {
+        let dimension = vectors.first().map_or(0, Vec::len);
+        self.multi_vector = Some(MultiVectorData {
+            vectors,
+            dimension,
+            model: None,
+        });
+        self
+    }
+
     pub fn with_text(mut self, content: String) -> Self {
         self.text = Some(TextData {
             content,
@@ -125,6 +146,10 @@ impl MultiModalDocument {
         self.vector.as_ref().map(|v| v.values.as_slice())
     }
 
+    pub fn get_multi_vector(&self) -> Option<&[Vec<f32>]> {
+        self.multi_vector.as_ref().map(|m| m.vectors.as_slice())
+    }
+
     pub fn get_text(&self) -> Option<&str> {
         self.text.as_ref().map(|t| t.content.as_str())
     }
@@ -150,4 +175,13 @@ mod tests {
         assert_eq!(doc.scalar_fields.len(), 2);
         assert!(doc.geo_location.is_some());
     }
+
+    #[test]
+    fn test_document_multi_vector() {
+        let doc = MultiModalDocument::new("doc1".to_string())
+            .with_multi_vector(vec![vec![0.1, 0.2], vec![0.3, 0.4], vec![0.5, 0.6]]);
+
+        assert_eq!(doc.multi_vector.as_ref().unwrap().dimension, 2);
+        assert_eq!(doc.get_multi_vector().unwrap().len(), 3);
+    }
 }
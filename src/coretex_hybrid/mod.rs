@@ -6,7 +6,7 @@ pub mod query;
 pub mod fusion;
 pub mod retriever;
 
-pub use document::{MultiModalDocument, VectorData, TextData, ScalarValue, TimeSeriesData, GeoLocation};
-pub use query::{HybridQuery, VectorQuery, TextQuery, ScalarFilter, FilterOperator, GeoFilter, QueryWeights, DistanceMetric};
+pub use document::{MultiModalDocument, VectorData, MultiVectorData, TextData, ScalarValue, TimeSeriesData, GeoLocation};
+pub use query::{HybridQuery, VectorQuery, SparseQuery, TextQuery, ScalarFilter, FilterOperator, GeoFilter, QueryWeights, DistanceMetric};
 pub use fusion::{ScoreFusion, ScoreFusionEngine, MultiModalResult, FusedResult};
-pub use retriever::{HybridRetriever, VectorRetriever, TextRetriever, TextSearchResult, BruteForceVectorAdapter, BM25TextAdapter};
+pub use retriever::{HybridRetriever, VectorRetriever, SparseRetriever, TextRetriever, TextSearchResult, BruteForceVectorAdapter, SparseIndexAdapter, BM25TextAdapter};
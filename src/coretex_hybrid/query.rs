@@ -4,9 +4,13 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::coretex_sparse::SparseVector;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HybridQuery {
     pub vector_query: Option<VectorQuery>,
+    #[serde(default)]
+    pub sparse_query: Option<SparseQuery>,
     pub text_query: Option<TextQuery>,
     pub scalar_filters: Vec<ScalarFilter>,
     pub geo_filter: Option<GeoFilter>,
@@ -22,6 +26,12 @@ pub struct VectorQuery {
     pub filter: Option<HashMap<String, serde_json::Value>>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SparseQuery {
+    pub vector: SparseVector,
+    pub filter: Option<HashMap<String, serde_json::Value>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextQuery {
     pub query: String,
@@ -106,16 +116,23 @@ pub struct TimeRange {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryWeights {
     pub vector_weight: f32,
+    #[serde(default = "default_sparse_weight")]
+    pub sparse_weight: f32,
     pub text_weight: f32,
     pub scalar_weight: f32,
     pub geo_weight: f32,
     pub time_weight: f32,
 }
 
+fn default_sparse_weight() -> f32 {
+    1.0
+}
+
 impl Default for QueryWeights {
     fn default() -> Self {
         Self {
             vector_weight: 1.0,
+            sparse_weight: default_sparse_weight(),
             text_weight: 1.0,
             scalar_weight: 1.0,
             geo_weight: 1.0,
@@ -128,6 +145,7 @@ impl Default for HybridQuery {
     fn default() -> Self {
         Self {
             vector_query: None,
+            sparse_query: None,
             text_query: None,
             scalar_filters: Vec::new(),
             geo_filter: None,
@@ -152,6 +170,14 @@ impl HybridQuery {
         self
     }
 
+    pub fn with_sparse(mut self, vector: SparseVector) -> Self {
+        self.sparse_query = Some(SparseQuery {
+            vector,
+            filter: None,
+        });
+        self
+    }
+
     pub fn with_text(mut self, query: impl Into<String>) -> Self {
         self.text_query = Some(TextQuery {
             query: query.into(),
@@ -6,12 +6,14 @@ use crate::coretex_hybrid::query::{HybridQuery, DistanceMetric, FilterOperator};
 use crate::coretex_hybrid::fusion::{ScoreFusionEngine, MultiModalResult, ScoreFusion, FusedResult};
 use crate::coretex_index::SearchResult;
 use crate::coretex_bm25::BM25Index;
+use crate::coretex_sparse::{SparseIndex, SparseSearchResult, SparseVector};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 pub struct HybridRetriever {
     vector_index: Arc<RwLock<Option<Box<dyn VectorRetriever>>>>,
+    sparse_index: Arc<RwLock<Option<Box<dyn SparseRetriever>>>>,
     text_index: Arc<RwLock<Option<Box<dyn TextRetriever>>>>,
     scalar_storage: Arc<RwLock<HashMap<String, HashMap<String, serde_json::Value>>>>,
     fusion_engine: ScoreFusionEngine,
@@ -22,6 +24,10 @@ pub trait VectorRetriever: Send + Sync {
     fn search(&self, vector: &[f32], k: usize, metric: DistanceMetric) -> Vec<SearchResult>;
 }
 
+pub trait SparseRetriever: Send + Sync {
+    fn search(&self, vector: &SparseVector, k: usize) -> Vec<SparseSearchResult>;
+}
+
 pub trait TextRetriever: Send + Sync {
     fn search(&self, query: &str, k: usize) -> Vec<TextSearchResult>;
 }
@@ -36,6 +42,7 @@ impl HybridRetriever {
     pub fn new() -> Self {
         Self {
             vector_index: Arc::new(RwLock::new(None)),
+            sparse_index: Arc::new(RwLock::new(None)),
             text_index: Arc::new(RwLock::new(None)),
             scalar_storage: Arc::new(RwLock::new(HashMap::new())),
             fusion_engine: ScoreFusionEngine::new(ScoreFusion::RRF { k: 60 }),
@@ -49,6 +56,12 @@ impl HybridRetriever {
         self
     }
 
+    pub fn with_sparse_index<T: SparseRetriever + 'static>(mut self, index: T) -> Self {
+        let index: Box<dyn SparseRetriever> = Box::new(index);
+        self.sparse_index = Arc::new(RwLock::new(Some(index)));
+        self
+    }
+
     pub fn with_text_index<T: TextRetriever + 'static>(self, index: T) -> Self {
         let index: Box<dyn TextRetriever> = Box::new(index);
         self.text_index.blocking_write().replace(index);
@@ -109,6 +122,22 @@ impl HybridRetriever {
             }
         }
 
+        if let Some(ref sq) = query.sparse_query {
+            if let Some(index) = self.sparse_index.read().await.as_ref() {
+                let sparse_results = index.search(&sq.vector, self.coarse_top_k);
+                for (rank, result) in sparse_results.into_iter().enumerate() {
+                    results.push(MultiModalResult {
+                        id: result.id,
+                        score: result.score,
+                        rank,
+                        source: "sparse".to_string(),
+                        weight: query.weights.sparse_weight,
+                        metadata: None,
+                    });
+                }
+            }
+        }
+
         if let Some(ref tq) = query.text_query {
             if let Some(index) = self.text_index.read().await.as_ref() {
                 let text_results = index.search(&tq.query, self.coarse_top_k);
@@ -253,6 +282,31 @@ fn calculate_distance(a: &[f32], b: &[f32], metric: &str) -> f32 {
     }
 }
 
+#[derive(Default)]
+pub struct SparseIndexAdapter {
+    index: std::sync::RwLock<SparseIndex>,
+}
+
+impl SparseIndexAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, id: &str, vector: SparseVector) {
+        self.index.write().unwrap().insert(id, vector);
+    }
+
+    pub fn remove(&self, id: &str) -> bool {
+        self.index.write().unwrap().remove(id)
+    }
+}
+
+impl SparseRetriever for SparseIndexAdapter {
+    fn search(&self, vector: &SparseVector, k: usize) -> Vec<SparseSearchResult> {
+        self.index.read().unwrap().search(vector, k)
+    }
+}
+
 pub struct BM25TextAdapter {
     index: BM25Index,
 }
@@ -299,4 +353,20 @@ mod tests {
         let results = retriever.search(&query).await;
         assert!(results.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_hybrid_retriever_sparse() {
+        let sparse = SparseIndexAdapter::new();
+        sparse.add("a", SparseVector::new(vec![1, 5], vec![0.5, 1.0]).unwrap());
+        sparse.add("b", SparseVector::new(vec![5], vec![3.0]).unwrap());
+        sparse.add("c", SparseVector::new(vec![9], vec![1.0]).unwrap());
+        let retriever = HybridRetriever::new().with_sparse_index(sparse);
+
+        let query = HybridQuery::new()
+            .with_sparse(SparseVector::new(vec![5], vec![1.0]).unwrap())
+            .with_top_k(10);
+
+        let results = retriever.search(&query).await;
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["b", "a"]);
+    }
 }
@@ -1,14 +1,37 @@
 //! Incremental Index Update for CortexDB
 //! Supports real-time index updates without full rebuild
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-
+use std::time::{Duration, Instant};
+use rand::seq::IteratorRandom;
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
+
+/// Updates `IncrementalIndex::flush` applies per hold of the index lock, so
+/// a search waits for one chunk at most, not the whole batch.
+const FLUSH_CHUNK: usize = 256;
+
+/// Builds an empty index for a dimension; see `IncrementalIndex::with_builder`.
+pub type IndexBuilder = Arc<dyn Fn(usize) -> Box<dyn IndexTrait + Send + Sync> + Send + Sync>;
+
+/// Writes land in `pending_updates` and reach the index on `flush`; searches
+/// overlay the pending updates on the index, so they never miss a write.
+/// Removed ids stay in the index as tombstones, skipped by searches, until
+/// `compact` or a `rebuild` drops them.
 pub struct IncrementalIndex {
     vectors: Arc<RwLock<HashMap<String, Vec<f32>>>>,
     index: Arc<RwLock<Option<Box<dyn IndexTrait + Send + Sync>>>>,
     pending_updates: Arc<RwLock<Vec<IndexUpdate>>>,
+    /// When the oldest pending update was queued.
+    pending_since: Arc<std::sync::Mutex<Option<Instant>>>,
+    tombstones: Arc<RwLock<HashSet<String>>>,
+    /// Updates flushed while a rebuild runs, replayed on the new index
+    /// before it is swapped in.
+    rebuild_log: Arc<RwLock<Option<Vec<IndexUpdate>>>>,
+    dimension: Arc<RwLock<Option<usize>>>,
+    builder: Option<IndexBuilder>,
     index_type: IndexType,
     config: IndexConfig,
 }
@@ -70,40 +93,63 @@ impl IncrementalIndex {
             vectors: Arc::new(RwLock::new(HashMap::new())),
             index: Arc::new(RwLock::new(None)),
             pending_updates: Arc::new(RwLock::new(Vec::new())),
+            pending_since: Arc::new(std::sync::Mutex::new(None)),
+            tombstones: Arc::new(RwLock::new(HashSet::new())),
+            rebuild_log: Arc::new(RwLock::new(None)),
+            dimension: Arc::new(RwLock::new(None)),
+            builder: None,
             index_type,
             config,
         }
     }
 
-    pub async fn initialize(&self, dimension: usize) -> Result<(), String> {
-        let mut index_guard = self.index.write().await;
-        
-        match self.index_type {
-            IndexType::BruteForce => {
-                *index_guard = Some(Box::new(BruteForceIndex::new(dimension)));
-            },
-            IndexType::HNSW => {
-                *index_guard = Some(Box::new(HnswIndex::new(
+    /// Builds indexes with `builder` instead of from `index_type`.
+    pub fn with_builder(mut self, builder: IndexBuilder) -> Self {
+        self.builder = Some(builder);
+        self
+    }
+
+    fn builder(&self) -> IndexBuilder {
+        if let Some(builder) = &self.builder {
+            return builder.clone();
+        }
+
+        let index_type = self.index_type.clone();
+        let config = self.config.clone();
+        Arc::new(move |dimension| -> Box<dyn IndexTrait + Send + Sync> {
+            match index_type {
+                IndexType::BruteForce => Box::new(BruteForceIndex::new(dimension)),
+                IndexType::HNSW => Box::new(HnswIndex::new(
                     dimension,
-                    self.config.hnsw_m.unwrap_or(16),
-                    self.config.hnsw_ef_construction.unwrap_or(200),
-                )));
-            },
-            IndexType::IVF => {
-                *index_guard = Some(Box::new(IvfIndex::new(
+                    config.hnsw_m.unwrap_or(16),
+                    config.hnsw_ef_construction.unwrap_or(200),
+                )),
+                IndexType::IVF => Box::new(IvfIndex::new(
                     dimension,
-                    self.config.ivf_nlist.unwrap_or(100),
-                )));
-            },
-            IndexType::PQ => {
-                *index_guard = Some(Box::new(PqIndex::new(
+                    config.ivf_nlist.unwrap_or(100),
+                )),
+                IndexType::PQ => Box::new(PqIndex::new(
                     dimension,
-                    self.config.pq_n_subquantizers.unwrap_or(8),
-                    self.config.pq_n_bits.unwrap_or(8),
-                )));
-            },
+                    config.pq_n_subquantizers.unwrap_or(8),
+                    config.pq_n_bits.unwrap_or(8),
+                )),
+            }
+        })
+    }
+
+    /// Builds the index over every vector added so far.
+    pub async fn initialize(&self, dimension: usize) -> Result<(), String> {
+        let mut index_guard = self.index.write().await;
+
+        let mut index = self.builder()(dimension);
+        for (id, vector) in self.vectors.read().await.iter() {
+            index.add(id.clone(), vector.clone())?;
         }
-        
+        self.get_pending_updates().await;
+        self.tombstones.write().await.clear();
+        *self.dimension.write().await = Some(dimension);
+        *index_guard = Some(index);
+
         Ok(())
     }
 
@@ -113,15 +159,7 @@ impl IncrementalIndex {
             vectors.insert(id.clone(), vector.clone());
         }
 
-        {
-            let mut pending = self.pending_updates.write().await;
-            pending.push(IndexUpdate::Insert { id: id.clone(), vector: vector.clone() });
-        }
-
-        if let Some(ref mut idx) = *self.index.write().await {
-            idx.add(id, vector)?;
-        }
-
+        self.queue(IndexUpdate::Insert { id, vector }).await;
         Ok(())
     }
 
@@ -131,15 +169,7 @@ impl IncrementalIndex {
             vectors.remove(id);
         }
 
-        {
-            let mut pending = self.pending_updates.write().await;
-            pending.push(IndexUpdate::Remove { id: id.to_string() });
-        }
-
-        if let Some(ref mut idx) = *self.index.write().await {
-            idx.remove(id)?;
-        }
-
+        self.queue(IndexUpdate::Remove { id: id.to_string() }).await;
         Ok(())
     }
 
@@ -149,58 +179,107 @@ impl IncrementalIndex {
             vectors.insert(id.to_string(), vector.clone());
         }
 
-        {
-            let mut pending = self.pending_updates.write().await;
-            pending.push(IndexUpdate::Update { 
-                id: id.to_string(), 
-                vector: vector.clone() 
-            });
-        }
-
-        if let Some(ref mut idx) = *self.index.write().await {
-            idx.remove(id)?;
-            idx.add(id.to_string(), vector)?;
-        }
-
+        self.queue(IndexUpdate::Update { id: id.to_string(), vector }).await;
         Ok(())
     }
 
+    async fn queue(&self, update: IndexUpdate) {
+        let mut pending = self.pending_updates.write().await;
+        pending.push(update);
+        self.pending_since.lock().unwrap().get_or_insert_with(Instant::now);
+    }
+
     pub async fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>, String> {
-        if let Some(ref idx) = *self.index.read().await {
-            idx.search(query, k)
-        } else {
-            Err("Index not initialized".to_string())
+        let index = self.index.read().await;
+        let Some(idx) = index.as_ref() else {
+            return Err("Index not initialized".to_string());
+        };
+        let tombstones = self.tombstones.read().await;
+        let pending = self.pending_updates.read().await;
+
+        // The latest pending state of each id wins over the index
+        let mut overlay: HashMap<&str, Option<&Vec<f32>>> = HashMap::new();
+        for update in pending.iter() {
+            match update {
+                IndexUpdate::Insert { id, vector } | IndexUpdate::Update { id, vector } => {
+                    overlay.insert(id, Some(vector));
+                }
+                IndexUpdate::Remove { id } => {
+                    overlay.insert(id, None);
+                }
+            }
         }
+
+        let mut results: Vec<SearchResult> = idx.search(query, k + tombstones.len() + overlay.len())?
+            .into_iter()
+            .filter(|r| !tombstones.contains(&r.id) && !overlay.contains_key(r.id.as_str()))
+            .collect();
+        results.extend(overlay.iter().filter_map(|(id, vector)| {
+            vector.map(|vector| SearchResult { id: id.to_string(), distance: cosine_distance(query, vector) })
+        }));
+
+        results.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(k);
+        Ok(results)
     }
 
     pub async fn get_pending_updates(&self) -> Vec<IndexUpdate> {
         let mut pending = self.pending_updates.write().await;
         let updates = pending.clone();
         pending.clear();
+        *self.pending_since.lock().unwrap() = None;
         updates
     }
 
+    pub async fn pending_count(&self) -> usize {
+        self.pending_updates.read().await.len()
+    }
+
+    /// How long the oldest pending update has waited.
+    pub fn pending_age(&self) -> Option<Duration> {
+        self.pending_since.lock().unwrap().map(|since| since.elapsed())
+    }
+
+    pub async fn tombstone_count(&self) -> usize {
+        self.tombstones.read().await.len()
+    }
+
+    /// Entries in the index, tombstones included.
+    pub async fn index_size(&self) -> usize {
+        self.index.read().await.as_ref().map_or(0, |idx| idx.size())
+    }
+
+    pub async fn is_rebuilding(&self) -> bool {
+        self.rebuild_log.read().await.is_some()
+    }
+
     pub async fn apply_batch_updates(&self, updates: Vec<IndexUpdate>) -> Result<(), String> {
-        for update in updates {
+        let mut index = self.index.write().await;
+        self.apply_locked(&mut index, updates).await
+    }
+
+    async fn apply_locked(&self, index: &mut Option<Box<dyn IndexTrait + Send + Sync>>, updates: Vec<IndexUpdate>) -> Result<(), String> {
+        let Some(idx) = index.as_mut() else {
+            return Ok(());
+        };
+
+        let mut tombstones = self.tombstones.write().await;
+        for update in &updates {
             match update {
-                IndexUpdate::Insert { id, vector } => {
-                    if let Some(ref mut idx) = *self.index.write().await {
-                        idx.add(id, vector)?;
-                    }
-                },
+                IndexUpdate::Insert { id, vector } | IndexUpdate::Update { id, vector } => {
+                    tombstones.remove(id);
+                    idx.remove(id)?;
+                    idx.add(id.clone(), vector.clone())?;
+                }
                 IndexUpdate::Remove { id } => {
-                    if let Some(ref mut idx) = *self.index.write().await {
-                        idx.remove(&id)?;
-                    }
-                },
-                IndexUpdate::Update { id, vector } => {
-                    if let Some(ref mut idx) = *self.index.write().await {
-                        idx.remove(&id)?;
-                        idx.add(id, vector)?;
-                    }
-                },
+                    tombstones.insert(id.clone());
+                }
             }
         }
+
+        if let Some(log) = self.rebuild_log.write().await.as_mut() {
+            log.extend(updates);
+        }
         Ok(())
     }
 
@@ -209,9 +288,312 @@ impl IncrementalIndex {
         vectors.len()
     }
 
-    pub async fn flush(&self) -> Result<(), String> {
-        let updates = self.get_pending_updates().await;
-        self.apply_batch_updates(updates).await
+    /// Applies the updates pending when called to the index and returns how
+    /// many there were. They go in chunks of `FLUSH_CHUNK`, releasing the
+    /// index lock between chunks so searches are not held up by the batch.
+    pub async fn flush(&self) -> Result<usize, String> {
+        let total = self.pending_count().await;
+        let mut flushed = 0;
+        while flushed < total {
+            // Drained under the index lock so no search sees neither
+            let mut index = self.index.write().await;
+            let updates = self.take_pending(FLUSH_CHUNK.min(total - flushed)).await;
+            if updates.is_empty() {
+                break;
+            }
+            flushed += updates.len();
+            self.apply_locked(&mut index, updates).await?;
+        }
+        Ok(flushed)
+    }
+
+    /// Removes up to `limit` of the oldest pending updates.
+    async fn take_pending(&self, limit: usize) -> Vec<IndexUpdate> {
+        let mut pending = self.pending_updates.write().await;
+        let limit = limit.min(pending.len());
+        let updates: Vec<IndexUpdate> = pending.drain(..limit).collect();
+        if pending.is_empty() {
+            *self.pending_since.lock().unwrap() = None;
+        }
+        updates
+    }
+
+    /// Removes tombstoned entries from the index and returns how many.
+    pub async fn compact(&self) -> Result<usize, String> {
+        let mut index = self.index.write().await;
+        let Some(idx) = index.as_mut() else {
+            return Ok(0);
+        };
+
+        let mut tombstones = self.tombstones.write().await;
+        let count = tombstones.len();
+        for id in tombstones.drain() {
+            idx.remove(&id)?;
+        }
+        Ok(count)
+    }
+
+    /// Average recall@k of the index against exact search, over up to
+    /// `samples` stored vectors used as queries.
+    pub async fn measure_recall(&self, samples: usize, k: usize) -> Result<f64, String> {
+        let queries: Vec<Vec<f32>> = {
+            let vectors = self.vectors.read().await;
+            vectors.values().cloned().choose_multiple(&mut rand::thread_rng(), samples)
+        };
+        if queries.is_empty() || k == 0 {
+            return Ok(1.0);
+        }
+
+        let mut total = 0.0;
+        for query in &queries {
+            let found: HashSet<String> = self.search(query, k).await?.into_iter().map(|r| r.id).collect();
+
+            let exact: Vec<String> = {
+                let vectors = self.vectors.read().await;
+                let mut scored: Vec<(f32, &String)> = vectors.iter()
+                    .map(|(id, vector)| (cosine_distance(query, vector), id))
+                    .collect();
+                scored.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+                scored.into_iter().take(k).map(|(_, id)| id.clone()).collect()
+            };
+            let hits = exact.iter().filter(|id| found.contains(*id)).count();
+            total += hits as f64 / exact.len().max(1) as f64;
+        }
+        Ok(total / queries.len() as f64)
+    }
+
+    /// Builds a fresh index from the stored vectors off the async runtime
+    /// and swaps it in. Searches keep using the old index until the swap,
+    /// which only waits for updates flushed during the build to be
+    /// replayed.
+    pub async fn rebuild(&self) -> Result<(), String> {
+        let dimension = (*self.dimension.read().await).ok_or("Index not initialized")?;
+        {
+            let mut log = self.rebuild_log.write().await;
+            if log.is_some() {
+                return Err("A rebuild is already running".to_string());
+            }
+            *log = Some(Vec::new());
+        }
+
+        let snapshot: Vec<(String, Vec<f32>)> = self.vectors.read().await
+            .iter()
+            .map(|(id, vector)| (id.clone(), vector.clone()))
+            .collect();
+        let builder = self.builder();
+        let built = tokio::task::spawn_blocking(move || {
+            let mut index = builder(dimension);
+            for (id, vector) in snapshot {
+                index.add(id, vector)?;
+            }
+            Ok::<_, String>(index)
+        }).await;
+
+        let mut index = self.index.write().await;
+        let log = self.rebuild_log.write().await.take().unwrap_or_default();
+        let mut fresh = built.map_err(|e| e.to_string())??;
+
+        for update in log {
+            match update {
+                IndexUpdate::Insert { id, vector } | IndexUpdate::Update { id, vector } => {
+                    fresh.remove(&id)?;
+                    fresh.add(id, vector)?;
+                }
+                IndexUpdate::Remove { id } => fresh.remove(&id)?,
+            }
+        }
+        self.tombstones.write().await.clear();
+        *index = Some(fresh);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MaintainerConfig {
+    pub interval_ms: u64,
+    /// Flush once this many updates are pending...
+    pub flush_max_pending: usize,
+    /// ...or the oldest has waited this long.
+    pub flush_max_age_ms: u64,
+    /// Compact once tombstones make up more than this share of the index.
+    pub max_tombstone_ratio: f64,
+    /// Queries sampled per recall check; 0 turns the check off.
+    pub recall_sample_size: usize,
+    pub recall_k: usize,
+    /// Rebuild when sampled recall falls below this.
+    pub min_recall: f64,
+    pub recall_check_interval_ms: u64,
+}
+
+impl Default for MaintainerConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 100,
+            flush_max_pending: 1_000,
+            flush_max_age_ms: 1_000,
+            max_tombstone_ratio: 0.2,
+            recall_sample_size: 20,
+            recall_k: 10,
+            min_recall: 0.9,
+            recall_check_interval_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceStats {
+    pub flushes: u64,
+    pub flushed_updates: u64,
+    pub compactions: u64,
+    pub compacted_entries: u64,
+    pub rebuilds: u64,
+    pub last_recall: Option<f64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceReport {
+    pub flushed: usize,
+    pub compacted: usize,
+    pub recall: Option<f64>,
+    /// A rebuild was started in the background.
+    pub rebuild_started: bool,
+}
+
+/// Keeps an `IncrementalIndex` in shape in the background: flushes pending
+/// updates, compacts tombstones and rebuilds the index when sampled recall
+/// drops.
+pub struct IndexMaintainer {
+    index: Arc<IncrementalIndex>,
+    config: MaintainerConfig,
+    stats: Arc<RwLock<MaintenanceStats>>,
+    last_recall_check: std::sync::Mutex<Option<Instant>>,
+    running: AtomicBool,
+    shutdown: Notify,
+}
+
+impl IndexMaintainer {
+    pub fn new(index: Arc<IncrementalIndex>, config: MaintainerConfig) -> Self {
+        Self {
+            index,
+            config,
+            stats: Arc::new(RwLock::new(MaintenanceStats::default())),
+            last_recall_check: std::sync::Mutex::new(None),
+            running: AtomicBool::new(false),
+            shutdown: Notify::new(),
+        }
+    }
+
+    pub fn config(&self) -> &MaintainerConfig {
+        &self.config
+    }
+
+    pub async fn stats(&self) -> MaintenanceStats {
+        self.stats.read().await.clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        self.running.store(true, Ordering::SeqCst);
+        let maintainer = self.clone();
+
+        tokio::spawn(async move {
+            let interval = Duration::from_millis(maintainer.config.interval_ms.max(1));
+
+            while maintainer.is_running() {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = maintainer.shutdown.notified() => break,
+                }
+
+                if !maintainer.is_running() {
+                    break;
+                }
+
+                let _ = maintainer.run_once().await;
+            }
+        })
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.shutdown.notify_one();
+    }
+
+    /// One maintenance pass. A rebuild it starts runs on its own task and
+    /// shows up in `stats` when it finishes.
+    pub async fn run_once(&self) -> Result<MaintenanceReport, String> {
+        let result = self.maintain().await;
+        if let Err(e) = &result {
+            self.stats.write().await.last_error = Some(e.clone());
+        }
+        result
+    }
+
+    async fn maintain(&self) -> Result<MaintenanceReport, String> {
+        let mut report = MaintenanceReport::default();
+
+        let flush_age = Duration::from_millis(self.config.flush_max_age_ms);
+        if self.index.pending_count().await >= self.config.flush_max_pending.max(1)
+            || self.index.pending_age().is_some_and(|age| age >= flush_age)
+        {
+            report.flushed = self.index.flush().await?;
+            let mut stats = self.stats.write().await;
+            stats.flushes += 1;
+            stats.flushed_updates += report.flushed as u64;
+        }
+
+        let tombstones = self.index.tombstone_count().await;
+        if tombstones > 0 && tombstones as f64 > self.config.max_tombstone_ratio * self.index.index_size().await as f64 {
+            report.compacted = self.index.compact().await?;
+            let mut stats = self.stats.write().await;
+            stats.compactions += 1;
+            stats.compacted_entries += report.compacted as u64;
+        }
+
+        if self.recall_check_due() && !self.index.is_rebuilding().await {
+            let recall = self.index.measure_recall(self.config.recall_sample_size, self.config.recall_k).await?;
+            report.recall = Some(recall);
+            self.stats.write().await.last_recall = Some(recall);
+
+            if recall < self.config.min_recall {
+                self.spawn_rebuild();
+                report.rebuild_started = true;
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn recall_check_due(&self) -> bool {
+        if self.config.recall_sample_size == 0 {
+            return false;
+        }
+
+        let mut last = self.last_recall_check.lock().unwrap();
+        let interval = Duration::from_millis(self.config.recall_check_interval_ms);
+        if last.is_some_and(|at| at.elapsed() < interval) {
+            return false;
+        }
+        *last = Some(Instant::now());
+        true
+    }
+
+    fn spawn_rebuild(&self) {
+        let index = self.index.clone();
+        let stats = self.stats.clone();
+
+        tokio::spawn(async move {
+            let result = index.rebuild().await;
+            let mut stats = stats.write().await;
+            match result {
+                Ok(()) => stats.rebuilds += 1,
+                Err(e) => stats.last_error = Some(e),
+            }
+        });
     }
 }
 
@@ -410,3 +792,168 @@ fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
     }
     1.0 - (dot / (norm_a * norm_b))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn unit(angle: f32) -> Vec<f32> {
+        vec![angle.cos(), angle.sin()]
+    }
+
+    async fn brute_force(dimension: usize) -> IncrementalIndex {
+        let index = IncrementalIndex::new(IndexType::BruteForce, IndexConfig::default());
+        index.initialize(dimension).await.unwrap();
+        index
+    }
+
+    /// Keeps only the first `capacity` vectors it is given, like an ANN
+    /// graph that stopped linking new nodes.
+    struct CappedIndex {
+        capacity: usize,
+        inner: BruteForceIndex,
+    }
+
+    impl IndexTrait for CappedIndex {
+        fn add(&mut self, id: String, vector: Vec<f32>) -> Result<(), String> {
+            if self.inner.size() < self.capacity {
+                self.inner.add(id, vector)?;
+            }
+            Ok(())
+        }
+
+        fn remove(&mut self, id: &str) -> Result<(), String> {
+            self.inner.remove(id)
+        }
+
+        fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>, String> {
+            self.inner.search(query, k)
+        }
+
+        fn size(&self) -> usize {
+            self.inner.size()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_sees_pending_updates_and_skips_tombstones() {
+        let index = brute_force(2).await;
+        index.add("a".to_string(), unit(0.0)).await.unwrap();
+        index.add("b".to_string(), unit(0.1)).await.unwrap();
+        index.add("c".to_string(), unit(1.5)).await.unwrap();
+
+        // Not flushed yet, still found
+        let results = index.search(&unit(0.0), 2).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(index.flush().await.unwrap(), 3);
+        assert_eq!(index.index_size().await, 3);
+
+        index.remove("a").await.unwrap();
+        index.update("c", unit(0.05)).await.unwrap();
+        let results = index.search(&unit(0.0), 2).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["c", "b"]);
+
+        // The removal becomes a tombstone, still skipped
+        index.flush().await.unwrap();
+        assert_eq!(index.tombstone_count().await, 1);
+        let results = index.search(&unit(0.0), 3).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.id != "a"));
+
+        assert_eq!(index.compact().await.unwrap(), 1);
+        assert_eq!(index.tombstone_count().await, 0);
+        assert_eq!(index.index_size().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_flush_applies_a_large_batch_in_chunks() {
+        let index = brute_force(2).await;
+        let count = FLUSH_CHUNK * 2 + 10;
+        for i in 0..count {
+            index.add(format!("v{}", i), unit(i as f32 / count as f32)).await.unwrap();
+        }
+        index.remove("v0").await.unwrap();
+
+        assert_eq!(index.flush().await.unwrap(), count + 1);
+        assert_eq!(index.pending_count().await, 0);
+        assert!(index.pending_age().is_none());
+        assert_eq!(index.index_size().await, count);
+        assert_eq!(index.tombstone_count().await, 1);
+        assert_eq!(index.search(&unit(0.0), 1).await.unwrap()[0].id, "v1");
+    }
+
+    #[tokio::test]
+    async fn test_maintainer_flushes_on_thresholds_and_compacts() {
+        let index = Arc::new(brute_force(2).await);
+        let maintainer = IndexMaintainer::new(index.clone(), MaintainerConfig {
+            flush_max_pending: 3,
+            flush_max_age_ms: 50,
+            max_tombstone_ratio: 0.1,
+            recall_sample_size: 0,
+            ..Default::default()
+        });
+
+        index.add("a".to_string(), unit(0.0)).await.unwrap();
+        index.add("b".to_string(), unit(0.5)).await.unwrap();
+        assert_eq!(maintainer.run_once().await.unwrap().flushed, 0);
+
+        index.add("c".to_string(), unit(1.0)).await.unwrap();
+        assert_eq!(maintainer.run_once().await.unwrap().flushed, 3);
+
+        // One update, flushed once it is old enough, and its tombstone compacted
+        index.remove("b").await.unwrap();
+        assert_eq!(maintainer.run_once().await.unwrap().flushed, 0);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let report = maintainer.run_once().await.unwrap();
+        assert_eq!(report.flushed, 1);
+        assert_eq!(report.compacted, 1);
+        assert_eq!(index.index_size().await, 2);
+
+        let stats = maintainer.stats().await;
+        assert_eq!(stats.flushes, 2);
+        assert_eq!(stats.flushed_updates, 4);
+        assert_eq!(stats.compactions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_recall_drop_triggers_background_rebuild() {
+        // The first index built degrades; rebuilds are exact
+        let builds = Arc::new(AtomicUsize::new(0));
+        let counter = builds.clone();
+        let builder: IndexBuilder = Arc::new(move |dimension| -> Box<dyn IndexTrait + Send + Sync> {
+            let capacity = if counter.fetch_add(1, Ordering::SeqCst) == 0 { 3 } else { usize::MAX };
+            Box::new(CappedIndex { capacity, inner: BruteForceIndex::new(dimension) })
+        });
+
+        let index = Arc::new(IncrementalIndex::new(IndexType::HNSW, IndexConfig::default()).with_builder(builder));
+        index.initialize(2).await.unwrap();
+        for i in 0..10 {
+            index.add(format!("v{}", i), unit(i as f32 * 0.15)).await.unwrap();
+        }
+        index.flush().await.unwrap();
+        assert!(index.measure_recall(10, 3).await.unwrap() < 0.9);
+
+        let maintainer = Arc::new(IndexMaintainer::new(index.clone(), MaintainerConfig {
+            interval_ms: 10,
+            recall_sample_size: 10,
+            recall_k: 3,
+            min_recall: 0.9,
+            ..Default::default()
+        }));
+        let handle = maintainer.start();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while maintainer.stats().await.rebuilds == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        maintainer.stop();
+        handle.await.unwrap();
+
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+        assert_eq!(index.index_size().await, 10);
+        assert_eq!(index.measure_recall(10, 3).await.unwrap(), 1.0);
+        assert!(maintainer.stats().await.last_recall.unwrap() < 0.9);
+    }
+}
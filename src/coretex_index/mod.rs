@@ -1,8 +1,12 @@
 //! Vector indexing for CortexDB
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 
+use crate::coretex_core::{IndexConfig, IndexType};
+use crate::coretex_monitoring_v2::MetricsCollector;
+
 /// Result of a vector search
 #[derive(Debug)]
 pub struct SearchResult {
@@ -132,6 +136,14 @@ impl HNSWIndex {
             max_level: 16, // Default maximum level
         }
     }
+
+    /// Set the graph construction and search parameters
+    pub fn with_params(mut self, m: usize, ef_construction: usize, ef_search: usize) -> Self {
+        self.m = m;
+        self.ef_construction = ef_construction;
+        self.ef_search = ef_search;
+        self
+    }
     
     /// Calculate distance between two vectors
     fn calculate_distance(&self, a: &[f32], b: &[f32]) -> f32 {
@@ -206,6 +218,13 @@ impl IVFIndex {
             vector_to_cluster: std::sync::Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new())),
         }
     }
+
+    /// Set the number of clusters and how many of them a search probes
+    pub fn with_params(mut self, nlist: usize, nprobe: usize) -> Self {
+        self.nlist = nlist;
+        self.nprobe = nprobe;
+        self
+    }
     
     /// Calculate distance between two vectors
     fn calculate_distance(&self, a: &[f32], b: &[f32]) -> f32 {
@@ -245,8 +264,8 @@ impl IVFIndex {
     }
     
     /// Assign a vector to the nearest centroid
-    fn assign_to_cluster(&self, vector: &[f32]) -> usize {
-        let centroids = self.centroids.blocking_read();
+    async fn assign_to_cluster(&self, vector: &[f32]) -> usize {
+        let centroids = self.centroids.read().await;
         if centroids.is_empty() {
             return 0;
         }
@@ -423,7 +442,7 @@ impl VectorIndex for IVFIndex {
         vectors.insert(id.to_string(), vector.to_vec());
         
         // Assign to cluster
-        let cluster_id = self.assign_to_cluster(vector);
+        let cluster_id = self.assign_to_cluster(vector).await;
         let mut vector_to_cluster = self.vector_to_cluster.write().await;
         vector_to_cluster.insert(id.to_string(), cluster_id);
         
@@ -567,9 +586,91 @@ impl VectorIndex for ScalarIndex {
     }
 }
 
+/// Writes go to both the serving index and the one being rebuilt; searches
+/// only see the serving index
+struct MirroredIndex {
+    primary: Box<dyn VectorIndex>,
+    shadow: Box<dyn VectorIndex>,
+}
+
+#[async_trait]
+impl VectorIndex for MirroredIndex {
+    async fn add(&self, id: &str, vector: &[f32]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.primary.add(id, vector).await?;
+        self.shadow.add(id, vector).await
+    }
+
+    async fn remove(&self, id: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let removed = self.primary.remove(id).await?;
+        self.shadow.remove(id).await?;
+        Ok(removed)
+    }
+
+    async fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync>> {
+        self.primary.search(query, k).await
+    }
+
+    async fn build(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.primary.build().await
+    }
+
+    async fn clear(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.primary.clear().await?;
+        self.shadow.clear().await
+    }
+
+    fn clone_box(&self) -> Box<dyn VectorIndex> {
+        Box::new(MirroredIndex {
+            primary: self.primary.clone_box(),
+            shadow: self.shadow.clone_box(),
+        })
+    }
+}
+
+/// State of an index rebuild
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RebuildStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Progress of the latest rebuild of an index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebuildProgress {
+    pub index_name: String,
+    pub index_type: IndexType,
+    pub status: RebuildStatus,
+    /// Vectors to backfill, counted when the rebuild started
+    pub total: usize,
+    pub indexed: usize,
+    pub started_at_ms: u64,
+    pub finished_at_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl RebuildProgress {
+    /// Fraction of the backfill done, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.indexed as f64 / self.total as f64).min(1.0)
+    }
+}
+
+struct Rebuild {
+    progress: RebuildProgress,
+    /// Serving index and its replacement, while running
+    indexes: Option<(Box<dyn VectorIndex>, Box<dyn VectorIndex>)>,
+}
+
 /// Index manager for handling multiple indexes
 pub struct IndexManager {
     indexes: std::sync::Arc<tokio::sync::RwLock<std::collections::HashMap<String, Box<dyn VectorIndex>>>>,
+    rebuilds: std::sync::Arc<tokio::sync::RwLock<std::collections::HashMap<String, Rebuild>>>,
+    metrics: Option<MetricsCollector>,
 }
 
 impl IndexManager {
@@ -577,8 +678,16 @@ impl IndexManager {
     pub fn new() -> Self {
         Self {
             indexes: std::sync::Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new())),
+            rebuilds: std::sync::Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new())),
+            metrics: None,
         }
     }
+
+    /// Report rebuild progress to `metrics`
+    pub fn with_metrics(mut self, metrics: MetricsCollector) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
     
     /// Create a new index
     pub async fn create_index(&self, name: &str, index_type: &str, metric: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -595,6 +704,41 @@ impl IndexManager {
         indexes.insert(name.to_string(), index);
         Ok(())
     }
+
+    /// Create an index from a configuration
+    pub async fn create_index_with_config(&self, name: &str, config: &IndexConfig, metric: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let index = Self::build_index(config, metric)?;
+        self.indexes.write().await.insert(name.to_string(), index);
+        Ok(())
+    }
+
+    /// Create an empty index from a configuration. Parameters missing from
+    /// `config.parameters` keep their defaults.
+    pub fn build_index(config: &IndexConfig, metric: &str) -> Result<Box<dyn VectorIndex>, Box<dyn Error + Send + Sync>> {
+        let param = |key: &str, default: usize| -> Result<usize, Box<dyn Error + Send + Sync>> {
+            match config.parameters.get(key) {
+                None => Ok(default),
+                Some(value) => value.as_u64()
+                    .filter(|v| *v > 0)
+                    .map(|v| v as usize)
+                    .ok_or_else(|| format!("Index parameter {} must be a positive integer", key).into()),
+            }
+        };
+
+        Ok(match config.index_type {
+            IndexType::BruteForce => Box::new(BruteForceIndex::new(metric)),
+            IndexType::HNSW => Box::new(HNSWIndex::new(metric).with_params(
+                param("m", 16)?,
+                param("ef_construction", 200)?,
+                param("ef_search", 50)?,
+            )),
+            IndexType::IVF => Box::new(IVFIndex::new(metric).with_params(
+                param("nlist", 100)?,
+                param("nprobe", 10)?,
+            )),
+            IndexType::Scalar => Box::new(ScalarIndex::new()),
+        })
+    }
     
     /// Get an index by name
     pub async fn get_index(&self, name: &str) -> Result<Option<Box<dyn VectorIndex + 'static>>, Box<dyn Error + Send + Sync>> {
@@ -608,8 +752,165 @@ impl IndexManager {
     /// Delete an index
     pub async fn delete_index(&self, name: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
         let mut indexes = self.indexes.write().await;
+        self.rebuilds.write().await.remove(name);
         Ok(indexes.remove(name).is_some())
     }
+
+    /// Takes every index of `other` in place of these, in one step for
+    /// searches. Rebuilds of the replaced indexes are dropped.
+    pub(crate) async fn replace_with(&self, other: &IndexManager) {
+        let replacement = std::mem::take(&mut *other.indexes.write().await);
+        let mut indexes = self.indexes.write().await;
+        self.rebuilds.write().await.clear();
+        *indexes = replacement;
+    }
+
+    /// Start replacing an index with an empty one built from `config`.
+    /// Until `finish_rebuild`, the old index keeps serving searches and
+    /// writes through `get_index` reach both. Returns the new index for the
+    /// caller to backfill with the `total` vectors already stored.
+    pub async fn begin_rebuild(&self, name: &str, config: &IndexConfig, metric: &str, total: usize) -> Result<Box<dyn VectorIndex>, Box<dyn Error + Send + Sync>> {
+        let shadow = Self::build_index(config, metric)?;
+
+        let mut indexes = self.indexes.write().await;
+        let mut rebuilds = self.rebuilds.write().await;
+        if rebuilds.get(name).is_some_and(|r| r.progress.status == RebuildStatus::Running) {
+            return Err(format!("Index {} is already being rebuilt", name).into());
+        }
+        let primary = indexes.get(name)
+            .map(|index| index.clone_box())
+            .ok_or_else(|| format!("Index {} not found", name))?;
+
+        indexes.insert(name.to_string(), Box::new(MirroredIndex {
+            primary: primary.clone_box(),
+            shadow: shadow.clone_box(),
+        }));
+        let progress = RebuildProgress {
+            index_name: name.to_string(),
+            index_type: config.index_type.clone(),
+            status: RebuildStatus::Running,
+            total,
+            indexed: 0,
+            started_at_ms: now_ms(),
+            finished_at_ms: None,
+            error: None,
+        };
+        rebuilds.insert(name.to_string(), Rebuild {
+            progress: progress.clone(),
+            indexes: Some((primary, shadow.clone_box())),
+        });
+        drop(rebuilds);
+        drop(indexes);
+
+        self.record_metrics(&progress).await;
+        Ok(shadow)
+    }
+
+    /// Record that `indexed` vectors have been backfilled
+    pub async fn record_rebuild_progress(&self, name: &str, indexed: usize) {
+        let progress = {
+            let mut rebuilds = self.rebuilds.write().await;
+            match rebuilds.get_mut(name) {
+                Some(rebuild) if rebuild.progress.status == RebuildStatus::Running => {
+                    rebuild.progress.indexed = indexed;
+                    rebuild.progress.clone()
+                }
+                _ => return,
+            }
+        };
+        self.record_metrics(&progress).await;
+    }
+
+    /// Swap the rebuilt index in for the old one
+    pub async fn finish_rebuild(&self, name: &str) -> Result<RebuildProgress, Box<dyn Error + Send + Sync>> {
+        let shadow = {
+            let rebuilds = self.rebuilds.read().await;
+            rebuilds.get(name)
+                .and_then(|r| r.indexes.as_ref())
+                .map(|(_, shadow)| shadow.clone_box())
+                .ok_or_else(|| format!("Index {} is not being rebuilt", name))?
+        };
+        shadow.build().await?;
+
+        let mut indexes = self.indexes.write().await;
+        let mut rebuilds = self.rebuilds.write().await;
+        let rebuild = rebuilds.get_mut(name)
+            .filter(|r| r.indexes.is_some())
+            .ok_or_else(|| format!("Index {} is not being rebuilt", name))?;
+        indexes.insert(name.to_string(), shadow);
+        rebuild.indexes = None;
+        rebuild.progress.status = RebuildStatus::Completed;
+        rebuild.progress.indexed = rebuild.progress.indexed.max(rebuild.progress.total);
+        rebuild.progress.finished_at_ms = Some(now_ms());
+        let progress = rebuild.progress.clone();
+        drop(rebuilds);
+        drop(indexes);
+
+        self.record_metrics(&progress).await;
+        Ok(progress)
+    }
+
+    /// Give up on a rebuild and keep serving from the old index
+    pub async fn abort_rebuild(&self, name: &str, error: &str) {
+        let mut indexes = self.indexes.write().await;
+        let mut rebuilds = self.rebuilds.write().await;
+        let Some(rebuild) = rebuilds.get_mut(name) else {
+            return;
+        };
+        let Some((primary, _)) = rebuild.indexes.take() else {
+            return;
+        };
+
+        if indexes.contains_key(name) {
+            indexes.insert(name.to_string(), primary);
+        }
+        rebuild.progress.status = RebuildStatus::Failed;
+        rebuild.progress.finished_at_ms = Some(now_ms());
+        rebuild.progress.error = Some(error.to_string());
+        let progress = rebuild.progress.clone();
+        drop(rebuilds);
+        drop(indexes);
+
+        self.record_metrics(&progress).await;
+    }
+
+    /// Progress of the running or latest rebuild of an index
+    pub async fn rebuild_progress(&self, name: &str) -> Option<RebuildProgress> {
+        self.rebuilds.read().await.get(name).map(|r| r.progress.clone())
+    }
+
+    async fn record_metrics(&self, progress: &RebuildProgress) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        let labels: std::collections::HashMap<String, String> = [("index".to_string(), progress.index_name.clone())].into();
+
+        metrics.set_gauge("coretex_index_rebuild_progress", progress.fraction(), labels.clone()).await;
+        metrics.set_gauge("coretex_index_rebuild_vectors_indexed", progress.indexed as f64, labels.clone()).await;
+        metrics.set_gauge("coretex_index_rebuild_running", (progress.status == RebuildStatus::Running) as u8 as f64, labels.clone()).await;
+
+        if let Some(finished_at_ms) = progress.finished_at_ms {
+            let status = match progress.status {
+                RebuildStatus::Failed => "failure",
+                _ => "success",
+            };
+            let mut status_labels = labels.clone();
+            status_labels.insert("status".to_string(), status.to_string());
+            metrics.increment_counter("coretex_index_rebuilds_total", 1.0, status_labels).await;
+            metrics.observe_histogram(
+                "coretex_index_rebuild_duration_seconds",
+                finished_at_ms.saturating_sub(progress.started_at_ms) as f64 / 1000.0,
+                labels,
+            ).await;
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
 }
 
 pub struct PQIndex {
@@ -137,3 +137,69 @@ async fn test_index_manager() {
     let index = manager.get_index("test-index").await.unwrap();
     assert!(index.is_none());
 }
+
+#[tokio::test]
+async fn test_index_manager_rebuild_mirrors_writes_and_swaps() {
+    let metrics = MetricsCollector::new(crate::coretex_monitoring_v2::MonitoringConfig::default());
+    let manager = IndexManager::new().with_metrics(metrics.clone());
+    manager.create_index("docs", "hnsw", "cosine").await.unwrap();
+    let old = manager.get_index("docs").await.unwrap().unwrap();
+    old.add("vec1", &[1.0, 0.0, 0.0]).await.unwrap();
+
+    let config = IndexConfig {
+        name: "docs_ivf".to_string(),
+        index_type: IndexType::IVF,
+        parameters: [("nlist".to_string(), serde_json::json!(8))].into(),
+    };
+    let shadow = manager.begin_rebuild("docs", &config, "cosine", 1).await.unwrap();
+    assert!(manager.begin_rebuild("docs", &config, "cosine", 1).await.is_err());
+
+    // Writes during the rebuild reach both; searches still see the old index
+    let serving = manager.get_index("docs").await.unwrap().unwrap();
+    serving.add("vec2", &[0.0, 1.0, 0.0]).await.unwrap();
+    assert_eq!(old.search(&[0.0, 1.0, 0.0], 1).await.unwrap()[0].id, "vec2");
+    assert_eq!(shadow.search(&[0.0, 1.0, 0.0], 1).await.unwrap()[0].id, "vec2");
+    assert_eq!(serving.search(&[1.0, 0.0, 0.0], 2).await.unwrap().len(), 2);
+
+    shadow.add("vec1", &[1.0, 0.0, 0.0]).await.unwrap();
+    manager.record_rebuild_progress("docs", 1).await;
+    let progress = manager.finish_rebuild("docs").await.unwrap();
+    assert_eq!(progress.status, RebuildStatus::Completed);
+    assert_eq!(progress.indexed, 1);
+
+    // The old index no longer sees writes
+    let serving = manager.get_index("docs").await.unwrap().unwrap();
+    serving.add("vec3", &[0.0, 0.0, 1.0]).await.unwrap();
+    assert_eq!(serving.search(&[1.0, 0.0, 0.0], 5).await.unwrap().len(), 3);
+    assert_eq!(old.search(&[1.0, 0.0, 0.0], 5).await.unwrap().len(), 2);
+
+    let gauge = metrics.get_all_metrics().await.into_iter().find_map(|m| match m {
+        crate::coretex_monitoring_v2::Metric::Gauge(g) if g.name == "coretex_index_rebuild_progress" => Some(g.value),
+        _ => None,
+    });
+    assert_eq!(gauge, Some(1.0));
+}
+
+#[tokio::test]
+async fn test_index_manager_abort_rebuild_keeps_old_index() {
+    let manager = IndexManager::new();
+    manager.create_index("docs", "hnsw", "cosine").await.unwrap();
+
+    let config = IndexConfig {
+        name: "docs_hnsw".to_string(),
+        index_type: IndexType::HNSW,
+        parameters: [("m".to_string(), serde_json::json!(-1))].into(),
+    };
+    assert!(manager.begin_rebuild("docs", &config, "cosine", 0).await.is_err());
+
+    let config = IndexConfig { parameters: [("m".to_string(), serde_json::json!(32))].into(), ..config };
+    let shadow = manager.begin_rebuild("docs", &config, "cosine", 0).await.unwrap();
+    manager.abort_rebuild("docs", "disk full").await;
+
+    let progress = manager.rebuild_progress("docs").await.unwrap();
+    assert_eq!(progress.status, RebuildStatus::Failed);
+    assert_eq!(progress.error.as_deref(), Some("disk full"));
+
+    manager.get_index("docs").await.unwrap().unwrap().add("vec1", &[1.0, 0.0]).await.unwrap();
+    assert!(shadow.search(&[1.0, 0.0], 1).await.unwrap().is_empty());
+}
@@ -0,0 +1,740 @@
+//! Journal of CoreTexDB changes
+//! A database from `CoreTexDB::open` appends every change to the write-ahead
+//! log in `wal_dir` before applying it, keeps documents in file storage and
+//! replays the log on the next `open`, so an acknowledged change survives a
+//! crash. Checkpoints drop the closed segments whose entries storage already
+//! holds. Backups archive the same log for point-in-time restores.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Mutex;
+
+use crate::coretex_side_vectors::{self as side_vectors, SideVectors};
+use crate::coretex_transaction::{VersionChanges, VersionKey, VersionValue};
+use crate::coretex_utils::wal::{WalEntry, WalEntryType, WriteAheadLog};
+use crate::{CollectionSchema, CoreTexDB, CoreTexError, DbConfig};
+
+/// The write-ahead log of a durable database. Databases from `new` and
+/// `with_config` keep none until `open`.
+#[derive(Default)]
+pub struct Journal {
+    wal: Mutex<Option<WriteAheadLog>>,
+    /// Entries appended but not yet applied to storage
+    unapplied: std::sync::Mutex<BTreeSet<u64>>,
+    /// Set when an append closes a segment, which a checkpoint may drop
+    segment_closed: AtomicBool,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the log in `dir`, closing segments at `segment_bytes` and
+    /// resuming after its last entry, or after `applied_lsn` when a
+    /// checkpoint dropped the segments up to it.
+    pub async fn open(&self, dir: &str, segment_bytes: u64, applied_lsn: u64) -> crate::Result<()> {
+        let mut wal = WriteAheadLog::new(dir).with_max_file_size(segment_bytes);
+        wal.init().await?;
+        wal.resume_after(applied_lsn).await;
+        *self.wal.lock().await = Some(wal);
+        Ok(())
+    }
+
+    pub async fn is_open(&self) -> bool {
+        self.wal.lock().await.is_some()
+    }
+
+    /// Durably appends an entry, or does nothing without a log. The entry
+    /// counts as unapplied until `applied` is called with its LSN.
+    pub async fn append(&self, entry_type: WalEntryType, collection: &str, data: serde_json::Value) -> crate::Result<Option<WalEntry>> {
+        let mut wal = self.wal.lock().await;
+        let Some(wal) = wal.as_mut() else {
+            return Ok(None);
+        };
+
+        let segment = wal.current_segment().clone();
+        let entry = wal.create_entry(entry_type, collection, data).await?;
+        self.unapplied.lock().unwrap().insert(entry.id);
+        if *wal.current_segment() != segment {
+            self.segment_closed.store(true, Ordering::Release);
+        }
+        Ok(Some(entry))
+    }
+
+    /// Marks an entry's changes as in storage. An entry whose changes
+    /// failed stays unapplied, so checkpoints keep it for the next `open`
+    /// to replay.
+    pub fn applied(&self, lsn: u64) {
+        self.unapplied.lock().unwrap().remove(&lsn);
+    }
+
+    /// Whether a segment was closed since the last call.
+    pub fn take_segment_closed(&self) -> bool {
+        self.segment_closed.swap(false, Ordering::AcqRel)
+    }
+
+    /// The last LSN up to which every entry is applied.
+    pub async fn applied_through(&self) -> u64 {
+        let last_lsn = self.last_lsn().await;
+        match self.unapplied.lock().unwrap().first() {
+            Some(lsn) => lsn - 1,
+            None => last_lsn,
+        }
+    }
+
+    /// Drops the closed segments holding only entries up to `lsn` written
+    /// before `keep_since_ms`. Returns the number of segments dropped.
+    pub async fn truncate(&self, lsn: u64, keep_since_ms: u64) -> crate::Result<usize> {
+        let mut wal = self.wal.lock().await;
+        let Some(wal) = wal.as_mut() else {
+            return Ok(0);
+        };
+
+        // Entries in the `as_of` window are still read by `load_history`.
+        // Segments go oldest first, so this stops at the first one to keep
+        // rather than reading the whole log on every checkpoint.
+        Ok(wal.drop_segments_while(|entry| entry.id <= lsn && entry_timestamp_ms(entry) < keep_since_ms).await?)
+    }
+
+    /// The LSN of the last entry, 0 without a log.
+    pub async fn last_lsn(&self) -> u64 {
+        match self.wal.lock().await.as_ref() {
+            Some(wal) => wal.last_lsn().await,
+            None => 0,
+        }
+    }
+
+    /// Up to `limit` entries right after `lsn`, oldest first, or `None` when
+    /// the log no longer reaches back to them. Reads only the newest
+    /// segments that hold them.
+    pub async fn tail(&self, lsn: u64, limit: usize) -> crate::Result<Option<Vec<WalEntry>>> {
+        let wal = self.wal.lock().await;
+        let Some(wal) = wal.as_ref() else {
+            return Err(CoreTexError::ConfigError("The database keeps no journal".to_string()));
+        };
+
+        let mut segments = Vec::new();
+        let mut reaches_back = false;
+        for segment in wal.segment_files().await?.into_iter().rev() {
+            let entries = WriteAheadLog::read_segment(&segment).await?;
+            let first = entries.first().map(|entry| entry.id);
+            segments.push(entries);
+            if first.is_some_and(|first| first <= lsn + 1) {
+                reaches_back = true;
+                break;
+            }
+        }
+        if !reaches_back && lsn < wal.last_lsn().await {
+            return Ok(None);
+        }
+
+        Ok(Some(segments.into_iter().rev().flatten()
+            .filter(|entry| entry.id > lsn)
+            .take(limit)
+            .collect()))
+    }
+
+    /// Entries with an LSN above `lsn`, oldest first.
+    pub async fn entries_after(&self, lsn: u64) -> crate::Result<Vec<WalEntry>> {
+        match self.wal.lock().await.as_ref() {
+            Some(wal) => Ok(wal.read_all().await?.into_iter().filter(|e| e.id > lsn).collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// The journal entry for document writes: an `Insert` of the puts or a
+/// `Delete` of the deletes of one collection, in the record format backups
+/// replay, or a `Transaction` of several when the writes need more than one.
+pub(crate) fn write_entry(writes: &[(VersionKey, VersionValue)]) -> Option<(WalEntryType, String, serde_json::Value)> {
+    let mut collections: Vec<&str> = writes.iter().map(|((collection, _), _)| collection.as_str()).collect();
+    collections.dedup();
+
+    let mut entries = Vec::new();
+    for collection in collections {
+        let (puts, deletes): (Vec<_>, Vec<_>) = writes.iter()
+            .filter(|((c, _), _)| c == collection)
+            .partition(|(_, value)| value.is_some());
+        if !puts.is_empty() {
+            let vectors: Vec<serde_json::Value> = puts.iter()
+                .filter_map(|((_, id), value)| value.as_ref().map(|(vector, metadata)| {
+                    serde_json::json!({"id": id, "vector": vector, "metadata": metadata})
+                }))
+                .collect();
+            entries.push((WalEntryType::Insert, collection.to_string(), serde_json::json!({"vectors": vectors})));
+        }
+        if !deletes.is_empty() {
+            let ids: Vec<&String> = deletes.iter().map(|((_, id), _)| id).collect();
+            entries.push((WalEntryType::Delete, collection.to_string(), serde_json::json!({"ids": ids})));
+        }
+    }
+
+    if entries.len() <= 1 {
+        return entries.pop();
+    }
+    let parts: Vec<serde_json::Value> = entries.into_iter()
+        .map(|(entry_type, collection, data)| serde_json::json!({
+            "entry_type": entry_type,
+            "collection": collection,
+            "data": data,
+        }))
+        .collect();
+    Some((WalEntryType::Transaction, String::new(), serde_json::json!({"entries": parts})))
+}
+
+/// Adds to an entry's data the versions its writes replace and when, so
+/// `as_of` history survives a restart.
+pub(crate) fn with_replaced(mut data: serde_json::Value, replaced: &[(VersionKey, VersionValue)]) -> serde_json::Value {
+    let replaced: Vec<serde_json::Value> = replaced.iter()
+        .map(|((collection, id), before)| serde_json::json!({
+            "collection": collection,
+            "id": id,
+            "before": before.as_ref().map(|(vector, metadata)| serde_json::json!({"vector": vector, "metadata": metadata})),
+        }))
+        .collect();
+    data["replaced"] = serde_json::json!(replaced);
+    data["timestamp_ms"] = serde_json::json!(crate::coretex_transaction::now_ms());
+    data
+}
+
+/// When an entry was written, to the millisecond if it records it.
+fn entry_timestamp_ms(entry: &WalEntry) -> u64 {
+    entry.data["timestamp_ms"].as_u64().unwrap_or(entry.timestamp * 1000)
+}
+
+/// When an entry was written and the versions it replaced, if it records
+/// them.
+fn entry_history(entry: &WalEntry) -> crate::Result<Option<(u64, VersionChanges)>> {
+    let (Some(timestamp_ms), Some(replaced)) = (entry.data["timestamp_ms"].as_u64(), entry.data["replaced"].as_array()) else {
+        return Ok(None);
+    };
+
+    let mut changes = Vec::with_capacity(replaced.len());
+    for record in replaced {
+        let (Some(collection), Some(id)) = (record["collection"].as_str(), record["id"].as_str()) else {
+            return Err(CoreTexError::ValidationError(format!("Journal entry {} has a malformed version", entry.id)));
+        };
+        let before = match &record["before"] {
+            serde_json::Value::Null => None,
+            before => Some((serde_json::from_value(before["vector"].clone())?, before["metadata"].clone())),
+        };
+        changes.push(((collection.to_string(), id.to_string()), before));
+    }
+    Ok(Some((timestamp_ms, changes)))
+}
+
+/// The writes a journal entry records.
+pub(crate) fn entry_writes(entry: &WalEntry) -> crate::Result<Vec<(VersionKey, VersionValue)>> {
+    let key = |id: &str| (entry.collection.clone(), id.to_string());
+    match entry.entry_type {
+        WalEntryType::Insert | WalEntryType::Update => {
+            let records = match entry.data.get("vectors").and_then(|v| v.as_array()) {
+                Some(batch) => batch.iter().collect(),
+                None => vec![&entry.data],
+            };
+            records.into_iter()
+                .map(|record| {
+                    let id = record["id"].as_str()
+                        .ok_or_else(|| CoreTexError::ValidationError(format!("Journal entry {} has no vector id", entry.id)))?;
+                    let vector: Vec<f32> = serde_json::from_value(record["vector"].clone())?;
+                    let metadata = record.get("metadata").cloned().unwrap_or(serde_json::Value::Null);
+                    Ok((key(id), Some((vector, metadata))))
+                })
+                .collect()
+        }
+        WalEntryType::Delete => {
+            let ids: Vec<String> = match entry.data.get("ids") {
+                Some(ids) => serde_json::from_value(ids.clone())?,
+                None => entry.data["id"].as_str().map(|id| vec![id.to_string()]).unwrap_or_default(),
+            };
+            Ok(ids.iter().map(|id| (key(id), None)).collect())
+        }
+        WalEntryType::CreateCollection | WalEntryType::DeleteCollection => Ok(Vec::new()),
+        WalEntryType::Transaction => {
+            let mut writes = Vec::new();
+            for part in entry.parts() {
+                if !matches!(part.entry_type, WalEntryType::Transaction) {
+                    writes.extend(entry_writes(&part)?);
+                }
+            }
+            Ok(writes)
+        }
+    }
+}
+
+/// Where a collection's schema is kept next to its documents.
+pub(crate) fn schema_path(data_dir: &str, collection: &str) -> PathBuf {
+    PathBuf::from(data_dir).join("collections").join(collection).join("schema.json")
+}
+
+/// Writes a collection's schema next to its documents, on disk before
+/// a checkpoint can drop the entry that created it.
+pub(crate) fn write_schema(data_dir: &str, schema: &CollectionSchema) -> crate::Result<()> {
+    let path = schema_path(data_dir, &schema.name);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(write_durably(&path, &serde_json::to_vec_pretty(schema)?)?)
+}
+
+/// Replaces a file through a synced temporary copy, so a crash leaves the
+/// old or the new content.
+pub(crate) fn write_durably(path: &std::path::Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    // Next to the file under its whole name, so files differing only in
+    // extension never share one
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    sync_parent(path)
+}
+
+/// Makes a file's creation, replacement or removal survive a crash.
+pub(crate) fn sync_parent(path: &std::path::Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::File::open(dir)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+/// Reads a file of JSON records, one per line, as `append_json_lines` and
+/// `write_json_lines` leave it. A final line without its newline is a write
+/// that never completed and is dropped; anything unreadable before it is
+/// corruption, reported as `InvalidData`. Also tells whether a line was
+/// dropped, so the file can be rewritten before appending to it again.
+pub(crate) fn read_json_lines<T: serde::de::DeserializeOwned>(path: &std::path::Path) -> std::io::Result<(Vec<T>, bool)> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), false)),
+        Err(e) => return Err(e),
+    };
+
+    let lines: Vec<&[u8]> = data.split(|byte| *byte == b'\n').collect();
+    let torn = lines.last().is_some_and(|line| !line.is_empty());
+    let mut records = Vec::with_capacity(lines.len());
+    for (i, line) in lines[..lines.len() - 1].iter().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let record = serde_json::from_slice(line).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+        })?;
+        records.push(record);
+    }
+    Ok((records, torn))
+}
+
+/// Appends records to a file of JSON lines, returning once they are on disk.
+pub(crate) fn append_json_lines<T: serde::Serialize>(path: &std::path::Path, records: &[T]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if records.is_empty() {
+        return Ok(());
+    }
+    let created = !path.exists();
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&encode_json_lines(records)?)?;
+    file.sync_data()?;
+    if created {
+        sync_parent(path)?;
+    }
+    Ok(())
+}
+
+/// Replaces a file of JSON lines with `records`, durably.
+pub(crate) fn write_json_lines<T: serde::Serialize>(path: &std::path::Path, records: &[T]) -> std::io::Result<()> {
+    write_durably(path, &encode_json_lines(records)?)
+}
+
+fn encode_json_lines<T: serde::Serialize>(records: &[T]) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut data, record)?;
+        data.push(b'\n');
+    }
+    Ok(data)
+}
+
+impl CoreTexDB {
+    /// Opens a durable database: loads the collections and documents kept
+    /// in `config.data_dir` and replays the journal entries written after
+    /// them. Every change is journaled from then on.
+    pub async fn open(mut config: DbConfig) -> crate::Result<Self> {
+        if config.memory_only {
+            return Err(CoreTexError::ConfigError("A memory-only database cannot be opened".to_string()));
+        }
+
+        // Recovery reads documents back from storage
+        config.file_storage = true;
+        let db = Self::with_config(config);
+        db.init().await?;
+        let applied_lsn = db.load_metadata().await?.applied_lsn;
+        db.journal.open(&db.config.wal_dir, db.config.wal_segment_bytes, applied_lsn).await?;
+        db.recover().await?;
+        Ok(db)
+    }
+
+    /// Records the applied changes of the journal entry at `lsn`, if any,
+    /// and checkpoints when the journal closed a segment since the last
+    /// checkpoint.
+    pub(crate) async fn entry_applied(&self, lsn: Option<u64>) -> crate::Result<()> {
+        if let Some(lsn) = lsn {
+            self.journal.applied(lsn);
+        }
+        if self.journal.take_segment_closed() {
+            self.checkpoint().await?;
+        }
+        Ok(())
+    }
+
+    /// Records in the metadata that storage holds every journal entry up to
+    /// the last one applied, then drops the journal segments holding only
+    /// such entries, keeping those in the `as_of` retention window. Storage
+    /// writes are synced as they are made, so the dropped entries are never
+    /// needed again; archive the journal with `BackupManager::archive_wal`
+    /// first for point-in-time restores to reach them. Returns the number
+    /// of segments dropped.
+    pub async fn checkpoint(&self) -> crate::Result<usize> {
+        if !self.journal.is_open().await {
+            return Ok(0);
+        }
+
+        let applied_lsn = self.journal.applied_through().await;
+        let mut metadata = self.load_metadata().await?;
+        if applied_lsn > metadata.applied_lsn {
+            metadata.applied_lsn = applied_lsn;
+            metadata.last_modified = chrono::Utc::now().timestamp() as u64;
+            self.save_metadata(&metadata).await?;
+        }
+
+        let keep_since_ms = crate::coretex_transaction::now_ms().saturating_sub(self.versions.config().retention_ms);
+        self.journal.truncate(metadata.applied_lsn, keep_since_ms).await
+    }
+
+    async fn recover(&self) -> crate::Result<()> {
+        // Schemas first, so documents find their collections and indexes
+        let collections_dir = PathBuf::from(&self.config.data_dir).join("collections");
+        if collections_dir.exists() {
+            let mut collections = self.collections.write().await;
+            for entry in std::fs::read_dir(&collections_dir)? {
+                let path = entry?.path().join("schema.json");
+                if path.exists() {
+                    let schema: CollectionSchema = serde_json::from_slice(&std::fs::read(&path)?)?;
+                    self.install_schema(&mut collections, schema).await?;
+                }
+            }
+        }
+
+        {
+            let collections = self.collections.read().await;
+            let mut data = self.data.write().await;
+            let storage = self.storage.read().await;
+            let keys = storage.list().await
+                .map_err(|e| CoreTexError::StorageError(e.to_string()))?;
+            for key in keys {
+                let Some((collection, id)) = key.split_once(':') else {
+                    continue;
+                };
+                if let Some(main) = side_vectors::main_collection(collection).filter(|main| collections.contains_key(*main)) {
+                    let side = storage.retrieve(&key).await
+                        .map_err(|e| CoreTexError::StorageError(e.to_string()))?;
+                    self.install_side_vectors(main, id, SideVectors::from_value(&side)?).await?;
+                    continue;
+                }
+                if !collections.contains_key(collection) {
+                    continue;
+                }
+                let document = storage.retrieve(&key).await
+                    .map_err(|e| CoreTexError::StorageError(e.to_string()))?;
+                if let Some((vector, metadata)) = document {
+                    if let Some(index) = self.index_manager.get_index(&format!("{}_hnsw", collection)).await
+                        .map_err(|e| CoreTexError::IndexError(e.to_string()))?
+                    {
+                        index.add(id, &vector).await
+                            .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+                    }
+                    data.entry(collection.to_string()).or_default().insert(id.to_string(), (vector, metadata));
+                }
+            }
+        }
+
+        // Entries may already be in storage if the crash came after they
+        // were applied; replaying them again leaves the same state
+        let mut metadata = self.load_metadata().await?;
+        for entry in self.journal.entries_after(metadata.applied_lsn).await? {
+            self.replay_entry(&entry).await?;
+        }
+
+        self.load_history().await?;
+
+        metadata.applied_lsn = self.journal.last_lsn().await;
+        metadata.collections = self.list_collections().await?;
+        metadata.last_modified = chrono::Utc::now().timestamp() as u64;
+        self.save_metadata(&metadata).await?;
+        self.checkpoint().await.map(|_| ())
+    }
+
+    /// Rebuilds the `as_of` history from the journal entries in the
+    /// retention window. Nothing before the journal's first entry is known.
+    async fn load_history(&self) -> crate::Result<()> {
+        let entries = self.journal.entries_after(0).await?;
+        let cutoff_ms = crate::coretex_transaction::now_ms().saturating_sub(self.versions.config().retention_ms);
+        let started_ms = entries.first()
+            .map(entry_timestamp_ms)
+            .unwrap_or_else(crate::coretex_transaction::now_ms);
+
+        let mut history = Vec::new();
+        for entry in &entries {
+            if let Some((timestamp_ms, changes)) = entry_history(entry)? {
+                if timestamp_ms >= cutoff_ms {
+                    history.push((timestamp_ms, changes));
+                }
+            }
+        }
+        self.versions.load(history, cutoff_ms.max(started_ms));
+        Ok(())
+    }
+
+    /// Puts a collection in place as another database described it, e.g. a
+    /// leader's, with its vector fields and multi-vector settings. An
+    /// existing collection of that name takes the new schema.
+    pub async fn put_collection_schema(&self, schema: CollectionSchema) -> crate::Result<()> {
+        let mut collections = self.collections.write().await;
+        self.change_schema(&mut collections, schema).await
+    }
+
+    /// Applies the document writes of an entry from another log, e.g. a
+    /// leader's, journaling them here as one entry.
+    pub(crate) async fn apply_entry_writes(&self, entry: &WalEntry) -> crate::Result<()> {
+        let writes: BTreeMap<_, _> = entry_writes(entry)?.into_iter().collect();
+        let mut data = self.data.write().await;
+        self.apply_writes(&mut data, writes).await
+    }
+
+    /// Applies a journal entry without journaling it again or recording the
+    /// versions it replaced, which `load_history` reads from the journal.
+    pub(crate) async fn replay_entry(&self, entry: &WalEntry) -> crate::Result<()> {
+        match entry.entry_type {
+            WalEntryType::CreateCollection => {
+                let schema: CollectionSchema = serde_json::from_value(entry.data["schema"].clone())?;
+                let mut collections = self.collections.write().await;
+                self.install_schema(&mut collections, schema).await
+            }
+            WalEntryType::DeleteCollection => {
+                let mut collections = self.collections.write().await;
+                if !collections.contains_key(&entry.collection) {
+                    return Ok(());
+                }
+                let mut data = self.data.write().await;
+                self.drop_collection(&mut collections, &mut data, &entry.collection).await
+            }
+            WalEntryType::Insert | WalEntryType::Update | WalEntryType::Delete | WalEntryType::Transaction => {
+                let writes: BTreeMap<_, _> = entry_writes(entry)?.into_iter().collect();
+                let mut data = self.data.write().await;
+                self.replay_logged_writes(&mut data, writes).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(root: &std::path::Path) -> DbConfig {
+        DbConfig::new(&root.to_string_lossy())
+    }
+
+    #[tokio::test]
+    async fn test_open_recovers_collections_and_documents() {
+        let root = tempfile::tempdir().unwrap();
+        {
+            let db = CoreTexDB::open(config(root.path())).await.unwrap();
+            db.create_collection("docs", 2, "cosine").await.unwrap();
+            db.create_collection("gone", 2, "cosine").await.unwrap();
+            db.insert_vectors("docs", vec![
+                ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"n": 1})),
+                ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"n": 2})),
+            ]).await.unwrap();
+            db.update_vector("docs", "a", vec![0.6, 0.8], Some(serde_json::json!({"n": 3}))).await.unwrap();
+            db.delete_vectors("docs", &["b".to_string()]).await.unwrap();
+            db.delete_collection("gone").await.unwrap();
+        }
+
+        let db = CoreTexDB::open(config(root.path())).await.unwrap();
+        assert_eq!(db.list_collections().await.unwrap(), vec!["docs".to_string()]);
+        let (vector, metadata) = db.get_vector("docs", "a", None).await.unwrap().unwrap();
+        assert_eq!(vector, vec![0.6, 0.8]);
+        assert_eq!(metadata["n"], 3);
+        assert!(db.get_vector("docs", "b", None).await.unwrap().is_none());
+        let results = db.search("docs", vec![0.6, 0.8], 1, None, None).await.unwrap();
+        assert_eq!(results[0].id, "a");
+        assert!(db.journal.last_lsn().await >= 6);
+    }
+
+    #[tokio::test]
+    async fn test_open_replays_entries_missing_from_storage() {
+        let root = tempfile::tempdir().unwrap();
+        {
+            let db = CoreTexDB::open(config(root.path())).await.unwrap();
+            db.create_collection("docs", 2, "euclidean").await.unwrap();
+        }
+
+        // As if the process died after journaling an insert but before
+        // storing it
+        let mut wal = WriteAheadLog::new(&config(root.path()).wal_dir);
+        wal.init().await.unwrap();
+        wal.create_entry(WalEntryType::Insert, "docs", serde_json::json!({
+            "vectors": [{"id": "a", "vector": [3.0, 4.0], "metadata": {}}],
+        })).await.unwrap();
+
+        let db = CoreTexDB::open(config(root.path())).await.unwrap();
+        assert_eq!(db.get_vector("docs", "a", None).await.unwrap().unwrap().0, vec![3.0, 4.0]);
+        assert_eq!(db.get_collection("docs").await.unwrap().distance_metric.as_str(), "euclidean");
+
+        // Now in storage, and not applied a second time
+        drop(db);
+        let db = CoreTexDB::open(config(root.path())).await.unwrap();
+        assert_eq!(db.get_vectors_count("docs").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_is_journaled_as_one_entry_and_recovered() {
+        let root = tempfile::tempdir().unwrap();
+        {
+            let db = CoreTexDB::open(config(root.path())).await.unwrap();
+            db.create_collection("a", 2, "cosine").await.unwrap();
+            db.create_collection("b", 2, "cosine").await.unwrap();
+            db.insert_vectors("b", vec![("old".to_string(), vec![1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+            let before = db.journal.last_lsn().await;
+
+            let txn = db.begin().await.unwrap();
+            txn.insert("a", vec![("x".to_string(), vec![1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+            txn.insert("b", vec![("y".to_string(), vec![0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+            txn.delete("b", &["old".to_string()]).await.unwrap();
+            txn.commit().await.unwrap();
+
+            assert_eq!(db.journal.last_lsn().await, before + 1);
+            let entry = db.journal.entries_after(before).await.unwrap().remove(0);
+            assert!(matches!(entry.entry_type, WalEntryType::Transaction));
+            assert_eq!(entry.parts().len(), 3);
+        }
+
+        let db = CoreTexDB::open(config(root.path())).await.unwrap();
+        assert!(db.get_vector("a", "x", None).await.unwrap().is_some());
+        assert!(db.get_vector("b", "y", None).await.unwrap().is_some());
+        assert!(db.get_vector("b", "old", None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_open_replays_a_journaled_transaction_whole() {
+        let root = tempfile::tempdir().unwrap();
+        {
+            let db = CoreTexDB::open(config(root.path())).await.unwrap();
+            db.create_collection("a", 2, "cosine").await.unwrap();
+            db.create_collection("b", 2, "cosine").await.unwrap();
+        }
+
+        // As if the process died after journaling a commit but before
+        // applying any of it
+        let writes = vec![
+            (("a".to_string(), "x".to_string()), Some((vec![1.0, 0.0], serde_json::json!({})))),
+            (("b".to_string(), "y".to_string()), Some((vec![0.0, 1.0], serde_json::json!({})))),
+        ];
+        let (entry_type, collection, data) = write_entry(&writes).unwrap();
+        let mut wal = WriteAheadLog::new(&config(root.path()).wal_dir);
+        wal.init().await.unwrap();
+        wal.create_entry(entry_type, &collection, data).await.unwrap();
+
+        let db = CoreTexDB::open(config(root.path())).await.unwrap();
+        assert_eq!(db.get_vector("a", "x", None).await.unwrap().unwrap().0, vec![1.0, 0.0]);
+        assert_eq!(db.get_vector("b", "y", None).await.unwrap().unwrap().0, vec![0.0, 1.0]);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_drop_applied_segments() {
+        let root = tempfile::tempdir().unwrap();
+        let config = || DbConfig { wal_segment_bytes: 512, version_retention_ms: 0, ..config(root.path()) };
+        let last_lsn;
+        {
+            let db = CoreTexDB::open(config()).await.unwrap();
+            db.create_collection("docs", 2, "cosine").await.unwrap();
+            for i in 0..20 {
+                db.insert_vectors("docs", vec![(format!("v{}", i), vec![i as f32, 1.0], serde_json::json!({"i": i}))]).await.unwrap();
+            }
+            db.delete_vectors("docs", &["v0".to_string()]).await.unwrap();
+            last_lsn = db.journal.last_lsn().await;
+
+            // The schema and early inserts are only in storage now
+            let entries = db.journal.entries_after(0).await.unwrap();
+            assert!(entries.first().unwrap().id > 1);
+            assert_eq!(entries.last().unwrap().id, last_lsn);
+            assert!(db.load_metadata().await.unwrap().applied_lsn >= entries.first().unwrap().id - 1);
+        }
+
+        let db = CoreTexDB::open(config()).await.unwrap();
+        assert_eq!(db.list_collections().await.unwrap(), vec!["docs".to_string()]);
+        assert_eq!(db.get_vectors_count("docs").await.unwrap(), 19);
+        assert!(db.get_vector("docs", "v0", None).await.unwrap().is_none());
+        assert_eq!(db.get_vector("docs", "v1", None).await.unwrap().unwrap().1["i"], 1);
+
+        // Numbering carries on past the dropped entries
+        db.insert_vectors("docs", vec![("new".to_string(), vec![1.0, 1.0], serde_json::json!({}))]).await.unwrap();
+        assert_eq!(db.journal.last_lsn().await, last_lsn + 1);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_keeps_unapplied_entries() {
+        let root = tempfile::tempdir().unwrap();
+        let db = CoreTexDB::open(DbConfig { version_retention_ms: 0, ..config(root.path()) }).await.unwrap();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+
+        // Journaled, then failed before reaching storage
+        let entry = db.journal.append(WalEntryType::Insert, "docs", serde_json::json!({
+            "vectors": [{"id": "a", "vector": [1.0, 0.0], "metadata": {}}],
+        })).await.unwrap().unwrap();
+        db.insert_vectors("docs", vec![("b".to_string(), vec![0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+
+        db.checkpoint().await.unwrap();
+        assert_eq!(db.load_metadata().await.unwrap().applied_lsn, entry.id - 1);
+        assert_eq!(db.journal.entries_after(entry.id - 1).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_open_reloads_version_history() {
+        use crate::AsOf;
+
+        let root = tempfile::tempdir().unwrap();
+        let before;
+        {
+            let db = CoreTexDB::open(config(root.path())).await.unwrap();
+            db.create_collection("docs", 2, "euclidean").await.unwrap();
+            db.insert_vectors("docs", vec![
+                ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"v": 1})),
+                ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"v": 1})),
+            ]).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            before = crate::coretex_transaction::now_ms();
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            db.update_vector("docs", "a", vec![5.0, 5.0], Some(serde_json::json!({"v": 2}))).await.unwrap();
+            db.delete_vectors("docs", &["b".to_string()]).await.unwrap();
+        }
+
+        let db = CoreTexDB::open(config(root.path())).await.unwrap();
+        let (vector, metadata) = db.get_vector("docs", "a", Some(AsOf::Timestamp(before))).await.unwrap().unwrap();
+        assert_eq!(vector, vec![1.0, 0.0]);
+        assert_eq!(metadata["v"], 1);
+        assert!(db.get_vector("docs", "b", Some(AsOf::Timestamp(before))).await.unwrap().is_some());
+
+        let hits = db.search("docs", vec![0.0, 1.0], 2, None, Some(AsOf::Timestamp(before))).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["b", "a"]);
+        assert!((hits[1].distance - 2f32.sqrt()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,439 @@
+//! Background tiering daemon
+//! Runs migration passes on an interval, enforces tier size budgets and
+//! persists the document table between passes
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::coretex_distributed::locks::{LockError, LockService};
+use crate::coretex_lakehouse::lakehouse::{MigrationOptions, MigrationReport, VectorLakehouse};
+use crate::coretex_lakehouse::tier::{EvictionOrder, StorageTier};
+use crate::coretex_monitoring_v2::MetricsCollector;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieringDaemonConfig {
+    pub interval_secs: u64,
+    /// Caps migration I/O per pass; `None` moves data as fast as storage allows.
+    pub max_bytes_per_sec: Option<u64>,
+    /// Caps the documents moved per pass; the rest wait for the next pass.
+    pub max_migrations_per_run: Option<usize>,
+    /// Saves the document table after every pass and on shutdown.
+    pub persist_metadata: bool,
+}
+
+impl Default for TieringDaemonConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 300,
+            max_bytes_per_sec: Some(64 * 1024 * 1024),
+            max_migrations_per_run: None,
+            persist_metadata: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TieringStats {
+    pub runs: u64,
+    pub failures: u64,
+    /// Passes left to another node that held the tiering lock.
+    pub skipped: u64,
+    pub total_migrated: u64,
+    pub total_bytes_moved: u64,
+    pub last_run_at: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+pub struct TieringDaemon {
+    lakehouse: Arc<VectorLakehouse>,
+    config: TieringDaemonConfig,
+    options: MigrationOptions,
+    metrics: Option<MetricsCollector>,
+    locks: Option<Arc<LockService>>,
+    progress: RwLock<MigrationReport>,
+    stats: RwLock<TieringStats>,
+    running: AtomicBool,
+    shutdown: Notify,
+}
+
+impl TieringDaemon {
+    /// Budgets and eviction order come from the lakehouse's `TierConfig`.
+    pub fn new(lakehouse: Arc<VectorLakehouse>, config: TieringDaemonConfig) -> Self {
+        let options = MigrationOptions {
+            max_bytes_per_sec: config.max_bytes_per_sec,
+            max_migrations: config.max_migrations_per_run,
+            ..MigrationOptions::from_config(lakehouse.config())
+        };
+
+        Self {
+            lakehouse,
+            config,
+            options,
+            metrics: None,
+            locks: None,
+            progress: RwLock::new(MigrationReport::default()),
+            stats: RwLock::new(TieringStats::default()),
+            running: AtomicBool::new(false),
+            shutdown: Notify::new(),
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: MetricsCollector) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Runs each pass under a cluster-wide lease on `lock_key()`, so daemons
+    /// on several nodes sharing one lakehouse never migrate at once. A pass
+    /// stops once its lease is lost, and its storage writes and the saved
+    /// document table are fenced by the lease's token.
+    pub fn with_lock(mut self, locks: Arc<LockService>) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    pub fn lock_key(&self) -> String {
+        format!("lakehouse-tiering:{}", self.lakehouse.data_dir())
+    }
+
+    /// Overrides the tier budgets in bytes. `None` leaves a tier unbounded.
+    pub fn with_budgets(mut self, hot_bytes: Option<u64>, warm_bytes: Option<u64>) -> Self {
+        self.options.hot_budget_bytes = hot_bytes;
+        self.options.warm_budget_bytes = warm_bytes;
+        self
+    }
+
+    pub fn with_eviction_order(mut self, order: EvictionOrder) -> Self {
+        self.options.eviction_order = order;
+        self
+    }
+
+    pub fn config(&self) -> &TieringDaemonConfig {
+        &self.config
+    }
+
+    /// The pass in flight, or the last finished one once `finished_at` is set.
+    pub async fn progress(&self) -> MigrationReport {
+        self.progress.read().await.clone()
+    }
+
+    pub async fn stats(&self) -> TieringStats {
+        self.stats.read().await.clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        self.running.store(true, Ordering::SeqCst);
+        let daemon = self.clone();
+
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(daemon.config.interval_secs.max(1));
+
+            while daemon.is_running() {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = daemon.shutdown.notified() => break,
+                }
+
+                if !daemon.is_running() {
+                    break;
+                }
+
+                let _ = daemon.run_once().await;
+            }
+
+            // Under a lock, only a pass holding the lease may save the table
+            if daemon.config.persist_metadata && daemon.locks.is_none() {
+                let _ = daemon.lakehouse.save_metadata().await;
+            }
+        })
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        // notify_one keeps a permit if the loop is not waiting yet
+        self.shutdown.notify_one();
+    }
+
+    /// Runs one migration pass, saves the document table and records the
+    /// outcome. With a lock service, a pass another node is running is
+    /// skipped and reported as an empty one.
+    pub async fn run_once(&self) -> Result<MigrationReport, String> {
+        let guard = match &self.locks {
+            Some(locks) => match locks.acquire(&self.lock_key()).await {
+                Ok(guard) => Some(guard),
+                Err(LockError::Held { .. }) => {
+                    self.stats.write().await.skipped += 1;
+                    return Ok(MigrationReport::default());
+                }
+                Err(e) => return Err(e.to_string()),
+            },
+            None => None,
+        };
+
+        let result = match &guard {
+            Some(guard) => self.lakehouse.migrate_fenced(&self.options, Some(&self.progress), guard).await,
+            None => self.lakehouse.migrate_with(&self.options, Some(&self.progress)).await,
+        };
+        let result = match (result, &guard) {
+            (Ok(report), _) if !self.config.persist_metadata => Ok(report),
+            (Ok(_), Some(guard)) if !guard.is_held() => Err(LockError::LeaseLost(guard.key()).to_string()),
+            (Ok(report), Some(guard)) => self.lakehouse.save_metadata_fenced(guard.token()).await.map(|_| report),
+            (Ok(report), None) => self.lakehouse.save_metadata().await.map(|_| report),
+            (Err(e), _) => Err(e),
+        };
+        if let Some(guard) = guard {
+            let _ = guard.release().await;
+        }
+
+        {
+            let mut stats = self.stats.write().await;
+            stats.runs += 1;
+            stats.last_run_at = Some(chrono::Utc::now().timestamp());
+            match &result {
+                Ok(report) => {
+                    stats.total_migrated += report.migrated_count;
+                    stats.total_bytes_moved += report.bytes_moved;
+                    stats.last_error = None;
+                }
+                Err(e) => {
+                    stats.failures += 1;
+                    stats.last_error = Some(e.clone());
+                }
+            }
+        }
+
+        if let Some(metrics) = &self.metrics {
+            self.record_metrics(metrics, &result).await;
+        }
+
+        result
+    }
+
+    async fn record_metrics(&self, metrics: &MetricsCollector, result: &Result<MigrationReport, String>) {
+        let label = |key: &str, value: &str| -> HashMap<String, String> {
+            [(key.to_string(), value.to_string())].into()
+        };
+
+        let status = if result.is_ok() { "success" } else { "failure" };
+        metrics.increment_counter("coretex_lakehouse_tiering_runs_total", 1.0, label("status", status)).await;
+
+        if let Ok(report) = result {
+            metrics.increment_counter("coretex_lakehouse_migrations_total", report.promoted_count as f64, label("direction", "promote")).await;
+            metrics.increment_counter("coretex_lakehouse_migrations_total", report.demoted_count as f64, label("direction", "demote")).await;
+            metrics.increment_counter("coretex_lakehouse_evictions_total", report.evicted_count as f64, HashMap::new()).await;
+            metrics.increment_counter("coretex_lakehouse_migration_failures_total", report.failed_count as f64, HashMap::new()).await;
+            metrics.increment_counter("coretex_lakehouse_migrated_bytes_total", report.bytes_moved as f64, HashMap::new()).await;
+            metrics.set_gauge("coretex_lakehouse_migrations_pending", report.pending_count as f64, HashMap::new()).await;
+            metrics.observe_histogram("coretex_lakehouse_migration_duration_seconds", report.duration_ms as f64 / 1000.0, HashMap::new()).await;
+        }
+
+        let stats = self.lakehouse.get_stats().await;
+        for (tier, count, bytes) in [
+            (StorageTier::Hot, stats.hot_count, stats.hot_size_bytes),
+            (StorageTier::Warm, stats.warm_count, stats.warm_size_bytes),
+            (StorageTier::Cold, stats.cold_count, stats.cold_size_bytes),
+        ] {
+            metrics.set_gauge("coretex_lakehouse_tier_documents", count as f64, label("tier", tier.as_str())).await;
+            metrics.set_gauge("coretex_lakehouse_tier_bytes", bytes as f64, label("tier", tier.as_str())).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coretex_lakehouse::policy::TTLTieringPolicy;
+    use crate::coretex_lakehouse::tier::TierConfig;
+    use crate::coretex_monitoring_v2::{Metric, MonitoringConfig};
+    use tempfile::TempDir;
+
+    fn unthrottled() -> TieringDaemonConfig {
+        TieringDaemonConfig { max_bytes_per_sec: None, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_budget_evicts_least_recently_accessed() {
+        let temp_dir = TempDir::new().unwrap();
+        let lakehouse = Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap()
+            .with_metric("euclidean")
+            .with_policy(Box::new(TTLTieringPolicy::new(TierConfig::default()))));
+
+        for (i, id) in ["a", "b", "c", "d"].iter().enumerate() {
+            lakehouse.write_vector("docs", id, vec![i as f32, 0.0], serde_json::json!({})).await.unwrap();
+        }
+        // Touch c and a so b and d are the least recently accessed
+        lakehouse.read("docs", "c").await.unwrap();
+        lakehouse.read("docs", "a").await.unwrap();
+
+        let size = lakehouse.document_meta("docs", "a").await.unwrap().size_bytes;
+        let metrics = MetricsCollector::new(MonitoringConfig::default());
+        let daemon = TieringDaemon::new(lakehouse.clone(), unthrottled())
+            .with_budgets(Some(size * 2), Some(size))
+            .with_metrics(metrics.clone());
+
+        let report = daemon.run_once().await.unwrap();
+        assert_eq!(report.migrated_count, 2);
+        assert_eq!(report.evicted_count, 2);
+        assert!(daemon.progress().await.finished_at.is_some());
+
+        let tier = |id: &'static str| {
+            let lakehouse = lakehouse.clone();
+            async move { lakehouse.document_meta("docs", id).await.unwrap().tier }
+        };
+        assert_eq!(tier("a").await, StorageTier::Hot);
+        assert_eq!(tier("c").await, StorageTier::Hot);
+        // b was evicted first, so it overflowed warm and moved on to cold
+        assert_eq!(tier("b").await, StorageTier::Cold);
+        assert_eq!(tier("d").await, StorageTier::Warm);
+
+        let results = lakehouse.search("docs", &[3.0, 0.0], &crate::TieredSearchOptions::new(1)).await.unwrap();
+        assert_eq!(results[0].id, "d");
+        assert_eq!(results[0].tier, StorageTier::Warm);
+
+        let hot_bytes = metrics.get_all_metrics().await.into_iter().find_map(|m| match m {
+            Metric::Gauge(g) if g.name == "coretex_lakehouse_tier_bytes" && g.labels["tier"] == "hot" => Some(g.value),
+            _ => None,
+        });
+        assert_eq!(hot_bytes, Some((size * 2) as f64));
+    }
+
+    #[tokio::test]
+    async fn test_metadata_survives_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+
+        {
+            let lakehouse = Arc::new(VectorLakehouse::new(&path).unwrap().with_metric("euclidean"));
+            lakehouse.write_vector("docs", "a", vec![0.0, 0.0], serde_json::json!({})).await.unwrap();
+            lakehouse.write_vector("docs", "b", vec![9.0, 9.0], serde_json::json!({})).await.unwrap();
+            lakehouse.read("docs", "a").await.unwrap();
+
+            let daemon = Arc::new(TieringDaemon::new(lakehouse, TieringDaemonConfig {
+                interval_secs: 3600,
+                ..unthrottled()
+            }));
+            let handle = daemon.start();
+            daemon.stop();
+            handle.await.unwrap();
+        }
+
+        let lakehouse = VectorLakehouse::new(&path).unwrap().with_metric("euclidean");
+        assert_eq!(lakehouse.load_metadata().await.unwrap(), 2);
+        assert_eq!(lakehouse.document_meta("docs", "a").await.unwrap().access_count, 1);
+
+        let results = lakehouse.search("docs", &[8.0, 8.0], &crate::TieredSearchOptions::new(1)).await.unwrap();
+        assert_eq!(results[0].id, "b");
+        assert_eq!(results[0].tier, StorageTier::Hot);
+    }
+
+    #[tokio::test]
+    async fn test_pass_limits_and_throttling() {
+        let temp_dir = TempDir::new().unwrap();
+        let cold = TierConfig { hot_threshold_days: 0, warm_threshold_days: 0, ..Default::default() };
+        let lakehouse = Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap()
+            .with_config(cold));
+
+        for id in ["a", "b", "c"] {
+            lakehouse.write("docs", id, &[0u8; 1000], None).await.unwrap();
+        }
+
+        let daemon = TieringDaemon::new(lakehouse.clone(), TieringDaemonConfig {
+            max_bytes_per_sec: Some(20_000),
+            max_migrations_per_run: Some(2),
+            ..Default::default()
+        });
+
+        let started = std::time::Instant::now();
+        let report = daemon.run_once().await.unwrap();
+        // Two moves at 2000 bytes of I/O each take 200ms at 20KB/s
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(report.migrated_count, 2);
+        assert_eq!(report.pending_count, 1);
+        assert_eq!(report.bytes_moved, 2000);
+
+        let report = daemon.run_once().await.unwrap();
+        assert_eq!(report.migrated_count, 1);
+        assert_eq!(lakehouse.get_stats().await.cold_count, 3);
+        assert_eq!(daemon.stats().await.total_migrated, 3);
+    }
+
+    #[tokio::test]
+    async fn test_locked_pass_runs_on_one_node() {
+        use crate::coretex_distributed::locks::{tests::lock_cluster, LockConfig};
+
+        let temp_dir = TempDir::new().unwrap();
+        let cold = TierConfig { hot_threshold_days: 0, warm_threshold_days: 0, ..Default::default() };
+        let lakehouse = Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap()
+            .with_config(cold));
+        for id in ["a", "b", "c"] {
+            lakehouse.write("docs", id, &[0u8; 100], None).await.unwrap();
+        }
+
+        let services = lock_cluster(2, LockConfig::default()).await;
+        let daemon = TieringDaemon::new(lakehouse.clone(), unthrottled()).with_lock(services[0].clone());
+
+        // Another node's daemon is mid-pass
+        let other = services[1].acquire(&daemon.lock_key()).await.unwrap();
+        let report = daemon.run_once().await.unwrap();
+        assert_eq!(report.migrated_count, 0);
+        assert_eq!(daemon.stats().await.skipped, 1);
+        assert_eq!(lakehouse.get_stats().await.cold_count, 0);
+
+        // Once it finishes, this one runs; the superseded holder can no
+        // longer save the document table
+        let stale = other.token();
+        other.release().await.unwrap();
+        assert_eq!(daemon.run_once().await.unwrap().migrated_count, 3);
+        assert!(services[0].table().lease(&daemon.lock_key()).is_none());
+        assert!(lakehouse.save_metadata_fenced(stale).await.unwrap_err().contains("older than"));
+    }
+
+    #[tokio::test]
+    async fn test_pass_stops_when_its_lease_is_lost() {
+        use crate::coretex_distributed::locks::{tests::lock_cluster, LockConfig};
+
+        let temp_dir = TempDir::new().unwrap();
+        let cold = TierConfig { hot_threshold_days: 0, warm_threshold_days: 0, ..Default::default() };
+        let lakehouse = Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap()
+            .with_config(cold));
+        for id in ["a", "b", "c"] {
+            lakehouse.write("docs", id, &[0u8; 1000], None).await.unwrap();
+        }
+
+        let config = LockConfig { lease_ms: 1_000, renew_interval_ms: 20, commit_timeout_ms: 1_000 };
+        let services = lock_cluster(2, config).await;
+        // Each move is throttled for 100ms
+        let daemon = Arc::new(TieringDaemon::new(lakehouse.clone(), TieringDaemonConfig {
+            max_bytes_per_sec: Some(20_000),
+            ..Default::default()
+        }).with_lock(services[0].clone()));
+        let pass = tokio::spawn({
+            let daemon = daemon.clone();
+            async move { daemon.run_once().await }
+        });
+
+        // The lease is taken over while the first move is throttled
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let lease = services[0].table().lease(&daemon.lock_key()).unwrap();
+        services[0].release(&lease).await.unwrap();
+        let next = services[1].acquire(&daemon.lock_key()).await.unwrap();
+
+        let err = pass.await.unwrap().unwrap_err();
+        assert!(err.contains("was lost"), "{}", err);
+        assert_eq!(lakehouse.get_stats().await.cold_count, 1);
+        assert_eq!(daemon.stats().await.failures, 1);
+
+        // The new holder's writes fence out the old one's
+        lakehouse.save_metadata_fenced(next.token()).await.unwrap();
+        assert!(lakehouse.save_metadata_fenced(lease.token).await.unwrap_err().contains("older than"));
+    }
+}
@@ -1,13 +1,31 @@
 //! Vector Lakehouse Manager
 //! Orchestrates data tiering and management across storage layers
 
-use crate::coretex_lakehouse::tier::{StorageTier, DocumentMeta, TierConfig};
+use crate::coretex_lakehouse::tier::{StorageTier, DocumentMeta, EvictionOrder, TierConfig};
 use crate::coretex_lakehouse::policy::{TieringPolicy, HybridTieringPolicy};
-use crate::coretex_lakehouse::storage::{StorageBackendTrait, LocalStorage};
+use crate::coretex_lakehouse::storage::{StorageBackend, StorageBackendTrait, LocalStorage};
+#[cfg(feature = "parquet")]
+use crate::coretex_lakehouse::segment::{ParquetSegmentStore, SegmentConfig};
+use crate::coretex_lakehouse::search::{TieredSearchOptions, TieredSearchResult, TieredVectorIndex, VectorRecord};
+use crate::coretex_distributed::locks::{Fence, FencingToken, LockError, LockGuard};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, Mutex, MutexGuard, RwLock};
+
+/// Documents share this many locks, by hash of their key.
+const KEY_LOCK_STRIPES: usize = 64;
+
+/// What migration passes under a lease are fenced on.
+const FENCE_RESOURCE: &str = "tiering";
+
+/// Demotions a slow subscriber may fall behind by before it is told it
+/// lagged.
+const DEMOTION_BUFFER: usize = 1024;
 
 pub struct VectorLakehouse {
     hot_storage: Arc<dyn StorageBackendTrait>,
@@ -16,6 +34,36 @@ pub struct VectorLakehouse {
     metadata: Arc<RwLock<HashMap<String, DocumentMeta>>>,
     policy: Box<dyn TieringPolicy>,
     config: TierConfig,
+    index: Arc<RwLock<TieredVectorIndex>>,
+    data_dir: String,
+    /// Kept in `{data_dir}/fence.json`, shared by every node tiering this
+    /// lakehouse.
+    fence: Fence,
+    /// Held by whatever moves a document's bytes between storage and
+    /// metadata, so a write and a migration of one document never
+    /// interleave while the table itself stays unlocked during I/O.
+    key_locks: Vec<Mutex<()>>,
+    /// Held while `{data_dir}/metadata.log` is appended to or compacted
+    table_log: Mutex<()>,
+    /// `(collection, id)` of every document placed in a tier below hot
+    demotions: broadcast::Sender<(String, String)>,
+    /// Accesses counted by reads and searches, by key, until they are
+    /// folded into the table, so reading never locks it for writing.
+    accesses: std::sync::Mutex<HashMap<String, (u32, chrono::DateTime<chrono::Utc>)>>,
+    /// Documents a pass moved but whose old copies it could not remove,
+    /// e.g. as it lost its lease, with the tier each old copy is in. The
+    /// next pass removes them along with its own.
+    stale_copies: std::sync::Mutex<Vec<(String, StorageTier)>>,
+    #[cfg(feature = "parquet")]
+    cold_segments: Option<Arc<ParquetSegmentStore>>,
+}
+
+/// A document's entry in the table as of a change to it, `None` once
+/// deleted; appended to `metadata.log` until the next `save_metadata`.
+#[derive(Serialize, Deserialize)]
+struct TableChange {
+    key: String,
+    meta: Option<DocumentMeta>,
 }
 
 impl VectorLakehouse {
@@ -35,6 +83,8 @@ impl VectorLakehouse {
         let config = TierConfig::default();
         let policy = Box::new(HybridTieringPolicy::new(config.clone()));
 
+        let index = TieredVectorIndex::new(Path::new(data_dir).join("warm_index"), "cosine");
+
         Ok(Self {
             hot_storage,
             warm_storage,
@@ -42,39 +92,160 @@ impl VectorLakehouse {
             metadata: Arc::new(RwLock::new(HashMap::new())),
             policy,
             config,
+            index: Arc::new(RwLock::new(index)),
+            data_dir: data_dir.to_string(),
+            fence: Fence::open(Path::new(data_dir).join("fence.json")),
+            key_locks: (0..KEY_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+            table_log: Mutex::new(()),
+            demotions: broadcast::channel(DEMOTION_BUFFER).0,
+            accesses: std::sync::Mutex::new(HashMap::new()),
+            stale_copies: std::sync::Mutex::new(Vec::new()),
+            #[cfg(feature = "parquet")]
+            cold_segments: None,
         })
     }
 
+    /// Distance metric for vector search ("cosine", "euclidean" or "dotproduct").
+    pub fn with_metric(mut self, metric: &str) -> Self {
+        let index = TieredVectorIndex::new(Path::new(&self.data_dir).join("warm_index"), metric);
+        self.index = Arc::new(RwLock::new(index));
+        self
+    }
+
+    /// Replaces the tier thresholds and budgets and resets the policy to a
+    /// `HybridTieringPolicy` over them.
+    pub fn with_config(mut self, config: TierConfig) -> Self {
+        self.policy = Box::new(HybridTieringPolicy::new(config.clone()));
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &TierConfig {
+        &self.config
+    }
+
+    pub fn data_dir(&self) -> &str {
+        &self.data_dir
+    }
+
     pub fn with_policy(mut self, policy: Box<dyn TieringPolicy>) -> Self {
         self.policy = policy;
         self
     }
 
+    /// Sends cold documents to `storage`, e.g. an `S3Storage` bucket.
+    pub fn with_cold_storage(mut self, storage: Arc<dyn StorageBackendTrait>) -> Self {
+        self.cold_storage = storage;
+        #[cfg(feature = "parquet")]
+        {
+            self.cold_segments = None;
+        }
+        self
+    }
+
+    pub fn with_cold_backend(self, backend: &StorageBackend) -> Result<Self, String> {
+        Ok(self.with_cold_storage(backend.build()?))
+    }
+
+    /// Packs warm and cold documents into Parquet segments on top of the
+    /// current warm and cold backends, so call it after `with_cold_storage`.
+    /// Documents already stored one file each are not imported.
+    #[cfg(feature = "parquet")]
+    pub fn with_parquet_segments(mut self, config: SegmentConfig) -> Self {
+        self.warm_storage = Arc::new(ParquetSegmentStore::new(self.warm_storage.clone(), "segments", config.clone()));
+        let cold = Arc::new(ParquetSegmentStore::new(self.cold_storage.clone(), "segments", config));
+        self.cold_segments = Some(cold.clone());
+        self.cold_storage = cold;
+        self
+    }
+
     pub async fn write(&self, collection: &str, id: &str, data: &[u8], vector_dim: Option<usize>) -> Result<(), String> {
         let key = format!("{}/{}", collection, id);
-        
-        let tier = {
-            let meta = self.metadata.read().await;
-            if let Some(existing) = meta.get(&key) {
-                self.policy.determine_tier(existing)
-            } else {
-                StorageTier::Hot
-            }
+        let (tier, moved_from) = {
+            let _key_lock = self.lock_key(&key).await;
+            self.store_locked(collection, id, data, vector_dim).await?
+        };
+        let moved: Vec<_> = moved_from.map(|from| (key.clone(), from)).into_iter().collect();
+        self.commit_stored(&[key], &moved).await?;
+        self.announce(collection, id, tier);
+        Ok(())
+    }
+
+    /// Stores a document under its key lock, without logging the table
+    /// change or removing the old copy. Returns the tier the document landed
+    /// in, and the tier it left if it moved.
+    async fn store_locked(&self, collection: &str, id: &str, data: &[u8], vector_dim: Option<usize>) -> Result<(StorageTier, Option<StorageTier>), String> {
+        let key = format!("{}/{}", collection, id);
+
+        self.fold_accesses().await;
+        let existing = self.metadata.read().await.get(&key).cloned();
+        let tier = match &existing {
+            Some(existing) => self.policy.determine_tier(existing),
+            None => StorageTier::Hot,
         };
 
         let storage = self.get_storage_for_tier(tier);
-        storage.write(&key, data)?;
+        storage.write(&key, data).await?;
 
-        let mut meta = DocumentMeta::new(id.to_string(), collection.to_string());
+        // Overwrites keep their access history so tiering decisions stay meaningful
+        let moved_from = existing.as_ref().map(|existing| existing.tier).filter(|from| *from != tier);
+        let mut meta = existing.unwrap_or_else(|| DocumentMeta::new(id.to_string(), collection.to_string()));
         meta.size_bytes = data.len() as u64;
         meta.vector_dimension = vector_dim;
         meta.tier = tier;
+        meta.updated_at = chrono::Utc::now();
 
         self.metadata.write().await.insert(key, meta);
+        Ok((tier, moved_from))
+    }
+
+    /// Logs the table entries of stored documents. Documents that moved
+    /// tiers, `(key, tier left)`, lose their old copy only after their new
+    /// one is flushed and the move is logged, as in a migration pass, so a
+    /// crash in between leaves both.
+    async fn commit_stored(&self, keys: &[String], moved: &[(String, StorageTier)]) -> Result<(), String> {
+        if !moved.is_empty() {
+            self.flush().await?;
+        }
+        self.log_changes(keys).await?;
 
+        for (key, from) in moved {
+            let _key_lock = self.lock_key(key).await;
+            // Moved back meanwhile
+            if self.metadata.read().await.get(key).map(|m| m.tier) == Some(*from) {
+                continue;
+            }
+            let storage = self.get_storage_for_tier(*from);
+            if storage.try_exists(key).await? {
+                storage.delete(key).await?;
+            }
+        }
         Ok(())
     }
 
+    /// Tells subscribers to `subscribe_demotions` about a document that is
+    /// now below the hot tier.
+    fn announce(&self, collection: &str, id: &str, tier: StorageTier) {
+        if tier != StorageTier::Hot {
+            // Nobody listening is fine
+            self.demotions.send((collection.to_string(), id.to_string())).ok();
+        }
+    }
+
+    /// `(collection, id)` of every document written to or moved into the
+    /// warm or cold tier from now on, once its table entry is logged.
+    pub fn subscribe_demotions(&self) -> broadcast::Receiver<(String, String)> {
+        self.demotions.subscribe()
+    }
+
+    /// `(collection, id)` of every document currently below the hot tier.
+    pub async fn demoted_documents(&self) -> Vec<(String, String)> {
+        self.metadata.read().await.values()
+            .filter(|meta| meta.tier != StorageTier::Hot)
+            .map(|meta| (meta.collection.clone(), meta.id.clone()))
+            .collect()
+    }
+
     pub async fn read(&self, collection: &str, id: &str) -> Result<Vec<u8>, String> {
         let key = format!("{}/{}", collection, id);
         
@@ -83,21 +254,14 @@ impl VectorLakehouse {
             meta.get(&key).map(|m| m.tier).unwrap_or(StorageTier::Hot)
         };
 
-        if let Ok(data) = self.get_storage_for_tier(tier).read(&key) {
-            let mut meta_map = self.metadata.write().await;
-            if let Some(meta) = meta_map.get_mut(&key) {
-                meta.record_access();
-            }
+        if let Ok(data) = self.get_storage_for_tier(tier).read(&key).await {
+            self.count_access(&key);
             return Ok(data);
         }
 
         for storage in [&self.hot_storage, &self.warm_storage, &self.cold_storage] {
-            if let Ok(data) = storage.read(&key) {
-                let mut meta_map = self.metadata.write().await;
-                if let Some(meta) = meta_map.get_mut(&key) {
-                    meta.tier = tier;
-                    meta.record_access();
-                }
+            if let Ok(data) = storage.read(&key).await {
+                self.count_access(&key);
                 return Ok(data);
             }
         }
@@ -107,48 +271,663 @@ impl VectorLakehouse {
 
     pub async fn delete(&self, collection: &str, id: &str) -> Result<(), String> {
         let key = format!("{}/{}", collection, id);
-        
+        let _key_lock = self.lock_key(&key).await;
+        self.remove_locked(collection, id).await?;
+        self.log_changes(&[key]).await
+    }
+
+    /// `delete` under the key lock, without logging the table change.
+    async fn remove_locked(&self, collection: &str, id: &str) -> Result<(), String> {
+        let key = format!("{}/{}", collection, id);
         let tier = {
             let meta = self.metadata.read().await;
             meta.get(&key).map(|m| m.tier).unwrap_or(StorageTier::Hot)
         };
 
-        self.get_storage_for_tier(tier).delete(&key)?;
+        self.get_storage_for_tier(tier).delete(&key).await?;
         self.metadata.write().await.remove(&key);
-
-        Ok(())
+        self.accesses.lock().unwrap().remove(&key);
+        self.index.write().await.remove(collection, id, tier).await
     }
 
+    /// Runs one migration pass with the budgets and eviction order from the
+    /// lakehouse's `TierConfig`.
     pub async fn migrate_data(&self) -> Result<MigrationReport, String> {
-        let mut report = MigrationReport::default();
-        
-        let mut meta_map = self.metadata.write().await;
-        
-        for (key, meta) in meta_map.iter_mut() {
-            let new_tier = self.policy.determine_tier(meta);
-            
-            if new_tier != meta.tier {
-                let old_storage = self.get_storage_for_tier(meta.tier);
-                let new_storage = self.get_storage_for_tier(new_tier);
-                
-                if let Ok(data) = old_storage.read(key) {
-                    if new_storage.write(key, &data).is_ok() {
-                        old_storage.delete(key).ok();
-                        meta.tier = new_tier;
-                        match new_tier {
-                            StorageTier::Hot => report.hot_count += 1,
-                            StorageTier::Warm => report.warm_count += 1,
-                            StorageTier::Cold => report.cold_count += 1,
+        self.migrate_with(&MigrationOptions::from_config(&self.config), None).await
+    }
+
+    /// Runs one migration pass. When `progress` is given it is updated after
+    /// every document so callers can watch a long pass.
+    pub async fn migrate_with(&self, options: &MigrationOptions, progress: Option<&RwLock<MigrationReport>>) -> Result<MigrationReport, String> {
+        self.migrate_pass(options, progress, None).await
+    }
+
+    /// `migrate_with` for a node holding the tiering lease. The pass stops
+    /// as soon as the lease is lost, and every storage write is fenced by
+    /// its token, so a holder that was superseded without noticing cannot
+    /// overwrite the next one's work.
+    pub async fn migrate_fenced(
+        &self,
+        options: &MigrationOptions,
+        progress: Option<&RwLock<MigrationReport>>,
+        guard: &LockGuard,
+    ) -> Result<MigrationReport, String> {
+        self.migrate_pass(options, progress, Some(guard)).await
+    }
+
+    async fn migrate_pass(
+        &self,
+        options: &MigrationOptions,
+        progress: Option<&RwLock<MigrationReport>>,
+        guard: Option<&LockGuard>,
+    ) -> Result<MigrationReport, String> {
+        let started = Instant::now();
+        let plan = self.plan_migrations(options).await;
+
+        let mut report = MigrationReport {
+            planned_count: plan.len() as u64,
+            started_at: Some(chrono::Utc::now().timestamp()),
+            ..Default::default()
+        };
+        if let Some(progress) = progress {
+            *progress.write().await = report.clone();
+        }
+
+        let limit = options.max_migrations.unwrap_or(usize::MAX);
+        let mut throttle = options.max_bytes_per_sec.map(IoThrottle::new);
+        let mut moved = Vec::new();
+
+        for (i, planned) in plan.iter().enumerate() {
+            if i >= limit {
+                report.pending_count = (plan.len() - i) as u64;
+                break;
+            }
+
+            if let Some(guard) = guard.filter(|guard| !guard.is_held()) {
+                self.stale_copies.lock().unwrap().extend(moved);
+                return Err(LockError::LeaseLost(guard.key()).to_string());
+            }
+
+            match self.migrate_document(planned, guard).await {
+                Ok(Some(bytes)) => {
+                    moved.push((planned.key.clone(), planned.from));
+                    report.migrated_count += 1;
+                    report.bytes_moved += bytes;
+                    match planned.to {
+                        StorageTier::Hot => report.hot_count += 1,
+                        StorageTier::Warm => report.warm_count += 1,
+                        StorageTier::Cold => report.cold_count += 1,
+                    }
+                    if planned.to.priority() > planned.from.priority() {
+                        report.promoted_count += 1;
+                    } else {
+                        report.demoted_count += 1;
+                    }
+                    if planned.evicted {
+                        report.evicted_count += 1;
+                    }
+                    // Every moved byte is read once and written once
+                    if let Some(throttle) = &mut throttle {
+                        throttle.consume(bytes * 2).await;
+                    }
+                }
+                Ok(None) => report.skipped_count += 1,
+                Err(e) => {
+                    report.failed_count += 1;
+                    report.errors.push(format!("{}: {}", planned.key, e));
+                }
+            }
+
+            if let Some(progress) = progress {
+                *progress.write().await = report.clone();
+            }
+        }
+
+        // Old copies are removed only once the target tiers have flushed,
+        // including those an earlier pass left behind
+        moved.extend(self.stale_copies.lock().unwrap().drain(..));
+        match self.fenced(guard, self.flush()).await {
+            Ok(()) => {
+                let keys: Vec<String> = moved.iter().map(|(key, _)| key.clone()).collect();
+                let logged = self.fenced(guard, self.log_changes(&keys)).await;
+                if let Err(e) = &logged {
+                    report.errors.push(format!("metadata log: {}", e));
+                }
+                for (key, from) in moved {
+                    let _key_lock = self.lock_key(&key).await;
+                    let current = self.metadata.read().await.get(&key)
+                        .map(|m| (m.collection.clone(), m.id.clone(), m.tier));
+                    if let Some((collection, id, tier)) = &current {
+                        if logged.is_ok() {
+                            self.announce(collection, id, *tier);
                         }
-                        report.migrated_count += 1;
+                    }
+                    // Refused by the fence once the lease is gone; a copy
+                    // still there then is left for the next pass
+                    let storage = self.get_storage_for_tier(from);
+                    if current.map(|(_, _, tier)| tier) != Some(from)
+                        && self.fenced(guard, storage.delete(&key)).await.is_err()
+                        && storage.try_exists(&key).await.unwrap_or(true)
+                    {
+                        self.stale_copies.lock().unwrap().push((key.clone(), from));
                     }
                 }
             }
+            Err(e) => {
+                report.errors.push(format!("flush: {}", e));
+                self.stale_copies.lock().unwrap().extend(moved);
+            }
         }
-        
+
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        report.finished_at = Some(chrono::Utc::now().timestamp());
+        if let Some(progress) = progress {
+            *progress.write().await = report.clone();
+        }
+
         Ok(report)
     }
 
+    /// Decides where every document should live. The policy picks a tier
+    /// first; tiers over budget then push documents down one tier in
+    /// `options.eviction_order` until they fit. Demotions come first in the
+    /// plan so space is freed before anything is promoted.
+    pub async fn plan_migrations(&self, options: &MigrationOptions) -> Vec<PlannedMigration> {
+        self.fold_accesses().await;
+        let meta_map = self.metadata.read().await;
+
+        let wanted: HashMap<&str, StorageTier> = meta_map.iter()
+            .map(|(key, meta)| (key.as_str(), self.policy.determine_tier(meta)))
+            .collect();
+        let mut target = wanted.clone();
+
+        for (tier, next, budget) in [
+            (StorageTier::Hot, StorageTier::Warm, options.hot_budget_bytes),
+            (StorageTier::Warm, StorageTier::Cold, options.warm_budget_bytes),
+        ] {
+            let budget = match budget {
+                Some(budget) => budget,
+                None => continue,
+            };
+
+            let mut resident: Vec<(&str, &DocumentMeta)> = meta_map.iter()
+                .filter(|(key, _)| target[key.as_str()] == tier)
+                .map(|(key, meta)| (key.as_str(), meta))
+                .collect();
+            let mut used: u64 = resident.iter().map(|(_, meta)| meta.size_bytes).sum();
+            if used <= budget {
+                continue;
+            }
+
+            resident.sort_by(|a, b| options.eviction_order.compare(a.1, b.1).then(a.0.cmp(b.0)));
+            for (key, meta) in resident {
+                if used <= budget {
+                    break;
+                }
+                target.insert(key, next);
+                used -= meta.size_bytes;
+            }
+        }
+
+        let mut plan: Vec<PlannedMigration> = meta_map.iter()
+            .filter(|(key, meta)| target[key.as_str()] != meta.tier)
+            .map(|(key, meta)| {
+                let to = target[key.as_str()];
+                PlannedMigration {
+                    key: key.clone(),
+                    from: meta.tier,
+                    to,
+                    size_bytes: meta.size_bytes,
+                    evicted: to.priority() < wanted[key.as_str()].priority(),
+                    updated_at: meta.updated_at,
+                }
+            })
+            .collect();
+
+        plan.sort_by(|a, b| {
+            let promotes = |p: &PlannedMigration| p.to.priority() > p.from.priority();
+            promotes(a).cmp(&promotes(b)).then(a.key.cmp(&b.key))
+        });
+        plan
+    }
+
+    /// Copies one planned document into its new tier; the caller removes the
+    /// old copy after flushing. Returns `None` when the document was
+    /// rewritten, moved or deleted since the plan was made.
+    async fn migrate_document(&self, planned: &PlannedMigration, guard: Option<&LockGuard>) -> Result<Option<u64>, String> {
+        // Writes and deletes of this document wait for the key lock, so the
+        // table only needs locking to check the plan and to commit the move
+        let _key_lock = self.lock_key(&planned.key).await;
+        let unchanged = |meta: &DocumentMeta| meta.tier == planned.from && meta.updated_at == planned.updated_at;
+        let (collection, id) = match self.metadata.read().await.get(&planned.key) {
+            Some(meta) if unchanged(meta) => (meta.collection.clone(), meta.id.clone()),
+            _ => return Ok(None),
+        };
+
+        let old_storage = self.get_storage_for_tier(planned.from);
+        let new_storage = self.get_storage_for_tier(planned.to);
+
+        let data = old_storage.read(&planned.key).await?;
+        self.fenced(guard, new_storage.write(&planned.key, &data)).await?;
+
+        let mut meta_map = self.metadata.write().await;
+        if let Ok(record) = VectorRecord::decode(&data) {
+            self.index.write().await
+                .move_tier(&collection, &id, &record.vector, planned.from, planned.to)
+                .await?;
+        }
+        // Only reads ran meanwhile; they may have counted an access
+        if let Some(meta) = meta_map.get_mut(&planned.key) {
+            meta.tier = planned.to;
+        }
+
+        Ok(Some(data.len() as u64))
+    }
+
+    fn count_access(&self, key: &str) {
+        let mut accesses = self.accesses.lock().unwrap();
+        let access = accesses.entry(key.to_string()).or_insert((0, chrono::Utc::now()));
+        access.0 += 1;
+        access.1 = chrono::Utc::now();
+    }
+
+    /// Adds the accesses counted since the last call to the table, before
+    /// anything reads or saves access history.
+    async fn fold_accesses(&self) {
+        let accesses = std::mem::take(&mut *self.accesses.lock().unwrap());
+        if accesses.is_empty() {
+            return;
+        }
+        let mut meta_map = self.metadata.write().await;
+        for (key, (count, at)) in accesses {
+            if let Some(meta) = meta_map.get_mut(&key) {
+                meta.record_accesses(count, at);
+            }
+        }
+    }
+
+    async fn lock_key(&self, key: &str) -> MutexGuard<'_, ()> {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.key_locks[hasher.finish() as usize % self.key_locks.len()].lock().await
+    }
+
+    fn metadata_path(&self) -> PathBuf {
+        Path::new(&self.data_dir).join("metadata.json")
+    }
+
+    fn table_log_path(&self) -> PathBuf {
+        Path::new(&self.data_dir).join("metadata.log")
+    }
+
+    /// Durably appends the current table entries of `keys` to
+    /// `metadata.log`, so documents written, deleted or moved since the
+    /// last `save_metadata` are found again after a crash.
+    async fn log_changes(&self, keys: &[String]) -> Result<(), String> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let _log = self.table_log.lock().await;
+        let mut lines = Vec::new();
+        {
+            let meta_map = self.metadata.read().await;
+            for key in keys {
+                let change = TableChange { key: key.clone(), meta: meta_map.get(key).cloned() };
+                lines.extend(serde_json::to_vec(&change).map_err(|e| e.to_string())?);
+                lines.push(b'\n');
+            }
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.table_log_path())
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(&lines).await.map_err(|e| e.to_string())?;
+        file.sync_data().await.map_err(|e| e.to_string())
+    }
+
+    /// Writes the document table to `{data_dir}/metadata.json` so tier
+    /// placement and access history survive restarts, and empties
+    /// `metadata.log`, whose changes the table now holds.
+    pub async fn save_metadata(&self) -> Result<(), String> {
+        // Never persist locations the tiers have not made durable yet
+        self.flush().await?;
+
+        let _log = self.table_log.lock().await;
+        self.fold_accesses().await;
+        let data = {
+            let meta_map = self.metadata.read().await;
+            serde_json::to_vec(&*meta_map).map_err(|e| e.to_string())?
+        };
+
+        let path = self.metadata_path();
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = tokio::fs::File::create(&tmp_path).await.map_err(|e| e.to_string())?;
+        file.write_all(&data).await.map_err(|e| e.to_string())?;
+        file.sync_all().await.map_err(|e| e.to_string())?;
+        tokio::fs::rename(&tmp_path, &path).await.map_err(|e| e.to_string())?;
+
+        match tokio::fs::remove_file(self.table_log_path()).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// `save_metadata` for a writer holding a lease. A save with a token
+    /// older than one the lakehouse's fence has seen is refused: its holder
+    /// was superseded, so its view of the table is stale.
+    pub async fn save_metadata_fenced(&self, token: FencingToken) -> Result<(), String> {
+        self.fence.fenced(FENCE_RESOURCE, token, self.save_metadata()).await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Runs a storage write of a migration pass, fenced by the token of the
+    /// pass's lease if it has one.
+    async fn fenced<T>(&self, guard: Option<&LockGuard>, write: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+        match guard {
+            Some(guard) => self.fence.fenced(FENCE_RESOURCE, guard.token(), write).await
+                .map_err(|e| e.to_string())?,
+            None => write.await,
+        }
+    }
+
+    /// Restores the table written by `save_metadata` with the changes
+    /// logged after it and re-indexes hot vectors. The warm index lives on
+    /// disk already and cold summaries are rebuilt on demand. Returns the
+    /// number of documents loaded.
+    pub async fn load_metadata(&self) -> Result<usize, String> {
+        let path = self.metadata_path();
+        let mut table: HashMap<String, DocumentMeta> = match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| e.to_string())?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        // A line torn by a crash is the last one and was never acknowledged
+        match tokio::fs::read(self.table_log_path()).await {
+            Ok(log) => {
+                for line in log.split(|b| *b == b'\n') {
+                    match serde_json::from_slice::<TableChange>(line) {
+                        Ok(TableChange { key, meta: Some(meta) }) => {
+                            table.insert(key, meta);
+                        }
+                        Ok(TableChange { key, meta: None }) => {
+                            table.remove(&key);
+                        }
+                        Err(_) => {}
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.to_string()),
+        }
+
+        let count = table.len();
+        let hot: Vec<DocumentMeta> = table.values()
+            .filter(|m| m.tier == StorageTier::Hot && m.vector_dimension.is_some())
+            .cloned()
+            .collect();
+
+        *self.metadata.write().await = table;
+
+        let mut index = self.index.write().await;
+        for meta in hot {
+            let key = format!("{}/{}", meta.collection, meta.id);
+            if let Ok(record) = self.hot_storage.read(&key).await.and_then(|d| VectorRecord::decode(&d)) {
+                index.insert(&meta.collection, &meta.id, &record.vector, StorageTier::Hot).await?;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Stores a vector document and indexes it in the tier it lands in.
+    pub async fn write_vector(&self, collection: &str, id: &str, vector: Vec<f32>, metadata: serde_json::Value) -> Result<(), String> {
+        let record = VectorRecord { vector, metadata };
+        self.apply_vectors(vec![(collection.to_string(), id.to_string(), Some(record))]).await
+    }
+
+    /// Stores (`Some`) or deletes (`None`) vector documents in order, with
+    /// one `metadata.log` append for the whole batch. Deletes of documents
+    /// the table does not hold are skipped. Changes applied before one that
+    /// fails are still logged.
+    pub async fn apply_vectors(&self, changes: Vec<(String, String, Option<VectorRecord>)>) -> Result<(), String> {
+        let mut applied = Vec::with_capacity(changes.len());
+        let mut moved = Vec::new();
+        let mut result = Ok(());
+        for (collection, id, record) in changes {
+            let key = format!("{}/{}", collection, id);
+            let _key_lock = self.lock_key(&key).await;
+            let previous = self.metadata.read().await.get(&key).map(|m| m.tier);
+
+            let stored = match (record, previous) {
+                (Some(record), _) => self.store_vector_locked(&collection, &id, record, previous).await.map(Some),
+                (None, Some(_)) => self.remove_locked(&collection, &id).await.map(|_| None),
+                (None, None) => continue,
+            };
+            match stored {
+                Ok(Some((tier, moved_from))) => {
+                    moved.extend(moved_from.map(|from| (key.clone(), from)));
+                    applied.push((key, collection, id, Some(tier)));
+                }
+                Ok(None) => applied.push((key, collection, id, None)),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let keys: Vec<String> = applied.iter().map(|(key, _, _, _)| key.clone()).collect();
+        self.commit_stored(&keys, &moved).await?;
+        for (_, collection, id, tier) in applied {
+            if let Some(tier) = tier {
+                self.announce(&collection, &id, tier);
+            }
+        }
+        result
+    }
+
+    /// Stores a vector document under its key lock and moves it in the
+    /// index from the `previous` tier to the one it lands in.
+    async fn store_vector_locked(
+        &self,
+        collection: &str,
+        id: &str,
+        record: VectorRecord,
+        previous: Option<StorageTier>,
+    ) -> Result<(StorageTier, Option<StorageTier>), String> {
+        let (tier, moved_from) = self.store_locked(collection, id, &record.encode()?, Some(record.vector.len())).await?;
+
+        let mut index = self.index.write().await;
+        if let Some(previous) = previous {
+            index.remove(collection, id, previous).await?;
+        }
+        index.insert(collection, id, &record.vector, tier).await?;
+        Ok((tier, moved_from))
+    }
+
+    pub async fn read_vector(&self, collection: &str, id: &str) -> Result<VectorRecord, String> {
+        VectorRecord::decode(&self.read(collection, id).await?)
+    }
+
+    /// Searches the hot index, then the warm index and cold summary as
+    /// `options` allows. Cold candidates are re-ranked with exact distances
+    /// after fetching them from cold storage.
+    pub async fn search(&self, collection: &str, query: &[f32], options: &TieredSearchOptions) -> Result<Vec<TieredSearchResult>, String> {
+        // Over-fetch so filtered-out candidates don't starve the result
+        let fetch = if options.filter.is_some() { options.k * 4 } else { options.k };
+        let mut candidates: Vec<(String, f32, StorageTier)> = Vec::new();
+        let mut records: HashMap<String, VectorRecord> = HashMap::new();
+
+        let cold_ids: std::collections::HashSet<String> = if options.include_cold {
+            self.metadata.read().await.values()
+                .filter(|m| m.collection == collection && m.tier == StorageTier::Cold)
+                .map(|m| m.id.clone())
+                .collect()
+        } else {
+            Default::default()
+        };
+
+        // Loading a collection's warm index the first time is the only
+        // part of a search that needs the index exclusively
+        if options.include_warm && !self.index.read().await.warm_loaded(collection) {
+            self.index.write().await.load_warm(collection).await?;
+        }
+
+        // The index is only locked around in-memory and local-disk work;
+        // cold-tier reads, possibly from S3, happen outside it
+        let (metric, unsummarized) = {
+            let index = self.index.read().await;
+            for (id, distance) in index.search_hot(collection, query, fetch).await? {
+                candidates.push((id, distance, StorageTier::Hot));
+            }
+
+            if options.include_warm {
+                for (id, distance) in index.search_warm(collection, query, fetch, options.warm_nprobe).await? {
+                    candidates.push((id, distance, StorageTier::Warm));
+                }
+            }
+
+            let unsummarized: Vec<String> = cold_ids.iter()
+                .filter(|id| !index.has_cold_summary(collection, id))
+                .cloned()
+                .collect();
+            (index.metric().to_string(), unsummarized)
+        };
+
+        if options.include_cold {
+            match self.scan_cold(collection, options.filter.as_ref()).await? {
+                // Segment statistics already pruned by the filter, so every
+                // remaining match is scored exactly
+                Some(scanned) => {
+                    for (id, record) in scanned {
+                        if cold_ids.contains(&id) {
+                            let distance = crate::coretex_lakehouse::search::distance(&metric, query, &record.vector);
+                            candidates.push((id.clone(), distance, StorageTier::Cold));
+                            records.insert(id, record);
+                        }
+                    }
+                }
+                None => {
+                    // Summaries are built lazily for cold documents the index has not seen yet
+                    let mut fetched = Vec::new();
+                    for id in unsummarized {
+                        if let Ok(record) = self.read_cold(collection, &id).await {
+                            fetched.push((id, record));
+                        }
+                    }
+                    let summary = {
+                        // A document migrated out of cold meanwhile must
+                        // not get its summary back. Lock order is metadata
+                        // before index, as in `migrate_document`
+                        let metadata = self.metadata.read().await;
+                        let mut index = self.index.write().await;
+                        for (id, record) in &fetched {
+                            let still_cold = metadata.get(&format!("{}/{}", collection, id))
+                                .is_some_and(|m| m.tier == StorageTier::Cold);
+                            if still_cold && !index.has_cold_summary(collection, id) {
+                                index.insert(collection, id, &record.vector, StorageTier::Cold).await?;
+                            }
+                        }
+                        let rerank = if options.cold_rerank == 0 { fetch * 4 } else { options.cold_rerank.max(fetch) };
+                        index.search_cold_summary(collection, query, rerank)
+                    };
+                    records.extend(fetched);
+
+                    for (id, _) in summary {
+                        let record = match records.remove(&id) {
+                            Some(record) => record,
+                            None => match self.read_cold(collection, &id).await {
+                                Ok(record) => record,
+                                Err(_) => continue,
+                            },
+                        };
+                        let distance = crate::coretex_lakehouse::search::distance(&metric, query, &record.vector);
+                        candidates.push((id.clone(), distance, StorageTier::Cold));
+                        records.insert(id, record);
+                    }
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut results = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for (id, distance, tier) in candidates {
+            if results.len() >= options.k {
+                break;
+            }
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+
+            let record = match records.remove(&id) {
+                Some(record) => Some(record),
+                None => self.get_storage_for_tier(tier)
+                    .read(&format!("{}/{}", collection, id))
+                    .await
+                    .ok()
+                    .and_then(|d| VectorRecord::decode(&d).ok()),
+            };
+            let metadata = record.map(|r| r.metadata);
+
+            if let Some(filter) = &options.filter {
+                match &metadata {
+                    Some(m) if crate::CoreTexDB::matches_filter(m, filter) => {}
+                    _ => continue,
+                }
+            }
+
+            results.push(TieredSearchResult { id, distance, tier, metadata });
+        }
+
+        if options.record_hits {
+            for result in &results {
+                self.count_access(&format!("{}/{}", collection, result.id));
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn read_cold(&self, collection: &str, id: &str) -> Result<VectorRecord, String> {
+        let data = self.cold_storage.read(&format!("{}/{}", collection, id)).await?;
+        VectorRecord::decode(&data)
+    }
+
+    /// Filtered scan of the cold tier when it is stored as Parquet segments.
+    #[cfg(feature = "parquet")]
+    async fn scan_cold(&self, collection: &str, filter: Option<&serde_json::Value>) -> Result<Option<Vec<(String, VectorRecord)>>, String> {
+        match (&self.cold_segments, filter) {
+            (Some(segments), Some(filter)) => Ok(Some(segments.scan(collection, Some(filter)).await?.records)),
+            _ => Ok(None),
+        }
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    async fn scan_cold(&self, _collection: &str, _filter: Option<&serde_json::Value>) -> Result<Option<Vec<(String, VectorRecord)>>, String> {
+        Ok(None)
+    }
+
+    /// Makes buffered tier writes durable.
+    pub async fn flush(&self) -> Result<(), String> {
+        for storage in [&self.hot_storage, &self.warm_storage, &self.cold_storage] {
+            storage.flush().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn document_meta(&self, collection: &str, id: &str) -> Option<DocumentMeta> {
+        self.fold_accesses().await;
+        self.metadata.read().await.get(&format!("{}/{}", collection, id)).cloned()
+    }
+
     pub async fn get_stats(&self) -> LakehouseStats {
         let meta_map = self.metadata.read().await;
         
@@ -196,12 +975,82 @@ impl VectorLakehouse {
     }
 }
 
-#[derive(Debug, Default)]
+/// Knobs for one migration pass.
+#[derive(Debug, Clone)]
+pub struct MigrationOptions {
+    pub hot_budget_bytes: Option<u64>,
+    pub warm_budget_bytes: Option<u64>,
+    pub eviction_order: EvictionOrder,
+    /// Caps migration I/O, counting bytes read plus bytes written.
+    pub max_bytes_per_sec: Option<u64>,
+    /// Caps the moves per pass; the remainder is reported as pending.
+    pub max_migrations: Option<usize>,
+}
+
+impl MigrationOptions {
+    pub fn from_config(config: &TierConfig) -> Self {
+        Self {
+            hot_budget_bytes: config.max_size_bytes(StorageTier::Hot),
+            warm_budget_bytes: config.max_size_bytes(StorageTier::Warm),
+            eviction_order: config.eviction_order,
+            max_bytes_per_sec: None,
+            max_migrations: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedMigration {
+    pub key: String,
+    pub from: StorageTier,
+    pub to: StorageTier,
+    pub size_bytes: u64,
+    /// The policy wanted a higher tier but the budget pushed it down.
+    pub evicted: bool,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct MigrationReport {
     pub migrated_count: u64,
     pub hot_count: u64,
     pub warm_count: u64,
     pub cold_count: u64,
+    pub planned_count: u64,
+    pub promoted_count: u64,
+    pub demoted_count: u64,
+    pub evicted_count: u64,
+    pub skipped_count: u64,
+    pub failed_count: u64,
+    pub pending_count: u64,
+    pub bytes_moved: u64,
+    pub duration_ms: u64,
+    pub started_at: Option<i64>,
+    /// `None` while the pass is still running.
+    pub finished_at: Option<i64>,
+    pub errors: Vec<String>,
+}
+
+/// Spreads I/O out so it averages at most `bytes_per_sec`.
+struct IoThrottle {
+    bytes_per_sec: u64,
+    started: Instant,
+    consumed: u64,
+}
+
+impl IoThrottle {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self { bytes_per_sec: bytes_per_sec.max(1), started: Instant::now(), consumed: 0 }
+    }
+
+    async fn consume(&mut self, bytes: u64) {
+        self.consumed += bytes;
+        let due = Duration::from_secs_f64(self.consumed as f64 / self.bytes_per_sec as f64);
+        let elapsed = self.started.elapsed();
+        if due > elapsed {
+            tokio::time::sleep(due - elapsed).await;
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -234,4 +1083,210 @@ mod tests {
         let stats = lakehouse.get_stats().await;
         assert_eq!(stats.total_count, 1);
     }
+
+    #[tokio::test]
+    async fn test_table_survives_a_crash_before_save() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        {
+            let lakehouse = VectorLakehouse::new(dir).unwrap();
+            lakehouse.write_vector("docs", "a", vec![1.0, 0.0], serde_json::json!({"n": 1})).await.unwrap();
+            lakehouse.write_vector("docs", "b", vec![0.0, 1.0], serde_json::json!({"n": 2})).await.unwrap();
+            lakehouse.delete("docs", "b").await.unwrap();
+        }
+
+        // Never saved: the table comes back from the log alone
+        let lakehouse = VectorLakehouse::new(dir).unwrap();
+        assert_eq!(lakehouse.load_metadata().await.unwrap(), 1);
+        assert!(lakehouse.document_meta("docs", "b").await.is_none());
+        assert_eq!(lakehouse.read_vector("docs", "a").await.unwrap().metadata["n"], 1);
+
+        lakehouse.write_vector("docs", "c", vec![0.6, 0.8], serde_json::json!({})).await.unwrap();
+        lakehouse.save_metadata().await.unwrap();
+        assert!(!temp_dir.path().join("metadata.log").exists());
+
+        let reopened = VectorLakehouse::new(dir).unwrap();
+        assert_eq!(reopened.load_metadata().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tiered_search_follows_migration() {
+        use crate::coretex_lakehouse::policy::TTLTieringPolicy;
+
+        let temp_dir = TempDir::new().unwrap();
+        let lakehouse = VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap()
+            .with_metric("euclidean");
+
+        lakehouse.write_vector("docs", "a", vec![0.0, 0.0], serde_json::json!({"kind": "x"})).await.unwrap();
+        lakehouse.write_vector("docs", "b", vec![5.0, 5.0], serde_json::json!({"kind": "y"})).await.unwrap();
+
+        let results = lakehouse.search("docs", &[0.1, 0.0], &TieredSearchOptions::new(1)).await.unwrap();
+        assert_eq!(results[0].id, "a");
+        assert_eq!(results[0].tier, StorageTier::Hot);
+        assert_eq!(lakehouse.document_meta("docs", "a").await.unwrap().access_count, 1);
+
+        // Everything older than zero days is warm
+        let warm = TierConfig { hot_threshold_days: 0, warm_threshold_days: 30, ..Default::default() };
+        let lakehouse = lakehouse.with_policy(Box::new(TTLTieringPolicy::new(warm)));
+        lakehouse.migrate_data().await.unwrap();
+
+        let options = TieredSearchOptions::new(2);
+        let results = lakehouse.search("docs", &[0.1, 0.0], &options).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.tier == StorageTier::Warm));
+        assert!(lakehouse.search("docs", &[0.1, 0.0], &options.clone().with_warm(false)).await.unwrap().is_empty());
+
+        let cold = TierConfig { hot_threshold_days: 0, warm_threshold_days: 0, ..Default::default() };
+        let lakehouse = lakehouse.with_policy(Box::new(TTLTieringPolicy::new(cold)));
+        lakehouse.migrate_data().await.unwrap();
+
+        assert!(lakehouse.search("docs", &[0.1, 0.0], &options).await.unwrap().is_empty());
+
+        let options = options.with_cold(true).with_filter(serde_json::json!({"kind": "y"}));
+        let results = lakehouse.search("docs", &[0.1, 0.0], &options).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "b");
+        assert_eq!(results[0].tier, StorageTier::Cold);
+        assert!((results[0].distance - (4.9f32 * 4.9 + 25.0).sqrt()).abs() < 1e-4);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_rewrite_into_another_tier_is_flushed_before_the_old_copy_goes() {
+        use crate::coretex_lakehouse::policy::TTLTieringPolicy;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        // Only new documents are hot
+        let warm = TierConfig { hot_threshold_days: 0, warm_threshold_days: 30, ..Default::default() };
+        let lakehouse = VectorLakehouse::new(dir).unwrap()
+            .with_policy(Box::new(TTLTieringPolicy::new(warm)))
+            .with_parquet_segments(SegmentConfig::default());
+
+        lakehouse.write_vector("docs", "a", vec![1.0, 0.0], serde_json::json!({})).await.unwrap();
+        lakehouse.write_vector("docs", "a", vec![0.0, 1.0], serde_json::json!({})).await.unwrap();
+        assert_eq!(lakehouse.document_meta("docs", "a").await.unwrap().tier, StorageTier::Warm);
+        assert!(!temp_dir.path().join("hot/docs/a").exists());
+
+        // In a segment, not just buffered by the lakehouse that wrote it
+        let reopened = VectorLakehouse::new(dir).unwrap().with_parquet_segments(SegmentConfig::default());
+        reopened.load_metadata().await.unwrap();
+        assert_eq!(reopened.read_vector("docs", "a").await.unwrap().vector, vec![0.0, 1.0]);
+    }
+
+    /// Reads and writes wait while the test holds `gate`, like a slow S3
+    /// bucket.
+    struct GatedStorage {
+        inner: LocalStorage,
+        gate: Arc<RwLock<()>>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackendTrait for GatedStorage {
+        async fn write(&self, key: &str, data: &[u8]) -> Result<(), String> {
+            let _open = self.gate.read().await;
+            self.inner.write(key, data).await
+        }
+        async fn read(&self, key: &str) -> Result<Vec<u8>, String> {
+            let _open = self.gate.read().await;
+            self.inner.read(key).await
+        }
+        async fn delete(&self, key: &str) -> Result<(), String> {
+            self.inner.delete(key).await
+        }
+        async fn exists(&self, key: &str) -> bool {
+            self.inner.exists(key).await
+        }
+        async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+            self.inner.list(prefix).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cold_reads_do_not_block_the_index() {
+        use crate::coretex_lakehouse::policy::TTLTieringPolicy;
+
+        let temp_dir = TempDir::new().unwrap();
+        let gate = Arc::new(RwLock::new(()));
+        let cold = GatedStorage {
+            inner: LocalStorage::new(temp_dir.path().join("gated").to_str().unwrap()),
+            gate: gate.clone(),
+        };
+        let config = TierConfig { hot_threshold_days: 0, warm_threshold_days: 0, ..Default::default() };
+        let lakehouse = Arc::new(
+            VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap()
+                .with_cold_storage(Arc::new(cold))
+                .with_policy(Box::new(TTLTieringPolicy::new(config))),
+        );
+        lakehouse.write_vector("docs", "a", vec![1.0, 0.0], serde_json::json!({})).await.unwrap();
+        lakehouse.migrate_data().await.unwrap();
+
+        let closed = gate.write().await;
+        let search = tokio::spawn({
+            let lakehouse = lakehouse.clone();
+            async move {
+                let options = TieredSearchOptions::new(1).with_cold(true);
+                lakehouse.search("docs", &[1.0, 0.0], &options).await
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!search.is_finished());
+
+        // The search waits on cold storage, not holding the index
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            lakehouse.write_vector("docs", "b", vec![0.0, 1.0], serde_json::json!({})),
+        ).await.unwrap().unwrap();
+
+        drop(closed);
+        let results = search.await.unwrap().unwrap();
+        assert_eq!(results[0].id, "a");
+        assert_eq!(results[0].tier, StorageTier::Cold);
+    }
+
+    #[tokio::test]
+    async fn test_migration_io_does_not_block_other_documents() {
+        use crate::coretex_lakehouse::policy::TTLTieringPolicy;
+
+        let temp_dir = TempDir::new().unwrap();
+        let gate = Arc::new(RwLock::new(()));
+        let cold = GatedStorage {
+            inner: LocalStorage::new(temp_dir.path().join("gated").to_str().unwrap()),
+            gate: gate.clone(),
+        };
+        let config = TierConfig { hot_threshold_days: 0, warm_threshold_days: 0, ..Default::default() };
+        let lakehouse = Arc::new(
+            VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap()
+                .with_cold_storage(Arc::new(cold))
+                .with_policy(Box::new(TTLTieringPolicy::new(config))),
+        );
+        lakehouse.write_vector("docs", "a", vec![1.0, 0.0], serde_json::json!({})).await.unwrap();
+
+        let closed = gate.write().await;
+        let migration = tokio::spawn({
+            let lakehouse = lakehouse.clone();
+            async move { lakehouse.migrate_data().await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!migration.is_finished());
+
+        // Stuck copying "a" to cold storage, but "b" is written regardless
+        let timeout = std::time::Duration::from_secs(5);
+        tokio::time::timeout(timeout, lakehouse.write_vector("docs", "b", vec![0.0, 1.0], serde_json::json!({})))
+            .await.unwrap().unwrap();
+        assert_eq!(lakehouse.document_meta("docs", "b").await.unwrap().tier, StorageTier::Hot);
+
+        // A write to "a" waits for its migration and is not overwritten by it
+        let rewrite = tokio::spawn({
+            let lakehouse = lakehouse.clone();
+            async move { lakehouse.write_vector("docs", "a", vec![0.5, 0.5], serde_json::json!({})).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!rewrite.is_finished());
+
+        drop(closed);
+        assert_eq!(migration.await.unwrap().unwrap().migrated_count, 1);
+        rewrite.await.unwrap().unwrap();
+        assert_eq!(lakehouse.read_vector("docs", "a").await.unwrap().vector, vec![0.5, 0.5]);
+    }
 }
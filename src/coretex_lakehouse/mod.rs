@@ -3,10 +3,20 @@
 
 pub mod tier;
 pub mod storage;
+pub mod s3;
+#[cfg(feature = "parquet")]
+pub mod segment;
+pub mod search;
 pub mod policy;
 pub mod lakehouse;
+pub mod daemon;
 
-pub use tier::{StorageTier, TierConfig, DocumentMeta};
-pub use storage::{StorageBackend, LocalConfig, S3Config, MinIOConfig, AzureConfig, StorageBackendTrait};
+pub use tier::{StorageTier, TierConfig, DocumentMeta, EvictionOrder};
+pub use storage::{StorageBackend, LocalConfig, S3Config, MinIOConfig, AzureConfig, StorageBackendTrait, LocalStorage};
+pub use s3::S3Storage;
+#[cfg(feature = "parquet")]
+pub use segment::{ParquetSegmentStore, SegmentConfig, SegmentInfo, SegmentScan};
+pub use search::{TieredSearchOptions, TieredSearchResult, TieredVectorIndex, VectorRecord, WarmIndex};
 pub use policy::{TieringPolicy, LRUTieringPolicy, TTLTieringPolicy, HybridTieringPolicy, SizeBasedPolicy};
-pub use lakehouse::{VectorLakehouse, MigrationReport, LakehouseStats};
+pub use lakehouse::{VectorLakehouse, MigrationOptions, MigrationReport, PlannedMigration, LakehouseStats};
+pub use daemon::{TieringDaemon, TieringDaemonConfig, TieringStats};
@@ -0,0 +1,528 @@
+//! Tier-aware vector search for the Vector Lakehouse
+//! Hot vectors live in an in-memory ANN index, warm vectors in an on-disk
+//! inverted-file index and cold vectors behind a scalar-quantized summary
+
+use crate::coretex_index::{HNSWIndex, VectorIndex};
+use crate::coretex_lakehouse::tier::StorageTier;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+/// Payload stored in the lakehouse for vector documents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorRecord {
+    pub vector: Vec<f32>,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+}
+
+impl VectorRecord {
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(self).map_err(|e| e.to_string())
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(data).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieredSearchOptions {
+    pub k: usize,
+    pub include_warm: bool,
+    /// Cold vectors live in object storage; searching them costs remote reads
+    pub include_cold: bool,
+    /// Warm index partitions probed per query
+    pub warm_nprobe: usize,
+    /// Cold candidates fetched for exact re-ranking; 0 means `4 * k`
+    pub cold_rerank: usize,
+    /// Exact-match metadata filter
+    pub filter: Option<serde_json::Value>,
+    /// Count returned documents as accesses so the tiering policy sees real traffic
+    pub record_hits: bool,
+}
+
+impl Default for TieredSearchOptions {
+    fn default() -> Self {
+        Self {
+            k: 10,
+            include_warm: true,
+            include_cold: false,
+            warm_nprobe: 4,
+            cold_rerank: 0,
+            filter: None,
+            record_hits: true,
+        }
+    }
+}
+
+impl TieredSearchOptions {
+    pub fn new(k: usize) -> Self {
+        Self { k, ..Default::default() }
+    }
+
+    pub fn with_warm(mut self, include: bool) -> Self {
+        self.include_warm = include;
+        self
+    }
+
+    pub fn with_cold(mut self, include: bool) -> Self {
+        self.include_cold = include;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: serde_json::Value) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieredSearchResult {
+    pub id: String,
+    pub distance: f32,
+    pub tier: StorageTier,
+    pub metadata: Option<serde_json::Value>,
+}
+
+pub(crate) fn distance(metric: &str, a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return f32::MAX;
+    }
+    match metric {
+        "euclidean" => a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f32>().sqrt(),
+        "dotproduct" => -a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>(),
+        _ => {
+            let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+            let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm_a == 0.0 || norm_b == 0.0 {
+                return 1.0;
+            }
+            1.0 - dot / (norm_a * norm_b)
+        }
+    }
+}
+
+fn sort_by_distance(results: &mut [(String, f32)]) {
+    results.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+/// 8-bit scalar quantization with a per-vector range, about 4x smaller than f32.
+#[derive(Debug, Clone)]
+struct QuantizedVector {
+    min: f32,
+    scale: f32,
+    codes: Vec<u8>,
+}
+
+impl QuantizedVector {
+    fn new(vector: &[f32]) -> Self {
+        let min = vector.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = vector.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let scale = if max > min { (max - min) / 255.0 } else { 0.0 };
+
+        let codes = vector.iter()
+            .map(|v| if scale > 0.0 { ((v - min) / scale).round().clamp(0.0, 255.0) as u8 } else { 0 })
+            .collect();
+
+        Self { min, scale, codes }
+    }
+
+    fn decode(&self) -> Vec<f32> {
+        self.codes.iter().map(|&c| self.min + c as f32 * self.scale).collect()
+    }
+}
+
+const RECORD_ADD: u8 = 1;
+const RECORD_REMOVE: u8 = 0;
+
+/// Per-collection state of the warm index. Vectors are appended to the
+/// partition of their nearest centroid; removals are tombstones.
+#[derive(Default)]
+struct WarmCollection {
+    centroids: Vec<Vec<f32>>,
+    locations: HashMap<String, usize>,
+}
+
+/// On-disk inverted-file index: only centroids and id locations stay in memory.
+pub struct WarmIndex {
+    dir: PathBuf,
+    metric: String,
+    num_partitions: usize,
+    collections: HashMap<String, WarmCollection>,
+}
+
+impl WarmIndex {
+    pub fn new(dir: PathBuf, metric: &str) -> Self {
+        Self {
+            dir,
+            metric: metric.to_string(),
+            num_partitions: 16,
+            collections: HashMap::new(),
+        }
+    }
+
+    pub fn with_partitions(mut self, num_partitions: usize) -> Self {
+        self.num_partitions = num_partitions.max(1);
+        self
+    }
+
+    fn collection_dir(&self, collection: &str) -> PathBuf {
+        self.dir.join(collection)
+    }
+
+    fn partition_path(&self, collection: &str, partition: usize) -> PathBuf {
+        self.collection_dir(collection).join(format!("p{}.bin", partition))
+    }
+
+    pub fn is_loaded(&self, collection: &str) -> bool {
+        self.collections.contains_key(collection)
+    }
+
+    /// Loads centroids and replays partitions the first time a collection is used.
+    pub async fn load(&mut self, collection: &str) -> Result<(), String> {
+        if self.collections.contains_key(collection) {
+            return Ok(());
+        }
+
+        let mut state = WarmCollection::default();
+        let centroids_path = self.collection_dir(collection).join("centroids.json");
+        if centroids_path.exists() {
+            let content = tokio::fs::read(&centroids_path).await.map_err(|e| e.to_string())?;
+            state.centroids = serde_json::from_slice(&content).map_err(|e| e.to_string())?;
+
+            // An id is only ever live in one partition; moves tombstone the old one first
+            for partition in 0..state.centroids.len() {
+                for id in self.live_records(collection, partition).await?.into_keys() {
+                    state.locations.insert(id, partition);
+                }
+            }
+        }
+
+        self.collections.insert(collection.to_string(), state);
+        Ok(())
+    }
+
+    async fn read_partition(&self, collection: &str, partition: usize) -> Result<Vec<(String, Option<Vec<f32>>)>, String> {
+        let path = self.partition_path(collection, partition);
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut records = Vec::new();
+        let mut pos = 0;
+        let read_u32 = |pos: usize| -> Option<u32> {
+            data.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        // A torn write at the tail only loses the record being appended
+        while pos < data.len() {
+            let op = data[pos];
+            let Some(id_len) = read_u32(pos + 1) else { break };
+            let id_start = pos + 5;
+            let Some(id) = data.get(id_start..id_start + id_len as usize) else { break };
+            let id = String::from_utf8_lossy(id).to_string();
+            pos = id_start + id_len as usize;
+
+            if op == RECORD_ADD {
+                let Some(dim) = read_u32(pos) else { break };
+                let start = pos + 4;
+                let Some(bytes) = data.get(start..start + dim as usize * 4) else { break };
+                let vector = bytes.chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                pos = start + dim as usize * 4;
+                records.push((id, Some(vector)));
+            } else {
+                records.push((id, None));
+            }
+        }
+
+        Ok(records)
+    }
+
+    async fn live_records(&self, collection: &str, partition: usize) -> Result<HashMap<String, Vec<f32>>, String> {
+        let mut live = HashMap::new();
+        for (id, vector) in self.read_partition(collection, partition).await? {
+            match vector {
+                Some(vector) => { live.insert(id, vector); }
+                None => { live.remove(&id); }
+            }
+        }
+        Ok(live)
+    }
+
+    fn encode_record(id: &str, vector: Option<&[f32]>) -> Vec<u8> {
+        let mut record = Vec::with_capacity(9 + id.len() + vector.map_or(0, |v| v.len() * 4));
+        record.push(if vector.is_some() { RECORD_ADD } else { RECORD_REMOVE });
+        record.extend_from_slice(&(id.len() as u32).to_le_bytes());
+        record.extend_from_slice(id.as_bytes());
+        if let Some(vector) = vector {
+            record.extend_from_slice(&(vector.len() as u32).to_le_bytes());
+            for v in vector {
+                record.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        record
+    }
+
+    async fn append(&self, collection: &str, partition: usize, id: &str, vector: Option<&[f32]>) -> Result<(), String> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.partition_path(collection, partition))
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(&Self::encode_record(id, vector)).await.map_err(|e| e.to_string())?;
+        file.flush().await.map_err(|e| e.to_string())
+    }
+
+    async fn save_centroids(&self, collection: &str) -> Result<(), String> {
+        let centroids = &self.collections[collection].centroids;
+        let content = serde_json::to_vec(centroids).map_err(|e| e.to_string())?;
+        tokio::fs::write(self.collection_dir(collection).join("centroids.json"), content)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    fn nearest_centroids(&self, centroids: &[Vec<f32>], vector: &[f32], n: usize) -> Vec<usize> {
+        let mut scored: Vec<(usize, f32)> = centroids.iter()
+            .enumerate()
+            .map(|(i, c)| (i, distance(&self.metric, vector, c)))
+            .collect();
+        scored.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.into_iter().take(n).map(|(i, _)| i).collect()
+    }
+
+    pub async fn insert(&mut self, collection: &str, id: &str, vector: &[f32]) -> Result<(), String> {
+        self.load(collection).await?;
+        self.remove(collection, id).await?;
+        tokio::fs::create_dir_all(self.collection_dir(collection)).await.map_err(|e| e.to_string())?;
+
+        // The first vectors seen become centroids; later vectors join the nearest one
+        let state = &self.collections[collection];
+        let partition = if state.centroids.len() < self.num_partitions {
+            let partition = state.centroids.len();
+            self.collections.get_mut(collection).unwrap().centroids.push(vector.to_vec());
+            self.save_centroids(collection).await?;
+            partition
+        } else {
+            self.nearest_centroids(&state.centroids, vector, 1)[0]
+        };
+
+        self.append(collection, partition, id, Some(vector)).await?;
+        self.collections.get_mut(collection).unwrap().locations.insert(id.to_string(), partition);
+        Ok(())
+    }
+
+    pub async fn remove(&mut self, collection: &str, id: &str) -> Result<bool, String> {
+        self.load(collection).await?;
+        let Some(partition) = self.collections.get_mut(collection).unwrap().locations.remove(id) else {
+            return Ok(false);
+        };
+        self.append(collection, partition, id, None).await?;
+        Ok(true)
+    }
+
+    pub async fn contains(&mut self, collection: &str, id: &str) -> Result<bool, String> {
+        self.load(collection).await?;
+        Ok(self.collections[collection].locations.contains_key(id))
+    }
+
+    pub async fn len(&mut self, collection: &str) -> Result<usize, String> {
+        self.load(collection).await?;
+        Ok(self.collections[collection].locations.len())
+    }
+
+    pub async fn search(&mut self, collection: &str, query: &[f32], k: usize, nprobe: usize) -> Result<Vec<(String, f32)>, String> {
+        self.load(collection).await?;
+        self.search_loaded(collection, query, k, nprobe).await
+    }
+
+    /// `search` without loading; a collection not loaded yet finds nothing.
+    pub async fn search_loaded(&self, collection: &str, query: &[f32], k: usize, nprobe: usize) -> Result<Vec<(String, f32)>, String> {
+        let Some(state) = self.collections.get(collection) else {
+            return Ok(Vec::new());
+        };
+
+        let mut results = Vec::new();
+        for partition in self.nearest_centroids(&state.centroids, query, nprobe.max(1)) {
+            let live = self.live_records(collection, partition).await?;
+            for (id, vector) in live {
+                if state.locations.get(&id) == Some(&partition) {
+                    results.push((id, distance(&self.metric, query, &vector)));
+                }
+            }
+        }
+
+        sort_by_distance(&mut results);
+        results.truncate(k);
+        Ok(results)
+    }
+
+    /// Rewrites a collection's partitions without tombstones or superseded records.
+    pub async fn compact(&mut self, collection: &str) -> Result<(), String> {
+        self.load(collection).await?;
+        for partition in 0..self.collections[collection].centroids.len() {
+            let live = self.live_records(collection, partition).await?;
+
+            let mut data = Vec::new();
+            for (id, vector) in &live {
+                if self.collections[collection].locations.get(id) == Some(&partition) {
+                    data.extend_from_slice(&Self::encode_record(id, Some(vector)));
+                }
+            }
+
+            let path = self.partition_path(collection, partition);
+            let tmp = path.with_extension("tmp");
+            tokio::fs::write(&tmp, data).await.map_err(|e| e.to_string())?;
+            tokio::fs::rename(&tmp, &path).await.map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// Indexes for all three tiers, keyed by collection.
+pub struct TieredVectorIndex {
+    metric: String,
+    hot: HashMap<String, HNSWIndex>,
+    warm: WarmIndex,
+    cold: HashMap<String, HashMap<String, QuantizedVector>>,
+}
+
+impl TieredVectorIndex {
+    pub fn new(warm_dir: PathBuf, metric: &str) -> Self {
+        Self {
+            metric: metric.to_string(),
+            hot: HashMap::new(),
+            warm: WarmIndex::new(warm_dir, metric),
+            cold: HashMap::new(),
+        }
+    }
+
+    pub fn metric(&self) -> &str {
+        &self.metric
+    }
+
+    pub async fn insert(&mut self, collection: &str, id: &str, vector: &[f32], tier: StorageTier) -> Result<(), String> {
+        match tier {
+            StorageTier::Hot => {
+                self.hot.entry(collection.to_string())
+                    .or_insert_with(|| HNSWIndex::new(&self.metric))
+                    .add(id, vector)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            StorageTier::Warm => self.warm.insert(collection, id, vector).await,
+            StorageTier::Cold => {
+                self.cold.entry(collection.to_string())
+                    .or_default()
+                    .insert(id.to_string(), QuantizedVector::new(vector));
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn remove(&mut self, collection: &str, id: &str, tier: StorageTier) -> Result<bool, String> {
+        match tier {
+            StorageTier::Hot => match self.hot.get(collection) {
+                Some(index) => index.remove(id).await.map_err(|e| e.to_string()),
+                None => Ok(false),
+            },
+            StorageTier::Warm => self.warm.remove(collection, id).await,
+            StorageTier::Cold => Ok(self.cold.get_mut(collection).and_then(|c| c.remove(id)).is_some()),
+        }
+    }
+
+    pub async fn move_tier(&mut self, collection: &str, id: &str, vector: &[f32], from: StorageTier, to: StorageTier) -> Result<(), String> {
+        self.remove(collection, id, from).await?;
+        self.insert(collection, id, vector, to).await
+    }
+
+    pub fn has_cold_summary(&self, collection: &str, id: &str) -> bool {
+        self.cold.get(collection).map(|c| c.contains_key(id)).unwrap_or(false)
+    }
+
+    pub async fn search_hot(&self, collection: &str, query: &[f32], k: usize) -> Result<Vec<(String, f32)>, String> {
+        match self.hot.get(collection) {
+            Some(index) => Ok(index.search(query, k).await
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|r| (r.id, r.distance))
+                .collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Searches the warm index of a collection loaded by `load_warm`.
+    pub async fn search_warm(&self, collection: &str, query: &[f32], k: usize, nprobe: usize) -> Result<Vec<(String, f32)>, String> {
+        self.warm.search_loaded(collection, query, k, nprobe).await
+    }
+
+    pub fn warm_loaded(&self, collection: &str) -> bool {
+        self.warm.is_loaded(collection)
+    }
+
+    pub async fn load_warm(&mut self, collection: &str) -> Result<(), String> {
+        self.warm.load(collection).await
+    }
+
+    /// Approximate distances from the quantized cold summary.
+    pub fn search_cold_summary(&self, collection: &str, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        let Some(summary) = self.cold.get(collection) else {
+            return Vec::new();
+        };
+
+        let mut results: Vec<(String, f32)> = summary.iter()
+            .map(|(id, q)| (id.clone(), distance(&self.metric, query, &q.decode())))
+            .collect();
+        sort_by_distance(&mut results);
+        results.truncate(k);
+        results
+    }
+
+    pub fn warm_index_mut(&mut self) -> &mut WarmIndex {
+        &mut self.warm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantized_vector_roundtrip() {
+        let vector = vec![-1.0, 0.25, 0.5, 3.0];
+        let decoded = QuantizedVector::new(&vector).decode();
+        for (a, b) in vector.iter().zip(&decoded) {
+            assert!((a - b).abs() < 0.02);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_index_persists_and_tombstones() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut index = WarmIndex::new(temp_dir.path().to_path_buf(), "euclidean").with_partitions(2);
+
+        index.insert("c", "a", &[0.0, 0.0]).await.unwrap();
+        index.insert("c", "b", &[10.0, 10.0]).await.unwrap();
+        index.insert("c", "c", &[0.5, 0.0]).await.unwrap();
+        index.remove("c", "a").await.unwrap();
+
+        let mut reopened = WarmIndex::new(temp_dir.path().to_path_buf(), "euclidean").with_partitions(2);
+        assert_eq!(reopened.len("c").await.unwrap(), 2);
+
+        let results = reopened.search("c", &[0.0, 0.0], 2, 1).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "c");
+
+        reopened.compact("c").await.unwrap();
+        let results = reopened.search("c", &[0.0, 0.0], 5, 2).await.unwrap();
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b"]);
+    }
+}
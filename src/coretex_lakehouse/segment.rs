@@ -0,0 +1,1000 @@
+//! Parquet segment storage for the lakehouse warm and cold tiers
+//! Packs documents into columnar segment files that DuckDB, Spark or pandas
+//! can read directly, with row-group statistics for predicate pushdown
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
+use arrow_array::{Array, ArrayRef, BinaryArray, FixedSizeListArray, Float32Array, RecordBatch, StringArray};
+use async_trait::async_trait;
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::{ArrowReaderMetadata, ArrowReaderOptions, ParquetRecordBatchReaderBuilder, RowSelection, RowSelector};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::metadata::{KeyValue, ParquetMetaData, ParquetMetaDataReader, RowGroupMetaData};
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+use parquet::file::reader::{ChunkReader, Length};
+use parquet::file::statistics::Statistics;
+use parquet::file::FOOTER_SIZE;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::coretex_export::{json_scalar_column, vector_array};
+use crate::coretex_lakehouse::search::VectorRecord;
+use crate::coretex_lakehouse::storage::StorageBackendTrait;
+
+/// Columns every segment may carry. Metadata fields with these names stay
+/// inside the `metadata` JSON column instead of getting their own column.
+const RESERVED_COLUMNS: [&str; 4] = ["id", "vector", "metadata", "payload"];
+
+/// Bytes fetched from the end of a segment when reading its footer. Enough
+/// for the metadata of a typical segment in one request.
+const FOOTER_PREFETCH: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentConfig {
+    /// Rows per segment file. Buffered writes are flushed once this many are pending.
+    pub target_rows: usize,
+    pub row_group_rows: usize,
+    /// Segments whose live fraction drops below this are rewritten on flush.
+    pub min_live_ratio: f64,
+    /// Whole segment files kept in memory for scans and compaction. Point
+    /// reads fetch only the footer and the row group they need.
+    pub cache_segments: usize,
+    pub zstd_level: i32,
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        Self {
+            target_rows: 65536,
+            row_group_rows: 4096,
+            min_live_ratio: 0.5,
+            cache_segments: 8,
+            zstd_level: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentInfo {
+    pub collection: String,
+    pub rows: usize,
+    pub live_rows: usize,
+    pub dimension: Option<usize>,
+    pub size_bytes: u64,
+    pub created_at: i64,
+}
+
+/// Result of a filtered scan, with how much pushdown saved.
+#[derive(Debug, Default)]
+pub struct SegmentScan {
+    pub records: Vec<(String, VectorRecord)>,
+    pub row_groups_read: usize,
+    pub row_groups_skipped: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct RowLocation {
+    segment: String,
+    row: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    next_segment: u64,
+    segments: BTreeMap<String, SegmentInfo>,
+    rows: HashMap<String, RowLocation>,
+}
+
+#[derive(Default)]
+struct SegmentState {
+    loaded: bool,
+    manifest: Manifest,
+    pending: BTreeMap<String, Vec<u8>>,
+}
+
+struct SegmentRow {
+    key: String,
+    vector: Option<Vec<f32>>,
+    metadata: Option<serde_json::Value>,
+    payload: Option<Vec<u8>>,
+}
+
+impl SegmentRow {
+    /// Vector documents are split into columns when that round-trips exactly;
+    /// anything else is kept as an opaque payload.
+    fn from_document(key: &str, data: &[u8]) -> Self {
+        if let Ok(record) = VectorRecord::decode(data) {
+            if !record.vector.is_empty() && record.encode().map(|e| e == data).unwrap_or(false) {
+                return Self {
+                    key: key.to_string(),
+                    vector: Some(record.vector),
+                    metadata: Some(record.metadata),
+                    payload: None,
+                };
+            }
+        }
+
+        Self { key: key.to_string(), vector: None, metadata: None, payload: Some(data.to_vec()) }
+    }
+
+    fn into_document(self) -> Result<Vec<u8>, String> {
+        match (self.payload, self.vector) {
+            (Some(payload), _) => Ok(payload),
+            (None, Some(vector)) => VectorRecord {
+                vector,
+                metadata: self.metadata.unwrap_or(serde_json::Value::Null),
+            }.encode(),
+            (None, None) => Err(format!("Segment row has no data: {}", self.key)),
+        }
+    }
+
+    fn collection(&self) -> &str {
+        collection_of(&self.key)
+    }
+}
+
+fn collection_of(key: &str) -> &str {
+    key.split_once('/').map(|(collection, _)| collection).unwrap_or("")
+}
+
+/// Stores documents in Parquet segment files on top of another backend.
+/// Writes are buffered and become segments on `flush`, one or more per
+/// collection and vector dimension. A JSON manifest maps every document to
+/// its segment row; deletes and overwrites only update the manifest until
+/// compaction rewrites the segment.
+pub struct ParquetSegmentStore {
+    inner: Arc<dyn StorageBackendTrait>,
+    prefix: String,
+    config: SegmentConfig,
+    state: Mutex<SegmentState>,
+    cache: std::sync::Mutex<VecDeque<(String, Bytes)>>,
+    /// Decoded footers of segments read by point lookups, dropped with the segment.
+    footers: std::sync::Mutex<HashMap<String, ArrowReaderMetadata>>,
+}
+
+impl ParquetSegmentStore {
+    pub fn new(inner: Arc<dyn StorageBackendTrait>, prefix: &str, config: SegmentConfig) -> Self {
+        Self {
+            inner,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            config,
+            state: Mutex::new(SegmentState::default()),
+            cache: std::sync::Mutex::new(VecDeque::new()),
+            footers: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &SegmentConfig {
+        &self.config
+    }
+
+    fn manifest_key(&self) -> String {
+        format!("{}/manifest.json", self.prefix)
+    }
+
+    async fn state(&self) -> Result<MutexGuard<'_, SegmentState>, String> {
+        let mut state = self.state.lock().await;
+        if !state.loaded {
+            let key = self.manifest_key();
+            // A manifest that could not be checked for must not pass for a
+            // missing one, which the next flush would write over
+            if self.inner.try_exists(&key).await? {
+                let data = self.inner.read(&key).await?;
+                state.manifest = serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+            }
+            state.loaded = true;
+        }
+        Ok(state)
+    }
+
+    pub async fn segments(&self) -> Result<Vec<(String, SegmentInfo)>, String> {
+        let state = self.state().await?;
+        Ok(state.manifest.segments.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+    }
+
+    pub async fn pending_rows(&self) -> Result<usize, String> {
+        Ok(self.state().await?.pending.len())
+    }
+
+    async fn segment_bytes(&self, segment: &str) -> Result<Bytes, String> {
+        if let Some((_, data)) = self.cache.lock().unwrap().iter().find(|(name, _)| name == segment) {
+            return Ok(data.clone());
+        }
+
+        let data = Bytes::from(self.inner.read(segment).await?);
+        let mut cache = self.cache.lock().unwrap();
+        cache.push_front((segment.to_string(), data.clone()));
+        cache.truncate(self.config.cache_segments.max(1));
+        Ok(data)
+    }
+
+    fn evict_cached(&self, segment: &str) {
+        self.cache.lock().unwrap().retain(|(name, _)| name != segment);
+        self.footers.lock().unwrap().remove(segment);
+    }
+
+    /// Reads the footer of a segment `size` bytes long with ranged reads of
+    /// its tail, so a point lookup never downloads the whole file.
+    async fn segment_footer(&self, segment: &str, size: u64) -> Result<ArrowReaderMetadata, String> {
+        if let Some(metadata) = self.footers.lock().unwrap().get(segment) {
+            return Ok(metadata.clone());
+        }
+
+        let tail_start = size.saturating_sub(FOOTER_PREFETCH);
+        let mut tail = self.inner.read_range(segment, tail_start, size).await?;
+        if tail.len() < FOOTER_SIZE {
+            return Err(format!("Segment is truncated: {}", segment));
+        }
+        let mut footer = [0u8; FOOTER_SIZE];
+        footer.copy_from_slice(&tail[tail.len() - FOOTER_SIZE..]);
+        let metadata_len = ParquetMetaDataReader::decode_footer(&footer).map_err(|e| e.to_string())?;
+
+        let needed = (metadata_len + FOOTER_SIZE) as u64;
+        if needed > size {
+            return Err(format!("Segment is truncated: {}", segment));
+        }
+        if needed > tail.len() as u64 {
+            let mut head = self.inner.read_range(segment, size - needed, tail_start).await?;
+            head.extend_from_slice(&tail);
+            tail = head;
+        }
+
+        let end = tail.len() - FOOTER_SIZE;
+        let metadata = ParquetMetaDataReader::decode_metadata(&tail[end - metadata_len..end]).map_err(|e| e.to_string())?;
+        let metadata = ArrowReaderMetadata::try_new(Arc::new(metadata), ArrowReaderOptions::default())
+            .map_err(|e| e.to_string())?;
+        self.footers.lock().unwrap().insert(segment.to_string(), metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Writes pending documents as segments and compacts small or mostly
+    /// deleted segments into full ones.
+    pub async fn flush(&self) -> Result<(), String> {
+        let mut state = self.state().await?;
+        self.flush_locked(&mut state).await
+    }
+
+    async fn flush_locked(&self, state: &mut SegmentState) -> Result<(), String> {
+        let mut rows: Vec<SegmentRow> = state.pending.iter()
+            .map(|(key, data)| SegmentRow::from_document(key, data))
+            .collect();
+        let pending_groups: BTreeSet<(String, Option<usize>)> = rows.iter()
+            .map(|row| (row.collection().to_string(), row.vector.as_ref().map(|v| v.len())))
+            .collect();
+
+        let (rewrite, dead) = self.compaction_candidates(&state.manifest, &pending_groups);
+        if rows.is_empty() && rewrite.is_empty() && dead.is_empty() {
+            return Ok(());
+        }
+
+        for segment in &rewrite {
+            let data = self.segment_bytes(segment).await?;
+            let collection = state.manifest.segments[segment].collection.clone();
+            let (segment_rows, _, _) = read_row_groups(data, &collection, |_| true)?;
+            rows.extend(segment_rows.into_iter()
+                .filter(|(row, r)| {
+                    !state.pending.contains_key(&r.key)
+                        && state.manifest.rows.get(&r.key) == Some(&RowLocation { segment: segment.clone(), row: *row })
+                })
+                .map(|(_, r)| r));
+        }
+
+        let obsolete: Vec<String> = rewrite.into_iter().chain(dead).collect();
+        for segment in &obsolete {
+            state.manifest.segments.remove(segment);
+        }
+
+        self.write_segments(&mut state.manifest, rows).await?;
+        state.pending.clear();
+
+        // Backends replace an object whole (a synced temporary file locally,
+        // one PUT on S3), so a crash leaves the old manifest or the new one
+        let manifest = serde_json::to_vec(&state.manifest).map_err(|e| e.to_string())?;
+        self.inner.write(&self.manifest_key(), &manifest).await?;
+
+        for segment in obsolete {
+            self.evict_cached(&segment);
+            self.inner.delete(&segment).await.ok();
+        }
+
+        Ok(())
+    }
+
+    /// Returns segments to rewrite and segments with no live rows left.
+    /// Small segments are merged when a group has more than one of them,
+    /// counting the segment about to be written from pending rows.
+    fn compaction_candidates(&self, manifest: &Manifest, pending_groups: &BTreeSet<(String, Option<usize>)>) -> (Vec<String>, Vec<String>) {
+        let small_rows = (self.config.target_rows / 2).max(1);
+        let mut dead = Vec::new();
+        let mut sparse = Vec::new();
+        let mut small: BTreeMap<(String, Option<usize>), Vec<String>> = BTreeMap::new();
+
+        for (name, info) in &manifest.segments {
+            if info.live_rows == 0 {
+                dead.push(name.clone());
+            } else if (info.live_rows as f64) < info.rows as f64 * self.config.min_live_ratio {
+                sparse.push(name.clone());
+            } else if info.rows < small_rows {
+                small.entry((info.collection.clone(), info.dimension)).or_default().push(name.clone());
+            }
+        }
+
+        for (group, names) in small {
+            if names.len() + pending_groups.contains(&group) as usize >= 2 {
+                sparse.extend(names);
+            }
+        }
+
+        (sparse, dead)
+    }
+
+    async fn write_segments(&self, manifest: &mut Manifest, rows: Vec<SegmentRow>) -> Result<(), String> {
+        let mut groups: BTreeMap<(String, Option<usize>), Vec<SegmentRow>> = BTreeMap::new();
+        for row in rows {
+            let group = (row.collection().to_string(), row.vector.as_ref().map(|v| v.len()));
+            groups.entry(group).or_default().push(row);
+        }
+
+        for ((collection, dimension), rows) in groups {
+            for chunk in rows.chunks(self.config.target_rows.max(1)) {
+                let name = format!(
+                    "{}/{}/segment-{:010}.parquet",
+                    self.prefix,
+                    if collection.is_empty() { "_" } else { &collection },
+                    manifest.next_segment,
+                );
+                manifest.next_segment += 1;
+
+                let data = self.encode_segment(&collection, dimension, chunk)?;
+                self.inner.write(&name, &data).await?;
+
+                for (row, r) in chunk.iter().enumerate() {
+                    let location = RowLocation { segment: name.clone(), row };
+                    if let Some(previous) = manifest.rows.insert(r.key.clone(), location) {
+                        if let Some(info) = manifest.segments.get_mut(&previous.segment) {
+                            info.live_rows = info.live_rows.saturating_sub(1);
+                        }
+                    }
+                }
+                manifest.segments.insert(name, SegmentInfo {
+                    collection: collection.clone(),
+                    rows: chunk.len(),
+                    live_rows: chunk.len(),
+                    dimension,
+                    size_bytes: data.len() as u64,
+                    created_at: chrono::Utc::now().timestamp(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_segment(&self, collection: &str, dimension: Option<usize>, rows: &[SegmentRow]) -> Result<Vec<u8>, String> {
+        let ids: StringArray = rows.iter()
+            .map(|r| Some(r.key.strip_prefix(&format!("{}/", collection)).unwrap_or(&r.key)))
+            .collect();
+        let mut columns: Vec<(String, ArrayRef)> = vec![("id".to_string(), Arc::new(ids))];
+
+        if let Some(dimension) = dimension {
+            let vectors: Vec<Option<Vec<f32>>> = rows.iter().map(|r| r.vector.clone()).collect();
+            columns.push(("vector".to_string(), vector_array(&vectors, dimension)));
+            let metadata: StringArray = rows.iter()
+                .map(|r| r.metadata.as_ref().map(|m| m.to_string()))
+                .collect();
+            columns.push(("metadata".to_string(), Arc::new(metadata)));
+
+            // Top-level scalar fields get typed columns so readers can filter
+            // on them and row groups carry min/max statistics for pushdown
+            let mut fields: BTreeSet<&str> = BTreeSet::new();
+            for row in rows {
+                if let Some(object) = row.metadata.as_ref().and_then(|m| m.as_object()) {
+                    fields.extend(object.keys().map(|k| k.as_str()));
+                }
+            }
+            for field in fields.into_iter().filter(|f| !RESERVED_COLUMNS.contains(f)) {
+                let values: Vec<Option<&serde_json::Value>> = rows.iter()
+                    .map(|r| r.metadata.as_ref().and_then(|m| m.get(field)).filter(|v| !v.is_null()))
+                    .collect();
+                if let Some(column) = json_scalar_column(&values) {
+                    columns.push((field.to_string(), column));
+                }
+            }
+        } else {
+            let payloads: BinaryArray = rows.iter().map(|r| r.payload.as_deref()).collect();
+            columns.push(("payload".to_string(), Arc::new(payloads)));
+        }
+
+        let batch = RecordBatch::try_from_iter(columns).map_err(|e| e.to_string())?;
+
+        let mut key_value = vec![KeyValue::new("coretex.collection".to_string(), collection.to_string())];
+        if let Some(dimension) = dimension {
+            key_value.push(KeyValue::new("coretex.dimension".to_string(), dimension.to_string()));
+        }
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::try_new(self.config.zstd_level).map_err(|e| e.to_string())?))
+            .set_max_row_group_size(self.config.row_group_rows.max(1))
+            .set_statistics_enabled(EnabledStatistics::Chunk)
+            .set_key_value_metadata(Some(key_value))
+            .build();
+
+        let mut data = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut data, batch.schema(), Some(props)).map_err(|e| e.to_string())?;
+        writer.write(&batch).map_err(|e| e.to_string())?;
+        writer.close().map_err(|e| e.to_string())?;
+        Ok(data)
+    }
+
+    /// Reads one row, fetching only the footer and the row group holding it
+    /// unless the whole segment is already cached.
+    async fn read_location(&self, location: &RowLocation) -> Result<Vec<u8>, String> {
+        let (collection, size) = self.state().await?.manifest.segments.get(&location.segment)
+            .map(|info| (info.collection.clone(), info.size_bytes))
+            .ok_or_else(|| format!("Segment not found: {}", location.segment))?;
+
+        let cached = self.cache.lock().unwrap().iter()
+            .find(|(name, _)| *name == location.segment)
+            .map(|(_, data)| data.clone());
+        if let Some(data) = cached {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(data).map_err(|e| e.to_string())?;
+            return read_row(builder, &collection, location.row)?.into_document();
+        }
+
+        let metadata = self.segment_footer(&location.segment, size).await?;
+        let (row_group, _) = locate_row(metadata.metadata(), location.row)?;
+        let (start, end) = row_group_range(metadata.metadata().row_group(row_group));
+        let data = Bytes::from(self.inner.read_range(&location.segment, start, end).await?);
+        if (data.len() as u64) < end - start {
+            return Err(format!("Segment is truncated: {}", location.segment));
+        }
+
+        let reader = RangeReader { start, data, size };
+        let builder = ParquetRecordBatchReaderBuilder::new_with_metadata(reader, metadata);
+        read_row(builder, &collection, location.row)?.into_document()
+    }
+
+    /// Reads the live vector documents of `collection` that match `filter`.
+    /// Row groups whose statistics rule out the filter are never decoded.
+    pub async fn scan(&self, collection: &str, filter: Option<&serde_json::Value>) -> Result<SegmentScan, String> {
+        let (pending, segments, locations) = {
+            let state = self.state().await?;
+            let prefix = format!("{}/", collection);
+            let pending: Vec<SegmentRow> = state.pending.iter()
+                .filter(|(key, _)| key.starts_with(&prefix))
+                .map(|(key, data)| SegmentRow::from_document(key, data))
+                .collect();
+            let segments: Vec<String> = state.manifest.segments.iter()
+                .filter(|(_, info)| info.collection == collection && info.dimension.is_some() && info.live_rows > 0)
+                .map(|(name, _)| name.clone())
+                .collect();
+            let locations: HashMap<String, RowLocation> = state.manifest.rows.iter()
+                .filter(|(key, _)| key.starts_with(&prefix) && !state.pending.contains_key(*key))
+                .map(|(key, location)| (key.clone(), location.clone()))
+                .collect();
+            (pending, segments, locations)
+        };
+
+        let mut scan = SegmentScan::default();
+        let mut rows = pending;
+
+        let filter_fields = filter.and_then(|f| f.as_object());
+        for segment in segments {
+            let data = self.segment_bytes(&segment).await?;
+            let (segment_rows, read, skipped) = read_row_groups(data, collection, |row_group| {
+                filter_fields.map(|fields| row_group_may_match(row_group, fields)).unwrap_or(true)
+            })?;
+            scan.row_groups_read += read;
+            scan.row_groups_skipped += skipped;
+
+            rows.extend(segment_rows.into_iter()
+                .filter(|(row, r)| locations.get(&r.key) == Some(&RowLocation { segment: segment.clone(), row: *row }))
+                .map(|(_, r)| r));
+        }
+
+        for row in rows {
+            if let (Some(vector), Some(metadata)) = (row.vector, row.metadata) {
+                if filter.map(|f| crate::CoreTexDB::matches_filter(&metadata, f)).unwrap_or(true) {
+                    let id = row.key[collection.len() + 1..].to_string();
+                    scan.records.push((id, VectorRecord { vector, metadata }));
+                }
+            }
+        }
+
+        Ok(scan)
+    }
+}
+
+#[async_trait]
+impl StorageBackendTrait for ParquetSegmentStore {
+    async fn write(&self, key: &str, data: &[u8]) -> Result<(), String> {
+        let mut state = self.state().await?;
+        state.pending.insert(key.to_string(), data.to_vec());
+        if state.pending.len() >= self.config.target_rows {
+            self.flush_locked(&mut state).await?;
+        }
+        Ok(())
+    }
+
+    async fn read(&self, key: &str) -> Result<Vec<u8>, String> {
+        let location = {
+            let state = self.state().await?;
+            if let Some(data) = state.pending.get(key) {
+                return Ok(data.clone());
+            }
+            state.manifest.rows.get(key).cloned()
+        };
+
+        match location {
+            Some(location) => match self.read_location(&location).await {
+                Ok(data) => Ok(data),
+                Err(e) => {
+                    // Compaction may have moved the row between lookup and read
+                    let current = self.state().await?.manifest.rows.get(key).cloned();
+                    match current {
+                        Some(current) if current != location => self.read_location(&current).await,
+                        _ => Err(e),
+                    }
+                }
+            },
+            None => Err(format!("Document not found: {}", key)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        let mut state = self.state().await?;
+        let pending = state.pending.remove(key).is_some();
+        match state.manifest.rows.remove(key) {
+            Some(location) => {
+                if let Some(info) = state.manifest.segments.get_mut(&location.segment) {
+                    info.live_rows = info.live_rows.saturating_sub(1);
+                }
+                Ok(())
+            }
+            None if pending => Ok(()),
+            None => Err(format!("Document not found: {}", key)),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> bool {
+        self.try_exists(key).await.unwrap_or(false)
+    }
+
+    async fn try_exists(&self, key: &str) -> Result<bool, String> {
+        let state = self.state().await?;
+        Ok(state.pending.contains_key(key) || state.manifest.rows.contains_key(key))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let state = self.state().await?;
+        let keys: BTreeSet<&String> = state.pending.keys()
+            .chain(state.manifest.rows.keys())
+            .filter(|key| key.starts_with(prefix))
+            .collect();
+        Ok(keys.into_iter().cloned().collect())
+    }
+
+    async fn flush(&self) -> Result<(), String> {
+        ParquetSegmentStore::flush(self).await
+    }
+}
+
+/// Bytes `start..start + data.len()` of a segment `size` bytes long: the
+/// column chunks of one row group, which is all a point read decodes.
+struct RangeReader {
+    start: u64,
+    data: Bytes,
+    size: u64,
+}
+
+impl RangeReader {
+    fn slice(&self, start: u64, length: usize) -> parquet::errors::Result<Bytes> {
+        let offset = start.checked_sub(self.start).map(|o| o as usize)
+            .filter(|o| o + length <= self.data.len())
+            .ok_or_else(|| parquet::errors::ParquetError::General(format!("Bytes {}..{} were not fetched", start, start + length as u64)))?;
+        Ok(self.data.slice(offset..offset + length))
+    }
+}
+
+impl Length for RangeReader {
+    fn len(&self) -> u64 {
+        self.size
+    }
+}
+
+impl ChunkReader for RangeReader {
+    type T = bytes::buf::Reader<Bytes>;
+
+    fn get_read(&self, start: u64) -> parquet::errors::Result<Self::T> {
+        let length = (self.start + self.data.len() as u64).saturating_sub(start) as usize;
+        Ok(bytes::Buf::reader(self.slice(start, length)?))
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> parquet::errors::Result<Bytes> {
+        self.slice(start, length)
+    }
+}
+
+/// Returns the row group holding `row` and the row's offset within it.
+fn locate_row(metadata: &ParquetMetaData, row: usize) -> Result<(usize, usize), String> {
+    let mut offset = row;
+    for (i, meta) in metadata.row_groups().iter().enumerate() {
+        let rows = meta.num_rows() as usize;
+        if offset < rows {
+            return Ok((i, offset));
+        }
+        offset -= rows;
+    }
+    Err(format!("Row {} is out of range", row))
+}
+
+/// Byte range `start..end` covering every column chunk of a row group.
+fn row_group_range(row_group: &RowGroupMetaData) -> (u64, u64) {
+    row_group.columns().iter()
+        .map(|column| {
+            let (start, length) = column.byte_range();
+            (start, start + length)
+        })
+        .reduce(|(a_start, a_end), (b_start, b_end)| (a_start.min(b_start), a_end.max(b_end)))
+        .unwrap_or((0, 0))
+}
+
+fn read_row<T: ChunkReader + 'static>(builder: ParquetRecordBatchReaderBuilder<T>, collection: &str, row: usize) -> Result<SegmentRow, String> {
+    let (row_group, offset) = locate_row(builder.metadata(), row)?;
+
+    let selection = RowSelection::from(vec![RowSelector::skip(offset), RowSelector::select(1)]);
+    let reader = builder
+        .with_row_groups(vec![row_group])
+        .with_row_selection(selection)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    for batch in reader {
+        let batch = batch.map_err(|e| e.to_string())?;
+        if let Some(row) = batch_rows(&batch, collection)?.into_iter().next() {
+            return Ok(row);
+        }
+    }
+    Err(format!("Row {} is out of range", row))
+}
+
+/// Rows with their position in the file, plus row groups read and skipped.
+type DecodedRowGroups = (Vec<(usize, SegmentRow)>, usize, usize);
+
+/// Decodes the row groups `keep` accepts.
+fn read_row_groups<F>(data: Bytes, collection: &str, keep: F) -> Result<DecodedRowGroups, String>
+where
+    F: Fn(&RowGroupMetaData) -> bool,
+{
+    let builder = ParquetRecordBatchReaderBuilder::try_new(data).map_err(|e| e.to_string())?;
+
+    let mut selected = Vec::new();
+    let mut offsets = Vec::new();
+    let mut offset = 0usize;
+    for (i, meta) in builder.metadata().row_groups().iter().enumerate() {
+        if keep(meta) {
+            selected.push(i);
+            offsets.push((offset, meta.num_rows() as usize));
+        }
+        offset += meta.num_rows() as usize;
+    }
+    let skipped = builder.metadata().num_row_groups() - selected.len();
+    if selected.is_empty() {
+        return Ok((Vec::new(), 0, skipped));
+    }
+
+    let read = selected.len();
+    let reader = builder.with_row_groups(selected).build().map_err(|e| e.to_string())?;
+
+    let mut decoded = Vec::new();
+    for batch in reader {
+        decoded.extend(batch_rows(&batch.map_err(|e| e.to_string())?, collection)?);
+    }
+
+    let positions = offsets.into_iter().flat_map(|(start, len)| start..start + len);
+    Ok((positions.zip(decoded).collect(), read, skipped))
+}
+
+fn batch_rows(batch: &RecordBatch, collection: &str) -> Result<Vec<SegmentRow>, String> {
+    let ids = batch.column_by_name("id")
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        .ok_or("Segment is missing the id column")?;
+    let vectors = batch.column_by_name("vector").and_then(|c| c.as_any().downcast_ref::<FixedSizeListArray>());
+    let metadata = batch.column_by_name("metadata").and_then(|c| c.as_any().downcast_ref::<StringArray>());
+    let payloads = batch.column_by_name("payload").and_then(|c| c.as_any().downcast_ref::<BinaryArray>());
+
+    (0..batch.num_rows())
+        .map(|i| {
+            let id = ids.value(i);
+            let vector = match vectors {
+                Some(vectors) if !vectors.is_null(i) => {
+                    let values = vectors.value(i);
+                    let values = values.as_any().downcast_ref::<Float32Array>().ok_or("Vector column is not float32")?;
+                    Some(values.values().to_vec())
+                }
+                _ => None,
+            };
+            let metadata = match metadata {
+                Some(metadata) if !metadata.is_null(i) => {
+                    Some(serde_json::from_str(metadata.value(i)).map_err(|e| e.to_string())?)
+                }
+                _ => None,
+            };
+            let payload = payloads.filter(|p| !p.is_null(i)).map(|p| p.value(i).to_vec());
+
+            Ok(SegmentRow {
+                key: if collection.is_empty() { id.to_string() } else { format!("{}/{}", collection, id) },
+                vector,
+                metadata,
+                payload,
+            })
+        })
+        .collect()
+}
+
+/// False only when the row group's statistics prove no row can equal every
+/// field in `filter`. Promoted columns hold one JSON type per segment, so a
+/// filter value of another type cannot match either.
+fn row_group_may_match(row_group: &RowGroupMetaData, filter: &serde_json::Map<String, serde_json::Value>) -> bool {
+    for (field, expected) in filter {
+        if RESERVED_COLUMNS.contains(&field.as_str()) || expected.is_null() {
+            continue;
+        }
+        let stats = match row_group.columns().iter()
+            .find(|c| c.column_path().string() == *field)
+            .and_then(|c| c.statistics())
+        {
+            Some(stats) => stats,
+            None => continue,
+        };
+
+        if stats.null_count_opt() == Some(row_group.num_rows() as u64) {
+            return false;
+        }
+
+        let within = |min: f64, max: f64, value: f64| min <= value && value <= max;
+        let may_match = match (stats, expected) {
+            (Statistics::ByteArray(s), serde_json::Value::String(value)) => match (s.min_opt(), s.max_opt()) {
+                (Some(min), Some(max)) => min.data() <= value.as_bytes() && value.as_bytes() <= max.data(),
+                _ => true,
+            },
+            (Statistics::Int64(s), serde_json::Value::Number(value)) => match (s.min_opt(), s.max_opt(), value.as_f64()) {
+                (Some(min), Some(max), Some(value)) => within(*min as f64, *max as f64, value),
+                _ => true,
+            },
+            (Statistics::Double(s), serde_json::Value::Number(value)) => match (s.min_opt(), s.max_opt(), value.as_f64()) {
+                (Some(min), Some(max), Some(value)) => within(*min, *max, value),
+                _ => true,
+            },
+            (Statistics::Boolean(s), serde_json::Value::Bool(value)) => match (s.min_opt(), s.max_opt()) {
+                (Some(min), Some(max)) => min <= value && value <= max,
+                _ => true,
+            },
+            (Statistics::ByteArray(_) | Statistics::Int64(_) | Statistics::Double(_) | Statistics::Boolean(_), _) => false,
+            _ => true,
+        };
+
+        if !may_match {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coretex_lakehouse::storage::LocalStorage;
+    use tempfile::TempDir;
+
+    fn document(vector: Vec<f32>, metadata: serde_json::Value) -> Vec<u8> {
+        VectorRecord { vector, metadata }.encode().unwrap()
+    }
+
+    fn parquet_files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                files.extend(parquet_files(&path));
+            } else if path.extension().map(|e| e == "parquet").unwrap_or(false) {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_and_compaction() {
+        let temp_dir = TempDir::new().unwrap();
+        let inner = Arc::new(LocalStorage::new(temp_dir.path().to_str().unwrap()));
+        let config = SegmentConfig { target_rows: 8, row_group_rows: 4, ..Default::default() };
+        let store = ParquetSegmentStore::new(inner.clone(), "segments", config.clone());
+
+        for i in 0..3 {
+            store.write(&format!("docs/{}", i), &document(vec![i as f32, 1.0], serde_json::json!({"n": i}))).await.unwrap();
+        }
+        store.write("docs/raw", b"not a vector").await.unwrap();
+        assert_eq!(store.read("docs/1").await.unwrap(), document(vec![1.0, 1.0], serde_json::json!({"n": 1})));
+        store.flush().await.unwrap();
+
+        // Vectors and opaque payloads land in separate segments
+        assert_eq!(store.segments().await.unwrap().len(), 2);
+        assert_eq!(store.read("docs/raw").await.unwrap(), b"not a vector");
+        assert_eq!(store.read("docs/2").await.unwrap(), document(vec![2.0, 1.0], serde_json::json!({"n": 2})));
+
+        for i in 3..6 {
+            store.write(&format!("docs/{}", i), &document(vec![i as f32, 1.0], serde_json::json!({"n": i}))).await.unwrap();
+        }
+        store.delete("docs/0").await.unwrap();
+        store.flush().await.unwrap();
+
+        // The small vector segment is merged with the new rows instead of
+        // leaving a second small file behind
+        assert_eq!(parquet_files(temp_dir.path()).len(), 2);
+        assert!(!store.exists("docs/0").await);
+
+        // A fresh store picks everything up from the manifest
+        let reopened = ParquetSegmentStore::new(inner, "segments", config);
+        let mut keys = reopened.list("docs/").await.unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["docs/1", "docs/2", "docs/3", "docs/4", "docs/5", "docs/raw"]);
+        assert_eq!(reopened.read("docs/5").await.unwrap(), document(vec![5.0, 1.0], serde_json::json!({"n": 5})));
+    }
+
+    /// Counts bytes read from segment files, whole or by range.
+    struct CountingStorage {
+        inner: LocalStorage,
+        segment_bytes: std::sync::atomic::AtomicU64,
+    }
+
+    #[async_trait]
+    impl StorageBackendTrait for CountingStorage {
+        async fn write(&self, key: &str, data: &[u8]) -> Result<(), String> {
+            self.inner.write(key, data).await
+        }
+
+        async fn read(&self, key: &str) -> Result<Vec<u8>, String> {
+            let data = self.inner.read(key).await?;
+            if key.ends_with(".parquet") {
+                self.segment_bytes.fetch_add(data.len() as u64, std::sync::atomic::Ordering::SeqCst);
+            }
+            Ok(data)
+        }
+
+        async fn read_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>, String> {
+            let data = self.inner.read_range(key, start, end).await?;
+            self.segment_bytes.fetch_add(data.len() as u64, std::sync::atomic::Ordering::SeqCst);
+            Ok(data)
+        }
+
+        async fn delete(&self, key: &str) -> Result<(), String> {
+            self.inner.delete(key).await
+        }
+
+        async fn exists(&self, key: &str) -> bool {
+            self.inner.exists(key).await
+        }
+
+        async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+            self.inner.list(prefix).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_point_reads_fetch_one_row_group() {
+        let temp_dir = TempDir::new().unwrap();
+        let inner = Arc::new(CountingStorage {
+            inner: LocalStorage::new(temp_dir.path().to_str().unwrap()),
+            segment_bytes: std::sync::atomic::AtomicU64::new(0),
+        });
+        let config = SegmentConfig { target_rows: 4096, row_group_rows: 64, ..Default::default() };
+        let store = ParquetSegmentStore::new(inner.clone(), "segments", config);
+
+        let vector = |i: usize| -> Vec<f32> { (0..64).map(|d| ((i * 64 + d) as f32).sqrt()).collect() };
+        for i in 0..4096 {
+            store.write(&format!("docs/{}", i), &document(vector(i), serde_json::json!({"n": i}))).await.unwrap();
+        }
+        store.flush().await.unwrap();
+        let (_, info) = store.segments().await.unwrap().remove(0);
+
+        assert_eq!(store.read("docs/3000").await.unwrap(), document(vector(3000), serde_json::json!({"n": 3000})));
+        let fetched = inner.segment_bytes.load(std::sync::atomic::Ordering::SeqCst);
+        assert!(fetched < info.size_bytes / 2, "fetched {} of {} bytes", fetched, info.size_bytes);
+
+        // The footer is cached, so another row costs only its row group
+        assert_eq!(store.read("docs/3").await.unwrap(), document(vector(3), serde_json::json!({"n": 3})));
+        let second = inner.segment_bytes.load(std::sync::atomic::Ordering::SeqCst) - fetched;
+        assert!(second < info.size_bytes / 16, "fetched {} of {} bytes", second, info.size_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_scan_pushes_filters_into_row_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let inner = Arc::new(LocalStorage::new(temp_dir.path().to_str().unwrap()));
+        let config = SegmentConfig { row_group_rows: 25, ..Default::default() };
+        let store = ParquetSegmentStore::new(inner, "segments", config);
+
+        for i in 0..100 {
+            let metadata = serde_json::json!({"bucket": i / 25, "kind": if i % 2 == 0 { "even" } else { "odd" }});
+            store.write(&format!("docs/{:03}", i), &document(vec![i as f32; 4], metadata)).await.unwrap();
+        }
+        store.flush().await.unwrap();
+
+        let scan = store.scan("docs", Some(&serde_json::json!({"bucket": 2, "kind": "odd"}))).await.unwrap();
+        assert_eq!(scan.records.len(), 12);
+        assert!(scan.records.iter().all(|(_, r)| r.metadata["bucket"] == 2));
+        assert_eq!(scan.row_groups_read, 1);
+        assert_eq!(scan.row_groups_skipped, 3);
+
+        // A value of the wrong type rules out every row group
+        let scan = store.scan("docs", Some(&serde_json::json!({"kind": 1}))).await.unwrap();
+        assert!(scan.records.is_empty());
+        assert_eq!(scan.row_groups_read, 0);
+    }
+
+    #[tokio::test]
+    async fn test_segments_are_plain_parquet() {
+        let temp_dir = TempDir::new().unwrap();
+        let inner = Arc::new(LocalStorage::new(temp_dir.path().to_str().unwrap()));
+        let store = ParquetSegmentStore::new(inner, "segments", SegmentConfig::default());
+
+        for i in 0..10 {
+            let metadata = serde_json::json!({"title": format!("doc {}", i), "score": i as f64 / 2.0});
+            store.write(&format!("docs/{}", i), &document(vec![i as f32; 3], metadata)).await.unwrap();
+        }
+        store.flush().await.unwrap();
+
+        let files = parquet_files(temp_dir.path());
+        assert_eq!(files.len(), 1);
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&files[0]).unwrap()).unwrap();
+        let schema = builder.schema().clone();
+        assert!(matches!(schema.field_with_name("vector").unwrap().data_type(),
+            arrow_schema::DataType::FixedSizeList(item, 3) if item.data_type() == &arrow_schema::DataType::Float32));
+        assert_eq!(schema.field_with_name("title").unwrap().data_type(), &arrow_schema::DataType::Utf8);
+        assert_eq!(schema.field_with_name("score").unwrap().data_type(), &arrow_schema::DataType::Float64);
+
+        let batch = builder.build().unwrap().next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 10);
+        let ids = batch.column_by_name("id").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(ids.value(0), "0");
+    }
+
+    #[tokio::test]
+    async fn test_lakehouse_cold_tier_uses_segments() {
+        use crate::coretex_lakehouse::lakehouse::VectorLakehouse;
+        use crate::coretex_lakehouse::search::TieredSearchOptions;
+        use crate::coretex_lakehouse::tier::{StorageTier, TierConfig};
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+        let cold = TierConfig { hot_threshold_days: 0, warm_threshold_days: 0, ..Default::default() };
+        let open = || VectorLakehouse::new(&path).unwrap()
+            .with_metric("euclidean")
+            .with_config(cold.clone())
+            .with_parquet_segments(SegmentConfig::default());
+
+        let lakehouse = open();
+        for i in 0..20 {
+            let metadata = serde_json::json!({"kind": if i < 10 { "a" } else { "b" }});
+            lakehouse.write_vector("docs", &i.to_string(), vec![i as f32, 0.0], metadata).await.unwrap();
+        }
+        assert_eq!(lakehouse.migrate_data().await.unwrap().cold_count, 20);
+        lakehouse.save_metadata().await.unwrap();
+
+        // One segment file instead of twenty documents
+        let cold_dir = temp_dir.path().join("cold");
+        assert_eq!(parquet_files(&cold_dir).len(), 1);
+        assert!(parquet_files(&temp_dir.path().join("hot")).is_empty());
+
+        let options = TieredSearchOptions::new(2).with_cold(true).with_filter(serde_json::json!({"kind": "b"}));
+        let results = lakehouse.search("docs", &[0.0, 0.0], &options).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["10", "11"]);
+        assert!(results.iter().all(|r| r.tier == StorageTier::Cold));
+
+        let reopened = open();
+        reopened.load_metadata().await.unwrap();
+        assert_eq!(reopened.read_vector("docs", "7").await.unwrap().vector, vec![7.0, 0.0]);
+    }
+}
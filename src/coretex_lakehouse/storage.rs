@@ -1,8 +1,12 @@
 //! Storage Backend for Vector Lakehouse
 //! Supports local, S3, and MinIO storage backends
 
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
+
+pub use super::s3::S3Storage;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StorageBackend {
@@ -43,12 +47,29 @@ pub struct AzureConfig {
     pub account_key: String,
 }
 
+#[async_trait]
 pub trait StorageBackendTrait: Send + Sync {
-    fn write(&self, key: &str, data: &[u8]) -> Result<(), String>;
-    fn read(&self, key: &str) -> Result<Vec<u8>, String>;
-    fn delete(&self, key: &str) -> Result<(), String>;
-    fn exists(&self, key: &str) -> bool;
-    fn list(&self, prefix: &str) -> Result<Vec<String>, String>;
+    async fn write(&self, key: &str, data: &[u8]) -> Result<(), String>;
+    async fn read(&self, key: &str) -> Result<Vec<u8>, String>;
+    /// Reads bytes `start..end` (end exclusive) of an object
+    async fn read_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>, String> {
+        let data = self.read(key).await?;
+        let end = (end as usize).min(data.len());
+        let start = (start as usize).min(end);
+        Ok(data[start..end].to_vec())
+    }
+    async fn delete(&self, key: &str) -> Result<(), String>;
+    async fn exists(&self, key: &str) -> bool;
+    /// Like `exists`, but a check that could not be made is an error
+    /// rather than a missing object.
+    async fn try_exists(&self, key: &str) -> Result<bool, String> {
+        Ok(self.exists(key).await)
+    }
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String>;
+    /// Makes buffered writes durable. Write-through backends have nothing to do.
+    async fn flush(&self) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 pub struct LocalStorage {
@@ -67,28 +88,49 @@ impl LocalStorage {
     }
 }
 
+#[async_trait]
 impl StorageBackendTrait for LocalStorage {
-    fn write(&self, key: &str, data: &[u8]) -> Result<(), String> {
+    /// Durable on return: replaced through a synced temporary file, so a
+    /// crash leaves the old object or the new one.
+    async fn write(&self, key: &str, data: &[u8]) -> Result<(), String> {
         let path = self.full_path(key);
         if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            tokio::fs::create_dir_all(parent).await.map_err(|e| e.to_string())?;
         }
-        std::fs::write(&path, data).map_err(|e| e.to_string())
+        let data = data.to_vec();
+        tokio::task::spawn_blocking(move || crate::coretex_journal::write_durably(&path, &data))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
     }
 
-    fn read(&self, key: &str) -> Result<Vec<u8>, String> {
-        std::fs::read(self.full_path(key)).map_err(|e| e.to_string())
+    async fn read(&self, key: &str) -> Result<Vec<u8>, String> {
+        tokio::fs::read(self.full_path(key)).await.map_err(|e| e.to_string())
     }
 
-    fn delete(&self, key: &str) -> Result<(), String> {
-        std::fs::remove_file(self.full_path(key)).map_err(|e| e.to_string())
+    async fn read_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>, String> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let mut file = tokio::fs::File::open(self.full_path(key)).await.map_err(|e| e.to_string())?;
+        file.seek(std::io::SeekFrom::Start(start)).await.map_err(|e| e.to_string())?;
+        let mut data = Vec::new();
+        file.take(end.saturating_sub(start)).read_to_end(&mut data).await.map_err(|e| e.to_string())?;
+        Ok(data)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        tokio::fs::remove_file(self.full_path(key)).await.map_err(|e| e.to_string())
     }
 
-    fn exists(&self, key: &str) -> bool {
+    async fn exists(&self, key: &str) -> bool {
         self.full_path(key).exists()
     }
 
-    fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+    async fn try_exists(&self, key: &str) -> Result<bool, String> {
+        tokio::fs::try_exists(self.full_path(key)).await.map_err(|e| e.to_string())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
         let mut results = Vec::new();
         let prefix_path = self.full_path(prefix);
         
@@ -108,104 +150,6 @@ impl StorageBackendTrait for LocalStorage {
     }
 }
 
-#[cfg(feature = "s3")]
-mod s3_backend {
-    use super::*;
-    
-    pub struct S3Storage {
-        client: aws_sdk_s3::Client,
-        bucket: String,
-    }
-    
-    impl S3Storage {
-        pub fn new(config: &S3Config) -> Self {
-            let sdk_config = aws_config::from_env()
-                .region(aws_sdk_s3::config::Region::new(&config.region))
-                .load()
-                .await;
-            
-            Self {
-                client: aws_sdk_s3::Client::new(&sdk_config),
-                bucket: config.bucket.clone(),
-            }
-        }
-    }
-    
-    impl StorageBackendTrait for S3Storage {
-        fn write(&self, key: &str, data: &[u8]) -> Result<(), String> {
-            tokio::runtime::Handle::current()
-                .block_on(async {
-                    self.client.put_object()
-                        .bucket(&self.bucket)
-                        .key(key)
-                        .body(data.into())
-                        .send()
-                        .await
-                        .map_err(|e| e.to_string())
-                })
-        }
-        
-        fn read(&self, key: &str) -> Result<Vec<u8>, String> {
-            tokio::runtime::Handle::current()
-                .block_on(async {
-                    let response = self.client.get_object()
-                        .bucket(&self.bucket)
-                        .key(key)
-                        .send()
-                        .await
-                        .map_err(|e| e.to_string())?;
-                    
-                    let bytes = response.body.collect().await.map_err(|e| e.to_string())?;
-                    Ok(bytes.to_vec())
-                })
-        }
-        
-        fn delete(&self, key: &str) -> Result<(), String> {
-            tokio::runtime::Handle::current()
-                .block_on(async {
-                    self.client.delete_object()
-                        .bucket(&self.bucket)
-                        .key(key)
-                        .send()
-                        .await
-                        .map_err(|e| e.to_string())
-                })
-        }
-        
-        fn exists(&self, key: &str) -> bool {
-            tokio::runtime::Handle::current()
-                .block_on(async {
-                    self.client.head_object()
-                        .bucket(&self.bucket)
-                        .key(key)
-                        .send()
-                        .await
-                        .is_ok()
-                })
-        }
-        
-        fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
-            tokio::runtime::Handle::current()
-                .block_on(async {
-                    let response = self.client.list_objects_v2()
-                        .bucket(&self.bucket)
-                        .prefix(prefix)
-                        .send()
-                        .await
-                        .map_err(|e| e.to_string())?;
-                    
-                    Ok(response.contents()
-                        .map(|objects| {
-                            objects.iter()
-                                .filter_map(|o| o.key().map(|k| k.to_string()))
-                                .collect()
-                        })
-                        .unwrap_or_default())
-                })
-        }
-    }
-}
-
 impl StorageBackend {
     pub fn create_local(path: &str) -> Self {
         Self::Local(LocalConfig {
@@ -233,6 +177,16 @@ impl StorageBackend {
             use_ssl: false,
         })
     }
+
+    /// Instantiates the configured backend.
+    pub fn build(&self) -> Result<Arc<dyn StorageBackendTrait>, String> {
+        match self {
+            StorageBackend::Local(config) => Ok(Arc::new(LocalStorage::new(&config.base_path))),
+            StorageBackend::S3(config) => Ok(Arc::new(S3Storage::new(config)?)),
+            StorageBackend::MinIO(config) => Ok(Arc::new(S3Storage::from_minio(config)?)),
+            StorageBackend::AzureBlob(_) => Err("Azure Blob storage is not supported yet".to_string()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -241,18 +195,19 @@ mod tests {
     use std::fs;
     use tempfile::TempDir;
 
-    #[test]
-    fn test_local_storage() {
+    #[tokio::test]
+    async fn test_local_storage() {
         let temp_dir = TempDir::new().unwrap();
         let storage = LocalStorage::new(temp_dir.path().to_str().unwrap());
         
-        storage.write("test/key.txt", b"hello").unwrap();
-        assert!(storage.exists("test/key.txt"));
+        storage.write("test/key.txt", b"hello").await.unwrap();
+        assert!(storage.exists("test/key.txt").await);
         
-        let data = storage.read("test/key.txt").unwrap();
+        let data = storage.read("test/key.txt").await.unwrap();
         assert_eq!(data, b"hello");
+        assert_eq!(storage.read_range("test/key.txt", 1, 3).await.unwrap(), b"el");
         
-        storage.delete("test/key.txt").unwrap();
-        assert!(!storage.exists("test/key.txt"));
+        storage.delete("test/key.txt").await.unwrap();
+        assert!(!storage.exists("test/key.txt").await);
     }
 }
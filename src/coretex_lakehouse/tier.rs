@@ -35,6 +35,8 @@ pub struct TierConfig {
     pub warm_threshold_days: u32,
     pub max_hot_size_gb: u64,
     pub max_warm_size_gb: u64,
+    #[serde(default)]
+    pub eviction_order: EvictionOrder,
 }
 
 impl Default for TierConfig {
@@ -45,6 +47,43 @@ impl Default for TierConfig {
             warm_threshold_days: 30,
             max_hot_size_gb: 100,
             max_warm_size_gb: 500,
+            eviction_order: EvictionOrder::default(),
+        }
+    }
+}
+
+impl TierConfig {
+    /// Capacity of `tier` in bytes. The cold tier is unbounded.
+    pub fn max_size_bytes(&self, tier: StorageTier) -> Option<u64> {
+        const GB: u64 = 1024 * 1024 * 1024;
+        match tier {
+            StorageTier::Hot => Some(self.max_hot_size_gb.saturating_mul(GB)),
+            StorageTier::Warm => Some(self.max_warm_size_gb.saturating_mul(GB)),
+            StorageTier::Cold => None,
+        }
+    }
+}
+
+/// Which documents leave a tier first when it is over its size budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EvictionOrder {
+    #[default]
+    LeastRecentlyAccessed,
+    LeastFrequentlyAccessed,
+    OldestFirst,
+    LargestFirst,
+}
+
+impl EvictionOrder {
+    /// Orders documents so the first one is the first to be evicted.
+    pub fn compare(&self, a: &DocumentMeta, b: &DocumentMeta) -> std::cmp::Ordering {
+        match self {
+            EvictionOrder::LeastRecentlyAccessed => a.last_accessed.cmp(&b.last_accessed)
+                .then(a.access_count.cmp(&b.access_count)),
+            EvictionOrder::LeastFrequentlyAccessed => a.access_count.cmp(&b.access_count)
+                .then(a.last_accessed.cmp(&b.last_accessed)),
+            EvictionOrder::OldestFirst => a.created_at.cmp(&b.created_at),
+            EvictionOrder::LargestFirst => b.size_bytes.cmp(&a.size_bytes),
         }
     }
 }
@@ -79,7 +118,11 @@ impl DocumentMeta {
     }
 
     pub fn record_access(&mut self) {
-        self.access_count += 1;
-        self.last_accessed = chrono::Utc::now();
+        self.record_accesses(1, chrono::Utc::now());
+    }
+
+    pub fn record_accesses(&mut self, count: u32, at: chrono::DateTime<chrono::Utc>) {
+        self.access_count = self.access_count.saturating_add(count);
+        self.last_accessed = self.last_accessed.max(at);
     }
 }
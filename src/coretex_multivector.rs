@@ -0,0 +1,397 @@
+//! Multi-vector documents for CoreTexDB
+//! ColBERT-style late interaction: a document carries many token-level
+//! vectors, candidates come from an index over every token vector, and each
+//! candidate is rescored exactly with MaxSim, the sum over query vectors of
+//! their best match among the document's vectors.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::coretex_core::{DistanceMetric, MultiVectorConfig};
+use crate::{simd_utils, CoreTexDB, CoreTexError, IndexManager};
+
+/// A collection's token vectors by document id.
+type DocumentTokens = HashMap<String, Vec<Vec<f32>>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiVectorSearchResult {
+    pub id: String,
+    /// MaxSim score; higher is better
+    pub score: f32,
+}
+
+/// Token vectors by collection.
+#[derive(Default)]
+pub struct MultiVectorStore {
+    vectors: RwLock<HashMap<String, DocumentTokens>>,
+}
+
+impl MultiVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) async fn get(&self, collection: &str, id: &str) -> Option<Vec<Vec<f32>>> {
+        self.vectors.read().await.get(collection).and_then(|documents| documents.get(id)).cloned()
+    }
+
+    /// Replaces the token vectors of a document in the store and the token
+    /// index; `None` drops them. If the index refuses a token, the index
+    /// gets the previous tokens back and the store is left as it was, so a
+    /// document never has a mix of old and new tokens.
+    pub(crate) async fn replace(&self, index_manager: &IndexManager, collection: &str, id: &str, tokens: Option<Vec<Vec<f32>>>) -> crate::Result<()> {
+        let mut vectors = self.vectors.write().await;
+        let documents = vectors.entry(collection.to_string()).or_default();
+        let previous = documents.get(id).cloned().unwrap_or_default();
+        let next = tokens.clone().unwrap_or_default();
+
+        if let Some(index) = index_manager.get_index(&token_index_name(collection)).await.ok().flatten() {
+            replace_tokens(index.as_ref(), id, &previous, &next).await?;
+        }
+
+        match tokens {
+            Some(tokens) => documents.insert(id.to_string(), tokens),
+            None => documents.remove(id),
+        };
+        Ok(())
+    }
+
+    /// Takes every token vector of `other` in place of these. Token indexes
+    /// move with `IndexManager::replace_with`.
+    pub(crate) async fn replace_with(&self, other: &MultiVectorStore) {
+        let vectors = std::mem::take(&mut *other.vectors.write().await);
+        *self.vectors.write().await = vectors;
+    }
+
+    /// Drops every token vector of a collection along with the token index.
+    pub(crate) async fn remove_collection(&self, index_manager: &IndexManager, collection: &str) {
+        self.vectors.write().await.remove(collection);
+        let _ = index_manager.delete_index(&token_index_name(collection)).await;
+    }
+}
+
+/// The `IndexManager` name of a collection's token index.
+pub fn token_index_name(collection: &str) -> String {
+    format!("{}.multi_vector", collection)
+}
+
+/// Token index entries are keyed `<document id>#<token position>`.
+fn token_id(id: &str, position: usize) -> String {
+    format!("{}#{}", id, position)
+}
+
+fn document_of(token_id: &str) -> &str {
+    token_id.rsplit_once('#').map_or(token_id, |(id, _)| id)
+}
+
+/// Moves a document's entries in the token index from `previous` to
+/// `next`, or back to `previous` if the index refuses one.
+async fn replace_tokens(index: &dyn crate::VectorIndex, id: &str, previous: &[Vec<f32>], next: &[Vec<f32>]) -> crate::Result<()> {
+    if let Err(e) = set_tokens(index, id, previous, next).await {
+        let _ = set_tokens(index, id, next, previous).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+async fn set_tokens(index: &dyn crate::VectorIndex, id: &str, from: &[Vec<f32>], to: &[Vec<f32>]) -> crate::Result<()> {
+    for i in to.len()..from.len() {
+        let _ = index.remove(&token_id(id, i)).await;
+    }
+    for (i, vector) in to.iter().enumerate() {
+        index.add(&token_id(id, i), vector).await
+            .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Similarity under `metric`, higher meaning closer.
+fn similarity(a: &[f32], b: &[f32], metric: &DistanceMetric) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => simd_utils::cosine_similarity(a, b),
+        DistanceMetric::DotProduct => simd_utils::dot_product(a, b),
+        DistanceMetric::Euclidean => -simd_utils::euclidean_distance(a, b),
+        DistanceMetric::Manhattan => -a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum::<f32>(),
+    }
+}
+
+/// Late-interaction score of a document for a query: each query vector's
+/// best similarity among the document vectors, summed.
+pub fn max_sim(query: &[Vec<f32>], document: &[Vec<f32>], metric: &DistanceMetric) -> f32 {
+    query.iter()
+        .filter_map(|q| document.iter().map(|d| similarity(q, d, metric)).max_by(|a, b| a.total_cmp(b)))
+        .sum()
+}
+
+fn check_dimensions(vectors: &[Vec<f32>], dimension: usize) -> crate::Result<()> {
+    if vectors.is_empty() {
+        return Err(CoreTexError::ValidationError("Multi-vector needs at least one vector".to_string()));
+    }
+    match vectors.iter().find(|v| v.len() != dimension) {
+        Some(v) => Err(CoreTexError::DimensionMismatch { expected: dimension, actual: v.len() }),
+        None => Ok(()),
+    }
+}
+
+impl CoreTexDB {
+    /// Lets a collection's documents carry token vectors for `search_multi_vector`.
+    pub async fn enable_multi_vector(&self, collection: &str, config: MultiVectorConfig) -> crate::Result<()> {
+        if config.dimension == 0 {
+            return Err(CoreTexError::InvalidDimension("Multi-vector needs a dimension".to_string()));
+        }
+
+        let mut collections = self.collections.write().await;
+        let mut schema = collections.get(collection)
+            .cloned()
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        if schema.multi_vector.is_some() {
+            return Err(CoreTexError::ValidationError(format!("Collection '{}' already has multi-vectors", collection)));
+        }
+
+        schema.multi_vector = Some(config);
+        self.change_schema(&mut collections, schema).await
+    }
+
+    fn multi_vector_config(schema: Option<&crate::CollectionSchema>, collection: &str) -> crate::Result<MultiVectorConfig> {
+        let schema = schema.ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        schema.multi_vector.clone()
+            .ok_or_else(|| CoreTexError::ValidationError(format!("Collection '{}' has no multi-vectors", collection)))
+    }
+
+    /// Sets the token vectors of a document that is already in the
+    /// collection, replacing earlier ones.
+    pub async fn set_multi_vector(&self, collection: &str, id: &str, vectors: Vec<Vec<f32>>) -> crate::Result<()> {
+        let config = Self::multi_vector_config(self.collections.read().await.get(collection), collection)?;
+        check_dimensions(&vectors, config.dimension)?;
+
+        self.update_side_vectors(collection, id, |side| side.multi = Some(vectors)).await
+    }
+
+    pub async fn get_multi_vector(&self, collection: &str, id: &str) -> crate::Result<Option<Vec<Vec<f32>>>> {
+        if !self.collections.read().await.contains_key(collection) {
+            return Err(CoreTexError::CollectionNotFound(collection.to_string()));
+        }
+
+        Ok(self.multi_vectors.get(collection, id).await)
+    }
+
+    /// Late-interaction search: documents with a token among the nearest
+    /// `candidates_per_token` of any query vector are rescored with MaxSim.
+    /// Documents tiered out of memory keep their tokens there and are found
+    /// too; a filter reads their metadata from storage.
+    pub async fn search_multi_vector(
+        &self,
+        collection: &str,
+        query: &[Vec<f32>],
+        k: usize,
+        filter: Option<serde_json::Value>,
+    ) -> crate::Result<Vec<MultiVectorSearchResult>> {
+        let config = Self::multi_vector_config(self.collections.read().await.get(collection), collection)?;
+        check_dimensions(query, config.dimension)?;
+
+        let index = self.index_manager.get_index(&token_index_name(collection)).await
+            .map_err(|e| CoreTexError::IndexError(e.to_string()))?
+            .ok_or_else(|| CoreTexError::IndexError(format!("Token index of '{}' is missing", collection)))?;
+        let mut candidates = HashSet::new();
+        for vector in query {
+            let hits = index.search(vector, config.candidates_per_token).await
+                .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+            candidates.extend(hits.into_iter().map(|hit| document_of(&hit.id).to_string()));
+        }
+
+        let data = self.data.read().await;
+        let collection_data = data.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        let stored = self.multi_vectors.vectors.read().await;
+        let Some(documents) = stored.get(collection) else {
+            return Ok(Vec::new());
+        };
+
+        let mut results = Vec::new();
+        for id in candidates {
+            let Some(tokens) = documents.get(&id) else {
+                continue;
+            };
+            let matches = match (collection_data.get(&id), &filter) {
+                (Some((_, metadata)), filter) => filter.as_ref().is_none_or(|f| Self::matches_filter(metadata, f)),
+                (None, None) => self.holds(collection_data, collection, &id),
+                (None, Some(f)) => self.document(collection_data, collection, &id).await?
+                    .is_some_and(|(_, metadata)| Self::matches_filter(&metadata, f)),
+            };
+            if matches {
+                results.push(MultiVectorSearchResult { score: max_sim(query, tokens, &config.distance_metric), id });
+            }
+        }
+        results.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+        results.truncate(k);
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IndexConfig, IndexType, VectorIndex};
+
+    fn config(candidates_per_token: usize) -> MultiVectorConfig {
+        MultiVectorConfig {
+            dimension: 2,
+            distance_metric: DistanceMetric::DotProduct,
+            index: IndexConfig {
+                name: "docs.multi_vector".to_string(),
+                index_type: IndexType::BruteForce,
+                parameters: HashMap::new(),
+            },
+            candidates_per_token,
+        }
+    }
+
+    #[test]
+    fn test_max_sim() {
+        let query = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let document = vec![vec![0.9, 0.1], vec![0.2, 0.5], vec![0.1, 0.3]];
+        assert!((max_sim(&query, &document, &DistanceMetric::DotProduct) - 1.4).abs() < 1e-6);
+        assert_eq!(max_sim(&query, &[], &DistanceMetric::DotProduct), 0.0);
+        assert_eq!(document_of("a#b#3"), "a#b");
+    }
+
+    #[tokio::test]
+    async fn test_multi_vector_search() {
+        let db = CoreTexDB::new();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![
+            ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"lang": "en"})),
+            ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"lang": "de"})),
+            ("c".to_string(), vec![0.6, 0.8], serde_json::json!({"lang": "en"})),
+        ]).await.unwrap();
+        assert!(db.set_multi_vector("docs", "a", vec![vec![1.0, 0.0]]).await.is_err());
+        db.enable_multi_vector("docs", config(100)).await.unwrap();
+
+        // "a" matches each query vector once; "b" matches one of them strongly
+        db.set_multi_vector("docs", "a", vec![vec![1.0, 0.0], vec![0.0, 1.0]]).await.unwrap();
+        db.set_multi_vector("docs", "b", vec![vec![0.0, 1.5], vec![0.1, 0.1], vec![0.2, 0.0]]).await.unwrap();
+        db.set_multi_vector("docs", "c", vec![vec![0.5, 0.5]]).await.unwrap();
+        assert!(matches!(
+            db.set_multi_vector("docs", "c", vec![vec![1.0, 0.0, 0.0]]).await,
+            Err(CoreTexError::DimensionMismatch { expected: 2, actual: 3 })
+        ));
+        assert!(matches!(
+            db.set_multi_vector("docs", "missing", vec![vec![1.0, 0.0]]).await,
+            Err(CoreTexError::DocumentNotFound(_))
+        ));
+
+        let query = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let results = db.search_multi_vector("docs", &query, 3, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert!((results[1].score - 1.7).abs() < 1e-6);
+
+        let results = db.search_multi_vector("docs", &query, 3, Some(serde_json::json!({"lang": "en"}))).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "c"]);
+
+        // Shrinking a document drops its extra tokens from the index
+        db.set_multi_vector("docs", "b", vec![vec![0.0, 0.1]]).await.unwrap();
+        db.delete_vectors("docs", &["a".to_string()]).await.unwrap();
+        assert!(db.get_multi_vector("docs", "a").await.unwrap().is_none());
+        let results = db.search_multi_vector("docs", &query, 3, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["c", "b"]);
+        let index = db.index_manager.get_index(&token_index_name("docs")).await.unwrap().unwrap();
+        assert_eq!(index.search(&[1.0, 1.0], 10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_multi_vector_candidates_come_from_token_index() {
+        let db = CoreTexDB::new();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![
+            ("near".to_string(), vec![1.0, 0.0], serde_json::json!({})),
+            ("far".to_string(), vec![0.0, 1.0], serde_json::json!({})),
+        ]).await.unwrap();
+        db.enable_multi_vector("docs", config(1)).await.unwrap();
+        db.set_multi_vector("docs", "near", vec![vec![1.0, 0.0]]).await.unwrap();
+        db.set_multi_vector("docs", "far", vec![vec![0.0, 1.0]]).await.unwrap();
+
+        let results = db.search_multi_vector("docs", &[vec![2.0, 0.0]], 10, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["near"]);
+    }
+
+    #[tokio::test]
+    async fn test_multi_vector_search_finds_tiered_out_documents() {
+        use crate::coretex_lakehouse::{MigrationOptions, VectorLakehouse};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let lakehouse = std::sync::Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap());
+        let db = CoreTexDB::new().with_lakehouse(lakehouse.clone());
+        db.init().await.unwrap();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![
+            ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"lang": "en"})),
+            ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"lang": "de"})),
+        ]).await.unwrap();
+        db.enable_multi_vector("docs", config(100)).await.unwrap();
+        db.set_multi_vector("docs", "a", vec![vec![1.0, 0.0]]).await.unwrap();
+        db.set_multi_vector("docs", "b", vec![vec![0.0, 1.0]]).await.unwrap();
+        db.flush_lakehouse().await;
+
+        let options = MigrationOptions { hot_budget_bytes: Some(0), ..MigrationOptions::from_config(lakehouse.config()) };
+        lakehouse.migrate_with(&options, None).await.unwrap();
+        db.flush_lakehouse().await;
+        assert!(db.data.read().await["docs"].is_empty());
+
+        let query = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let results = db.search_multi_vector("docs", &query, 3, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        let results = db.search_multi_vector("docs", &query, 3, Some(serde_json::json!({"lang": "de"}))).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["b"]);
+    }
+
+    /// A token index that refuses empty vectors.
+    #[derive(Clone)]
+    struct PickyIndex(crate::BruteForceIndex);
+
+    #[async_trait::async_trait]
+    impl VectorIndex for PickyIndex {
+        async fn add(&self, id: &str, vector: &[f32]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            if vector.is_empty() {
+                return Err("empty token".into());
+            }
+            self.0.add(id, vector).await
+        }
+
+        async fn remove(&self, id: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+            self.0.remove(id).await
+        }
+
+        async fn search(&self, query: &[f32], k: usize) -> Result<Vec<crate::SearchResult>, Box<dyn std::error::Error + Send + Sync>> {
+            self.0.search(query, k).await
+        }
+
+        async fn build(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.0.build().await
+        }
+
+        async fn clear(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.0.clear().await
+        }
+
+        fn clone_box(&self) -> Box<dyn VectorIndex> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refused_token_restores_previous_tokens() {
+        let index = PickyIndex(crate::BruteForceIndex::new("cosine"));
+        let previous = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        replace_tokens(&index, "a", &[], &previous).await.unwrap();
+
+        // The first token goes in before the second is refused
+        let next = vec![vec![-1.0, 0.0], vec![]];
+        assert!(replace_tokens(&index, "a", &previous, &next).await.is_err());
+
+        let hits = index.search(&[1.0, 0.0], 10).await.unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].id, "a#0");
+        assert!(hits[0].distance.abs() < 1e-6);
+    }
+}
@@ -229,28 +229,28 @@ impl PersistenceManager {
             #[cfg(feature = "compression")]
             {
                 let compressed = Self::compress_data(&vector_bytes)?;
-                std::fs::write(&vector_path, compressed)
-                    .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+                self.write_file(&vector_path, &compressed)?;
             }
             #[cfg(not(feature = "compression"))]
             {
-                std::fs::write(&vector_path, &vector_bytes)
-                    .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+                self.write_file(&vector_path, &vector_bytes)?;
             }
         } else {
-            std::fs::write(&vector_path, &vector_bytes)
-                .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+            self.write_file(&vector_path, &vector_bytes)?;
         }
 
         if let Some(meta) = metadata {
             let metadata_path = collection_dir.join("metadata").join(format!("{}.json", id));
             let meta_json = serde_json::to_vec(meta)
                 .map_err(|e| PersistenceError::SerializationError(e.to_string()))?;
-            std::fs::write(&metadata_path, meta_json)
-                .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+            self.write_file(&metadata_path, &meta_json)?;
         }
 
         if self.config.sync_write {
+            Self::sync_directory(&collection_dir.join("vectors"))?;
+            if metadata.is_some() {
+                Self::sync_directory(&collection_dir.join("metadata"))?;
+            }
             Self::sync_directory(&collection_dir)?;
         }
 
@@ -331,6 +331,13 @@ impl PersistenceManager {
         if metadata_path.exists() {
             std::fs::remove_file(&metadata_path)
                 .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+            if self.config.sync_write {
+                Self::sync_directory(&collection_dir.join("metadata"))?;
+            }
+        }
+
+        if deleted && self.config.sync_write {
+            Self::sync_directory(&collection_dir.join("vectors"))?;
         }
 
         Ok(deleted)
@@ -450,12 +457,29 @@ impl PersistenceManager {
         Ok(decompressed)
     }
 
+    /// Writes a file, flushed to disk with `sync_write`.
+    fn write_file(&self, path: &PathBuf, bytes: &[u8]) -> Result<(), PersistenceError> {
+        use std::io::Write;
+
+        let mut file = std::fs::File::create(path)
+            .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+        file.write_all(bytes)
+            .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+        if self.config.sync_write {
+            file.sync_all()
+                .map_err(|e| PersistenceError::IoError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Flushes a directory's entries, so files created or removed in it
+    /// stay that way after a crash.
     fn sync_directory(dir: &PathBuf) -> Result<(), PersistenceError> {
         #[cfg(unix)]
         {
-            use std::os::unix::fs::FileTypeExt;
-            let _ = dir;
-            Ok(())
+            std::fs::File::open(dir)
+                .and_then(|dir| dir.sync_all())
+                .map_err(|e| PersistenceError::IoError(e.to_string()))
         }
         #[cfg(not(unix))]
         {
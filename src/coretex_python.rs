@@ -122,7 +122,7 @@ impl PyCortexDB {
         
         let results = pyo3_asyncio::tokio::run(async move {
             let db = db.read().await;
-            db.search(&collection, query, k, None)
+            db.search(&collection, query, k, None, None)
                 .await
                 .map_err(|e| PyCoreTexError::new(e.to_string()))
         })?;
@@ -147,7 +147,7 @@ impl PyCortexDB {
         
         let result = pyo3_asyncio::tokio::run(async move {
             let db = db.read().await;
-            db.get_vector(&collection, &id)
+            db.get_vector(&collection, &id, None)
                 .await
                 .map_err(|e| PyCoreTexError::new(e.to_string()))
         })?;
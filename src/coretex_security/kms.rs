@@ -46,6 +46,10 @@ impl VaultKMS {
         }
     }
 
+    pub fn config(&self) -> &KMSConfig {
+        &self.config
+    }
+
     pub async fn connect(&self) -> Result<(), String> {
         match self.config.provider {
             KMSProvider::Vault => {
@@ -0,0 +1,235 @@
+//! Side vectors of CoreTexDB documents
+//! A document's named, sparse and token vectors are written like a document
+//! of their own under `side_collection`, so they are journaled, stored,
+//! versioned, backed up and replicated along with its main vector. The
+//! per-kind stores hold the current ones and the indexes that search them.
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::coretex_sparse::SparseVector;
+use crate::coretex_transaction::{AsOf, VersionKey, VersionValue};
+use crate::{CoreTexDB, CoreTexError};
+
+const SIDE_SUFFIX: &str = "#side";
+
+/// Where the side vectors of `collection` are written.
+pub(crate) fn side_collection(collection: &str) -> String {
+    format!("{}{}", collection, SIDE_SUFFIX)
+}
+
+/// The collection whose side vectors `name` holds, if it holds any.
+pub(crate) fn main_collection(name: &str) -> Option<&str> {
+    name.strip_suffix(SIDE_SUFFIX)
+}
+
+/// Refuses a collection name that would be read as another collection's
+/// side vectors.
+pub(crate) fn check_collection_name(name: &str) -> crate::Result<()> {
+    if main_collection(name).is_some() {
+        return Err(CoreTexError::ValidationError(format!(
+            "Collection names ending in '{}' are reserved", SIDE_SUFFIX
+        )));
+    }
+    Ok(())
+}
+
+/// Everything a document carries next to its main vector.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SideVectors {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub named: HashMap<String, Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<SparseVector>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multi: Option<Vec<Vec<f32>>>,
+}
+
+impl SideVectors {
+    pub fn is_empty(&self) -> bool {
+        self.named.is_empty() && self.sparse.is_none() && self.multi.is_none()
+    }
+
+    /// As a write: the vectors go in the metadata, none means deleted.
+    pub(crate) fn to_value(&self) -> VersionValue {
+        if self.is_empty() {
+            return None;
+        }
+        Some((Vec::new(), serde_json::to_value(self).unwrap_or_default()))
+    }
+
+    pub(crate) fn from_value(value: &VersionValue) -> crate::Result<Option<Self>> {
+        match value {
+            Some((_, metadata)) => Ok(Some(serde_json::from_value(metadata.clone())?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl CoreTexDB {
+    /// A document's side vectors as the stores hold them.
+    pub(crate) async fn current_side_vectors(&self, collection: &str, id: &str) -> SideVectors {
+        SideVectors {
+            named: self.named_vectors.get(collection, id).await,
+            sparse: self.sparse_vectors.get(collection, id).await,
+            multi: self.multi_vectors.get(collection, id).await,
+        }
+    }
+
+    /// Changes the side vectors of a document that is in the collection,
+    /// written like any other change.
+    pub(crate) async fn update_side_vectors(&self, collection: &str, id: &str, update: impl FnOnce(&mut SideVectors)) -> crate::Result<()> {
+        // Deletes take the data lock for writing too, so none can come
+        // between the check and the write
+        let mut data = self.data.write().await;
+        let collection_data = data.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        if !self.holds(collection_data, collection, id) {
+            return Err(CoreTexError::DocumentNotFound(id.to_string()));
+        }
+
+        let mut side = self.current_side_vectors(collection, id).await;
+        update(&mut side);
+        let writes = BTreeMap::from([((side_collection(collection), id.to_string()), side.to_value())]);
+        self.apply_writes(&mut data, writes).await
+    }
+
+    /// `writes` plus deleting the side vectors of every document they delete.
+    pub(crate) async fn with_side_deletes(&self, mut writes: BTreeMap<VersionKey, VersionValue>) -> BTreeMap<VersionKey, VersionValue> {
+        let deleted: Vec<VersionKey> = writes.iter()
+            .filter(|((collection, _), value)| value.is_none() && main_collection(collection).is_none())
+            .map(|(key, _)| key.clone())
+            .collect();
+        for (collection, id) in deleted {
+            if !self.current_side_vectors(&collection, &id).await.is_empty() {
+                writes.insert((side_collection(&collection), id), None);
+            }
+        }
+        writes
+    }
+
+    /// Puts a document's side vectors in the stores and indexes; `None`
+    /// drops them.
+    pub(crate) async fn install_side_vectors(&self, collection: &str, id: &str, side: Option<SideVectors>) -> crate::Result<()> {
+        let side = side.unwrap_or_default();
+        self.multi_vectors.replace(&self.index_manager, collection, id, side.multi).await?;
+        self.named_vectors.replace(&self.index_manager, collection, id, side.named).await?;
+        self.sparse_vectors.replace(collection, id, side.sparse).await;
+        Ok(())
+    }
+
+    /// Replaces the side vectors of documents in a collection, in one write.
+    pub async fn put_side_vectors(&self, collection: &str, side_vectors: BTreeMap<String, SideVectors>) -> crate::Result<()> {
+        let mut data = self.data.write().await;
+        let collection_data = data.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        if let Some(id) = side_vectors.keys().find(|id| !self.holds(collection_data, collection, id)) {
+            return Err(CoreTexError::DocumentNotFound(id.clone()));
+        }
+
+        let side = side_collection(collection);
+        let writes = side_vectors.into_iter()
+            .map(|(id, vectors)| ((side.clone(), id), vectors.to_value()))
+            .collect();
+        self.apply_writes(&mut data, writes).await
+    }
+
+    /// A document's side vectors as they were at `as_of`.
+    pub async fn get_side_vectors_as_of(&self, collection: &str, id: &str, as_of: AsOf) -> crate::Result<Option<SideVectors>> {
+        let data = self.data.read().await;
+        if !data.contains_key(collection) {
+            return Err(CoreTexError::CollectionNotFound(collection.to_string()));
+        }
+
+        let point = self.versions.point(as_of)?;
+        let key = (side_collection(collection), id.to_string());
+        let current = self.current_side_vectors(collection, id).await.to_value();
+        SideVectors::from_value(&self.versions.value_at(point, &key, current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coretex_core::{DistanceMetric, IndexConfig, IndexType, MultiVectorConfig, VectorField};
+
+    fn index(name: &str) -> IndexConfig {
+        IndexConfig {
+            name: name.to_string(),
+            index_type: IndexType::BruteForce,
+            parameters: HashMap::new(),
+        }
+    }
+
+    async fn open(root: &std::path::Path) -> CoreTexDB {
+        let db = CoreTexDB::open(crate::DbConfig::new(&root.to_string_lossy())).await.unwrap();
+        if db.list_collections().await.unwrap().is_empty() {
+            db.create_collection_with_fields("docs", 2, "cosine", vec![
+                VectorField::new("title", 2, DistanceMetric::Cosine, index("title_index")),
+            ]).await.unwrap();
+            db.enable_multi_vector("docs", MultiVectorConfig {
+                dimension: 2,
+                distance_metric: DistanceMetric::DotProduct,
+                index: index("docs.multi_vector"),
+                candidates_per_token: 10,
+            }).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_side_vectors_survive_reopen_and_read_as_of() {
+        let root = tempfile::tempdir().unwrap();
+        let before;
+        {
+            let db = open(root.path()).await;
+            db.insert_vectors("docs", vec![
+                ("a".to_string(), vec![1.0, 0.0], serde_json::json!({})),
+                ("b".to_string(), vec![0.0, 1.0], serde_json::json!({})),
+            ]).await.unwrap();
+            db.set_named_vectors("docs", "a", HashMap::from([("title".to_string(), vec![1.0, 0.0])])).await.unwrap();
+            db.set_sparse_vector("docs", "a", SparseVector::new(vec![3], vec![1.0]).unwrap()).await.unwrap();
+            db.set_multi_vector("docs", "a", vec![vec![1.0, 0.0], vec![0.0, 1.0]]).await.unwrap();
+            db.set_sparse_vector("docs", "b", SparseVector::new(vec![3], vec![2.0]).unwrap()).await.unwrap();
+
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            before = crate::coretex_transaction::now_ms();
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            db.set_multi_vector("docs", "a", vec![vec![0.5, 0.5]]).await.unwrap();
+            db.delete_vectors("docs", &["b".to_string()]).await.unwrap();
+        }
+
+        let db = open(root.path()).await;
+        assert_eq!(db.get_named_vectors("docs", "a").await.unwrap()["title"], vec![1.0, 0.0]);
+        assert_eq!(db.get_multi_vector("docs", "a").await.unwrap().unwrap(), vec![vec![0.5, 0.5]]);
+        assert!(db.get_sparse_vector("docs", "b").await.unwrap().is_none());
+        let hits = db.search_sparse("docs", &SparseVector::new(vec![3], vec![1.0]).unwrap(), 10, None).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+        let hits = db.search_field("docs", "title", vec![1.0, 0.0], 1, None).await.unwrap();
+        assert_eq!(hits[0].id, "a");
+
+        let old = db.get_side_vectors_as_of("docs", "a", AsOf::Timestamp(before)).await.unwrap().unwrap();
+        assert_eq!(old.multi.unwrap(), vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        let old = db.get_side_vectors_as_of("docs", "b", AsOf::Timestamp(before)).await.unwrap().unwrap();
+        assert_eq!(old.sparse.unwrap().values(), &[2.0]);
+
+        // Deleting the document deleted its stored side vectors too
+        let side_dir = root.path().join("data/collections").join(side_collection("docs")).join("vectors");
+        assert!(side_dir.join("a.vec").exists());
+        assert!(!side_dir.join("b.vec").exists());
+    }
+
+    #[tokio::test]
+    async fn test_side_vector_suffix_is_reserved() {
+        let root = tempfile::tempdir().unwrap();
+        let db = open(root.path()).await;
+        assert!(matches!(
+            db.create_collection(&side_collection("docs"), 2, "cosine").await,
+            Err(CoreTexError::ValidationError(_))
+        ));
+        let mut schema = db.get_collection("docs").await.unwrap();
+        schema.name = side_collection("other");
+        assert!(db.put_collection_schema(schema).await.is_err());
+        assert_eq!(db.list_collections().await.unwrap().len(), 1);
+    }
+}
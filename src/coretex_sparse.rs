@@ -0,0 +1,695 @@
+//! Sparse Vectors for CoreTexDB
+//! Sparse vectors (e.g. SPLADE or BM25 term weights) as dimension/value
+//! pairs, searched by dot product over an inverted index with MaxScore
+//! pruning. Collections store one per document next to its dense vector.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::coretex_hybrid::{FusedResult, HybridQuery, MultiModalResult, ScoreFusion, ScoreFusionEngine};
+use crate::{CoreTexDB, CoreTexError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SparseError {
+    LengthMismatch { indices: usize, values: usize },
+    DuplicateIndex(u32),
+    InvalidValue(u32),
+}
+
+impl fmt::Display for SparseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SparseError::LengthMismatch { indices, values } => write!(f, "Sparse vector has {} indices but {} values", indices, values),
+            SparseError::DuplicateIndex(index) => write!(f, "Sparse vector repeats index {}", index),
+            SparseError::InvalidValue(index) => write!(f, "Sparse vector value at index {} is not finite", index),
+        }
+    }
+}
+
+impl std::error::Error for SparseError {}
+
+impl From<SparseError> for CoreTexError {
+    fn from(e: SparseError) -> Self {
+        CoreTexError::ValidationError(e.to_string())
+    }
+}
+
+/// A sparse vector, kept sorted by index.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawSparseVector")]
+pub struct SparseVector {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct RawSparseVector {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+impl TryFrom<RawSparseVector> for SparseVector {
+    type Error = SparseError;
+
+    fn try_from(raw: RawSparseVector) -> Result<Self, Self::Error> {
+        SparseVector::new(raw.indices, raw.values)
+    }
+}
+
+impl SparseVector {
+    pub fn new(indices: Vec<u32>, values: Vec<f32>) -> Result<Self, SparseError> {
+        if indices.len() != values.len() {
+            return Err(SparseError::LengthMismatch { indices: indices.len(), values: values.len() });
+        }
+        Self::from_pairs(indices.into_iter().zip(values))
+    }
+
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (u32, f32)>) -> Result<Self, SparseError> {
+        let mut pairs: Vec<(u32, f32)> = pairs.into_iter().collect();
+        pairs.sort_by_key(|(index, _)| *index);
+
+        for (i, (index, value)) in pairs.iter().enumerate() {
+            if !value.is_finite() {
+                return Err(SparseError::InvalidValue(*index));
+            }
+            if i > 0 && pairs[i - 1].0 == *index {
+                return Err(SparseError::DuplicateIndex(*index));
+            }
+        }
+
+        let (indices, values) = pairs.into_iter().unzip();
+        Ok(Self { indices, values })
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    /// Number of stored dimensions.
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.indices.iter().copied().zip(self.values.iter().copied())
+    }
+
+    pub fn dot(&self, other: &SparseVector) -> f32 {
+        let (mut i, mut j, mut sum) = (0, 0, 0.0);
+        while i < self.indices.len() && j < other.indices.len() {
+            match self.indices[i].cmp(&other.indices[j]) {
+                Ordering::Less => i += 1,
+                Ordering::Greater => j += 1,
+                Ordering::Equal => {
+                    sum += self.values[i] * other.values[j];
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        sum
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SparseSearchResult {
+    pub id: String,
+    /// Dot product with the query; higher is better
+    pub score: f32,
+}
+
+struct PostingList {
+    /// (document, value), sorted by document
+    entries: Vec<(u32, f32)>,
+    /// Bounds over every value ever added; removals leave them loose but
+    /// still valid
+    max_value: f32,
+    min_value: f32,
+}
+
+/// Cursor over one query dimension's posting list during a search.
+struct TermCursor<'a> {
+    entries: &'a [(u32, f32)],
+    weight: f32,
+    /// Most this dimension can add to a document's score
+    bound: f32,
+    pos: usize,
+}
+
+impl TermCursor<'_> {
+    fn doc(&self) -> Option<u32> {
+        self.entries.get(self.pos).map(|(doc, _)| *doc)
+    }
+
+    fn seek(&mut self, doc: u32) {
+        self.pos += self.entries[self.pos..].partition_point(|(d, _)| *d < doc);
+    }
+
+    /// The term's contribution to `doc`, moving past it.
+    fn score(&mut self, doc: u32) -> f32 {
+        match self.entries.get(self.pos) {
+            Some((d, value)) if *d == doc => {
+                self.pos += 1;
+                self.weight * value
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+struct Candidate {
+    score: f32,
+    doc: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score.total_cmp(&other.score).then(other.doc.cmp(&self.doc))
+    }
+}
+
+/// Inverted index over sparse vectors for top-k dot product search.
+/// Removed documents leave their postings behind as tombstones, skipped by
+/// searches, until enough pile up that `compact` renumbers the rest.
+#[derive(Default)]
+pub struct SparseIndex {
+    /// Document ids by internal number; `None` once removed
+    ids: Vec<Option<String>>,
+    docs: HashMap<String, (u32, SparseVector)>,
+    postings: HashMap<u32, PostingList>,
+}
+
+impl SparseIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&SparseVector> {
+        self.docs.get(id).map(|(_, vector)| vector)
+    }
+
+    /// Adds or replaces a document's vector.
+    pub fn insert(&mut self, id: &str, vector: SparseVector) {
+        self.remove(id);
+        if self.ids.len() >= u32::MAX as usize {
+            self.compact();
+        }
+
+        let doc = self.ids.len() as u32;
+        self.ids.push(Some(id.to_string()));
+        for (index, value) in vector.iter() {
+            let list = self.postings.entry(index).or_insert(PostingList {
+                entries: Vec::new(),
+                max_value: value,
+                min_value: value,
+            });
+            list.entries.push((doc, value));
+            list.max_value = list.max_value.max(value);
+            list.min_value = list.min_value.min(value);
+        }
+        self.docs.insert(id.to_string(), (doc, vector));
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some((doc, _)) = self.docs.remove(id) else {
+            return false;
+        };
+
+        self.ids[doc as usize] = None;
+        if self.ids.len() > 2 * self.docs.len() + 1024 {
+            self.compact();
+        }
+        true
+    }
+
+    /// Whether `doc` is still in the index and `accept` takes its id.
+    fn accepts(&self, doc: u32, accept: &dyn Fn(&str) -> bool) -> bool {
+        self.ids[doc as usize].as_deref().is_some_and(accept)
+    }
+
+    /// Renumbers documents to drop the slots and postings of removed ones.
+    fn compact(&mut self) {
+        let docs: Vec<(String, SparseVector)> = self.docs.drain()
+            .map(|(id, (_, vector))| (id, vector))
+            .collect();
+        self.ids.clear();
+        self.postings.clear();
+        for (id, vector) in docs {
+            self.insert(&id, vector);
+        }
+    }
+
+    /// The `k` documents with the highest dot product with `query`.
+    ///
+    /// Uses MaxScore: query dimensions whose combined upper bounds cannot
+    /// lift a document into the current top `k` stop driving candidates and
+    /// are only probed for documents that might still make it. Negative
+    /// values break the bounds, so queries touching them are scored
+    /// exhaustively.
+    pub fn search(&self, query: &SparseVector, k: usize) -> Vec<SparseSearchResult> {
+        self.search_filtered(query, k, &|_| true)
+    }
+
+    /// `search` over the documents whose ids `accept` takes. The others are
+    /// passed over as they come up, so `k` results come back whenever `k`
+    /// documents match.
+    pub fn search_filtered(&self, query: &SparseVector, k: usize, accept: &dyn Fn(&str) -> bool) -> Vec<SparseSearchResult> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut terms: Vec<TermCursor> = query.iter()
+            .filter_map(|(index, weight)| self.postings.get(&index).map(|list| (weight, list)))
+            .map(|(weight, list)| TermCursor {
+                entries: &list.entries,
+                weight,
+                bound: weight * list.max_value,
+                pos: 0,
+            })
+            .collect();
+        let negative = query.iter().any(|(index, weight)| {
+            weight < 0.0 || self.postings.get(&index).is_some_and(|list| list.min_value < 0.0)
+        });
+        if negative {
+            return self.exhaustive(query, k, accept);
+        }
+
+        terms.sort_by(|a, b| a.bound.total_cmp(&b.bound));
+        // bound_sums[i]: the most terms[..=i] can add together
+        let bound_sums: Vec<f32> = terms.iter()
+            .scan(0.0, |sum, term| {
+                *sum += term.bound;
+                Some(*sum)
+            })
+            .collect();
+
+        let mut top: BinaryHeap<Reverse<Candidate>> = BinaryHeap::with_capacity(k + 1);
+        let mut threshold = f32::NEG_INFINITY;
+        // terms[..first_essential] cannot make a document on their own
+        let mut first_essential = 0;
+
+        while let Some(doc) = terms[first_essential..].iter().filter_map(TermCursor::doc).min() {
+            let mut score = 0.0;
+            for term in &mut terms[first_essential..] {
+                score += term.score(doc);
+            }
+            if !self.accepts(doc, accept) {
+                continue;
+            }
+
+            let mut pruned = false;
+            for i in (0..first_essential).rev() {
+                if score + bound_sums[i] <= threshold {
+                    pruned = true;
+                    break;
+                }
+                terms[i].seek(doc);
+                score += terms[i].score(doc);
+            }
+            if pruned || (top.len() == k && score <= threshold) {
+                continue;
+            }
+
+            top.push(Reverse(Candidate { score, doc }));
+            if top.len() > k {
+                top.pop();
+            }
+            if top.len() == k {
+                threshold = top.peek().map_or(threshold, |Reverse(c)| c.score);
+                while first_essential < terms.len() && bound_sums[first_essential] <= threshold {
+                    first_essential += 1;
+                }
+            }
+        }
+
+        self.results(top.into_iter().map(|Reverse(c)| c))
+    }
+
+    /// Scores every document sharing a dimension with the query.
+    pub fn search_exhaustive(&self, query: &SparseVector, k: usize) -> Vec<SparseSearchResult> {
+        self.exhaustive(query, k, &|_| true)
+    }
+
+    fn exhaustive(&self, query: &SparseVector, k: usize, accept: &dyn Fn(&str) -> bool) -> Vec<SparseSearchResult> {
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        for (index, weight) in query.iter() {
+            if let Some(list) = self.postings.get(&index) {
+                for (doc, value) in &list.entries {
+                    *scores.entry(*doc).or_insert(0.0) += weight * value;
+                }
+            }
+        }
+
+        let mut candidates: Vec<Candidate> = scores.into_iter()
+            .filter(|(doc, _)| self.accepts(*doc, accept))
+            .map(|(doc, score)| Candidate { score, doc })
+            .collect();
+        candidates.sort_by(|a, b| b.cmp(a));
+        candidates.truncate(k);
+        self.results(candidates.into_iter())
+    }
+
+    fn results(&self, candidates: impl Iterator<Item = Candidate>) -> Vec<SparseSearchResult> {
+        let mut candidates: Vec<Candidate> = candidates.collect();
+        candidates.sort_by(|a, b| b.cmp(a));
+        candidates.into_iter()
+            .filter_map(|c| self.ids[c.doc as usize].clone().map(|id| SparseSearchResult { id, score: c.score }))
+            .collect()
+    }
+}
+
+/// Sparse indexes by collection.
+#[derive(Default)]
+pub struct SparseVectorStore {
+    indexes: RwLock<HashMap<String, SparseIndex>>,
+}
+
+impl SparseVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) async fn get(&self, collection: &str, id: &str) -> Option<SparseVector> {
+        self.indexes.read().await.get(collection).and_then(|index| index.get(id)).cloned()
+    }
+
+    /// Replaces the sparse vector of a document; `None` drops it.
+    pub(crate) async fn replace(&self, collection: &str, id: &str, vector: Option<SparseVector>) {
+        let mut indexes = self.indexes.write().await;
+        match vector {
+            Some(vector) => indexes.entry(collection.to_string()).or_default().insert(id, vector),
+            None => {
+                if let Some(index) = indexes.get_mut(collection) {
+                    index.remove(id);
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn remove_collection(&self, collection: &str) {
+        self.indexes.write().await.remove(collection);
+    }
+
+    /// Takes every sparse vector of `other` in place of these.
+    pub(crate) async fn replace_with(&self, other: &SparseVectorStore) {
+        let indexes = std::mem::take(&mut *other.indexes.write().await);
+        *self.indexes.write().await = indexes;
+    }
+}
+
+impl CoreTexDB {
+    /// Sets the sparse vector of a document that is already in the
+    /// collection.
+    pub async fn set_sparse_vector(&self, collection: &str, id: &str, vector: SparseVector) -> crate::Result<()> {
+        self.update_side_vectors(collection, id, |side| side.sparse = Some(vector)).await
+    }
+
+    pub async fn get_sparse_vector(&self, collection: &str, id: &str) -> crate::Result<Option<SparseVector>> {
+        if !self.data.read().await.contains_key(collection) {
+            return Err(CoreTexError::CollectionNotFound(collection.to_string()));
+        }
+
+        Ok(self.sparse_vectors.get(collection, id).await)
+    }
+
+    /// Dot product search over a collection's sparse vectors.
+    pub async fn search_sparse(
+        &self,
+        collection: &str,
+        query: &SparseVector,
+        k: usize,
+        filter: Option<serde_json::Value>,
+    ) -> crate::Result<Vec<SparseSearchResult>> {
+        let data = self.data.read().await;
+        let collection_data = data.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+
+        let indexes = self.sparse_vectors.indexes.read().await;
+        let Some(index) = indexes.get(collection) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(index.search_filtered(query, k, &|id| match collection_data.get(id) {
+            Some((_, metadata)) => filter.as_ref().is_none_or(|f| Self::matches_filter(metadata, f)),
+            None => false,
+        }))
+    }
+
+    /// Runs the dense and sparse parts of `query` against a collection and
+    /// fuses them with `fusion`. Dense hits score `1 / (1 + distance)` and
+    /// sparse hits their dot product, each scaled to 0..=1 across its part
+    /// so `query.weights` rather than the scale of either decides the mix.
+    /// Each part filters on its own metadata filter.
+    pub async fn search_hybrid(&self, collection: &str, query: &HybridQuery, fusion: ScoreFusion) -> crate::Result<Vec<FusedResult>> {
+        if query.text_query.is_some() || query.geo_filter.is_some() || query.time_range.is_some() || !query.scalar_filters.is_empty() {
+            return Err(CoreTexError::ValidationError(
+                "Collections support the vector and sparse parts of a hybrid query; filter with their metadata filters".to_string(),
+            ));
+        }
+        if query.vector_query.is_none() && query.sparse_query.is_none() {
+            return Err(CoreTexError::ValidationError("Hybrid query has neither a vector nor a sparse part".to_string()));
+        }
+        if matches!(fusion, ScoreFusion::LearningToRank) {
+            return Err(CoreTexError::ValidationError("Learning to rank fusion is not supported for collections".to_string()));
+        }
+
+        let as_filter = |filter: &Option<HashMap<String, serde_json::Value>>| {
+            filter.as_ref().map(|f| serde_json::Value::Object(f.clone().into_iter().collect()))
+        };
+        // Each part brings more than `top_k` so fusion can reorder them
+        let candidates = query.top_k * 2;
+        let mut results = Vec::new();
+
+        if let Some(vq) = &query.vector_query {
+            let hits = self.search(collection, vq.vector.clone(), candidates, as_filter(&vq.filter), None).await?;
+            let start = results.len();
+            results.extend(hits.into_iter().enumerate().map(|(rank, hit)| MultiModalResult {
+                id: hit.id,
+                score: 1.0 / (1.0 + hit.distance),
+                rank,
+                source: "vector".to_string(),
+                weight: query.weights.vector_weight,
+                metadata: None,
+            }));
+            normalize_scores(&mut results[start..]);
+        }
+
+        if let Some(sq) = &query.sparse_query {
+            let hits = self.search_sparse(collection, &sq.vector, candidates, as_filter(&sq.filter)).await?;
+            let start = results.len();
+            results.extend(hits.into_iter().enumerate().map(|(rank, hit)| MultiModalResult {
+                id: hit.id,
+                score: hit.score,
+                rank,
+                source: "sparse".to_string(),
+                weight: query.weights.sparse_weight,
+                metadata: None,
+            }));
+            normalize_scores(&mut results[start..]);
+        }
+
+        let mut fused = ScoreFusionEngine::new(fusion).fuse(&results);
+        fused.truncate(query.top_k);
+        Ok(fused)
+    }
+}
+
+/// Min-max scales the scores of one part of a hybrid query to 0..=1. A
+/// part whose hits all score the same gives each of them 1.
+fn normalize_scores(results: &mut [MultiModalResult]) {
+    let min = results.iter().map(|r| r.score).fold(f32::INFINITY, f32::min);
+    let max = results.iter().map(|r| r.score).fold(f32::NEG_INFINITY, f32::max);
+    for result in results {
+        result.score = if max > min { (result.score - min) / (max - min) } else { 1.0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coretex_hybrid::{DistanceMetric, QueryWeights};
+    use rand::Rng;
+
+    fn sparse(pairs: &[(u32, f32)]) -> SparseVector {
+        SparseVector::from_pairs(pairs.iter().copied()).unwrap()
+    }
+
+    #[test]
+    fn test_sparse_vector_validates_and_sorts() {
+        let v = SparseVector::new(vec![7, 2], vec![0.5, 1.5]).unwrap();
+        assert_eq!(v.indices(), &[2, 7]);
+        assert_eq!(v.values(), &[1.5, 0.5]);
+        assert_eq!(v.dot(&sparse(&[(7, 2.0), (9, 4.0)])), 1.0);
+
+        assert_eq!(SparseVector::new(vec![1], vec![]), Err(SparseError::LengthMismatch { indices: 1, values: 0 }));
+        assert_eq!(SparseVector::new(vec![3, 3], vec![1.0, 2.0]), Err(SparseError::DuplicateIndex(3)));
+        assert_eq!(SparseVector::new(vec![4], vec![f32::NAN]), Err(SparseError::InvalidValue(4)));
+        assert!(serde_json::from_str::<SparseVector>(r#"{"indices": [1, 1], "values": [1.0, 1.0]}"#).is_err());
+    }
+
+    #[test]
+    fn test_maxscore_matches_exhaustive_search() {
+        let mut rng = rand::thread_rng();
+        let mut index = SparseIndex::new();
+        for i in 0..2000 {
+            let pairs: Vec<(u32, f32)> = (0..rng.gen_range(1..20))
+                .map(|_| (rng.gen_range(0..500), rng.gen_range(0.01..3.0)))
+                .collect::<HashMap<_, _>>()
+                .into_iter()
+                .collect();
+            index.insert(&format!("doc{}", i), SparseVector::from_pairs(pairs).unwrap());
+        }
+        for i in (0..2000).step_by(3) {
+            index.remove(&format!("doc{}", i));
+        }
+
+        for _ in 0..20 {
+            let pairs: HashMap<u32, f32> = (0..rng.gen_range(1..30))
+                .map(|_| (rng.gen_range(0..500), rng.gen_range(0.01..2.0)))
+                .collect();
+            let query = SparseVector::from_pairs(pairs).unwrap();
+
+            let pruned = index.search(&query, 10);
+            let exact = index.search_exhaustive(&query, 10);
+            assert_eq!(pruned.len(), exact.len());
+            for (a, b) in pruned.iter().zip(&exact) {
+                assert!((a.score - b.score).abs() < 1e-4, "{} vs {}", a.score, b.score);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_index_replaces_and_removes() {
+        let mut index = SparseIndex::new();
+        index.insert("a", sparse(&[(1, 1.0), (2, 1.0)]));
+        index.insert("b", sparse(&[(2, 3.0)]));
+        index.insert("a", sparse(&[(3, 1.0)]));
+
+        let results = index.search(&sparse(&[(2, 1.0), (3, 2.0)]), 5);
+        assert_eq!(results.iter().map(|r| (r.id.as_str(), r.score)).collect::<Vec<_>>(), vec![("b", 3.0), ("a", 2.0)]);
+
+        // Negative weights skip pruning but still rank right
+        let results = index.search(&sparse(&[(2, -1.0), (3, 1.0)]), 1);
+        assert_eq!(results[0].id, "a");
+
+        assert!(index.remove("b"));
+        assert!(!index.remove("b"));
+        assert_eq!(index.len(), 1);
+        assert!(index.search(&sparse(&[(2, 1.0)]), 5).is_empty());
+        assert!(index.search_exhaustive(&sparse(&[(2, 1.0)]), 5).is_empty());
+    }
+
+    #[test]
+    fn test_sparse_index_compacts_tombstones() {
+        let mut index = SparseIndex::new();
+        for i in 0..3000 {
+            index.insert(&format!("doc{}", i), sparse(&[(1, 1.0 + i as f32)]));
+        }
+        for i in 0..2900 {
+            index.remove(&format!("doc{}", i));
+        }
+
+        // Compacted on the way, so the slots of removed documents went too
+        assert!(index.ids.len() <= 2 * index.len() + 1024);
+        assert_eq!(index.len(), 100);
+        let results = index.search(&sparse(&[(1, 1.0)]), 3);
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["doc2999", "doc2998", "doc2997"]);
+        assert_eq!(index.postings[&1].entries.len(), index.ids.len());
+    }
+
+    #[tokio::test]
+    async fn test_collection_sparse_and_hybrid_search() {
+        let db = CoreTexDB::new();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![
+            ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"lang": "en"})),
+            ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"lang": "de"})),
+            ("c".to_string(), vec![0.6, 0.8], serde_json::json!({"lang": "en"})),
+        ]).await.unwrap();
+        // Sparse scores far above the dense ones, as BM25-style weights give
+        db.set_sparse_vector("docs", "a", sparse(&[(10, 20.0)])).await.unwrap();
+        db.set_sparse_vector("docs", "b", sparse(&[(10, 200.0), (11, 100.0)])).await.unwrap();
+        db.set_sparse_vector("docs", "c", sparse(&[(11, 150.0)])).await.unwrap();
+        assert!(matches!(
+            db.set_sparse_vector("docs", "missing", sparse(&[(1, 1.0)])).await,
+            Err(CoreTexError::DocumentNotFound(_))
+        ));
+
+        let results = db.search_sparse("docs", &sparse(&[(10, 1.0)]), 5, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["b", "a"]);
+        let results = db.search_sparse("docs", &sparse(&[(10, 1.0)]), 5, Some(serde_json::json!({"lang": "en"}))).await.unwrap();
+        assert_eq!(results[0].id, "a");
+
+        // Dense favours "a", sparse favours "b"; "c" is second in both. The
+        // weights decide between them however large the sparse scores are.
+        let query = HybridQuery::new()
+            .with_vector(vec![1.0, 0.1], DistanceMetric::Cosine)
+            .with_sparse(sparse(&[(10, 1.0), (11, 1.0)]))
+            .with_top_k(3);
+        let weighted = |sparse_weight| query.clone().with_weights(QueryWeights { sparse_weight, ..Default::default() });
+        let fused = db.search_hybrid("docs", &weighted(2.0), ScoreFusion::WeightedSum).await.unwrap();
+        assert_eq!(fused.len(), 3);
+        assert_eq!(fused[0].id, "b");
+        let fused = db.search_hybrid("docs", &weighted(0.1), ScoreFusion::WeightedSum).await.unwrap();
+        assert_eq!(fused[0].id, "a");
+        assert!(fused.iter().all(|r| r.score <= 1.1));
+        assert!(db.search_hybrid("docs", &query.clone().with_text("hello"), ScoreFusion::WeightedSum).await.is_err());
+
+        db.delete_vectors("docs", &["b".to_string()]).await.unwrap();
+        assert!(db.get_sparse_vector("docs", "b").await.unwrap().is_none());
+        let results = db.search_sparse("docs", &sparse(&[(10, 1.0), (11, 1.0)]), 5, None).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["c", "a"]);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_sparse_search_returns_k_matches() {
+        let db = CoreTexDB::new();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        let docs: Vec<_> = (0..100)
+            .map(|i| (format!("doc{}", i), vec![1.0, 0.0], serde_json::json!({"rare": i % 25 == 0})))
+            .collect();
+        db.insert_vectors("docs", docs).await.unwrap();
+        for i in 0..100 {
+            db.set_sparse_vector("docs", &format!("doc{}", i), sparse(&[(1, i as f32 + 1.0)])).await.unwrap();
+        }
+
+        // The best matches all fail the filter; those that pass are found anyway
+        let results = db.search_sparse("docs", &sparse(&[(1, 1.0)]), 3, Some(serde_json::json!({"rare": true}))).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["doc75", "doc50", "doc25"]);
+        let results = db.search_sparse("docs", &sparse(&[(1, 1.0)]), 10, Some(serde_json::json!({"rare": true}))).await.unwrap();
+        assert_eq!(results.len(), 4);
+    }
+}
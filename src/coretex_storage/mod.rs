@@ -7,6 +7,8 @@ use std::path::Path;
 use rocksdb::{DB, Options};
 use bincode;
 
+use crate::coretex_persistence::{PersistenceConfig, PersistenceManager, StorageBackend};
+
 /// Storage engine trait
 #[async_trait]
 pub trait StorageEngine: Send + Sync {
@@ -76,6 +78,90 @@ impl StorageEngine for MemoryStorage {
     }
 }
 
+/// File storage in the `collections/<name>/vectors` layout of
+/// `PersistenceManager`, which backups copy and point-in-time restores
+/// replay into. Keys are `collection:id`.
+pub struct FileStorage {
+    data_dir: std::path::PathBuf,
+    persistence: PersistenceManager,
+}
+
+impl FileStorage {
+    /// Create a file storage engine under `data_dir`
+    pub fn new(data_dir: &str) -> Self {
+        Self {
+            data_dir: std::path::PathBuf::from(data_dir),
+            persistence: PersistenceManager::new(PersistenceConfig {
+                backend: StorageBackend::FileSystem,
+                data_dir: data_dir.to_string(),
+                rocksdb_config: None,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn split_key(key: &str) -> Result<(&str, &str), Box<dyn Error>> {
+        key.split_once(':')
+            .ok_or_else(|| format!("Storage key '{}' is not collection:id", key).into())
+    }
+}
+
+#[async_trait]
+impl StorageEngine for FileStorage {
+    async fn init(&mut self) -> Result<(), Box<dyn Error>> {
+        self.persistence.initialize().await?;
+        Ok(())
+    }
+
+    async fn store(&self, id: &str, vector: &[f32], metadata: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        let (collection, id) = Self::split_key(id)?;
+        self.persistence.save_vector(collection, id, vector, Some(metadata)).await?;
+        Ok(())
+    }
+
+    async fn retrieve(&self, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, Box<dyn Error>> {
+        let (collection, id) = Self::split_key(id)?;
+        Ok(self.persistence.load_vector(collection, id).await?
+            .map(|(vector, metadata)| (vector, metadata.unwrap_or(serde_json::Value::Null))))
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool, Box<dyn Error>> {
+        let (collection, id) = Self::split_key(id)?;
+        Ok(self.persistence.delete_vector(collection, id).await?)
+    }
+
+    async fn list(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut keys = Vec::new();
+        let collections_dir = self.data_dir.join("collections");
+        if !collections_dir.exists() {
+            return Ok(keys);
+        }
+
+        for collection in std::fs::read_dir(&collections_dir)? {
+            let collection = collection?;
+            let vectors_dir = collection.path().join("vectors");
+            if !vectors_dir.is_dir() {
+                continue;
+            }
+            let name = collection.file_name().to_string_lossy().to_string();
+            for vector in std::fs::read_dir(&vectors_dir)? {
+                let path = vector?.path();
+                if path.extension().is_some_and(|ext| ext == "vec") {
+                    if let Some(id) = path.file_stem() {
+                        keys.push(format!("{}:{}", name, id.to_string_lossy()));
+                    }
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    async fn count(&self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.list().await?.len())
+    }
+}
+
 /// Persistent storage implementation (uses RocksDB)
 #[cfg(feature = "rocksdb")]
 pub struct PersistentStorage {
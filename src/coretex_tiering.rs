@@ -0,0 +1,213 @@
+//! Hot tier of a CoreTexDB with a lakehouse
+//! Writes are applied in memory first and copied to the lakehouse after,
+//! in the order they were applied, by a feed task, so no lakehouse I/O runs
+//! under the data lock and a lakehouse failure never leaves a journaled
+//! write half applied. Documents the lakehouse moves below its hot tier
+//! leave the in-memory data and index; they stay in storage, where reads
+//! find them, and `search_tiered` finds them in the lakehouse.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+
+use crate::coretex_lakehouse::{StorageTier, VectorLakehouse, VectorRecord};
+use crate::coretex_transaction::{VersionKey, VersionValue};
+use crate::{CollectionData, CoreTexDB, CoreTexError, IndexManager};
+
+/// Ids of each collection's documents that were moved out of memory
+pub type TieredOut = HashMap<String, BTreeSet<String>>;
+
+enum FeedMessage {
+    Changes(Vec<(String, String, Option<VectorRecord>)>),
+    /// Answered once everything sent before it has reached the lakehouse
+    Flush(oneshot::Sender<()>),
+}
+
+/// Hands applied writes to the feed task of a database's lakehouse.
+pub struct LakehouseFeed {
+    sender: mpsc::UnboundedSender<FeedMessage>,
+    /// Changes sent but not yet in the lakehouse, by document. A document
+    /// with any stays in memory, as the lakehouse does not hold its current
+    /// version; one whose change failed to reach it stays until restart.
+    pending: Arc<Mutex<HashMap<VersionKey, usize>>>,
+    last_error: Arc<Mutex<Option<String>>>,
+}
+
+/// What the feed task works on, cloned from the database when it starts.
+struct FeedTask {
+    lakehouse: Arc<VectorLakehouse>,
+    data: Arc<RwLock<HashMap<String, CollectionData>>>,
+    index_manager: Arc<IndexManager>,
+    tiered_out: Arc<std::sync::RwLock<TieredOut>>,
+    pending: Arc<Mutex<HashMap<VersionKey, usize>>>,
+    last_error: Arc<Mutex<Option<String>>>,
+}
+
+impl LakehouseFeed {
+    /// Starts the feed task of `db`'s lakehouse. Call inside a tokio
+    /// runtime; the task ends when the feed is dropped.
+    pub(crate) fn start(db: &CoreTexDB, lakehouse: Arc<VectorLakehouse>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let last_error = Arc::new(Mutex::new(None));
+        let task = FeedTask {
+            lakehouse,
+            data: db.data.clone(),
+            index_manager: db.index_manager.clone(),
+            tiered_out: db.tiered_out.clone(),
+            pending: pending.clone(),
+            last_error: last_error.clone(),
+        };
+        tokio::spawn(task.run(receiver));
+        Self { sender, pending, last_error }
+    }
+}
+
+impl FeedTask {
+    async fn run(self, mut changes: mpsc::UnboundedReceiver<FeedMessage>) {
+        let mut demotions = self.lakehouse.subscribe_demotions();
+        // Documents demoted before this database was opened
+        self.evict_demoted().await;
+
+        loop {
+            tokio::select! {
+                message = changes.recv() => match message {
+                    Some(FeedMessage::Changes(batch)) => self.apply(batch).await,
+                    Some(FeedMessage::Flush(done)) => {
+                        while let Ok(demoted) = demotions.try_recv() {
+                            self.evict(&demoted.0, &demoted.1).await;
+                        }
+                        done.send(()).ok();
+                    }
+                    None => break,
+                },
+                demoted = demotions.recv() => match demoted {
+                    Ok((collection, id)) => self.evict(&collection, &id).await,
+                    Err(broadcast::error::RecvError::Lagged(_)) => self.evict_demoted().await,
+                    // Never happens: the task holds the lakehouse, and so its sender
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+
+    async fn apply(&self, batch: Vec<(String, String, Option<VectorRecord>)>) {
+        let keys: Vec<VersionKey> = batch.iter().map(|(collection, id, _)| (collection.clone(), id.clone())).collect();
+        if let Err(e) = self.lakehouse.apply_vectors(batch).await {
+            *self.last_error.lock().unwrap() = Some(e);
+            return;
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        for key in keys {
+            if let Some(count) = pending.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    pending.remove(&key);
+                }
+            }
+        }
+    }
+
+    async fn evict_demoted(&self) {
+        for (collection, id) in self.lakehouse.demoted_documents().await {
+            self.evict(&collection, &id).await;
+        }
+    }
+
+    /// Drops a document from memory and the collection's index if the
+    /// lakehouse holds its current version below the hot tier.
+    async fn evict(&self, collection: &str, id: &str) {
+        // Writers count pending changes under the data lock
+        let mut data = self.data.write().await;
+        if self.pending.lock().unwrap().contains_key(&(collection.to_string(), id.to_string())) {
+            return;
+        }
+        let demoted = self.lakehouse.document_meta(collection, id).await
+            .is_some_and(|meta| meta.tier != StorageTier::Hot);
+        let Some(collection_data) = data.get_mut(collection).filter(|_| demoted) else {
+            return;
+        };
+        if collection_data.remove(id).is_none() {
+            return;
+        }
+
+        if let Ok(Some(index)) = self.index_manager.get_index(&format!("{}_hnsw", collection)).await {
+            index.remove(id).await.ok();
+        }
+        self.tiered_out.write().unwrap()
+            .entry(collection.to_string())
+            .or_default()
+            .insert(id.to_string());
+    }
+}
+
+impl CoreTexDB {
+    /// Whether a collection holds `id`, in memory or tiered out of it.
+    pub(crate) fn holds(&self, collection_data: &CollectionData, collection: &str, id: &str) -> bool {
+        collection_data.contains_key(id)
+            || self.tiered_out.read().unwrap().get(collection).is_some_and(|ids| ids.contains(id))
+    }
+
+    /// Ids of a collection's documents that were moved out of memory.
+    pub(crate) fn tiered_out_ids(&self, collection: &str) -> BTreeSet<String> {
+        self.tiered_out.read().unwrap().get(collection).cloned().unwrap_or_default()
+    }
+
+    /// A document from memory or, if it was tiered out, from storage.
+    pub(crate) async fn document(&self, collection_data: &CollectionData, collection: &str, id: &str) -> crate::Result<VersionValue> {
+        if let Some(document) = collection_data.get(id) {
+            return Ok(Some(document.clone()));
+        }
+        if !self.holds(collection_data, collection, id) {
+            return Ok(None);
+        }
+        self.storage.read().await.retrieve(&format!("{}:{}", collection, id)).await
+            .map_err(|e| CoreTexError::StorageError(e.to_string()))
+    }
+
+    /// Marks a document as back in memory, or gone, after a write to it.
+    pub(crate) fn untier(&self, collection: &str, id: &str) {
+        if let Some(ids) = self.tiered_out.write().unwrap().get_mut(collection) {
+            ids.remove(id);
+        }
+    }
+
+    /// Queues writes of main collections, just applied in memory under the
+    /// data lock, for the lakehouse.
+    pub(crate) fn feed_lakehouse(&self, writes: Vec<(VersionKey, VersionValue)>) {
+        let Some(feed) = &self.lakehouse_feed else {
+            return;
+        };
+        if writes.is_empty() {
+            return;
+        }
+
+        {
+            let mut pending = feed.pending.lock().unwrap();
+            for (key, _) in &writes {
+                *pending.entry(key.clone()).or_default() += 1;
+            }
+        }
+        let batch = writes.into_iter()
+            .map(|((collection, id), value)| (collection, id, value.map(|(vector, metadata)| VectorRecord { vector, metadata })))
+            .collect();
+        feed.sender.send(FeedMessage::Changes(batch)).ok();
+    }
+
+    /// Waits until every write applied so far has reached the lakehouse and
+    /// documents it demoted meanwhile have left memory.
+    pub async fn flush_lakehouse(&self) {
+        if let Some(feed) = &self.lakehouse_feed {
+            let (done, flushed) = oneshot::channel();
+            if feed.sender.send(FeedMessage::Flush(done)).is_ok() {
+                flushed.await.ok();
+            }
+        }
+    }
+
+    /// The last error copying writes to the lakehouse, if any.
+    pub fn lakehouse_feed_error(&self) -> Option<String> {
+        self.lakehouse_feed.as_ref().and_then(|feed| feed.last_error.lock().unwrap().clone())
+    }
+}
@@ -1,11 +1,15 @@
 //! Transaction and Version Control for CortexDB
 //! Implements MVCC and WAL for ACID transactions and time-travel queries
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
+use crate::coretex_fields::field_distance;
+use crate::{CoreTexDB, CoreTexError, SearchResult};
+
 pub struct TransactionManager {
     active_transactions: Arc<RwLock<HashMap<TransactionId, Transaction>>>,
     wal: Arc<RwLock<WriteAheadLog>>,
@@ -144,6 +148,12 @@ impl TransactionManager {
     }
 
     pub async fn commit(&self, txn_id: TransactionId) -> Result<(), TransactionError> {
+        self.commit_with_writes(txn_id, Vec::new()).await
+    }
+
+    /// Logs `writes` followed by the commit record in one WAL append, so the
+    /// log never holds part of a committed transaction.
+    pub async fn commit_with_writes(&self, txn_id: TransactionId, writes: Vec<WriteOperation>) -> Result<(), TransactionError> {
         let mut active = self.active_transactions.write().await;
         
         let transaction = active.get_mut(&txn_id)
@@ -162,15 +172,25 @@ impl TransactionManager {
 
         {
             let mut wal = self.wal.write().await;
-            let lsn = wal.entries.len() as u64;
-            wal.append(WalEntry {
-                transaction_id: txn_id,
-                timestamp,
-                operation: WalOperation::Commit { txn_id },
-                lsn,
-            });
+            let operations = writes.iter()
+                .map(|write| match write.clone() {
+                    WriteOperation::Insert { key, value } => WalOperation::Insert { key, value },
+                    WriteOperation::Update { key, old_value, new_value } => WalOperation::Update { key, old_value, new_value },
+                    WriteOperation::Delete { key, old_value } => WalOperation::Delete { key, value: old_value },
+                })
+                .chain(std::iter::once(WalOperation::Commit { txn_id }));
+            for operation in operations {
+                let lsn = wal.entries.len() as u64;
+                wal.append(WalEntry {
+                    transaction_id: txn_id,
+                    timestamp,
+                    operation,
+                    lsn,
+                });
+            }
         }
 
+        transaction.write_set.extend(writes);
         transaction.state = TransactionState::Committed;
 
         Ok(())
@@ -389,3 +409,638 @@ impl std::fmt::Display for TransactionError {
 }
 
 impl std::error::Error for TransactionError {}
+
+impl From<TransactionError> for crate::CoreTexError {
+    fn from(e: TransactionError) -> Self {
+        crate::CoreTexError::TransactionError(e.to_string())
+    }
+}
+
+/// A vector with its metadata as staged or overwritten; `None` means
+/// deleted.
+pub type VersionValue = Option<(Vec<f32>, serde_json::Value)>;
+/// `(collection, vector id)`
+pub type VersionKey = (String, String);
+/// What one write replaced, as `(key, version before it)`.
+pub(crate) type VersionChanges = Vec<(VersionKey, VersionValue)>;
+
+/// A point in the version history to read at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AsOf {
+    /// Milliseconds since the Unix epoch.
+    Timestamp(u64),
+    /// A snapshot from `CoreTexDB::create_snapshot`.
+    Snapshot(SnapshotId),
+}
+
+#[derive(Debug, Clone)]
+pub struct VersionConfig {
+    /// How long overwritten and deleted versions stay readable.
+    pub retention_ms: u64,
+}
+
+impl Default for VersionConfig {
+    fn default() -> Self {
+        Self {
+            // Two weeks, so "as of last Tuesday" still answers
+            retention_ms: 14 * 24 * 60 * 60 * 1000,
+        }
+    }
+}
+
+/// What one write replaced.
+struct VersionRecord {
+    seq: u64,
+    timestamp_ms: u64,
+    key: VersionKey,
+    before: VersionValue,
+}
+
+/// Where a read is positioned; records after it hold the values it sees.
+#[derive(Clone, Copy)]
+pub(crate) enum VersionPoint {
+    Seq(u64),
+    Timestamp(u64),
+}
+
+impl VersionRecord {
+    fn is_after(&self, point: VersionPoint) -> bool {
+        match point {
+            VersionPoint::Seq(seq) => self.seq > seq,
+            VersionPoint::Timestamp(timestamp_ms) => self.timestamp_ms > timestamp_ms,
+        }
+    }
+}
+
+#[derive(Default)]
+struct VersionState {
+    /// Ordered by `seq`, and so by `timestamp_ms`.
+    records: VecDeque<VersionRecord>,
+    last_seq: u64,
+    last_timestamp_ms: u64,
+    /// History before this time has been collected.
+    horizon_ms: u64,
+    /// Sequence numbers that open transactions and snapshots read at.
+    pins: HashMap<u64, u64>,
+    next_pin: u64,
+    /// Pinned sequence numbers by snapshot id; the id is also the pin.
+    snapshots: HashMap<SnapshotId, u64>,
+}
+
+/// Multi-version history of a `CoreTexDB`. Every write records the versions
+/// it replaced; a read at an earlier point swaps those back in. Versions are
+/// collected once they are older than the retention window and no open
+/// transaction or snapshot reads at or before them. History starts when the
+/// store is created; a database from `CoreTexDB::open` reloads it from the
+/// journal, whose entries carry what each write replaced.
+pub struct VersionStore {
+    config: VersionConfig,
+    state: std::sync::RwLock<VersionState>,
+}
+
+impl VersionStore {
+    pub fn new(config: VersionConfig) -> Self {
+        Self {
+            config,
+            state: std::sync::RwLock::new(VersionState {
+                // Nothing before this is known
+                horizon_ms: now_ms(),
+                ..VersionState::default()
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &VersionConfig {
+        &self.config
+    }
+
+    /// Number of old versions currently kept.
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records what one write replaced; all of it gets one sequence number.
+    /// Callers hold the database's data lock, so history and data change
+    /// together.
+    pub(crate) fn record(&self, changes: Vec<(VersionKey, VersionValue)>) {
+        let now = now_ms();
+        let mut state = self.state.write().unwrap();
+        state.last_seq += 1;
+        state.last_timestamp_ms = state.last_timestamp_ms.max(now);
+
+        let (seq, timestamp_ms) = (state.last_seq, state.last_timestamp_ms);
+        state.records.extend(changes.into_iter().map(|(key, before)| VersionRecord { seq, timestamp_ms, key, before }));
+        Self::collect(&mut state, now.saturating_sub(self.config.retention_ms));
+    }
+
+    /// Replaces the history with `history`, one `(timestamp_ms, changes)`
+    /// per write, oldest first, known from `horizon_ms` on. Used when a
+    /// durable database is opened, before anything reads or pins it.
+    pub(crate) fn load(&self, history: Vec<(u64, VersionChanges)>, horizon_ms: u64) {
+        let mut state = self.state.write().unwrap();
+        state.records.clear();
+        state.last_timestamp_ms = 0;
+        state.horizon_ms = horizon_ms;
+        for (timestamp_ms, changes) in history {
+            state.last_seq += 1;
+            state.last_timestamp_ms = state.last_timestamp_ms.max(timestamp_ms);
+            let (seq, timestamp_ms) = (state.last_seq, state.last_timestamp_ms);
+            state.records.extend(changes.into_iter().map(|(key, before)| VersionRecord { seq, timestamp_ms, key, before }));
+        }
+        Self::collect(&mut state, now_ms().saturating_sub(self.config.retention_ms));
+    }
+
+    fn collect(state: &mut VersionState, cutoff_ms: u64) {
+        let oldest_pin = state.pins.values().min().copied().unwrap_or(u64::MAX);
+        while let Some(record) = state.records.front() {
+            if record.timestamp_ms >= cutoff_ms || record.seq > oldest_pin {
+                break;
+            }
+            state.horizon_ms = state.horizon_ms.max(record.timestamp_ms);
+            state.records.pop_front();
+        }
+    }
+
+    /// Drops versions that fell out of the retention window.
+    pub fn gc(&self) {
+        let cutoff_ms = now_ms().saturating_sub(self.config.retention_ms);
+        Self::collect(&mut self.state.write().unwrap(), cutoff_ms);
+    }
+
+    fn current_seq(&self) -> u64 {
+        self.state.read().unwrap().last_seq
+    }
+
+    /// Keeps versions after `seq` until `unpin`.
+    fn pin(&self, seq: u64) -> u64 {
+        let mut state = self.state.write().unwrap();
+        state.next_pin += 1;
+        let pin = state.next_pin;
+        state.pins.insert(pin, seq);
+        pin
+    }
+
+    fn unpin(&self, pin: u64) {
+        let mut state = self.state.write().unwrap();
+        state.pins.remove(&pin);
+        Self::collect(&mut state, now_ms().saturating_sub(self.config.retention_ms));
+    }
+
+    /// Pins the current state under a new snapshot id. Callers hold the
+    /// data lock.
+    pub(crate) fn create_snapshot(&self) -> SnapshotId {
+        let seq = self.current_seq();
+        let pin = self.pin(seq);
+        self.state.write().unwrap().snapshots.insert(pin, seq);
+        pin
+    }
+
+    pub fn release_snapshot(&self, id: SnapshotId) -> bool {
+        let released = self.state.write().unwrap().snapshots.remove(&id).is_some();
+        if released {
+            self.unpin(id);
+        }
+        released
+    }
+
+    pub(crate) fn point(&self, as_of: AsOf) -> crate::Result<VersionPoint> {
+        let state = self.state.read().unwrap();
+        match as_of {
+            AsOf::Snapshot(id) => state.snapshots.get(&id)
+                .map(|seq| VersionPoint::Seq(*seq))
+                .ok_or_else(|| TransactionError::SnapshotNotFound(id).into()),
+            AsOf::Timestamp(timestamp_ms) if timestamp_ms < state.horizon_ms => Err(CoreTexError::ValidationError(format!(
+                "as_of {} is older than the retained history, which starts at {}", timestamp_ms, state.horizon_ms
+            ))),
+            AsOf::Timestamp(timestamp_ms) => Ok(VersionPoint::Timestamp(timestamp_ms)),
+        }
+    }
+
+    /// Whether anything wrote to one of `keys` after `seq`.
+    fn changed_after(&self, seq: u64, keys: &BTreeMap<VersionKey, VersionValue>) -> Option<VersionKey> {
+        let state = self.state.read().unwrap();
+        state.records.iter()
+            .rev()
+            .take_while(|record| record.seq > seq)
+            .find(|record| keys.contains_key(&record.key))
+            .map(|record| record.key.clone())
+    }
+
+    /// Value of `key` at `point`, given its current value.
+    pub(crate) fn value_at(&self, point: VersionPoint, key: &VersionKey, current: VersionValue) -> VersionValue {
+        let state = self.state.read().unwrap();
+        state.records.iter()
+            .find(|record| record.is_after(point) && record.key == *key)
+            .map_or(current, |record| record.before.clone())
+    }
+
+    /// Ids of `collection` whose version at `point`, with `staged` applied
+    /// on top, differs from the current one, and that version.
+    fn overrides_at(&self, point: VersionPoint, collection: &str, staged: &BTreeMap<VersionKey, VersionValue>) -> HashMap<String, VersionValue> {
+        let state = self.state.read().unwrap();
+        let mut overrides = HashMap::new();
+        for record in state.records.iter().filter(|record| record.is_after(point) && record.key.0 == collection) {
+            overrides.entry(record.key.1.clone()).or_insert_with(|| record.before.clone());
+        }
+        for ((staged_collection, id), value) in staged {
+            if staged_collection == collection {
+                overrides.insert(id.clone(), value.clone());
+            }
+        }
+        overrides
+    }
+}
+impl Default for VersionStore {
+    fn default() -> Self {
+        Self::new(VersionConfig::default())
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+impl CoreTexDB {
+    /// Reserves documents for `holder`, e.g. a prepared distributed
+    /// transaction, failing if another holder has any of them.
+    pub(crate) fn reserve(&self, holder: &str, keys: &[VersionKey]) -> crate::Result<()> {
+        let mut reserved = self.transactions.reserved.lock().unwrap();
+        if let Some((collection, id)) = keys.iter().find(|key| reserved.get(*key).is_some_and(|h| h != holder)) {
+            return Err(TransactionError::WriteConflict(format!("{}/{} is reserved by another transaction", collection, id)).into());
+        }
+        for key in keys {
+            reserved.insert(key.clone(), holder.to_string());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn release(&self, holder: &str) {
+        self.transactions.reserved.lock().unwrap().retain(|_, h| h != holder);
+    }
+
+    /// Fails if a document written, or the one whose side vectors are, is
+    /// reserved by anyone but `holder`.
+    pub(crate) fn check_reserved<'k>(&self, keys: impl IntoIterator<Item = &'k VersionKey>, holder: Option<&str>) -> crate::Result<()> {
+        let reserved = self.transactions.reserved.lock().unwrap();
+        if reserved.is_empty() {
+            return Ok(());
+        }
+        for (collection, id) in keys {
+            let collection = crate::coretex_side_vectors::main_collection(collection).unwrap_or(collection);
+            let key = (collection.to_string(), id.clone());
+            if reserved.get(&key).is_some_and(|h| Some(h.as_str()) != holder) {
+                return Err(TransactionError::WriteConflict(format!("{}/{} is reserved by another transaction", collection, id)).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if a document of `collection` is reserved.
+    pub(crate) fn check_unreserved_collection(&self, collection: &str) -> crate::Result<()> {
+        if let Some(((_, id), _)) = self.transactions.reserved.lock().unwrap().iter().find(|((c, _), _)| c == collection) {
+            return Err(TransactionError::WriteConflict(format!("{}/{} is reserved by another transaction", collection, id)).into());
+        }
+        Ok(())
+    }
+
+    /// `current`, the value of a document now, as it was at `as_of`.
+    pub(crate) fn version_as_of(&self, as_of: AsOf, collection: &str, id: &str, current: VersionValue) -> crate::Result<VersionValue> {
+        let point = self.versions.point(as_of)?;
+        Ok(self.versions.value_at(point, &(collection.to_string(), id.to_string()), current))
+    }
+
+    /// Search over a collection as it was at `as_of`.
+    pub(crate) async fn search_as_of(
+        &self,
+        collection: &str,
+        query: Vec<f32>,
+        k: usize,
+        filter: Option<serde_json::Value>,
+        as_of: AsOf,
+    ) -> crate::Result<Vec<SearchResult>> {
+        let point = self.versions.point(as_of)?;
+        self.search_at(point, collection, &BTreeMap::new(), &query, k, filter.as_ref()).await
+    }
+
+    /// Search over `collection` at `point` with `staged` applied on top.
+    /// Candidates come from the collection's index, which holds current
+    /// versions; enough extra are fetched to make up for those that changed
+    /// since, and the versions visible at `point` are scored with the
+    /// collection's metric and merged in.
+    async fn search_at(
+        &self,
+        point: VersionPoint,
+        collection: &str,
+        staged: &BTreeMap<VersionKey, VersionValue>,
+        query: &[f32],
+        k: usize,
+        filter: Option<&serde_json::Value>,
+    ) -> crate::Result<Vec<SearchResult>> {
+        let collections = self.collections.read().await;
+        let schema = collections.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        let data = self.data.read().await;
+        let current = data.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+
+        let overrides = self.versions.overrides_at(point, collection, staged);
+        let visible = |(id, metadata): (&str, &serde_json::Value)| {
+            !overrides.contains_key(id) && filter.is_none_or(|filter| CoreTexDB::matches_filter(metadata, filter))
+        };
+
+        let index = self.index_manager.get_index(&format!("{}_hnsw", collection)).await
+            .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+        let mut results: Vec<SearchResult> = match index {
+            Some(index) => index.search(query, k * 2 + overrides.len()).await
+                .map_err(|e| CoreTexError::IndexError(e.to_string()))?
+                .into_iter()
+                .filter(|r| current.get(&r.id).is_some_and(|(_, metadata)| visible((&r.id, metadata))))
+                .collect(),
+            None => current.iter()
+                .filter(|(id, (_, metadata))| visible((id, metadata)))
+                .map(|(id, (vector, _))| SearchResult {
+                    id: id.clone(),
+                    distance: field_distance(query, vector, &schema.distance_metric),
+                })
+                .collect(),
+        };
+
+        results.extend(overrides.iter()
+            .filter_map(|(id, value)| value.as_ref().map(|(vector, metadata)| (id, vector, metadata)))
+            .filter(|(_, _, metadata)| filter.is_none_or(|filter| CoreTexDB::matches_filter(metadata, filter)))
+            .map(|(id, vector, _)| SearchResult {
+                id: id.clone(),
+                distance: field_distance(query, vector, &schema.distance_metric),
+            }));
+
+        CoreTexDB::sort_search_results(&mut results);
+        results.truncate(k);
+        Ok(results)
+    }
+
+    /// Pins the current state; `AsOf::Snapshot` reads it until
+    /// `release_snapshot`, regardless of the retention window.
+    pub async fn create_snapshot(&self) -> SnapshotId {
+        let _data = self.data.read().await;
+        self.versions.create_snapshot()
+    }
+
+    pub async fn release_snapshot(&self, id: SnapshotId) -> bool {
+        self.versions.release_snapshot(id)
+    }
+}
+
+struct PendingTransaction {
+    /// Writes numbered up to here are visible to the transaction.
+    snapshot_seq: u64,
+    /// Keeps the versions the transaction reads from being collected.
+    pin: u64,
+    writes: BTreeMap<VersionKey, VersionValue>,
+    /// Whose reserved documents the transaction may write.
+    holder: Option<String>,
+}
+
+/// Transactions over one `CoreTexDB`. Writes are staged per transaction and
+/// applied in one step at commit, which fails if anything else wrote one of
+/// the same vectors since the transaction began. Reads see the database as
+/// of `begin` plus the transaction's own writes.
+pub struct DbTransactions {
+    manager: TransactionManager,
+    pending: RwLock<HashMap<TransactionId, PendingTransaction>>,
+    /// Documents reserved by prepared distributed transactions, by holder.
+    /// Until released, only the holder writes them and their collections
+    /// cannot be deleted, so a prepared transaction can always commit.
+    reserved: std::sync::Mutex<HashMap<VersionKey, String>>,
+}
+
+impl DbTransactions {
+    pub fn new() -> Self {
+        Self {
+            manager: TransactionManager::new(),
+            pending: RwLock::new(HashMap::new()),
+            reserved: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The underlying manager, whose in-memory log keeps the history of
+    /// recent commits. The database's journal is what makes them durable.
+    pub fn manager(&self) -> &TransactionManager {
+        &self.manager
+    }
+
+    pub async fn active_count(&self) -> usize {
+        self.pending.read().await.len()
+    }
+
+    async fn is_pending(&self, txn_id: TransactionId) -> bool {
+        self.pending.read().await.contains_key(&txn_id)
+    }
+}
+
+impl Default for DbTransactions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn encode_version(value: &(Vec<f32>, serde_json::Value)) -> Vec<u8> {
+    serde_json::to_vec(value).unwrap_or_default()
+}
+
+/// A transaction on a `CoreTexDB`, from `CoreTexDB::begin`. Dropping it
+/// leaves the transaction open; `CoreTexDB::transaction` picks it up again
+/// by id.
+pub struct DbTransaction<'a> {
+    db: &'a CoreTexDB,
+    id: TransactionId,
+}
+
+impl<'a> DbTransaction<'a> {
+    pub(crate) async fn begin(db: &'a CoreTexDB, holder: Option<String>) -> crate::Result<Self> {
+        let id = db.transactions.manager.begin_transaction(IsolationLevel::Snapshot).await?;
+
+        // Writers record versions while holding the data lock
+        let (snapshot_seq, pin) = {
+            let _data = db.data.read().await;
+            let seq = db.versions.current_seq();
+            (seq, db.versions.pin(seq))
+        };
+        db.transactions.pending.write().await.insert(id, PendingTransaction {
+            snapshot_seq,
+            pin,
+            writes: BTreeMap::new(),
+            holder,
+        });
+
+        Ok(Self { db, id })
+    }
+
+    pub(crate) async fn resume(db: &'a CoreTexDB, id: TransactionId) -> crate::Result<Self> {
+        if !db.transactions.is_pending(id).await {
+            return Err(TransactionError::TransactionNotFound(id).into());
+        }
+        Ok(Self { db, id })
+    }
+
+    pub fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    async fn check_vectors(&self, collection: &str, vectors: &[(String, Vec<f32>, serde_json::Value)]) -> crate::Result<()> {
+        let collections = self.db.collections.read().await;
+        let schema = collections.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+
+        for (_, vector, _) in vectors {
+            if vector.len() != schema.dimension {
+                return Err(CoreTexError::DimensionMismatch {
+                    expected: schema.dimension,
+                    actual: vector.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn stage(&self, collection: &str, writes: Vec<(String, VersionValue)>) -> crate::Result<()> {
+        let mut pending = self.db.transactions.pending.write().await;
+        let txn = pending.get_mut(&self.id).ok_or(TransactionError::TransactionNotFound(self.id))?;
+        for (id, value) in writes {
+            txn.writes.insert((collection.to_string(), id), value);
+        }
+        Ok(())
+    }
+
+    /// Stages inserts; like `CoreTexDB::insert_vectors`, an existing id is
+    /// overwritten.
+    pub async fn insert(&self, collection: &str, vectors: Vec<(String, Vec<f32>, serde_json::Value)>) -> crate::Result<Vec<String>> {
+        self.check_vectors(collection, &vectors).await?;
+
+        let ids: Vec<String> = vectors.iter().map(|(id, _, _)| id.clone()).collect();
+        let writes = vectors.into_iter().map(|(id, vector, metadata)| (id, Some((vector, metadata)))).collect();
+        self.stage(collection, writes).await?;
+        Ok(ids)
+    }
+
+    /// Stages writes and reports which ids were new and which existed, as
+    /// this transaction sees them.
+    pub async fn upsert(&self, collection: &str, vectors: Vec<(String, Vec<f32>, serde_json::Value)>) -> crate::Result<(Vec<String>, Vec<String>)> {
+        self.check_vectors(collection, &vectors).await?;
+
+        let mut inserted = Vec::new();
+        let mut updated = Vec::new();
+        for (id, _, _) in &vectors {
+            if self.get_vector(collection, id).await?.is_some() {
+                updated.push(id.clone());
+            } else {
+                inserted.push(id.clone());
+            }
+        }
+
+        let writes = vectors.into_iter().map(|(id, vector, metadata)| (id, Some((vector, metadata)))).collect();
+        self.stage(collection, writes).await?;
+        Ok((inserted, updated))
+    }
+
+    /// Stages deletes and returns how many of the ids this transaction saw.
+    pub async fn delete(&self, collection: &str, ids: &[String]) -> crate::Result<usize> {
+        let mut deleted = 0;
+        for id in ids {
+            if self.get_vector(collection, id).await?.is_some() {
+                deleted += 1;
+            }
+        }
+
+        self.stage(collection, ids.iter().map(|id| (id.clone(), None)).collect()).await?;
+        Ok(deleted)
+    }
+
+    pub async fn get_vector(&self, collection: &str, id: &str) -> crate::Result<Option<(Vec<f32>, serde_json::Value)>> {
+        let key = (collection.to_string(), id.to_string());
+
+        let pending = self.db.transactions.pending.read().await;
+        let txn = pending.get(&self.id).ok_or(TransactionError::TransactionNotFound(self.id))?;
+        if let Some(staged) = txn.writes.get(&key) {
+            return Ok(staged.clone());
+        }
+
+        let data = self.db.data.read().await;
+        let collection_data = data.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        let current = self.db.document(collection_data, collection, id).await?;
+        Ok(self.db.versions.value_at(VersionPoint::Seq(txn.snapshot_seq), &key, current))
+    }
+
+    /// Search over the collection as this transaction sees it.
+    pub async fn search(&self, collection: &str, query: Vec<f32>, k: usize, filter: Option<serde_json::Value>) -> crate::Result<Vec<SearchResult>> {
+        let pending = self.db.transactions.pending.read().await;
+        let txn = pending.get(&self.id).ok_or(TransactionError::TransactionNotFound(self.id))?;
+
+        let point = VersionPoint::Seq(txn.snapshot_seq);
+        self.db.search_at(point, collection, &txn.writes, &query, k, filter.as_ref()).await
+    }
+
+    /// Applies every staged write at once, or none if one of the vectors
+    /// changed since the transaction began.
+    pub async fn commit(self) -> crate::Result<()> {
+        let txn = self.db.transactions.pending.write().await.remove(&self.id)
+            .ok_or(TransactionError::TransactionNotFound(self.id))?;
+        let pin = txn.pin;
+
+        let result = self.apply(txn).await;
+        if result.is_err() {
+            let _ = self.db.transactions.manager.abort(self.id).await;
+        }
+        self.db.versions.unpin(pin);
+        result
+    }
+
+    async fn apply(&self, txn: PendingTransaction) -> crate::Result<()> {
+        let collections = self.db.collections.read().await;
+        let mut data = self.db.data.write().await;
+
+        if let Some((collection, id)) = self.db.versions.changed_after(txn.snapshot_seq, &txn.writes) {
+            return Err(TransactionError::WriteConflict(format!("{}/{} changed since the transaction began", collection, id)).into());
+        }
+        self.db.check_reserved(txn.writes.keys(), txn.holder.as_deref())?;
+        for (collection, _) in txn.writes.keys() {
+            if !collections.contains_key(collection) || !data.contains_key(collection) {
+                return Err(CoreTexError::CollectionNotFound(collection.clone()));
+            }
+        }
+
+        let mut log = Vec::with_capacity(txn.writes.len());
+        for ((collection, id), value) in &txn.writes {
+            let key = format!("{}/{}", collection, id);
+            let before = self.db.document(&data[collection], collection, id).await?;
+            log.push(match (before.as_ref(), value) {
+                (None, Some(value)) => WriteOperation::Insert { key, value: encode_version(value) },
+                (Some(before), Some(value)) => WriteOperation::Update { key, old_value: encode_version(before), new_value: encode_version(value) },
+                (Some(before), None) => WriteOperation::Delete { key, old_value: encode_version(before) },
+                (None, None) => continue,
+            });
+        }
+
+        // Journal before touching the data; on a durable database the commit
+        // is replayed from there after a crash
+        let writes = self.db.with_side_deletes(txn.writes.clone()).await;
+        let lsn = self.db.journal_writes(&data, &writes).await?;
+        self.db.transactions.manager.commit_with_writes(self.id, log).await?;
+
+        self.db.apply_logged_writes(&mut data, writes).await?;
+        self.db.entry_applied(lsn).await
+    }
+
+    /// Discards every staged write.
+    pub async fn rollback(self) -> crate::Result<()> {
+        let txn = self.db.transactions.pending.write().await.remove(&self.id)
+            .ok_or(TransactionError::TransactionNotFound(self.id))?;
+        self.db.versions.unpin(txn.pin);
+        self.db.transactions.manager.abort(self.id).await?;
+        Ok(())
+    }
+}
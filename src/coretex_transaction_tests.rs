@@ -218,4 +218,204 @@ mod tests {
         let msg = format!("{}", error);
         assert!(msg.contains("456"));
     }
+
+    async fn db_with_collections() -> crate::CoreTexDB {
+        let db = crate::CoreTexDB::new();
+        db.create_collection("docs", 3, "cosine").await.unwrap();
+        db.create_collection("chunks", 3, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![
+            ("a".to_string(), vec![1.0, 0.0, 0.0], serde_json::json!({ "model": "v1" })),
+            ("b".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({ "model": "v1" })),
+        ]).await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_db_transaction_commits_across_collections() {
+        let db = db_with_collections().await;
+
+        let txn = db.begin().await.unwrap();
+        let txn_id = txn.id();
+        txn.insert("chunks", vec![("c1".to_string(), vec![0.0, 0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+        let (inserted, updated) = txn.upsert("docs", vec![
+            ("a".to_string(), vec![0.5, 0.5, 0.0], serde_json::json!({ "model": "v2" })),
+            ("c".to_string(), vec![0.0, 0.0, 1.0], serde_json::json!({ "model": "v2" })),
+        ]).await.unwrap();
+        assert_eq!(inserted, vec!["c".to_string()]);
+        assert_eq!(updated, vec!["a".to_string()]);
+        assert_eq!(txn.delete("docs", &["b".to_string()]).await.unwrap(), 1);
+
+        // Staged writes are visible to the transaction only
+        assert!(txn.get_vector("docs", "b").await.unwrap().is_none());
+        assert!(db.get_vector("docs", "b", None).await.unwrap().is_some());
+        assert!(db.get_vector("chunks", "c1", None).await.unwrap().is_none());
+        let hits = txn.search("docs", vec![0.0, 0.0, 1.0], 10, None).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["c", "a"]);
+
+        txn.commit().await.unwrap();
+
+        assert!(db.get_vector("docs", "b", None).await.unwrap().is_none());
+        assert_eq!(db.get_vector("docs", "a", None).await.unwrap().unwrap().1["model"], "v2");
+        assert!(db.get_vector("chunks", "c1", None).await.unwrap().is_some());
+        assert!(db.transaction(txn_id).await.is_err());
+
+        let wal = db.transactions.manager().get_wal_entries(0).await;
+        let logged: Vec<_> = wal.iter().filter(|e| e.transaction_id == txn_id).map(|e| &e.operation).collect();
+        assert_eq!(logged.len(), 6);
+        assert!(matches!(logged.last(), Some(WalOperation::Commit { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_db_transaction_rollback() {
+        let db = db_with_collections().await;
+
+        let txn = db.begin().await.unwrap();
+        txn.delete("docs", &["a".to_string(), "b".to_string()]).await.unwrap();
+        txn.insert("chunks", vec![("c1".to_string(), vec![0.0, 0.0, 1.0], serde_json::json!({}))]).await.unwrap();
+        txn.rollback().await.unwrap();
+
+        assert_eq!(db.get_vectors_count("docs").await.unwrap(), 2);
+        assert_eq!(db.get_vectors_count("chunks").await.unwrap(), 0);
+        assert_eq!(db.transactions.active_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_db_transaction_snapshot_isolation_and_conflicts() {
+        let db = db_with_collections().await;
+
+        let reader = db.begin().await.unwrap();
+        let writer = db.begin().await.unwrap();
+        writer.upsert("docs", vec![("a".to_string(), vec![0.0, 0.0, 1.0], serde_json::json!({ "model": "v2" }))]).await.unwrap();
+        writer.delete("docs", &["b".to_string()]).await.unwrap();
+        writer.commit().await.unwrap();
+
+        // The reader keeps seeing the database as of its begin
+        assert_eq!(reader.get_vector("docs", "a").await.unwrap().unwrap().1["model"], "v1");
+        let hits = reader.search("docs", vec![1.0, 0.0, 0.0], 10, None).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(hits[0].distance, 0.0);
+
+        // First committer wins
+        reader.insert("docs", vec![("a".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({ "model": "v3" }))]).await.unwrap();
+        let err = reader.commit().await.unwrap_err();
+        assert!(err.to_string().contains("Write conflict"));
+        assert_eq!(db.get_vector("docs", "a", None).await.unwrap().unwrap().1["model"], "v2");
+
+        // A transaction begun after the commit sees it and can write
+        let later = db.begin().await.unwrap();
+        assert!(later.get_vector("docs", "b").await.unwrap().is_none());
+        later.insert("docs", vec![("a".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({ "model": "v3" }))]).await.unwrap();
+        later.commit().await.unwrap();
+        assert_eq!(db.get_vector("docs", "a", None).await.unwrap().unwrap().1["model"], "v3");
+    }
+
+    fn now_ms() -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64
+    }
+
+    #[tokio::test]
+    async fn test_db_reads_as_of_timestamp() {
+        use crate::AsOf;
+
+        let db = db_with_collections().await;
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let before = now_ms();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+        db.update_vector("docs", "a", vec![0.0, 0.0, 1.0], Some(serde_json::json!({ "model": "v2" }))).await.unwrap();
+        db.delete_vectors("docs", &["b".to_string()]).await.unwrap();
+        db.insert_vectors("docs", vec![("c".to_string(), vec![1.0, 0.0, 0.0], serde_json::json!({ "model": "v2" }))]).await.unwrap();
+
+        let (vector, metadata) = db.get_vector("docs", "a", Some(AsOf::Timestamp(before))).await.unwrap().unwrap();
+        assert_eq!(vector, vec![1.0, 0.0, 0.0]);
+        assert_eq!(metadata["model"], "v1");
+        assert!(db.get_vector("docs", "b", Some(AsOf::Timestamp(before))).await.unwrap().is_some());
+        assert!(db.get_vector("docs", "c", Some(AsOf::Timestamp(before))).await.unwrap().is_none());
+
+        let hits = db.search("docs", vec![1.0, 0.0, 0.0], 10, None, Some(AsOf::Timestamp(before))).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        let hits = db.search("docs", vec![1.0, 0.0, 0.0], 10, None, Some(AsOf::Timestamp(now_ms()))).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["c", "a"]);
+    }
+
+    #[tokio::test]
+    async fn test_db_snapshot_reads_until_released() {
+        use crate::AsOf;
+
+        let db = db_with_collections().await;
+        let snapshot = db.create_snapshot().await;
+        db.upsert_vectors("docs", vec![("a".to_string(), vec![0.0, 1.0, 0.0], serde_json::json!({ "model": "v2" }))]).await.unwrap();
+        db.delete_collection("docs").await.unwrap();
+        db.create_collection("docs", 3, "cosine").await.unwrap();
+
+        let (_, metadata) = db.get_vector("docs", "a", Some(AsOf::Snapshot(snapshot))).await.unwrap().unwrap();
+        assert_eq!(metadata["model"], "v1");
+        let hits = db.search("docs", vec![0.0, 1.0, 0.0], 10, None, Some(AsOf::Snapshot(snapshot))).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].id, "b");
+
+        assert!(db.release_snapshot(snapshot).await);
+        assert!(!db.release_snapshot(snapshot).await);
+        assert!(db.get_vector("docs", "a", Some(AsOf::Snapshot(snapshot))).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_db_versions_outside_retention_are_collected() {
+        use crate::AsOf;
+
+        let db = crate::CoreTexDB::with_config(crate::DbConfig {
+            memory_only: true,
+            create_dirs_on_init: false,
+            version_retention_ms: 0,
+            ..crate::DbConfig::default()
+        });
+        db.create_collection("docs", 3, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![("a".to_string(), vec![1.0, 0.0, 0.0], serde_json::json!({}))]).await.unwrap();
+        let before = now_ms();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        db.update_vector("docs", "a", vec![0.0, 1.0, 0.0], None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        db.versions.gc();
+
+        assert!(db.versions.is_empty());
+        assert!(db.get_vector("docs", "a", Some(AsOf::Timestamp(before))).await.is_err());
+        assert!(db.get_vector("docs", "a", Some(AsOf::Timestamp(now_ms()))).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_db_history_starts_when_the_database_does() {
+        use crate::AsOf;
+
+        let created = now_ms();
+        let db = db_with_collections().await;
+        assert!(db.get_vector("docs", "a", Some(AsOf::Timestamp(created.saturating_sub(1000)))).await.is_err());
+        assert!(db.get_vector("docs", "a", Some(AsOf::Timestamp(now_ms()))).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_db_search_as_of_merges_old_versions_with_index_candidates() {
+        use crate::AsOf;
+
+        let db = crate::CoreTexDB::new();
+        db.create_collection("docs", 2, "euclidean").await.unwrap();
+        let vectors = (0..20)
+            .map(|i| (format!("v{:02}", i), vec![i as f32, 0.0], serde_json::json!({})))
+            .collect();
+        db.insert_vectors("docs", vectors).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let before = now_ms();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+        // The nearest vectors all move away; as of `before` they are still nearest
+        for i in 0..3 {
+            db.update_vector("docs", &format!("v{:02}", i), vec![100.0 + i as f32, 0.0], None).await.unwrap();
+        }
+
+        let hits = db.search("docs", vec![0.0, 0.0], 3, None, Some(AsOf::Timestamp(before))).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["v00", "v01", "v02"]);
+        assert_eq!(hits.iter().map(|h| h.distance).collect::<Vec<_>>(), vec![0.0, 1.0, 2.0]);
+
+        let hits = db.search("docs", vec![0.0, 0.0], 3, None, Some(AsOf::Timestamp(now_ms()))).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["v03", "v04", "v05"]);
+    }
 }
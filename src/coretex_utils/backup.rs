@@ -44,7 +44,7 @@ impl BackupManager {
             let count = db.get_vectors_count(collection_name).await.unwrap_or(0);
             
             for i in 0..count {
-                if let Ok(Some((vector, metadata))) = db.get_vector(collection_name, &format!("vec_{}", i)).await {
+                if let Ok(Some((vector, metadata))) = db.get_vector(collection_name, &format!("vec_{}", i), None).await {
                     vectors_data.push(serde_json::json!({
                         "id": format!("vec_{}", i),
                         "vector": vector,
@@ -5,7 +5,9 @@ use tokio::sync::RwLock;
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::raft::RaftNode;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeRole {
     Leader,
     Follower,
@@ -36,9 +38,10 @@ pub struct ClusterManager {
     current_node_id: String,
     shards: Arc<RwLock<HashMap<u32, Shard>>>,
     replication_factor: usize,
+    raft: Option<Arc<RaftNode>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Shard {
     pub id: u32,
     pub primary_node: String,
@@ -52,6 +55,34 @@ impl ClusterManager {
             current_node_id: node_id.to_string(),
             shards: Arc::new(RwLock::new(HashMap::new())),
             replication_factor,
+            raft: None,
+        }
+    }
+
+    /// Takes node roles from this node's Raft instance instead of the
+    /// roles nodes were registered with.
+    pub fn with_raft(mut self, raft: Arc<RaftNode>) -> Self {
+        self.raft = Some(raft);
+        self
+    }
+
+    /// Copies roles from Raft: the Raft leader becomes `Leader`, this node
+    /// takes its own Raft role and every other node is a `Follower`.
+    pub async fn sync_roles(&self) {
+        let Some(raft) = &self.raft else {
+            return;
+        };
+        let status = raft.status().await;
+
+        let mut nodes = self.nodes.write().await;
+        for node in nodes.values_mut() {
+            node.role = if node.id == self.current_node_id {
+                status.role.clone()
+            } else if status.leader.as_deref() == Some(node.id.as_str()) {
+                NodeRole::Leader
+            } else {
+                NodeRole::Follower
+            };
         }
     }
 
@@ -79,6 +110,7 @@ impl ClusterManager {
     }
 
     pub async fn get_leader(&self) -> Option<ClusterNode> {
+        self.sync_roles().await;
         let nodes = self.nodes.read().await;
         nodes.values()
             .find(|n| matches!(n.role, NodeRole::Leader))
@@ -104,6 +136,25 @@ impl ClusterManager {
         });
     }
 
+    pub async fn get_shard(&self, shard_id: u32) -> Option<Shard> {
+        self.shards.read().await.get(&shard_id).cloned()
+    }
+
+    pub async fn remove_shard(&self, shard_id: u32) {
+        self.shards.write().await.remove(&shard_id);
+    }
+
+    /// Puts back a shard as it was placed before a restart.
+    pub async fn restore_shard(&self, shard: Shard) {
+        self.shards.write().await.insert(shard.id, shard);
+    }
+
+    /// One past the highest shard id in use.
+    pub async fn next_shard_id(&self) -> u32 {
+        let shards = self.shards.read().await;
+        shards.keys().max().map_or(0, |id| id + 1)
+    }
+
     pub async fn get_shard_nodes(&self, shard_id: u32) -> Option<(String, Vec<String>)> {
         let shards = self.shards.read().await;
         
@@ -114,6 +165,26 @@ impl ClusterManager {
         }
     }
 
+    /// Hands `from`'s copy of a shard, primary or replica, to `to`.
+    pub async fn replace_shard_node(&self, shard_id: u32, from: &str, to: &str) -> bool {
+        let mut shards = self.shards.write().await;
+        let Some(shard) = shards.get_mut(&shard_id) else {
+            return false;
+        };
+
+        if shard.primary_node == from {
+            shard.primary_node = to.to_string();
+            return true;
+        }
+        match shard.replica_nodes.iter_mut().find(|node| *node == from) {
+            Some(node) => {
+                *node = to.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
     pub async fn rebalance_shards(&self) {
         let active_nodes = self.get_active_nodes().await;
         
@@ -8,12 +8,14 @@ pub mod cluster;
 pub mod backup;
 pub mod monitoring;
 pub mod cache;
+pub mod raft;
 
 pub use wal::WriteAheadLog;
 pub use transaction::{TransactionManager, LockManager, Transaction, TransactionOperation, TransactionState};
 pub use cluster::{ClusterManager, ClusterNode, NodeRole, NodeState, Shard};
 pub use backup::BackupManager;
 pub use monitoring::{MonitoringService, Metrics};
+pub use raft::{RaftNode, RaftConfig, RaftError, RaftStatus, RaftTransport, RaftStateMachine, RaftCommand, Proposal};
 pub use cache::{LRUCache, TimedLRUCache, AsyncLRUCache, MultiLevelCache, CacheStats, MultiLevelCacheStats};
 
 /// Calculate cosine similarity between two vectors
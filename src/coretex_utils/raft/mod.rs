@@ -0,0 +1,1333 @@
+//! Raft consensus for CortexDB
+//! Replicates WAL entries across the cluster: leader election with terms, log
+//! replication, snapshots that compact the log, and single-server membership
+//! changes. Nodes are driven by `tick()` (or `start()`, which ticks on a
+//! timer) and talk through a `RaftTransport`; `sim` provides an in-process
+//! network with partitions for tests, `coretex_distributed::consensus` the
+//! HTTP one servers use.
+//!
+//! The cluster configuration lives in the log as `RaftCommand::Configuration`
+//! entries. A bootstrapping node writes the initial one at index 1, so nodes
+//! that join later learn the membership from the leader.
+
+mod storage;
+pub mod sim;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+
+use super::cluster::NodeRole;
+use super::wal::WalEntry;
+use storage::{HardState, RaftStorage};
+
+#[derive(Debug, Clone)]
+pub enum RaftError {
+    /// Carries the leader this node knows of, if any.
+    NotLeader(Option<String>),
+    Unreachable(String),
+    ConfigChangePending,
+    /// A later leader overwrote the proposal at this index.
+    Superseded(u64),
+    Timeout(u64),
+    StorageError(String),
+    Corrupted(String),
+    StateMachineError(String),
+}
+
+impl std::fmt::Display for RaftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RaftError::NotLeader(Some(leader)) => write!(f, "Not the leader; leader is {}", leader),
+            RaftError::NotLeader(None) => write!(f, "Not the leader; no leader known"),
+            RaftError::Unreachable(node) => write!(f, "Node unreachable: {}", node),
+            RaftError::ConfigChangePending => write!(f, "A membership change is already in progress"),
+            RaftError::Superseded(index) => write!(f, "Entry {} was overwritten by a newer leader", index),
+            RaftError::Timeout(index) => write!(f, "Timed out waiting for entry {} to commit", index),
+            RaftError::StorageError(msg) => write!(f, "Storage error: {}", msg),
+            RaftError::Corrupted(msg) => write!(f, "Corrupted raft state: {}", msg),
+            RaftError::StateMachineError(msg) => write!(f, "State machine error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RaftError {}
+
+#[derive(Debug, Clone)]
+pub struct RaftConfig {
+    pub tick_interval_ms: u64,
+    /// Followers campaign after a random 1-2x this many ticks without
+    /// hearing from a leader.
+    pub election_ticks: u64,
+    pub heartbeat_ticks: u64,
+    pub max_entries_per_append: usize,
+    /// Applied entries kept in the log before they are compacted into a snapshot.
+    pub snapshot_threshold: u64,
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        Self {
+            tick_interval_ms: 100,
+            election_ticks: 10,
+            heartbeat_ticks: 1,
+            max_entries_per_append: 256,
+            snapshot_threshold: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RaftCommand {
+    /// Appended by every new leader so entries from earlier terms can commit.
+    Noop,
+    Wal(WalEntry),
+    /// The full set of voting members from this entry on.
+    Configuration(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub index: u64,
+    pub term: u64,
+    pub command: RaftCommand,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RaftSnapshot {
+    pub last_index: u64,
+    pub last_term: u64,
+    /// Membership as of `last_index`.
+    pub members: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RaftRequest {
+    RequestVote {
+        term: u64,
+        candidate_id: String,
+        last_log_index: u64,
+        last_log_term: u64,
+    },
+    AppendEntries {
+        term: u64,
+        leader_id: String,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    },
+    InstallSnapshot {
+        term: u64,
+        leader_id: String,
+        snapshot: RaftSnapshot,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RaftResponse {
+    RequestVote {
+        term: u64,
+        vote_granted: bool,
+    },
+    /// On success `match_index` is the last index known to match the leader;
+    /// on failure it is where the leader should retry from (exclusive).
+    AppendEntries {
+        term: u64,
+        success: bool,
+        match_index: u64,
+    },
+    InstallSnapshot {
+        term: u64,
+        last_index: u64,
+    },
+}
+
+#[async_trait]
+pub trait RaftTransport: Send + Sync {
+    async fn send(&self, from: &str, to: &str, request: RaftRequest) -> Result<RaftResponse, RaftError>;
+}
+
+/// Receives committed WAL entries in log order. An entry that fails to
+/// apply is skipped, and its proposal fails with the error.
+#[async_trait]
+pub trait RaftStateMachine: Send + Sync {
+    async fn apply(&mut self, index: u64, entry: &WalEntry) -> Result<(), RaftError>;
+    async fn snapshot(&self) -> Result<Vec<u8>, RaftError>;
+    async fn restore(&mut self, data: &[u8]) -> Result<(), RaftError>;
+}
+
+/// Where a proposal landed in the leader's log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Proposal {
+    pub index: u64,
+    pub term: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RaftStatus {
+    pub node_id: String,
+    pub role: NodeRole,
+    pub term: u64,
+    pub leader: Option<String>,
+    pub commit_index: u64,
+    pub last_applied: u64,
+    pub last_log_index: u64,
+    pub snapshot_index: u64,
+    pub members: Vec<String>,
+    /// The last time durable state could not be saved, if it has happened.
+    pub last_storage_error: Option<String>,
+    /// The last committed entry the state machine refused, and why. It was
+    /// skipped, and entries after it applied as usual.
+    pub apply_error: Option<String>,
+}
+
+/// Refused entries remembered for proposers still waiting on them.
+const FAILED_ENTRIES_KEPT: usize = 1024;
+
+/// How far the state machine has got, as watched by proposers.
+#[derive(Debug, Clone, Default)]
+struct Applied {
+    index: u64,
+    /// Recent entries up to `index` the state machine refused, and why,
+    /// oldest first.
+    failed: VecDeque<(u64, RaftError)>,
+}
+
+struct RaftState {
+    role: NodeRole,
+    term: u64,
+    voted_for: Option<String>,
+    leader: Option<String>,
+    /// Entries after `snapshot.last_index`, contiguous.
+    log: Vec<LogEntry>,
+    snapshot: RaftSnapshot,
+    members: Vec<String>,
+    commit_index: u64,
+    last_applied: u64,
+    elapsed_ticks: u64,
+    election_timeout: u64,
+    next_index: HashMap<String, u64>,
+    match_index: HashMap<String, u64>,
+    votes: HashSet<String>,
+    state_machine: Box<dyn RaftStateMachine>,
+    storage: Option<RaftStorage>,
+    last_storage_error: Option<String>,
+}
+
+impl RaftState {
+    fn last_index(&self) -> u64 {
+        self.snapshot.last_index + self.log.len() as u64
+    }
+
+    fn last_term(&self) -> u64 {
+        self.log.last().map_or(self.snapshot.last_term, |entry| entry.term)
+    }
+
+    /// `None` for indexes past the log or compacted into the snapshot.
+    fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot.last_index {
+            Some(self.snapshot.last_term)
+        } else {
+            self.entry(index).map(|entry| entry.term)
+        }
+    }
+
+    fn entry(&self, index: u64) -> Option<&LogEntry> {
+        if index <= self.snapshot.last_index {
+            return None;
+        }
+        self.log.get((index - self.snapshot.last_index - 1) as usize)
+    }
+
+    /// Membership in effect at `index`: the latest configuration entry up to
+    /// it, or the snapshot's.
+    fn members_at(&self, index: u64) -> Vec<String> {
+        self.log.iter()
+            .rev()
+            .filter(|entry| entry.index <= index)
+            .find_map(|entry| match &entry.command {
+                RaftCommand::Configuration(members) => Some(members.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.snapshot.members.clone())
+    }
+
+    fn quorum(&self) -> usize {
+        self.members.len() / 2 + 1
+    }
+
+    fn persist_hard_state(&self) -> Result<(), RaftError> {
+        match &self.storage {
+            Some(storage) => storage.save_hard_state(&HardState {
+                term: self.term,
+                voted_for: self.voted_for.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps a storage failure that has no caller to go to for `status`.
+    fn record_storage_error(&mut self, e: RaftError) {
+        self.last_storage_error = Some(e.to_string());
+    }
+
+    fn has_pending_config_change(&self) -> bool {
+        self.log.iter().any(|entry| {
+            entry.index > self.commit_index && matches!(entry.command, RaftCommand::Configuration(_))
+        })
+    }
+}
+
+pub struct RaftNode {
+    id: String,
+    config: RaftConfig,
+    transport: Arc<dyn RaftTransport>,
+    state: Mutex<RaftState>,
+    applied_tx: watch::Sender<Applied>,
+}
+
+impl RaftNode {
+    /// An in-memory node. `members` bootstraps a new cluster; pass an empty
+    /// list for a node that will be added to an existing one.
+    pub fn new(
+        id: &str,
+        members: Vec<String>,
+        transport: Arc<dyn RaftTransport>,
+        state_machine: Box<dyn RaftStateMachine>,
+    ) -> Self {
+        let mut node = Self::build(id, transport, state_machine, None, HardState::default(), None, Vec::new());
+        if !members.is_empty() {
+            let state = node.state.get_mut();
+            state.log.push(bootstrap_entry(members.clone()));
+            state.members = members;
+        }
+        node
+    }
+
+    /// A node persisted in `dir`. Term, vote, log and snapshot are recovered;
+    /// `members` is only used when the directory holds no state yet. The
+    /// state machine must be empty: it is restored from the snapshot and the
+    /// rest of the log is re-applied as the commit index is learned again.
+    pub async fn open(
+        dir: &Path,
+        id: &str,
+        members: Vec<String>,
+        transport: Arc<dyn RaftTransport>,
+        state_machine: Box<dyn RaftStateMachine>,
+    ) -> Result<Self, RaftError> {
+        let (storage, mut recovered) = RaftStorage::open(dir)?;
+        let fresh = recovered.snapshot.is_none() && recovered.entries.is_empty();
+        if fresh && !members.is_empty() {
+            let entry = bootstrap_entry(members);
+            storage.append(std::slice::from_ref(&entry))?;
+            recovered.entries.push(entry);
+        }
+
+        let mut node = Self::build(
+            id,
+            transport,
+            state_machine,
+            Some(storage),
+            recovered.hard_state,
+            recovered.snapshot,
+            recovered.entries,
+        );
+
+        let state = node.state.get_mut();
+        if state.snapshot.last_index > 0 {
+            let data = state.snapshot.data.clone();
+            state.state_machine.restore(&data).await?;
+        }
+        Ok(node)
+    }
+
+    fn build(
+        id: &str,
+        transport: Arc<dyn RaftTransport>,
+        state_machine: Box<dyn RaftStateMachine>,
+        storage: Option<RaftStorage>,
+        hard_state: HardState,
+        snapshot: Option<RaftSnapshot>,
+        log: Vec<LogEntry>,
+    ) -> Self {
+        let config = RaftConfig::default();
+        let snapshot = snapshot.unwrap_or_default();
+        let (applied_tx, _) = watch::channel(Applied { index: snapshot.last_index, failed: VecDeque::new() });
+
+        let mut state = RaftState {
+            role: NodeRole::Follower,
+            term: hard_state.term,
+            voted_for: hard_state.voted_for,
+            leader: None,
+            log,
+            commit_index: snapshot.last_index,
+            last_applied: snapshot.last_index,
+            snapshot,
+            members: Vec::new(),
+            elapsed_ticks: 0,
+            election_timeout: random_election_timeout(&config),
+            next_index: HashMap::new(),
+            match_index: HashMap::new(),
+            votes: HashSet::new(),
+            state_machine,
+            storage,
+            last_storage_error: None,
+        };
+        state.members = state.members_at(state.last_index());
+
+        Self {
+            id: id.to_string(),
+            config,
+            transport,
+            state: Mutex::new(state),
+            applied_tx,
+        }
+    }
+
+    pub fn with_config(mut self, config: RaftConfig) -> Self {
+        self.state.get_mut().election_timeout = random_election_timeout(&config);
+        self.config = config;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub async fn status(&self) -> RaftStatus {
+        let state = self.state.lock().await;
+        RaftStatus {
+            node_id: self.id.clone(),
+            role: state.role.clone(),
+            term: state.term,
+            leader: state.leader.clone(),
+            commit_index: state.commit_index,
+            last_applied: state.last_applied,
+            last_log_index: state.last_index(),
+            snapshot_index: state.snapshot.last_index,
+            members: state.members.clone(),
+            last_storage_error: state.last_storage_error.clone(),
+            apply_error: self.applied_tx.borrow().failed.back()
+                .map(|(index, e)| format!("Entry {}: {}", index, e)),
+        }
+    }
+
+    pub async fn is_leader(&self) -> bool {
+        self.state.lock().await.role == NodeRole::Leader
+    }
+
+    pub async fn leader(&self) -> Option<String> {
+        self.state.lock().await.leader.clone()
+    }
+
+    /// Ticks every `tick_interval_ms` until the task is aborted.
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        let node = self.clone();
+
+        tokio::spawn(async move {
+            let interval = Duration::from_millis(node.config.tick_interval_ms.max(1));
+            loop {
+                tokio::time::sleep(interval).await;
+                node.tick().await;
+            }
+        })
+    }
+
+    /// Advances logical time by one tick: leaders send heartbeats, followers
+    /// that have not heard from a leader in time start an election.
+    pub async fn tick(&self) {
+        let (heartbeat, campaign) = {
+            let mut state = self.state.lock().await;
+            state.elapsed_ticks += 1;
+            match state.role {
+                NodeRole::Leader => (state.elapsed_ticks >= self.config.heartbeat_ticks, false),
+                _ => (false, state.elapsed_ticks >= state.election_timeout),
+            }
+        };
+
+        if heartbeat {
+            self.replicate().await;
+        } else if campaign {
+            self.campaign().await;
+        }
+    }
+
+    /// Starts an election for the next term. Returns whether this node won.
+    /// Nodes outside the current membership never campaign.
+    pub async fn campaign(&self) -> bool {
+        let (term, request, peers) = {
+            let mut state = self.state.lock().await;
+            state.elapsed_ticks = 0;
+            state.election_timeout = random_election_timeout(&self.config);
+            if !state.members.contains(&self.id) {
+                return false;
+            }
+
+            state.term += 1;
+            state.role = NodeRole::Candidate;
+            state.voted_for = Some(self.id.clone());
+            state.leader = None;
+            state.votes = HashSet::from([self.id.clone()]);
+            if let Err(e) = state.persist_hard_state() {
+                state.role = NodeRole::Follower;
+                state.record_storage_error(e);
+                return false;
+            }
+
+            let request = RaftRequest::RequestVote {
+                term: state.term,
+                candidate_id: self.id.clone(),
+                last_log_index: state.last_index(),
+                last_log_term: state.last_term(),
+            };
+            (state.term, request, self.peers(&state))
+        };
+
+        let responses = futures::future::join_all(
+            peers.iter().map(|peer| self.transport.send(&self.id, peer, request.clone()))
+        ).await;
+
+        {
+            let mut state = self.state.lock().await;
+            for (peer, response) in peers.into_iter().zip(responses) {
+                if let Ok(RaftResponse::RequestVote { term: peer_term, vote_granted }) = response {
+                    if peer_term > state.term {
+                        self.become_follower(&mut state, peer_term, None);
+                    } else if vote_granted && state.term == term {
+                        state.votes.insert(peer);
+                    }
+                }
+            }
+
+            let votes = state.votes.iter().filter(|id| state.members.contains(id)).count();
+            if state.role != NodeRole::Candidate || state.term != term || votes < state.quorum() {
+                return false;
+            }
+            if !self.become_leader(&mut state) {
+                return false;
+            }
+        }
+
+        self.replicate().await;
+        true
+    }
+
+    /// Appends a WAL entry to the leader's log and replicates it. The entry
+    /// is committed once `wait_committed` returns `Ok`.
+    pub async fn propose(&self, entry: WalEntry) -> Result<Proposal, RaftError> {
+        let proposal = {
+            let mut state = self.state.lock().await;
+            self.leader_append(&mut state, RaftCommand::Wal(entry))?
+        };
+        self.replicate().await;
+        Ok(proposal)
+    }
+
+    pub async fn add_member(&self, node_id: &str) -> Result<Proposal, RaftError> {
+        self.change_members(|members| {
+            if !members.iter().any(|m| m == node_id) {
+                members.push(node_id.to_string());
+            }
+        }).await
+    }
+
+    /// A leader that removes itself keeps leading until the change commits,
+    /// then steps down.
+    pub async fn remove_member(&self, node_id: &str) -> Result<Proposal, RaftError> {
+        self.change_members(|members| members.retain(|m| m != node_id)).await
+    }
+
+    /// Membership changes one server at a time, so old and new majorities
+    /// always overlap; a second change waits for the first to commit.
+    async fn change_members(&self, change: impl FnOnce(&mut Vec<String>)) -> Result<Proposal, RaftError> {
+        let proposal = {
+            let mut state = self.state.lock().await;
+            if state.role != NodeRole::Leader {
+                return Err(RaftError::NotLeader(state.leader.clone()));
+            }
+            if state.has_pending_config_change() {
+                return Err(RaftError::ConfigChangePending);
+            }
+
+            let mut members = state.members.clone();
+            change(&mut members);
+            self.leader_append(&mut state, RaftCommand::Configuration(members))?
+        };
+        self.replicate().await;
+        Ok(proposal)
+    }
+
+    /// Waits until the proposal is committed and applied here. Fails with
+    /// the state machine's error if it refused the entry.
+    pub async fn wait_committed(&self, proposal: Proposal, timeout: Duration) -> Result<(), RaftError> {
+        let mut applied_rx = self.applied_tx.subscribe();
+        let applied = tokio::time::timeout(timeout, async {
+            loop {
+                let applied = applied_rx.borrow_and_update().clone();
+                if applied.index >= proposal.index {
+                    return match applied.failed.into_iter().find(|(index, _)| *index == proposal.index) {
+                        Some((_, e)) => Err(e),
+                        None => Ok(()),
+                    };
+                }
+                if applied_rx.changed().await.is_err() {
+                    return Ok(());
+                }
+            }
+        }).await;
+
+        match applied {
+            Err(_) => return Err(RaftError::Timeout(proposal.index)),
+            Ok(Err(e)) => return Err(e),
+            Ok(Ok(())) => {}
+        }
+
+        let state = self.state.lock().await;
+        match state.term_at(proposal.index) {
+            Some(term) if term != proposal.term => Err(RaftError::Superseded(proposal.index)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Entry point for requests arriving from the transport.
+    pub async fn handle(&self, request: RaftRequest) -> RaftResponse {
+        let mut state = self.state.lock().await;
+        match request {
+            RaftRequest::RequestVote { term, candidate_id, last_log_index, last_log_term } => {
+                self.handle_request_vote(&mut state, term, candidate_id, last_log_index, last_log_term)
+            }
+            RaftRequest::AppendEntries { term, leader_id, prev_log_index, prev_log_term, entries, leader_commit } => {
+                self.handle_append_entries(&mut state, term, leader_id, prev_log_index, prev_log_term, entries, leader_commit).await
+            }
+            RaftRequest::InstallSnapshot { term, leader_id, snapshot } => {
+                self.handle_install_snapshot(&mut state, term, leader_id, snapshot).await
+            }
+        }
+    }
+
+    fn handle_request_vote(
+        &self,
+        state: &mut RaftState,
+        term: u64,
+        candidate_id: String,
+        last_log_index: u64,
+        last_log_term: u64,
+    ) -> RaftResponse {
+        if term > state.term && !self.become_follower(state, term, None) {
+            return RaftResponse::RequestVote { term: state.term, vote_granted: false };
+        }
+
+        let up_to_date = (last_log_term, last_log_index) >= (state.last_term(), state.last_index());
+        let free = state.voted_for.as_ref().is_none_or(|voted| *voted == candidate_id);
+        let mut vote_granted = term == state.term && up_to_date && free;
+
+        if vote_granted {
+            state.voted_for = Some(candidate_id);
+            if let Err(e) = state.persist_hard_state() {
+                state.voted_for = None;
+                state.record_storage_error(e);
+                vote_granted = false;
+            }
+            state.elapsed_ticks = 0;
+        }
+
+        RaftResponse::RequestVote {
+            term: state.term,
+            vote_granted,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_append_entries(
+        &self,
+        state: &mut RaftState,
+        term: u64,
+        leader_id: String,
+        mut prev_log_index: u64,
+        mut prev_log_term: u64,
+        mut entries: Vec<LogEntry>,
+        leader_commit: u64,
+    ) -> RaftResponse {
+        if term < state.term || !self.become_follower(state, term, Some(leader_id)) {
+            return RaftResponse::AppendEntries { term: state.term, success: false, match_index: 0 };
+        }
+        state.elapsed_ticks = 0;
+
+        let reject = |state: &RaftState, retry_after: u64| RaftResponse::AppendEntries {
+            term: state.term,
+            success: false,
+            match_index: retry_after,
+        };
+
+        if prev_log_index > state.last_index() {
+            return reject(state, state.last_index());
+        }
+
+        // Entries up to the snapshot are committed here already
+        if prev_log_index < state.snapshot.last_index {
+            let skip = (state.snapshot.last_index - prev_log_index) as usize;
+            entries.drain(..skip.min(entries.len()));
+            prev_log_index = state.snapshot.last_index;
+            prev_log_term = state.snapshot.last_term;
+        }
+
+        let local_term = state.term_at(prev_log_index);
+        if local_term != Some(prev_log_term) {
+            // Skip back over the whole conflicting term in one round trip
+            let mut retry_after = prev_log_index.saturating_sub(1);
+            while retry_after > state.snapshot.last_index && state.term_at(retry_after) == local_term {
+                retry_after -= 1;
+            }
+            return reject(state, retry_after);
+        }
+
+        let last_new = prev_log_index + entries.len() as u64;
+        // What the log held before, to put back if the new entries cannot
+        // be stored
+        let mut kept = state.log.len();
+        let mut truncated = Vec::new();
+        let mut appended = Vec::new();
+        for entry in entries {
+            match state.term_at(entry.index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => {
+                    kept = (entry.index - state.snapshot.last_index - 1) as usize;
+                    truncated = state.log.split_off(kept);
+                }
+                None => {}
+            }
+            appended.push(entry.clone());
+            state.log.push(entry);
+        }
+
+        let persisted = match &state.storage {
+            Some(storage) if !truncated.is_empty() => storage.rewrite_log(&state.log),
+            Some(storage) => storage.append(&appended),
+            None => Ok(()),
+        };
+        if let Err(e) = persisted {
+            state.log.truncate(kept);
+            state.log.extend(truncated);
+            state.record_storage_error(e);
+            return reject(state, state.commit_index);
+        }
+
+        let config_changed = !truncated.is_empty() || appended.iter().any(|e| matches!(e.command, RaftCommand::Configuration(_)));
+        if config_changed {
+            state.members = state.members_at(state.last_index());
+        }
+
+        if leader_commit > state.commit_index {
+            state.commit_index = state.commit_index.max(leader_commit.min(last_new));
+            self.apply_committed(state).await;
+        }
+
+        RaftResponse::AppendEntries {
+            term: state.term,
+            success: true,
+            match_index: last_new,
+        }
+    }
+
+    async fn handle_install_snapshot(
+        &self,
+        state: &mut RaftState,
+        term: u64,
+        leader_id: String,
+        snapshot: RaftSnapshot,
+    ) -> RaftResponse {
+        if term < state.term || !self.become_follower(state, term, Some(leader_id)) {
+            return RaftResponse::InstallSnapshot { term: state.term, last_index: 0 };
+        }
+        state.elapsed_ticks = 0;
+
+        let last_index = snapshot.last_index;
+        if last_index <= state.commit_index {
+            return RaftResponse::InstallSnapshot { term: state.term, last_index };
+        }
+        if state.state_machine.restore(&snapshot.data).await.is_err() {
+            return RaftResponse::InstallSnapshot { term: state.term, last_index: 0 };
+        }
+
+        // Keep any suffix that follows on from the snapshot
+        if state.term_at(last_index) == Some(snapshot.last_term) {
+            state.log.retain(|entry| entry.index > last_index);
+        } else {
+            state.log.clear();
+        }
+        state.snapshot = snapshot;
+        state.commit_index = last_index;
+        state.last_applied = last_index;
+        state.members = state.members_at(state.last_index());
+
+        if let Some(Err(e)) = state.storage.as_ref().map(|storage| storage.save_snapshot(&state.snapshot, &state.log)) {
+            state.record_storage_error(e);
+            return RaftResponse::InstallSnapshot { term: state.term, last_index: 0 };
+        }
+        self.applied_tx.send_modify(|applied| applied.index = state.last_applied);
+
+        RaftResponse::InstallSnapshot {
+            term: state.term,
+            last_index,
+        }
+    }
+
+    /// Sends every peer the entries it is missing (or the snapshot, if they
+    /// were compacted away), then advances the commit index.
+    async fn replicate(&self) {
+        let (term, requests) = {
+            let mut state = self.state.lock().await;
+            if state.role != NodeRole::Leader {
+                return;
+            }
+            state.elapsed_ticks = 0;
+
+            let last_index = state.last_index();
+            let mut requests = Vec::new();
+            for peer in self.peers(&state) {
+                let next = *state.next_index.entry(peer.clone()).or_insert(last_index + 1);
+                let request = if next <= state.snapshot.last_index {
+                    RaftRequest::InstallSnapshot {
+                        term: state.term,
+                        leader_id: self.id.clone(),
+                        snapshot: state.snapshot.clone(),
+                    }
+                } else {
+                    let prev_log_index = next - 1;
+                    let end = last_index.min(prev_log_index + self.config.max_entries_per_append as u64);
+                    RaftRequest::AppendEntries {
+                        term: state.term,
+                        leader_id: self.id.clone(),
+                        prev_log_index,
+                        prev_log_term: state.term_at(prev_log_index).unwrap_or(0),
+                        entries: (next..=end).filter_map(|i| state.entry(i).cloned()).collect(),
+                        leader_commit: state.commit_index,
+                    }
+                };
+                requests.push((peer, request));
+            }
+            (state.term, requests)
+        };
+
+        let responses = futures::future::join_all(
+            requests.iter().map(|(peer, request)| self.transport.send(&self.id, peer, request.clone()))
+        ).await;
+
+        let mut state = self.state.lock().await;
+        for ((peer, _), response) in requests.into_iter().zip(responses) {
+            let (peer_term, matched, success) = match response {
+                Ok(RaftResponse::AppendEntries { term, success, match_index }) => (term, match_index, success),
+                Ok(RaftResponse::InstallSnapshot { term, last_index }) => (term, last_index, last_index > 0),
+                _ => continue,
+            };
+
+            if peer_term > state.term {
+                self.become_follower(&mut state, peer_term, None);
+                return;
+            }
+            if state.role != NodeRole::Leader || state.term != term {
+                return;
+            }
+
+            let match_index = state.match_index.get(&peer).copied().unwrap_or(0);
+            if success {
+                let match_index = match_index.max(matched);
+                state.match_index.insert(peer.clone(), match_index);
+                state.next_index.insert(peer, match_index + 1);
+            } else {
+                let next = state.next_index.get(&peer).copied().unwrap_or(1);
+                let retry = next.saturating_sub(1).min(matched + 1).max(match_index + 1);
+                state.next_index.insert(peer, retry);
+            }
+        }
+
+        self.advance_commit(&mut state).await;
+    }
+
+    /// Commits the highest entry of the current term stored on a majority.
+    async fn advance_commit(&self, state: &mut RaftState) {
+        let last_index = state.last_index();
+        for index in (state.commit_index + 1..=last_index).rev() {
+            if state.term_at(index) != Some(state.term) {
+                break;
+            }
+
+            let replicated = state.members.iter()
+                .filter(|member| {
+                    if **member == self.id {
+                        true
+                    } else {
+                        state.match_index.get(*member).is_some_and(|m| *m >= index)
+                    }
+                })
+                .count();
+
+            if replicated >= state.quorum() {
+                state.commit_index = index;
+                self.apply_committed(state).await;
+                break;
+            }
+        }
+    }
+
+    /// Applies committed entries in order. One the state machine refuses is
+    /// skipped as a no-op: every node must get past the same committed
+    /// entries, and a refusal can depend on state local to this node.
+    async fn apply_committed(&self, state: &mut RaftState) {
+        let mut failed = Vec::new();
+        while state.last_applied < state.commit_index {
+            let index = state.last_applied + 1;
+            let Some(command) = state.entry(index).map(|entry| entry.command.clone()) else {
+                break;
+            };
+
+            match command {
+                RaftCommand::Wal(entry) => {
+                    if let Err(e) = state.state_machine.apply(index, &entry).await {
+                        failed.push((index, e));
+                    }
+                }
+                RaftCommand::Configuration(members) => {
+                    if state.role == NodeRole::Leader && !members.contains(&self.id) {
+                        state.role = NodeRole::Follower;
+                        state.leader = None;
+                    }
+                }
+                RaftCommand::Noop => {}
+            }
+            state.last_applied = index;
+        }
+
+        self.maybe_snapshot(state).await;
+        self.applied_tx.send_modify(|applied| {
+            applied.index = state.last_applied;
+            applied.failed.extend(failed);
+            let excess = applied.failed.len().saturating_sub(FAILED_ENTRIES_KEPT);
+            applied.failed.drain(..excess);
+        });
+    }
+
+    async fn maybe_snapshot(&self, state: &mut RaftState) {
+        let last_applied = state.last_applied;
+        if last_applied - state.snapshot.last_index < self.config.snapshot_threshold.max(1) {
+            return;
+        }
+        let Ok(data) = state.state_machine.snapshot().await else {
+            return;
+        };
+
+        let snapshot = RaftSnapshot {
+            last_index: last_applied,
+            last_term: state.term_at(last_applied).unwrap_or(0),
+            members: state.members_at(last_applied),
+            data,
+        };
+        let log: Vec<LogEntry> = state.log.iter().filter(|entry| entry.index > last_applied).cloned().collect();
+
+        // Compacts only once stored; the old snapshot and full log stay
+        // valid if that fails
+        if let Some(Err(e)) = state.storage.as_ref().map(|storage| storage.save_snapshot(&snapshot, &log)) {
+            state.record_storage_error(e);
+            return;
+        }
+        state.log = log;
+        state.snapshot = snapshot;
+    }
+
+    fn leader_append(&self, state: &mut RaftState, command: RaftCommand) -> Result<Proposal, RaftError> {
+        if state.role != NodeRole::Leader {
+            return Err(RaftError::NotLeader(state.leader.clone()));
+        }
+
+        let entry = LogEntry {
+            index: state.last_index() + 1,
+            term: state.term,
+            command,
+        };
+        if let Some(storage) = &state.storage {
+            storage.append(std::slice::from_ref(&entry))?;
+        }
+        if let RaftCommand::Configuration(members) = &entry.command {
+            state.members = members.clone();
+        }
+
+        let proposal = Proposal { index: entry.index, term: entry.term };
+        state.log.push(entry);
+        Ok(proposal)
+    }
+
+    /// Takes over as leader, unless its opening entry cannot be stored:
+    /// without it, entries from earlier terms could not commit.
+    fn become_leader(&self, state: &mut RaftState) -> bool {
+        state.role = NodeRole::Leader;
+        state.leader = Some(self.id.clone());
+        state.elapsed_ticks = 0;
+
+        let next = state.last_index() + 1;
+        state.next_index = self.peers(state).into_iter().map(|peer| (peer, next)).collect();
+        state.match_index.clear();
+
+        if let Err(e) = self.leader_append(state, RaftCommand::Noop) {
+            state.role = NodeRole::Follower;
+            state.leader = None;
+            state.record_storage_error(e);
+            return false;
+        }
+        true
+    }
+
+    /// Adopts `term` (clearing the vote if it is newer) and follows `leader`.
+    /// A newer term that cannot be stored is not adopted and the failure is
+    /// recorded, but the node still stops leading or campaigning. Returns
+    /// whether `term` is now the node's.
+    fn become_follower(&self, state: &mut RaftState, term: u64, leader: Option<String>) -> bool {
+        state.role = NodeRole::Follower;
+        state.votes.clear();
+        if term > state.term {
+            let (previous_term, previous_vote) = (state.term, state.voted_for.take());
+            state.term = term;
+            if let Err(e) = state.persist_hard_state() {
+                state.term = previous_term;
+                state.voted_for = previous_vote;
+                state.leader = None;
+                state.record_storage_error(e);
+                return false;
+            }
+        }
+        state.leader = leader;
+        true
+    }
+
+
+    fn peers(&self, state: &RaftState) -> Vec<String> {
+        state.members.iter().filter(|member| **member != self.id).cloned().collect()
+    }
+}
+
+fn bootstrap_entry(members: Vec<String>) -> LogEntry {
+    LogEntry {
+        index: 1,
+        term: 0,
+        command: RaftCommand::Configuration(members),
+    }
+}
+
+fn random_election_timeout(config: &RaftConfig) -> u64 {
+    let base = config.election_ticks.max(1);
+    rand::thread_rng().gen_range(base..base * 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::sim::RaftCluster;
+    use crate::coretex_utils::cluster::{ClusterManager, ClusterNode, NodeState};
+    use crate::coretex_utils::wal::WalEntryType;
+
+    fn wal_entry(id: u64) -> WalEntry {
+        WalEntry {
+            id,
+            timestamp: 0,
+            entry_type: WalEntryType::Insert,
+            collection: "docs".to_string(),
+            data: serde_json::json!({ "id": id }),
+        }
+    }
+
+    fn applied_ids(cluster: &RaftCluster, node_id: &str) -> Vec<u64> {
+        cluster.state_machine(node_id).entries().iter().map(|e| e.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_raft_elects_leader_and_replicates() {
+        let cluster = RaftCluster::new(3, RaftConfig::default());
+        let leader = cluster.wait_for_leader(100).await.expect("no leader elected");
+
+        for id in 1..=3 {
+            let proposal = leader.propose(wal_entry(id)).await.unwrap();
+            leader.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+        }
+        cluster.tick(2).await;
+
+        let mut leaders = 0;
+        for node in cluster.nodes() {
+            let status = node.status().await;
+            assert_eq!(status.leader.as_deref(), Some(leader.id()));
+            if status.role == NodeRole::Leader {
+                leaders += 1;
+            }
+            assert_eq!(applied_ids(&cluster, node.id()), vec![1, 2, 3]);
+        }
+        assert_eq!(leaders, 1);
+
+        let follower = cluster.nodes().iter().find(|n| n.id() != leader.id()).unwrap().clone();
+        assert!(matches!(follower.propose(wal_entry(4)).await, Err(RaftError::NotLeader(Some(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_raft_partitioned_leader_is_replaced() {
+        let cluster = RaftCluster::new(3, RaftConfig::default());
+        let old_leader = cluster.wait_for_leader(100).await.unwrap();
+        let old_term = old_leader.status().await.term;
+        let committed = old_leader.propose(wal_entry(1)).await.unwrap();
+        old_leader.wait_committed(committed, Duration::from_secs(1)).await.unwrap();
+
+        // Cut the leader off: its writes can no longer reach a majority
+        cluster.network().isolate(old_leader.id());
+        let lost = old_leader.propose(wal_entry(2)).await.unwrap();
+        assert!(matches!(
+            old_leader.wait_committed(lost, Duration::from_millis(50)).await,
+            Err(RaftError::Timeout(_))
+        ));
+
+        let new_leader = cluster.wait_for_leader(100).await.unwrap();
+        assert_ne!(new_leader.id(), old_leader.id());
+        assert!(new_leader.status().await.term > old_term);
+        let proposal = new_leader.propose(wal_entry(3)).await.unwrap();
+        new_leader.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+
+        cluster.network().heal();
+        cluster.tick(5).await;
+
+        assert!(!old_leader.is_leader().await);
+        assert!(matches!(
+            old_leader.wait_committed(lost, Duration::from_secs(1)).await,
+            Err(RaftError::Superseded(_))
+        ));
+        for node in cluster.nodes() {
+            assert_eq!(applied_ids(&cluster, node.id()), vec![1, 3]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raft_membership_change_catches_up_from_snapshot() {
+        let config = RaftConfig {
+            snapshot_threshold: 4,
+            max_entries_per_append: 3,
+            ..RaftConfig::default()
+        };
+        let mut cluster = RaftCluster::new(3, config);
+        let leader = cluster.wait_for_leader(100).await.unwrap();
+
+        for id in 1..=10 {
+            let proposal = leader.propose(wal_entry(id)).await.unwrap();
+            leader.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+        }
+        assert!(leader.status().await.snapshot_index > 0);
+
+        cluster.add_node("node4");
+        let proposal = leader.add_member("node4").await.unwrap();
+        leader.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+        cluster.tick(5).await;
+
+        let joined = cluster.node("node4").unwrap();
+        assert_eq!(joined.status().await.members.len(), 4);
+        assert_eq!(applied_ids(&cluster, "node4"), (1..=10).collect::<Vec<_>>());
+
+        // With node4 in, a write still commits while one old member is gone
+        let removed = cluster.nodes().iter().find(|n| n.id() != leader.id() && n.id() != "node4").unwrap().clone();
+        let proposal = leader.remove_member(removed.id()).await.unwrap();
+        leader.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+        cluster.network().isolate(removed.id());
+
+        let proposal = leader.propose(wal_entry(11)).await.unwrap();
+        leader.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+        cluster.tick(1).await;
+        assert_eq!(leader.status().await.members.len(), 3);
+        assert_eq!(applied_ids(&cluster, "node4").last(), Some(&11));
+    }
+
+    #[tokio::test]
+    async fn test_raft_node_recovers_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let network = Arc::new(sim::SimNetwork::new());
+        let members = vec!["solo".to_string()];
+
+        {
+            let node = Arc::new(RaftNode::open(
+                dir.path(), "solo", members.clone(), network.clone(), Box::new(sim::MemoryStateMachine::new()),
+            ).await.unwrap().with_config(RaftConfig { snapshot_threshold: 3, ..RaftConfig::default() }));
+            assert!(node.campaign().await);
+            for id in 1..=5 {
+                let proposal = node.propose(wal_entry(id)).await.unwrap();
+                node.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+            }
+        }
+
+        let machine = sim::MemoryStateMachine::new();
+        let node = RaftNode::open(dir.path(), "solo", members, network, Box::new(machine.clone())).await.unwrap();
+        let status = node.status().await;
+        assert_eq!(status.term, 1);
+        assert!(status.snapshot_index > 0);
+        assert_eq!(status.last_log_index, 7);
+
+        // Re-elected, the node commits and re-applies the rest of its log
+        assert!(node.campaign().await);
+        assert_eq!(machine.entries().iter().map(|e| e.id).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    }
+
+    /// Refuses the entries whose ids are in `refused`.
+    struct RefusingStateMachine {
+        refused: Arc<std::sync::Mutex<HashSet<u64>>>,
+        inner: sim::MemoryStateMachine,
+    }
+
+    #[async_trait]
+    impl RaftStateMachine for RefusingStateMachine {
+        async fn apply(&mut self, index: u64, entry: &WalEntry) -> Result<(), RaftError> {
+            if self.refused.lock().unwrap().contains(&entry.id) {
+                return Err(RaftError::StateMachineError(format!("refused {}", entry.id)));
+            }
+            self.inner.apply(index, entry).await
+        }
+
+        async fn snapshot(&self) -> Result<Vec<u8>, RaftError> {
+            self.inner.snapshot().await
+        }
+
+        async fn restore(&mut self, data: &[u8]) -> Result<(), RaftError> {
+            self.inner.restore(data).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refused_entry_fails_its_proposal_and_is_skipped() {
+        let refused = Arc::new(std::sync::Mutex::new(HashSet::from([2])));
+        let machine = sim::MemoryStateMachine::new();
+        let node = RaftNode::new(
+            "solo",
+            vec!["solo".to_string()],
+            Arc::new(sim::SimNetwork::new()),
+            Box::new(RefusingStateMachine { refused, inner: machine.clone() }),
+        );
+        assert!(node.campaign().await);
+
+        let proposal = node.propose(wal_entry(1)).await.unwrap();
+        node.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+        let refused_proposal = node.propose(wal_entry(2)).await.unwrap();
+        assert!(matches!(
+            node.wait_committed(refused_proposal, Duration::from_secs(1)).await,
+            Err(RaftError::StateMachineError(_))
+        ));
+        let proposal = node.propose(wal_entry(3)).await.unwrap();
+        node.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+
+        let status = node.status().await;
+        assert_eq!(status.apply_error, Some(format!("Entry {}: State machine error: refused 2", refused_proposal.index)));
+        assert_eq!(status.last_applied, proposal.index);
+        assert_eq!(machine.entries().iter().map(|e| e.id).collect::<Vec<_>>(), vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_follower_skips_an_entry_it_refuses() {
+        let network = Arc::new(sim::SimNetwork::new());
+        let members = vec!["node1".to_string(), "node2".to_string()];
+        let leader_machine = sim::MemoryStateMachine::new();
+        let follower_machine = sim::MemoryStateMachine::new();
+        let leader = Arc::new(RaftNode::new("node1", members.clone(), network.clone(), Box::new(leader_machine.clone())));
+        let follower = Arc::new(RaftNode::new(
+            "node2",
+            members,
+            network.clone(),
+            Box::new(RefusingStateMachine {
+                refused: Arc::new(std::sync::Mutex::new(HashSet::from([2]))),
+                inner: follower_machine.clone(),
+            }),
+        ));
+        network.register(&leader);
+        network.register(&follower);
+        assert!(leader.campaign().await);
+
+        let mut last = None;
+        for id in 1..=4 {
+            let proposal = leader.propose(wal_entry(id)).await.unwrap();
+            leader.wait_committed(proposal, Duration::from_secs(1)).await.unwrap();
+            last = Some(proposal);
+        }
+        // The next heartbeat carries the commit index to the follower
+        leader.tick().await;
+
+        let status = follower.status().await;
+        assert_eq!(status.last_applied, last.unwrap().index);
+        assert!(status.apply_error.is_some());
+        assert_eq!(follower_machine.entries().iter().map(|e| e.id).collect::<Vec<_>>(), vec![1, 3, 4]);
+        assert_eq!(leader_machine.entries().iter().map(|e| e.id).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_follower_keeps_its_state_when_it_cannot_be_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let raft_dir = dir.path().join("raft");
+        let members = vec!["node1".to_string(), "node2".to_string()];
+        let node = RaftNode::open(
+            &raft_dir, "node2", members, Arc::new(sim::SimNetwork::new()), Box::new(sim::MemoryStateMachine::new()),
+        ).await.unwrap();
+        let append = |term, entries| RaftRequest::AppendEntries {
+            term,
+            leader_id: "node1".to_string(),
+            prev_log_index: 1,
+            prev_log_term: 0,
+            entries,
+            leader_commit: 0,
+        };
+
+        node.handle(append(1, Vec::new())).await;
+        std::fs::remove_dir_all(&raft_dir).unwrap();
+
+        let entries = vec![LogEntry { index: 2, term: 1, command: RaftCommand::Noop }];
+        match node.handle(append(1, entries)).await {
+            RaftResponse::AppendEntries { success, .. } => assert!(!success),
+            response => panic!("unexpected {:?}", response),
+        }
+        let status = node.status().await;
+        assert_eq!(status.last_log_index, 1);
+        assert!(status.last_storage_error.is_some());
+
+        // Nor does it take a newer term it cannot store
+        let vote = RaftRequest::RequestVote {
+            term: 2,
+            candidate_id: "node1".to_string(),
+            last_log_index: 1,
+            last_log_term: 0,
+        };
+        match node.handle(vote).await {
+            RaftResponse::RequestVote { term, vote_granted } => assert_eq!((term, vote_granted), (1, false)),
+            response => panic!("unexpected {:?}", response),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cluster_manager_roles_follow_raft() {
+        let cluster = RaftCluster::new(3, RaftConfig::default());
+        let leader = cluster.wait_for_leader(100).await.unwrap();
+        cluster.tick(1).await;
+
+        let follower = cluster.nodes().iter().find(|n| n.id() != leader.id()).unwrap().clone();
+        let manager = ClusterManager::new(follower.id(), 3).with_raft(follower.clone());
+        for node in cluster.nodes() {
+            manager.add_node(ClusterNode {
+                id: node.id().to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 7000,
+                role: NodeRole::Candidate,
+                state: NodeState::Active,
+                last_heartbeat: 0,
+                shard_ids: Vec::new(),
+            }).await;
+        }
+
+        assert_eq!(manager.get_leader().await.unwrap().id, leader.id());
+        for node in manager.get_nodes().await {
+            let expected = if node.id == leader.id() { NodeRole::Leader } else { NodeRole::Follower };
+            assert_eq!(node.role, expected);
+        }
+    }
+}
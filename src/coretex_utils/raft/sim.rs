@@ -0,0 +1,217 @@
+//! In-process Raft network
+//! `SimNetwork` delivers requests by calling the target node directly and can
+//! cut links to simulate partitions; `RaftCluster` wires a set of nodes to one
+//! and drives them tick by tick.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use async_trait::async_trait;
+
+use super::{RaftConfig, RaftError, RaftNode, RaftRequest, RaftResponse, RaftStateMachine, RaftTransport};
+use crate::coretex_utils::cluster::NodeRole;
+use crate::coretex_utils::wal::WalEntry;
+
+#[derive(Default)]
+pub struct SimNetwork {
+    nodes: RwLock<HashMap<String, Weak<RaftNode>>>,
+    /// Directed links that drop every request.
+    blocked: RwLock<HashSet<(String, String)>>,
+}
+
+impl SimNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, node: &Arc<RaftNode>) {
+        self.nodes.write().unwrap().insert(node.id().to_string(), Arc::downgrade(node));
+    }
+
+    /// Cuts every link between nodes in different groups. Nodes not listed
+    /// keep their links.
+    pub fn partition(&self, groups: &[&[&str]]) {
+        let mut blocked = self.blocked.write().unwrap();
+        for (i, group) in groups.iter().enumerate() {
+            for other in groups.iter().skip(i + 1) {
+                for a in group.iter() {
+                    for b in other.iter() {
+                        blocked.insert((a.to_string(), b.to_string()));
+                        blocked.insert((b.to_string(), a.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Cuts every link to and from `node_id`.
+    pub fn isolate(&self, node_id: &str) {
+        let nodes: Vec<String> = self.nodes.read().unwrap().keys().cloned().collect();
+        let mut blocked = self.blocked.write().unwrap();
+        for other in nodes.into_iter().filter(|other| other != node_id) {
+            blocked.insert((node_id.to_string(), other.clone()));
+            blocked.insert((other, node_id.to_string()));
+        }
+    }
+
+    pub fn heal(&self) {
+        self.blocked.write().unwrap().clear();
+    }
+
+    pub fn is_connected(&self, from: &str, to: &str) -> bool {
+        from == to || !self.blocked.read().unwrap().contains(&(from.to_string(), to.to_string()))
+    }
+}
+
+#[async_trait]
+impl RaftTransport for SimNetwork {
+    async fn send(&self, from: &str, to: &str, request: RaftRequest) -> Result<RaftResponse, RaftError> {
+        if !self.is_connected(from, to) {
+            return Err(RaftError::Unreachable(to.to_string()));
+        }
+
+        let node = self.nodes.read().unwrap()
+            .get(to)
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| RaftError::Unreachable(to.to_string()))?;
+        Ok(node.handle(request).await)
+    }
+}
+
+/// Records applied entries; clones share the same list, so a test can keep
+/// one while the node owns another.
+#[derive(Clone, Default)]
+pub struct MemoryStateMachine {
+    entries: Arc<Mutex<Vec<WalEntry>>>,
+}
+
+impl MemoryStateMachine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> Vec<WalEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl RaftStateMachine for MemoryStateMachine {
+    async fn apply(&mut self, _index: u64, entry: &WalEntry) -> Result<(), RaftError> {
+        self.entries.lock().unwrap().push(entry.clone());
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> Result<Vec<u8>, RaftError> {
+        serde_json::to_vec(&*self.entries.lock().unwrap())
+            .map_err(|e| RaftError::StateMachineError(e.to_string()))
+    }
+
+    async fn restore(&mut self, data: &[u8]) -> Result<(), RaftError> {
+        let entries = serde_json::from_slice(data)
+            .map_err(|e| RaftError::StateMachineError(e.to_string()))?;
+        *self.entries.lock().unwrap() = entries;
+        Ok(())
+    }
+}
+
+/// Nodes `node1..nodeN` on one `SimNetwork`, each with a `MemoryStateMachine`.
+pub struct RaftCluster {
+    network: Arc<SimNetwork>,
+    config: RaftConfig,
+    nodes: Vec<Arc<RaftNode>>,
+    state_machines: HashMap<String, MemoryStateMachine>,
+}
+
+impl RaftCluster {
+    pub fn new(size: usize, config: RaftConfig) -> Self {
+        let mut cluster = Self {
+            network: Arc::new(SimNetwork::new()),
+            config,
+            nodes: Vec::new(),
+            state_machines: HashMap::new(),
+        };
+
+        let members: Vec<String> = (1..=size).map(|i| format!("node{}", i)).collect();
+        for id in &members {
+            cluster.spawn(id, members.clone());
+        }
+        cluster
+    }
+
+    /// Adds a node outside the membership; a leader's `add_member` brings it in.
+    pub fn add_node(&mut self, node_id: &str) -> Arc<RaftNode> {
+        self.spawn(node_id, Vec::new())
+    }
+
+    fn spawn(&mut self, node_id: &str, members: Vec<String>) -> Arc<RaftNode> {
+        let state_machine = MemoryStateMachine::new();
+        let node = Arc::new(
+            RaftNode::new(node_id, members, self.network.clone(), Box::new(state_machine.clone()))
+                .with_config(self.config.clone()),
+        );
+
+        self.network.register(&node);
+        self.state_machines.insert(node_id.to_string(), state_machine);
+        self.nodes.push(node.clone());
+        node
+    }
+
+    pub fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    pub fn nodes(&self) -> &[Arc<RaftNode>] {
+        &self.nodes
+    }
+
+    pub fn node(&self, node_id: &str) -> Option<Arc<RaftNode>> {
+        self.nodes.iter().find(|node| node.id() == node_id).cloned()
+    }
+
+    pub fn state_machine(&self, node_id: &str) -> &MemoryStateMachine {
+        &self.state_machines[node_id]
+    }
+
+    /// Ticks every node `rounds` times.
+    pub async fn tick(&self, rounds: usize) {
+        for _ in 0..rounds {
+            for node in &self.nodes {
+                node.tick().await;
+            }
+        }
+    }
+
+    /// The leader that can still reach a majority of its members. A leader
+    /// cut off by a partition keeps its role until it hears of a newer term,
+    /// so it is skipped here.
+    pub async fn leader(&self) -> Option<Arc<RaftNode>> {
+        let mut best: Option<(u64, Arc<RaftNode>)> = None;
+        for node in &self.nodes {
+            let status = node.status().await;
+            if status.role != NodeRole::Leader {
+                continue;
+            }
+
+            let reachable = status.members.iter()
+                .filter(|member| self.network.is_connected(node.id(), member))
+                .count();
+            if reachable < status.members.len() / 2 + 1 {
+                continue;
+            }
+            if best.as_ref().is_none_or(|(term, _)| status.term > *term) {
+                best = Some((status.term, node.clone()));
+            }
+        }
+        best.map(|(_, node)| node)
+    }
+
+    pub async fn wait_for_leader(&self, max_rounds: usize) -> Option<Arc<RaftNode>> {
+        for _ in 0..max_rounds {
+            if let Some(leader) = self.leader().await {
+                return Some(leader);
+            }
+            self.tick(1).await;
+        }
+        None
+    }
+}
@@ -0,0 +1,152 @@
+//! Durable Raft state
+//! `raft.state` holds the current term and vote, `raft.snapshot` the latest
+//! snapshot and `raft.log` the entries after it, one JSON document per line.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use super::{LogEntry, RaftError, RaftSnapshot};
+use crate::coretex_journal::{append_json_lines, read_json_lines, write_durably, write_json_lines};
+
+const HARD_STATE_FILE: &str = "raft.state";
+const SNAPSHOT_FILE: &str = "raft.snapshot";
+const LOG_FILE: &str = "raft.log";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct HardState {
+    pub(crate) term: u64,
+    pub(crate) voted_for: Option<String>,
+}
+
+pub(crate) struct Recovered {
+    pub(crate) hard_state: HardState,
+    pub(crate) snapshot: Option<RaftSnapshot>,
+    pub(crate) entries: Vec<LogEntry>,
+}
+
+pub(crate) struct RaftStorage {
+    dir: PathBuf,
+}
+
+impl RaftStorage {
+    pub(crate) fn open(dir: &Path) -> Result<(Self, Recovered), RaftError> {
+        fs::create_dir_all(dir).map_err(storage_error)?;
+        let storage = Self { dir: dir.to_path_buf() };
+
+        let hard_state = match fs::read(storage.path(HARD_STATE_FILE)) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| RaftError::Corrupted(format!("hard state: {}", e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HardState::default(),
+            Err(e) => return Err(storage_error(e)),
+        };
+
+        let snapshot = match fs::read(storage.path(SNAPSHOT_FILE)) {
+            Ok(data) => Some(bincode::deserialize(&data)
+                .map_err(|e| RaftError::Corrupted(format!("snapshot: {}", e)))?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(storage_error(e)),
+        };
+
+        let (mut entries, torn) = storage.read_log()?;
+        // A crash between saving a snapshot and compacting the log leaves
+        // entries the snapshot covers
+        let compacted = snapshot.as_ref().map_or(0, |snapshot: &RaftSnapshot| snapshot.last_index);
+        let stale = entries.iter().any(|entry| entry.index <= compacted);
+        entries.retain(|entry| entry.index > compacted);
+        // Appends must start on a line of their own
+        if torn || stale {
+            storage.rewrite_log(&entries)?;
+        }
+        Ok((storage, Recovered { hard_state, snapshot, entries }))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    fn read_log(&self) -> Result<(Vec<LogEntry>, bool), RaftError> {
+        read_json_lines(&self.path(LOG_FILE)).map_err(|e| match e.kind() {
+            std::io::ErrorKind::InvalidData => RaftError::Corrupted(format!("log {}", e)),
+            _ => storage_error(e),
+        })
+    }
+
+    pub(crate) fn save_hard_state(&self, hard_state: &HardState) -> Result<(), RaftError> {
+        let data = serde_json::to_vec(hard_state).map_err(|e| RaftError::StorageError(e.to_string()))?;
+        write_durably(&self.path(HARD_STATE_FILE), &data).map_err(storage_error)
+    }
+
+    pub(crate) fn append(&self, entries: &[LogEntry]) -> Result<(), RaftError> {
+        append_json_lines(&self.path(LOG_FILE), entries).map_err(storage_error)
+    }
+
+    /// Replaces the whole log, after a conflict truncated it or a snapshot
+    /// compacted it.
+    pub(crate) fn rewrite_log(&self, entries: &[LogEntry]) -> Result<(), RaftError> {
+        write_json_lines(&self.path(LOG_FILE), entries).map_err(storage_error)
+    }
+
+    pub(crate) fn save_snapshot(&self, snapshot: &RaftSnapshot, entries: &[LogEntry]) -> Result<(), RaftError> {
+        let data = bincode::serialize(snapshot).map_err(|e| RaftError::StorageError(e.to_string()))?;
+        write_durably(&self.path(SNAPSHOT_FILE), &data).map_err(storage_error)?;
+        self.rewrite_log(entries)
+    }
+}
+
+fn storage_error(e: std::io::Error) -> RaftError {
+    RaftError::StorageError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::RaftCommand;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn entry(index: u64, term: u64) -> LogEntry {
+        LogEntry { index, term, command: RaftCommand::Noop }
+    }
+
+    #[test]
+    fn test_raft_storage_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (storage, recovered) = RaftStorage::open(dir.path()).unwrap();
+            assert_eq!(recovered.hard_state.term, 0);
+            assert!(recovered.snapshot.is_none());
+            assert!(recovered.entries.is_empty());
+
+            storage.save_hard_state(&HardState { term: 3, voted_for: Some("node2".to_string()) }).unwrap();
+            storage.append(&[entry(1, 1), entry(2, 1)]).unwrap();
+            storage.append(&[entry(3, 3)]).unwrap();
+            storage.save_snapshot(&RaftSnapshot {
+                last_index: 2,
+                last_term: 1,
+                members: vec!["node1".to_string(), "node2".to_string()],
+                data: vec![1, 2, 3],
+            }, &[entry(3, 3)]).unwrap();
+        }
+
+        // Simulate a crash halfway through appending
+        let mut log = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        log.write_all(b"{\"index\":4,\"te").unwrap();
+
+        let (_, recovered) = RaftStorage::open(dir.path()).unwrap();
+        assert_eq!(recovered.hard_state.term, 3);
+        assert_eq!(recovered.hard_state.voted_for.as_deref(), Some("node2"));
+        let snapshot = recovered.snapshot.unwrap();
+        assert_eq!((snapshot.last_index, snapshot.last_term), (2, 1));
+        assert_eq!(snapshot.data, vec![1, 2, 3]);
+        assert_eq!(recovered.entries.len(), 1);
+        assert_eq!((recovered.entries[0].index, recovered.entries[0].term), (3, 3));
+
+        // The torn line is gone, so the next append is read back
+        let (storage, _) = RaftStorage::open(dir.path()).unwrap();
+        storage.append(&[entry(4, 3)]).unwrap();
+        let (_, recovered) = RaftStorage::open(dir.path()).unwrap();
+        assert_eq!(recovered.entries.iter().map(|e| e.index).collect::<Vec<_>>(), vec![3, 4]);
+    }
+}
@@ -3,7 +3,7 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncWriteExt, AsyncReadExt, AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncWriteExt, AsyncReadExt};
 use std::path::PathBuf;
 use std::collections::VecDeque;
 use serde::{Serialize, Deserialize};
@@ -16,6 +16,8 @@ pub enum WalEntryType {
     Delete,
     CreateCollection,
     DeleteCollection,
+    /// Entries under `data.entries` that apply together or not at all.
+    Transaction,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +29,28 @@ pub struct WalEntry {
     pub data: serde_json::Value,
 }
 
+impl WalEntry {
+    /// The entries a `Transaction` groups, sharing its id and timestamp, or
+    /// the entry itself.
+    pub fn parts(&self) -> Vec<WalEntry> {
+        if !matches!(self.entry_type, WalEntryType::Transaction) {
+            return vec![self.clone()];
+        }
+
+        self.data["entries"].as_array()
+            .map(|parts| parts.iter()
+                .filter_map(|part| Some(WalEntry {
+                    id: self.id,
+                    timestamp: self.timestamp,
+                    entry_type: serde_json::from_value(part["entry_type"].clone()).ok()?,
+                    collection: part["collection"].as_str()?.to_string(),
+                    data: part["data"].clone(),
+                }))
+                .collect())
+            .unwrap_or_default()
+    }
+}
+
 pub struct WriteAheadLog {
     log_dir: PathBuf,
     current_file: PathBuf,
@@ -49,23 +73,209 @@ impl WriteAheadLog {
         }
     }
 
-    pub async fn init(&self) -> std::io::Result<()> {
+    pub async fn init(&mut self) -> std::io::Result<()> {
         tokio::fs::create_dir_all(&self.log_dir).await?;
+
+        // Appends go to the newest segment, not back to `wal.log`
+        if let Some(latest) = self.segment_files().await?.pop() {
+            self.current_file = latest;
+        }
         
         if !self.current_file.exists() {
             let file = File::create(&self.current_file).await?;
             file.sync_all().await?;
         }
+        Self::truncate_torn_tail(&self.current_file).await?;
+
+        // Resume LSNs after the highest entry already on disk so that ids stay
+        // monotonic across restarts and can be used as recovery targets.
+        let mut max_id = 0;
+        for segment in self.segment_files().await? {
+            for entry in Self::read_segment(&segment).await? {
+                max_id = max_id.max(entry.id);
+            }
+        }
+        *self.entry_counter.write().await = max_id;
+
+        if let Ok(meta) = tokio::fs::metadata(&self.current_file).await {
+            *self.current_size.write().await = meta.len();
+        }
         
         Ok(())
     }
 
+    /// Cuts a half-written last line left by a crash, so the next entry
+    /// starts on a line of its own instead of being glued to the torn one.
+    async fn truncate_torn_tail(path: &std::path::Path) -> std::io::Result<()> {
+        let content = tokio::fs::read(path).await?;
+        if content.last().is_none_or(|b| *b == b'\n') {
+            return Ok(());
+        }
+
+        let keep = content.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        let file = OpenOptions::new().write(true).open(path).await?;
+        file.set_len(keep as u64).await?;
+        file.sync_all().await
+    }
+
+    /// Closes segments once they reach `bytes` instead of 64 MiB.
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    pub fn log_dir(&self) -> &PathBuf {
+        &self.log_dir
+    }
+
+    /// The segment appends go to.
+    pub fn current_segment(&self) -> &PathBuf {
+        &self.current_file
+    }
+
+    /// Numbers the next entry after `lsn` if the log ends before it, e.g.
+    /// when the segments holding it were dropped by a checkpoint.
+    pub async fn resume_after(&self, lsn: u64) {
+        let mut counter = self.entry_counter.write().await;
+        *counter = (*counter).max(lsn);
+    }
+
+    pub async fn last_lsn(&self) -> u64 {
+        *self.entry_counter.read().await
+    }
+
+    /// Lists every WAL segment in the log directory, oldest first.
+    pub async fn segment_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut segments = Vec::new();
+
+        if !self.log_dir.exists() {
+            return Ok(segments);
+        }
+
+        let mut entries = tokio::fs::read_dir(&self.log_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if Self::is_segment(&path) {
+                segments.push(path);
+            }
+        }
+
+        segments.sort_by_key(|p| Self::segment_order(p));
+        Ok(segments)
+    }
+
+    pub fn is_segment(path: &std::path::Path) -> bool {
+        path.is_file()
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("wal") && n.ends_with(".log"))
+                .unwrap_or(false)
+    }
+
+    /// `wal.log` is the first segment, rotated segments are `wal_<millis>.log`
+    /// (`wal_<secs>.log` before).
+    fn segment_order(path: &std::path::Path) -> u64 {
+        path.file_stem()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("wal_"))
+            .and_then(|ts| ts.parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub async fn read_segment(path: &std::path::Path) -> std::io::Result<Vec<WalEntry>> {
+        Ok(Self::parse_segment(&tokio::fs::read(path).await?))
+    }
+
+    /// Parses a segment's content, skipping lines that are not entries.
+    pub fn parse_segment(content: &[u8]) -> Vec<WalEntry> {
+        content.split(|b| *b == b'\n')
+            .filter_map(|line| serde_json::from_slice::<WalEntry>(line).ok())
+            .collect()
+    }
+
+    /// Serializes entries in the line format `parse_segment` reads.
+    pub fn serialize_segment(entries: &[WalEntry]) -> std::io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        for entry in entries {
+            content.extend(serde_json::to_vec(entry)?);
+            content.push(b'\n');
+        }
+        Ok(content)
+    }
+
+    /// Replaces a segment's entries, writing a temporary file first so a
+    /// crash leaves either the old or the new segment.
+    pub async fn write_segment(path: &std::path::Path, entries: &[WalEntry]) -> std::io::Result<()> {
+        let content = Self::serialize_segment(entries)?;
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || crate::coretex_journal::write_durably(&path, &content))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    /// Drops every entry after `lsn`, so the log ends at it and the next
+    /// entry is numbered `lsn + 1`. Segments left empty are removed.
+    /// Returns the number of entries dropped.
+    pub async fn truncate_after(&mut self, lsn: u64) -> std::io::Result<usize> {
+        let mut dropped = 0;
+        for segment in self.segment_files().await? {
+            let entries = Self::read_segment(&segment).await?;
+            let kept: Vec<WalEntry> = entries.iter().filter(|e| e.id <= lsn).cloned().collect();
+            if kept.len() == entries.len() {
+                continue;
+            }
+
+            dropped += entries.len() - kept.len();
+            if kept.is_empty() {
+                tokio::fs::remove_file(&segment).await?;
+                let segment = segment.clone();
+                tokio::task::spawn_blocking(move || crate::coretex_journal::sync_parent(&segment))
+                    .await
+                    .map_err(std::io::Error::other)??;
+            } else {
+                Self::write_segment(&segment, &kept).await?;
+            }
+        }
+
+        self.current_file = self.log_dir.join("wal.log");
+        self.init().await?;
+        Ok(dropped)
+    }
+
+    /// Removes the closed segments whose entries all have an LSN of at most
+    /// `lsn`, oldest first, stopping at the first one that holds a later
+    /// entry. Returns the number of segments removed.
+    pub async fn drop_segments_through(&mut self, lsn: u64) -> std::io::Result<usize> {
+        self.drop_segments_while(|entry| entry.id <= lsn).await
+    }
+
+    /// Removes the closed segments whose entries all pass `droppable`,
+    /// oldest first, stopping at the first one that holds an entry that
+    /// does not. Reads only the segments it removes and the one it stops
+    /// at. Returns the number of segments removed.
+    pub async fn drop_segments_while(&mut self, droppable: impl Fn(&WalEntry) -> bool) -> std::io::Result<usize> {
+        let mut dropped = 0;
+        for segment in self.segment_files().await? {
+            if segment == self.current_file {
+                break;
+            }
+            let entries = Self::read_segment(&segment).await?;
+            if !entries.iter().all(&droppable) {
+                break;
+            }
+            tokio::fs::remove_file(&segment).await?;
+            dropped += 1;
+        }
+        Ok(dropped)
+    }
+
     pub async fn append(&mut self, entry: &WalEntry) -> std::io::Result<()> {
         let serialized = serde_json::to_vec(entry)?;
         let entry_size = serialized.len() as u64;
         
         let mut current_size = self.current_size.write().await;
-        if *current_size + entry_size > self.max_file_size {
+        if *current_size > 0 && *current_size + entry_size > self.max_file_size {
             drop(current_size);
             self.rotate().await?;
             current_size = self.current_size.write().await;
@@ -86,10 +296,13 @@ impl WriteAheadLog {
     }
 
     async fn rotate(&mut self) -> std::io::Result<()> {
+        // Milliseconds, and past the current segment, so segments closed
+        // within the same second still sort in order
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
-            .as_secs();
+            .as_millis() as u64;
+        let timestamp = timestamp.max(Self::segment_order(&self.current_file) + 1);
         
         let new_file = self.log_dir.join(format!("wal_{}.log", timestamp));
         
@@ -107,21 +320,19 @@ impl WriteAheadLog {
         Ok(())
     }
 
-    pub async fn read_entries(&self) -> std::io::Result<Vec<WalEntry>> {
-        let file = File::open(&self.current_file).await?;
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
+    /// Entries of every segment, oldest first.
+    pub async fn read_all(&self) -> std::io::Result<Vec<WalEntry>> {
         let mut entries = Vec::new();
-        
-        while let Ok(Some(line)) = lines.next_line().await {
-            if let Ok(entry) = serde_json::from_str::<WalEntry>(&line) {
-                entries.push(entry);
-            }
+        for segment in self.segment_files().await? {
+            entries.extend(Self::read_segment(&segment).await?);
         }
-        
         Ok(entries)
     }
 
+    pub async fn read_entries(&self) -> std::io::Result<Vec<WalEntry>> {
+        Self::read_segment(&self.current_file).await
+    }
+
     pub async fn replay(&self, handler: &impl Fn(WalEntry)) -> std::io::Result<()> {
         let entries = self.read_entries().await?;
         
@@ -1,138 +1,212 @@
 //! CoreTexDB - A multimodal vector database for AI applications 
 
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+/// Items of the server, built for native targets with the `server`
+/// feature; the wasm32 build and the C library carry just the EdgeDB
+/// (`coretex_edge`).
+macro_rules! native {
+    ($($item:item)*) => {
+        $(
+            #[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+            $item
+        )*
+    };
+}
+
+native! {
+    use std::collections::{BTreeMap, HashMap};
+    use std::ops::Bound;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use coretex_utils::wal::WalEntryType;
+}
 use serde::{Deserialize, Serialize};
 
 pub const DB_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub mod coretex_core; 
-pub mod coretex_storage; 
-pub mod coretex_index; 
-pub mod coretex_query; 
-pub mod coretex_api; 
-pub mod coretex_cli; 
-pub mod coretex_utils; 
-pub mod coretex_embedding; 
-pub mod coretex_grpc;
-pub mod coretex_gis;
-pub mod coretex_timeseries;
-pub mod coretex_export;
-pub mod coretex_ann;
-pub mod coretex_distributed;
-pub mod coretex_auth;
-pub mod coretex_monitoring;
-pub mod coretex_sql;
-pub mod coretex_compression;
-pub mod coretex_security; 
-#[cfg(feature = "python")]
-pub mod coretex_python;
-pub mod coretex_onnx;
-pub mod coretex_bm25;
-pub mod coretex_incremental;
-pub mod coretex_cdc;
-pub mod coretex_transaction;
+native! {
+    pub mod coretex_core;
+    pub mod coretex_storage;
+    pub mod coretex_index;
+    pub mod coretex_query;
+    pub mod coretex_api;
+    pub mod coretex_cli;
+    pub mod coretex_utils;
+    pub mod coretex_embedding;
+    pub mod coretex_grpc;
+    pub mod coretex_gis;
+    pub mod coretex_timeseries;
+    pub mod coretex_export;
+    pub mod coretex_ann;
+    pub mod coretex_distributed;
+    pub mod coretex_auth;
+    pub mod coretex_monitoring;
+    pub mod coretex_sql;
+    pub mod coretex_compression;
+    pub mod coretex_security;
+    #[cfg(feature = "python")]
+    pub mod coretex_python;
+    pub mod coretex_onnx;
+    pub mod coretex_bm25;
+    pub mod coretex_incremental;
+    pub mod coretex_cdc;
+    pub mod coretex_transaction;
+    pub mod coretex_simd;
+    pub mod coretex_websocket;
+    pub mod coretex_failover;
+    pub mod coretex_permissions;
+    pub mod coretex_tracing;
+    pub mod coretex_persistence;
+    pub mod coretex_backup;
+    pub mod coretex_monitoring_v2;
+    // pub mod coretex_tantivy;
+    pub mod coretex_graph;
+    pub mod coretex_hybrid;
+    pub mod coretex_rerank;
+    pub mod coretex_lakehouse;
+    pub mod coretex_document;
+    pub mod coretex_fields;
+    pub mod coretex_sparse;
+    pub mod coretex_multivector;
+    pub mod coretex_journal;
+    pub mod coretex_side_vectors;
+    pub mod coretex_tiering;
+
+    #[cfg(test)]
+    mod coretex_bm25_tests;
+    #[cfg(test)]
+    mod coretex_security_tests;
+    #[cfg(test)]
+    mod coretex_transaction_tests;
+    #[cfg(test)]
+    mod coretex_embedding_tests;
+}
 pub mod coretex_edge;
-pub mod coretex_simd;
-pub mod coretex_websocket;
-pub mod coretex_failover;
-pub mod coretex_permissions;
-pub mod coretex_tracing;
-pub mod coretex_persistence;
-pub mod coretex_backup;
-pub mod coretex_monitoring_v2;
-// pub mod coretex_tantivy;
-pub mod coretex_graph;
-pub mod coretex_hybrid;
-pub mod coretex_rerank;
-pub mod coretex_lakehouse;
-pub mod coretex_document;
-
-#[cfg(test)]
-mod coretex_bm25_tests;
-#[cfg(test)]
-mod coretex_security_tests;
-#[cfg(test)]
-mod coretex_transaction_tests;
-#[cfg(test)]
-mod coretex_embedding_tests;
-
-#[cfg(feature = "python")]
-pub use coretex_python::{PyCortexDB, PySearchResult, PyCollectionInfo, PyCoreTexError};
-pub use coretex_incremental::{IncrementalIndex, IndexUpdate};
-pub use coretex_cdc::{CdcEngine, CdcEvent, CdcConfig};
-pub use coretex_transaction::{TransactionManager, TransactionId, Snapshot, WriteAheadLog};
-pub use coretex_edge::{EdgeDB, EdgeConfig, EdgeStats, EdgeSearchResult}; 
-
-pub use coretex_core::{Vector, Document, CollectionSchema, IndexConfig, IndexType, CoreTexError, Result};
-pub use coretex_storage::{StorageEngine, MemoryStorage};
-#[cfg(feature = "rocksdb")]
-pub use coretex_storage::PersistentStorage; 
-pub use coretex_index::{VectorIndex, BruteForceIndex, IndexManager, SearchResult, HNSWIndex, IVFIndex, ScalarIndex}; 
-pub use coretex_query::{QueryType, QueryParams, QueryResult as CoreTexQueryResult, DefaultQueryProcessor, QueryPlanner, QueryItem}; 
-pub use coretex_bm25::{BM25Index, BM25Result, HybridQueryEngine, HybridSearchResult, MetadataFilter, FilterCondition}; 
-pub use coretex_api::rest::{start_server, ApiConfig};
-pub use coretex_api::graphql::{GraphQLExecutor, GraphQLServer, GraphQLRequest, GraphQLResponse}; 
-pub use coretex_cli::run_cli; 
-pub use coretex_utils::{
-    LockManager, Transaction, TransactionOperation, TransactionState,
-    ClusterManager, ClusterNode, NodeRole, NodeState, Shard,
-    BackupManager, MonitoringService, Metrics,
-    cosine_similarity, euclidean_distance, normalize_vector, parse_vector, random_vector,
-    LRUCache, TimedLRUCache, AsyncLRUCache, MultiLevelCache, CacheStats, MultiLevelCacheStats
-}; 
-pub use coretex_embedding::{
-    TextEmbeddingService, ImageEmbeddingService, AudioEmbeddingService, 
-    VideoEmbeddingService, PointCloudEmbeddingService, EmbeddingRouter,
-    EmbeddingRequest, EmbeddingResponse, DataType, EmbeddingConfig,
-    StreamingEmbedder, StreamItem, StreamResult, EmbeddingStream, StreamingStats,
-    BatchedStreamEmbedder, WindowedStreamEmbedder, BackpressureStreamEmbedder, BackpressureSignal
-}; 
-pub use coretex_grpc::{CoretexService, start_grpc_server}; 
-pub use coretex_gis::{GeoIndex, GeoPoint, GeoBoundingBox, GeoPolygon, GeoLineString, GeoQuery}; 
-pub use coretex_timeseries::{TimeSeriesIndex, TimeSeries, TimeSeriesPoint, TimeSeriesStats, Aggregation, RollingWindow, ExponentialMovingAverage};
-pub use coretex_export::{DataExporter, VectorExporter, BatchExporter, CollectionExporter, ExportResult, ExportFormat};
-pub use coretex_ann::{ANNConfig, ANNAlgorithm, ANNParameters, HNSWParameters, IVFParameters, PQParameters, NSGParameters, SearchParameters, ANNTuner, IndexOptimizer, PerformanceRecord};
-pub use coretex_distributed::{TwoPhaseCommit, DistributedTransaction, DistributedOperation, DistributedTransactionState, TransactionCoordinator, DistributedLockManager, DistributedLock, ParticipantState, ParticipantStatus};
-pub use coretex_auth::{AuthService, User, Role, Permission, JWTConfig, TokenClaims, AuthToken, UserInfo, RateLimiter};
-pub use coretex_monitoring::{PrometheusMetrics, DatabaseMetrics, AlertManager, AlertRule, AlertCondition, AlertSeverity, Alert, GrafanaConfig, GrafanaClient};
-pub use coretex_sql::{SQLExecutor, SQLStatement, SQLSelect, SQLInsert, SQLDelete, SQLResult, SQLValue, SQLLexer, SQLParser};
-pub use coretex_compression::{VectorCompressor, CompressedVector, CompressionAlgorithm, CompressionStats, RunLengthEncoding, DeltaCoding, QuantizationCompressor};
-pub use coretex_security::{TlsConfig, TlsServer, TlsClient, EncryptionService, EncryptedData, EncryptionKey, KeyManager, AuditLogger, AuditEvent, AuditLevel, AuditAction, ACLEngine, ACLPolicy, Subject, SubjectType, Resource, ResourceType, Action, Effect, ACLValidator, VaultKMS, KMSConfig, KMSProvider, ExternalKey, KeyRotationManager, InputValidator, RateLimitValidator, NetworkIsolation, NetworkPolicy, IpRange, PolicyAction, IPRangeManager}; 
-pub use coretex_simd::{simd_utils, SimdCapabilities};
-pub use coretex_websocket::{WebSocketServer, WebSocketClient, WebSocketConfig, WebSocketMessage, WebSocketStats}; 
-// pub use coretex_tantivy::{TantivySearcher, TantivyDocumentResult};
-pub use coretex_graph::{GraphDatabase, GraphNode, GraphEdge, GraphPath, GraphError};
-pub use coretex_hybrid::{
-    MultiModalDocument, VectorData, TextData, ScalarValue, TimeSeriesData, GeoLocation,
-    HybridQuery, VectorQuery, TextQuery, ScalarFilter, FilterOperator, QueryWeights, DistanceMetric,
-    ScoreFusion, ScoreFusionEngine, MultiModalResult, FusedResult,
-    HybridRetriever, VectorRetriever, TextRetriever,
-};
-pub use coretex_rerank::{
-    CoarseRanker, CoarseRankerConfig, CoarseResult,
-    FineRanker, FineRankerConfig, FineResult, RerankDocument, RerankModel, TwoStageSearchPipeline,
-};
-pub use coretex_lakehouse::{
-    StorageTier, TierConfig, DocumentMeta,
-    VectorLakehouse, MigrationReport, LakehouseStats,
-    LRUTieringPolicy, TTLTieringPolicy, HybridTieringPolicy,
-};
-pub use coretex_document::{
-    ParsedDocument, ImageData, TableData,
-    DocumentParser, DocumentParserRegistry, PdfParser, ImageParser, AudioParser,
-    HighDimVector, HighDimVectorStore, PQCompressor,
-}; 
 
+native! {
+    #[cfg(feature = "python")]
+    pub use coretex_python::{PyCortexDB, PySearchResult, PyCollectionInfo, PyCoreTexError};
+    pub use coretex_incremental::{IncrementalIndex, IndexUpdate, IndexBuilder, IndexMaintainer, MaintainerConfig, MaintenanceStats, MaintenanceReport};
+    pub use coretex_cdc::{CdcEngine, CdcEvent, CdcConfig};
+    pub use coretex_transaction::{TransactionManager, TransactionId, Snapshot, SnapshotId, WriteAheadLog, DbTransaction, DbTransactions,
+        VersionStore, VersionConfig, AsOf};
+
+    pub use coretex_core::{Vector, Document, CollectionSchema, IndexConfig, IndexType, VectorField, MultiVectorConfig, CoreTexError, Result};
+    pub use coretex_storage::{StorageEngine, MemoryStorage, FileStorage};
+    #[cfg(feature = "rocksdb")]
+    pub use coretex_storage::PersistentStorage;
+    pub use coretex_index::{VectorIndex, BruteForceIndex, IndexManager, SearchResult, HNSWIndex, IVFIndex, ScalarIndex, RebuildProgress, RebuildStatus};
+    pub use coretex_query::{QueryType, QueryParams, QueryResult as CoreTexQueryResult, DefaultQueryProcessor, QueryPlanner, QueryItem};
+    pub use coretex_bm25::{BM25Index, BM25Result, HybridQueryEngine, HybridSearchResult, MetadataFilter, FilterCondition};
+    pub use coretex_api::rest::{start_server, ApiConfig, LakehouseConfig, ShardsConfig};
+    pub use coretex_api::graphql::{GraphQLExecutor, GraphQLServer, GraphQLRequest, GraphQLResponse};
+    pub use coretex_cli::run_cli;
+    pub use coretex_utils::{
+        LockManager, Transaction, TransactionOperation, TransactionState,
+        ClusterManager, ClusterNode, NodeRole, NodeState, Shard,
+        RaftNode, RaftConfig, RaftError, RaftStatus, RaftTransport, RaftStateMachine,
+        BackupManager, MonitoringService, Metrics,
+        cosine_similarity, euclidean_distance, normalize_vector, parse_vector, random_vector,
+        LRUCache, TimedLRUCache, AsyncLRUCache, MultiLevelCache, CacheStats, MultiLevelCacheStats
+    };
+    pub use coretex_embedding::{
+        TextEmbeddingService, ImageEmbeddingService, AudioEmbeddingService,
+        VideoEmbeddingService, PointCloudEmbeddingService, EmbeddingRouter,
+        EmbeddingRequest, EmbeddingResponse, DataType, EmbeddingConfig,
+        StreamingEmbedder, StreamItem, StreamResult, EmbeddingStream, StreamingStats,
+        BatchedStreamEmbedder, WindowedStreamEmbedder, BackpressureStreamEmbedder, BackpressureSignal
+    };
+    pub use coretex_grpc::{CoretexService, start_grpc_server};
+    pub use coretex_gis::{GeoIndex, GeoPoint, GeoBoundingBox, GeoPolygon, GeoLineString, GeoQuery};
+    pub use coretex_timeseries::{TimeSeriesIndex, TimeSeries, TimeSeriesPoint, TimeSeriesStats, Aggregation, RollingWindow, ExponentialMovingAverage};
+    pub use coretex_export::{DataExporter, VectorExporter, BatchExporter, CollectionExporter, ExportResult, ExportFormat};
+    pub use coretex_ann::{ANNConfig, ANNAlgorithm, ANNParameters, HNSWParameters, IVFParameters, PQParameters, NSGParameters, SearchParameters, ANNTuner, IndexOptimizer, PerformanceRecord};
+    pub use coretex_distributed::{TwoPhaseCommit, DistributedTransaction, DistributedOperation, DistributedTransactionState, TransactionCoordinator, DistributedLockManager, DistributedLock, ParticipantState, ParticipantStatus,
+        ShardCoordinator, ShardingConfig, ShardingStrategy, ShardedSearchResult, ShardClient, LocalShardClient, HttpShardClient,
+        RebalanceConfig, ShardMove, MigrationState, MigrationProgress, RebalanceProgress, RebalanceReport,
+        ReplicationLeader, ReplicaNode, ReplicaClient, HttpReplicaClient, ReplicationConfig, ReplicationRole, ReplicationStatus, ReplicaLag, ReadPreference, RoutedSearch,
+        TwoPhaseNode, TwoPhaseConfig, TwoPhaseError, TwoPhaseTransport, HttpTwoPhaseTransport, TwoPhaseStatus,
+        LockService, LockTable, LockConfig, LockError, LockGuard, Lease, FencingToken, Fence, HttpLockTransport,
+        HttpRaftTransport, DbStateMachine};
+    pub use coretex_auth::{AuthService, User, Role, Permission, JWTConfig, TokenClaims, AuthToken, UserInfo, RateLimiter};
+    pub use coretex_monitoring::{PrometheusMetrics, DatabaseMetrics, AlertManager, AlertRule, AlertCondition, AlertSeverity, Alert, GrafanaConfig, GrafanaClient};
+    pub use coretex_sql::{SQLExecutor, SQLStatement, SQLSelect, SQLInsert, SQLDelete, SQLResult, SQLValue, SQLLexer, SQLParser};
+    pub use coretex_compression::{VectorCompressor, CompressedVector, CompressionAlgorithm, CompressionStats, RunLengthEncoding, DeltaCoding, QuantizationCompressor};
+    pub use coretex_security::{TlsConfig, TlsServer, TlsClient, EncryptionService, EncryptedData, EncryptionKey, KeyManager, AuditLogger, AuditEvent, AuditLevel, AuditAction, ACLEngine, ACLPolicy, Subject, SubjectType, Resource, ResourceType, Action, Effect, ACLValidator, VaultKMS, KMSConfig, KMSProvider, ExternalKey, KeyRotationManager, InputValidator, RateLimitValidator, NetworkIsolation, NetworkPolicy, IpRange, PolicyAction, IPRangeManager};
+    pub use coretex_simd::{simd_utils, SimdCapabilities};
+    pub use coretex_websocket::{WebSocketServer, WebSocketClient, WebSocketConfig, WebSocketMessage, WebSocketStats};
+    // pub use coretex_tantivy::{TantivySearcher, TantivyDocumentResult};
+    pub use coretex_graph::{GraphDatabase, GraphNode, GraphEdge, GraphPath, GraphError};
+    pub use coretex_hybrid::{
+        MultiModalDocument, VectorData, TextData, ScalarValue, TimeSeriesData, GeoLocation,
+        HybridQuery, VectorQuery, TextQuery, ScalarFilter, FilterOperator, QueryWeights, DistanceMetric,
+        ScoreFusion, ScoreFusionEngine, MultiModalResult, FusedResult,
+        HybridRetriever, VectorRetriever, TextRetriever,
+    };
+    pub use coretex_rerank::{
+        CoarseRanker, CoarseRankerConfig, CoarseResult,
+        FineRanker, FineRankerConfig, FineResult, RerankDocument, RerankModel, TwoStageSearchPipeline,
+    };
+    pub use coretex_lakehouse::{
+        StorageTier, TierConfig, DocumentMeta,
+        VectorLakehouse, MigrationReport, LakehouseStats,
+        TieringDaemon, TieringDaemonConfig,
+        LRUTieringPolicy, TTLTieringPolicy, HybridTieringPolicy,
+        TieredSearchOptions, TieredSearchResult,
+    };
+    pub use coretex_document::{
+        ParsedDocument, ImageData, TableData,
+        DocumentParser, DocumentParserRegistry, PdfParser, ImageParser, AudioParser,
+        HighDimVector, HighDimVectorStore, PQCompressor,
+    };
+    pub use coretex_fields::{FieldQuery, NamedVectorStore, DEFAULT_VECTOR_FIELD};
+    pub use coretex_sparse::{SparseVector, SparseIndex, SparseSearchResult, SparseVectorStore, SparseError};
+    pub use coretex_multivector::{MultiVectorStore, MultiVectorSearchResult, max_sim};
+    pub use coretex_journal::Journal;
+    pub use coretex_side_vectors::SideVectors;
+    pub use coretex_tiering::{LakehouseFeed, TieredOut};
+}
+pub use coretex_edge::{EdgeDB, EdgeConfig, EdgeStats, EdgeSearchResult, EdgeSyncClient, SyncTransport};
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+pub use coretex_edge::{SyncServer, HttpSyncTransport};
+
+/// Vectors backfilled per hold of the data lock during an index rebuild
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+const INDEX_REBUILD_BATCH_SIZE: usize = 1000;
+
+/// A collection's documents by id, in id order so scans can page through
+/// them without sorting.
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+pub type CollectionData = BTreeMap<String, (Vec<f32>, serde_json::Value)>;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
 pub struct CoreTexDB {
     pub storage: Arc<RwLock<Box<dyn StorageEngine>>>,
     pub index_manager: Arc<IndexManager>,
     pub collections: Arc<RwLock<HashMap<String, CollectionSchema>>>,
-    pub data: Arc<RwLock<HashMap<String, HashMap<String, (Vec<f32>, serde_json::Value)>>>>,
+    pub data: Arc<RwLock<HashMap<String, CollectionData>>>,
     pub config: DbConfig,
+    /// Tiered copies of the vectors written, moved between hot, warm and
+    /// cold storage by its tiering daemon
+    pub lakehouse: Option<Arc<VectorLakehouse>>,
+    /// Copies applied writes to `lakehouse` in the background
+    pub lakehouse_feed: Option<LakehouseFeed>,
+    /// Documents in storage but not in `data`, as the lakehouse moved them
+    /// below its hot tier
+    pub tiered_out: Arc<std::sync::RwLock<TieredOut>>,
+    /// Open transactions from `begin`
+    pub transactions: Arc<DbTransactions>,
+    /// Earlier versions of vectors, for transactions and `as_of` reads
+    pub versions: Arc<VersionStore>,
+    /// Vectors of the collections' named vector fields
+    pub named_vectors: Arc<NamedVectorStore>,
+    /// Sparse vectors of the collections' documents
+    pub sparse_vectors: Arc<SparseVectorStore>,
+    /// Token vectors of the collections' multi-vector documents
+    pub multi_vectors: Arc<MultiVectorStore>,
+    /// Write-ahead log of every change, kept by databases from `open`
+    pub journal: Arc<Journal>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -144,8 +218,33 @@ pub struct DbConfig {
     pub backup_dir: String,
     pub include_dir: String,
     pub memory_only: bool,
+    /// Keeps documents in files under `data_dir` instead of in memory;
+    /// `CoreTexDB::open` always does
+    #[serde(default)]
+    pub file_storage: bool,
     pub max_vectors_per_collection: usize,
     pub create_dirs_on_init: bool,
+    /// How long overwritten and deleted vectors stay readable with `as_of`
+    #[serde(default = "default_version_retention_ms")]
+    pub version_retention_ms: u64,
+    /// Size at which a journal segment is closed and a checkpoint runs
+    #[serde(default = "default_wal_segment_bytes")]
+    pub wal_segment_bytes: u64,
+}
+
+fn default_wal_segment_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+fn default_version_retention_ms() -> u64 {
+    VersionConfig::default().retention_ms
+}
+
+/// Builds without the server have no version history to retain
+#[cfg(not(all(not(target_arch = "wasm32"), feature = "server")))]
+fn default_version_retention_ms() -> u64 {
+    0
 }
 
 impl Default for DbConfig {
@@ -159,8 +258,11 @@ impl Default for DbConfig {
             backup_dir: format!("{}/backup", base_dir),
             include_dir: format!("{}/include", base_dir),
             memory_only: false,
+            file_storage: false,
             max_vectors_per_collection: 1000000,
             create_dirs_on_init: true,
+            version_retention_ms: default_version_retention_ms(),
+            wal_segment_bytes: default_wal_segment_bytes(),
         }
     }
 }
@@ -171,6 +273,9 @@ pub struct DatabaseMetadata {
     pub created_at: u64,
     pub last_modified: u64,
     pub collections: Vec<String>,
+    /// The last journal entry reflected in storage
+    #[serde(default)]
+    pub applied_lsn: u64,
 }
 
 impl Default for DatabaseMetadata {
@@ -183,6 +288,7 @@ impl Default for DatabaseMetadata {
                 .as_secs(),
             last_modified: 0,
             collections: vec![],
+            applied_lsn: 0,
         }
     }
 }
@@ -197,12 +303,16 @@ impl DbConfig {
             backup_dir: format!("{}/backup", base_dir),
             include_dir: format!("{}/include", base_dir),
             memory_only: false,
+            file_storage: false,
             max_vectors_per_collection: 1000000,
             create_dirs_on_init: true,
+            version_retention_ms: default_version_retention_ms(),
+            wal_segment_bytes: default_wal_segment_bytes(),
         }
     }
 }
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
 impl CoreTexDB {
     pub fn new() -> Self {
         let storage: Box<dyn StorageEngine> = Box::new(MemoryStorage::new());
@@ -213,6 +323,15 @@ impl CoreTexDB {
             collections: Arc::new(RwLock::new(HashMap::new())),
             data: Arc::new(RwLock::new(HashMap::new())),
             config: DbConfig::default(),
+            lakehouse: None,
+            lakehouse_feed: None,
+            tiered_out: Arc::new(std::sync::RwLock::new(TieredOut::new())),
+            transactions: Arc::new(DbTransactions::new()),
+            versions: Arc::new(VersionStore::default()),
+            named_vectors: Arc::new(NamedVectorStore::new()),
+            sparse_vectors: Arc::new(SparseVectorStore::new()),
+            multi_vectors: Arc::new(MultiVectorStore::new()),
+            journal: Arc::new(Journal::new()),
         }
     }
 
@@ -223,7 +342,13 @@ impl CoreTexDB {
             #[cfg(feature = "rocksdb")]
             { Box::new(PersistentStorage::new(&config.data_dir)) }
             #[cfg(not(feature = "rocksdb"))]
-            { Box::new(MemoryStorage::new()) }
+            {
+                if config.file_storage {
+                    Box::new(FileStorage::new(&config.data_dir))
+                } else {
+                    Box::new(MemoryStorage::new())
+                }
+            }
         };
         
         Self {
@@ -231,10 +356,37 @@ impl CoreTexDB {
             index_manager: Arc::new(IndexManager::new()),
             collections: Arc::new(RwLock::new(HashMap::new())),
             data: Arc::new(RwLock::new(HashMap::new())),
+            versions: Arc::new(VersionStore::new(VersionConfig {
+                retention_ms: config.version_retention_ms,
+            })),
             config,
+            lakehouse: None,
+            lakehouse_feed: None,
+            tiered_out: Arc::new(std::sync::RwLock::new(TieredOut::new())),
+            transactions: Arc::new(DbTransactions::new()),
+            named_vectors: Arc::new(NamedVectorStore::new()),
+            sparse_vectors: Arc::new(SparseVectorStore::new()),
+            multi_vectors: Arc::new(MultiVectorStore::new()),
+            journal: Arc::new(Journal::new()),
         }
     }
 
+    /// Copies every vector written into `lakehouse`, and makes `search_tiered`
+    /// also consult its warm and cold tiers. Documents it moves below its
+    /// hot tier leave memory. Call inside a tokio runtime, after
+    /// `with_metrics`.
+    pub fn with_lakehouse(mut self, lakehouse: Arc<VectorLakehouse>) -> Self {
+        self.lakehouse_feed = Some(LakehouseFeed::start(&self, lakehouse.clone()));
+        self.lakehouse = Some(lakehouse);
+        self
+    }
+
+    /// Reports index rebuilds to `metrics`. Set before creating collections.
+    pub fn with_metrics(mut self, metrics: coretex_monitoring_v2::MetricsCollector) -> Self {
+        self.index_manager = Arc::new(IndexManager::new().with_metrics(metrics));
+        self
+    }
+
     pub async fn init(&self) -> Result<()> {
         if self.config.create_dirs_on_init && !self.config.memory_only {
             self.create_directories().await?;
@@ -317,9 +469,7 @@ impl CoreTexDB {
         let metadata_path = PathBuf::from(&self.config.data_dir).join("metadata.json");
         let content = serde_json::to_string_pretty(metadata)
             .map_err(|e| CoreTexError::Serialization(e))?;
-        fs::write(&metadata_path, content)
-            .map_err(|e| CoreTexError::Io(e))?;
-        Ok(())
+        Ok(coretex_journal::write_durably(&metadata_path, content.as_bytes())?)
     }
 
     pub async fn create_collection(&self, name: &str, dimension: usize, metric: &str) -> Result<()> {
@@ -332,47 +482,257 @@ impl CoreTexDB {
         let schema = CollectionSchema {
             name: name.to_string(),
             dimension,
-            distance_metric: match metric {
-                "euclidean" => coretex_core::DistanceMetric::Euclidean,
-                "dotproduct" => coretex_core::DistanceMetric::DotProduct,
-                "manhattan" => coretex_core::DistanceMetric::Manhattan,
-                _ => coretex_core::DistanceMetric::Cosine,
-            },
+            distance_metric: coretex_core::DistanceMetric::from_name(metric),
             indexes: vec![],
             metadata_schema: None,
+            vector_fields: vec![],
+            multi_vector: None,
         };
 
-        collections.insert(name.to_string(), schema.clone());
+        self.change_schema(&mut collections, schema).await
+    }
 
-        let mut data = self.data.write().await;
-        data.insert(name.to_string(), HashMap::new());
+    /// Journals a new or changed schema and puts it in place.
+    pub(crate) async fn change_schema(&self, collections: &mut HashMap<String, CollectionSchema>, schema: CollectionSchema) -> Result<()> {
+        coretex_side_vectors::check_collection_name(&schema.name)?;
+        // Reserved documents must still fit when their writes commit
+        if collections.get(&schema.name).is_some_and(|existing| existing.dimension != schema.dimension) {
+            self.check_unreserved_collection(&schema.name)?;
+        }
+        let entry = self.journal.append(WalEntryType::CreateCollection, &schema.name, serde_json::json!({"schema": schema})).await?;
+        self.install_schema(collections, schema).await?;
+        self.entry_applied(entry.map(|entry| entry.id)).await
+    }
 
-        let index_name = format!("{}_hnsw", name);
-        self.index_manager.create_index(&index_name, "hnsw", metric).await
-            .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+    /// Puts a collection's schema in place, creating the indexes it names
+    /// that do not exist yet.
+    pub(crate) async fn install_schema(&self, collections: &mut HashMap<String, CollectionSchema>, schema: CollectionSchema) -> Result<()> {
+        let index_error = |e: Box<dyn std::error::Error + Send + Sync>| CoreTexError::IndexError(e.to_string());
+        let missing = |name: String| async move {
+            self.index_manager.get_index(&name).await.map(|index| index.is_none().then_some(name)).map_err(index_error)
+        };
 
+        if let Some(name) = missing(format!("{}_hnsw", schema.name)).await? {
+            match schema.indexes.first() {
+                Some(config) => self.index_manager.create_index_with_config(&name, config, schema.distance_metric.as_str()).await,
+                None => self.index_manager.create_index(&name, "hnsw", schema.distance_metric.as_str()).await,
+            }.map_err(index_error)?;
+        }
+        for field in &schema.vector_fields {
+            if let Some(name) = missing(coretex_fields::field_index_name(&schema.name, &field.name)).await? {
+                self.index_manager.create_index_with_config(&name, &field.index, field.distance_metric.as_str()).await
+                    .map_err(index_error)?;
+            }
+        }
+        if let Some(config) = &schema.multi_vector {
+            if let Some(name) = missing(coretex_multivector::token_index_name(&schema.name)).await? {
+                self.index_manager.create_index_with_config(&name, &config.index, config.distance_metric.as_str()).await
+                    .map_err(index_error)?;
+            }
+        }
+
+        if self.journal.is_open().await {
+            coretex_journal::write_schema(&self.config.data_dir, &schema)?;
+        }
+        self.data.write().await.entry(schema.name.clone()).or_default();
+        collections.insert(schema.name.clone(), schema);
         Ok(())
     }
 
     pub async fn delete_collection(&self, name: &str) -> Result<()> {
         let mut collections = self.collections.write().await;
-        
         if !collections.contains_key(name) {
             return Err(CoreTexError::CollectionNotFound(name.to_string()));
         }
 
-        collections.remove(name);
+        self.check_unreserved_collection(name)?;
 
         let mut data = self.data.write().await;
+        let entry = self.journal.append(WalEntryType::DeleteCollection, name, serde_json::json!({})).await?;
+        self.drop_collection(&mut collections, &mut data, name).await?;
+        self.entry_applied(entry.map(|entry| entry.id)).await
+    }
+
+    /// Removes a collection with its documents, side vectors and indexes.
+    pub(crate) async fn drop_collection(
+        &self,
+        collections: &mut HashMap<String, CollectionSchema>,
+        data: &mut HashMap<String, CollectionData>,
+        name: &str,
+    ) -> Result<()> {
+        // Versioned as deleting every vector, so a collection later created
+        // under the same name reads right at earlier points
+        let writes = data.get(name)
+            .map(|vectors| vectors.keys().cloned().chain(self.tiered_out_ids(name)).map(|id| ((name.to_string(), id), None)).collect())
+            .unwrap_or_default();
+        self.apply_logged_writes(data, writes).await?;
         data.remove(name);
+        self.tiered_out.write().unwrap().remove(name);
+        let schema = collections.remove(name);
+
+        let fields = schema.map(|schema| schema.vector_fields).unwrap_or_default();
+        self.named_vectors.remove_collection(&self.index_manager, name, &fields).await;
+        self.sparse_vectors.remove_collection(name).await;
+        self.multi_vectors.remove_collection(&self.index_manager, name).await;
 
         let index_name = format!("{}_hnsw", name);
         self.index_manager.delete_index(&index_name).await
             .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
 
+        if self.journal.is_open().await {
+            for dir in [name.to_string(), coretex_side_vectors::side_collection(name)] {
+                let collection_dir = PathBuf::from(&self.config.data_dir).join("collections").join(dir);
+                if collection_dir.exists() {
+                    fs::remove_dir_all(&collection_dir)?;
+                }
+            }
+        }
+
         Ok(())
     }
 
+    /// Rebuilds a collection's index with new settings without taking search
+    /// offline. The new index is backfilled from stored vectors in the
+    /// background while the old one keeps serving; writes made meanwhile go to
+    /// both, and the new index replaces the old one once the backfill is done.
+    /// Follow along with `index_rebuild_progress`.
+    pub async fn rebuild_index(&self, collection: &str, config: IndexConfig) -> Result<RebuildProgress> {
+        self.start_rebuild(collection, config, None).await
+    }
+
+    /// `rebuild_index` under a cluster-wide lease on `index-rebuild:{collection}`,
+    /// so nodes sharing the lock service never rebuild a collection's index
+    /// at once. The lease is held until the new index is swapped in; the
+    /// rebuild is abandoned if it is lost first.
+    pub async fn rebuild_index_locked(&self, collection: &str, config: IndexConfig, locks: &Arc<LockService>) -> Result<RebuildProgress> {
+        let guard = locks.acquire(&format!("index-rebuild:{}", collection)).await
+            .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+        self.start_rebuild(collection, config, Some(guard)).await
+    }
+
+    async fn start_rebuild(&self, collection: &str, config: IndexConfig, guard: Option<LockGuard>) -> Result<RebuildProgress> {
+        let metric = {
+            let collections = self.collections.read().await;
+            let schema = collections.get(collection)
+                .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+            schema.distance_metric.as_str()
+        };
+        if matches!(config.index_type, IndexType::Scalar) {
+            return Err(CoreTexError::IndexError("A scalar index cannot serve vector search".to_string()));
+        }
+
+        // Taken with the data lock so no write falls between the count and
+        // the mirroring that `begin_rebuild` turns on
+        let index_name = format!("{}_hnsw", collection);
+        let (shadow, ids) = {
+            let data = self.data.read().await;
+            let ids: Vec<String> = data.get(collection)
+                .map(|vectors| vectors.keys().cloned().collect())
+                .unwrap_or_default();
+            let shadow = self.index_manager.begin_rebuild(&index_name, &config, metric, ids.len()).await
+                .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+            (shadow, ids)
+        };
+        let progress = self.index_manager.rebuild_progress(&index_name).await
+            .ok_or_else(|| CoreTexError::IndexError(format!("Index {} is not being rebuilt", index_name)))?;
+
+        let data = self.data.clone();
+        let collections = self.collections.clone();
+        let index_manager = self.index_manager.clone();
+        let journal = self.journal.clone();
+        let data_dir = self.config.data_dir.clone();
+        let collection = collection.to_string();
+        tokio::spawn(async move {
+            let lease_lost = || guard.as_ref().filter(|guard| !guard.is_held()).map(|guard| {
+                coretex_distributed::LockError::LeaseLost(guard.key()).to_string()
+            });
+
+            // The lease outlives the swap-in, so no other node starts a rebuild
+            // before this one is journaled
+            let rebuild = async {
+                let mut indexed = 0;
+                for batch in ids.chunks(INDEX_REBUILD_BATCH_SIZE) {
+                    if let Some(e) = lease_lost() {
+                        index_manager.abort_rebuild(&index_name, &e).await;
+                        return;
+                    }
+                    // Read under the data lock so a concurrent delete, mirrored
+                    // to the new index, cannot be undone by the backfill
+                    let result = {
+                        let data = data.read().await;
+                        let mut result = Ok(());
+                        if let Some(vectors) = data.get(&collection) {
+                            for id in batch {
+                                if let Some((vector, _)) = vectors.get(id) {
+                                    if let Err(e) = shadow.add(id, vector).await {
+                                        result = Err(e.to_string());
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        result
+                    };
+                    if let Err(e) = result {
+                        index_manager.abort_rebuild(&index_name, &e).await;
+                        return;
+                    }
+
+                    indexed += batch.len();
+                    index_manager.record_rebuild_progress(&index_name, indexed).await;
+                    tokio::task::yield_now().await;
+                }
+
+                if let Some(e) = lease_lost() {
+                    index_manager.abort_rebuild(&index_name, &e).await;
+                    return;
+                }
+                // Journaled before the swap so the index is built the same way
+                // on the next open; the rebuild only completes once it is durable
+                let saved = {
+                    let mut collections = collections.write().await;
+                    match collections.get_mut(&collection) {
+                        Some(schema) => {
+                            let mut updated = schema.clone();
+                            updated.indexes = vec![config];
+                            let saved = async {
+                                let logged = journal.append(WalEntryType::CreateCollection, &collection, serde_json::json!({"schema": updated})).await?;
+                                if let Some(entry) = logged {
+                                    coretex_journal::write_schema(&data_dir, &updated)?;
+                                    journal.applied(entry.id);
+                                }
+                                Ok::<_, CoreTexError>(())
+                            }.await.map_err(|e| e.to_string());
+                            if saved.is_ok() {
+                                *schema = updated;
+                            }
+                            saved
+                        }
+                        None => Err(format!("Collection {} no longer exists", collection)),
+                    }
+                };
+                if let Err(e) = saved {
+                    index_manager.abort_rebuild(&index_name, &format!("Failed to save the index config: {}", e)).await;
+                    return;
+                }
+                if let Err(e) = index_manager.finish_rebuild(&index_name).await {
+                    index_manager.abort_rebuild(&index_name, &e.to_string()).await;
+                }
+            };
+            rebuild.await;
+            if let Some(guard) = guard {
+                let _ = guard.release().await;
+            }
+        });
+
+        Ok(progress)
+    }
+
+    /// Progress of the running or latest rebuild of a collection's index.
+    pub async fn index_rebuild_progress(&self, collection: &str) -> Option<RebuildProgress> {
+        self.index_manager.rebuild_progress(&format!("{}_hnsw", collection)).await
+    }
+
     pub async fn list_collections(&self) -> Result<Vec<String>> {
         let collections = self.collections.read().await;
         Ok(collections.keys().cloned().collect())
@@ -402,64 +762,255 @@ impl CoreTexDB {
         drop(collections);
 
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
-            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
-
-        let index_name = format!("{}_hnsw", collection);
-        if let Ok(Some(index)) = self.index_manager.get_index(&index_name).await {
-            for (id, vector, _) in &vectors {
-                let _ = index.add(id, vector).await;
-            }
+        if !data.contains_key(collection) {
+            return Err(CoreTexError::CollectionNotFound(collection.to_string()));
         }
 
-        let mut ids = Vec::new();
-        for (id, vector, metadata) in vectors {
-            collection_data.insert(id.clone(), (vector.clone(), metadata.clone()));
-            ids.push(id.clone());
-            
-            let storage = self.storage.read().await;
-            let storage_key = format!("{}:{}", collection, id);
-            let _ = storage.store(&storage_key, &vector, &metadata).await;
-        }
+        let ids = vectors.iter().map(|(id, _, _)| id.clone()).collect();
+        self.apply_writes(&mut data, Self::puts(collection, vectors)).await?;
 
         Ok(ids)
     }
 
-    pub async fn get_vector(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>> {
+    /// A vector as it is now, or as it was at `as_of`.
+    pub async fn get_vector(&self, collection: &str, id: &str, as_of: Option<AsOf>) -> Result<Option<(Vec<f32>, serde_json::Value)>> {
         let data = self.data.read().await;
         let collection_data = data.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
         
-        Ok(collection_data.get(id).cloned())
+        let current = self.document(collection_data, collection, id).await?;
+        match as_of {
+            Some(as_of) => self.version_as_of(as_of, collection, id, current),
+            None => Ok(current),
+        }
     }
 
     pub async fn delete_vectors(&self, collection: &str, ids: &[String]) -> Result<usize> {
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
+        let writes = self.deletes(&data, collection, ids)?;
+
+        let deleted = writes.len();
+        self.apply_writes(&mut data, writes).await?;
+
+        Ok(deleted)
+    }
+
+    /// Writes `vectors` to a collection, replacing documents with the same
+    /// ids.
+    fn puts(collection: &str, vectors: Vec<(String, Vec<f32>, serde_json::Value)>) -> BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue> {
+        vectors.into_iter()
+            .map(|(id, vector, metadata)| ((collection.to_string(), id), Some((vector, metadata))))
+            .collect()
+    }
+
+    /// Deletes of the `ids` that are in the collection.
+    fn deletes(
+        &self,
+        data: &HashMap<String, CollectionData>,
+        collection: &str,
+        ids: &[String],
+    ) -> Result<BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>> {
+        let collection_data = data.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        Ok(ids.iter()
+            .filter(|id| self.holds(collection_data, collection, id))
+            .map(|id| ((collection.to_string(), id.clone()), None))
+            .collect())
+    }
 
-        let index_name = format!("{}_hnsw", collection);
-        if let Ok(Some(index)) = self.index_manager.get_index(&index_name).await {
-            for id in ids {
-                let _ = index.remove(id).await;
-            }
+    /// Applies writes to documents: `Some` adds or overwrites one, `None`
+    /// deletes it along with its named, sparse and multi-vectors. Every path
+    /// that writes or deletes documents ends here while holding the data
+    /// lock for writing; setters of side vectors hold it for reading, so
+    /// side vectors never outlive their document. History is recorded before
+    /// anything changes.
+    pub(crate) async fn apply_writes(
+        &self,
+        data: &mut HashMap<String, CollectionData>,
+        writes: BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>,
+    ) -> Result<()> {
+        self.apply_journaled_writes(data, writes).await.map(|_| ())
+    }
+
+    /// `apply_writes`, returning the LSN of the journal entry that holds the
+    /// writes, if they needed one.
+    pub(crate) async fn apply_journaled_writes(
+        &self,
+        data: &mut HashMap<String, CollectionData>,
+        writes: BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>,
+    ) -> Result<Option<u64>> {
+        self.check_reserved(writes.keys(), None)?;
+        let writes = self.with_side_deletes(writes).await;
+        let lsn = self.journal_writes(data, &writes).await?;
+        self.apply_logged_writes(data, writes).await?;
+        self.entry_applied(lsn).await?;
+        Ok(lsn)
+    }
+
+    /// Journals writes as one entry, so after a crash either all of them
+    /// are replayed or none, along with the versions they replace for
+    /// `as_of` reads. Nothing is applied unless it is journaled first.
+    pub(crate) async fn journal_writes(
+        &self,
+        data: &HashMap<String, CollectionData>,
+        writes: &BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>,
+    ) -> Result<Option<u64>> {
+        if !self.journal.is_open().await {
+            return Ok(None);
         }
 
-        let mut deleted = 0;
-        for id in ids {
-            if collection_data.remove(id).is_some() {
-                deleted += 1;
-                
-                let storage = self.storage.read().await;
+        let (changed, replaced): (Vec<_>, Vec<_>) = writes.iter()
+            .zip(self.current_values(data, writes).await?)
+            .filter(|((_, value), (_, before))| value.is_some() || before.is_some())
+            .map(|((key, value), before)| ((key.clone(), value.clone()), before))
+            .unzip();
+        if let Some((entry_type, collection, entry)) = coretex_journal::write_entry(&changed) {
+            let entry = coretex_journal::with_replaced(entry, &replaced);
+            return Ok(self.journal.append(entry_type, &collection, entry).await?.map(|entry| entry.id));
+        }
+        Ok(None)
+    }
+
+    /// The current value of each written key.
+    async fn current_values(
+        &self,
+        data: &HashMap<String, CollectionData>,
+        writes: &BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>,
+    ) -> Result<Vec<(coretex_transaction::VersionKey, coretex_transaction::VersionValue)>> {
+        let mut values = Vec::with_capacity(writes.len());
+        for (collection, id) in writes.keys() {
+            let value = match coretex_side_vectors::main_collection(collection) {
+                Some(main) if data.contains_key(main) => self.current_side_vectors(main, id).await.to_value(),
+                Some(main) => return Err(CoreTexError::CollectionNotFound(main.to_string())),
+                None => {
+                    let collection_data = data.get(collection)
+                        .ok_or_else(|| CoreTexError::CollectionNotFound(collection.clone()))?;
+                    self.document(collection_data, collection, id).await?
+                }
+            };
+            values.push(((collection.clone(), id.clone()), value));
+        }
+        Ok(values)
+    }
+
+    /// `apply_writes` for writes that are already journaled, e.g. on replay.
+    pub(crate) async fn apply_logged_writes(
+        &self,
+        data: &mut HashMap<String, CollectionData>,
+        writes: BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>,
+    ) -> Result<()> {
+        let writes = self.with_side_deletes(writes).await;
+        let changes: Vec<_> = self.current_values(data, &writes).await?
+            .into_iter()
+            .zip(writes.values())
+            .filter(|((_, before), value)| before.is_some() || value.is_some())
+            .map(|(change, _)| change)
+            .collect();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.versions.record(changes);
+        self.store_writes(data, writes).await
+    }
+
+    /// `apply_logged_writes` without recording the versions they replace,
+    /// for recovery, whose `load_history` records them from the journal.
+    pub(crate) async fn replay_logged_writes(
+        &self,
+        data: &mut HashMap<String, CollectionData>,
+        writes: BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>,
+    ) -> Result<()> {
+        let writes = self.with_side_deletes(writes).await;
+        self.store_writes(data, writes).await
+    }
+
+    /// Puts writes in storage, the index and memory.
+    async fn store_writes(
+        &self,
+        data: &mut HashMap<String, CollectionData>,
+        writes: BTreeMap<coretex_transaction::VersionKey, coretex_transaction::VersionValue>,
+    ) -> Result<()> {
+        // Copied to the lakehouse once applied here, including what was
+        // applied before an error
+        let mut tiered = Vec::new();
+        let applied = async {
+            let storage = self.storage.read().await;
+            for ((collection, id), value) in writes {
                 let storage_key = format!("{}:{}", collection, id);
-                let _ = storage.delete(&storage_key).await;
+
+                if let Some(main) = coretex_side_vectors::main_collection(&collection) {
+                    let side = SideVectors::from_value(&value)?;
+                    match &value {
+                        Some((vector, metadata)) => storage.store(&storage_key, vector, metadata).await
+                            .map_err(|e| CoreTexError::StorageError(e.to_string()))?,
+                        None => {
+                            storage.delete(&storage_key).await
+                                .map_err(|e| CoreTexError::StorageError(e.to_string()))?;
+                        }
+                    }
+                    self.install_side_vectors(main, &id, side).await?;
+                    continue;
+                }
+
+                let collection_data = data.get_mut(&collection)
+                    .ok_or_else(|| CoreTexError::CollectionNotFound(collection.clone()))?;
+                let index = self.index_manager.get_index(&format!("{}_hnsw", collection)).await
+                    .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+
+                match value {
+                    Some((vector, metadata)) => {
+                        if let Some(index) = &index {
+                            index.add(&id, &vector).await
+                                .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+                        }
+                        storage.store(&storage_key, &vector, &metadata).await
+                            .map_err(|e| CoreTexError::StorageError(e.to_string()))?;
+                        if self.lakehouse_feed.is_some() {
+                            tiered.push(((collection.clone(), id.clone()), Some((vector.clone(), metadata.clone()))));
+                        }
+                        self.untier(&collection, &id);
+                        collection_data.insert(id, (vector, metadata));
+                    }
+                    None => {
+                        if !self.holds(collection_data, &collection, &id) {
+                            continue;
+                        }
+                        // Tiered-out documents already left the index
+                        if let Some(index) = index.as_ref().filter(|_| collection_data.contains_key(&id)) {
+                            index.remove(&id).await
+                                .map_err(|e| CoreTexError::IndexError(e.to_string()))?;
+                        }
+                        storage.delete(&storage_key).await
+                            .map_err(|e| CoreTexError::StorageError(e.to_string()))?;
+                        if self.lakehouse_feed.is_some() {
+                            tiered.push(((collection.clone(), id.clone()), None));
+                        }
+                        self.untier(&collection, &id);
+                        collection_data.remove(&id);
+                    }
+                }
             }
-        }
 
-        Ok(deleted)
+            Ok::<_, CoreTexError>(())
+        }.await;
+
+        self.feed_lakehouse(tiered);
+        applied
     }
 
-    pub async fn search(&self, collection: &str, query: Vec<f32>, k: usize, filter: Option<serde_json::Value>) -> Result<Vec<SearchResult>> {
+    /// Search over a collection as it is now, or as it was at `as_of`.
+    pub async fn search(
+        &self,
+        collection: &str,
+        query: Vec<f32>,
+        k: usize,
+        filter: Option<serde_json::Value>,
+        as_of: Option<AsOf>,
+    ) -> Result<Vec<SearchResult>> {
+        if let Some(as_of) = as_of {
+            return self.search_as_of(collection, query, k, filter, as_of).await;
+        }
+
         let collections = self.collections.read().await;
         let _schema = collections.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
@@ -512,18 +1063,100 @@ impl CoreTexDB {
         Ok(results.into_iter().take(k).collect())
     }
 
+    /// Searches in-memory data as the hot tier and, when a lakehouse is attached,
+    /// its tiers as selected by `options`. In-memory hits win over lakehouse
+    /// copies of the same id.
+    pub async fn search_tiered(&self, collection: &str, query: Vec<f32>, options: &TieredSearchOptions) -> Result<Vec<TieredSearchResult>> {
+        let hot = self.search(collection, query.clone(), options.k, options.filter.clone(), None).await?;
+
+        let mut results: Vec<TieredSearchResult> = {
+            let data = self.data.read().await;
+            let collection_data = data.get(collection);
+            hot.into_iter()
+                .map(|r| TieredSearchResult {
+                    metadata: collection_data.and_then(|cd| cd.get(&r.id)).map(|(_, m)| m.clone()),
+                    id: r.id,
+                    distance: r.distance,
+                    tier: StorageTier::Hot,
+                })
+                .collect()
+        };
+
+        if let Some(lakehouse) = &self.lakehouse {
+            let tiered = lakehouse.search(collection, &query, options).await
+                .map_err(CoreTexError::StorageError)?;
+            for result in tiered {
+                if !results.iter().any(|r| r.id == result.id) {
+                    results.push(result);
+                }
+            }
+        }
+
+        results.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(options.k);
+        Ok(results)
+    }
+
     fn sort_search_results(results: &mut Vec<SearchResult>) {
         results.sort_by(|a, b| {
             a.distance.partial_cmp(&b.distance).unwrap_or(std::cmp::Ordering::Equal)
         });
     }
 
+    /// Pages through a collection in id order: up to `limit` vectors with ids
+    /// after `after`.
+    pub async fn scan_vectors(&self, collection: &str, after: Option<&str>, limit: usize) -> Result<Vec<(String, Vec<f32>, serde_json::Value)>> {
+        let data = self.data.read().await;
+        let collection_data = data.get(collection)
+            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+
+        let start = match after {
+            Some(after) => Bound::Excluded(after.to_string()),
+            None => Bound::Unbounded,
+        };
+        // Merged with the ids of tiered-out documents, which are read back
+        // from storage
+        let tiered_out = self.tiered_out_ids(collection);
+        let mut ids: Vec<&String> = collection_data.range((start.clone(), Bound::Unbounded))
+            .map(|(id, _)| id)
+            .take(limit)
+            .chain(tiered_out.range((start, Bound::Unbounded)).take(limit))
+            .collect();
+        ids.sort();
+        ids.truncate(limit);
+
+        let mut vectors = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some((vector, metadata)) = self.document(collection_data, collection, id).await? {
+                vectors.push((id.clone(), vector, metadata));
+            }
+        }
+        Ok(vectors)
+    }
+
+    /// Starts a snapshot-isolated transaction that can write to several
+    /// collections and commits atomically.
+    pub async fn begin(&self) -> Result<DbTransaction<'_>> {
+        DbTransaction::begin(self, None).await
+    }
+
+    /// `begin` for the holder of reserved documents, which it may write.
+    pub(crate) async fn begin_as(&self, holder: &str) -> Result<DbTransaction<'_>> {
+        DbTransaction::begin(self, Some(holder.to_string())).await
+    }
+
+    /// An open transaction by id, e.g. one started in an earlier request.
+    pub async fn transaction(&self, id: TransactionId) -> Result<DbTransaction<'_>> {
+        DbTransaction::resume(self, id).await
+    }
+
     pub async fn get_vectors_count(&self, collection: &str) -> Result<usize> {
         let data = self.data.read().await;
         let collection_data = data.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
         
-        Ok(collection_data.len())
+        let tiered_out = self.tiered_out.read().unwrap().get(collection).map_or(0, |ids| ids.len());
+        Ok(collection_data.len() + tiered_out)
     }
 
     pub async fn update_vector(
@@ -546,20 +1179,15 @@ impl CoreTexDB {
         drop(collections);
 
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
+        let collection_data = data.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
 
-        if !collection_data.contains_key(id) {
+        if !self.holds(collection_data, collection, id) {
             return Ok(false);
         }
 
         let meta = metadata.unwrap_or(serde_json::json!({}));
-        collection_data.insert(id.to_string(), (vector.clone(), meta));
-
-        let index_name = format!("{}_hnsw", collection);
-        if let Ok(Some(index)) = self.index_manager.get_index(&index_name).await {
-            let _ = index.add(id, &vector).await;
-        }
+        self.apply_writes(&mut data, Self::puts(collection, vec![(id.to_string(), vector, meta)])).await?;
 
         Ok(true)
     }
@@ -583,22 +1211,14 @@ impl CoreTexDB {
         }
         drop(collections);
 
-        let mut inserted = Vec::new();
-        let mut updated = Vec::new();
-
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
+        let collection_data = data.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
 
-        for (id, vector, metadata) in vectors {
-            if collection_data.contains_key(&id) {
-                collection_data.insert(id.clone(), (vector, metadata));
-                updated.push(id);
-            } else {
-                collection_data.insert(id.clone(), (vector, metadata));
-                inserted.push(id);
-            }
-        }
+        let (updated, inserted): (Vec<String>, Vec<String>) = vectors.iter()
+            .map(|(id, _, _)| id.clone())
+            .partition(|id| self.holds(collection_data, collection, id));
+        self.apply_writes(&mut data, Self::puts(collection, vectors)).await?;
 
         Ok((inserted, updated))
     }
@@ -623,15 +1243,13 @@ impl CoreTexDB {
         drop(collections);
 
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
-            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
-
-        let mut ids = Vec::new();
-        for (id, vector, metadata) in vectors {
-            collection_data.insert(id.clone(), (vector, metadata));
-            ids.push(id.clone());
+        if !data.contains_key(collection) {
+            return Err(CoreTexError::CollectionNotFound(collection.to_string()));
         }
 
+        let ids = vectors.iter().map(|(id, _, _)| id.clone()).collect();
+        self.apply_writes(&mut data, Self::puts(collection, vectors)).await?;
+
         Ok(ids)
     }
 
@@ -655,16 +1273,14 @@ impl CoreTexDB {
         drop(collections);
 
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
+        let collection_data = data.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
 
-        let mut updated_ids = Vec::new();
-        for (id, vector, metadata) in vectors {
-            if collection_data.contains_key(&id) {
-                collection_data.insert(id.clone(), (vector, metadata));
-                updated_ids.push(id);
-            }
-        }
+        let vectors: Vec<_> = vectors.into_iter()
+            .filter(|(id, _, _)| self.holds(collection_data, collection, id))
+            .collect();
+        let updated_ids = vectors.iter().map(|(id, _, _)| id.clone()).collect();
+        self.apply_writes(&mut data, Self::puts(collection, vectors)).await?;
 
         Ok(updated_ids)
     }
@@ -674,17 +1290,11 @@ impl CoreTexDB {
         collection: &str,
         ids: Vec<String>,
     ) -> Result<Vec<String>> {
-        let mut deleted_ids = Vec::new();
-
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
-            .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
+        let writes = self.deletes(&data, collection, &ids)?;
 
-        for id in &ids {
-            if collection_data.remove(id).is_some() {
-                deleted_ids.push(id.clone());
-            }
-        }
+        let deleted_ids = writes.keys().map(|(_, id)| id.clone()).collect();
+        self.apply_writes(&mut data, writes).await?;
 
         Ok(deleted_ids)
     }
@@ -708,22 +1318,14 @@ impl CoreTexDB {
         }
         drop(collections);
 
-        let mut inserted = Vec::new();
-        let mut updated = Vec::new();
-
         let mut data = self.data.write().await;
-        let collection_data = data.get_mut(collection)
+        let collection_data = data.get(collection)
             .ok_or(CoreTexError::CollectionNotFound(collection.to_string()))?;
 
-        for (id, vector, metadata) in vectors {
-            if collection_data.contains_key(&id) {
-                collection_data.insert(id.clone(), (vector, metadata));
-                updated.push(id);
-            } else {
-                collection_data.insert(id.clone(), (vector, metadata));
-                inserted.push(id);
-            }
-        }
+        let (updated, inserted): (Vec<String>, Vec<String>) = vectors.iter()
+            .map(|(id, _, _)| id.clone())
+            .partition(|id| self.holds(collection_data, collection, id));
+        self.apply_writes(&mut data, Self::puts(collection, vectors)).await?;
 
         Ok(BulkResult {
             inserted,
@@ -731,7 +1333,7 @@ impl CoreTexDB {
         })
     }
 
-    fn matches_filter(metadata: &serde_json::Value, filter: &serde_json::Value) -> bool {
+    pub(crate) fn matches_filter(metadata: &serde_json::Value, filter: &serde_json::Value) -> bool {
         if let (Some(metadata_obj), Some(filter_obj)) = (
             metadata.as_object(),
             filter.as_object()
@@ -766,6 +1368,7 @@ impl CoreTexDB {
     }
 }
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
 impl Default for CoreTexDB {
     fn default() -> Self {
         Self::new()
@@ -778,7 +1381,7 @@ pub struct BulkResult {
     pub updated: Vec<String>,
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(target_arch = "wasm32"), feature = "server"))]
 mod tests {
     use super::*;
 
@@ -808,12 +1411,116 @@ mod tests {
 
         db.insert_vectors("test", vectors).await.unwrap();
 
-        let results = db.search("test", vec![1.0, 0.0, 0.0, 0.0], 2, None).await.unwrap();
+        let results = db.search("test", vec![1.0, 0.0, 0.0, 0.0], 2, None, None).await.unwrap();
 
         assert!(!results.is_empty());
         assert_eq!(results[0].id, "vec1");
     }
 
+    #[tokio::test]
+    async fn test_rebuild_index_serves_searches_and_keeps_writes() {
+        let db = CoreTexDB::new();
+        db.create_collection("test", 2, "cosine").await.unwrap();
+
+        let vectors: Vec<_> = (0..2500)
+            .map(|i| (format!("vec{}", i), vec![1.0, i as f32], serde_json::json!({})))
+            .collect();
+        db.insert_vectors("test", vectors).await.unwrap();
+
+        let config = IndexConfig {
+            name: "test_hnsw".to_string(),
+            index_type: IndexType::HNSW,
+            parameters: [("m".to_string(), serde_json::json!(32))].into(),
+        };
+        let progress = db.rebuild_index("test", config.clone()).await.unwrap();
+        assert_eq!(progress.status, RebuildStatus::Running);
+        assert_eq!(progress.total, 2500);
+        assert!(db.rebuild_index("test", config).await.is_err());
+
+        // Writes and searches carry on during the rebuild
+        db.insert_vectors("test", vec![("new".to_string(), vec![0.0, -1.0], serde_json::json!({}))]).await.unwrap();
+        db.delete_vectors("test", &["vec0".to_string()]).await.unwrap();
+        assert_eq!(db.search("test", vec![1.0, 0.0], 1, None, None).await.unwrap()[0].id, "vec1");
+
+        let progress = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                let progress = db.index_rebuild_progress("test").await.unwrap();
+                if progress.status != RebuildStatus::Running {
+                    return progress;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        }).await.unwrap();
+        assert_eq!(progress.status, RebuildStatus::Completed);
+        assert_eq!(progress.indexed, 2500);
+
+        assert_eq!(db.search("test", vec![0.0, -1.0], 1, None, None).await.unwrap()[0].id, "new");
+        assert_eq!(db.search("test", vec![1.0, 0.0], 1, None, None).await.unwrap()[0].id, "vec1");
+        assert_eq!(db.search("test", vec![1.0, 0.0], 5000, None, None).await.unwrap().len(), 2500);
+        assert!(matches!(db.get_collection("test").await.unwrap().indexes[0].index_type, IndexType::HNSW));
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_fails_when_its_config_cannot_be_saved() {
+        let root = tempfile::tempdir().unwrap();
+        let config = DbConfig::new(&root.path().to_string_lossy());
+        let db = CoreTexDB::open(config.clone()).await.unwrap();
+        db.create_collection("test", 2, "cosine").await.unwrap();
+        db.insert_vectors("test", vec![("a".to_string(), vec![1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+        let indexes = db.get_collection("test").await.unwrap().indexes;
+
+        // A directory where the schema file goes makes saving it fail
+        let schema_path = coretex_journal::schema_path(&config.data_dir, "test");
+        std::fs::remove_file(&schema_path).unwrap();
+        std::fs::create_dir(&schema_path).unwrap();
+
+        let index_config = IndexConfig {
+            name: "test_hnsw".to_string(),
+            index_type: IndexType::HNSW,
+            parameters: [("m".to_string(), serde_json::json!(32))].into(),
+        };
+        db.rebuild_index("test", index_config).await.unwrap();
+        let progress = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                let progress = db.index_rebuild_progress("test").await.unwrap();
+                if progress.status != RebuildStatus::Running {
+                    return progress;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        }).await.unwrap();
+        assert_eq!(progress.status, RebuildStatus::Failed);
+        assert!(progress.error.unwrap().starts_with("Failed to save the index config"));
+        assert_eq!(db.get_collection("test").await.unwrap().indexes.len(), indexes.len());
+        assert_eq!(db.search("test", vec![1.0, 0.0], 1, None, None).await.unwrap()[0].id, "a");
+    }
+
+    #[tokio::test]
+    async fn test_locked_rebuild_waits_for_the_lease_and_releases_it() {
+        let services = coretex_distributed::locks::tests::lock_cluster(2, LockConfig::default()).await;
+        let db = CoreTexDB::new();
+        db.create_collection("test", 2, "cosine").await.unwrap();
+        db.insert_vectors("test", vec![("a".to_string(), vec![1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+        let config = IndexConfig {
+            name: "test_hnsw".to_string(),
+            index_type: IndexType::HNSW,
+            parameters: HashMap::new(),
+        };
+
+        // Another node is rebuilding the collection
+        let other = services[1].acquire("index-rebuild:test").await.unwrap();
+        assert!(db.rebuild_index_locked("test", config.clone(), &services[0]).await.is_err());
+        other.release().await.unwrap();
+
+        db.rebuild_index_locked("test", config, &services[0]).await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while services[0].table().lease("index-rebuild:test").is_some() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        }).await.unwrap();
+        assert_eq!(db.index_rebuild_progress("test").await.unwrap().status, RebuildStatus::Completed);
+    }
+
     #[tokio::test]
     async fn test_delete_collection() {
         let db = CoreTexDB::new();
@@ -844,7 +1551,7 @@ mod tests {
         let count = db.get_vectors_count("test_workflow").await.unwrap();
         assert_eq!(count, 3);
 
-        let results = db.search("test_workflow", vec![1.0, 0.0, 0.0, 0.0], 2, None).await.unwrap();
+        let results = db.search("test_workflow", vec![1.0, 0.0, 0.0, 0.0], 2, None, None).await.unwrap();
         assert!(!results.is_empty());
 
         db.delete_collection("test_workflow").await.unwrap();
@@ -852,4 +1559,128 @@ mod tests {
         let collections = db.list_collections().await.unwrap();
         assert!(!collections.contains(&"test_workflow".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_every_delete_path_drops_side_vectors() {
+        let index = |name: &str| IndexConfig {
+            name: name.to_string(),
+            index_type: IndexType::BruteForce,
+            parameters: HashMap::new(),
+        };
+        let db = CoreTexDB::new();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.add_vector_field("docs", VectorField::new("title", 2, coretex_core::DistanceMetric::Cosine, index("title"))).await.unwrap();
+        db.enable_multi_vector("docs", MultiVectorConfig {
+            dimension: 2,
+            distance_metric: coretex_core::DistanceMetric::DotProduct,
+            index: index("tokens"),
+            candidates_per_token: 10,
+        }).await.unwrap();
+
+        for id in ["a", "b", "c"] {
+            db.insert_vectors("docs", vec![(id.to_string(), vec![1.0, 0.0], serde_json::json!({}))]).await.unwrap();
+            db.set_named_vectors("docs", id, [("title".to_string(), vec![0.0, 1.0])].into()).await.unwrap();
+            db.set_sparse_vector("docs", id, SparseVector::new(vec![1], vec![1.0]).unwrap()).await.unwrap();
+            db.set_multi_vector("docs", id, vec![vec![1.0, 1.0]]).await.unwrap();
+        }
+
+        db.bulk_delete("docs", vec!["a".to_string()]).await.unwrap();
+        let txn = db.begin().await.unwrap();
+        txn.delete("docs", &["b".to_string()]).await.unwrap();
+        txn.commit().await.unwrap();
+
+        for id in ["a", "b"] {
+            assert!(db.get_named_vectors("docs", id).await.unwrap().is_empty());
+            assert!(db.get_sparse_vector("docs", id).await.unwrap().is_none());
+            assert!(db.get_multi_vector("docs", id).await.unwrap().is_none());
+        }
+        let ids = |results: Vec<SearchResult>| results.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(db.search_field("docs", "title", vec![0.0, 1.0], 3, None).await.unwrap()), vec!["c"]);
+        let sparse = db.search_sparse("docs", &SparseVector::new(vec![1], vec![1.0]).unwrap(), 3, None).await.unwrap();
+        assert_eq!(sparse.len(), 1);
+        let multi = db.search_multi_vector("docs", &[vec![1.0, 1.0]], 3, None).await.unwrap();
+        assert_eq!(multi.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_tiered_merges_lakehouse() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let lakehouse = Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap());
+        lakehouse.write_vector("tiered", "archived", vec![0.0, 1.0, 0.0, 0.0], serde_json::json!({})).await.unwrap();
+
+        let db = CoreTexDB::new().with_lakehouse(lakehouse);
+        db.init().await.unwrap();
+        db.create_collection("tiered", 4, "cosine").await.unwrap();
+        db.insert_vectors("tiered", vec![
+            ("live".to_string(), vec![1.0, 0.0, 0.0, 0.0], serde_json::json!({})),
+        ]).await.unwrap();
+
+        let results = db.search_tiered("tiered", vec![1.0, 0.2, 0.0, 0.0], &TieredSearchOptions::new(5)).await.unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["live", "archived"]);
+    }
+
+    #[tokio::test]
+    async fn test_writes_feed_the_lakehouse() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let lakehouse = Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap());
+        let db = CoreTexDB::new().with_lakehouse(lakehouse.clone());
+        db.init().await.unwrap();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![
+            ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"n": 1})),
+            ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"n": 2})),
+        ]).await.unwrap();
+        db.update_vector("docs", "a", vec![0.6, 0.8], Some(serde_json::json!({"n": 3}))).await.unwrap();
+        db.delete_vectors("docs", &["b".to_string()]).await.unwrap();
+        db.flush_lakehouse().await;
+
+        assert!(lakehouse.document_meta("docs", "a").await.is_some());
+        assert!(lakehouse.document_meta("docs", "b").await.is_none());
+        let record = lakehouse.read_vector("docs", "a").await.unwrap();
+        assert_eq!(record.vector, vec![0.6, 0.8]);
+        assert_eq!(record.metadata["n"], 3);
+
+        db.delete_collection("docs").await.unwrap();
+        db.flush_lakehouse().await;
+        assert!(lakehouse.document_meta("docs", "a").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_demoted_documents_leave_memory() {
+        use coretex_lakehouse::MigrationOptions;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let lakehouse = Arc::new(VectorLakehouse::new(temp_dir.path().to_str().unwrap()).unwrap());
+        let db = CoreTexDB::new().with_lakehouse(lakehouse.clone());
+        db.init().await.unwrap();
+        db.create_collection("docs", 2, "cosine").await.unwrap();
+        db.insert_vectors("docs", vec![
+            ("a".to_string(), vec![1.0, 0.0], serde_json::json!({"n": 1})),
+            ("b".to_string(), vec![0.0, 1.0], serde_json::json!({"n": 2})),
+        ]).await.unwrap();
+        db.flush_lakehouse().await;
+
+        // No room in the hot tier
+        let options = MigrationOptions { hot_budget_bytes: Some(0), ..MigrationOptions::from_config(lakehouse.config()) };
+        assert_eq!(lakehouse.migrate_with(&options, None).await.unwrap().migrated_count, 2);
+        db.flush_lakehouse().await;
+
+        assert!(db.data.read().await["docs"].is_empty());
+        assert_eq!(db.get_vectors_count("docs").await.unwrap(), 2);
+        assert_eq!(db.get_vector("docs", "a", None).await.unwrap().unwrap().1["n"], 1);
+        let scanned: Vec<String> = db.scan_vectors("docs", None, 10).await.unwrap().into_iter().map(|(id, _, _)| id).collect();
+        assert_eq!(scanned, vec!["a", "b"]);
+        let tiered = db.search_tiered("docs", vec![1.0, 0.0], &TieredSearchOptions::new(1)).await.unwrap();
+        assert_eq!(tiered[0].id, "a");
+        assert_eq!(tiered[0].tier, StorageTier::Warm);
+
+        // Tiered-out documents are still written and deleted like any other
+        db.update_vector("docs", "a", vec![0.6, 0.8], None).await.unwrap();
+        assert_eq!(db.delete_vectors("docs", &["b".to_string()]).await.unwrap(), 1);
+        db.flush_lakehouse().await;
+        assert_eq!(db.get_vector("docs", "a", None).await.unwrap().unwrap().0, vec![0.6, 0.8]);
+        assert_eq!(db.get_vectors_count("docs").await.unwrap(), 1);
+        assert!(lakehouse.document_meta("docs", "b").await.is_none());
+    }
 }
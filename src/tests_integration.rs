@@ -20,7 +20,7 @@ async fn test_full_workflow() {
     let count = db.get_vectors_count("test_workflow").await.unwrap();
     assert_eq!(count, 3);
     
-    let results = db.search("test_workflow", vec![1.0, 0.0, 0.0, 0.0], 2, None).await.unwrap();
+    let results = db.search("test_workflow", vec![1.0, 0.0, 0.0, 0.0], 2, None, None).await.unwrap();
     assert!(!results.is_empty());
     
     db.delete_collection("test_workflow").await.unwrap();
@@ -0,0 +1,48 @@
+// IndexedDB persistence for the in-browser EdgeDB (`WasmDB`).
+// The database is stored as a single snapshot blob per name.
+//
+//   import init, { WasmDB } from "./pkg/coretexdb_wasm.js";
+//   import { loadDB, saveDB } from "./indexeddb.js";
+//
+//   await init();
+//   const db = await loadDB(WasmDB, "memories");
+//   db.insert("pages", id, embedding, { url });
+//   await saveDB(db, "memories");
+
+const DB_NAME = "coretexdb";
+const STORE = "snapshots";
+
+function open() {
+  return new Promise((resolve, reject) => {
+    const request = indexedDB.open(DB_NAME, 1);
+    request.onupgradeneeded = () => request.result.createObjectStore(STORE);
+    request.onsuccess = () => resolve(request.result);
+    request.onerror = () => reject(request.error);
+  });
+}
+
+function run(mode, action) {
+  return open().then((idb) => new Promise((resolve, reject) => {
+    const tx = idb.transaction(STORE, mode);
+    const request = action(tx.objectStore(STORE));
+    tx.oncomplete = () => { idb.close(); resolve(request.result); };
+    tx.onerror = () => { idb.close(); reject(tx.error); };
+  }));
+}
+
+// Writes `db.snapshot()` under `name`. The write is atomic: a crash leaves
+// the previous snapshot in place.
+export function saveDB(db, name) {
+  const snapshot = db.snapshot();
+  return run("readwrite", (store) => store.put(snapshot, name));
+}
+
+// Restores the snapshot saved under `name`, or returns an empty database.
+export async function loadDB(WasmDB, name) {
+  const snapshot = await run("readonly", (store) => store.get(name));
+  return snapshot ? WasmDB.fromSnapshot(new Uint8Array(snapshot)) : new WasmDB();
+}
+
+export function deleteDB(name) {
+  return run("readwrite", (store) => store.delete(name));
+}
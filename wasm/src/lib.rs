@@ -0,0 +1,270 @@
+//! In-browser EdgeDB
+//! Built for `wasm32-unknown-unknown` and packaged with wasm-bindgen, from
+//! this directory:
+//!
+//! ```text
+//! wasm-pack build --target web
+//! ```
+//!
+//! The browser has no filesystem, so persistence goes through `snapshot()` /
+//! `WasmDB.fromSnapshot()`; `indexeddb.js` stores the snapshot in
+//! IndexedDB. The JS-level tests run in node:
+//!
+//! ```text
+//! cargo test -p coretexdb-wasm --target wasm32-unknown-unknown
+//! ```
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+use wasm_bindgen::prelude::*;
+
+use coretexdb::coretex_edge::{EdgeConfig, EdgeDB, EdgeError, EdgeSearchResult};
+
+/// An in-memory `EdgeDB` with a synchronous API for JavaScript.
+#[wasm_bindgen]
+pub struct WasmDB {
+    db: EdgeDB,
+}
+
+impl Default for WasmDB {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmDB {
+    pub fn new() -> Self {
+        Self {
+            db: EdgeDB::in_memory(),
+        }
+    }
+
+    pub fn create_collection(&mut self, name: &str, dimension: usize) -> Result<(), EdgeError> {
+        now(self.db.create_collection(name, dimension))
+    }
+
+    pub fn delete_collection(&mut self, name: &str) -> Result<(), EdgeError> {
+        now(self.db.delete_collection(name))
+    }
+
+    pub fn list_collections(&self) -> Vec<String> {
+        now(self.db.list_collections())
+    }
+
+    /// Without metadata an overwrite keeps the existing metadata.
+    pub fn insert(&mut self, collection: &str, id: &str, vector: Vec<f32>, metadata: Option<serde_json::Value>) -> Result<(), EdgeError> {
+        now(self.db.insert(collection, id, vector, metadata))
+    }
+
+    pub fn get(&self, collection: &str, id: &str) -> Result<Option<(Vec<f32>, serde_json::Value)>, EdgeError> {
+        now(self.db.get(collection, id))
+    }
+
+    pub fn delete(&mut self, collection: &str, id: &str) -> Result<bool, EdgeError> {
+        now(self.db.delete(collection, id))
+    }
+
+    pub fn search(&self, collection: &str, query: &[f32], k: usize) -> Result<Vec<EdgeSearchResult>, EdgeError> {
+        now(self.db.search(collection, query, k))
+    }
+
+    /// Serializes every collection in EdgeDB's snapshot format.
+    pub fn snapshot(&self) -> Result<Vec<u8>, EdgeError> {
+        now(self.db.snapshot())
+    }
+
+    pub fn from_snapshot(data: &[u8]) -> Result<Self, EdgeError> {
+        Ok(Self {
+            db: EdgeDB::from_snapshot(EdgeConfig::default(), data)?,
+        })
+    }
+}
+
+/// Runs an `EdgeDB` call to completion. In memory they never wait: JS is
+/// single-threaded, so the collection lock is always free.
+fn now<F: Future>(future: F) -> F::Output {
+    match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("in-memory EdgeDB calls complete immediately"),
+    }
+}
+
+/// JavaScript API. Vectors cross as `Float32Array`, metadata as plain objects.
+#[wasm_bindgen]
+impl WasmDB {
+    #[wasm_bindgen(constructor)]
+    pub fn js_new() -> WasmDB {
+        Self::new()
+    }
+
+    #[wasm_bindgen(js_name = createCollection)]
+    pub fn js_create_collection(&mut self, name: &str, dimension: usize) -> Result<(), JsError> {
+        self.create_collection(name, dimension).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = deleteCollection)]
+    pub fn js_delete_collection(&mut self, name: &str) -> Result<(), JsError> {
+        self.delete_collection(name).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = listCollections)]
+    pub fn js_list_collections(&self) -> js_sys::Array {
+        self.list_collections().into_iter().map(JsValue::from).collect()
+    }
+
+    /// `metadata` may be `undefined`/`null`.
+    #[wasm_bindgen(js_name = insert)]
+    pub fn js_insert(&mut self, collection: &str, id: &str, vector: Vec<f32>, metadata: JsValue) -> Result<(), JsError> {
+        let metadata = json_from_js(&metadata)?;
+        self.insert(collection, id, vector, metadata).map_err(js_error)
+    }
+
+    /// Returns `{ id, vector, metadata }`, or `undefined` when the id is unknown.
+    #[wasm_bindgen(js_name = get)]
+    pub fn js_get(&self, collection: &str, id: &str) -> Result<JsValue, JsError> {
+        match self.get(collection, id).map_err(js_error)? {
+            Some((vector, metadata)) => json_to_js(&serde_json::json!({
+                "id": id,
+                "vector": vector,
+                "metadata": metadata,
+            })),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    #[wasm_bindgen(js_name = delete)]
+    pub fn js_delete(&mut self, collection: &str, id: &str) -> Result<bool, JsError> {
+        self.delete(collection, id).map_err(js_error)
+    }
+
+    /// Returns `[{ id, distance, metadata }]`, nearest first.
+    #[wasm_bindgen(js_name = search)]
+    pub fn js_search(&self, collection: &str, query: Vec<f32>, k: usize) -> Result<JsValue, JsError> {
+        let mut results = Vec::new();
+        for r in self.search(collection, &query, k).map_err(js_error)? {
+            let metadata = self.get(collection, &r.id).map_err(js_error)?.map(|(_, metadata)| metadata);
+            results.push(serde_json::json!({
+                "id": r.id,
+                "distance": r.distance,
+                "metadata": metadata,
+            }));
+        }
+        json_to_js(&serde_json::Value::Array(results))
+    }
+
+    /// Snapshot bytes (`Uint8Array`) to hand to IndexedDB.
+    #[wasm_bindgen(js_name = snapshot)]
+    pub fn js_snapshot(&self) -> Result<Vec<u8>, JsError> {
+        self.snapshot().map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = fromSnapshot)]
+    pub fn js_from_snapshot(data: &[u8]) -> Result<WasmDB, JsError> {
+        Self::from_snapshot(data).map_err(js_error)
+    }
+}
+
+fn js_error(e: EdgeError) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+fn json_from_js(value: &JsValue) -> Result<Option<serde_json::Value>, JsError> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(None);
+    }
+    let text: String = js_sys::JSON::stringify(value)
+        .map_err(|_| JsError::new("metadata must be JSON-serializable"))?
+        .into();
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn json_to_js(value: &serde_json::Value) -> Result<JsValue, JsError> {
+    js_sys::JSON::parse(&value.to_string()).map_err(|_| JsError::new("failed to build result"))
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_db_snapshot_round_trip() {
+        let mut db = WasmDB::new();
+        db.create_collection("pages", 3).unwrap();
+        db.insert("pages", "a", vec![1.0, 0.0, 0.0], Some(serde_json::json!({"url": "https://example.com"}))).unwrap();
+        db.insert("pages", "b", vec![0.0, 1.0, 0.0], None).unwrap();
+        assert!(matches!(db.insert("pages", "c", vec![1.0], None), Err(EdgeError::InvalidDimension(_))));
+
+        let restored = WasmDB::from_snapshot(&db.snapshot().unwrap()).unwrap();
+        let (vector, metadata) = restored.get("pages", "a").unwrap().unwrap();
+        assert_eq!(vector, vec![1.0, 0.0, 0.0]);
+        assert_eq!(metadata["url"], "https://example.com");
+
+        let results = restored.search("pages", &[0.9, 0.1, 0.0], 1).unwrap();
+        assert_eq!(results[0].id, "a");
+
+        assert!(matches!(WasmDB::from_snapshot(b"garbage"), Err(EdgeError::Corrupted(_))));
+    }
+
+    #[test]
+    fn test_wasm_db_overwrite_keeps_metadata() {
+        let mut db = WasmDB::new();
+        db.create_collection("pages", 2).unwrap();
+        db.insert("pages", "a", vec![1.0, 0.0], Some(serde_json::json!({"url": "https://example.com"}))).unwrap();
+        db.insert("pages", "a", vec![0.0, 1.0], None).unwrap();
+
+        let (vector, metadata) = db.get("pages", "a").unwrap().unwrap();
+        assert_eq!(vector, vec![0.0, 1.0]);
+        assert_eq!(metadata["url"], "https://example.com");
+    }
+
+    #[test]
+    fn test_wasm_db_delete() {
+        let mut db = WasmDB::new();
+        db.create_collection("pages", 2).unwrap();
+        db.insert("pages", "a", vec![1.0, 0.0], None).unwrap();
+
+        assert!(db.delete("pages", "a").unwrap());
+        assert!(!db.delete("pages", "a").unwrap());
+        db.delete_collection("pages").unwrap();
+        assert!(db.list_collections().is_empty());
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn metadata(url: &str) -> JsValue {
+        json_to_js(&serde_json::json!({ "url": url })).unwrap()
+    }
+
+    fn field(value: &JsValue, path: &[&str]) -> JsValue {
+        path.iter().fold(value.clone(), |value, key| js_sys::Reflect::get(&value, &JsValue::from_str(key)).unwrap())
+    }
+
+    #[wasm_bindgen_test]
+    fn test_js_api_round_trip() {
+        let mut db = WasmDB::js_new();
+        db.js_create_collection("pages", 2).unwrap();
+        db.js_insert("pages", "a", vec![1.0, 0.0], metadata("https://example.com")).unwrap();
+        db.js_insert("pages", "b", vec![0.0, 1.0], JsValue::NULL).unwrap();
+        assert!(db.js_insert("pages", "c", vec![1.0], JsValue::UNDEFINED).is_err());
+
+        // Overwriting without metadata keeps what was there
+        db.js_insert("pages", "a", vec![0.9, 0.1], JsValue::UNDEFINED).unwrap();
+
+        let restored = WasmDB::js_from_snapshot(&db.js_snapshot().unwrap()).unwrap();
+        let results = restored.js_search("pages", vec![1.0, 0.0], 1).unwrap();
+        let nearest = js_sys::Array::from(&results).get(0);
+        assert_eq!(field(&nearest, &["id"]), "a");
+        assert_eq!(field(&nearest, &["metadata", "url"]), "https://example.com");
+
+        assert!(restored.js_get("pages", "missing").unwrap().is_undefined());
+        assert_eq!(restored.js_list_collections().length(), 1);
+        assert!(WasmDB::js_from_snapshot(b"garbage").is_err());
+    }
+}